@@ -112,6 +112,11 @@ fn ok_record(
     output_bytes: u64,
     started: Instant,
 ) -> ReportRecord {
+    crate::logging::event(
+        "finish",
+        input,
+        format_args!("{operation} -> {}", output.display()),
+    );
     ReportRecord::new(
         input.display().to_string(),
         output.display().to_string(),
@@ -122,6 +127,7 @@ fn ok_record(
         elapsed_ms(started),
         None,
     )
+    .with_hashes(crate::output_hash::hash_output(output))
 }
 
 fn failed_record(
@@ -131,6 +137,7 @@ fn failed_record(
     started: Instant,
     error: impl std::fmt::Display,
 ) -> ReportRecord {
+    crate::logging::event("failed", input, format_args!("{operation} failed: {error}"));
     ReportRecord::new(
         input.display().to_string(),
         String::new(),
@@ -144,6 +151,12 @@ fn failed_record(
 }
 
 fn skipped_record(input: &Path, operation: &str, error: Option<String>) -> ReportRecord {
+    match &error {
+        Some(reason) => {
+            crate::logging::event("skip", input, format_args!("{operation} skipped: {reason}"))
+        }
+        None => crate::logging::event("skip", input, format_args!("{operation} skipped")),
+    }
     ReportRecord::new(
         input.display().to_string(),
         String::new(),
@@ -352,7 +365,7 @@ pub async fn cso_decompress(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) =
             decompress_from_cso_cancellable(progress, path.clone(), output, true, cancel.clone())
                 .await
@@ -551,7 +564,7 @@ pub async fn rvz_compress(
             }
         };
         let input_bytes = file_len(&path);
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) =
             compress_disc_cancellable(&path, &output, opts, progress, cancel.clone()).await
         {
@@ -677,7 +690,7 @@ pub async fn rvz_decompress(
             }
         };
         let input_bytes = file_len(&path);
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) = decompress_disc_cancellable(&path, &output, progress, cancel.clone()).await
         {
             if matches!(e, rom_converto_lib::nintendo::rvz::RvzError::Cancelled) {
@@ -964,7 +977,7 @@ pub async fn nx_compress(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) = compress_container_async_cancellable(
             path.clone(),
             output,
@@ -1104,7 +1117,7 @@ pub async fn nx_decompress(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) = decompress_container_async_cancellable(
             path.clone(),
             output,
@@ -1269,6 +1282,100 @@ pub async fn wup_verify(
     finish_verify(VerifyTally { total, ok, failed })
 }
 
+/// Build every title in a `ctr batch` manifest and report each one.
+#[allow(clippy::too_many_arguments)]
+pub async fn ctr_manifest(
+    progress: &dyn ProgressReporter,
+    total_progress: &dyn ProgressReporter,
+    manifest_path: &Path,
+    jobs: usize,
+    seed_retries: u32,
+    policy: ConflictPolicy,
+    dry_run: bool,
+    cancel: CancelToken,
+) -> Result<()> {
+    use rom_converto_lib::nintendo::ctr::manifest::{load_cdn_manifest, run_cdn_manifest};
+    use rom_converto_lib::nintendo::ctr::z3ds::derive_compressed_path;
+    use rom_converto_lib::nintendo::ctr::{CdnNameFrom, cdn_cia_file_name};
+
+    let manifest = load_cdn_manifest(manifest_path)?;
+    let mut tally = Tally::new();
+    if dry_run {
+        for entry in &manifest.titles {
+            let output = match &entry.output {
+                Some(output) => output.clone(),
+                None => {
+                    let name = match cdn_cia_file_name(
+                        &entry.cdn_dir,
+                        CdnNameFrom::Dir,
+                        entry.tmd_version,
+                    )
+                    .await
+                    {
+                        Ok(name) => name,
+                        Err(e) => {
+                            warn!("[FAIL] {}: {e}", entry.cdn_dir.display());
+                            tally.record_failed();
+                            continue;
+                        }
+                    };
+                    entry
+                        .cdn_dir
+                        .parent()
+                        .unwrap_or_else(|| Path::new("."))
+                        .join(name)
+                }
+            };
+            let resolved = if entry.compress {
+                derive_compressed_path(&output)
+            } else {
+                output
+            };
+            let decision = resolve_output(&resolved, policy)?;
+            crate::dry_run::log_plan("convert", &entry.cdn_dir, &resolved, &decision, None, None);
+            crate::dry_run::record(&mut tally, &entry.cdn_dir, &decision);
+        }
+        info!("{}", tally.summary_line(TallyDirection::DryRun));
+        return Ok(());
+    }
+
+    let outcomes = run_cdn_manifest(
+        &manifest,
+        policy,
+        jobs,
+        seed_retries,
+        progress,
+        total_progress,
+        cancel.clone(),
+    )
+    .await;
+    if cancel.is_cancelled() {
+        return Err(rom_converto_lib::nintendo::ctr::error::NintendoCTRError::Cancelled.into());
+    }
+    for outcome in outcomes {
+        match outcome.result {
+            Ok(()) => {
+                info!(
+                    "[OK] {} in {:.1}s",
+                    outcome.cdn_dir.display(),
+                    outcome.elapsed.as_secs_f64()
+                );
+                tally.record_ok(0, 0, outcome.elapsed);
+            }
+            Err(e) => {
+                warn!("[FAIL] {}: {e:#}", outcome.cdn_dir.display());
+                tally.record_failed();
+            }
+        }
+    }
+    info!("{}", tally.summary_line(TallyDirection::CountOnly));
+    let failed = tally.failed_count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} titles failed", tally.count());
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn chd_compress(
     progress: &dyn ProgressReporter,
@@ -1282,6 +1389,8 @@ pub async fn chd_compress(
     max_depth: Option<usize>,
     dry_run: bool,
     skip_space_check: bool,
+    fail_fast: bool,
+    dedupe: bool,
     report_path: Option<&Path>,
     cancel: CancelToken,
     cache: &HashCache,
@@ -1303,6 +1412,7 @@ pub async fn chd_compress(
     total_progress.begin(total as u64, files_bytes(&files));
     let mut tally = Tally::new();
     let mut records: Vec<ReportRecord> = Vec::new();
+    let mut written: Vec<PathBuf> = Vec::new();
     for path in files {
         if cancel.is_cancelled() {
             break;
@@ -1398,7 +1508,7 @@ pub async fn chd_compress(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) = convert_disc_to_chd_cancellable(
             progress,
             path.clone(),
@@ -1415,6 +1525,10 @@ pub async fn chd_compress(
             warn!("Failed to compress {}: {e}", path.display());
             tally.record_failed();
             records.push(failed_record(&path, "compress", input_bytes, started, e));
+            if fail_fast {
+                total_progress.advance(input_bytes);
+                break;
+            }
         } else {
             let out_bytes = file_len(&out_path);
             tally.record_ok(input_bytes, out_bytes, started.elapsed());
@@ -1426,6 +1540,7 @@ pub async fn chd_compress(
                 out_bytes,
                 started,
             ));
+            written.push(out_path);
         }
         total_progress.advance(input_bytes);
     }
@@ -1433,6 +1548,9 @@ pub async fn chd_compress(
     if cancel.is_cancelled() {
         return Ok(());
     }
+    if dedupe {
+        link_duplicate_chds(&written);
+    }
     finish_tally(
         &tally,
         TallyDirection::Compress,
@@ -1442,12 +1560,44 @@ pub async fn chd_compress(
     )
 }
 
+/// The `--dedupe` post-pass: hard-link CHDs this run wrote that match an
+/// earlier one. A failure is logged and leaves the run's outcome alone.
+fn link_duplicate_chds(written: &[PathBuf]) {
+    let duplicates = match rom_converto_lib::chd::dedupe::dedupe_chds(written, true) {
+        Ok(duplicates) => duplicates,
+        Err(e) => {
+            warn!("Skipped deduplication: {e}");
+            return;
+        }
+    };
+    let mut reclaimed = 0;
+    for duplicate in &duplicates {
+        if duplicate.linked {
+            info!(
+                "Linked duplicate {} to {}",
+                duplicate.path.display(),
+                duplicate.original.display()
+            );
+            reclaimed += duplicate.bytes;
+        }
+    }
+    if !duplicates.is_empty() {
+        info!(
+            "Deduplicated {} of {} CHDs, reclaimed {}",
+            duplicates.iter().filter(|d| d.linked).count(),
+            written.len(),
+            rom_converto_lib::util::format_bytes(reclaimed)
+        );
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn chd_extract(
     progress: &dyn ProgressReporter,
     total_progress: &crate::util::TotalProgress,
     input_dir: &Path,
     parent: Option<PathBuf>,
+    extract_options: rom_converto_lib::chd::ChdExtractOptions,
     policy: ConflictPolicy,
     output_dir: Option<&Path>,
     output_template: Option<&str>,
@@ -1457,14 +1607,15 @@ pub async fn chd_extract(
     report_path: Option<&Path>,
     cancel: CancelToken,
 ) -> Result<()> {
-    use rom_converto_lib::chd::extract_from_chd_cancellable;
+    use rom_converto_lib::chd::extract_from_chd_with_options;
 
     let files = collect_or_warn(input_dir, &["chd"], max_depth)?;
     if files.is_empty() {
         return Ok(());
     }
     if !dry_run && !skip_space_check {
-        space_preflight(&files, output_dir.unwrap_or(input_dir))?;
+        let required: u64 = files.iter().map(|p| chd_logical_bytes(p)).sum();
+        space_preflight_for_size(required, output_dir.unwrap_or(input_dir))?;
     }
     if !dry_run && let Some(dir) = output_dir {
         std::fs::create_dir_all(dir)?;
@@ -1538,12 +1689,13 @@ pub async fn chd_extract(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
-        if let Err(e) = extract_from_chd_cancellable(
+        let started = crate::logging::start_event(&path);
+        if let Err(e) = extract_from_chd_with_options(
             progress,
             path.clone(),
             output,
             parent.clone(),
+            extract_options.clone(),
             cancel.clone(),
         )
         .await
@@ -1702,7 +1854,7 @@ pub async fn cso_compress(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) = compress_to_cso_cancellable(
             progress,
             path.clone(),
@@ -1754,12 +1906,14 @@ fn cso_uncompressed_size(path: &Path) -> u64 {
         .unwrap_or(0)
 }
 
-/// Best-effort flat-ISO size read from a CHD header, for the space
-/// preflight on the extract-then-compress direction.
-fn chd_logical_bytes(path: &Path) -> u64 {
+/// Best-effort extracted size read from a CHD header, for the space
+/// preflight on extract and the extract-then-compress direction. CD
+/// logical bytes also count subcode and track padding, so the estimate
+/// errs high; an unreadable header falls back to the file's own size.
+pub(crate) fn chd_logical_bytes(path: &Path) -> u64 {
     rom_converto_lib::chd::info::read_info(path)
         .map(|info| info.logical_bytes)
-        .unwrap_or(0)
+        .unwrap_or_else(|_| file_len(path))
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1891,7 +2045,7 @@ pub async fn cso_to_chd(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) = cso_to_chd_cancellable(
             progress,
             path.clone(),
@@ -2064,7 +2218,7 @@ pub async fn chd_to_cso(
         };
         let input_bytes = file_len(&path);
         let out_path = output.clone();
-        let started = Instant::now();
+        let started = crate::logging::start_event(&path);
         if let Err(e) =
             chd_to_cso_cancellable(progress, path.clone(), output, opts.clone(), cancel.clone())
                 .await
@@ -1,24 +1,66 @@
 use crate::commands::ConflictPolicyArg;
 use crate::commands::cso::CsoFormatArg;
 use crate::commands::info_command::InfoCommand;
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use rom_converto_lib::cd::CD_SECTOR_SIZES;
+use rom_converto_lib::chd::benchmark::DEFAULT_BENCHMARK_HUNKS;
+use rom_converto_lib::util::validate_template;
 use std::path::PathBuf;
 
+fn parse_sector_size(text: &str) -> Result<usize, String> {
+    let size: usize = text.parse().map_err(|e| format!("{e}"))?;
+    if CD_SECTOR_SIZES.contains(&size) {
+        Ok(size)
+    } else {
+        Err("expected 2048, 2352, or 2448".to_string())
+    }
+}
+
 /// Commands specific to CHD formats
 #[derive(Subcommand, Debug, Eq, PartialEq)]
 pub enum ChdCommands {
     Compress(CompressCommand),
+    CompressSet(CompressSetCommand),
     Extract(ExtractCommand),
     Verify(VerifyCommand),
+    Compare(CompareCommand),
+    Benchmark(BenchmarkCommand),
     ToCso(ToCsoCommand),
     Info(InfoCommand),
+    Map(MapCommand),
+    Meta(MetaCommand),
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum AudioEndianArg {
+    /// Little-endian samples, as CD-DA bins are normally stored
+    #[default]
+    Le,
+    /// Big-endian samples: every 16-bit audio sample is byte-swapped
+    Be,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SingleCodecArg {
+    /// CD mode: LZMA sector data, deflate subcode
+    Cdlz,
+    /// CD mode: deflate sector data and subcode
+    Cdzl,
+    /// CD mode: FLAC sector data, deflate subcode, data tracks included
+    Cdfl,
+    /// DVD mode: LZMA
+    Lzma,
+    /// DVD mode: deflate
+    Zlib,
+    /// DVD mode: zstd, implies --zstd
+    Zstd,
 }
 
 /// Compress a disc image to a CHD (Compressed Hunks of Data) file
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
     long_about = "Compress a disc image to a CHD (Compressed Hunks of Data) file\n\nA .cue input (with its .bin) becomes a CD-mode CHD. An .iso is probed for its console family: CD-media images (PS1, PS2-CD) become CD-mode CHDs with a single MODE1/2048 track (the chdman createcd equivalent), DVD-media images (PS2-DVD, PSP) become DVD-mode CHDs (the createdvd equivalent). The mode is picked automatically so the createcd/createdvd mixup cannot happen. Default DVD codecs are lzma+zlib, which every emulator reads, including AetherSX2/NetherSX2.",
-    after_long_help = "EXAMPLES:\n  Single file:     rom-converto chd compress game.cue\n  Explicit output: rom-converto chd compress game.iso out.chd\n  Whole folder:    rom-converto chd compress -R ./roms --output-dir ./chd\n"
+    after_long_help = "EXAMPLES:\n  Single file:     rom-converto chd compress game.cue\n  Explicit output: rom-converto chd compress game.iso out.chd\n  Whole folder:    rom-converto chd compress -R ./roms --output-dir ./chd\n  Link duplicates: rom-converto chd compress -R ./roms --dedupe\n  Check layout:    rom-converto chd compress game.cue --list\n  Audio-heavy CD:  rom-converto chd compress game.cue --fast\n  Subchannel dump: rom-converto chd compress game.cue --sector-size 2448\n  Over-dumped ISO: rom-converto chd compress game.iso --trim-trailing-zeros\n"
 )]
 pub struct CompressCommand {
     /// Input image (.cue, or .iso with CD/DVD media auto-detected), or a directory with --recursive
@@ -42,10 +84,10 @@ pub struct CompressCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Force DVD mode (.iso input only)
@@ -64,8 +106,65 @@ pub struct CompressCommand {
     #[arg(long)]
     pub zstd: bool,
 
-    /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
-    #[arg(long = "on-conflict", value_enum)]
+    /// LZMA preset 0..=9 for the DVD `lzma` codec. Defaults to 8, chdman's level
+    #[arg(long = "lzma-level", value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=9))]
+    pub lzma_level: Option<u32>,
+
+    /// zstd level 1..=22 for the DVD `zstd` codec. Defaults to 19, chdman's level. Requires --zstd
+    #[arg(long = "zstd-level", value_name = "LEVEL", requires = "zstd", value_parser = clap::value_parser!(i32).range(1..=22))]
+    pub zstd_level: Option<i32>,
+
+    /// CD mode: skip the LZMA trial on audio hunks and leave them to FLAC and zlib. Faster on audio-heavy discs for a slightly larger file; data hunks are compressed as before
+    #[arg(long, default_value_t = false, conflicts_with = "dvd")]
+    pub fast: bool,
+
+    /// CD mode: bytes per sector in the .cue's bin. 2352 for raw dumps (the default), 2048 for cooked user data, 2448 for raw sectors followed by subchannel
+    #[arg(long = "sector-size", value_name = "BYTES", value_parser = parse_sector_size, conflicts_with = "dvd")]
+    pub sector_size: Option<usize>,
+
+    /// Leave trailing all-zero hunks out of the CHD, for images padded with zero sectors past the end of the disc. Logs how many sectors were dropped. The CHD then no longer matches hashes of the full-size image
+    #[arg(long = "trim-trailing-zeros", default_value_t = false)]
+    pub trim_trailing_zeros: bool,
+
+    /// CD mode: fail instead of warning when the .cue's bin is too short to hold its last track or ends in a partial sector
+    #[arg(long, default_value_t = false, conflicts_with = "dvd")]
+    pub strict: bool,
+
+    /// Compress every hunk with this one codec, or store it raw when that does not shrink it, instead of keeping the smallest result of all codecs. The output then no longer depends on which codec wins each hunk, so builds with the same codec library produce identical files
+    #[arg(
+        long = "single-codec",
+        value_name = "CODEC",
+        value_enum,
+        conflicts_with = "fast"
+    )]
+    pub single_codec: Option<SingleCodecArg>,
+
+    /// Developer aid: store the hunk map as raw entries instead of Huffman coding it, to tell map coder bugs from codec bugs. Only rom-converto reads the result; chdman and emulators reject it
+    #[arg(long = "uncompressed-map", default_value_t = false)]
+    pub uncompressed_map: bool,
+
+    /// After each CHD is written, log the time spent reading sectors, in each codec's trials (summed across workers), and compressing the map
+    #[arg(long, default_value_t = false)]
+    pub timing: bool,
+
+    /// Cap the memory used by the read and write buffers and the hunks in flight, in MiB. A tight cap queues fewer hunks and runs fewer workers. Uncapped by default
+    #[arg(long = "max-memory", value_name = "MIB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory: Option<u64>,
+
+    /// Checkpoint DVD-mode writes to <OUTPUT>.chdtmp and <OUTPUT>.chdtmp.state, and continue from them when an earlier run was interrupted
+    #[arg(long, default_value_t = false)]
+    pub resume: bool,
+
+    /// Store TEXT in a CMNT metadata entry, prefixed with the rom-converto version. The entry is unhashed, so the CHD's SHA-1 is unchanged
+    #[arg(long, value_name = "TEXT")]
+    pub comment: Option<String>,
+
+    /// Print how the .cue is read, each track's number, type, start LBA, frame count, and file, then exit without writing
+    #[arg(long, default_value_t = false, conflicts_with_all = ["recursive", "dvd"])]
+    pub list: bool,
+
+    /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling. Defaults to skip with --recursive and error otherwise
+    #[arg(long = "on-conflict", alias = "overwrite-policy", value_enum)]
     pub on_conflict: Option<ConflictPolicyArg>,
 
     /// Alias for --on-conflict overwrite
@@ -85,6 +184,10 @@ pub struct CompressCommand {
     #[arg(long = "max-depth", value_name = "N", requires = "recursive")]
     pub max_depth: Option<usize>,
 
+    /// After a --recursive run, replace each CHD it wrote that matches an earlier one (same header SHA-1 and size) with a hard link to it. Duplicates that cannot be linked, such as across filesystems, are logged and kept
+    #[arg(long, default_value_t = false, requires = "recursive")]
+    pub dedupe: bool,
+
     /// Write a run report to FILE. Format inferred from the extension: .csv, .json, .html or .htm. Unknown extensions default to JSON. The file is overwritten directly
     #[arg(long = "report", value_name = "FILE")]
     pub report: Option<PathBuf>,
@@ -93,21 +196,21 @@ pub struct CompressCommand {
 /// Extract files from a CHD file to a specified output directory
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
-    after_long_help = "EXAMPLES:\n  Single file:     rom-converto chd extract game.chd game.cue\n  Explicit output: rom-converto chd extract game.chd --output-dir ./extracted\n  Whole folder:    rom-converto chd extract -R ./chds --output-dir ./extracted\n"
+    after_long_help = "EXAMPLES:\n  Single file:     rom-converto chd extract game.chd game.cue\n  Explicit output: rom-converto chd extract game.chd --output-dir ./extracted\n  To stdout:       rom-converto chd extract game.chd - > game.iso\n  Whole folder:    rom-converto chd extract -R ./chds --output-dir ./extracted\n"
 )]
 pub struct ExtractCommand {
     /// Input CHD file, or a directory of .chd files when --recursive is set
     #[arg(value_name = "INPUT")]
     pub input: PathBuf,
 
-    /// Output path for extracted files (ignored with --recursive)
+    /// Output path for extracted files. `-` streams a DVD CHD's iso to stdout (ignored with --recursive)
     #[arg(
         value_name = "OUTPUT",
         required_unless_present_any = ["recursive", "output_flag", "output_dir"]
     )]
     pub output: Option<PathBuf>,
 
-    /// Output path for extracted files (ignored with --recursive)
+    /// Output path for extracted files. `-` streams a DVD CHD's iso to stdout (ignored with --recursive)
     #[arg(
         short = 'o',
         long = "output",
@@ -120,16 +223,20 @@ pub struct ExtractCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Optional parent CHD file (for CHDs that reference a parent); not allowed with --recursive
     #[arg(long, short = 'p', value_name = "PARENT", conflicts_with = "recursive")]
     pub parent: Option<PathBuf>,
 
+    /// Byte order of audio-track samples in the extracted bin. Data tracks and DVD CHDs are unaffected
+    #[arg(long = "audio-endian", value_enum, default_value_t = AudioEndianArg::Le)]
+    pub audio_endian: AudioEndianArg,
+
     /// Extract every .chd in INPUT and its subdirectories; outputs go beside each input
     #[arg(long, short = 'R', default_value_t = false)]
     pub recursive: bool,
@@ -156,6 +263,30 @@ pub struct ExtractCommand {
     pub report: Option<PathBuf>,
 }
 
+/// Compress a multi-disc game's cues to CHDs and write an .m3u playlist
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Compress a multi-disc game's cues to CHDs and write an .m3u playlist\n\nScans the top level of DIR for .cue files whose names carry a disc number (Redump \"(Disc N)\" or TOSEC \"Disc N of M\"), compresses each to a CD-mode CHD, and writes one <title>.m3u per game listing its CHDs in disc order, the layout RetroArch and other emulators use for disc swaps. Cues without a disc number are skipped. A disc that fails to compress stops the run before its playlist is written.",
+    after_long_help = "EXAMPLES:\n  Beside the cues: rom-converto chd compress-set \"./Final Fantasy VII\"\n  Separate folder: rom-converto chd compress-set ./ff7 -o ./chd\n"
+)]
+pub struct CompressSetCommand {
+    /// Directory holding the game's "(Disc N)" .cue/.bin files
+    #[arg(value_name = "DIR")]
+    pub input: PathBuf,
+
+    /// Write the CHDs and the .m3u into this directory, defaults to DIR. Created if missing
+    #[arg(short = 'o', long = "output-dir", value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Store a provenance note in each CHD's CMNT metadata entry
+    #[arg(long = "comment", value_name = "TEXT")]
+    pub comment: Option<String>,
+
+    /// Overwrite existing CHDs and playlists
+    #[arg(long, short = 'f', default_value_t = false)]
+    pub force: bool,
+}
+
 /// Extract a CHD straight to a CSO or ZSO, through a temporary ISO
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
@@ -184,10 +315,10 @@ pub struct ToCsoCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Output container format
@@ -251,6 +382,75 @@ pub struct VerifyCommand {
     pub max_depth: Option<usize>,
 }
 
+/// Check that a CHD decodes to exactly the image it was made from
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Check that a CHD decodes to exactly the image it was made from\n\nDecodes every hunk and compares it, sector by sector, with the source: a .cue (its bins read back to back) for a CD-mode CHD, or the .iso for a DVD-mode or single-track CD-mode CHD. Reports the first differing sector with its LBA and track and exits non-zero on any difference, so it can gate deleting the originals.",
+    after_long_help = "EXAMPLES:\n  Bin/cue source: rom-converto chd compare game.chd game.cue\n  ISO source:     rom-converto chd compare game.chd game.iso && rm game.iso\n"
+)]
+pub struct CompareCommand {
+    /// CHD file to check
+    #[arg(value_name = "CHD")]
+    pub chd: PathBuf,
+
+    /// Source image the CHD was made from: a .cue, or a flat image such as an .iso
+    #[arg(value_name = "SOURCE")]
+    pub source: PathBuf,
+}
+
+/// Compare CD codec combinations on a sample of a disc's hunks
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Compare CD codec combinations on a sample of a disc's hunks\n\nCompresses hunks spread evenly over the cue's first bin with each CD codec (CDLZ, CDZL, and CDFL on audio hunks) and prints the compressed size, ratio, and single-thread codec time of each combination, including what compress writes by default and with --fast. No CHD is written.",
+    after_long_help = "EXAMPLES:\n  Default sample: rom-converto chd benchmark game.cue\n  More hunks:     rom-converto chd benchmark game.cue --hunks 512\n"
+)]
+pub struct BenchmarkCommand {
+    /// Cue sheet of the disc to sample
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Number of hunks to sample, spread evenly over the image. The whole image is used when it has fewer
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_BENCHMARK_HUNKS as u64, value_parser = clap::value_parser!(u64).range(1..))]
+    pub hunks: u64,
+}
+
+/// Print a CHD's hunk map as a table
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Print a CHD's hunk map as a table\n\nDecodes the map and lists, for every hunk, how it is stored (the header codec it was compressed with, none for raw hunks, or self and parent for references), its length in the file, its file offset, and the CRC16 of its decompressed data. References show the hunk or parent unit they point at in place of an offset. Useful for finding the hunk and codec behind a failed verify.",
+    after_long_help = "EXAMPLES:\n  Whole map:      rom-converto chd map game.chd\n  One codec only: rom-converto chd map game.chd | grep cdfl\n"
+)]
+pub struct MapCommand {
+    /// CHD file to read
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+}
+
+/// Edit a CHD's metadata in place without recompressing it
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Edit a CHD's metadata in place without recompressing it\n\nAdds tagged text entries (the chdman addmeta equivalent), deletes entries by tag (delmeta), or replaces fields of one CD track's CHT2 metadata, such as a wrong TYPE. The new metadata chain is appended to the file and the header's metadata offset and overall SHA-1 are rewritten; the compressed hunks and the raw data SHA-1 are left alone. CHT2 and DVD entries describe the disc layout and cannot be deleted, and a track edit can only replace fields the track already has. CHDs that use this file as their parent record its old SHA-1 and must be re-pointed.",
+    after_long_help = "EXAMPLES:\n  Fix a track type: rom-converto chd meta game.chd --set TRACK:1 TYPE:MODE2_RAW\n  Add a tag:        rom-converto chd meta game.chd --add IDNT=SLUS-00001\n  Delete a tag:     rom-converto chd meta game.chd --delete IDNT\n",
+    group(ArgGroup::new("edits").required(true).multiple(true).args(["add", "delete", "set"]))
+)]
+pub struct MetaCommand {
+    /// CHD file to edit in place
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Append a hashed text entry under a 1-4 character tag. Repeatable
+    #[arg(long = "add", value_name = "TAG=TEXT")]
+    pub add: Vec<String>,
+
+    /// Remove every entry with this tag. Repeatable
+    #[arg(long = "delete", value_name = "TAG")]
+    pub delete: Vec<String>,
+
+    /// Replace track fields: TRACK:N followed by KEY:VALUE pairs; a further TRACK:N starts the next track
+    #[arg(long = "set", value_name = "FIELD", num_args = 1..)]
+    pub set: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,6 +471,224 @@ mod tests {
         assert_eq!(c.output, None);
         assert!(!c.dvd && !c.cd && !c.zstd && !c.force && !c.recursive);
         assert_eq!(c.hunk_size, None);
+        assert_eq!(c.sector_size, None);
+        assert!(!c.trim_trailing_zeros && !c.strict && !c.uncompressed_map);
+        assert_eq!(c.single_codec, None);
+    }
+
+    #[test]
+    fn single_codec_takes_a_codec_name_and_conflicts_with_fast() {
+        let h = Harness::parse_from(["bin", "compress", "game.cue", "--single-codec", "cdzl"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert_eq!(c.single_codec, Some(SingleCodecArg::Cdzl));
+        for bad in [
+            &["bin", "compress", "game.cue", "--single-codec", "huff"][..],
+            &[
+                "bin",
+                "compress",
+                "game.cue",
+                "--single-codec",
+                "cdlz",
+                "--fast",
+            ],
+        ] {
+            assert!(Harness::try_parse_from(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn sector_size_accepts_only_cd_sizes() {
+        let h = Harness::parse_from(["bin", "compress", "game.cue", "--sector-size", "2448"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert_eq!(c.sector_size, Some(2448));
+        let h = Harness::parse_from([
+            "bin",
+            "compress",
+            "game.cue",
+            "--trim-trailing-zeros",
+            "--strict",
+            "--uncompressed-map",
+        ]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(c.trim_trailing_zeros && c.strict && c.uncompressed_map);
+        for bad in [
+            &["bin", "compress", "game.cue", "--sector-size", "2336"][..],
+            &[
+                "bin",
+                "compress",
+                "game.iso",
+                "--dvd",
+                "--sector-size",
+                "2048",
+            ],
+        ] {
+            assert!(Harness::try_parse_from(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn parses_compress_codec_levels() {
+        let h = Harness::parse_from([
+            "bin",
+            "compress",
+            "game.iso",
+            "--lzma-level",
+            "9",
+            "--zstd",
+            "--zstd-level",
+            "22",
+        ]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert_eq!(c.lzma_level, Some(9));
+        assert_eq!(c.zstd_level, Some(22));
+        assert!(
+            Harness::try_parse_from(["bin", "compress", "game.iso", "--lzma-level", "10"]).is_err()
+        );
+        assert!(
+            Harness::try_parse_from(["bin", "compress", "game.iso", "--zstd-level", "5"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_compress_list() {
+        let h = Harness::parse_from(["bin", "compress", "game.cue", "--list"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(c.list);
+        assert!(Harness::try_parse_from(["bin", "compress", "-R", "./roms", "--list"]).is_err());
+    }
+
+    #[test]
+    fn parses_compress_timing() {
+        let h = Harness::parse_from(["bin", "compress", "game.cue", "--timing"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(c.timing);
+
+        let h = Harness::parse_from(["bin", "compress", "game.cue"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(!c.timing);
+    }
+
+    #[test]
+    fn parses_benchmark() {
+        let h = Harness::parse_from(["bin", "benchmark", "game.cue"]);
+        let ChdCommands::Benchmark(c) = h.cmd else {
+            panic!("expected Benchmark");
+        };
+        assert_eq!(c.input, PathBuf::from("game.cue"));
+        assert_eq!(c.hunks, 64);
+        assert!(Harness::try_parse_from(["bin", "benchmark", "game.cue", "--hunks", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_map() {
+        let h = Harness::parse_from(["bin", "map", "game.chd"]);
+        let ChdCommands::Map(c) = h.cmd else {
+            panic!("expected Map");
+        };
+        assert_eq!(c.input, PathBuf::from("game.chd"));
+    }
+
+    #[test]
+    fn parses_compress_fast() {
+        let h = Harness::parse_from(["bin", "compress", "game.cue", "--fast"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(c.fast);
+        assert!(
+            Harness::try_parse_from(["bin", "compress", "game.iso", "--dvd", "--fast"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_compress_max_memory() {
+        let h = Harness::parse_from(["bin", "compress", "game.iso", "--max-memory", "64"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert_eq!(c.max_memory, Some(64));
+        assert!(
+            Harness::try_parse_from(["bin", "compress", "game.iso", "--max-memory", "0"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_compress_resume() {
+        let h = Harness::parse_from(["bin", "compress", "game.iso", "--resume"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(c.resume);
+    }
+
+    #[test]
+    fn parses_compress_comment() {
+        let h = Harness::parse_from(["bin", "compress", "game.iso", "--comment", "my dump"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert_eq!(c.comment.as_deref(), Some("my dump"));
+    }
+
+    #[test]
+    fn parses_compress_set() {
+        let h = Harness::parse_from(["bin", "compress-set", "./ff7", "-o", "./chd", "-f"]);
+        let ChdCommands::CompressSet(c) = h.cmd else {
+            panic!("expected CompressSet");
+        };
+        assert_eq!(c.input, PathBuf::from("./ff7"));
+        assert_eq!(c.output_dir, Some(PathBuf::from("./chd")));
+        assert!(c.force);
+        assert!(Harness::try_parse_from(["bin", "compress-set"]).is_err());
+    }
+
+    #[test]
+    fn parses_compare() {
+        let h = Harness::parse_from(["bin", "compare", "game.chd", "game.cue"]);
+        let ChdCommands::Compare(c) = h.cmd else {
+            panic!("expected Compare");
+        };
+        assert_eq!(c.chd, PathBuf::from("game.chd"));
+        assert_eq!(c.source, PathBuf::from("game.cue"));
+        assert!(Harness::try_parse_from(["bin", "compare", "game.chd"]).is_err());
+    }
+
+    #[test]
+    fn parses_meta_edits() {
+        let h = Harness::parse_from([
+            "bin",
+            "meta",
+            "game.chd",
+            "--set",
+            "TRACK:1",
+            "TYPE:MODE2_RAW",
+            "--add",
+            "IDNT=SLUS-00001",
+            "--delete",
+            "CMNT",
+        ]);
+        let ChdCommands::Meta(m) = h.cmd else {
+            panic!("expected Meta");
+        };
+        assert_eq!(m.input, PathBuf::from("game.chd"));
+        assert_eq!(m.set, ["TRACK:1", "TYPE:MODE2_RAW"]);
+        assert_eq!(m.add, ["IDNT=SLUS-00001"]);
+        assert_eq!(m.delete, ["CMNT"]);
+        assert!(Harness::try_parse_from(["bin", "meta", "game.chd"]).is_err());
     }
 
     #[test]
@@ -383,6 +801,32 @@ mod tests {
         assert_eq!(c.on_conflict, Some(ConflictPolicyArg::Skip));
     }
 
+    #[test]
+    fn overwrite_policy_is_an_alias_of_on_conflict() {
+        let h = Harness::parse_from([
+            "bin",
+            "compress",
+            "-R",
+            "./games",
+            "--overwrite-policy",
+            "overwrite",
+        ]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert_eq!(c.on_conflict, Some(ConflictPolicyArg::Overwrite));
+    }
+
+    #[test]
+    fn dedupe_requires_recursive() {
+        let h = Harness::parse_from(["bin", "compress", "-R", "./games", "--dedupe"]);
+        let ChdCommands::Compress(c) = h.cmd else {
+            panic!("expected Compress");
+        };
+        assert!(c.dedupe);
+        assert!(Harness::try_parse_from(["bin", "compress", "game.cue", "--dedupe"]).is_err());
+    }
+
     #[test]
     fn parses_on_conflict_rename() {
         let h = Harness::parse_from(["bin", "compress", "game.iso", "--on-conflict", "rename"]);
@@ -428,6 +872,28 @@ mod tests {
         assert_eq!(c.output_dir, Some(PathBuf::from("out")));
     }
 
+    #[test]
+    fn extract_audio_endian_defaults_to_le() {
+        let h = Harness::parse_from(["bin", "extract", "in.chd", "out.cue"]);
+        let ChdCommands::Extract(c) = h.cmd else {
+            panic!("expected Extract");
+        };
+        assert_eq!(c.audio_endian, AudioEndianArg::Le);
+
+        let h = Harness::parse_from([
+            "bin",
+            "extract",
+            "in.chd",
+            "out.cue",
+            "--audio-endian",
+            "be",
+        ]);
+        let ChdCommands::Extract(c) = h.cmd else {
+            panic!("expected Extract");
+        };
+        assert_eq!(c.audio_endian, AudioEndianArg::Be);
+    }
+
     #[test]
     fn parses_compress_report_flag() {
         let h = Harness::parse_from(["bin", "compress", "game.iso", "--report", "out.json"]);
@@ -1,6 +1,7 @@
 use crate::commands::ConflictPolicyArg;
 use crate::commands::info_command::InfoCommand;
 use clap::{Parser, Subcommand, ValueEnum};
+use rom_converto_lib::util::validate_template;
 use std::path::PathBuf;
 
 /// Commands for CSO/ZSO compressed ISO images (PSP, PS2)
@@ -50,10 +51,10 @@ pub struct CompressCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Output container format
@@ -117,10 +118,10 @@ pub struct DecompressCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
@@ -177,10 +178,10 @@ pub struct ToChdCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Force DVD mode for the intermediate ISO
@@ -199,6 +200,22 @@ pub struct ToChdCommand {
     #[arg(long)]
     pub zstd: bool,
 
+    /// LZMA preset 0..=9 for the DVD `lzma` codec. Defaults to 8, chdman's level
+    #[arg(long = "lzma-level", value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=9))]
+    pub lzma_level: Option<u32>,
+
+    /// zstd level 1..=22 for the DVD `zstd` codec. Defaults to 19, chdman's level. Requires --zstd
+    #[arg(long = "zstd-level", value_name = "LEVEL", requires = "zstd", value_parser = clap::value_parser!(i32).range(1..=22))]
+    pub zstd_level: Option<i32>,
+
+    /// After each CHD is written, log the time spent reading sectors, in each codec's trials (summed across workers), and compressing the map
+    #[arg(long, default_value_t = false)]
+    pub timing: bool,
+
+    /// Cap the memory used by the read and write buffers and the hunks in flight, in MiB. A tight cap queues fewer hunks and runs fewer workers. Uncapped by default
+    #[arg(long = "max-memory", value_name = "MIB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory: Option<u64>,
+
     /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
     #[arg(long = "on-conflict", value_enum)]
     pub on_conflict: Option<ConflictPolicyArg>,
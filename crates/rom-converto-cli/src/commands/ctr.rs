@@ -1,30 +1,56 @@
 use crate::commands::ConflictPolicyArg;
 use crate::commands::info_command::InfoCommand;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use rom_converto_lib::nintendo::ctr::region::CtrRegion;
+use rom_converto_lib::nintendo::ctr::title_key::{CommonKeyIndex, parse_title_key};
+use rom_converto_lib::util::validate_template;
 use std::path::PathBuf;
 
 /// Commands specific to CTR (3DS) formats
 #[derive(Subcommand, Debug, Eq, PartialEq)]
 pub enum CtrCommands {
     CdnToCia(CdnToCiaCommand),
+    Batch(BatchCommand),
     GenerateCdnTicket(GenerateCdnTicketCommand),
     Decrypt(DecryptCommand),
     Encrypt(EncryptCommand),
+    EncryptNcch(EncryptNcchCommand),
     Compress(CompressRomCommand),
     Decompress(DecompressRomCommand),
     Verify(VerifyCommand),
     Convert(ConvertCommand),
+    Merge(MergeCommand),
     Info(InfoCommand),
+    InspectNcch(InspectNcchCommand),
+    #[command(hide = true)]
+    DumpKeys(DumpKeysCommand),
+    RomfsList(RomfsListCommand),
+    Anonymize(AnonymizeCommand),
+    #[command(visible_alias = "extract-contents")]
+    CiaToCdn(CiaToCdnCommand),
+    ExtractContent(ExtractContentCommand),
+    FixTmd(FixTmdCommand),
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum CdnNameFromArg {
+    /// The CDN directory name: title.cia
+    #[default]
+    Dir,
+    /// The TMD title ID: 0004000000155400.cia
+    TitleId,
+    /// The TMD title ID and version: "0004000000155400 v1024.cia"
+    TitleIdVersion,
 }
 
 /// Convert CDN content to CIA format
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
     long_about = "Convert CDN content to CIA format\n\nNote: By default the output CIA file is encrypted, if you want to decrypt it after conversion, use the --decrypt flag\nYou can also use the --compress flag to compress the CIA into Z3DS format (.zcia) after conversion, this requires the CIA to be decrypted first",
-    after_long_help = "EXAMPLES:\n  Single file:     rom-converto ctr cdn-to-cia ./cdn-content\n  Explicit output: rom-converto ctr cdn-to-cia ./cdn-content game.cia\n  Whole folder:    rom-converto ctr cdn-to-cia -R ./cdn-dumps --output-dir ./cia\n"
+    after_long_help = "EXAMPLES:\n  Single file:     rom-converto ctr cdn-to-cia ./cdn-content\n  Explicit output: rom-converto ctr cdn-to-cia ./cdn-content game.cia\n  Whole folder:    rom-converto ctr cdn-to-cia -R ./cdn-dumps --output-dir ./cia\n  From an archive: rom-converto ctr cdn-to-cia ./cdn-content.7z\n  Check output:    rom-converto ctr cdn-to-cia ./cdn-content --verify-after\n  Pick a TMD:      rom-converto ctr cdn-to-cia ./cdn-content --tmd-version 1024\n  One region:      rom-converto ctr cdn-to-cia -R ./cdn-dumps --region usa\n  Name by TMD:     rom-converto ctr cdn-to-cia -R ./cdn-dumps --output-template \"{titleId} v{titleVersion}.{ext}\"\n"
 )]
 pub struct CdnToCiaCommand {
-    /// Path to the CDN content directory
+    /// Path to the CDN content directory, or an archive holding one (not with --recursive)
     #[arg(value_name = "CDN_DIR")]
     pub cdn_dir: PathBuf,
 
@@ -45,6 +71,16 @@ pub struct CdnToCiaCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
+    /// How to name a derived output: the CDN directory name, or the TMD title ID with or without the title version. Ignored when OUTPUT is given
+    #[arg(long = "name-from", value_enum, default_value_t = CdnNameFromArg::Dir)]
+    pub name_from: CdnNameFromArg,
+
+    /// Output path template per title, used instead of --name-from. Tokens as for the other
+    /// commands; {titleId} and {titleVersion} come from the TMD, {basename} is the CDN
+    /// directory name. Joined under --output-dir, or next to the CDN directory
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag", "name_from"])]
+    pub output_template: Option<String>,
+
     /// Clean up after conversion by removing the original CDN files
     #[arg(long, short = 'C', default_value = "false")]
     pub cleanup: bool,
@@ -57,6 +93,23 @@ pub struct CdnToCiaCommand {
     #[arg(long, short = 'T', default_value = "false")]
     pub ensure_ticket_exists: bool,
 
+    /// Use this ticket instead of looking for one in CDN_DIR. Cannot be combined with --recursive
+    #[arg(long = "ticket", value_name = "PATH", conflicts_with = "recursive")]
+    pub ticket: Option<PathBuf>,
+
+    /// Common key a generated ticket's title key is encrypted with: 0-5, or `auto` to judge it from the title ID
+    #[arg(
+        long = "common-key-index",
+        value_name = "INDEX",
+        default_value = "auto",
+        requires = "ensure_ticket_exists"
+    )]
+    pub common_key_index: CommonKeyIndex,
+
+    /// Write this encrypted title key (32 hex digits) into a generated ticket instead of deriving one
+    #[arg(long = "title-key", value_name = "KEY", value_parser = parse_title_key, requires = "ensure_ticket_exists")]
+    pub title_key: Option<[u8; 16]>,
+
     /// Decrypt the CIA file after conversion, useful for emulators like Azahar
     #[arg(long, short = 'D', default_value = "false")]
     pub decrypt: bool,
@@ -65,6 +118,59 @@ pub struct CdnToCiaCommand {
     #[arg(long, short = 'Z', default_value = "false")]
     pub compress: bool,
 
+    /// Reopen each built CIA and check its content sizes, and its content hashes when it is still encrypted, against the TMD before writing it
+    #[arg(long = "verify-after", default_value_t = false)]
+    pub verify_after: bool,
+
+    /// Trim or zero-pad each content file to the size its TMD declares instead of refusing a mis-sized download
+    #[arg(long = "fix-content-size", default_value_t = false)]
+    pub fix_content_size: bool,
+
+    /// Keep the TMD and ticket certificate chains whole, dropping only exact duplicates, instead of picking one CA, XS, and CP certificate by name. For titles whose atypical chains lose certificates otherwise
+    #[arg(long = "raw-certs", alias = "no-cert-merge", default_value_t = false)]
+    pub raw_certs: bool,
+
+    /// Build from tmd.<VERSION> when the CDN directory holds several TMDs, failing if that file is missing. Without it a bare `tmd` wins, then the highest tmd.<n>
+    #[arg(long = "tmd-version", value_name = "VERSION")]
+    pub tmd_version: Option<u32>,
+
+    /// Only convert titles released for this region when --recursive is set: free, jpn, usa, eur, aus, chn, kor, or twn. Read from each title's NCCH product code; region-free titles always match, titles whose region cannot be read are skipped
+    #[arg(long = "region", value_name = "REGION", requires = "recursive")]
+    pub region: Option<CtrRegion>,
+
+    /// Read up to N content files at once while writing each CIA. Contents are still written in TMD order; memory grows by a few 4 MB buffers per file. Helps most on network drives and other high-latency storage
+    #[arg(long = "content-chunk-concurrency", value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=64))]
+    pub content_chunk_concurrency: u64,
+
+    /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
+    #[arg(long = "on-conflict", value_enum, default_value_t = ConflictPolicyArg::Error)]
+    pub on_conflict: ConflictPolicyArg,
+
+    /// Alias for --on-conflict overwrite
+    #[arg(
+        long,
+        short = 'f',
+        default_value_t = false,
+        conflicts_with = "on_conflict"
+    )]
+    pub force: bool,
+}
+
+/// Convert the CDN titles listed in a manifest to CIA format
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Convert the CDN titles listed in a manifest to CIA format\n\nThe manifest is a TOML file with one [[title]] table per title. Each sets cdn_dir and optionally output, decrypt, compress, ensure_ticket, common_key_index, title_key, verify_after, fix_content_size, raw_certs, tmd_version, and ticket. Relative paths resolve against the manifest's directory. A failed title does not stop the others; every title's result is reported at the end, and the command fails if any title did.",
+    after_long_help = "EXAMPLES:\n  Build a list:   rom-converto ctr batch titles.toml\n  Four at once:   rom-converto ctr batch titles.toml --jobs 4\n  Resume a run:   rom-converto ctr batch titles.toml --on-conflict skip\n\nMANIFEST:\n  [[title]]\n  cdn_dir = \"dumps/0004000000155400\"\n  output = \"cia/Game.cia\"\n  decrypt = true\n  ensure_ticket = true\n  common_key_index = \"auto\"\n"
+)]
+pub struct BatchCommand {
+    /// TOML manifest listing the titles to build
+    #[arg(value_name = "MANIFEST")]
+    pub manifest: PathBuf,
+
+    /// Number of titles to build at once. Per-title progress is hidden above 1
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: u64,
+
     /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
     #[arg(long = "on-conflict", value_enum, default_value_t = ConflictPolicyArg::Error)]
     pub on_conflict: ConflictPolicyArg,
@@ -83,34 +189,50 @@ pub struct CdnToCiaCommand {
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
     long_about = "Generate a Ticket file from CDN content\n\nThis Ticket file is not official from Nintendo: it has non-important data like the console ID set to null. A CIA file built with this ticket will not work on a stock 3DS, but works fine on emulators or a 3DS with custom firmware.",
-    after_long_help = "EXAMPLES:\n  Default name: rom-converto ctr generate-cdn-ticket ./cdn-content\n  Custom name:  rom-converto ctr generate-cdn-ticket ./cdn-content my-ticket.tik\n"
+    after_long_help = "EXAMPLES:\n  Default name: rom-converto ctr generate-cdn-ticket ./cdn-content\n  Custom name:  rom-converto ctr generate-cdn-ticket ./cdn-content my-ticket.tik\n  Password:     rom-converto ctr generate-cdn-ticket ./cdn-content --password password\n  Common key:   rom-converto ctr generate-cdn-ticket ./cdn-content --common-key-index 1\n"
 )]
 pub struct GenerateCdnTicketCommand {
-    /// Path to the CDN content directory
+    /// Path to the CDN content directory, or an archive holding one
     #[arg(value_name = "CDN_DIR")]
     pub cdn_dir: PathBuf,
 
     /// Output Ticket file path
     #[arg(value_name = "OUTPUT", default_value = "ticket.tik")]
     pub output: PathBuf,
+
+    /// Write this title version (0-65535) into the ticket instead of the TMD's. A version that does not match the TMD may prevent installation on retail units
+    #[arg(long = "title-version", value_name = "VERSION")]
+    pub title_version: Option<u16>,
+
+    /// Derive the title key from this password instead of the default `mypass`, for titles whose community tickets use another one
+    #[arg(long = "password", value_name = "PASSWORD")]
+    pub password: Option<String>,
+
+    /// Common key the title key is encrypted with: 0-5, or `auto` to use 1 for system titles and 0 for everything else, judged from the title ID
+    #[arg(
+        long = "common-key-index",
+        value_name = "INDEX",
+        default_value = "auto"
+    )]
+    pub common_key_index: CommonKeyIndex,
 }
 
 /// Decrypt an encrypted 3DS ROM file
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
-    long_about = "Decrypt an encrypted 3DS ROM file\n\nSupported input formats: .cia, .3ds, .cci, .cxi\nThe format is auto-detected from the file contents.\n\nIf OUTPUT is omitted the decrypted file is written next to the input as <name>.decrypted.<ext>.\n\nUse --recursive/-R to point INPUT at a directory and decrypt every matching file in it and its subdirectories; pass --max-depth N to limit the descent depth (1 = top level only). In batch mode OUTPUT is ignored and each decrypted file is written next to its source as <name>.decrypted.<ext>.",
-    after_long_help = "EXAMPLES:\n  Single file:     rom-converto ctr decrypt game.cia\n  Explicit output: rom-converto ctr decrypt game.3ds game.decrypted.3ds\n  Whole folder:    rom-converto ctr decrypt -R ./roms --output-dir ./decrypted\n"
+    long_about = "Decrypt an encrypted 3DS ROM file\n\nSupported input formats: .cia, .3ds, .cci, .cxi\nThe format is auto-detected from the file contents.\n\nIf OUTPUT is omitted the decrypted file is written next to the input as <name>.decrypted.<ext>.\n\nAn INPUT of `-` reads the ROM from stdin. It is buffered to a temporary file first, since decryption seeks around the container, and OUTPUT must then be given explicitly.\n\nUse --recursive/-R to point INPUT at a directory and decrypt every matching file in it and its subdirectories; pass --max-depth N to limit the descent depth (1 = top level only). In batch mode OUTPUT is ignored and each decrypted file is written next to its source as <name>.decrypted.<ext>.",
+    after_long_help = "EXAMPLES:\n  Single file:     rom-converto ctr decrypt game.cia\n  Explicit output: rom-converto ctr decrypt game.3ds game.decrypted.3ds\n  To stdout:       rom-converto ctr decrypt game.cia - | gzip > game.cia.gz\n  From stdin:      zcat game.cia.gz | rom-converto ctr decrypt - game.decrypted.cia\n  Whole folder:    rom-converto ctr decrypt -R ./roms --output-dir ./decrypted\n  Loose NCCH:      rom-converto ctr decrypt game.cia ./game-ncch --ncch-only\n"
 )]
 pub struct DecryptCommand {
-    /// Input ROM file path, or a directory when --recursive is set (.cia, .3ds, .cci, or .cxi)
+    /// Input ROM file path, or a directory when --recursive is set (.cia, .3ds, .cci, or .cxi). `-` reads from stdin
     #[arg(value_name = "INPUT")]
     pub input: PathBuf,
 
-    /// Output decrypted file path, defaults to <name>.decrypted.<ext> next to the input (ignored with --recursive)
+    /// Output decrypted file path, defaults to <name>.decrypted.<ext> next to the input. `-` writes to stdout (ignored with --recursive)
     #[arg(value_name = "OUTPUT")]
     pub output: Option<PathBuf>,
 
-    /// Output decrypted file path, defaults to <name>.decrypted.<ext> next to the input (ignored with --recursive)
+    /// Output decrypted file path, defaults to <name>.decrypted.<ext> next to the input. `-` writes to stdout (ignored with --recursive)
     #[arg(
         short = 'o',
         long = "output",
@@ -123,10 +245,10 @@ pub struct DecryptCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Process all matching files in INPUT and its subdirectories
@@ -149,6 +271,68 @@ pub struct DecryptCommand {
         conflicts_with = "on_conflict"
     )]
     pub force: bool,
+
+    /// When a seed-crypto title's seed cannot be found, copy that NCCH through still encrypted, header flags included, instead of failing
+    #[arg(long = "allow-missing-seed", default_value_t = false)]
+    pub allow_missing_seed: bool,
+
+    /// Check each decrypted RomFS superblock against the hash in its NCCH header and log whether it matches
+    #[arg(long = "check-romfs", default_value_t = false)]
+    pub check_romfs: bool,
+
+    /// Use this seed (32 hex digits) for seed-crypto titles instead of the seed database or the CDN. It is checked against each NCCH's seedcheck first and the decrypt fails on a mismatch
+    #[arg(long = "seed", value_name = "HEX", value_parser = parse_seed)]
+    pub seed: Option<[u8; 16]>,
+
+    /// CIA input only: write each decrypted content as a loose <name>.<index>.<id>.ncch file and skip the CIA rebuild. OUTPUT (or --output-dir) names the directory, defaulting to the input's directory
+    #[arg(long = "ncch-only", default_value_t = false, conflicts_with_all = ["recursive", "output_template"])]
+    pub ncch_only: bool,
+}
+
+fn parse_seed(text: &str) -> Result<[u8; 16], String> {
+    rom_converto_lib::nintendo::ctr::seed::parse_seed_hex(text).map_err(|e| e.to_string())
+}
+
+/// Encrypt a single decrypted NCCH
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Encrypt a single decrypted NCCH\n\nSupported input formats: .ncch, .cxi, .cfa. CIA and NCSD images are refused; use `ctr encrypt` for those.\n\nThe crypto method (key slot, fixed key, seed) is read from the NCCH header flags the same way `ctr decrypt` reads it. The ExHeader, ExeFS, and RomFS are AES-CTR encrypted in place of the plain bytes and the no-crypto flag is cleared. Use it to re-encrypt an NCCH after modifying it.\n\nAn NCCH written by `ctr decrypt --ncch-only` from CIA content 1 or later carries a small RomFS fixup that has to be re-applied. Its index is read from the <name>.<index>.<id>.ncch file name; pass --content-index when the file was renamed.",
+    after_long_help = "EXAMPLES:\n  Single file:     rom-converto ctr encrypt-ncch game.cxi\n  Explicit output: rom-converto ctr encrypt-ncch patched.ncch game.encrypted.ncch\n  Split content:   rom-converto ctr encrypt-ncch dlc.ncch --content-index 3\n"
+)]
+pub struct EncryptNcchCommand {
+    /// Input decrypted NCCH file path (.ncch, .cxi, or .cfa)
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Output encrypted file path, defaults to <name>.encrypted.<ext> next to the input
+    #[arg(value_name = "OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Output encrypted file path, defaults to <name>.encrypted.<ext> next to the input
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "OUTPUT",
+        conflicts_with = "output"
+    )]
+    pub output_flag: Option<PathBuf>,
+
+    /// CIA content index the NCCH was split from by `ctr decrypt --ncch-only`. Defaults to the index in such a file's name, or none for any other NCCH
+    #[arg(long = "content-index", value_name = "INDEX")]
+    pub content_index: Option<u16>,
+
+    /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
+    #[arg(long = "on-conflict", value_enum, default_value_t = ConflictPolicyArg::Error)]
+    pub on_conflict: ConflictPolicyArg,
+
+    /// Alias for --on-conflict overwrite
+    #[arg(
+        long,
+        short = 'f',
+        default_value_t = false,
+        conflicts_with = "on_conflict"
+    )]
+    pub force: bool,
 }
 
 /// Encrypt a decrypted 3DS ROM file
@@ -179,10 +363,10 @@ pub struct EncryptCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Process all matching files in INPUT and its subdirectories
@@ -235,10 +419,10 @@ pub struct CompressRomCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Zstd compression level (0 = library default, 22 = maximum ratio). Higher levels produce smaller output at the cost of compression time. Defaults to the library default when unset
@@ -299,10 +483,10 @@ pub struct DecompressRomCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Process all matching files in INPUT and its subdirectories
@@ -355,10 +539,10 @@ pub struct ConvertCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Process all matching files in INPUT and its subdirectories
@@ -383,6 +567,186 @@ pub struct ConvertCommand {
     pub force: bool,
 }
 
+/// Merge update and DLC CIAs into a base title CIA
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Merge update and DLC CIAs into a base title CIA\n\nThe base CIA keeps its certificate chain, ticket, and meta block. Contents from each update are layered on top by content index, so an update's program content replaces the base one, and the TMD takes the last update's title version. DLC contents are renumbered to follow the base and update ones, since DLC numbers its contents from 0 as well. The TMD is rebuilt with fresh content info hashes.\n\nEvery input must be decrypted first (see `ctr decrypt`): each title's contents are encrypted under its own title key. A warning is printed when an input's title ID does not belong to the base title.",
+    after_long_help = "EXAMPLES:\n  Base + update:       rom-converto ctr merge game.cia update.cia -o merged.cia\n  Base + update + DLC: rom-converto ctr merge game.cia update.cia dlc.cia -o merged.cia\n"
+)]
+pub struct MergeCommand {
+    /// Decrypted base title CIA
+    #[arg(value_name = "BASE")]
+    pub base: PathBuf,
+
+    /// Decrypted update and DLC CIAs, applied in order
+    #[arg(value_name = "CIA", required = true, num_args = 1..)]
+    pub additions: Vec<PathBuf>,
+
+    /// Output merged CIA path
+    #[arg(short = 'o', long = "output", value_name = "OUTPUT")]
+    pub output: PathBuf,
+
+    /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
+    #[arg(long = "on-conflict", value_enum, default_value_t = ConflictPolicyArg::Error)]
+    pub on_conflict: ConflictPolicyArg,
+
+    /// Alias for --on-conflict overwrite
+    #[arg(
+        long,
+        short = 'f',
+        default_value_t = false,
+        conflicts_with = "on_conflict"
+    )]
+    pub force: bool,
+}
+
+/// Report the crypto method of every NCCH partition without decrypting
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Report the crypto method of every NCCH partition without decrypting\n\nSupported formats: .cia, .3ds, .cci, .cxi\n\nFor each NCSD partition or CIA content, prints whether the NCCH is encrypted and how: keyslot 0x2C plus the extra keyslot (0x25, 0x18 or 0x1B) used for the ExeFS code and RomFS, the zero or system fixed key, and whether seed crypto (9.6+) is in use and its seed is in the local seeddb.bin. CIA contents also show whether they sit under the ticket's title key. Nothing is written.",
+    after_long_help = "EXAMPLES:\n  Single file: rom-converto ctr inspect-ncch game.3ds\n  As JSON:     rom-converto ctr inspect-ncch game.cia --json\n"
+)]
+pub struct InspectNcchCommand {
+    /// Input ROM file path (.cia, .3ds, .cci, .cxi)
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Emit JSON instead of pretty text
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+/// Print a CIA's decrypted title key and NCCH KeyYs, for debugging decryption
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Print a CIA's decrypted title key and NCCH KeyYs, for debugging decryption\n\nDecrypts the ticket's title key with the common key it names and prints it with the common key index, then reads the first block of every content and prints its NCCH KeyY (the first 16 bytes of the header signature), its crypto method, and the seed-derived KeyY when seed crypto is on and the seed is in the local seeddb.bin. Nothing is written. The output is key material; do not paste it publicly.",
+    after_long_help = "EXAMPLES:\n  Single file: rom-converto ctr dump-keys game.cia\n  As JSON:     rom-converto ctr dump-keys game.cia --json\n"
+)]
+pub struct DumpKeysCommand {
+    /// Input CIA file path
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Emit JSON instead of pretty text
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+/// List the files in the RomFS of a decrypted NCCH
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "List the files in the RomFS of a decrypted NCCH\n\nSupported formats: .cxi, .3ds, .cci, and .cia with the title key layer removed\n\nParses the RomFS directory and file metadata tables and prints every file path with its size. For an NCSD the main partition is listed, for a CIA its first content. Both the IVFC-wrapped level 3 used by retail titles and a bare level-3 image are accepted. Encrypted input is refused; run `ctr decrypt` first. Nothing is written.",
+    after_long_help = "EXAMPLES:\n  Single file: rom-converto ctr romfs-list game.cxi\n  As JSON:     rom-converto ctr romfs-list game.decrypted.3ds --json\n"
+)]
+pub struct RomfsListCommand {
+    /// Input ROM file path (.cxi, .3ds, .cci, .cia)
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Emit JSON instead of pretty text
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+/// Zero the console ID and eShop account ID in a ticket
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Zero the console ID and eShop account ID in a ticket\n\nSupported formats: .cia, .tik\n\neShop tickets record the console and account they were bought on, which identifies the owner of a dump. This rewrites the ticket in place with both fields cleared; a CIA keeps every other byte. The ticket signature covers these fields, so a legit ticket stops verifying: the title still installs on custom firmware with signature patches and runs in emulators, but not on a stock 3DS.",
+    after_long_help = "EXAMPLES:\n  CIA:    rom-converto ctr anonymize game.cia\n  Ticket: rom-converto ctr anonymize title.tik\n"
+)]
+pub struct AnonymizeCommand {
+    /// CIA or ticket file to rewrite in place (.cia, .tik)
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+}
+
+/// Split a CIA back into a CDN directory without decrypting
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Split a CIA back into a CDN directory without decrypting\n\nSupported formats: .cia\n\nWrites each content as stored, named by its content ID (00000000, 00000001, ...), the TMD as `tmd`, and the ticket as `cetk`, with the matching certificates from the CIA chain appended to both. Title-key encrypted contents stay encrypted. The result is the layout `ctr cdn-to-cia` reads, so the two commands are inverses and a title can be re-converted with different options.\n\nAlso available as `extract-contents`.",
+    after_long_help = "EXAMPLES:\n  Single file:  rom-converto ctr cia-to-cdn game.cia ./game\n  CDN naming:   rom-converto ctr cia-to-cdn game.cia ./game --versioned-tmd --tik\n  Round trip:   rom-converto ctr cia-to-cdn game.cia ./game && rom-converto ctr cdn-to-cia ./game game2.cia\n"
+)]
+pub struct CiaToCdnCommand {
+    /// Input CIA file path
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// Directory to write the CDN files into; created if missing
+    #[arg(value_name = "OUTPUT_DIR")]
+    pub output_dir: PathBuf,
+
+    /// Name the TMD `tmd.<title version>` instead of `tmd`
+    #[arg(long, default_value_t = false)]
+    pub versioned_tmd: bool,
+
+    /// Name the ticket `<title id>.tik` instead of `cetk`
+    #[arg(long, default_value_t = false)]
+    pub tik: bool,
+
+    /// Overwrite CDN files that already exist in OUTPUT_DIR
+    #[arg(long, short = 'f', default_value_t = false)]
+    pub force: bool,
+}
+
+/// Write one content of a CIA to a file
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Write one content of a CIA to a file
+
+Supported formats: .cia
+
+Finds the content with the given TMD content index and copies only its bytes, so a single content of a large title (index 0 is the main CXI) can be pulled out without decrypting or splitting the whole title. The content is written as stored unless --decrypt is given, which derives the title key from the CIA's ticket and removes the title key layer. The NCCH inside keeps its own encryption; run `ctr decrypt` on the result to remove that too.",
+    after_long_help = "EXAMPLES:
+  Main CXI:      rom-converto ctr extract-content game.cia --index 0 main.app
+  Decrypted:     rom-converto ctr extract-content game.cia --index 0 main.cxi --decrypt
+  Manual:        rom-converto ctr extract-content game.cia --index 1 manual.cfa -D
+"
+)]
+pub struct ExtractContentCommand {
+    /// Input CIA file path
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// TMD content index of the content to write, e.g. 0 for the main CXI
+    #[arg(long, value_name = "N")]
+    pub index: u16,
+
+    /// Output file path
+    #[arg(value_name = "OUTPUT")]
+    pub output: PathBuf,
+
+    /// Remove the title key encryption from the content
+    #[arg(long, short = 'D', default_value_t = false)]
+    pub decrypt: bool,
+
+    /// Overwrite OUTPUT if it already exists
+    #[arg(long, short = 'f', default_value_t = false)]
+    pub force: bool,
+}
+
+/// Recompute the content hashes in a TMD after editing contents
+#[derive(Parser, Debug, Clone, Eq, PartialEq)]
+#[command(
+    long_about = "Recompute the content hashes in a TMD after editing contents
+
+Supported formats: a CDN `tmd` or `tmd.<version>` file
+
+Hashes each content in CONTENT_DIR, found by its content ID (00000000, 00000001, ...), stores the SHA-256 in the TMD's chunk records, and rebuilds the content info record hashes and the header hash over them, the same chain `ctr decrypt` writes. The TMD is rewritten in place; certificates appended after it are kept. Contents must be decrypted and match the sizes the TMD declares. The TMD signature covers these hashes, so a signed TMD stops verifying once anything changes.",
+    after_long_help = "EXAMPLES:
+  CDN directory: rom-converto ctr fix-tmd ./game/tmd ./game
+  Round trip:    rom-converto ctr fix-tmd ./game/tmd ./game && rom-converto ctr cdn-to-cia ./game game.cia
+"
+)]
+pub struct FixTmdCommand {
+    /// TMD file to rewrite in place
+    #[arg(value_name = "TMD")]
+    pub tmd: PathBuf,
+
+    /// Directory holding the decrypted contents, named by content ID
+    #[arg(value_name = "CONTENT_DIR")]
+    pub content_dir: PathBuf,
+}
+
 /// Verify CTR ROM file integrity and legitimacy
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
@@ -432,6 +796,225 @@ mod tests {
         assert_eq!(c.output_flag, Some(PathBuf::from("out.cia")));
     }
 
+    #[test]
+    fn decrypt_accepts_dash_for_stdin() {
+        let h = Harness::parse_from(["bin", "decrypt", "-", "out.cia"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert_eq!(c.input, PathBuf::from("-"));
+        assert_eq!(c.output, Some(PathBuf::from("out.cia")));
+    }
+
+    #[test]
+    fn decrypt_accepts_dash_for_stdout() {
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia", "-"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert_eq!(c.output, Some(PathBuf::from("-")));
+
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia", "-o", "-"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert_eq!(c.output_flag, Some(PathBuf::from("-")));
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_tmd_version() {
+        let h = Harness::parse_from(["bin", "cdn-to-cia", "./cdn", "--tmd-version", "1024"]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.tmd_version, Some(1024));
+        assert!(
+            Harness::try_parse_from(["bin", "cdn-to-cia", "./cdn", "--tmd-version", "v2"]).is_err()
+        );
+    }
+
+    #[test]
+    fn decrypt_allow_missing_seed_defaults_off() {
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert!(!c.allow_missing_seed);
+
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia", "--allow-missing-seed"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert!(c.allow_missing_seed);
+    }
+
+    #[test]
+    fn decrypt_check_romfs_defaults_off() {
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert!(!c.check_romfs);
+
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia", "--check-romfs"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert!(c.check_romfs);
+    }
+
+    #[test]
+    fn decrypt_seed_must_be_32_hex_digits() {
+        let h = Harness::parse_from([
+            "bin",
+            "decrypt",
+            "game.cia",
+            "--seed",
+            "000102030405060708090A0B0C0D0E0F",
+        ]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert_eq!(
+            c.seed,
+            Some([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15])
+        );
+        for bad in ["0001", "not-hex"] {
+            assert!(
+                Harness::try_parse_from(["bin", "decrypt", "game.cia", "--seed", bad]).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn decrypt_ncch_only_rejects_recursive() {
+        let h = Harness::parse_from(["bin", "decrypt", "game.cia", "out", "--ncch-only"]);
+        let CtrCommands::Decrypt(c) = h.cmd else {
+            panic!("expected Decrypt");
+        };
+        assert!(c.ncch_only);
+        assert_eq!(c.output, Some(PathBuf::from("out")));
+        assert!(Harness::try_parse_from(["bin", "decrypt", "-R", "roms", "--ncch-only"]).is_err());
+    }
+
+    #[test]
+    fn parses_merge_inputs_in_order() {
+        let h = Harness::parse_from([
+            "bin",
+            "merge",
+            "game.cia",
+            "update.cia",
+            "dlc.cia",
+            "-o",
+            "merged.cia",
+        ]);
+        let CtrCommands::Merge(c) = h.cmd else {
+            panic!("expected Merge");
+        };
+        assert_eq!(c.base, PathBuf::from("game.cia"));
+        assert_eq!(
+            c.additions,
+            vec![PathBuf::from("update.cia"), PathBuf::from("dlc.cia")]
+        );
+        assert_eq!(c.output, PathBuf::from("merged.cia"));
+    }
+
+    #[test]
+    fn merge_requires_an_addition_and_output() {
+        assert!(Harness::try_parse_from(["bin", "merge", "game.cia", "-o", "m.cia"]).is_err());
+        assert!(Harness::try_parse_from(["bin", "merge", "game.cia", "update.cia"]).is_err());
+    }
+
+    #[test]
+    fn parses_inspect_ncch() {
+        let h = Harness::parse_from(["bin", "inspect-ncch", "game.3ds", "--json"]);
+        let CtrCommands::InspectNcch(c) = h.cmd else {
+            panic!("expected InspectNcch");
+        };
+        assert_eq!(c.input, PathBuf::from("game.3ds"));
+        assert!(c.json);
+    }
+
+    #[test]
+    fn parses_hidden_dump_keys() {
+        let h = Harness::parse_from(["bin", "dump-keys", "game.cia"]);
+        let CtrCommands::DumpKeys(c) = h.cmd else {
+            panic!("expected DumpKeys");
+        };
+        assert_eq!(c.input, PathBuf::from("game.cia"));
+        assert!(!c.json);
+    }
+
+    #[test]
+    fn parses_info_dat_xml() {
+        let h = Harness::parse_from(["bin", "info", "game.cia", "--dat-xml", "game.dat"]);
+        let CtrCommands::Info(c) = h.cmd else {
+            panic!("expected Info");
+        };
+        assert_eq!(c.dat_xml, Some(PathBuf::from("game.dat")));
+    }
+
+    #[test]
+    fn parses_anonymize() {
+        let h = Harness::parse_from(["bin", "anonymize", "title.tik"]);
+        let CtrCommands::Anonymize(c) = h.cmd else {
+            panic!("expected Anonymize");
+        };
+        assert_eq!(c.input, PathBuf::from("title.tik"));
+    }
+
+    #[test]
+    fn parses_cia_to_cdn_and_its_alias() {
+        let h = Harness::parse_from(["bin", "cia-to-cdn", "game.cia", "out", "--versioned-tmd"]);
+        let CtrCommands::CiaToCdn(c) = h.cmd else {
+            panic!("expected CiaToCdn");
+        };
+        assert_eq!(c.input, PathBuf::from("game.cia"));
+        assert_eq!(c.output_dir, PathBuf::from("out"));
+        assert!(c.versioned_tmd);
+        assert!(!c.tik);
+
+        let h = Harness::parse_from(["bin", "extract-contents", "game.cia", "out", "-f"]);
+        let CtrCommands::CiaToCdn(c) = h.cmd else {
+            panic!("expected CiaToCdn");
+        };
+        assert!(c.force);
+        assert!(Harness::try_parse_from(["bin", "cia-to-cdn", "game.cia"]).is_err());
+    }
+
+    #[test]
+    fn parses_extract_content_and_requires_an_index() {
+        let h = Harness::parse_from([
+            "bin",
+            "extract-content",
+            "game.cia",
+            "--index",
+            "1",
+            "manual.cfa",
+            "-D",
+        ]);
+        let CtrCommands::ExtractContent(c) = h.cmd else {
+            panic!("expected ExtractContent");
+        };
+        assert_eq!(c.index, 1);
+        assert_eq!(c.output, PathBuf::from("manual.cfa"));
+        assert!(c.decrypt && !c.force);
+        assert!(
+            Harness::try_parse_from(["bin", "extract-content", "game.cia", "out.app"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_fix_tmd() {
+        let h = Harness::parse_from(["bin", "fix-tmd", "game/tmd", "game"]);
+        let CtrCommands::FixTmd(c) = h.cmd else {
+            panic!("expected FixTmd");
+        };
+        assert_eq!(c.tmd, PathBuf::from("game/tmd"));
+        assert_eq!(c.content_dir, PathBuf::from("game"));
+        assert!(Harness::try_parse_from(["bin", "fix-tmd", "game/tmd"]).is_err());
+    }
+
     #[test]
     fn output_flag_conflicts_with_positional() {
         let result =
@@ -458,6 +1041,17 @@ mod tests {
         assert_eq!(c.output, None);
     }
 
+    #[test]
+    fn parses_encrypt_ncch_content_index() {
+        let h = Harness::parse_from(["bin", "encrypt-ncch", "dlc.ncch", "--content-index", "3"]);
+        let CtrCommands::EncryptNcch(c) = h.cmd else {
+            panic!("expected EncryptNcch");
+        };
+        assert_eq!(c.input, PathBuf::from("dlc.ncch"));
+        assert_eq!(c.content_index, Some(3));
+        assert_eq!(c.output, None);
+    }
+
     #[test]
     fn parses_compress_output_dir() {
         let h = Harness::parse_from(["bin", "compress", "game.cia", "--output-dir", "out"]);
@@ -538,6 +1132,233 @@ mod tests {
         };
         assert!(c.recursive);
         assert_eq!(c.on_conflict, ConflictPolicyArg::Skip);
+        assert_eq!(c.name_from, CdnNameFromArg::Dir);
+        assert!(!c.verify_after);
+    }
+
+    #[test]
+    fn cdn_to_cia_region_parses_and_needs_recursive() {
+        let h = Harness::parse_from(["bin", "cdn-to-cia", "-R", "./cdn", "--region", "JPN"]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.region, Some(CtrRegion::Jpn));
+        assert!(
+            Harness::try_parse_from(["bin", "cdn-to-cia", "./cdn", "--region", "usa"]).is_err()
+        );
+        assert!(
+            Harness::try_parse_from(["bin", "cdn-to-cia", "-R", "./cdn", "--region", "mars"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_verify_after() {
+        let h = Harness::parse_from(["bin", "cdn-to-cia", "./cdn", "--verify-after"]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert!(c.verify_after);
+        assert!(!c.fix_content_size);
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_fix_content_size() {
+        let h = Harness::parse_from(["bin", "cdn-to-cia", "./cdn", "--fix-content-size"]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert!(c.fix_content_size);
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_raw_certs_and_its_alias() {
+        for flag in ["--raw-certs", "--no-cert-merge"] {
+            let h = Harness::parse_from(["bin", "cdn-to-cia", "./cdn", flag]);
+            let CtrCommands::CdnToCia(c) = h.cmd else {
+                panic!("expected CdnToCia");
+            };
+            assert!(c.raw_certs, "{flag}");
+        }
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_ticket_and_refuses_it_with_recursive() {
+        let h = Harness::parse_from(["bin", "cdn-to-cia", "./cdn", "--ticket", "a.tik"]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.ticket, Some(PathBuf::from("a.tik")));
+        assert!(
+            Harness::try_parse_from(["bin", "cdn-to-cia", "./cdn", "-R", "--ticket", "a.tik"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn cdn_to_cia_output_template_is_validated_when_parsed() {
+        let h = Harness::parse_from([
+            "bin",
+            "cdn-to-cia",
+            "./cdn",
+            "--output-template",
+            "{titleId} v{titleVersion}.{ext}",
+        ]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(
+            c.output_template.as_deref(),
+            Some("{titleId} v{titleVersion}.{ext}")
+        );
+        for args in [
+            &["--output-template", "{game_id}.{ext}"][..],
+            &[
+                "--output-template",
+                "{titleId}.{ext}",
+                "--name-from",
+                "title-id",
+            ][..],
+        ] {
+            let mut argv = vec!["bin", "cdn-to-cia", "./cdn"];
+            argv.extend_from_slice(args);
+            assert!(Harness::try_parse_from(argv).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn generate_cdn_ticket_title_version_must_fit_u16() {
+        let h = Harness::parse_from([
+            "bin",
+            "generate-cdn-ticket",
+            "./cdn",
+            "--title-version",
+            "1024",
+        ]);
+        let CtrCommands::GenerateCdnTicket(c) = h.cmd else {
+            panic!("expected GenerateCdnTicket");
+        };
+        assert_eq!(c.title_version, Some(1024));
+        assert_eq!(c.password, None);
+        assert_eq!(c.common_key_index, CommonKeyIndex::Auto);
+        for bad in ["65536", "-1", "v1024"] {
+            let result = Harness::try_parse_from([
+                "bin",
+                "generate-cdn-ticket",
+                "./cdn",
+                "--title-version",
+                bad,
+            ]);
+            assert!(result.is_err(), "{bad} should be rejected");
+        }
+    }
+
+    #[test]
+    fn generate_cdn_ticket_parses_common_key_index() {
+        let h = Harness::parse_from([
+            "bin",
+            "generate-cdn-ticket",
+            "./cdn",
+            "--common-key-index",
+            "1",
+        ]);
+        let CtrCommands::GenerateCdnTicket(c) = h.cmd else {
+            panic!("expected GenerateCdnTicket");
+        };
+        assert_eq!(c.common_key_index, CommonKeyIndex::Fixed(1));
+        for bad in ["6", "system"] {
+            let result = Harness::try_parse_from([
+                "bin",
+                "generate-cdn-ticket",
+                "./cdn",
+                "--common-key-index",
+                bad,
+            ]);
+            assert!(result.is_err(), "{bad} should be rejected");
+        }
+    }
+
+    #[test]
+    fn cdn_to_cia_ticket_keys_need_ensure_ticket() {
+        let key = "3c7faeff5b1d784d25011149f33f50a7";
+        let h = Harness::parse_from([
+            "bin",
+            "cdn-to-cia",
+            "./cdn",
+            "-T",
+            "--common-key-index",
+            "1",
+            "--title-key",
+            key,
+        ]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.common_key_index, CommonKeyIndex::Fixed(1));
+        assert_eq!(c.title_key, Some(parse_title_key(key).unwrap()));
+        assert!(
+            Harness::try_parse_from(["bin", "cdn-to-cia", "./cdn", "--title-key", key]).is_err()
+        );
+        assert!(
+            Harness::try_parse_from(["bin", "cdn-to-cia", "./cdn", "-T", "--title-key", "abcd"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn batch_parses_jobs() {
+        let h = Harness::parse_from(["bin", "batch", "titles.toml", "-j", "4"]);
+        let CtrCommands::Batch(c) = h.cmd else {
+            panic!("expected Batch");
+        };
+        assert_eq!(c.manifest, PathBuf::from("titles.toml"));
+        assert_eq!(c.jobs, 4);
+        assert!(Harness::try_parse_from(["bin", "batch", "titles.toml", "--jobs", "0"]).is_err());
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_content_chunk_concurrency() {
+        let h = Harness::parse_from(["bin", "cdn-to-cia", "./cdn"]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.content_chunk_concurrency, 1);
+        let h = Harness::parse_from([
+            "bin",
+            "cdn-to-cia",
+            "./cdn",
+            "--content-chunk-concurrency",
+            "4",
+        ]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.content_chunk_concurrency, 4);
+        assert!(
+            Harness::try_parse_from([
+                "bin",
+                "cdn-to-cia",
+                "./cdn",
+                "--content-chunk-concurrency",
+                "0"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn cdn_to_cia_parses_name_from() {
+        let h = Harness::parse_from([
+            "bin",
+            "cdn-to-cia",
+            "./cdn",
+            "--name-from",
+            "title-id-version",
+        ]);
+        let CtrCommands::CdnToCia(c) = h.cmd else {
+            panic!("expected CdnToCia");
+        };
+        assert_eq!(c.name_from, CdnNameFromArg::TitleIdVersion);
     }
 
     #[test]
@@ -1,6 +1,7 @@
 use crate::commands::ConflictPolicyArg;
 use crate::commands::info_command::InfoCommand;
 use clap::{Parser, Subcommand};
+use rom_converto_lib::util::validate_template;
 use std::path::PathBuf;
 
 /// Commands specific to DOL (GameCube) disc images
@@ -121,10 +122,10 @@ pub struct CompressDiscCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Zstandard compression level (signed, negative levels allowed). Defaults to 22 (archive quality). Lower values trade ratio for speed; Dolphin's documented suggestion is 5
@@ -189,10 +190,10 @@ pub struct DecompressDiscCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
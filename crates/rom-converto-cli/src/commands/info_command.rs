@@ -4,7 +4,7 @@ use std::path::PathBuf;
 /// Print metadata about a ROM or disc image: title, region, hashes and embedded artwork
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(
-    after_long_help = "EXAMPLES:\n  Single file: rom-converto ctr info game.cia\n  Save icon:   rom-converto ctr info game.cia --save-icon ./icons\n  As JSON:     rom-converto ctr info game.cia --json\n"
+    after_long_help = "EXAMPLES:\n  Single file: rom-converto ctr info game.cia\n  Save icon:   rom-converto ctr info game.cia --save-icon ./icons\n  As JSON:     rom-converto ctr info game.cia --json\n  DAT entry:   rom-converto ctr info game.cia --dat-xml game.dat\n"
 )]
 pub struct InfoCommand {
     /// File or directory to inspect
@@ -22,4 +22,8 @@ pub struct InfoCommand {
     /// Path to prod.keys for Switch, or a disc master key file for Wii U .wud/.wux info. Other consoles do not use it
     #[arg(long, value_name = "FILE")]
     pub keys: Option<PathBuf>,
+
+    /// Write a Logiqx DAT with one game entry for the file (name, size, CRC32, MD5 and SHA-1) to PATH, for building custom DATs. CTR only
+    #[arg(long = "dat-xml", value_name = "PATH")]
+    pub dat_xml: Option<PathBuf>,
 }
@@ -56,6 +56,10 @@ pub struct Cli {
     #[arg(short = 'v', long, global = true, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Hide progress bars. Without this, a non-terminal stderr (CI, cron, redirected logs) gets a plain line every 10% instead of a bar
+    #[arg(long = "no-progress", global = true)]
+    pub no_progress: bool,
+
     /// Skip the check for a newer release
     #[arg(long = "no-update-check", global = true)]
     pub no_update_check: bool,
@@ -76,10 +80,22 @@ pub struct Cli {
     #[arg(long = "debug-log", global = true, value_name = "FILE")]
     pub debug_log: Option<PathBuf>,
 
+    /// Write a JSON-lines record of every file started, finished, skipped or failed, plus warnings and errors, to FILE
+    #[arg(long = "log-file", global = true, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// After each conversion, print the CRC32, MD5 and SHA-1 of the output and add them to the JSON --report
+    #[arg(long = "hashes", global = true)]
+    pub hashes: bool,
+
     /// Skip the free-space preflight before writing output
     #[arg(long = "skip-space-check", global = true)]
     pub skip_space_check: bool,
 
+    /// Stop a recursive CTR or CHD compress batch at the first file that fails instead of logging it and moving on
+    #[arg(long = "fail-fast", global = true)]
+    pub fail_fast: bool,
+
     /// Ignore the persistent hash and verify cache for this run
     #[arg(long = "no-cache", global = true, conflicts_with = "rebuild_cache")]
     pub no_cache: bool,
@@ -87,6 +103,14 @@ pub struct Cli {
     /// Discard the persistent cache and rebuild it from this run
     #[arg(long = "rebuild-cache", global = true)]
     pub rebuild_cache: bool,
+
+    /// Read 3DS seeds from this seeddb.bin path or http(s) URL instead of ./seeddb.bin; gzip is decompressed
+    #[arg(long = "seeddb", global = true, value_name = "PATH_OR_URL")]
+    pub seeddb: Option<String>,
+
+    /// Retry a 3DS seed lookup on Nintendo's CDN up to N more times, with exponential backoff, when it fails on timeouts or server errors
+    #[arg(long = "seed-retries", global = true, value_name = "N", default_value_t = rom_converto_lib::nintendo::ctr::seed::DEFAULT_SEED_RETRIES)]
+    pub seed_retries: u32,
 }
 
 #[derive(Subcommand, Debug, Eq, PartialEq)]
@@ -180,4 +204,22 @@ mod tests {
             Cli::try_parse_from(["bin", "--no-cache", "--rebuild-cache", "hash", "game.iso"]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn seed_retries_defaults_and_parses() {
+        let cli = Cli::try_parse_from(["bin", "ctr", "decrypt", "game.cia"]).unwrap();
+        assert_eq!(cli.seed_retries, 2);
+        let cli = Cli::try_parse_from(["bin", "ctr", "decrypt", "game.cia", "--seed-retries", "0"])
+            .unwrap();
+        assert_eq!(cli.seed_retries, 0);
+    }
+
+    #[test]
+    fn fail_fast_is_global_and_off_by_default() {
+        let cli = Cli::try_parse_from(["bin", "ctr", "decrypt", "-R", "roms"]).unwrap();
+        assert!(!cli.fail_fast);
+        let cli =
+            Cli::try_parse_from(["bin", "ctr", "decrypt", "-R", "roms", "--fail-fast"]).unwrap();
+        assert!(cli.fail_fast);
+    }
 }
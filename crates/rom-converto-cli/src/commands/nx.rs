@@ -1,6 +1,7 @@
 use crate::commands::ConflictPolicyArg;
 use crate::commands::info_command::InfoCommand;
 use clap::{Parser, Subcommand};
+use rom_converto_lib::util::validate_template;
 use std::path::PathBuf;
 
 /// Commands specific to Nintendo Switch (NX) NSP/XCI containers
@@ -44,10 +45,10 @@ pub struct NxCompressCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// Zstd compression level. nsz default is 18; the maximum 22 needs over 1 GiB of RAM during decompression on the Switch
@@ -124,10 +125,10 @@ pub struct NxDecompressCommand {
     #[arg(long = "output-dir", value_name = "DIR", conflicts_with_all = ["output", "output_flag"])]
     pub output_dir: Option<PathBuf>,
 
-    /// Output path template applied per file. Tokens: {title}, {titleId}, {region},
-    /// {console}, {serial}, {ext}, {basename}. Resolves against extracted metadata;
-    /// missing tokens fall back to the input basename. Joined under --output-dir
-    #[arg(long = "output-template", value_name = "TEMPLATE", conflicts_with_all = ["output", "output_flag"])]
+    /// Output path template applied per file. Tokens: {title}, {titleId}, {titleVersion},
+    /// {region}, {console}, {serial}, {disc}, {ext}, {basename}. Resolves against extracted
+    /// metadata; missing tokens fall back to the input basename. Joined under --output-dir
+    #[arg(long = "output-template", value_name = "TEMPLATE", value_parser = validate_template, conflicts_with_all = ["output", "output_flag"])]
     pub output_template: Option<String>,
 
     /// What to do when an output already exists: error, overwrite, skip, or rename to a numbered sibling
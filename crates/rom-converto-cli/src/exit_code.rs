@@ -0,0 +1,357 @@
+//! Process exit codes, so scripts can tell failure categories apart.
+//!
+//! `main` walks the error chain from the outermost cause inward and exits
+//! with the code of the first cause that falls into a known category.
+//! Anything uncategorised keeps the generic code 1. Usage errors exit 2
+//! from clap before a command runs, and Ctrl-C exits 130.
+
+use rom_converto_lib::chd::error::ChdError;
+use rom_converto_lib::cso::CsoError;
+use rom_converto_lib::cue::error::CueError;
+use rom_converto_lib::dat::DatError;
+use rom_converto_lib::nintendo::ctr::error::NintendoCTRError;
+use rom_converto_lib::nintendo::ctr::z3ds::error::Z3dsError;
+use rom_converto_lib::nintendo::gcz::GczError;
+use rom_converto_lib::nintendo::nkit::NkitError;
+use rom_converto_lib::nintendo::nx::NxError;
+use rom_converto_lib::nintendo::rvz::RvzError;
+use rom_converto_lib::nintendo::wbfs::WbfsError;
+use rom_converto_lib::nintendo::wia::WiaError;
+use rom_converto_lib::nintendo::wup::WupError;
+use std::error::Error as StdError;
+
+use crate::github::error::GithubError;
+
+/// Broad failure category behind a non-zero exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCategory {
+    /// Any failure without a more specific category.
+    Failure,
+    /// A file is missing or could not be read or written.
+    Io,
+    /// An input is truncated, corrupt, or not the format it claims to be.
+    Malformed,
+    /// A key, seed, or ticket is missing or does not decrypt the input.
+    Crypto,
+    /// A network request failed or the server answered with an error.
+    Network,
+}
+
+impl ExitCategory {
+    /// Category of the first cause in `err`'s chain that has one.
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(categorize)
+            .unwrap_or(ExitCategory::Failure)
+    }
+
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCategory::Failure => 1,
+            ExitCategory::Io => 3,
+            ExitCategory::Malformed => 4,
+            ExitCategory::Crypto => 5,
+            ExitCategory::Network => 6,
+        }
+    }
+}
+
+// Wrapper variants are `#[error(transparent)]`, which hides the wrapped
+// error from `chain()`, so each enum recurses into them here.
+fn categorize(cause: &(dyn StdError + 'static)) -> Option<ExitCategory> {
+    use ExitCategory::{Crypto, Io, Malformed, Network};
+
+    if cause.is::<std::io::Error>() {
+        return Some(Io);
+    }
+    if let Some(err) = cause.downcast_ref::<binrw::Error>() {
+        return Some(binrw_category(err));
+    }
+    if cause.is::<reqwest::Error>() {
+        return Some(Network);
+    }
+    if let Some(err) = cause.downcast_ref::<GithubError>() {
+        return match err {
+            GithubError::NoSuccessStatusCode(..) => Some(Network),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<NintendoCTRError>() {
+        return match err {
+            NintendoCTRError::IoError(_)
+            | NintendoCTRError::NoTitleFileFound(_)
+            | NintendoCTRError::NoTmdFileFound(_)
+            | NintendoCTRError::TmdVersionNotFound { .. } => Some(Io),
+            NintendoCTRError::UnsupportedNcchFormatVersion(_)
+            | NintendoCTRError::BuiltCiaVerifyFailed { .. } => Some(Malformed),
+            NintendoCTRError::UnknownFixedKey(_)
+            | NintendoCTRError::UnknownCommonKeyIndex(_)
+            | NintendoCTRError::SuppliedSeedMismatch(_)
+            | NintendoCTRError::SeedVerificationFailed(_) => Some(Crypto),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<Z3dsError>() {
+        return match err {
+            Z3dsError::IoError(_) => Some(Io),
+            Z3dsError::BinRWError(e) => Some(binrw_category(e)),
+            Z3dsError::UnsupportedVersion(_) | Z3dsError::DecompressedSizeMismatch { .. } => {
+                Some(Malformed)
+            }
+            Z3dsError::InputNotDecrypted | Z3dsError::EncryptionStateUnknown => Some(Crypto),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<ChdError>() {
+        return match err {
+            ChdError::IoError(_) => Some(Io),
+            ChdError::BinRWError(e) => Some(binrw_category(e)),
+            ChdError::CueError(e) => categorize(e),
+            ChdError::NoFileReferencedInCueSheet
+            | ChdError::IsoNotSectorAligned { .. }
+            | ChdError::UnsupportedChdVersion
+            | ChdError::UnknownCompressionCodec(_)
+            | ChdError::HunkCrcMismatch { .. }
+            | ChdError::Sha1Mismatch { .. }
+            | ChdError::DecompressionSizeMismatch { .. }
+            | ChdError::InvalidTrackMetadata(_) => Some(Malformed),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<CueError>() {
+        return match err {
+            CueError::IoError(_) => Some(Io),
+            _ => Some(Malformed),
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<CsoError>() {
+        return match err {
+            CsoError::IoError(_) => Some(Io),
+            CsoError::BinRWError(e) => Some(binrw_category(e)),
+            CsoError::InvalidHeader(_)
+            | CsoError::BlockSizeMismatch { .. }
+            | CsoError::CorruptIndex(_) => Some(Malformed),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<DatError>() {
+        return match err {
+            DatError::IoError(_) => Some(Io),
+            DatError::HttpError(_)
+            | DatError::Transport(_)
+            | DatError::Api { .. }
+            | DatError::Truncated(_)
+            | DatError::BadResponse(_) => Some(Network),
+            DatError::Container(_) => Some(Malformed),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<NxError>() {
+        return match err {
+            NxError::IoError(_) => Some(Io),
+            NxError::BinRwError(e) => Some(binrw_category(e)),
+            NxError::InvalidNcaHeader
+            | NxError::UnsupportedNcaVersion(_)
+            | NxError::Pfs0BadMagic
+            | NxError::Hfs0BadMagic
+            | NxError::NczBadMagic(_)
+            | NxError::BlockSizeOutOfRange(_)
+            | NxError::IncompleteSection
+            | NxError::UnknownContainer
+            | NxError::InvalidXci
+            | NxError::InvalidTicket => Some(Malformed),
+            NxError::KeyfileMissing(_)
+            | NxError::KeyfileParse { .. }
+            | NxError::MissingKey { .. }
+            | NxError::InvalidKeyHex { .. }
+            | NxError::UnsupportedEncryption(_)
+            | NxError::AesError(_)
+            | NxError::MissingTicket(_) => Some(Crypto),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<RvzError>() {
+        return rvz_category(err);
+    }
+    if let Some(err) = cause.downcast_ref::<WiaError>() {
+        return wia_category(err);
+    }
+    if let Some(err) = cause.downcast_ref::<WbfsError>() {
+        return wbfs_category(err);
+    }
+    if let Some(err) = cause.downcast_ref::<GczError>() {
+        return gcz_category(err);
+    }
+    if let Some(err) = cause.downcast_ref::<NkitError>() {
+        return match err {
+            NkitError::Io(_) => Some(Io),
+            NkitError::Gcz(e) => gcz_category(e),
+            NkitError::InvalidHeader(_)
+            | NkitError::InvalidGap(_)
+            | NkitError::CrcMismatch { .. } => Some(Malformed),
+            _ => None,
+        };
+    }
+    if let Some(err) = cause.downcast_ref::<WupError>() {
+        return match err {
+            WupError::IoError(_)
+            | WupError::MissingRequiredFile(_)
+            | WupError::ContentNotFound { .. }
+            | WupError::DiscKeyMissing(_) => Some(Io),
+            WupError::BinRwError(e) => Some(binrw_category(e)),
+            WupError::UnrecognizedTitleDirectory(_)
+            | WupError::InvalidAppXml(_)
+            | WupError::InvalidTicket
+            | WupError::InvalidTmd
+            | WupError::InvalidFst
+            | WupError::UnsupportedDiscFormat(_)
+            | WupError::NoGamePartitionFound
+            | WupError::DiscTruncated { .. }
+            | WupError::InvalidPartitionHeader
+            | WupError::InvalidZArchive(_) => Some(Malformed),
+            WupError::InvalidCommonKeyIndex(_)
+            | WupError::TitleKeyDecryptFailed
+            | WupError::UnsupportedContentMode
+            | WupError::AesError(_)
+            | WupError::DiscKeyMalformed(_)
+            | WupError::DiscKeyWrong => Some(Crypto),
+            _ => None,
+        };
+    }
+    None
+}
+
+fn binrw_category(err: &binrw::Error) -> ExitCategory {
+    match err {
+        binrw::Error::Io(_) => ExitCategory::Io,
+        _ => ExitCategory::Malformed,
+    }
+}
+
+fn rvz_category(err: &RvzError) -> Option<ExitCategory> {
+    use ExitCategory::{Crypto, Io, Malformed};
+    match err {
+        RvzError::IoError(_) => Some(Io),
+        RvzError::BinRWError(e) => Some(binrw_category(e)),
+        RvzError::Wbfs(e) => wbfs_category(e),
+        RvzError::Gcz(e) => gcz_category(e),
+        RvzError::Wia(e) => wia_category(e),
+        RvzError::Nkit(e) => categorize(e),
+        RvzError::InvalidMagic(_)
+        | RvzError::UnsupportedVersion(_)
+        | RvzError::UnsupportedCompression(_)
+        | RvzError::UnsupportedDiscType(_)
+        | RvzError::HeaderHashMismatch
+        | RvzError::DiscHashMismatch
+        | RvzError::PartitionHashMismatch
+        | RvzError::DecompressedSizeMismatch { .. }
+        | RvzError::UnrecognizedDisc => Some(Malformed),
+        RvzError::UnknownCommonKeyIndex(_) | RvzError::AesError(_) => Some(Crypto),
+        _ => None,
+    }
+}
+
+fn wia_category(err: &WiaError) -> Option<ExitCategory> {
+    use ExitCategory::{Io, Malformed};
+    match err {
+        WiaError::Io(_) => Some(Io),
+        WiaError::BinRw(e) => Some(binrw_category(e)),
+        WiaError::Rvz(e) => rvz_category(e),
+        WiaError::InvalidMagic(_)
+        | WiaError::UnsupportedVersion { .. }
+        | WiaError::UnsupportedCompression(_)
+        | WiaError::InvalidHeader(_)
+        | WiaError::HashChainMismatch(_)
+        | WiaError::Decode(_) => Some(Malformed),
+        _ => None,
+    }
+}
+
+fn wbfs_category(err: &WbfsError) -> Option<ExitCategory> {
+    use ExitCategory::{Io, Malformed};
+    match err {
+        WbfsError::Io(_) => Some(Io),
+        WbfsError::InvalidMagic(_)
+        | WbfsError::UnsupportedHdSectorSize(_)
+        | WbfsError::UnsupportedWbfsSectorSize(_)
+        | WbfsError::NoDiscs
+        | WbfsError::UnrecognizedDisc => Some(Malformed),
+        _ => None,
+    }
+}
+
+fn gcz_category(err: &GczError) -> Option<ExitCategory> {
+    use ExitCategory::{Io, Malformed};
+    match err {
+        GczError::Io(_) => Some(Io),
+        GczError::BinRw(e) => Some(binrw_category(e)),
+        GczError::InvalidMagic(_)
+        | GczError::InvalidHeader(_)
+        | GczError::BlockHashMismatch { .. }
+        | GczError::Inflate { .. } => Some(Malformed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn uncategorised_errors_keep_the_generic_code() {
+        let err = anyhow::anyhow!("something went wrong");
+        assert_eq!(ExitCategory::of(&err), ExitCategory::Failure);
+        assert_eq!(ExitCategory::of(&err).code(), 1);
+    }
+
+    #[test]
+    fn io_errors_under_context_map_to_io() {
+        let err = Err::<(), _>(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context("cannot open game.cia")
+            .unwrap_err();
+        assert_eq!(ExitCategory::of(&err).code(), 3);
+    }
+
+    #[test]
+    fn transparent_wrappers_are_unwrapped() {
+        let inner = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let err = anyhow::Error::from(RvzError::Wia(Box::new(WiaError::Io(inner))));
+        assert_eq!(ExitCategory::of(&err), ExitCategory::Io);
+    }
+
+    #[test]
+    fn each_category_has_a_distinct_code() {
+        let cases = [
+            (
+                anyhow::Error::from(ChdError::UnsupportedChdVersion),
+                ExitCategory::Malformed,
+            ),
+            (
+                anyhow::Error::from(NintendoCTRError::SeedVerificationFailed(
+                    "0004000000155400".into(),
+                )),
+                ExitCategory::Crypto,
+            ),
+            (
+                anyhow::Error::from(DatError::Transport("timed out".into())),
+                ExitCategory::Network,
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(ExitCategory::of(&err), expected, "{err}");
+        }
+        let mut codes: Vec<i32> = [
+            ExitCategory::Failure,
+            ExitCategory::Io,
+            ExitCategory::Malformed,
+            ExitCategory::Crypto,
+            ExitCategory::Network,
+        ]
+        .map(ExitCategory::code)
+        .to_vec();
+        codes.dedup();
+        assert_eq!(codes.len(), 5);
+        assert!(!codes.contains(&2) && !codes.contains(&130));
+    }
+}
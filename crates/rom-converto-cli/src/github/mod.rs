@@ -1,5 +1,5 @@
 //! Minimal GitHub Releases API client used by the self-update flow.
 
 pub mod api;
-mod error;
+pub(crate) mod error;
 mod model;
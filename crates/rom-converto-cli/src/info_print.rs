@@ -116,6 +116,9 @@ fn render_chd(info: &rom_converto_lib::info::ChdInfo) -> String {
     if let Some(vers) = &info.version_string {
         t.push("chdman version", vers.clone());
     }
+    if let Some(comment) = &info.comment {
+        t.push("Comment", comment.clone());
+    }
     if let Some(dvd) = &info.dvd {
         let layer = match dvd.layer_class {
             rom_converto_lib::chd::info::DvdLayerClass::SingleLayer => "single-layer (4.7 GB)",
@@ -200,6 +203,17 @@ fn render_ctr(info: &rom_converto_lib::info::CtrInfo) -> String {
             t.push("Derived KeyY", keyy.clone());
         }
     }
+    if let Some(ticket) = &info.ticket {
+        t.push("Ticket ID", ticket.ticket_id.clone());
+        t.push("Console ID", format!("0x{:08X}", ticket.console_id));
+        t.push("License type", format!("{}", ticket.license_type));
+        t.push(
+            "eShop account ID",
+            format!("0x{:08X}", ticket.eshop_account_id),
+        );
+        t.push("Audit", format!("{}", ticket.audit));
+        t.push("Limits", format_ticket_limits(&ticket.limits));
+    }
     let mut out = t.render();
 
     if let Some(s) = &info.smdh {
@@ -244,6 +258,291 @@ fn render_ctr(info: &rom_converto_lib::info::CtrInfo) -> String {
     out
 }
 
+fn format_ticket_limits(
+    limits: &[rom_converto_lib::nintendo::ctr::info::CtrTicketLimit],
+) -> String {
+    if limits.is_empty() {
+        return "none".to_string();
+    }
+    limits
+        .iter()
+        .map(|limit| match limit.kind.as_str() {
+            "play_minutes" => format!("{} minutes of play", limit.value),
+            "launch_count" => format!("{} launches", limit.value),
+            _ => format!("type {} = {}", limit.limit_type, limit.value),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn print_ncch_crypto(
+    report: &rom_converto_lib::nintendo::ctr::inspect::NcchCryptoReport,
+    json: bool,
+) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+    print!("{}", render_ncch_crypto(report));
+    Ok(())
+}
+
+fn render_ncch_crypto(
+    report: &rom_converto_lib::nintendo::ctr::inspect::NcchCryptoReport,
+) -> String {
+    let mut out = String::new();
+    for (i, part) in report.partitions.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let mut t = KeyValueTable::new();
+        t.push("Partition", part.partition.clone());
+        t.push("Title ID", part.title_id.clone());
+        if let Some(title_key) = part.title_key_encrypted {
+            t.push(
+                "Title key",
+                if title_key { "encrypted" } else { "decrypted" },
+            );
+        }
+        t.push("Encrypted", if part.encrypted { "yes" } else { "no" });
+        t.push("Crypto", part.method.to_string());
+        if part.seed_crypto {
+            let seed = match part.seed_found {
+                Some(true) => "yes (seed in local seeddb)",
+                Some(false) => "yes (seed not found)",
+                None => "yes",
+            };
+            t.push("Seed crypto", seed);
+        }
+        out.push_str(&t.render());
+    }
+    out
+}
+
+pub fn print_cia_keys(
+    dump: &rom_converto_lib::nintendo::ctr::inspect::CiaKeyDump,
+    json: bool,
+) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(dump)?);
+        return Ok(());
+    }
+    print!("{}", render_cia_keys(dump));
+    Ok(())
+}
+
+fn render_cia_keys(dump: &rom_converto_lib::nintendo::ctr::inspect::CiaKeyDump) -> String {
+    let mut t = KeyValueTable::new();
+    t.push("Title ID", dump.title_id.clone());
+    t.push("Common key", dump.common_key_index.to_string());
+    t.push("Title key", dump.title_key.clone());
+    let mut out = t.render();
+    for content in &dump.contents {
+        out.push('\n');
+        let mut t = KeyValueTable::new();
+        t.push("Content", content.content.clone());
+        t.push("Crypto", content.method.to_string());
+        t.push("KeyY", content.key_y.clone());
+        if let Some(seed_key_y) = &content.seed_key_y {
+            t.push("Seed KeyY", seed_key_y.clone());
+        }
+        out.push_str(&t.render());
+    }
+    out
+}
+
+pub fn print_romfs_listing(
+    listing: &rom_converto_lib::nintendo::ctr::romfs::RomfsListing,
+    json: bool,
+) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(listing)?);
+        return Ok(());
+    }
+    print!("{}", render_romfs_listing(listing));
+    Ok(())
+}
+
+fn render_romfs_listing(listing: &rom_converto_lib::nintendo::ctr::romfs::RomfsListing) -> String {
+    let width = listing
+        .files
+        .iter()
+        .map(|f| f.size.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let mut out = String::new();
+    for file in &listing.files {
+        let _ = writeln!(out, "{:>width$}  {}", file.size, file.path);
+    }
+    let _ = writeln!(
+        out,
+        "{} files, {} bytes",
+        listing.files.len(),
+        listing.total_size
+    );
+    out
+}
+
+pub fn print_cd_layout(layout: &rom_converto_lib::chd::ChdCdLayout) {
+    print!("{}", render_cd_layout(layout));
+}
+
+fn render_cd_layout(layout: &rom_converto_lib::chd::ChdCdLayout) -> String {
+    let header = ["Track", "Type", "CHD type", "Start LBA", "Frames", "File"];
+    let rows: Vec<[String; 6]> = layout
+        .tracks
+        .iter()
+        .map(|t| {
+            [
+                format!("{:02}", t.number),
+                t.track_type.to_string(),
+                t.chd_type.to_string(),
+                t.start_lba.to_string(),
+                t.frames.to_string(),
+                t.file.clone(),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut out = String::new();
+    let mut push_row = |cells: [&str; 6]| {
+        let line = format!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {:>w3$}  {:>w4$}  {}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            cells[4],
+            cells[5],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+            w4 = widths[4],
+        );
+        let _ = writeln!(out, "{}", line.trim_end());
+    };
+    push_row(header);
+    for row in &rows {
+        push_row(row.each_ref().map(String::as_str));
+    }
+    out
+}
+
+pub fn print_chd_benchmark(bench: &rom_converto_lib::chd::benchmark::ChdBenchmark) {
+    print!("{}", render_chd_benchmark(bench));
+}
+
+fn render_chd_benchmark(bench: &rom_converto_lib::chd::benchmark::ChdBenchmark) -> String {
+    let header = ["Codecs", "Mode", "Bytes", "Ratio", "Codec time"];
+    let rows: Vec<[String; 5]> = bench
+        .rows
+        .iter()
+        .map(|row| {
+            [
+                row.codecs.to_string(),
+                row.note.to_string(),
+                row.compressed_bytes.to_string(),
+                format!("{:.1}%", row.ratio(bench.raw_bytes)),
+                format!("{:.3}s", row.codec_time.as_secs_f64()),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut out = format!(
+        "{} hunks sampled ({} audio), {} bytes raw\n",
+        bench.hunks, bench.audio_hunks, bench.raw_bytes
+    );
+    let mut push_row = |cells: [&str; 5]| {
+        let _ = writeln!(
+            out,
+            "{:<w0$}  {:<w1$}  {:>w2$}  {:>w3$}  {:>w4$}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            cells[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+            w4 = widths[4],
+        );
+    };
+    push_row(header);
+    for row in &rows {
+        push_row(row.each_ref().map(String::as_str));
+    }
+    out
+}
+
+pub fn print_chd_map(map: &[rom_converto_lib::chd::info::ChdMapEntry]) {
+    print!("{}", render_chd_map(map));
+}
+
+fn render_chd_map(map: &[rom_converto_lib::chd::info::ChdMapEntry]) -> String {
+    use rom_converto_lib::chd::info::ChdHunkStorage;
+    let header = ["Hunk", "Type", "Length", "Offset", "CRC16"];
+    let rows: Vec<[String; 5]> = map
+        .iter()
+        .map(|entry| {
+            let (offset, crc16) = match entry.storage {
+                ChdHunkStorage::SelfRef => (format!("hunk {}", entry.offset), "-".to_string()),
+                ChdHunkStorage::ParentRef => (format!("unit {}", entry.offset), "-".to_string()),
+                _ => (
+                    format!("0x{:X}", entry.offset),
+                    format!("{:04x}", entry.crc16),
+                ),
+            };
+            [
+                entry.hunk.to_string(),
+                entry.storage.to_string(),
+                entry.length.to_string(),
+                offset,
+                crc16,
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut out = String::new();
+    let mut push_row = |cells: [&str; 5]| {
+        let _ = writeln!(
+            out,
+            "{:>w0$}  {:<w1$}  {:>w2$}  {:>w3$}  {:>w4$}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            cells[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+            w4 = widths[4],
+        );
+    };
+    push_row(header);
+    for row in &rows {
+        push_row(row.each_ref().map(String::as_str));
+    }
+    out
+}
+
 fn render_dol(info: &rom_converto_lib::info::DolInfo) -> String {
     let mut t = KeyValueTable::new();
     t.push("Format", format!("GameCube ({})", info.container));
@@ -656,6 +955,40 @@ fn render_nx(info: &rom_converto_lib::info::NxInfo) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn ctr_ticket_limits_render_in_words() {
+        use rom_converto_lib::info::CtrInfo;
+        use rom_converto_lib::nintendo::ctr::info::{CtrTicketInfo, CtrTicketLimit};
+
+        let info = CtrInfo {
+            ticket: Some(CtrTicketInfo {
+                ticket_id: "0004000000123400".to_string(),
+                eshop_account_id: 0x1234,
+                limits: vec![
+                    CtrTicketLimit {
+                        limit_type: 1,
+                        kind: "play_minutes".to_string(),
+                        value: 60,
+                    },
+                    CtrTicketLimit {
+                        limit_type: 4,
+                        kind: "launch_count".to_string(),
+                        value: 30,
+                    },
+                ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let out = render_ctr(&info);
+        assert!(
+            out.contains("Limits:") && out.contains("60 minutes of play, 30 launches"),
+            "{out}"
+        );
+        assert!(out.contains("0x00001234"), "{out}");
+        assert!(!render_ctr(&CtrInfo::default()).contains("Ticket ID"));
+    }
+
     #[test]
     fn table_aligns_to_longest_key() {
         let mut t = KeyValueTable::new();
@@ -682,4 +1015,151 @@ mod tests {
         assert!(out.contains("CHD v5"));
         assert!(out.contains("42"));
     }
+
+    #[test]
+    fn render_ncch_crypto_lists_each_partition() {
+        use rom_converto_lib::nintendo::ctr::info::CtrFormat;
+        use rom_converto_lib::nintendo::ctr::inspect::{
+            NcchCryptoInfo, NcchCryptoMethod, NcchCryptoReport,
+        };
+        let part = |name: &str, method| NcchCryptoInfo {
+            partition: name.to_string(),
+            title_id: "0004000000055D00".to_string(),
+            title_key_encrypted: None,
+            encrypted: method != NcchCryptoMethod::None,
+            method,
+            seed_crypto: false,
+            seed_found: None,
+        };
+        let report = NcchCryptoReport {
+            format: CtrFormat::Ncsd,
+            partitions: vec![
+                part("Main", NcchCryptoMethod::Keyslot { slot: 0x25 }),
+                part("Manual", NcchCryptoMethod::None),
+            ],
+        };
+        let out = render_ncch_crypto(&report);
+        assert!(out.contains("keyslot 0x2C + 0x25"));
+        assert!(out.contains("Manual"));
+        assert!(!out.contains("Title key"));
+    }
+
+    #[test]
+    fn render_romfs_listing_aligns_sizes_and_totals() {
+        use rom_converto_lib::nintendo::ctr::romfs::{RomfsFileEntry, RomfsListing};
+        let listing = RomfsListing {
+            files: vec![
+                RomfsFileEntry {
+                    path: "/a.txt".into(),
+                    size: 5,
+                },
+                RomfsFileEntry {
+                    path: "/sub/b.bin".into(),
+                    size: 1024,
+                },
+            ],
+            total_size: 1029,
+        };
+        let out = render_romfs_listing(&listing);
+        assert_eq!(out, "   5  /a.txt\n1024  /sub/b.bin\n2 files, 1029 bytes\n");
+    }
+
+    #[test]
+    fn render_cd_layout_aligns_columns() {
+        use rom_converto_lib::chd::{ChdCdLayout, ChdCdTrackLayout};
+        let layout = ChdCdLayout {
+            files: vec!["game.bin".into()],
+            tracks: vec![
+                ChdCdTrackLayout {
+                    number: 1,
+                    track_type: "MODE1/2352",
+                    chd_type: "MODE1_RAW",
+                    start_lba: 0,
+                    frames: 1200,
+                    file: "game.bin".into(),
+                },
+                ChdCdTrackLayout {
+                    number: 2,
+                    track_type: "AUDIO",
+                    chd_type: "AUDIO",
+                    start_lba: 1200,
+                    frames: 75,
+                    file: "game.bin".into(),
+                },
+            ],
+        };
+        assert_eq!(
+            render_cd_layout(&layout),
+            "Track  Type        CHD type   Start LBA  Frames  File\n\
+             01     MODE1/2352  MODE1_RAW          0    1200  game.bin\n\
+             02     AUDIO       AUDIO           1200      75  game.bin\n"
+        );
+    }
+
+    #[test]
+    fn render_chd_benchmark_aligns_columns() {
+        use rom_converto_lib::chd::benchmark::{ChdBenchmark, ChdBenchmarkRow};
+        use std::time::Duration;
+        let bench = ChdBenchmark {
+            hunks: 2,
+            audio_hunks: 1,
+            raw_bytes: 1000,
+            rows: vec![
+                ChdBenchmarkRow {
+                    codecs: "cdlz+cdzl+cdfl",
+                    note: "default",
+                    compressed_bytes: 500,
+                    codec_time: Duration::from_millis(1500),
+                },
+                ChdBenchmarkRow {
+                    codecs: "cdzl",
+                    note: "zlib only",
+                    compressed_bytes: 640,
+                    codec_time: Duration::from_millis(250),
+                },
+            ],
+        };
+        assert_eq!(
+            render_chd_benchmark(&bench),
+            "2 hunks sampled (1 audio), 1000 bytes raw\n\
+             Codecs          Mode       Bytes  Ratio  Codec time\n\
+             cdlz+cdzl+cdfl  default      500  50.0%      1.500s\n\
+             cdzl            zlib only    640  64.0%      0.250s\n"
+        );
+    }
+
+    #[test]
+    fn render_chd_map_aligns_columns_and_names_references() {
+        use rom_converto_lib::chd::info::{ChdHunkStorage, ChdMapEntry};
+        let entry = |hunk, storage, length, offset, crc16| ChdMapEntry {
+            hunk,
+            storage,
+            length,
+            offset,
+            crc16,
+        };
+        let map = [
+            entry(
+                0,
+                ChdHunkStorage::Codec {
+                    slot: 0,
+                    name: Some("cdlz".into()),
+                },
+                1200,
+                0x1A4,
+                0xBEEF,
+            ),
+            entry(1, ChdHunkStorage::Uncompressed, 19584, 0x654, 0x0102),
+            entry(2, ChdHunkStorage::SelfRef, 0, 0, 0),
+            entry(3, ChdHunkStorage::ParentRef, 0, 24, 0),
+        ];
+        assert_eq!(
+            render_chd_map(&map),
+            "Hunk  Type    Length   Offset  CRC16\n   \
+                0  cdlz      1200    0x1A4   beef\n   \
+                1  none     19584    0x654   0102\n   \
+                2  self         0   hunk 0      -\n   \
+                3  parent       0  unit 24      -\n"
+        );
+    }
 }
@@ -1,7 +1,14 @@
+use log::kv::{Key, Value, VisitSource};
+use std::ffi::OsStr;
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_DIM: &str = "\x1b[2m";
 
 pub fn resolve_log_levels(quiet: bool, verbose: u8) -> (log::LevelFilter, log::LevelFilter) {
     use log::LevelFilter::{Debug, Info, Trace, Warn};
@@ -16,28 +23,127 @@ pub fn resolve_log_levels(quiet: bool, verbose: u8) -> (log::LevelFilter, log::L
     }
 }
 
-pub struct DualLogger {
+/// Whether console lines get color and a timestamp: only when stderr is
+/// a terminal and `NO_COLOR` is unset or empty (see no-color.org).
+pub fn console_is_styled(stderr_is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+    stderr_is_terminal && no_color.is_none_or(|value| value.is_empty())
+}
+
+fn level_color(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "\x1b[1;31m",
+        log::Level::Warn => "\x1b[1;33m",
+        log::Level::Info => "\x1b[32m",
+        log::Level::Debug => "\x1b[34m",
+        log::Level::Trace => "\x1b[35m",
+    }
+}
+
+/// Console line at default verbosity: info lines are user-facing
+/// summaries and print bare, other levels keep a label that is colored
+/// when `styled`.
+pub fn summary_line(level: log::Level, message: impl Display, styled: bool) -> String {
+    match (level, styled) {
+        (log::Level::Info, _) => message.to_string(),
+        (level, true) => format!("{}[{level}]{ANSI_RESET} {message}", level_color(level)),
+        (level, false) => format!("[{level}] {message}"),
+    }
+}
+
+/// Verbose console line for a terminal: a dim `MM:SS.mmm` run time, the
+/// level colored and padded so messages line up, then the target.
+pub fn styled_line(
+    level: log::Level,
+    target: &str,
+    message: impl Display,
+    elapsed: Duration,
+) -> String {
+    let millis = elapsed.as_millis();
+    let (minutes, seconds, millis) = (millis / 60_000, (millis / 1000) % 60, millis % 1000);
+    format!(
+        "{ANSI_DIM}{minutes:02}:{seconds:02}.{millis:03}{ANSI_RESET} {}{:<5}{ANSI_RESET} {ANSI_DIM}{target}{ANSI_RESET} {message}",
+        level_color(level),
+        level.as_str(),
+    )
+}
+
+/// Log target for structured per-file events (start, finish, skip,
+/// error). Hidden from the console, which already prints its own
+/// summaries, and written only to the `--log-file` sink.
+pub const EVENT_TARGET: &str = "rom_converto::event";
+
+/// Record a structured event for `file`. No-op unless `--log-file` is set.
+pub fn event(event: &str, file: &Path, message: std::fmt::Arguments) {
+    log::info!(target: EVENT_TARGET, event, file:% = file.display(); "{message}");
+}
+
+/// Record a `skip` event for `input`, naming the existing `output` that
+/// caused it in its own field.
+pub fn skip_event(input: &Path, output: &Path) {
+    log::info!(
+        target: EVENT_TARGET,
+        event = "skip",
+        file:% = input.display(),
+        output:% = output.display();
+        "Skipped, output exists"
+    );
+}
+
+/// Record a `start` event for `file` and return the timer its summary
+/// line measures from.
+pub fn start_event(file: &Path) -> Instant {
+    event("start", file, format_args!("Processing {}", file.display()));
+    Instant::now()
+}
+
+/// Console logger plus the optional `--debug-log` (plain text, every
+/// level) and `--log-file` (JSON lines, info and above) sinks.
+pub struct TeeLogger {
     console: env_logger::Logger,
-    file: Mutex<BufWriter<File>>,
+    debug_file: Option<Mutex<BufWriter<File>>>,
+    json_file: Option<Mutex<BufWriter<File>>>,
 }
 
-impl DualLogger {
-    pub fn new(console: env_logger::Logger, file: BufWriter<File>) -> Self {
+impl TeeLogger {
+    pub fn new(
+        console: env_logger::Logger,
+        debug_file: Option<BufWriter<File>>,
+        json_file: Option<BufWriter<File>>,
+    ) -> Self {
         Self {
             console,
-            file: Mutex::new(file),
+            debug_file: debug_file.map(Mutex::new),
+            json_file: json_file.map(Mutex::new),
+        }
+    }
+
+    /// Level the global `log` filter must admit for every sink to see
+    /// what it records.
+    pub fn max_level(&self) -> log::LevelFilter {
+        if self.debug_file.is_some() {
+            log::LevelFilter::Trace
+        } else if self.json_file.is_some() {
+            self.console.filter().max(log::LevelFilter::Info)
+        } else {
+            self.console.filter()
         }
     }
 }
 
-impl log::Log for DualLogger {
+impl log::Log for TeeLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        self.console.enabled(metadata) || metadata.level() <= log::Level::Trace
+        self.console.enabled(metadata)
+            || self.debug_file.is_some()
+            || (self.json_file.is_some() && metadata.level() <= log::Level::Info)
     }
 
     fn log(&self, record: &log::Record) {
-        self.console.log(record);
-        if let Ok(mut guard) = self.file.lock() {
+        if record.target() != EVENT_TARGET {
+            self.console.log(record);
+        }
+        if let Some(file) = &self.debug_file
+            && let Ok(mut guard) = file.lock()
+        {
             let _ = writeln!(
                 guard,
                 "[{}] [{}] {}: {}",
@@ -47,16 +153,71 @@ impl log::Log for DualLogger {
                 record.args()
             );
         }
+        if record.level() <= log::Level::Info
+            && let Some(file) = &self.json_file
+            && let Ok(mut guard) = file.lock()
+        {
+            let _ = writeln!(guard, "{}", json_line(record, &format_timestamp()));
+        }
     }
 
     fn flush(&self) {
         self.console.flush();
-        if let Ok(mut guard) = self.file.lock() {
-            let _ = guard.flush();
+        for file in [&self.debug_file, &self.json_file].into_iter().flatten() {
+            if let Ok(mut guard) = file.lock() {
+                let _ = guard.flush();
+            }
         }
     }
 }
 
+/// `event`, `file` and `output` key-values picked off a log record.
+#[derive(Default)]
+struct EventFields {
+    event: Option<String>,
+    file: Option<String>,
+    output: Option<String>,
+}
+
+impl<'kvs> VisitSource<'kvs> for EventFields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        match key.as_str() {
+            "event" => self.event = Some(value.to_string()),
+            "file" => self.file = Some(value.to_string()),
+            "output" => self.output = Some(value.to_string()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// One `--log-file` record. Lines without an explicit `event` key are
+/// classified by level, so every warning and error still lands in the
+/// file with a stable event name.
+fn json_line(record: &log::Record, timestamp: &str) -> String {
+    let mut fields = EventFields::default();
+    let _ = record.key_values().visit(&mut fields);
+    let event = fields.event.unwrap_or_else(|| {
+        match record.level() {
+            log::Level::Error => "error",
+            log::Level::Warn => "warning",
+            _ => "message",
+        }
+        .to_string()
+    });
+    let mut line = serde_json::json!({
+        "timestamp": timestamp,
+        "level": record.level().as_str().to_ascii_lowercase(),
+        "event": event,
+        "file": fields.file,
+        "message": record.args().to_string(),
+    });
+    if let Some(output) = fields.output {
+        line["output"] = output.into();
+    }
+    line.to_string()
+}
+
 fn format_timestamp() -> String {
     let secs = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -110,6 +271,97 @@ mod tests {
         assert_eq!(resolve_log_levels(false, 2), (Trace, Warn));
     }
 
+    #[test]
+    fn styling_needs_a_terminal_and_no_no_color() {
+        assert!(console_is_styled(true, None));
+        assert!(console_is_styled(true, Some(OsStr::new(""))));
+        assert!(!console_is_styled(true, Some(OsStr::new("1"))));
+        assert!(!console_is_styled(false, None));
+    }
+
+    #[test]
+    fn summary_lines_keep_info_bare() {
+        assert_eq!(summary_line(log::Level::Info, "done", true), "done");
+        assert_eq!(
+            summary_line(log::Level::Warn, "careful", false),
+            "[WARN] careful"
+        );
+        assert_eq!(
+            summary_line(log::Level::Error, "failed", true),
+            "\x1b[1;31m[ERROR]\x1b[0m failed"
+        );
+    }
+
+    #[test]
+    fn styled_lines_align_levels_behind_the_run_time() {
+        let line = styled_line(
+            log::Level::Info,
+            "rom_converto_lib::chd",
+            "hello",
+            Duration::from_millis(65_432),
+        );
+        assert_eq!(
+            line,
+            "\x1b[2m01:05.432\x1b[0m \x1b[32mINFO \x1b[0m \x1b[2mrom_converto_lib::chd\x1b[0m hello"
+        );
+        let warn = styled_line(log::Level::Warn, "t", "x", Duration::ZERO);
+        assert!(warn.contains("\x1b[1;33mWARN \x1b[0m"));
+    }
+
+    fn parsed_line(record: &log::Record) -> serde_json::Value {
+        serde_json::from_str(&json_line(record, "2026-01-01T00:00:00Z")).unwrap()
+    }
+
+    #[test]
+    fn json_line_carries_event_and_file_key_values() {
+        let kvs = [("event", "skip"), ("file", "roms/game.cia")];
+        let line = parsed_line(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .target(EVENT_TARGET)
+                .args(format_args!("Skipped, output exists"))
+                .key_values(&kvs)
+                .build(),
+        );
+        assert_eq!(line["timestamp"], "2026-01-01T00:00:00Z");
+        assert_eq!(line["level"], "info");
+        assert_eq!(line["event"], "skip");
+        assert_eq!(line["file"], "roms/game.cia");
+        assert_eq!(line["message"], "Skipped, output exists");
+        assert!(line.get("output").is_none());
+    }
+
+    #[test]
+    fn json_line_carries_the_skipped_output_in_its_own_field() {
+        let kvs = [
+            ("event", "skip"),
+            ("file", "roms/game.cia"),
+            ("output", "out/game.cci"),
+        ];
+        let line = parsed_line(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .target(EVENT_TARGET)
+                .args(format_args!("Skipped, output exists"))
+                .key_values(&kvs)
+                .build(),
+        );
+        assert_eq!(line["file"], "roms/game.cia");
+        assert_eq!(line["output"], "out/game.cci");
+    }
+
+    #[test]
+    fn json_line_falls_back_to_the_level_for_plain_records() {
+        let line = parsed_line(
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .args(format_args!("title ID mismatch"))
+                .build(),
+        );
+        assert_eq!(line["event"], "warning");
+        assert!(line["file"].is_null());
+    }
+
     #[test]
     fn triple_v_and_above_opens_global_trace() {
         assert_eq!(resolve_log_levels(false, 3), (Trace, Trace));
@@ -3,13 +3,14 @@
 //! adds argument parsing, progress reporting, batch/dry-run orchestration,
 //! and config file resolution around those calls.
 
-use crate::commands::chd::ChdCommands;
+use crate::commands::chd::{AudioEndianArg, ChdCommands, SingleCodecArg};
 use crate::commands::completions::ShellCompletionsCommand;
 use crate::commands::cso::{CsoCommands, CsoFormatArg};
-use crate::commands::ctr::CtrCommands;
+use crate::commands::ctr::{CdnNameFromArg, CtrCommands};
 use crate::commands::cue::CueCommands;
 use crate::commands::dat::DatCommands;
 use crate::commands::dol::DolCommands;
+use crate::commands::info_command::InfoCommand;
 use crate::commands::nx::NxCommands;
 use crate::commands::playlist::PlaylistModeArg;
 use crate::commands::rvl::RvlCommands;
@@ -19,16 +20,20 @@ use crate::github::api::GithubApi;
 use crate::updater::{check_for_new_version_and_notify, cleanup_old_executable, self_update};
 use crate::util::{
     IndicatifProgress, TotalProgress, WriteDecision, ensure_input_exists, policy_of,
-    resolve_output, resolve_output_dir, resolve_policy,
+    resolve_output, resolve_output_dir, resolve_policy, stdin, stdout,
 };
 use anyhow::{Context, Result};
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, generate_to};
 use indicatif::MultiProgress;
 use indicatif_log_bridge::LogWrapper;
+use rom_converto_lib::cd::SECTOR_SIZE;
+use rom_converto_lib::chd::compression::ChdCodec;
+use rom_converto_lib::chd::meta::{ChdMetaEdit, ChdTrackEdit, edit_chd_metadata};
 use rom_converto_lib::chd::{
-    ChdDvdOptions, DiscMode, convert_disc_to_chd_cancellable, extract_from_chd_cancellable,
-    verify_chd, verify_chd_batch,
+    AudioEndian, ChdDvdOptions, ChdExtractOptions, DiscMode, compare_chd_to_source,
+    convert_disc_set_to_chd, convert_disc_to_chd_cancellable, extract_dvd_to_writer_cancellable,
+    extract_from_chd_with_options, plan_cd_layout, plan_disc_sets, verify_chd, verify_chd_batch,
 };
 use rom_converto_lib::cso::{
     CsoCompressOptions, CsoFormat, compress_to_cso_cancellable, decompress_from_cso_cancellable,
@@ -39,6 +44,8 @@ use rom_converto_lib::cue::to_iso::cue_to_iso;
 use rom_converto_lib::nintendo::ctr::convert::{
     convert_rom_batch_cancellable, convert_rom_cancellable, derive_converted_path,
 };
+use rom_converto_lib::nintendo::ctr::merge::merge_cia_cancellable;
+use rom_converto_lib::nintendo::ctr::region::{CdnTicketSource, cdn_title_region};
 use rom_converto_lib::nintendo::ctr::verify::{
     CtrVerifyOptions, CtrVerifyResult, verify_ctr, verify_ctr_batch,
 };
@@ -47,9 +54,11 @@ use rom_converto_lib::nintendo::ctr::z3ds::{
     derive_compressed_path, derive_decompressed_path,
 };
 use rom_converto_lib::nintendo::ctr::{
-    CdnToCiaOptions, convert_cdn_to_cia_cancellable, decrypt_rom_batch_cancellable,
-    decrypt_rom_cancellable, derive_decrypted_path, derive_encrypted_path,
-    encrypt_rom_batch_cancellable, encrypt_rom_cancellable, generate_ticket_from_cdn,
+    CdnNameFrom, CdnToCiaOptions, DecryptOptions, cdn_cia_output_path,
+    convert_cdn_to_cia_cancellable, decrypt_rom_batch_cancellable, decrypt_rom_cancellable,
+    derive_decrypted_path, derive_encrypted_path, encrypt_ncch_file_cancellable,
+    encrypt_rom_batch_cancellable, encrypt_rom_cancellable, generate_ticket_from_cdn_cancellable,
+    ncch_content_index_from_name,
 };
 use rom_converto_lib::nintendo::dol::verify::{DolVerifyOptions, verify_dol};
 use rom_converto_lib::nintendo::legacy_input::{
@@ -87,9 +96,11 @@ mod batch;
 mod commands;
 mod config;
 mod dry_run;
+mod exit_code;
 mod github;
 mod info_print;
 mod logging;
+mod output_hash;
 mod updater;
 mod util;
 // Mirrors the inline logic in build.rs; kept here so it is unit-testable.
@@ -104,6 +115,7 @@ pub mod built_info {
 // count summary matches what each lib batch function actually processed.
 const CTR_DECRYPT_EXTS: &[&str] = &["cia", "3ds", "cci", "cxi"];
 const CTR_ENCRYPT_EXTS: &[&str] = &["cia", "3ds", "cci", "cxi"];
+const CTR_NCCH_EXTS: &[&str] = &["ncch", "cxi", "cfa"];
 const CTR_COMPRESS_EXTS: &[&str] = &["cia", "cci", "3ds", "cxi", "3dsx"];
 const CTR_DECOMPRESS_EXTS: &[&str] = &["zcia", "zcci", "zcxi", "z3dsx"];
 const CTR_CONVERT_EXTS: &[&str] = &["cia", "3ds", "cci"];
@@ -123,7 +135,10 @@ fn file_len(path: &Path) -> u64 {
 fn log_single_summary(input: &Path, output: &Path, direction: TallyDirection, started: Instant) {
     let mut tally = Tally::new();
     tally.record_ok(file_len(input), file_len(output), started.elapsed());
-    log::info!("{}", tally.summary_line(direction));
+    let summary = tally.summary_line(direction);
+    logging::event("finish", input, format_args!("{summary}"));
+    log::info!("{summary}");
+    output_hash::hash_output(output);
 }
 
 fn finish_single(
@@ -143,7 +158,10 @@ fn finish_single(
     let out_bytes = file_len(output);
     let mut tally = Tally::new();
     tally.record_ok(in_bytes, out_bytes, elapsed);
-    log::info!("{}", tally.summary_line(direction));
+    let summary = tally.summary_line(direction);
+    logging::event("finish", input, format_args!("{summary}"));
+    log::info!("{summary}");
+    let hashes = output_hash::hash_output(output);
     if let Some(path) = report {
         let elapsed_ms = elapsed.as_millis().min(u64::MAX as u128) as u64;
         let record = ReportRecord::new(
@@ -155,7 +173,8 @@ fn finish_single(
             out_bytes,
             elapsed_ms,
             None,
-        );
+        )
+        .with_hashes(hashes);
         let totals = ReportTotals {
             total_files: 1,
             ok: 1,
@@ -262,7 +281,8 @@ pub(crate) fn print_hash_row(path: &Path, d: &FileDigests, algos: &[HashAlgo]) {
     log::info!("{}  {}", path.display(), cells.join("  "));
 }
 
-fn log_skipped(output: &Path) {
+fn log_skipped(input: &Path, output: &Path) {
+    logging::skip_event(input, output);
     log::info!("Skipped, output exists: {}", output.display());
 }
 
@@ -533,7 +553,16 @@ fn wants_wbfs_output(path: &std::path::Path) -> bool {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(err) = run().await {
+        // Same report a `Result`-returning main prints, with the exit code
+        // picked from the error's category instead of always 1.
+        eprintln!("Error: {err:?}");
+        std::process::exit(exit_code::ExitCategory::of(&err).code());
+    }
+}
+
+async fn run() -> Result<()> {
     dotenv::dotenv().ok();
 
     let cli = Cli::parse();
@@ -553,45 +582,76 @@ async fn main() -> Result<()> {
         }
         None => None,
     };
+    let json_file = match cli.log_file.as_deref() {
+        Some(path) => {
+            let f = std::fs::File::create(path)
+                .with_context(|| format!("cannot open log file: {}", path.display()))?;
+            Some(std::io::BufWriter::new(f))
+        }
+        None => None,
+    };
+
+    let interactive = std::io::stderr().is_terminal();
+    let styled = logging::console_is_styled(interactive, std::env::var_os("NO_COLOR").as_deref());
 
     let mut builder = env_logger::builder();
     builder
         .filter_level(global_level)
         .filter_module("rom_converto", project_level)
         .filter_module("rom_converto_lib", project_level)
-        .format_timestamp(None);
+        .filter_module(logging::EVENT_TARGET, log::LevelFilter::Off)
+        .format_timestamp(None)
+        .write_style(if styled {
+            env_logger::WriteStyle::Always
+        } else {
+            env_logger::WriteStyle::Never
+        });
     if cli.verbose == 0 && !cli.quiet {
         builder.format_target(false);
         // At default verbosity ordinary info lines are user-facing
         // summaries, so they print as plain text; warnings and errors
         // keep a label so they still stand out.
-        builder.format(|buf, record| {
+        builder.format(move |buf, record| {
             use std::io::Write;
-            match record.level() {
-                log::Level::Info => writeln!(buf, "{}", record.args()),
-                level => writeln!(buf, "[{level}] {}", record.args()),
-            }
+            writeln!(
+                buf,
+                "{}",
+                logging::summary_line(record.level(), record.args(), styled)
+            )
+        });
+    } else if styled {
+        let started = std::time::Instant::now();
+        builder.format(move |buf, record| {
+            use std::io::Write;
+            writeln!(
+                buf,
+                "{}",
+                logging::styled_line(
+                    record.level(),
+                    record.target(),
+                    record.args(),
+                    started.elapsed()
+                )
+            )
         });
     }
+    // RUST_LOG still overrides the filters, and RUST_LOG_STYLE=never strips
+    // the colors again.
     let console_logger = builder.parse_default_env().build();
 
-    let pb = MultiProgress::new();
-
-    let max_level = if debug_file.is_some() {
-        log::LevelFilter::Trace
+    // Bars never draw to a non-terminal stderr anyway; hiding them outright
+    // keeps --quiet and --no-progress quiet, and piped runs fall back to
+    // percentage lines below.
+    let show_bars = interactive && !cli.quiet && !cli.no_progress;
+    let pb = if show_bars {
+        MultiProgress::new()
     } else {
-        console_logger.filter()
+        MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden())
     };
 
-    match debug_file {
-        Some(file) => {
-            let dual = logging::DualLogger::new(console_logger, file);
-            LogWrapper::new(pb.clone(), dual).try_init()?;
-        }
-        None => {
-            LogWrapper::new(pb.clone(), console_logger).try_init()?;
-        }
-    }
+    let logger = logging::TeeLogger::new(console_logger, debug_file, json_file);
+    let max_level = logger.max_level();
+    LogWrapper::new(pb.clone(), logger).try_init()?;
     log::set_max_level(max_level);
 
     cleanup_old_executable().await?;
@@ -609,6 +669,11 @@ async fn main() -> Result<()> {
     }
 
     let progress = IndicatifProgress::new(pb.clone());
+    let progress = if !interactive && !cli.quiet && !cli.no_progress {
+        progress.with_percent_logging()
+    } else {
+        progress
+    };
     let total_progress = TotalProgress::new(pb);
 
     let user_config = rom_converto_lib::config::load_config(cli.config.as_deref())?;
@@ -616,14 +681,33 @@ async fn main() -> Result<()> {
     let effective = config::resolve(&user_config, preset.as_ref());
     let dry_run = cli.dry_run;
     let skip_space_check = cli.skip_space_check;
+    if cli.hashes && !dry_run {
+        output_hash::enable();
+    }
     let cache = rom_converto_lib::util::HashCache::load(cli.no_cache, cli.rebuild_cache);
+    if let Some(source) = &cli.seeddb {
+        let count = rom_converto_lib::nintendo::ctr::seed::load_seed_database(source).await?;
+        log::debug!("Using {count} seeds from {source}");
+    }
 
     let cancel = rom_converto_lib::util::CancelToken::new();
     {
         let cancel = cancel.clone();
         tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            cancel.cancel();
+            log::warn!("Cancelling, press Ctrl-C again to stop immediately");
+            // Some steps only notice the cancel at their next chunk, and a
+            // few not at all; a second press exits now, taking the
+            // half-written outputs with it.
             if tokio::signal::ctrl_c().await.is_ok() {
-                cancel.cancel();
+                let removed = rom_converto_lib::util::remove_scratch_outputs();
+                log::info!(target: logging::EVENT_TARGET, event = "cancelled"; "Cancelled, removed {removed} partial outputs");
+                log::logger().flush();
+                eprintln!("Cancelled");
+                std::process::exit(130);
             }
         });
     }
@@ -635,6 +719,8 @@ async fn main() -> Result<()> {
         &effective,
         dry_run,
         skip_space_check,
+        cli.fail_fast,
+        cli.seed_retries,
         cancel.clone(),
         &mut github,
         &cache,
@@ -646,9 +732,13 @@ async fn main() -> Result<()> {
 
     if let Err(err) = dispatch {
         if cancel.is_cancelled() && is_cancelled_error(&err) {
+            log::info!(target: logging::EVENT_TARGET, event = "cancelled"; "Cancelled");
+            log::logger().flush();
             eprintln!("Cancelled");
             std::process::exit(130);
         }
+        log::error!(target: logging::EVENT_TARGET, event = "error"; "{err:#}");
+        log::logger().flush();
         return Err(err);
     }
     Ok(())
@@ -693,6 +783,8 @@ async fn dispatch_command(
     effective: &config::Effective,
     dry_run: bool,
     skip_space_check: bool,
+    fail_fast: bool,
+    seed_retries: u32,
     cancel: rom_converto_lib::util::CancelToken,
     github: &mut GithubApi,
     cache: &rom_converto_lib::util::HashCache,
@@ -702,6 +794,17 @@ async fn dispatch_command(
             CtrCommands::CdnToCia(cmd) => {
                 let mut output = cmd.output_flag.or(cmd.output);
                 let mut output_dir = cmd.output_dir;
+                let name_from = match cmd.name_from {
+                    CdnNameFromArg::Dir => CdnNameFrom::Dir,
+                    CdnNameFromArg::TitleId => CdnNameFrom::TitleId,
+                    CdnNameFromArg::TitleIdVersion => CdnNameFrom::TitleIdVersion,
+                };
+                let tickets = CdnTicketSource {
+                    ticket: cmd.ticket.clone(),
+                    generate: cmd.ensure_ticket_exists,
+                    title_key: cmd.title_key,
+                    common_key_index: cmd.common_key_index,
+                };
                 if cmd.recursive && dry_run {
                     ensure_input_exists(&cmd.cdn_dir)?;
                     let policy = policy_of(cmd.on_conflict, cmd.force);
@@ -718,15 +821,40 @@ async fn dispatch_command(
                         .collect();
                     dirs.sort();
                     for dir in &dirs {
-                        let name = dir
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .map(|n| format!("{n}.cia"))
-                            .unwrap_or_else(|| "output.cia".to_string());
-                        let base = rom_converto_lib::util::place_in_dir(
-                            &dir.parent().unwrap_or_else(|| Path::new(".")).join(name),
+                        if let Some(filter) = cmd.region {
+                            match cdn_title_region(dir, cmd.tmd_version, &tickets).await {
+                                Ok(region) if filter.accepts(region) => {}
+                                Ok(region) => {
+                                    log::info!(
+                                        "Skipping {}: region {region} does not match {filter}",
+                                        dir.display()
+                                    );
+                                    tally.record_skipped();
+                                    continue;
+                                }
+                                Err(e) => {
+                                    log::info!("Skipping {}: region unknown ({e})", dir.display());
+                                    tally.record_skipped();
+                                    continue;
+                                }
+                            }
+                        }
+                        let base = match cdn_cia_output_path(
+                            dir,
+                            name_from,
+                            cmd.output_template.as_deref(),
+                            cmd.tmd_version,
                             output_dir.as_deref(),
-                        );
+                            &tickets,
+                        )
+                        .await
+                        {
+                            Ok(path) => path,
+                            Err(e) => {
+                                log::warn!("Skipping {}: {e}", dir.display());
+                                continue;
+                            }
+                        };
                         let resolved = if cmd.compress {
                             derive_compressed_path(&base)
                         } else {
@@ -739,26 +867,40 @@ async fn dispatch_command(
                     log::info!("{}", tally.summary_line(TallyDirection::DryRun));
                     return Ok(());
                 }
-                if !cmd.recursive {
+                // A CDN folder packed in an archive is unpacked to a temp dir
+                // that lives until the conversion finishes.
+                let cdn_input = if cmd.recursive {
+                    None
+                } else {
                     ensure_input_exists(&cmd.cdn_dir)?;
+                    Some(rom_converto_lib::util::resolve_cdn_input(&cmd.cdn_dir)?)
+                };
+                let cdn_dir = match &cdn_input {
+                    Some(resolved) if resolved.path() != cmd.cdn_dir => {
+                        if output.is_none() && output_dir.is_none() {
+                            output_dir = resolved.output_basis().parent().map(Path::to_path_buf);
+                        }
+                        resolved.path().to_path_buf()
+                    }
+                    _ => cmd.cdn_dir.clone(),
+                };
+                if !cmd.recursive {
                     let base = match output.clone() {
                         Some(p) => p,
                         None => {
-                            if !dry_run && let Some(dir) = output_dir.as_deref() {
-                                std::fs::create_dir_all(dir)?;
+                            let path = cdn_cia_output_path(
+                                &cdn_dir,
+                                name_from,
+                                cmd.output_template.as_deref(),
+                                cmd.tmd_version,
+                                output_dir.as_deref(),
+                                &tickets,
+                            )
+                            .await?;
+                            if !dry_run && let Some(parent) = path.parent() {
+                                std::fs::create_dir_all(parent)?;
                             }
-                            let name = cmd
-                                .cdn_dir
-                                .file_name()
-                                .and_then(|n| n.to_str())
-                                .map(|n| format!("{n}.cia"))
-                                .unwrap_or_else(|| "output.cia".to_string());
-                            let derived = cmd
-                                .cdn_dir
-                                .parent()
-                                .unwrap_or_else(|| std::path::Path::new("."))
-                                .join(name);
-                            rom_converto_lib::util::place_in_dir(&derived, output_dir.as_deref())
+                            path
                         }
                     };
                     let resolved = if cmd.compress {
@@ -781,7 +923,7 @@ async fn dispatch_command(
                     }
                     match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&resolved);
+                            log_skipped(&cmd.cdn_dir, &resolved);
                             return Ok(());
                         }
                         WriteDecision::Write(p) if p != resolved => {
@@ -798,7 +940,7 @@ async fn dispatch_command(
                     }
                 }
                 let opts = CdnToCiaOptions {
-                    cdn_dir: cmd.cdn_dir,
+                    cdn_dir,
                     output,
                     cleanup: cmd.cleanup,
                     recursive: cmd.recursive,
@@ -807,9 +949,48 @@ async fn dispatch_command(
                     compress: cmd.compress,
                     output_dir,
                     on_conflict: policy_of(cmd.on_conflict, cmd.force),
+                    name_from,
+                    verify_after: cmd.verify_after,
+                    fix_content_size: cmd.fix_content_size,
+                    tmd_version: cmd.tmd_version,
+                    common_key_index: cmd.common_key_index,
+                    title_key: cmd.title_key,
+                    raw_certs: cmd.raw_certs,
+                    ticket: cmd.ticket.clone(),
+                    output_template: cmd.output_template.clone(),
+                    region: cmd.region,
+                    content_concurrency: cmd.content_chunk_concurrency as usize,
+                    seed_retries,
+                    fail_fast,
                 };
-                convert_cdn_to_cia_cancellable(opts, &progress, &total_progress, cancel.clone())
-                    .await?
+                let recursive = opts.recursive;
+                let mut tally = Tally::new();
+                let result = convert_cdn_to_cia_cancellable(
+                    opts,
+                    &progress,
+                    &total_progress,
+                    &mut tally,
+                    cancel.clone(),
+                )
+                .await;
+                if recursive {
+                    log::info!("{}", tally.summary_line(TallyDirection::Convert));
+                }
+                result?
+            }
+            CtrCommands::Batch(cmd) => {
+                ensure_input_exists(&cmd.manifest)?;
+                batch::ctr_manifest(
+                    &progress,
+                    &total_progress,
+                    &cmd.manifest,
+                    cmd.jobs as usize,
+                    seed_retries,
+                    policy_of(cmd.on_conflict, cmd.force),
+                    dry_run,
+                    cancel.clone(),
+                )
+                .await?
             }
             CtrCommands::GenerateCdnTicket(cmd) => {
                 ensure_input_exists(&cmd.cdn_dir)?;
@@ -825,9 +1006,24 @@ async fn dispatch_command(
                         None,
                     );
                 }
-                generate_ticket_from_cdn(&cmd.cdn_dir, &cmd.output).await?
+                let cdn_input = rom_converto_lib::util::resolve_cdn_input(&cmd.cdn_dir)?;
+                generate_ticket_from_cdn_cancellable(
+                    cdn_input.path(),
+                    &cmd.output,
+                    cmd.title_version,
+                    cmd.password.as_deref(),
+                    cmd.common_key_index,
+                    &cancel,
+                )
+                .await?
             }
             CtrCommands::Decrypt(cmd) => {
+                let decrypt_opts = DecryptOptions {
+                    allow_missing_seed: cmd.allow_missing_seed,
+                    seed: cmd.seed,
+                    seed_retries,
+                    check_romfs: cmd.check_romfs,
+                };
                 if cmd.recursive {
                     if !cmd.input.is_dir() {
                         anyhow::bail!(
@@ -851,23 +1047,93 @@ async fn dispatch_command(
                         let check_dir = cmd.output_dir.as_deref().unwrap_or(&cmd.input);
                         batch::space_preflight(&files, check_dir)?;
                     }
-                    let tally = Tally::new();
-                    let count = files.len();
-                    decrypt_rom_batch_cancellable(
+                    let mut tally = Tally::new();
+                    let result = decrypt_rom_batch_cancellable(
                         &cmd.input,
                         cmd.output_dir.as_deref(),
+                        &decrypt_opts,
                         &progress,
                         &total_progress,
                         cmd.max_depth,
+                        fail_fast,
+                        &mut tally,
                         cancel.clone(),
                     )
-                    .await?;
-                    log_count_summary(count, tally);
+                    .await;
+                    log::info!("{}", tally.summary_line(TallyDirection::Convert));
+                    result?;
                 } else {
-                    ensure_input_exists(&cmd.input)?;
+                    let staged_stdin = if stdin::is_stdin(&cmd.input) {
+                        let has_output = cmd.output_flag.is_some() || cmd.output.is_some();
+                        // The default --ncch-only directory would be the
+                        // staged input's, which is deleted afterwards.
+                        if cmd.ncch_only && !has_output && cmd.output_dir.is_none() {
+                            anyhow::bail!(
+                                "reading INPUT from stdin with --ncch-only needs an explicit OUTPUT directory or --output-dir"
+                            );
+                        }
+                        if !cmd.ncch_only && !has_output {
+                            anyhow::bail!(
+                                "reading INPUT from stdin needs an explicit OUTPUT (`-` for stdout)"
+                            );
+                        }
+                        // A dry run only plans the write, so stdin is left unread.
+                        if dry_run {
+                            None
+                        } else {
+                            Some(stdin::stage_stdin().await?)
+                        }
+                    } else {
+                        ensure_input_exists(&cmd.input)?;
+                        None
+                    };
+                    let input_path = staged_stdin
+                        .as_ref()
+                        .map_or(cmd.input.as_path(), |s| s.path());
                     let resolved =
-                        rom_converto_lib::util::resolve_input(&cmd.input, CTR_DECRYPT_EXTS)?;
+                        rom_converto_lib::util::resolve_input(input_path, CTR_DECRYPT_EXTS)?;
                     let input = resolved.path();
+                    if cmd.ncch_only {
+                        let out_dir = cmd
+                            .output_flag
+                            .or(cmd.output)
+                            .or(cmd.output_dir)
+                            .unwrap_or_else(|| {
+                                input
+                                    .parent()
+                                    .filter(|p| !p.as_os_str().is_empty())
+                                    .unwrap_or_else(|| Path::new("."))
+                                    .to_path_buf()
+                            });
+                        if stdout::is_stdout(&out_dir) {
+                            anyhow::bail!(
+                                "--ncch-only writes a directory and cannot target stdout"
+                            );
+                        }
+                        if dry_run {
+                            let decision = WriteDecision::Write(out_dir.clone());
+                            return dry_run_single(
+                                "decrypt", &cmd.input, &out_dir, &decision, None, None, None,
+                            );
+                        }
+                        if !skip_space_check {
+                            batch::space_preflight_for_size(file_len(input), &out_dir)?;
+                        }
+                        let overwrite = policy_of(cmd.on_conflict, cmd.force)
+                            == rom_converto_lib::util::ConflictPolicy::Overwrite;
+                        let started = logging::start_event(&cmd.input);
+                        rom_converto_lib::nintendo::ctr::decrypt_cia_to_ncch_cancellable(
+                            input,
+                            &out_dir,
+                            overwrite,
+                            &decrypt_opts,
+                            &progress,
+                            cancel.clone(),
+                        )
+                        .await?;
+                        log_single_summary(&cmd.input, &out_dir, TallyDirection::Convert, started);
+                        return Ok(());
+                    }
                     let output = match cmd.output_flag.or(cmd.output) {
                         Some(p) => p,
                         None => {
@@ -893,6 +1159,33 @@ async fn dispatch_command(
                             }
                         }
                     };
+                    if stdout::is_stdout(&output) {
+                        if dry_run {
+                            let decision = WriteDecision::Write(output.clone());
+                            return dry_run_single(
+                                "decrypt", &cmd.input, &output, &decision, None, None, None,
+                            );
+                        }
+                        let scratch = stdout::scratch_dir()?;
+                        if !skip_space_check {
+                            batch::space_preflight_for_size(file_len(input), scratch.path())?;
+                        }
+                        let staged = scratch.path().join(
+                            derive_decrypted_path(resolved.output_basis())
+                                .file_name()
+                                .unwrap_or_default(),
+                        );
+                        decrypt_rom_cancellable(
+                            input,
+                            &staged,
+                            &decrypt_opts,
+                            &progress,
+                            cancel.clone(),
+                        )
+                        .await?;
+                        stdout::copy_to_stdout(&staged).await?;
+                        return Ok(());
+                    }
                     let policy = policy_of(cmd.on_conflict, cmd.force);
                     let decision = resolve_output(&output, policy)?;
                     if dry_run {
@@ -902,7 +1195,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -911,8 +1204,15 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
-                    decrypt_rom_cancellable(input, &output, &progress, cancel.clone()).await?;
+                    let started = logging::start_event(&cmd.input);
+                    decrypt_rom_cancellable(
+                        input,
+                        &output,
+                        &decrypt_opts,
+                        &progress,
+                        cancel.clone(),
+                    )
+                    .await?;
                     log_single_summary(&cmd.input, &output, TallyDirection::Convert, started);
                 }
             }
@@ -940,18 +1240,20 @@ async fn dispatch_command(
                         let check_dir = cmd.output_dir.as_deref().unwrap_or(&cmd.input);
                         batch::space_preflight(&files, check_dir)?;
                     }
-                    let tally = Tally::new();
-                    let count = files.len();
-                    encrypt_rom_batch_cancellable(
+                    let mut tally = Tally::new();
+                    let result = encrypt_rom_batch_cancellable(
                         &cmd.input,
                         cmd.output_dir.as_deref(),
                         &progress,
                         &total_progress,
                         cmd.max_depth,
+                        fail_fast,
+                        &mut tally,
                         cancel.clone(),
                     )
-                    .await?;
-                    log_count_summary(count, tally);
+                    .await;
+                    log::info!("{}", tally.summary_line(TallyDirection::Convert));
+                    result?;
                 } else {
                     ensure_input_exists(&cmd.input)?;
                     let resolved =
@@ -991,7 +1293,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1000,11 +1302,53 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     encrypt_rom_cancellable(input, &output, &progress, cancel.clone()).await?;
                     log_single_summary(&cmd.input, &output, TallyDirection::Convert, started);
                 }
             }
+            CtrCommands::EncryptNcch(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let resolved = rom_converto_lib::util::resolve_input(&cmd.input, CTR_NCCH_EXTS)?;
+                let input = resolved.path();
+                let output = cmd
+                    .output_flag
+                    .or(cmd.output)
+                    .unwrap_or_else(|| derive_encrypted_path(resolved.output_basis()));
+                let decision = resolve_output(&output, policy_of(cmd.on_conflict, cmd.force))?;
+                if dry_run {
+                    return dry_run_single(
+                        "encrypt", &cmd.input, &output, &decision, None, None, None,
+                    );
+                }
+                let output = match decision {
+                    WriteDecision::Skip => {
+                        log_skipped(&cmd.input, &output);
+                        return Ok(());
+                    }
+                    WriteDecision::Write(p) => p,
+                };
+                let content_index = cmd.content_index.or_else(|| {
+                    let name = resolved.output_basis().file_name()?.to_str()?;
+                    let index = ncch_content_index_from_name(name)?;
+                    log::info!("Using CIA content index {index} from the file name");
+                    Some(index)
+                });
+                if !skip_space_check {
+                    let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
+                    batch::space_preflight_for_size(file_len(input), check_dir)?;
+                }
+                let started = logging::start_event(&cmd.input);
+                encrypt_ncch_file_cancellable(
+                    input,
+                    &output,
+                    content_index,
+                    &progress,
+                    cancel.clone(),
+                )
+                .await?;
+                log_single_summary(&cmd.input, &output, TallyDirection::Convert, started);
+            }
             CtrCommands::Compress(cmd) => {
                 if cmd.recursive {
                     if !cmd.input.is_dir() {
@@ -1029,9 +1373,8 @@ async fn dispatch_command(
                         let check_dir = cmd.output_dir.as_deref().unwrap_or(&cmd.input);
                         batch::space_preflight(&files, check_dir)?;
                     }
-                    let tally = Tally::new();
-                    let count = files.len();
-                    compress_rom_batch(
+                    let mut tally = Tally::new();
+                    let result = compress_rom_batch(
                         &cmd.input,
                         cmd.level,
                         cmd.output_dir.as_deref(),
@@ -1039,9 +1382,12 @@ async fn dispatch_command(
                         &total_progress,
                         cmd.max_depth,
                         cmd.allow_encrypted,
+                        fail_fast,
+                        &mut tally,
                     )
-                    .await?;
-                    log_count_summary(count, tally);
+                    .await;
+                    log::info!("{}", tally.summary_line(TallyDirection::Compress));
+                    result?;
                 } else {
                     ensure_input_exists(&cmd.input)?;
                     let resolved =
@@ -1081,7 +1427,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1090,7 +1436,7 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     compress_rom_cancellable(
                         input,
                         &output,
@@ -1127,17 +1473,19 @@ async fn dispatch_command(
                         let check_dir = cmd.output_dir.as_deref().unwrap_or(&cmd.input);
                         batch::space_preflight(&files, check_dir)?;
                     }
-                    let tally = Tally::new();
-                    let count = files.len();
-                    decompress_rom_batch(
+                    let mut tally = Tally::new();
+                    let result = decompress_rom_batch(
                         &cmd.input,
                         cmd.output_dir.as_deref(),
                         &progress,
                         &total_progress,
                         cmd.max_depth,
+                        fail_fast,
+                        &mut tally,
                     )
-                    .await?;
-                    log_count_summary(count, tally);
+                    .await;
+                    log::info!("{}", tally.summary_line(TallyDirection::Decompress));
+                    result?;
                 } else {
                     ensure_input_exists(&cmd.input)?;
                     let resolved =
@@ -1183,7 +1531,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1192,7 +1540,7 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     decompress_rom_cancellable(input, &output, &progress, cancel.clone()).await?;
                     log_single_summary(&cmd.input, &output, TallyDirection::Decompress, started);
                 }
@@ -1221,18 +1569,20 @@ async fn dispatch_command(
                         let check_dir = cmd.output_dir.as_deref().unwrap_or(&cmd.input);
                         batch::space_preflight(&files, check_dir)?;
                     }
-                    let tally = Tally::new();
-                    let count = files.len();
-                    convert_rom_batch_cancellable(
+                    let mut tally = Tally::new();
+                    let result = convert_rom_batch_cancellable(
                         &cmd.input,
                         cmd.output_dir.as_deref(),
                         &progress,
                         &total_progress,
                         cmd.max_depth,
+                        fail_fast,
+                        &mut tally,
                         cancel.clone(),
                     )
-                    .await?;
-                    log_count_summary(count, tally);
+                    .await;
+                    log::info!("{}", tally.summary_line(TallyDirection::Convert));
+                    result?;
                 } else {
                     ensure_input_exists(&cmd.input)?;
                     let resolved =
@@ -1272,7 +1622,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1281,11 +1631,55 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     convert_rom_cancellable(input, &output, &progress, cancel.clone()).await?;
                     log_single_summary(&cmd.input, &output, TallyDirection::Convert, started);
                 }
             }
+            CtrCommands::Merge(cmd) => {
+                ensure_input_exists(&cmd.base)?;
+                for addition in &cmd.additions {
+                    ensure_input_exists(addition)?;
+                }
+                let policy = policy_of(cmd.on_conflict, cmd.force);
+                let decision = resolve_output(&cmd.output, policy)?;
+                if dry_run {
+                    return dry_run_single(
+                        "merge",
+                        &cmd.base,
+                        &cmd.output,
+                        &decision,
+                        None,
+                        None,
+                        None,
+                    );
+                }
+                let output = match decision {
+                    WriteDecision::Skip => {
+                        log_skipped(&cmd.base, &cmd.output);
+                        return Ok(());
+                    }
+                    WriteDecision::Write(p) => p,
+                };
+                if !skip_space_check {
+                    let needed = std::iter::once(&cmd.base)
+                        .chain(&cmd.additions)
+                        .map(|p| file_len(p))
+                        .sum();
+                    let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
+                    batch::space_preflight_for_size(needed, check_dir)?;
+                }
+                let started = logging::start_event(&cmd.base);
+                merge_cia_cancellable(
+                    &cmd.base,
+                    &cmd.additions,
+                    &output,
+                    &progress,
+                    cancel.clone(),
+                )
+                .await?;
+                log_single_summary(&cmd.base, &output, TallyDirection::Convert, started);
+            }
             CtrCommands::Verify(cmd) => {
                 let opts = CtrVerifyOptions {
                     verify_content_hashes: cmd.verify_content,
@@ -1371,8 +1765,119 @@ async fn dispatch_command(
                 if let Some(dir) = &cmd.save_icon {
                     save_ctr_icon(&info, dir)?;
                 }
+                if let Some(dat) = &cmd.dat_xml {
+                    write_dat_entry(resolved.path(), dat, &progress, &cancel)?;
+                }
                 info_print::print(&rom_converto_lib::info::InfoResult::Ctr(info), cmd.json)?;
             }
+            CtrCommands::InspectNcch(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let report = rom_converto_lib::nintendo::ctr::inspect::inspect_ncch(&cmd.input)?;
+                info_print::print_ncch_crypto(&report, cmd.json)?;
+            }
+            CtrCommands::DumpKeys(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let dump = rom_converto_lib::nintendo::ctr::inspect::dump_cia_keys(&cmd.input)?;
+                info_print::print_cia_keys(&dump, cmd.json)?;
+            }
+            CtrCommands::RomfsList(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let listing = rom_converto_lib::nintendo::ctr::romfs::list_romfs(&cmd.input)?;
+                info_print::print_romfs_listing(&listing, cmd.json)?;
+            }
+            CtrCommands::Anonymize(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let report = rom_converto_lib::nintendo::ctr::anonymize::anonymize(&cmd.input)?;
+                if report.changed() {
+                    log::info!(
+                        "Cleared console ID {:08X} and eShop account ID {:08X} in {}",
+                        report.console_id,
+                        report.eshop_account_id,
+                        cmd.input.display()
+                    );
+                    log::warn!(
+                        "The ticket signature no longer verifies; the title needs signature patches (CFW) or an emulator"
+                    );
+                } else {
+                    log::info!(
+                        "Ticket in {} already has no console or account ID, nothing changed",
+                        cmd.input.display()
+                    );
+                }
+            }
+            CtrCommands::CiaToCdn(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let layout = rom_converto_lib::nintendo::ctr::extract::CdnLayout {
+                    versioned_tmd: cmd.versioned_tmd,
+                    tik_ticket: cmd.tik,
+                };
+                let extracted =
+                    rom_converto_lib::nintendo::ctr::extract::extract_contents_cancellable(
+                        &cmd.input,
+                        &cmd.output_dir,
+                        layout,
+                        cmd.force,
+                        &progress,
+                        &cancel,
+                    )
+                    .await?;
+                log::info!(
+                    "Extracted {} contents of {:016X} to {}",
+                    extracted.contents.len(),
+                    extracted.title_id,
+                    cmd.output_dir.display()
+                );
+            }
+            CtrCommands::ExtractContent(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let extracted =
+                    rom_converto_lib::nintendo::ctr::extract::extract_content_cancellable(
+                        &cmd.input,
+                        cmd.index,
+                        &cmd.output,
+                        cmd.decrypt,
+                        cmd.force,
+                        &progress,
+                        &cancel,
+                    )
+                    .await?;
+                log::info!(
+                    "Extracted content {:08x} (index {}, {}{}) to {}",
+                    extracted.content_id,
+                    extracted.content_index,
+                    rom_converto_lib::util::format_bytes(extracted.size),
+                    if extracted.decrypted {
+                        ", decrypted"
+                    } else {
+                        ""
+                    },
+                    cmd.output.display()
+                );
+            }
+            CtrCommands::FixTmd(cmd) => {
+                ensure_input_exists(&cmd.tmd)?;
+                ensure_input_exists(&cmd.content_dir)?;
+                let report =
+                    rom_converto_lib::nintendo::ctr::fix_tmd::fix_tmd(&cmd.tmd, &cmd.content_dir)?;
+                if report.rewritten {
+                    log::info!(
+                        "Updated hashes of {} of {} contents of {:016X} in {}",
+                        report.updated.len(),
+                        report.contents,
+                        report.title_id,
+                        cmd.tmd.display()
+                    );
+                    log::warn!(
+                        "The TMD signature no longer verifies; the title needs signature patches (CFW) or an emulator"
+                    );
+                } else {
+                    log::info!(
+                        "All {} content hashes in {} already match, nothing changed",
+                        report.contents,
+                        cmd.tmd.display()
+                    );
+                }
+            }
         },
         Commands::Dol(inner) => match inner {
             DolCommands::Compress(cmd) => {
@@ -1483,7 +1988,7 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1492,7 +1997,7 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     compress_disc_cancellable(input, &output, opts, &progress, cancel.clone())
                         .await?;
                     finish_single(
@@ -1543,7 +2048,7 @@ async fn dispatch_command(
                     let policy = policy_of(crate::commands::ConflictPolicyArg::Error, cmd.force);
                     let output = match resolve_output(&output, policy)? {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1622,7 +2127,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1631,7 +2136,7 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     if wants_wbfs_output(&output) {
                         decompress_disc_to_wbfs_cancellable(
                             input,
@@ -1693,6 +2198,7 @@ async fn dispatch_command(
                 }
             }
             DolCommands::Info(cmd) => {
+                reject_dat_xml(&cmd)?;
                 if cmd.keys.is_some() {
                     anyhow::bail!("--keys is only supported by nx and wup info");
                 }
@@ -1813,7 +2319,7 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1822,7 +2328,7 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     compress_disc_cancellable(input, &output, opts, &progress, cancel.clone())
                         .await?;
                     finish_single(
@@ -1873,7 +2379,7 @@ async fn dispatch_command(
                     let policy = policy_of(crate::commands::ConflictPolicyArg::Error, cmd.force);
                     let output = match resolve_output(&output, policy)? {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1952,7 +2458,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -1961,7 +2467,7 @@ async fn dispatch_command(
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
                         batch::space_preflight_for_size(file_len(input), check_dir)?;
                     }
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     if wants_wbfs_output(&output) {
                         decompress_disc_to_wbfs_cancellable(
                             input,
@@ -2041,6 +2547,7 @@ async fn dispatch_command(
                 }
             }
             RvlCommands::Info(cmd) => {
+                reject_dat_xml(&cmd)?;
                 if cmd.keys.is_some() {
                     anyhow::bail!("--keys is only supported by nx and wup info");
                 }
@@ -2092,7 +2599,7 @@ async fn dispatch_command(
                 }
                 let output = match decision {
                     WriteDecision::Skip => {
-                        log_skipped(&cmd.output);
+                        log_skipped(cmd.inputs.first().unwrap_or(&cmd.output), &cmd.output);
                         return Ok(());
                     }
                     WriteDecision::Write(p) => p,
@@ -2148,7 +2655,7 @@ async fn dispatch_command(
                 }
                 match decision {
                     WriteDecision::Skip => {
-                        log_skipped(&cmd.output);
+                        log_skipped(&cmd.input, &cmd.output);
                         return Ok(());
                     }
                     WriteDecision::Write(_) => {}
@@ -2192,6 +2699,7 @@ async fn dispatch_command(
                 }
             }
             WupCommands::Info(cmd) => {
+                reject_dat_xml(&cmd)?;
                 ensure_input_exists(&cmd.input)?;
                 let resolved = rom_converto_lib::util::resolve_input(&cmd.input, ALL_IMAGE_EXTS)?;
                 let info = rom_converto_lib::nintendo::wup::info::read_info(
@@ -2362,7 +2870,7 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -2373,7 +2881,7 @@ async fn dispatch_command(
                     }
                     let in_path = input.to_path_buf();
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     compress_container_async_cancellable(
                         in_path.clone(),
                         output,
@@ -2498,7 +3006,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -2509,7 +3017,7 @@ async fn dispatch_command(
                     }
                     let in_path = input.to_path_buf();
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     decompress_container_async_cancellable(
                         in_path.clone(),
                         output,
@@ -2562,6 +3070,7 @@ async fn dispatch_command(
                 }
             }
             NxCommands::Info(cmd) => {
+                reject_dat_xml(&cmd)?;
                 ensure_input_exists(&cmd.input)?;
                 let resolved = rom_converto_lib::util::resolve_input(&cmd.input, ALL_IMAGE_EXTS)?;
                 let info = rom_converto_lib::nintendo::nx::info::read_info(
@@ -2576,11 +3085,54 @@ async fn dispatch_command(
         },
         Commands::Chd(inner) => match inner {
             ChdCommands::Compress(cmd) => {
+                if cmd.list {
+                    ensure_input_exists(&cmd.input)?;
+                    let resolved = rom_converto_lib::util::resolve_input(&cmd.input, &["cue"])?;
+                    let input = resolved.path();
+                    let is_cue = input
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| e.eq_ignore_ascii_case("cue"));
+                    if !is_cue {
+                        anyhow::bail!("--list reads a .cue sheet; {} is not one", input.display());
+                    }
+                    let layout =
+                        plan_cd_layout(input, cmd.sector_size.unwrap_or(SECTOR_SIZE)).await?;
+                    if layout.files.len() > 1 {
+                        log::warn!(
+                            "{} names {} files but chd compress reads only {}; merge them first with `rom-converto cue merge`",
+                            input.display(),
+                            layout.files.len(),
+                            layout.files[0]
+                        );
+                    }
+                    info_print::print_cd_layout(&layout);
+                    return Ok(());
+                }
                 let eff = &effective.chd;
                 let mut opts = ChdDvdOptions {
                     hunk_size: cmd.hunk_size.or(eff.hunk_size),
                     allow_zstd: cmd.zstd,
+                    lzma_level: cmd.lzma_level,
+                    zstd_level: cmd.zstd_level,
                     force: cmd.force,
+                    timing: cmd.timing,
+                    max_memory: cmd.max_memory.map(|mib| mib * 1024 * 1024),
+                    resume: cmd.resume,
+                    comment: cmd.comment.clone(),
+                    fast: cmd.fast,
+                    sector_size: cmd.sector_size,
+                    trim_trailing_zeros: cmd.trim_trailing_zeros,
+                    strict: cmd.strict,
+                    uncompressed_map: cmd.uncompressed_map,
+                    single_codec: cmd.single_codec.map(|codec| match codec {
+                        SingleCodecArg::Cdlz => ChdCodec::Cdlz,
+                        SingleCodecArg::Cdzl => ChdCodec::Cdzl,
+                        SingleCodecArg::Cdfl => ChdCodec::Cdfl,
+                        SingleCodecArg::Lzma => ChdCodec::Lzma,
+                        SingleCodecArg::Zlib => ChdCodec::Zlib,
+                        SingleCodecArg::Zstd => ChdCodec::Zstd,
+                    }),
                 };
                 let output_dir = cmd.output_dir.clone().or_else(|| eff.output_dir.clone());
                 let report = cmd.report.clone().or_else(|| eff.report.clone());
@@ -2594,6 +3146,11 @@ async fn dispatch_command(
                 };
                 if cmd.recursive {
                     require_dir(&cmd.input)?;
+                    // A re-run batch resumes where it stopped unless told otherwise.
+                    let fallback = match eff.on_conflict {
+                        Some(_) => fallback,
+                        None => rom_converto_lib::util::ConflictPolicy::Skip,
+                    };
                     let policy = resolve_policy(cmd.on_conflict, cmd.force, fallback);
                     batch::chd_compress(
                         &progress,
@@ -2607,6 +3164,8 @@ async fn dispatch_command(
                         cmd.max_depth,
                         dry_run,
                         skip_space_check,
+                        fail_fast,
+                        cmd.dedupe,
                         report.as_deref(),
                         cancel.clone(),
                         cache,
@@ -2680,7 +3239,7 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -2691,7 +3250,7 @@ async fn dispatch_command(
                     }
                     opts.force = true;
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     convert_disc_to_chd_cancellable(
                         &progress,
                         input.to_path_buf(),
@@ -2711,11 +3270,54 @@ async fn dispatch_command(
                     )?;
                 }
             }
+            ChdCommands::CompressSet(cmd) => {
+                require_dir(&cmd.input)?;
+                let output_dir = cmd.output_dir.clone().unwrap_or_else(|| cmd.input.clone());
+                if dry_run {
+                    let policy = policy_of(crate::commands::ConflictPolicyArg::Error, cmd.force);
+                    let mut tally = Tally::new();
+                    for group in plan_disc_sets(&cmd.input)? {
+                        for cue in &group.discs {
+                            let desired = output_dir
+                                .join(cue.with_extension("chd").file_name().unwrap_or_default());
+                            let decision = resolve_output(&desired, policy)?;
+                            dry_run::log_plan("compress", cue, &desired, &decision, None, None);
+                            dry_run::record(&mut tally, cue, &decision);
+                        }
+                        let m3u = output_dir.join(format!("{}.m3u", group.base_title));
+                        let decision = resolve_output(&m3u, policy)?;
+                        dry_run::log_plan("write", &cmd.input, &m3u, &decision, None, None);
+                        dry_run::record(&mut tally, &cmd.input, &decision);
+                    }
+                    return dry_run::finish(&tally, &[], None);
+                }
+                let opts = ChdDvdOptions {
+                    force: cmd.force,
+                    comment: cmd.comment.clone(),
+                    ..ChdDvdOptions::default()
+                };
+                let sets = convert_disc_set_to_chd(
+                    &progress,
+                    &total_progress,
+                    &cmd.input,
+                    &output_dir,
+                    opts,
+                    cancel.clone(),
+                )
+                .await?;
+                log::info!("Compressed {} multi-disc sets", sets.len());
+            }
             ChdCommands::Extract(cmd) => {
                 let eff = &effective.chd;
                 let output_dir = cmd.output_dir.clone().or_else(|| eff.output_dir.clone());
                 let report = cmd.report.clone().or_else(|| eff.report.clone());
                 let fallback = config::policy_fallback(&eff.on_conflict)?;
+                let extract_options = ChdExtractOptions {
+                    audio_endian: match cmd.audio_endian {
+                        AudioEndianArg::Le => AudioEndian::Little,
+                        AudioEndianArg::Be => AudioEndian::Big,
+                    },
+                };
                 if cmd.recursive {
                     require_dir(&cmd.input)?;
                     let policy = resolve_policy(cmd.on_conflict, cmd.force, fallback);
@@ -2724,6 +3326,7 @@ async fn dispatch_command(
                         &total_progress,
                         &cmd.input,
                         cmd.parent,
+                        extract_options,
                         policy,
                         output_dir.as_deref(),
                         cmd.output_template.as_deref(),
@@ -2763,6 +3366,35 @@ async fn dispatch_command(
                             }
                         }
                     };
+                    if stdout::is_stdout(&output) {
+                        if rom_converto_lib::chd::info::read_info(input)?.dvd.is_none() {
+                            anyhow::bail!(
+                                "only DVD CHDs can be extracted to stdout; CD CHDs extract to a cue/bin pair: {}",
+                                cmd.input.display()
+                            );
+                        }
+                        if dry_run {
+                            let decision = WriteDecision::Write(output.clone());
+                            return dry_run_single(
+                                "extract",
+                                &cmd.input,
+                                &output,
+                                &decision,
+                                None,
+                                None,
+                                report.as_deref(),
+                            );
+                        }
+                        extract_dvd_to_writer_cancellable(
+                            &progress,
+                            input.to_path_buf(),
+                            std::io::stdout(),
+                            cmd.parent,
+                            cancel.clone(),
+                        )
+                        .await?;
+                        return Ok(());
+                    }
                     let policy = resolve_policy(cmd.on_conflict, cmd.force, fallback);
                     let decision = resolve_output(&output, policy)?;
                     if dry_run {
@@ -2778,23 +3410,27 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
                     };
                     if !skip_space_check {
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
-                        batch::space_preflight_for_size(file_len(input), check_dir)?;
+                        batch::space_preflight_for_size(
+                            batch::chd_logical_bytes(input),
+                            check_dir,
+                        )?;
                     }
                     let in_path = input.to_path_buf();
                     let out_path = output.clone();
-                    let started = Instant::now();
-                    extract_from_chd_cancellable(
+                    let started = logging::start_event(&cmd.input);
+                    extract_from_chd_with_options(
                         &progress,
                         in_path.clone(),
                         output,
                         cmd.parent,
+                        extract_options,
                         cancel.clone(),
                     )
                     .await?;
@@ -2836,6 +3472,40 @@ async fn dispatch_command(
                     .await?
                 }
             }
+            ChdCommands::Compare(cmd) => {
+                ensure_input_exists(&cmd.chd)?;
+                ensure_input_exists(&cmd.source)?;
+                let comparison = compare_chd_to_source(
+                    &progress,
+                    cmd.chd.clone(),
+                    cmd.source.clone(),
+                    cancel.clone(),
+                )
+                .await?;
+                if !comparison.is_identical() {
+                    anyhow::bail!(
+                        "{} does not match {}: {comparison}",
+                        cmd.chd.display(),
+                        cmd.source.display()
+                    );
+                }
+                log::info!(
+                    "{} matches {}: {comparison}",
+                    cmd.chd.display(),
+                    cmd.source.display()
+                );
+            }
+            ChdCommands::Benchmark(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let bench = rom_converto_lib::chd::benchmark::benchmark_cd_codecs(
+                    &progress,
+                    &cmd.input,
+                    cmd.hunks as usize,
+                    cancel.clone(),
+                )
+                .await?;
+                info_print::print_chd_benchmark(&bench);
+            }
             ChdCommands::ToCso(cmd) => {
                 let eff = &effective.cso;
                 let format = match cmd.format {
@@ -2936,22 +3606,22 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
                     };
                     if !skip_space_check {
                         let check_dir = output.parent().unwrap_or_else(|| Path::new("."));
-                        let required = rom_converto_lib::chd::info::read_info(input)
-                            .map(|info| info.logical_bytes)
-                            .unwrap_or_else(|_| file_len(input));
-                        batch::space_preflight_for_size(required, check_dir)?;
+                        batch::space_preflight_for_size(
+                            batch::chd_logical_bytes(input),
+                            check_dir,
+                        )?;
                     }
                     opts.force = true;
                     let in_path = input.to_path_buf();
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     chd_to_cso_cancellable(&progress, in_path, output, opts, cancel.clone())
                         .await?;
                     finish_single(
@@ -2965,6 +3635,7 @@ async fn dispatch_command(
                 }
             }
             ChdCommands::Info(cmd) => {
+                reject_dat_xml(&cmd)?;
                 if cmd.keys.is_some() {
                     anyhow::bail!("--keys is only supported by nx and wup info");
                 }
@@ -2978,6 +3649,45 @@ async fn dispatch_command(
                 let info = rom_converto_lib::chd::info::read_info(resolved.path())?;
                 info_print::print(&rom_converto_lib::info::InfoResult::Chd(info), cmd.json)?;
             }
+            ChdCommands::Map(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let map = rom_converto_lib::chd::info::read_map(&cmd.input)?;
+                info_print::print_chd_map(&map);
+            }
+            ChdCommands::Meta(cmd) => {
+                ensure_input_exists(&cmd.input)?;
+                let edit = ChdMetaEdit {
+                    delete: cmd.delete.clone(),
+                    set_track: ChdTrackEdit::parse_many(&cmd.set)?,
+                    add: cmd
+                        .add
+                        .iter()
+                        .map(|entry| {
+                            entry
+                                .split_once('=')
+                                .map(|(tag, text)| (tag.to_string(), text.to_string()))
+                                .ok_or_else(|| {
+                                    anyhow::anyhow!("--add expects TAG=TEXT, got {entry:?}")
+                                })
+                        })
+                        .collect::<anyhow::Result<_>>()?,
+                };
+                if dry_run {
+                    log::info!(
+                        "dry run: would edit the metadata of {}",
+                        cmd.input.display()
+                    );
+                    return Ok(());
+                }
+                let report = edit_chd_metadata(&cmd.input, &edit)?;
+                log::info!(
+                    "Rewrote metadata of {} ({}): SHA-1 {} -> {}",
+                    cmd.input.display(),
+                    report.tags.join(", "),
+                    report.old_sha1,
+                    report.new_sha1
+                );
+            }
         },
         Commands::Cso(inner) => match inner {
             CsoCommands::Compress(cmd) => {
@@ -3080,7 +3790,7 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -3091,7 +3801,7 @@ async fn dispatch_command(
                     }
                     opts.force = true;
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     compress_to_cso_cancellable(
                         &progress,
                         input.to_path_buf(),
@@ -3174,7 +3884,7 @@ async fn dispatch_command(
                     }
                     let output = match decision {
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -3185,7 +3895,7 @@ async fn dispatch_command(
                     }
                     let in_path = input.to_path_buf();
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     decompress_from_cso_cancellable(
                         &progress,
                         in_path.clone(),
@@ -3227,7 +3937,19 @@ async fn dispatch_command(
                 let mut opts = ChdDvdOptions {
                     hunk_size: cmd.hunk_size.or(eff.hunk_size),
                     allow_zstd: cmd.zstd,
+                    lzma_level: cmd.lzma_level,
+                    zstd_level: cmd.zstd_level,
                     force: cmd.force,
+                    timing: cmd.timing,
+                    max_memory: cmd.max_memory.map(|mib| mib * 1024 * 1024),
+                    resume: false,
+                    comment: None,
+                    fast: false,
+                    sector_size: None,
+                    trim_trailing_zeros: false,
+                    strict: false,
+                    uncompressed_map: false,
+                    single_codec: None,
                 };
                 let output_dir = cmd.output_dir.clone().or_else(|| eff.output_dir.clone());
                 let report = cmd.report.clone().or_else(|| eff.report.clone());
@@ -3326,7 +4048,7 @@ async fn dispatch_command(
                             }
                         }
                         WriteDecision::Skip => {
-                            log_skipped(&output);
+                            log_skipped(&cmd.input, &output);
                             return Ok(());
                         }
                         WriteDecision::Write(p) => p,
@@ -3341,7 +4063,7 @@ async fn dispatch_command(
                     opts.force = true;
                     let in_path = input.to_path_buf();
                     let out_path = output.clone();
-                    let started = Instant::now();
+                    let started = logging::start_event(&cmd.input);
                     cso_to_chd_cancellable(&progress, in_path, output, mode, opts, cancel.clone())
                         .await?;
                     finish_single(
@@ -3355,6 +4077,7 @@ async fn dispatch_command(
                 }
             }
             CsoCommands::Info(cmd) => {
+                reject_dat_xml(&cmd)?;
                 if cmd.keys.is_some() {
                     anyhow::bail!("--keys is only supported by nx and wup info");
                 }
@@ -3389,7 +4112,7 @@ async fn dispatch_command(
                 }
                 let output_cue = match decision {
                     WriteDecision::Skip => {
-                        log_skipped(&cmd.output_cue);
+                        log_skipped(&cmd.input_cue, &cmd.output_cue);
                         return Ok(());
                     }
                     WriteDecision::Write(p) => p,
@@ -3418,7 +4141,7 @@ async fn dispatch_command(
                 }
                 let output = match decision {
                     WriteDecision::Skip => {
-                        log_skipped(&output);
+                        log_skipped(&cmd.input, &output);
                         return Ok(());
                     }
                     WriteDecision::Write(p) => p,
@@ -3457,7 +4180,7 @@ async fn dispatch_command(
                 }
                 let output = match decision {
                     WriteDecision::Skip => {
-                        log_skipped(&output);
+                        log_skipped(&cmd.input, &output);
                         return Ok(());
                     }
                     WriteDecision::Write(p) => p,
@@ -3782,6 +4505,42 @@ fn save_dol_banner(info: &rom_converto_lib::info::DolInfo, dir: &std::path::Path
     Ok(())
 }
 
+/// `--dat-xml` is CTR only; other consoles' info commands refuse it
+/// rather than ignoring it.
+fn reject_dat_xml(cmd: &InfoCommand) -> Result<()> {
+    if cmd.dat_xml.is_some() {
+        anyhow::bail!("--dat-xml is only supported by ctr info");
+    }
+    Ok(())
+}
+
+/// Hash `input` in one pass and write it as a one-game Logiqx DAT named
+/// after the file.
+fn write_dat_entry(
+    input: &Path,
+    dat: &Path,
+    progress: &dyn rom_converto_lib::util::ProgressReporter,
+    cancel: &rom_converto_lib::util::CancelToken,
+) -> Result<()> {
+    let size = std::fs::metadata(input)?.len();
+    let hashes = rom_converto_lib::util::hash_output_cancellable(input, progress, cancel)?;
+    let rom_name = input
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let game_name = input
+        .file_stem()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| rom_name.clone());
+    let mut out = std::io::BufWriter::new(std::fs::File::create(dat)?);
+    rom_converto_lib::dat::fixdat::write_game_dat_xml(
+        &mut out, &game_name, &rom_name, size, &hashes,
+    )?;
+    std::io::Write::flush(&mut out)?;
+    log::info!("Wrote {}", dat.display());
+    Ok(())
+}
+
 fn save_ctr_icon(info: &rom_converto_lib::info::CtrInfo, dir: &std::path::Path) -> Result<()> {
     let Some(img) = &info.icon else {
         log::warn!("No SMDH icon decoded; nothing to save");
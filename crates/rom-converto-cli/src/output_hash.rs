@@ -0,0 +1,39 @@
+//! `--hashes`: CRC32, MD5 and SHA-1 of every file a conversion writes, so
+//! outputs can be matched against No-Intro or Redump DATs without a
+//! separate `hash` run.
+
+use rom_converto_lib::util::{CancelToken, NoProgress, OutputHashes, hash_output_cancellable};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Hash `output` and log the digests when `--hashes` is set. Outputs
+/// that are not a single file (extracted trees, CDN directories) are
+/// skipped, and a read failure is a warning rather than failing the
+/// finished conversion.
+pub fn hash_output(output: &Path) -> Option<OutputHashes> {
+    if !ENABLED.load(Ordering::Relaxed) || !output.is_file() {
+        return None;
+    }
+    match hash_output_cancellable(output, &NoProgress, &CancelToken::new()) {
+        Ok(hashes) => {
+            log::info!(
+                "{}: crc32 {} md5 {} sha1 {}",
+                output.display(),
+                hashes.crc32,
+                hashes.md5,
+                hashes.sha1
+            );
+            Some(hashes)
+        }
+        Err(e) => {
+            log::warn!("Could not hash {}: {e}", output.display());
+            None
+        }
+    }
+}
@@ -8,6 +8,7 @@ use futures::StreamExt;
 use log::{debug, error, info, warn};
 use release::compare_latest_release_to_current_version;
 use std::env::temp_dir;
+use std::path::{Path, PathBuf};
 use tokio::fs::{File, create_dir_all};
 use tokio::io;
 use tokio::io::AsyncWriteExt;
@@ -17,15 +18,28 @@ mod constants;
 mod error;
 pub mod release;
 
-pub async fn cleanup_old_executable() -> anyhow::Result<()> {
-    let current_exe = std::env::current_exe()?;
-    let current_exe_parent = current_exe
+/// Where the running executable is parked during a self-update:
+/// `<stem>_old` next to it, so a renamed binary keeps its own backup
+/// instead of assuming `rom-converto`. The extension is dropped, which
+/// keeps the default name at the `rom-converto_old` older releases left.
+fn outdated_executable_path(current_exe: &Path) -> anyhow::Result<PathBuf> {
+    let parent = current_exe
         .parent()
         .ok_or_else(|| anyhow::anyhow!("current executable path has no parent directory"))?;
+    let mut name = current_exe
+        .file_stem()
+        .ok_or_else(|| anyhow::anyhow!("current executable path has no file name"))?
+        .to_os_string();
+    name.push("_old");
+    Ok(parent.join(name))
+}
+
+pub async fn cleanup_old_executable() -> anyhow::Result<()> {
+    let current_exe = std::env::current_exe()?;
 
     debug!("Checking if an outdated executable exists");
 
-    let outdated_exe = current_exe_parent.join("rom-converto_old");
+    let outdated_exe = outdated_executable_path(&current_exe)?;
 
     let exists = tokio::fs::try_exists(&outdated_exe).await?;
 
@@ -144,10 +158,7 @@ pub async fn self_update(github_api: &mut GithubApi) -> anyhow::Result<()> {
 
     let current_exe = std::env::current_exe()?;
 
-    let current_exe_renamed = current_exe
-        .parent()
-        .ok_or_else(|| anyhow::anyhow!("current executable path has no parent directory"))?
-        .join("rom-converto_old");
+    let current_exe_renamed = outdated_executable_path(&current_exe)?;
 
     tokio::fs::rename(&current_exe, &current_exe_renamed).await?;
 
@@ -167,3 +178,25 @@ pub async fn self_update(github_api: &mut GithubApi) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outdated_executable_follows_the_binary_name() {
+        let dir = Path::new("opt").join("bin");
+        assert_eq!(
+            outdated_executable_path(&dir.join("rom-converto")).unwrap(),
+            dir.join("rom-converto_old")
+        );
+        assert_eq!(
+            outdated_executable_path(&dir.join("rc-nightly")).unwrap(),
+            dir.join("rc-nightly_old")
+        );
+        assert_eq!(
+            outdated_executable_path(&dir.join("rom-converto.exe")).unwrap(),
+            dir.join("rom-converto_old")
+        );
+    }
+}
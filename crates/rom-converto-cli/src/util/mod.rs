@@ -1,4 +1,6 @@
 pub mod http;
+pub mod stdin;
+pub mod stdout;
 
 use crate::commands::ConflictPolicyArg;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
@@ -9,7 +11,7 @@ use rom_converto_lib::util::{
 };
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 /// Resolve an `--output-template` to a concrete output path joined under
 /// `base_dir`. Metadata is read best-effort: a failed or key-less read
@@ -182,17 +184,26 @@ pub fn resolve_output_dir(path: &Path, policy: ConflictPolicy) -> anyhow::Result
 
 pub fn ensure_input_exists(path: &std::path::Path) -> anyhow::Result<()> {
     if !path.exists() {
-        anyhow::bail!("input not found: {}", path.display());
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("input not found: {}", path.display()),
+        )
+        .into());
     }
     Ok(())
 }
 
 const PROGRESS_TEMPLATE: &str = "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, {eta})";
 
+/// Percentage step between progress lines when bars are replaced by logging.
+const PROGRESS_LOG_STEP: u64 = 10;
+
 /// Bridges the library's `ProgressReporter` trait to indicatif `ProgressBar`.
 pub struct IndicatifProgress {
     mp: MultiProgress,
     bar: Mutex<Option<ProgressBar>>,
+    log_percent: bool,
+    last_logged: AtomicU64,
 }
 
 impl IndicatifProgress {
@@ -200,6 +211,27 @@ impl IndicatifProgress {
         Self {
             mp,
             bar: Mutex::new(None),
+            log_percent: false,
+            last_logged: AtomicU64::new(0),
+        }
+    }
+
+    /// Also log an info line every [`PROGRESS_LOG_STEP`] percent. Used when
+    /// stderr is not a terminal, where the bar itself is hidden, so CI and
+    /// cron logs still show how far a long conversion got.
+    pub fn with_percent_logging(mut self) -> Self {
+        self.log_percent = true;
+        self
+    }
+
+    fn log_step(&self, bar: &ProgressBar) {
+        let Some(total) = bar.length().filter(|&t| t > 0) else {
+            return;
+        };
+        let percent =
+            (bar.position().min(total) * 100 / total) / PROGRESS_LOG_STEP * PROGRESS_LOG_STEP;
+        if percent > self.last_logged.fetch_max(percent, Ordering::Relaxed) {
+            log::info!("{}: {percent}%", bar.message());
         }
     }
 }
@@ -213,12 +245,16 @@ impl ProgressReporter for IndicatifProgress {
             .progress_chars("#>-");
         pg.set_style(style);
         pg.set_message(msg.to_string());
+        self.last_logged.store(0, Ordering::Relaxed);
         *self.bar.lock().unwrap() = Some(pg);
     }
 
     fn inc(&self, delta: u64) {
         if let Some(bar) = self.bar.lock().unwrap().as_ref() {
             bar.inc(delta);
+            if self.log_percent {
+                self.log_step(bar);
+            }
         }
     }
 
@@ -237,6 +273,7 @@ impl ProgressReporter for IndicatifProgress {
 
 const TOTAL_PROGRESS_TEMPLATE: &str =
     "{msg} [{wide_bar:.green/blue}] {binary_bytes}/{binary_total_bytes} ({eta})";
+const TOTAL_COUNT_TEMPLATE: &str = "{msg} [{wide_bar:.green/blue}] ({eta})";
 
 /// Terminal taskbar progress via OSC 9;4 (Windows Terminal, ConEmu).
 /// Unsupported terminals ignore the sequence; skipped entirely when stderr
@@ -263,6 +300,7 @@ pub struct TotalProgress {
     bar: Mutex<Option<ProgressBar>>,
     done: AtomicU64,
     total_files: AtomicU64,
+    by_count: AtomicBool,
     taskbar_percent: AtomicU64,
 }
 
@@ -273,25 +311,36 @@ impl TotalProgress {
             bar: Mutex::new(None),
             done: AtomicU64::new(0),
             total_files: AtomicU64::new(0),
+            by_count: AtomicBool::new(false),
             taskbar_percent: AtomicU64::new(0),
         }
     }
 
     /// Start (or restart, for a new command) the aggregate bar for
     /// `total_files` files totaling `total_bytes`. Added before any per-file
-    /// bar so it stays pinned above them. Zero bytes (empty or unknown-size
-    /// batch) falls back to a spinner instead of a stuck full bar.
+    /// bar so it stays pinned above them. Zero bytes with a known file count
+    /// (callers that only count items) measures by files instead, so the
+    /// ETA still works; an empty batch falls back to a spinner instead of a
+    /// stuck full bar.
     pub fn begin(&self, total_files: u64, total_bytes: u64) {
         self.done.store(0, Ordering::Relaxed);
         self.total_files.store(total_files, Ordering::Relaxed);
-        let pg = self.mp.add(ProgressBar::new(total_bytes));
-        let style = if total_bytes == 0 {
+        let by_count = total_bytes == 0 && total_files > 0;
+        self.by_count.store(by_count, Ordering::Relaxed);
+        let length = if by_count { total_files } else { total_bytes };
+        let pg = self.mp.add(ProgressBar::new(length));
+        let style = if length == 0 {
             ProgressStyle::default_spinner()
                 .template("{spinner} {msg}")
                 .expect("valid progress template")
         } else {
+            let template = if by_count {
+                TOTAL_COUNT_TEMPLATE
+            } else {
+                TOTAL_PROGRESS_TEMPLATE
+            };
             ProgressStyle::default_bar()
-                .template(TOTAL_PROGRESS_TEMPLATE)
+                .template(template)
                 .expect("valid progress template")
                 .progress_chars("#>-")
         };
@@ -310,7 +359,9 @@ impl TotalProgress {
         let total = self.total_files.load(Ordering::Relaxed);
         if let Some(bar) = self.bar.lock().unwrap().as_ref() {
             bar.set_message(format!("{done}/{total} files"));
-            if bar.length() == Some(0) {
+            if self.by_count.load(Ordering::Relaxed) {
+                bar.inc(1);
+            } else if bar.length() == Some(0) {
                 bar.tick();
             } else {
                 bar.inc(file_bytes);
@@ -519,6 +570,16 @@ mod tests {
         assert!(!cli.skip_space_check);
     }
 
+    #[test]
+    fn hashes_flag_parses_after_subcommand() {
+        use crate::commands::Cli;
+        use clap::Parser;
+        let cli = Cli::parse_from(["bin", "ctr", "compress", "game.cia", "--hashes"]);
+        assert!(cli.hashes);
+        let cli = Cli::parse_from(["bin", "ctr", "compress", "game.cia"]);
+        assert!(!cli.hashes);
+    }
+
     #[test]
     fn templated_output_dry_run_skips_mkdir() {
         let dir = tempdir().unwrap();
@@ -553,6 +614,24 @@ mod tests {
         MultiProgress::with_draw_target(indicatif::ProgressDrawTarget::hidden())
     }
 
+    #[test]
+    fn percent_logging_fires_once_per_step() {
+        let progress = IndicatifProgress::new(hidden_multi_progress()).with_percent_logging();
+        progress.start(1000, "Compressing");
+        progress.inc(50);
+        assert_eq!(progress.last_logged.load(Ordering::Relaxed), 0);
+        progress.inc(60);
+        assert_eq!(progress.last_logged.load(Ordering::Relaxed), 10);
+        progress.inc(5);
+        assert_eq!(progress.last_logged.load(Ordering::Relaxed), 10);
+        progress.inc(2000);
+        assert_eq!(progress.last_logged.load(Ordering::Relaxed), 100);
+        progress.finish();
+
+        progress.start(10, "Again");
+        assert_eq!(progress.last_logged.load(Ordering::Relaxed), 0);
+    }
+
     #[test]
     fn total_progress_advances_bytes_and_file_count() {
         let tp = TotalProgress::new(hidden_multi_progress());
@@ -565,6 +644,19 @@ mod tests {
         tp.finish();
     }
 
+    #[test]
+    fn total_progress_counts_files_when_sizes_are_unknown() {
+        let tp = TotalProgress::new(hidden_multi_progress());
+        tp.begin(4, 0);
+        tp.advance(0);
+        tp.advance(0);
+        let bar = tp.bar.lock().unwrap().as_ref().unwrap().clone();
+        assert_eq!(bar.length(), Some(4));
+        assert_eq!(bar.position(), 2);
+        assert_eq!(bar.message(), "2/4 files");
+        tp.finish();
+    }
+
     #[test]
     fn total_progress_zero_bytes_does_not_panic() {
         let tp = TotalProgress::new(hidden_multi_progress());
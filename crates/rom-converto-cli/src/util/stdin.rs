@@ -0,0 +1,60 @@
+//! `-` as an input path. The CTR readers seek around the container, so a
+//! piped image is first copied into a private scratch directory and read
+//! from there; the copy goes away with the directory.
+
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Input path that selects stdin.
+pub const STDIN_PATH: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+/// Stdin buffered to disk. The scratch directory is removed on drop, so an
+/// error or Ctrl-C leaves nothing behind.
+pub struct StagedStdin {
+    _dir: tempfile::TempDir,
+    path: PathBuf,
+}
+
+impl StagedStdin {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Copy all of stdin into a scratch file. Refuses an interactive terminal,
+/// where `-` almost always means the user forgot the pipe.
+pub async fn stage_stdin() -> anyhow::Result<StagedStdin> {
+    if std::io::stdin().is_terminal() {
+        anyhow::bail!("INPUT is `-` but stdin is a terminal; pipe the image in");
+    }
+    let dir = tempfile::Builder::new()
+        .prefix(".rom-converto-stdin.")
+        .tempdir()?;
+    let path = dir.path().join("stdin");
+    let mut file = tokio::fs::File::create(&path).await?;
+    let copied = tokio::io::copy(&mut tokio::io::stdin(), &mut file).await?;
+    file.flush().await?;
+    if copied == 0 {
+        anyhow::bail!("INPUT is `-` but stdin was empty");
+    }
+    log::debug!("Buffered {copied} bytes from stdin to {}", path.display());
+    Ok(StagedStdin { _dir: dir, path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_bare_dash_selects_stdin() {
+        assert!(is_stdin(Path::new("-")));
+        assert!(!is_stdin(Path::new("./-")));
+        assert!(!is_stdin(Path::new("-.cia")));
+        assert!(!is_stdin(Path::new("game.cci")));
+    }
+}
@@ -0,0 +1,44 @@
+//! `-` as an output path. DVD CHD extraction writes its ISO front to back
+//! and streams straight to stdout. The decrypt pipeline seeks back to patch
+//! hashes and headers, so it still writes a real file; that lands in a
+//! private scratch directory and is copied to stdout once complete.
+
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+/// Output path that selects stdout.
+pub const STDOUT_PATH: &str = "-";
+
+pub fn is_stdout(path: &Path) -> bool {
+    path == Path::new(STDOUT_PATH)
+}
+
+/// Scratch directory for a stdout-bound write. Removed on drop, so an
+/// error or Ctrl-C leaves nothing behind.
+pub fn scratch_dir() -> std::io::Result<tempfile::TempDir> {
+    tempfile::Builder::new()
+        .prefix(".rom-converto-stdout.")
+        .tempdir()
+}
+
+/// Stream a finished file to stdout.
+pub async fn copy_to_stdout(path: &Path) -> anyhow::Result<()> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut stdout = tokio::io::stdout();
+    tokio::io::copy(&mut file, &mut stdout).await?;
+    stdout.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_bare_dash_selects_stdout() {
+        assert!(is_stdout(Path::new("-")));
+        assert!(!is_stdout(Path::new("./-")));
+        assert!(!is_stdout(Path::new("-.iso")));
+        assert!(!is_stdout(Path::new("out.cia")));
+    }
+}
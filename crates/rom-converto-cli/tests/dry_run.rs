@@ -120,6 +120,7 @@ fn dry_run_missing_input_exits_nonzero() {
         .unwrap();
 
     assert!(!output.status.success(), "{}", combined(&output));
+    assert_eq!(output.status.code(), Some(3), "{}", combined(&output));
 }
 
 #[test]
@@ -0,0 +1,43 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn bin() -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_rom-converto"));
+    cmd.env("ROM_CONVERTO_NO_UPDATE_CHECK", "1");
+    cmd
+}
+
+fn combined(output: &Output) -> String {
+    let mut s = String::from_utf8_lossy(&output.stdout).into_owned();
+    s.push_str(&String::from_utf8_lossy(&output.stderr));
+    s
+}
+
+fn run_with_stdin(cmd: &mut Command, input: &[u8]) -> Output {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The command may refuse before reading stdin, closing the pipe early.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn ncch_only_decrypt_from_stdin_needs_an_output_directory() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = run_with_stdin(
+        bin()
+            .current_dir(dir.path())
+            .args(["ctr", "decrypt", "-", "--ncch-only"]),
+        b"not a cia",
+    );
+
+    assert!(!output.status.success(), "{}", combined(&output));
+    let text = combined(&output);
+    assert!(text.contains("--output-dir"), "{text}");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
@@ -210,6 +210,7 @@ const CTR_INFO = {
 	icon: null,
 	small_icon: null,
 	compressed: false,
+	ticket: null,
 };
 
 const DOL_INFO = {
@@ -348,6 +349,7 @@ const CHD_INFO = {
 	tracks: [{ number: 1, track_type: "MODE1_RAW", frames: 330_000, pregap: 0, subtype: null, pgtype: null, pgsub: null, postgap: null }],
 	metadata_tags: [],
 	version_string: "MAME compress 0.264",
+	comment: null,
 	dvd: null,
 };
 
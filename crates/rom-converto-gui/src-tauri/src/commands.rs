@@ -22,13 +22,14 @@ use rom_converto_lib::dat::{
 };
 use rom_converto_lib::info::{InfoOptions, InfoResult, read_info};
 use rom_converto_lib::nintendo::ctr::convert::{convert_rom_cancellable, derive_converted_path};
+use rom_converto_lib::nintendo::ctr::title_key::CommonKeyIndex;
 use rom_converto_lib::nintendo::ctr::verify::{CtrVerifyOptions, verify_ctr};
 use rom_converto_lib::nintendo::ctr::z3ds::{
     compress_rom_cancellable, decompress_rom_cancellable, derive_compressed_path,
     derive_decompressed_path,
 };
 use rom_converto_lib::nintendo::ctr::{
-    CdnToCiaOptions, convert_cdn_to_cia_cancellable, decrypt_rom_cancellable,
+    CdnNameFrom, CdnToCiaOptions, convert_cdn_to_cia_cancellable, decrypt_rom_cancellable,
     derive_decrypted_path, derive_encrypted_path, encrypt_rom_cancellable,
     generate_ticket_from_cdn,
 };
@@ -741,6 +742,19 @@ pub async fn cmd_cdn_to_cia(
         compress,
         output_dir: None,
         on_conflict: conflict_policy(on_conflict.as_deref()),
+        name_from: CdnNameFrom::Dir,
+        verify_after: false,
+        fix_content_size: false,
+        tmd_version: None,
+        common_key_index: CommonKeyIndex::Auto,
+        title_key: None,
+        raw_certs: false,
+        ticket: None,
+        output_template: None,
+        region: None,
+        content_concurrency: 1,
+        seed_retries: rom_converto_lib::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+        fail_fast: false,
     };
     let required: u64 = collect_all_files(&opts.cdn_dir, None)
         .map(|files| files.iter().map(|p| input_size(p)).sum())
@@ -764,6 +778,7 @@ pub async fn cmd_cdn_to_cia(
             opts,
             progress.as_ref(),
             total_progress.as_ref(),
+            &mut rom_converto_lib::util::Tally::new(),
             token,
         ))
         .map_err(err_to_string)
@@ -867,6 +882,7 @@ pub async fn cmd_decrypt_rom(
         rt.block_on(decrypt_rom_cancellable(
             &resolved_path,
             &output,
+            &Default::default(),
             progress.as_ref(),
             token,
         ))
@@ -1276,7 +1292,19 @@ pub async fn cmd_chd_compress(
     let opts = ChdDvdOptions {
         hunk_size,
         allow_zstd: zstd.unwrap_or(false),
+        lzma_level: None,
+        zstd_level: None,
         force: true,
+        timing: false,
+        max_memory: None,
+        resume: false,
+        comment: None,
+        fast: false,
+        sector_size: None,
+        trim_trailing_zeros: false,
+        strict: false,
+        uncompressed_map: false,
+        single_codec: None,
     };
     let in_bytes = input_size(&input_path);
     let record_input = input_path.clone();
@@ -1547,7 +1575,19 @@ pub async fn cmd_cso_to_chd(
     let opts = ChdDvdOptions {
         hunk_size,
         allow_zstd: zstd.unwrap_or(false),
+        lzma_level: None,
+        zstd_level: None,
         force: true,
+        timing: false,
+        max_memory: None,
+        resume: false,
+        comment: None,
+        fast: false,
+        sector_size: None,
+        trim_trailing_zeros: false,
+        strict: false,
+        uncompressed_map: false,
+        single_codec: None,
     };
     let in_bytes = input_size(&input_path);
     let record_input = input_path.clone();
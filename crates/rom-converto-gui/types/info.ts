@@ -57,6 +57,7 @@ export interface ChdInfo {
   }>;
   metadata_tags: Array<{ tag: string; length: number }>;
   version_string: string | null;
+  comment: string | null;
   dvd: { total_sectors: number; layer_class: string } | null;
 }
 
@@ -106,6 +107,20 @@ export interface CtrInfo {
   icon: Image | null;
   small_icon: Image | null;
   compressed: boolean;
+  ticket: CtrTicketInfo | null;
+}
+
+export interface CtrTicketInfo {
+  ticket_id: string;
+  console_id: number;
+  license_type: number;
+  eshop_account_id: number;
+  audit: number;
+  limits: Array<{
+    limit_type: number;
+    kind: "play_minutes" | "launch_count" | "unknown";
+    value: number;
+  }>;
 }
 
 export interface DolInfo {
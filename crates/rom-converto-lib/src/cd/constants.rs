@@ -2,6 +2,11 @@
 pub const SECTOR_SIZE: usize = 2352;
 pub const SUBCODE_SIZE: usize = 96;
 pub const FRAME_SIZE: usize = SECTOR_SIZE + SUBCODE_SIZE;
+/// User data of a cooked MODE1/MODE2 form 1 sector.
+pub const COOKED_SECTOR_SIZE: usize = 2048;
+/// Bin sector sizes the CD writer reads: cooked user data, raw sectors,
+/// and raw sectors followed by their subchannel.
+pub const CD_SECTOR_SIZES: [usize; 3] = [COOKED_SECTOR_SIZE, SECTOR_SIZE, FRAME_SIZE];
 pub const FRAMES_PER_HUNK: u32 = 8;
 pub const CD_HUNK_BYTES: u32 = FRAME_SIZE as u32 * FRAMES_PER_HUNK;
 
@@ -0,0 +1,275 @@
+//! Codec comparison for CD-mode CHDs.
+//!
+//! Runs every CD codec on an evenly spaced sample of hunks from a cue's
+//! bin and derives the size and codec time of each codec combination
+//! from the per-codec results, so one pass covers every row and no CHD
+//! is written.
+
+use crate::cd::{CD_HUNK_BYTES, FRAME_SIZE, FRAMES_PER_HUNK, SECTOR_SIZE};
+use crate::chd::compression::{CdCodecSet, HunkTrials};
+use crate::chd::error::{ChdError, ChdResult};
+use crate::cue::CueParser;
+use crate::util::{CancelToken, ProgressReporter, await_with_progress_cancel};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Hunks sampled when the caller does not say otherwise.
+pub const DEFAULT_BENCHMARK_HUNKS: usize = 64;
+
+/// A codec combination: which codec slots (0=CDLZ, 1=CDZL, 2=CDFL) are
+/// tried on audio hunks and on data hunks.
+struct Combination {
+    codecs: &'static str,
+    note: &'static str,
+    audio: [bool; 3],
+    data: [bool; 3],
+}
+
+const COMBINATIONS: [Combination; 5] = [
+    Combination {
+        codecs: "cdlz+cdzl+cdfl",
+        note: "default",
+        audio: [true, true, true],
+        data: [true, true, false],
+    },
+    Combination {
+        codecs: "cdzl+cdfl / cdlz+cdzl",
+        note: "--fast",
+        audio: [false, true, true],
+        data: [true, true, false],
+    },
+    Combination {
+        codecs: "cdlz+cdzl",
+        note: "no FLAC",
+        audio: [true, true, false],
+        data: [true, true, false],
+    },
+    Combination {
+        codecs: "cdzl+cdfl",
+        note: "no LZMA",
+        audio: [false, true, true],
+        data: [false, true, false],
+    },
+    Combination {
+        codecs: "cdzl",
+        note: "zlib only",
+        audio: [false, true, false],
+        data: [false, true, false],
+    },
+];
+
+/// Result of [`benchmark_cd_codecs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdBenchmark {
+    /// Hunks compressed.
+    pub hunks: usize,
+    /// Of those, hunks without a sync header, where CDFL was tried.
+    pub audio_hunks: usize,
+    /// Uncompressed size of the sampled hunks.
+    pub raw_bytes: u64,
+    pub rows: Vec<ChdBenchmarkRow>,
+}
+
+/// One codec combination's outcome over the sampled hunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdBenchmarkRow {
+    /// The codecs tried, e.g. `cdlz+cdzl+cdfl`.
+    pub codecs: &'static str,
+    /// What the combination corresponds to, e.g. `default` or `--fast`.
+    pub note: &'static str,
+    /// Sum of the smallest result per hunk, or the raw hunk when no
+    /// codec beat it.
+    pub compressed_bytes: u64,
+    /// Time spent in the combination's codec trials, on one thread.
+    pub codec_time: Duration,
+}
+
+impl ChdBenchmarkRow {
+    /// Compressed size as a percentage of `raw_bytes`.
+    pub fn ratio(&self, raw_bytes: u64) -> f64 {
+        if raw_bytes == 0 {
+            return 0.0;
+        }
+        self.compressed_bytes as f64 / raw_bytes as f64 * 100.0
+    }
+}
+
+/// Compress up to `sample_hunks` hunks spread evenly over the first bin
+/// of `cue_path` with each CD codec, and report size and codec time for
+/// every codec combination `chd compress` can use. Nothing is written.
+pub async fn benchmark_cd_codecs(
+    progress: &dyn ProgressReporter,
+    cue_path: &Path,
+    sample_hunks: usize,
+    cancel: CancelToken,
+) -> ChdResult<ChdBenchmark> {
+    let cue_sheet = CueParser::new(cue_path).parse().await?;
+    let Some(first) = cue_sheet.files.first() else {
+        return Err(ChdError::NoFileReferencedInCueSheet);
+    };
+    let cue_dir = cue_path.parent().unwrap_or(Path::new("."));
+    let bin_path = cue_dir.join(&first.filename);
+    let sectors = tokio::fs::metadata(&bin_path).await?.len() / SECTOR_SIZE as u64;
+    let hunk_indices = sample_hunk_indices(
+        sectors.div_ceil(FRAMES_PER_HUNK as u64),
+        sample_hunks.max(1),
+    );
+
+    progress.start(
+        hunk_indices.len() as u64,
+        &format!("Benchmarking CHD codecs on {} hunks", hunk_indices.len()),
+    );
+    let hunks_done = Arc::new(AtomicU64::new(0));
+    let hunks_done_bg = hunks_done.clone();
+    let cancel_bg = cancel.clone();
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<ChdBenchmark> {
+        let mut bin = std::fs::File::open(&bin_path)?;
+        let mut codecs = CdCodecSet::new(CD_HUNK_BYTES as usize, false)?;
+        let mut trials = Vec::with_capacity(hunk_indices.len());
+        for &index in &hunk_indices {
+            if cancel_bg.is_cancelled() {
+                return Err(ChdError::Cancelled);
+            }
+            let hunk = read_hunk(&mut bin, index, sectors)?;
+            trials.push(codecs.trial_hunk(&hunk)?);
+            hunks_done_bg.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(summarize(&trials))
+    });
+
+    await_with_progress_cancel(progress, &hunks_done, handle, &cancel, || {
+        ChdError::Cancelled
+    })
+    .await
+}
+
+/// `count` hunk indices spread evenly over `total`, or every hunk when
+/// there are no more than `count`.
+fn sample_hunk_indices(total: u64, count: usize) -> Vec<u64> {
+    if total <= count as u64 {
+        return (0..total).collect();
+    }
+    (0..count as u64)
+        .map(|i| i * total / count as u64)
+        .collect()
+}
+
+/// Hunk `index` of the bin, interleaved with zero subcode the way the
+/// CD writer feeds its codecs, zero-padded past the last sector.
+fn read_hunk(bin: &mut std::fs::File, index: u64, sectors: u64) -> ChdResult<Vec<u8>> {
+    let first_sector = index * FRAMES_PER_HUNK as u64;
+    let count = (sectors - first_sector).min(FRAMES_PER_HUNK as u64) as usize;
+    let mut sector_buf = vec![0u8; count * SECTOR_SIZE];
+    bin.seek(SeekFrom::Start(first_sector * SECTOR_SIZE as u64))?;
+    bin.read_exact(&mut sector_buf)?;
+
+    let mut hunk = vec![0u8; CD_HUNK_BYTES as usize];
+    for (s, sector) in sector_buf.chunks_exact(SECTOR_SIZE).enumerate() {
+        hunk[s * FRAME_SIZE..s * FRAME_SIZE + SECTOR_SIZE].copy_from_slice(sector);
+    }
+    Ok(hunk)
+}
+
+fn summarize(trials: &[HunkTrials]) -> ChdBenchmark {
+    let hunk_bytes = CD_HUNK_BYTES as usize;
+    let rows = COMBINATIONS
+        .iter()
+        .map(|combination| {
+            let mut compressed_bytes = 0u64;
+            let mut codec_time = Duration::ZERO;
+            for trial in trials {
+                let enabled = if trial.audio {
+                    combination.audio
+                } else {
+                    combination.data
+                };
+                let mut best = hunk_bytes;
+                for slot in (0..3).filter(|&slot| enabled[slot]) {
+                    codec_time += trial.times[slot];
+                    if let Some(size) = trial.sizes[slot] {
+                        best = best.min(size);
+                    }
+                }
+                compressed_bytes += best as u64;
+            }
+            ChdBenchmarkRow {
+                codecs: combination.codecs,
+                note: combination.note,
+                compressed_bytes,
+                codec_time,
+            }
+        })
+        .collect();
+    ChdBenchmark {
+        hunks: trials.len(),
+        audio_hunks: trials.iter().filter(|trial| trial.audio).count(),
+        raw_bytes: (trials.len() * hunk_bytes) as u64,
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::NoProgress;
+
+    #[test]
+    fn sample_spreads_over_the_image_and_caps_at_its_length() {
+        assert_eq!(sample_hunk_indices(3, 8), vec![0, 1, 2]);
+        assert_eq!(sample_hunk_indices(100, 4), vec![0, 25, 50, 75]);
+    }
+
+    #[tokio::test]
+    async fn audio_image_reports_every_combination() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin: Vec<u8> = (0..20 * SECTOR_SIZE)
+            .map(|i| ((i / 4) as f64 * 0.05).sin().mul_add(90.0, 128.0) as u8)
+            .collect();
+        std::fs::write(dir.path().join("disc.bin"), &bin).unwrap();
+        let cue = dir.path().join("disc.cue");
+        std::fs::write(
+            &cue,
+            "FILE \"disc.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n",
+        )
+        .unwrap();
+
+        let bench = benchmark_cd_codecs(&NoProgress, &cue, 2, CancelToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!((bench.hunks, bench.audio_hunks), (2, 2));
+        assert_eq!(bench.raw_bytes, 2 * CD_HUNK_BYTES as u64);
+        assert_eq!(bench.rows.len(), COMBINATIONS.len());
+        let default = &bench.rows[0];
+        for row in &bench.rows {
+            assert!(row.compressed_bytes <= bench.raw_bytes, "{}", row.codecs);
+            assert!(
+                default.compressed_bytes <= row.compressed_bytes,
+                "{}",
+                row.codecs
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_benchmark_stops() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("disc.bin"), vec![0u8; 8 * SECTOR_SIZE]).unwrap();
+        let cue = dir.path().join("disc.cue");
+        std::fs::write(
+            &cue,
+            "FILE \"disc.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+        )
+        .unwrap();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let err = benchmark_cd_codecs(&NoProgress, &cue, 4, cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ChdError::Cancelled));
+    }
+}
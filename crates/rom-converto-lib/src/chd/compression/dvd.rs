@@ -8,13 +8,68 @@
 //! compressor list contains zstd, and that silent incompatibility is
 //! the community pain this default avoids.
 
-use super::lzma::{LzmaDecoder, LzmaEncoder};
-use super::{ChdCompression, deflate_decompress_with, deflate_with_reset, tag_to_bytes};
+use super::lzma::{LZMA_LEVEL, LZMA_LEVEL_RANGE, LzmaDecoder, LzmaEncoder};
+use super::{
+    ChdCompression, CodecTimes, deflate_decompress_with, deflate_with_reset, tag_to_bytes,
+};
 use crate::chd::error::{ChdError, ChdResult};
+use std::time::Instant;
 
 /// chdman compresses zstd hunks at the maximum level; the level only
 /// affects ratio, never decode compatibility.
-const ZSTD_LEVEL: i32 = 19;
+pub(crate) const ZSTD_LEVEL: i32 = 19;
+/// Valid zstd levels for the `zstd` slot.
+pub(crate) const ZSTD_LEVEL_RANGE: std::ops::RangeInclusive<i32> = 1..=22;
+
+/// Compression levels for the plain `lzma` and `zstd` DVD codecs.
+/// Levels only trade speed for ratio; any level decodes with the
+/// same reader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DvdCodecLevels {
+    pub lzma: i32,
+    pub zstd: i32,
+}
+
+impl Default for DvdCodecLevels {
+    fn default() -> Self {
+        Self {
+            lzma: LZMA_LEVEL,
+            zstd: ZSTD_LEVEL,
+        }
+    }
+}
+
+impl DvdCodecLevels {
+    /// Apply optional overrides on top of the chdman defaults,
+    /// rejecting levels the codec would not accept.
+    pub fn new(lzma: Option<u32>, zstd: Option<i32>) -> ChdResult<Self> {
+        let mut levels = Self::default();
+        if let Some(level) = lzma {
+            levels.lzma = checked_level("lzma", level as i32, LZMA_LEVEL_RANGE)?;
+        }
+        if let Some(level) = zstd {
+            levels.zstd = checked_level("zstd", level, ZSTD_LEVEL_RANGE)?;
+        }
+        Ok(levels)
+    }
+}
+
+fn checked_level(
+    codec: &'static str,
+    level: i32,
+    range: std::ops::RangeInclusive<i32>,
+) -> ChdResult<i32> {
+    if range.contains(&level) {
+        Ok(level)
+    } else {
+        Err(ChdError::InvalidCodecLevel {
+            codec,
+            level,
+            min: *range.start(),
+            max: *range.end(),
+        })
+    }
+}
 
 /// Header compressor slots for a DVD CHD.
 pub(crate) fn dvd_compressors(allow_zstd: bool) -> [[u8; 4]; 4] {
@@ -34,21 +89,43 @@ pub(crate) struct DvdCodecSet {
     lzma: LzmaEncoder,
     deflate: flate2::Compress,
     zstd: Option<zstd::bulk::Compressor<'static>>,
+    codec_time: CodecTimes,
+    /// Header slot every hunk is limited to, see [`Self::only`].
+    only: Option<u8>,
 }
 
 impl DvdCodecSet {
-    pub fn new(hunk_bytes: usize, allow_zstd: bool) -> ChdResult<Self> {
+    pub fn new(hunk_bytes: usize, allow_zstd: bool, levels: DvdCodecLevels) -> ChdResult<Self> {
         Ok(Self {
-            lzma: LzmaEncoder::new(hunk_bytes)?,
+            lzma: LzmaEncoder::with_level(hunk_bytes, levels.lzma)?,
             deflate: flate2::Compress::new(flate2::Compression::best(), false),
             zstd: if allow_zstd {
-                Some(zstd::bulk::Compressor::new(ZSTD_LEVEL)?)
+                Some(zstd::bulk::Compressor::new(levels.zstd)?)
             } else {
                 None
             },
+            codec_time: CodecTimes::default(),
+            only: None,
         })
     }
 
+    /// Try only the codec in header slot `slot` (0=lzma, 1=zlib,
+    /// 2=zstd) on every hunk, storing the hunk raw when it does not
+    /// shrink.
+    pub fn only(mut self, slot: Option<u8>) -> Self {
+        self.only = slot;
+        self
+    }
+
+    fn tries(&self, slot: u8) -> bool {
+        self.only.is_none_or(|only| only == slot)
+    }
+
+    /// Codec trial time accumulated since the last call, reset to zero.
+    pub fn take_codec_time(&mut self) -> CodecTimes {
+        std::mem::take(&mut self.codec_time)
+    }
+
     /// Compress a hunk trying every enabled codec, return the
     /// smallest result as `(data, codec_slot)`. Slots match
     /// [`dvd_compressors`]; an incompressible hunk comes back
@@ -58,26 +135,42 @@ impl DvdCodecSet {
         let mut best_type = ChdCompression::None as u8;
         let best_len = |best: &Option<Vec<u8>>| best.as_ref().map_or(hunk.len(), |b| b.len());
 
-        if let Ok(result) = self.lzma.compress(hunk)
-            && result.len() < best_len(&best)
-        {
-            best_type = 0;
-            best = Some(result);
+        if self.tries(0) {
+            let started = Instant::now();
+            let lzma = self.lzma.compress(hunk);
+            self.codec_time[0] += started.elapsed();
+            if let Ok(result) = lzma
+                && result.len() < best_len(&best)
+            {
+                best_type = 0;
+                best = Some(result);
+            }
         }
 
-        if let Ok(result) = deflate_with_reset(&mut self.deflate, hunk)
-            && result.len() < best_len(&best)
-        {
-            best_type = 1;
-            best = Some(result);
+        if self.tries(1) {
+            let started = Instant::now();
+            let zlib = deflate_with_reset(&mut self.deflate, hunk);
+            self.codec_time[1] += started.elapsed();
+            if let Ok(result) = zlib
+                && result.len() < best_len(&best)
+            {
+                best_type = 1;
+                best = Some(result);
+            }
         }
 
-        if let Some(zstd) = self.zstd.as_mut()
-            && let Ok(result) = zstd.compress(hunk)
-            && result.len() < best_len(&best)
+        if self.tries(2)
+            && let Some(zstd) = self.zstd.as_mut()
         {
-            best_type = 2;
-            best = Some(result);
+            let started = Instant::now();
+            let compressed = zstd.compress(hunk);
+            self.codec_time[2] += started.elapsed();
+            if let Ok(result) = compressed
+                && result.len() < best_len(&best)
+            {
+                best_type = 2;
+                best = Some(result);
+            }
         }
 
         match best {
@@ -169,7 +262,7 @@ mod tests {
     #[test]
     fn best_of_round_trips_through_the_winning_codec() {
         let hunk = compressible_hunk(4096);
-        let mut set = DvdCodecSet::new(hunk.len(), true).unwrap();
+        let mut set = DvdCodecSet::new(hunk.len(), true, DvdCodecLevels::default()).unwrap();
         let (data, slot) = set.compress_hunk(&hunk).unwrap();
         assert!(data.len() < hunk.len());
 
@@ -188,7 +281,7 @@ mod tests {
     #[test]
     fn each_codec_round_trips() {
         let hunk = compressible_hunk(4096);
-        let mut set = DvdCodecSet::new(hunk.len(), true).unwrap();
+        let mut set = DvdCodecSet::new(hunk.len(), true, DvdCodecLevels::default()).unwrap();
 
         let lzma = set.lzma.compress(&hunk).unwrap();
         let back = LzmaDecoder::new(hunk.len())
@@ -204,10 +297,52 @@ mod tests {
         assert_eq!(zstd::decode_all(&zstd_out[..]).unwrap(), hunk);
     }
 
+    #[test]
+    fn custom_levels_decode_with_the_default_reader() {
+        let hunk = compressible_hunk(0x10000);
+        for lzma in [0, 9] {
+            let levels = DvdCodecLevels::new(Some(lzma), Some(1)).unwrap();
+            let mut set = DvdCodecSet::new(hunk.len(), true, levels).unwrap();
+            let lzma_out = set.lzma.compress(&hunk).unwrap();
+            let back = LzmaDecoder::new(hunk.len())
+                .unwrap()
+                .decompress(&lzma_out, hunk.len())
+                .unwrap();
+            assert_eq!(back, hunk);
+            let zstd_out = set.zstd.as_mut().unwrap().compress(&hunk).unwrap();
+            assert_eq!(zstd::decode_all(&zstd_out[..]).unwrap(), hunk);
+        }
+    }
+
+    #[test]
+    fn out_of_range_levels_are_rejected() {
+        assert_eq!(
+            DvdCodecLevels::new(None, None).unwrap(),
+            DvdCodecLevels::default()
+        );
+        assert!(matches!(
+            DvdCodecLevels::new(Some(10), None),
+            Err(ChdError::InvalidCodecLevel { codec: "lzma", .. })
+        ));
+        assert!(matches!(
+            DvdCodecLevels::new(None, Some(0)),
+            Err(ChdError::InvalidCodecLevel { codec: "zstd", .. })
+        ));
+    }
+
+    #[test]
+    fn plain_compressors_emit_non_cd_tags() {
+        use crate::chd::compression::ChdCompressor;
+        use crate::chd::compression::lzma::LzmaCompressor;
+        use crate::chd::compression::zstd::ZstdCompressor;
+        assert_eq!(&LzmaCompressor::default().tag_bytes(), b"lzma");
+        assert_eq!(&ZstdCompressor { level: 3 }.tag_bytes(), b"zstd");
+    }
+
     #[test]
     fn incompressible_hunk_stores_raw() {
         let hunk = xorshift_hunk(2048);
-        let mut set = DvdCodecSet::new(hunk.len(), false).unwrap();
+        let mut set = DvdCodecSet::new(hunk.len(), false, DvdCodecLevels::default()).unwrap();
         let (data, slot) = set.compress_hunk(&hunk).unwrap();
         assert_eq!(slot, ChdCompression::None as u8);
         assert_eq!(data, hunk);
@@ -7,7 +7,16 @@ use std::ptr;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)] // CHD spec codec
-pub struct LzmaCompressor;
+pub struct LzmaCompressor {
+    /// LZMA preset, 0..=9.
+    pub level: i32,
+}
+
+impl Default for LzmaCompressor {
+    fn default() -> Self {
+        Self { level: LZMA_LEVEL }
+    }
+}
 
 impl ChdCompressor for LzmaCompressor {
     fn name(&self) -> &'static str {
@@ -19,11 +28,14 @@ impl ChdCompressor for LzmaCompressor {
     }
 
     fn compress(&self, data: &[u8]) -> ChdResult<Vec<u8>> {
-        lzma_compress(data)
+        lzma_compress_with_level(data, self.level)
     }
 }
 
-const LZMA_LEVEL: i32 = 8;
+/// chdman's LZMA preset, used unless a caller picks another.
+pub(crate) const LZMA_LEVEL: i32 = 8;
+/// Valid LZMA presets.
+pub(crate) const LZMA_LEVEL_RANGE: std::ops::RangeInclusive<i32> = 0..=9;
 
 /// Upper bound for LZMA compressed output size.
 fn lzma_max_output_size(input_len: usize) -> usize {
@@ -32,11 +44,13 @@ fn lzma_max_output_size(input_len: usize) -> usize {
 
 /// Configure LZMA encoder properties matching chdman's configure_properties.
 /// Uses hunk_bytes as reduceSize (matching chdman which passes hunkbytes, not base data length).
-fn configure_props(hunk_bytes: usize) -> CLzmaEncProps {
+/// `reduceSize` caps the dictionary at the hunk size for every level, so
+/// the decoder's props (always built at [`LZMA_LEVEL`]) read any level.
+fn configure_props(hunk_bytes: usize, level: i32) -> CLzmaEncProps {
     unsafe {
         let mut props = CLzmaEncProps::default();
         LzmaEncProps_Init(&mut props);
-        props.level = LZMA_LEVEL;
+        props.level = level;
         props.reduceSize = hunk_bytes as u64;
         LzmaEncProps_Normalize(&mut props);
         props
@@ -66,12 +80,16 @@ unsafe impl Send for LzmaEncoder {}
 
 impl LzmaEncoder {
     pub fn new(hunk_bytes: usize) -> io::Result<Self> {
+        Self::with_level(hunk_bytes, LZMA_LEVEL)
+    }
+
+    pub fn with_level(hunk_bytes: usize, level: i32) -> io::Result<Self> {
         let alloc = Allocator::default();
         let handle = unsafe { LzmaEnc_Create(alloc.as_ref()) };
         if handle.is_null() {
             return Err(io::Error::other("Failed to create LZMA encoder"));
         }
-        let props = configure_props(hunk_bytes);
+        let props = configure_props(hunk_bytes, level);
         unsafe {
             let res = LzmaEnc_SetProps(handle, &props);
             if res != SZ_OK as i32 {
@@ -116,7 +134,11 @@ impl Drop for LzmaEncoder {
 }
 
 pub(crate) fn lzma_compress(data: &[u8]) -> ChdResult<Vec<u8>> {
-    let props = configure_props(CD_HUNK_BYTES as usize);
+    lzma_compress_with_level(data, LZMA_LEVEL)
+}
+
+pub(crate) fn lzma_compress_with_level(data: &[u8], level: i32) -> ChdResult<Vec<u8>> {
+    let props = configure_props(CD_HUNK_BYTES as usize, level);
     let alloc = Allocator::default();
 
     // Output buffer: compressed data can't be much larger than input
@@ -152,7 +174,7 @@ pub(crate) fn lzma_compress(data: &[u8]) -> ChdResult<Vec<u8>> {
 
 pub(crate) fn lzma_decompress(data: &[u8], expected_len: usize) -> ChdResult<Vec<u8>> {
     // Reconstruct the same props that were used during compression (hunk_bytes, not data length)
-    let props = configure_props(CD_HUNK_BYTES as usize);
+    let props = configure_props(CD_HUNK_BYTES as usize, LZMA_LEVEL);
     let props_encoded = encode_props(&props);
     let alloc = Allocator::default();
 
@@ -199,7 +221,7 @@ unsafe impl Send for LzmaDecoder {}
 
 impl LzmaDecoder {
     pub fn new(hunk_bytes: usize) -> io::Result<Self> {
-        let props = configure_props(hunk_bytes);
+        let props = configure_props(hunk_bytes, LZMA_LEVEL);
         Self::with_props(&encode_props(&props))
     }
 
@@ -12,6 +12,7 @@ use flate2::read::DeflateDecoder;
 use flate2::write::DeflateEncoder;
 use std::fmt::Debug;
 use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
 
 const CD_SHORT_HUNK_LIMIT: usize = 0x1_0000;
 const CD_ECC_DIVISOR: usize = 8;
@@ -27,12 +28,65 @@ pub mod lzma;
 pub mod zlib;
 pub mod zstd;
 
+/// Wall time spent in each codec trial, indexed like the header
+/// compressor slots. Codec sets accumulate into it per hunk and the
+/// writer sums it across workers for the timing breakdown.
+pub(crate) type CodecTimes = [Duration; 4];
+
 pub const fn tag_to_bytes(tag: &str) -> [u8; 4] {
     let bytes = tag.as_bytes();
     assert!(bytes.len() == 4, "tag must be exactly 4 bytes");
     [bytes[0], bytes[1], bytes[2], bytes[3]]
 }
 
+/// A codec `--single-codec` can force every hunk through. The first
+/// three are the CD-mode codecs, the others the DVD-mode ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChdCodec {
+    Cdlz,
+    Cdzl,
+    Cdfl,
+    Lzma,
+    Zlib,
+    Zstd,
+}
+
+impl ChdCodec {
+    pub const ALL: [ChdCodec; 6] = [
+        Self::Cdlz,
+        Self::Cdzl,
+        Self::Cdfl,
+        Self::Lzma,
+        Self::Zlib,
+        Self::Zstd,
+    ];
+
+    /// The codec's tag in the header compressor list.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cdlz => "cdlz",
+            Self::Cdzl => "cdzl",
+            Self::Cdfl => "cdfl",
+            Self::Lzma => "lzma",
+            Self::Zlib => "zlib",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+impl std::str::FromStr for ChdCodec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(text))
+            .ok_or_else(|| {
+                format!("unknown codec {text:?}; expected cdlz, cdzl, cdfl, lzma, zlib, or zstd")
+            })
+    }
+}
+
 // IMPORTANT: These values map to positions in the header, not codec IDs
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(dead_code)] // CHD spec values: Self_ and Parent are used in map constants.
@@ -192,6 +246,17 @@ fn write_cd_header(buf: &mut [u8], ecc_bytes: usize, base_len: usize, complen_by
     }
 }
 
+/// Per-codec outcome of [`CdCodecSet::trial_hunk`], indexed by codec
+/// slot (0=CDLZ, 1=CDZL, 2=CDFL). A `None` size is a codec that was not
+/// tried or failed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HunkTrials {
+    /// The hunk has no sync header in its first sector, so CDFL was tried.
+    pub audio: bool,
+    pub sizes: [Option<usize>; 3],
+    pub times: [Duration; 3],
+}
+
 /// Persistent codec state for CD hunk compression, matching chdman's approach
 /// of reusing encoder instances across hunks rather than creating new ones each time.
 pub(crate) struct CdCodecSet {
@@ -199,18 +264,53 @@ pub(crate) struct CdCodecSet {
     cdlz_subcode_deflate: flate2::Compress,
     cdzl_base_deflate: flate2::Compress,
     cdzl_subcode_deflate: flate2::Compress,
+    codec_time: CodecTimes,
+    fast: bool,
+    /// Header slot every hunk is limited to, see [`Self::only`].
+    only: Option<u8>,
 }
 
 impl CdCodecSet {
-    pub fn new(hunk_bytes: usize) -> io::Result<Self> {
+    /// With `fast` set, audio hunks skip the CDLZ trial (LZMA rarely
+    /// beats FLAC on PCM and is the slowest codec) and data hunks keep
+    /// CDLZ and CDZL, which is all they ever try.
+    pub fn new(hunk_bytes: usize, fast: bool) -> io::Result<Self> {
         Ok(Self {
             lzma: LzmaEncoder::new(hunk_bytes)?,
             cdlz_subcode_deflate: flate2::Compress::new(Compression::best(), false),
             cdzl_base_deflate: flate2::Compress::new(Compression::best(), false),
             cdzl_subcode_deflate: flate2::Compress::new(Compression::best(), false),
+            codec_time: CodecTimes::default(),
+            fast,
+            only: None,
         })
     }
 
+    /// Try only the codec in header slot `slot` (0=CDLZ, 1=CDZL,
+    /// 2=CDFL) on every hunk, audio or data, falling back to storing
+    /// the hunk raw when it does not shrink. Overrides `fast`.
+    pub fn only(mut self, slot: Option<u8>) -> Self {
+        self.only = slot;
+        self
+    }
+
+    /// Whether `compress_hunk` tries the codec in `slot` on a hunk.
+    fn tries(&self, slot: u8, audio: bool) -> bool {
+        match self.only {
+            Some(only) => only == slot,
+            None => match slot {
+                0 => !(self.fast && audio),
+                1 => true,
+                _ => audio,
+            },
+        }
+    }
+
+    /// Codec trial time accumulated since the last call, reset to zero.
+    pub fn take_codec_time(&mut self) -> CodecTimes {
+        std::mem::take(&mut self.codec_time)
+    }
+
     /// Compress a CD hunk trying all codecs, return best result.
     /// Returns `(compressed_data, codec_index)` where codec_index maps to the
     /// header codec slots (0=CDLZ, 1=CDZL, 2=CDFL).
@@ -232,48 +332,66 @@ impl CdCodecSet {
 
         let best_len = |best: &Option<Vec<u8>>| best.as_ref().map_or(hunk.len(), |b| b.len());
 
-        // Try CDLZ (LZMA base + deflate subcode).
-        if let Ok(result) = self.compress_cdlz(
-            &base,
-            &subcode,
-            &ecc_flags,
-            header_bytes,
-            ecc_bytes,
-            complen_bytes,
-        ) && result.len() < best_len(&best)
-        {
-            best_type = 0;
-            best = Some(result);
+        // Try CDLZ (LZMA base + deflate subcode). Fast mode leaves
+        // audio hunks to CDFL and CDZL.
+        if self.tries(0, cdfl_candidate) {
+            let started = Instant::now();
+            let cdlz = self.compress_cdlz(
+                &base,
+                &subcode,
+                &ecc_flags,
+                header_bytes,
+                ecc_bytes,
+                complen_bytes,
+            );
+            self.codec_time[0] += started.elapsed();
+            if let Ok(result) = cdlz
+                && result.len() < best_len(&best)
+            {
+                best_type = 0;
+                best = Some(result);
+            }
         }
 
         // Try CDZL (deflate base + deflate subcode).
-        if let Ok(result) = self.compress_cdzl(
-            &base,
-            &subcode,
-            &ecc_flags,
-            header_bytes,
-            ecc_bytes,
-            complen_bytes,
-        ) && result.len() < best_len(&best)
-        {
-            best_type = 1;
-            best = Some(result);
+        if self.tries(1, cdfl_candidate) {
+            let started = Instant::now();
+            let cdzl = self.compress_cdzl(
+                &base,
+                &subcode,
+                &ecc_flags,
+                header_bytes,
+                ecc_bytes,
+                complen_bytes,
+            );
+            self.codec_time[1] += started.elapsed();
+            if let Ok(result) = cdzl
+                && result.len() < best_len(&best)
+            {
+                best_type = 1;
+                best = Some(result);
+            }
         }
 
-        // Try CDFL only for audio tracks (no CD sync header in first sector).
-        if cdfl_candidate
-            && let Ok(result) = self.compress_cdfl(
+        // Try CDFL only for audio tracks (no CD sync header in first
+        // sector), unless it is the single codec.
+        if self.tries(2, cdfl_candidate) {
+            let started = Instant::now();
+            let cdfl = self.compress_cdfl(
                 &base,
                 &subcode,
                 &ecc_flags,
                 header_bytes,
                 ecc_bytes,
                 complen_bytes,
-            )
-            && result.len() < best_len(&best)
-        {
-            best_type = 2;
-            best = Some(result);
+            );
+            self.codec_time[2] += started.elapsed();
+            if let Ok(result) = cdfl
+                && result.len() < best_len(&best)
+            {
+                best_type = 2;
+                best = Some(result);
+            }
         }
 
         match best {
@@ -282,6 +400,56 @@ impl CdCodecSet {
         }
     }
 
+    /// Run every codec [`Self::compress_hunk`] would consider on `hunk`
+    /// and report each one's size and time instead of keeping the best.
+    /// CDFL is only tried on audio hunks, as in the writer.
+    pub fn trial_hunk(&mut self, hunk: &[u8]) -> ChdResult<HunkTrials> {
+        let (frames, mut base, subcode) = split_cd_frames(hunk)?;
+        let (header_bytes, ecc_bytes, complen_bytes) = cd_header_sizes(hunk.len(), frames);
+        let audio = base.len() >= 12 && base[..12] != CD_SYNC_HEADER;
+        let ecc_flags = strip_ecc_from_base(&mut base, frames, ecc_bytes);
+
+        let mut trials = HunkTrials {
+            audio,
+            ..HunkTrials::default()
+        };
+        for slot in 0..3 {
+            if slot == 2 && !audio {
+                continue;
+            }
+            let started = Instant::now();
+            let result = match slot {
+                0 => self.compress_cdlz(
+                    &base,
+                    &subcode,
+                    &ecc_flags,
+                    header_bytes,
+                    ecc_bytes,
+                    complen_bytes,
+                ),
+                1 => self.compress_cdzl(
+                    &base,
+                    &subcode,
+                    &ecc_flags,
+                    header_bytes,
+                    ecc_bytes,
+                    complen_bytes,
+                ),
+                _ => self.compress_cdfl(
+                    &base,
+                    &subcode,
+                    &ecc_flags,
+                    header_bytes,
+                    ecc_bytes,
+                    complen_bytes,
+                ),
+            };
+            trials.times[slot] = started.elapsed();
+            trials.sizes[slot] = result.ok().map(|data| data.len());
+        }
+        Ok(trials)
+    }
+
     fn compress_cdlz(
         &mut self,
         base: &[u8],
@@ -603,4 +771,54 @@ mod tests {
         .unwrap();
         assert_eq!(decompressed, hunk);
     }
+
+    #[test]
+    fn fast_mode_skips_cdlz_on_audio_hunks() {
+        let hunk_bytes = 8 * FRAME_SIZE;
+        let mut audio = vec![0u8; hunk_bytes];
+        for (i, b) in audio.iter_mut().enumerate() {
+            *b = ((i * 7) % 251) as u8;
+        }
+
+        let mut fast = CdCodecSet::new(hunk_bytes, true).unwrap();
+        let (_, codec) = fast.compress_hunk(&audio).unwrap();
+        assert_ne!(codec, 0);
+        assert_eq!(fast.take_codec_time()[0], Duration::ZERO);
+
+        let mut data = audio.clone();
+        data[..12].copy_from_slice(&CD_SYNC_HEADER);
+        let mut full = CdCodecSet::new(hunk_bytes, false).unwrap();
+        assert_eq!(
+            fast.compress_hunk(&data).unwrap(),
+            full.compress_hunk(&data).unwrap()
+        );
+    }
+
+    #[test]
+    fn single_codec_is_the_only_one_tried_on_audio_and_data() {
+        let hunk_bytes = 8 * FRAME_SIZE;
+        let audio: Vec<u8> = (0..hunk_bytes).map(|i| ((i * 7) % 251) as u8).collect();
+        let mut data = audio.clone();
+        data[..12].copy_from_slice(&CD_SYNC_HEADER);
+
+        for slot in 0..3u8 {
+            let mut set = CdCodecSet::new(hunk_bytes, true).unwrap().only(Some(slot));
+            for hunk in [&audio, &data] {
+                let (_, codec) = set.compress_hunk(hunk).unwrap();
+                assert!(codec == slot || codec == ChdCompression::None as u8);
+                let times = set.take_codec_time();
+                for other in (0..3).filter(|&other| other != slot as usize) {
+                    assert_eq!(times[other], Duration::ZERO, "slot {slot} tried {other}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn codec_names_parse_case_insensitively() {
+        for codec in ChdCodec::ALL {
+            assert_eq!(codec.name().to_uppercase().parse::<ChdCodec>(), Ok(codec));
+        }
+        assert!("huff".parse::<ChdCodec>().is_err());
+    }
 }
@@ -3,7 +3,18 @@ use crate::chd::error::ChdResult;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)] // CHD spec codec
-pub struct ZstdCompressor;
+pub struct ZstdCompressor {
+    /// zstd level, 1..=22.
+    pub level: i32,
+}
+
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        Self {
+            level: super::dvd::ZSTD_LEVEL,
+        }
+    }
+}
 
 impl ChdCompressor for ZstdCompressor {
     fn name(&self) -> &'static str {
@@ -15,6 +26,6 @@ impl ChdCompressor for ZstdCompressor {
     }
 
     fn compress(&self, data: &[u8]) -> ChdResult<Vec<u8>> {
-        Ok(zstd::encode_all(data, 0)?)
+        Ok(zstd::encode_all(data, self.level)?)
     }
 }
@@ -0,0 +1,173 @@
+//! Duplicate CHD detection for batch compress. Two CHDs whose headers
+//! carry the same overall SHA-1 hold the same raw data and hashed
+//! metadata, but unhashed metadata such as a `CMNT` comment can still
+//! differ, so candidates are byte-compared before the later copies
+//! become hard links to the first.
+
+use crate::chd::error::{ChdError, ChdResult};
+use crate::chd::models::{CHD_V5_HEADER_SIZE, ChdHeaderV5, ChdVersion, SHA1_BYTES};
+use crate::util::{publish_temp, scratch_output_path};
+use binrw::BinRead;
+use std::collections::HashMap;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+/// A CHD that duplicates an earlier one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdDuplicate {
+    pub path: PathBuf,
+    /// The first CHD in the input order with the same bytes.
+    pub original: PathBuf,
+    pub bytes: u64,
+    /// Whether `path` was replaced with a hard link to `original`.
+    pub linked: bool,
+}
+
+/// The overall SHA-1 stored in a CHD v5 header.
+pub fn chd_header_sha1(path: &Path) -> ChdResult<[u8; SHA1_BYTES]> {
+    let mut buf = [0u8; CHD_V5_HEADER_SIZE as usize];
+    std::fs::File::open(path)?.read_exact(&mut buf)?;
+    let header = ChdHeaderV5::read(&mut Cursor::new(&buf))?;
+    if header.version != ChdVersion::V5 {
+        return Err(ChdError::UnsupportedChdVersion);
+    }
+    Ok(header.sha1)
+}
+
+/// Find CHDs in `paths` that are byte-for-byte copies of an earlier one.
+/// Header SHA-1 and file size pick the candidates; only those are read
+/// in full, since unhashed metadata such as a `CMNT` comment can differ
+/// at the same length. With `link`, each duplicate is replaced by a
+/// hard link to its original through a sibling temp file and a rename,
+/// so a failed link leaves the duplicate in place; the failure is
+/// logged and the entry reported with `linked` unset.
+pub fn dedupe_chds(paths: &[PathBuf], link: bool) -> ChdResult<Vec<ChdDuplicate>> {
+    let mut seen: HashMap<([u8; SHA1_BYTES], u64), Vec<&PathBuf>> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for path in paths {
+        let sha1 = chd_header_sha1(path)?;
+        let bytes = std::fs::metadata(path)?.len();
+        let candidates = seen.entry((sha1, bytes)).or_default();
+        let mut original = None;
+        for candidate in candidates.iter() {
+            if same_contents(candidate, path)? {
+                original = Some(*candidate);
+                break;
+            }
+        }
+        let Some(original) = original else {
+            candidates.push(path);
+            continue;
+        };
+        let linked = link
+            && match replace_with_hard_link(original, path) {
+                Ok(()) => true,
+                Err(err) => {
+                    log::warn!(
+                        "Could not link {} to {}: {err}",
+                        path.display(),
+                        original.display()
+                    );
+                    false
+                }
+            };
+        duplicates.push(ChdDuplicate {
+            path: path.clone(),
+            original: original.clone(),
+            bytes,
+            linked,
+        });
+    }
+    Ok(duplicates)
+}
+
+/// Whether two files of the same length hold the same bytes.
+fn same_contents(a: &Path, b: &Path) -> std::io::Result<bool> {
+    const CHUNK: usize = 64 * 1024;
+    let mut a = BufReader::new(std::fs::File::open(a)?);
+    let mut b = BufReader::new(std::fs::File::open(b)?);
+    let mut buf_a = vec![0u8; CHUNK];
+    let mut buf_b = vec![0u8; CHUNK];
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+fn replace_with_hard_link(original: &Path, duplicate: &Path) -> std::io::Result<()> {
+    let temp = scratch_output_path(duplicate)?.into_temp_path();
+    std::fs::remove_file(&temp)?;
+    std::fs::hard_link(original, &temp)?;
+    publish_temp(temp, duplicate, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_chd(dir: &Path, name: &str, sha1: u8, tail: &[u8]) -> PathBuf {
+        let header = ChdHeaderV5 {
+            length: CHD_V5_HEADER_SIZE,
+            version: ChdVersion::V5,
+            compressor_0: *b"lzma",
+            compressor_1: [0; 4],
+            compressor_2: [0; 4],
+            compressor_3: [0; 4],
+            logical_bytes: 0,
+            map_offset: 0,
+            meta_offset: 0,
+            hunk_bytes: 4096,
+            unit_bytes: 2048,
+            raw_sha1: [sha1; SHA1_BYTES],
+            sha1: [sha1; SHA1_BYTES],
+            parent_sha1: [0; SHA1_BYTES],
+        };
+        let mut bytes = Vec::new();
+        binrw::BinWrite::write(&header, &mut Cursor::new(&mut bytes)).unwrap();
+        bytes.extend_from_slice(tail);
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn later_copies_become_links_to_the_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = fake_chd(dir.path(), "a.chd", 1, b"data");
+        let b = fake_chd(dir.path(), "b.chd", 2, b"data");
+        let c = fake_chd(dir.path(), "c.chd", 1, b"data");
+        // Same SHA-1 but a longer unhashed tail, as a comment would add.
+        let d = fake_chd(dir.path(), "d.chd", 1, b"data+comment");
+        // Same SHA-1 and size, but a different comment of the same length.
+        let e = fake_chd(dir.path(), "e.chd", 1, b"DATA");
+
+        let dupes = dedupe_chds(&[a.clone(), b, c.clone(), d, e], true).unwrap();
+        assert_eq!(dupes.len(), 1);
+        assert_eq!(dupes[0].path, c);
+        assert_eq!(dupes[0].original, a);
+        assert!(dupes[0].linked);
+
+        std::fs::write(&a, b"rewritten").unwrap();
+        assert_eq!(std::fs::read(&c).unwrap(), b"rewritten");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn report_only_leaves_the_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = fake_chd(dir.path(), "a.chd", 7, b"");
+        let b = fake_chd(dir.path(), "b.chd", 7, b"");
+
+        let dupes = dedupe_chds(&[a.clone(), b.clone()], false).unwrap();
+        assert_eq!(dupes.len(), 1);
+        assert!(!dupes[0].linked);
+        std::fs::write(&a, b"changed").unwrap();
+        assert_ne!(std::fs::read(&b).unwrap(), b"changed");
+    }
+}
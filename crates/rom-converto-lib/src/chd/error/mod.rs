@@ -25,6 +25,11 @@ pub enum ChdError {
     #[error("output already exists; pass --on-conflict overwrite to replace it")]
     ChdFileAlreadyExists,
 
+    /// A multi-disc set's `.m3u` playlist already exists and no overwrite
+    /// was requested.
+    #[error("playlist {} already exists; pass --force to replace it", .0.display())]
+    PlaylistAlreadyExists(std::path::PathBuf),
+
     /// The CUE sheet does not reference any files to compress.
     #[error("no files are referenced in the CUE sheet")]
     NoFileReferencedInCueSheet,
@@ -33,6 +38,15 @@ pub enum ChdError {
     #[error("invalid hunk size for CHD data")]
     InvalidHunkSize,
 
+    /// A requested codec level is outside the range the codec accepts.
+    #[error("{codec} level {level} is out of range ({min}..={max})")]
+    InvalidCodecLevel {
+        codec: &'static str,
+        level: i32,
+        min: i32,
+        max: i32,
+    },
+
     /// The raw ISO input size is not a multiple of the 2048-byte sector size.
     #[error(
         "input size {size} is not a multiple of 2048; not a 2048-byte-sector image \
@@ -40,10 +54,65 @@ pub enum ChdError {
     )]
     IsoNotSectorAligned { size: u64 },
 
+    /// A CD sector size override is not one the writer can store.
+    #[error("unsupported CD sector size {0}: expected 2048, 2352, or 2448")]
+    UnsupportedSectorSize(usize),
+
+    /// The bin size is not a multiple of the CD sector size it is read with.
+    #[error(
+        "bin size {size} is not a multiple of {sector_size}; the image was dumped \
+         with a different sector size (see --sector-size)"
+    )]
+    BinNotSectorAligned { size: u64, sector_size: usize },
+
+    /// Cooked 2048-byte input was given for a cue with a track that is
+    /// not a 2048-byte data mode.
+    #[error(
+        "track {track} is {mode}, but cooked 2048-byte sectors only hold MODE1/2048 or \
+         MODE2/2048 tracks"
+    )]
+    CookedTrackMode { track: u8, mode: &'static str },
+
+    /// The bin ends before the cue's last track has its minimum length,
+    /// so the track frame counts the CHD records would be wrong.
+    #[error(
+        "bin holds {bin_frames} frames but track {track} starts at frame {start} and needs \
+         at least {min_frames}; the bin is truncated or belongs to another cue"
+    )]
+    BinShorterThanCue {
+        track: u8,
+        start: u32,
+        bin_frames: u32,
+        min_frames: u32,
+    },
+
+    /// `--single-codec` named a codec the CHD mode being written does
+    /// not list in its header.
+    #[error(
+        "--single-codec {codec} is not a codec of this CHD mode; CD mode uses cdlz, cdzl, or cdfl and DVD mode lzma, zlib, or zstd"
+    )]
+    SingleCodecUnavailable { codec: &'static str },
+
     /// A `.cue` input was given with `--dvd`, which needs a flat `.iso` instead.
     #[error("DVD mode needs a flat .iso input; a .cue describes a CD-layout disc, drop --dvd")]
     DvdModeNeedsIso,
 
+    /// A CD-mode CHD was asked to extract to a single stream; it extracts
+    /// to a cue/bin pair instead.
+    #[error(
+        "only DVD-mode CHDs extract to a single stream; a CD-mode CHD extracts to a cue/bin pair"
+    )]
+    CdModeNotStreamable,
+
+    /// The extracted bin's file name is not valid UTF-8, so the generated
+    /// `.cue` cannot name it.
+    #[error("bin file name is not valid UTF-8, a .cue cannot reference it: {0}")]
+    NonUtf8BinName(String),
+
+    /// A metadata entry is longer than the 24-bit length field can record.
+    #[error("CHD metadata entry is {0} bytes; the format allows at most 16777215")]
+    MetadataTooLong(usize),
+
     /// Compressing a CHD map hunk failed.
     #[error("CHD map compression failed")]
     MapCompressionError,
@@ -92,6 +161,26 @@ pub enum ChdError {
     #[error("worker pool writer thread panicked")]
     WorkerPoolPanic,
 
+    /// A metadata tag is not 1-4 printable ASCII characters.
+    #[error("invalid CHD metadata tag {0:?}: expected 1-4 printable ASCII characters")]
+    InvalidMetadataTag(String),
+
+    /// The metadata entry describes the disc layout and cannot be deleted.
+    #[error("CHD metadata tag {0:?} describes the disc layout and cannot be deleted")]
+    ProtectedMetadataTag(String),
+
+    /// A track edit names a track the CHT2 metadata does not list.
+    #[error("track {0} is not in the CHD track metadata")]
+    TrackNotInMetadata(u8),
+
+    /// A track edit names a field the track does not carry.
+    #[error("track {track} has no {field} field to replace")]
+    UnknownTrackField { track: u8, field: String },
+
+    /// A track edit is not `TRACK:N` followed by `KEY:VALUE` fields.
+    #[error("invalid track edit {0:?}: expected TRACK:N followed by KEY:VALUE fields")]
+    InvalidTrackEdit(String),
+
     /// The operation was cancelled by the caller.
     #[error("operation cancelled")]
     Cancelled,
@@ -1,7 +1,10 @@
 //! `info` extractor for CHD files. Surfaces v5 header, hash triplet,
 //! per-track CHT2 metadata, optional DVD geometry, and the chdman build
-//! string when present.
+//! string and `CMNT` comment when present. [`read_map`] lists the
+//! decoded hunk map for `chd map`.
 
+use crate::chd::map::{COMPRESSION_NONE, COMPRESSION_PARENT, COMPRESSION_SELF};
+use crate::chd::models::CHD_METADATA_TAG_COMMENT;
 use crate::chd::reader::cue_generator::parse_chd_track_metadata;
 use crate::chd::reader::open_chd_sync;
 use anyhow::Result;
@@ -25,6 +28,9 @@ pub struct ChdInfo {
     pub metadata_tags: Vec<ChdMetadataTagSummary>,
     /// Chdman build string from the optional `VERS` metadata tag.
     pub version_string: Option<String>,
+    /// Free-form note from the `CMNT` metadata tag, written by
+    /// `chd compress --comment`.
+    pub comment: Option<String>,
     /// DVD-only fields derived when a `DVD ` metadata tag is present.
     pub dvd: Option<ChdDvdInfo>,
 }
@@ -91,7 +97,8 @@ pub fn read_info(path: &Path) -> Result<ChdInfo> {
     };
 
     let tracks = extract_tracks(&handle);
-    let version_string = extract_version_string(&handle);
+    let version_string = extract_text_tag(&handle, CHD_METADATA_TAG_VERS);
+    let comment = extract_text_tag(&handle, CHD_METADATA_TAG_COMMENT);
     let dvd = extract_dvd_info(&handle, logical_bytes);
 
     let metadata_tags = handle
@@ -118,18 +125,98 @@ pub fn read_info(path: &Path) -> Result<ChdInfo> {
         tracks,
         metadata_tags,
         version_string,
+        comment,
         dvd,
     })
 }
 
+/// How one hunk is stored, from its map entry's compression type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChdHunkStorage {
+    /// Compressed with the header's codec in this slot (0-3).
+    Codec { slot: u8, name: Option<String> },
+    /// Stored as raw hunk bytes.
+    Uncompressed,
+    /// Same data as the earlier hunk in `ChdMapEntry::offset`.
+    SelfRef,
+    /// Read from the parent CHD, at the unit in `ChdMapEntry::offset`.
+    ParentRef,
+}
+
+impl std::fmt::Display for ChdHunkStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Codec {
+                name: Some(name), ..
+            } => f.write_str(name),
+            Self::Codec { slot, name: None } => write!(f, "codec {slot}"),
+            Self::Uncompressed => f.write_str("none"),
+            Self::SelfRef => f.write_str("self"),
+            Self::ParentRef => f.write_str("parent"),
+        }
+    }
+}
+
+/// One decoded hunk map entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChdMapEntry {
+    pub hunk: u64,
+    pub storage: ChdHunkStorage,
+    /// Bytes the hunk takes in the file; 0 for self and parent references.
+    pub length: u32,
+    /// File offset of the hunk's data, or the referenced hunk or parent
+    /// unit for references.
+    pub offset: u64,
+    /// CRC16 of the decompressed hunk; 0 for references, which carry none.
+    pub crc16: u16,
+}
+
+/// Decode the hunk map of the CHD at `path`, one entry per hunk.
+pub fn read_map(path: &Path) -> Result<Vec<ChdMapEntry>> {
+    let handle = open_chd_sync(path).map_err(into_anyhow)?;
+    let header = &handle.header;
+    let compressors = [
+        &header.compressor_0,
+        &header.compressor_1,
+        &header.compressor_2,
+        &header.compressor_3,
+    ];
+    Ok(handle
+        .map
+        .iter()
+        .enumerate()
+        .map(|(hunk, entry)| {
+            let storage = match entry.compression {
+                COMPRESSION_NONE => ChdHunkStorage::Uncompressed,
+                COMPRESSION_SELF => ChdHunkStorage::SelfRef,
+                COMPRESSION_PARENT => ChdHunkStorage::ParentRef,
+                slot => ChdHunkStorage::Codec {
+                    slot,
+                    name: compressors
+                        .get(slot as usize)
+                        .and_then(|tag| fourcc_to_string(tag)),
+                },
+            };
+            ChdMapEntry {
+                hunk: hunk as u64,
+                storage,
+                length: entry.length,
+                offset: entry.offset,
+                crc16: entry.crc16,
+            }
+        })
+        .collect())
+}
+
 const CHD_METADATA_TAG_VERS: [u8; 4] = *b"VERS";
 const CHD_METADATA_TAG_DVD: [u8; 4] = *b"DVD ";
 
-fn extract_version_string(handle: &crate::chd::reader::SyncChdHandle) -> Option<String> {
+fn extract_text_tag(handle: &crate::chd::reader::SyncChdHandle, tag: [u8; 4]) -> Option<String> {
     handle
         .metadata
         .iter()
-        .find(|m| m.tag == CHD_METADATA_TAG_VERS)
+        .find(|m| m.tag == tag)
         .map(|m| {
             String::from_utf8_lossy(&m.data)
                 .trim_end_matches('\0')
@@ -232,4 +319,44 @@ mod tests {
     fn fourcc_rejects_non_printable() {
         assert_eq!(fourcc_to_string(&[0x01, 0x02, 0x03, 0x04]), None);
     }
+
+    #[tokio::test]
+    async fn read_map_lists_every_hunk_with_its_codec() {
+        use crate::chd::{ChdDvdOptions, convert_iso_to_chd};
+        use crate::util::{CancelToken, NoProgress};
+
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        let iso: Vec<u8> = (0..5 * 4096).map(|i| (i % 7) as u8).collect();
+        std::fs::write(&iso_path, &iso).unwrap();
+        let chd_path = dir.path().join("game.chd");
+        convert_iso_to_chd(
+            &NoProgress,
+            iso_path,
+            chd_path.clone(),
+            ChdDvdOptions::default(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let info = read_info(&chd_path).unwrap();
+        let map = read_map(&chd_path).unwrap();
+        assert_eq!(map.len() as u64, info.hunk_count);
+        for (n, entry) in map.iter().enumerate() {
+            assert_eq!(entry.hunk, n as u64);
+            let ChdHunkStorage::Codec { slot, name } = &entry.storage else {
+                panic!("hunk {n} stored as {}", entry.storage);
+            };
+            assert_eq!(
+                name.as_deref(),
+                Some(info.compressors[*slot as usize].as_str())
+            );
+            assert!(entry.length > 0 && entry.offset > 0);
+        }
+        assert!(
+            map.windows(2)
+                .all(|w| w[0].offset + w[0].length as u64 == w[1].offset)
+        );
+    }
 }
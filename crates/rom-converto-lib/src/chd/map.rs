@@ -30,6 +30,10 @@ const HUFFMAN_CODES: usize = 16;
 const HUFFMAN_MAX_BITS: u8 = 8;
 const MAP_ENTRY_SIZE: usize = 12;
 const MAP_HEADER_SIZE: usize = 16;
+/// Set in the map header's reserved last byte when the entries follow
+/// as raw 12-byte records instead of the Huffman-coded stream. chdman
+/// always writes 0 there and cannot read such a map.
+const MAP_FLAG_RAW: u8 = 1;
 const RLE_SMALL_BASE: u32 = 3;
 const RLE_SMALL_MAX_EXTRA: u32 = 15;
 const RLE_LARGE_BASE: u32 = 3 + 16;
@@ -217,6 +221,35 @@ pub(crate) fn compress_v5_map(
     Ok(output)
 }
 
+/// The map as [`encode_raw_map`] lays it out, behind the usual 16-byte
+/// header with [`MAP_FLAG_RAW`] set. Bigger than [`compress_v5_map`]'s
+/// output but trivially inspectable, for debugging the map coder.
+pub(crate) fn encode_uncompressed_v5_map(entries: &[MapEntry]) -> Vec<u8> {
+    let rawmap = encode_raw_map(entries);
+    let mut output = vec![0u8; MAP_HEADER_SIZE];
+    BigEndian::write_u32(&mut output[0..4], rawmap.len() as u32);
+    write_u48_be(
+        &mut output[4..10],
+        entries.first().map_or(0, |entry| entry.offset),
+    );
+    BigEndian::write_u16(&mut output[10..12], crc16_ccitt(&rawmap));
+    output[15] = MAP_FLAG_RAW;
+    output.extend_from_slice(&rawmap);
+    output
+}
+
+fn decode_raw_map(rawmap: &[u8]) -> Vec<MapEntry> {
+    rawmap
+        .chunks_exact(MAP_ENTRY_SIZE)
+        .map(|raw| MapEntry {
+            compression: raw[0],
+            length: BigEndian::read_u24(&raw[1..4]),
+            offset: read_u48_be(&raw[4..10]),
+            crc16: BigEndian::read_u16(&raw[10..12]),
+        })
+        .collect()
+}
+
 fn push_symbol(list: &mut Vec<u8>, encoder: &mut HuffmanEncoder, value: u8) {
     list.push(value);
     encoder.histo_one(value);
@@ -581,47 +614,7 @@ pub(crate) struct HuffmanDecoder {
 impl HuffmanDecoder {
     /// Import Huffman tree from RLE-encoded bit-lengths (reverse of HuffmanEncoder::export_tree_rle)
     pub fn import_tree_rle(bits: &mut BitReader) -> ChdResult<Self> {
-        let num_bits: u8 = if HUFFMAN_MAX_BITS >= 16 {
-            5
-        } else if HUFFMAN_MAX_BITS >= 8 {
-            4
-        } else {
-            3
-        };
-
-        let mut bit_lengths = [0u8; HUFFMAN_CODES];
-        let mut idx = 0;
-
-        while idx < HUFFMAN_CODES {
-            let v = bits.read(num_bits)? as u8;
-            if v == 1 {
-                // Could be literal value 1 (encoded as pair of 1s) or RLE marker
-                let next = bits.read(num_bits)? as u8;
-                if next == 1 {
-                    // Literal value 1 with one occurrence.
-                    if idx < HUFFMAN_CODES {
-                        bit_lengths[idx] = 1;
-                        idx += 1;
-                    }
-                } else {
-                    // RLE: the repeated value is `next`, count is read(num_bits)+3
-                    let count = bits.read(num_bits)? as usize + 3;
-                    for _ in 0..count {
-                        if idx >= HUFFMAN_CODES {
-                            break;
-                        }
-                        bit_lengths[idx] = next;
-                        idx += 1;
-                    }
-                }
-            } else {
-                // Literal value (one occurrence)
-                bit_lengths[idx] = v;
-                idx += 1;
-            }
-        }
-
-        Self::from_bit_lengths(&bit_lengths)
+        Self::from_bit_lengths(&read_tree_rle(bits)?)
     }
 
     fn from_bit_lengths(bit_lengths: &[u8; HUFFMAN_CODES]) -> ChdResult<Self> {
@@ -684,6 +677,52 @@ impl HuffmanDecoder {
     }
 }
 
+/// Read the RLE-encoded per-symbol code lengths written by
+/// [`HuffmanEncoder::export_tree_rle`].
+fn read_tree_rle(bits: &mut BitReader) -> ChdResult<[u8; HUFFMAN_CODES]> {
+    let num_bits: u8 = if HUFFMAN_MAX_BITS >= 16 {
+        5
+    } else if HUFFMAN_MAX_BITS >= 8 {
+        4
+    } else {
+        3
+    };
+
+    let mut bit_lengths = [0u8; HUFFMAN_CODES];
+    let mut idx = 0;
+
+    while idx < HUFFMAN_CODES {
+        let v = bits.read(num_bits)? as u8;
+        if v == 1 {
+            // Could be literal value 1 (encoded as pair of 1s) or RLE marker
+            let next = bits.read(num_bits)? as u8;
+            if next == 1 {
+                // Literal value 1 with one occurrence.
+                if idx < HUFFMAN_CODES {
+                    bit_lengths[idx] = 1;
+                    idx += 1;
+                }
+            } else {
+                // RLE: the repeated value is `next`, count is read(num_bits)+3
+                let count = bits.read(num_bits)? as usize + 3;
+                for _ in 0..count {
+                    if idx >= HUFFMAN_CODES {
+                        break;
+                    }
+                    bit_lengths[idx] = next;
+                    idx += 1;
+                }
+            }
+        } else {
+            // Literal value (one occurrence)
+            bit_lengths[idx] = v;
+            idx += 1;
+        }
+    }
+
+    Ok(bit_lengths)
+}
+
 pub(crate) fn decompress_v5_map(
     map_data: &[u8],
     hunk_count: u32,
@@ -706,6 +745,14 @@ pub(crate) fn decompress_v5_map(
         return Err(ChdError::MapDecompressionError);
     }
 
+    if map_data[15] == MAP_FLAG_RAW {
+        let rawmap = &map_data[MAP_HEADER_SIZE..MAP_HEADER_SIZE + compressed_len];
+        if rawmap.len() != hunk_count as usize * MAP_ENTRY_SIZE || crc16_ccitt(rawmap) != map_crc {
+            return Err(ChdError::MapDecompressionError);
+        }
+        return Ok(decode_raw_map(rawmap));
+    }
+
     let compressed = map_data[MAP_HEADER_SIZE..MAP_HEADER_SIZE + compressed_len].to_vec();
     let mut bits = BitReader::new(compressed);
 
@@ -892,6 +939,228 @@ mod tests {
         println!("Roundtrip OK");
     }
 
+    /// The writer and reader share this one `MapEntry`, so self
+    /// references written by a deduplicating writer must survive the
+    /// same `compress_v5_map` / `decompress_v5_map` pair, including the
+    /// run-length `SELF_0` / `SELF_1` shortcuts.
+    #[test]
+    fn map_roundtrip_keeps_self_references() {
+        let hunk_bytes = 4096u32;
+        let unit_bytes = 2048u32;
+        let stored = |compression, length, offset, crc16| MapEntry {
+            compression,
+            length,
+            offset,
+            crc16,
+        };
+        let self_ref = |hunk: u64| stored(COMPRESSION_SELF, 0, hunk, 0);
+        let entries = vec![
+            stored(COMPRESSION_TYPE_0, 100, 229, 0x1111),
+            stored(COMPRESSION_TYPE_1, 200, 329, 0x2222),
+            self_ref(0),
+            self_ref(1),
+            self_ref(0),
+            stored(COMPRESSION_NONE, hunk_bytes, 529, 0x3333),
+        ];
+
+        let compressed = compress_v5_map(&entries, hunk_bytes, unit_bytes).unwrap();
+        let decompressed =
+            decompress_v5_map(&compressed, entries.len() as u32, hunk_bytes, unit_bytes).unwrap();
+
+        assert_eq!(decompressed.len(), entries.len());
+        for (i, (orig, dec)) in entries.iter().zip(&decompressed).enumerate() {
+            assert_eq!(orig.compression, dec.compression, "compression at hunk {i}");
+            assert_eq!(orig.length, dec.length, "length at hunk {i}");
+            assert_eq!(orig.offset, dec.offset, "offset at hunk {i}");
+            assert_eq!(orig.crc16, dec.crc16, "crc16 at hunk {i}");
+        }
+    }
+
+    #[test]
+    fn decompress_v5_map_rejects_bad_crc_and_truncation() {
+        let entries: Vec<MapEntry> = (0..8u32)
+            .map(|i| MapEntry {
+                compression: COMPRESSION_TYPE_0,
+                length: 1000,
+                offset: 229 + i as u64 * 1000,
+                crc16: i as u16,
+            })
+            .collect();
+        let mut compressed = compress_v5_map(&entries, 4096, 2048).unwrap();
+        assert!(decompress_v5_map(&compressed[..MAP_HEADER_SIZE - 1], 8, 4096, 2048).is_err());
+        assert!(decompress_v5_map(&compressed[..compressed.len() - 1], 8, 4096, 2048).is_err());
+
+        compressed[10] ^= 0xFF;
+        assert!(matches!(
+            decompress_v5_map(&compressed, 8, 4096, 2048),
+            Err(ChdError::MapDecompressionError)
+        ));
+    }
+
+    #[test]
+    fn uncompressed_map_round_trips_and_checks_its_crc() {
+        let entries = vec![
+            MapEntry {
+                compression: COMPRESSION_TYPE_2,
+                length: 700,
+                offset: 229,
+                crc16: 0xBEEF,
+            },
+            MapEntry {
+                compression: COMPRESSION_SELF,
+                length: 0,
+                offset: 0,
+                crc16: 0,
+            },
+            MapEntry {
+                compression: COMPRESSION_NONE,
+                length: 4096,
+                offset: 929,
+                crc16: 0x1234,
+            },
+        ];
+        let mut raw = encode_uncompressed_v5_map(&entries);
+        assert_eq!(raw.len(), MAP_HEADER_SIZE + entries.len() * MAP_ENTRY_SIZE);
+        assert_eq!(raw[15], MAP_FLAG_RAW);
+
+        let decoded = decompress_v5_map(&raw, 3, 4096, 2048).unwrap();
+        for (orig, dec) in entries.iter().zip(&decoded) {
+            assert_eq!(
+                (orig.compression, orig.length, orig.offset, orig.crc16),
+                (dec.compression, dec.length, dec.offset, dec.crc16)
+            );
+        }
+        assert!(decompress_v5_map(&raw, 4, 4096, 2048).is_err());
+
+        raw[MAP_HEADER_SIZE + 1] ^= 0xFF;
+        assert!(matches!(
+            decompress_v5_map(&raw, 3, 4096, 2048),
+            Err(ChdError::MapDecompressionError)
+        ));
+    }
+
+    fn encoder_for(histo: [u32; HUFFMAN_CODES]) -> HuffmanEncoder {
+        let mut encoder = HuffmanEncoder::new();
+        encoder.datahisto = histo;
+        encoder.compute_tree_from_histo().unwrap();
+        encoder
+    }
+
+    fn code_lengths(encoder: &HuffmanEncoder) -> [u8; HUFFMAN_CODES] {
+        std::array::from_fn(|code| encoder.nodes[code].num_bits)
+    }
+
+    /// Export the tree, encode every used symbol, then read both back
+    /// through the decoder side and check nothing changed.
+    fn assert_tree_round_trips(encoder: &HuffmanEncoder) {
+        let lengths = code_lengths(encoder);
+        let symbols: Vec<u8> = (0..HUFFMAN_CODES as u8)
+            .filter(|&code| lengths[code as usize] > 0)
+            .collect();
+
+        let mut writer = BitWriter::new();
+        encoder.export_tree_rle(&mut writer).unwrap();
+        for &symbol in &symbols {
+            encoder.encode_one(&mut writer, symbol);
+        }
+        // `decode_one` peeks a full HUFFMAN_MAX_BITS window.
+        writer.write(0, HUFFMAN_MAX_BITS);
+
+        let mut reader = BitReader::new(writer.finish());
+        let imported = read_tree_rle(&mut reader).unwrap();
+        assert_eq!(imported, lengths);
+        let decoder = HuffmanDecoder::from_bit_lengths(&imported).unwrap();
+        for &symbol in &symbols {
+            assert_eq!(decoder.decode_one(&mut reader).unwrap(), symbol);
+        }
+    }
+
+    fn assert_prefix_free(encoder: &HuffmanEncoder) {
+        let used: Vec<HuffNode> = encoder.nodes[..HUFFMAN_CODES]
+            .iter()
+            .copied()
+            .filter(|node| node.num_bits > 0)
+            .collect();
+        for (i, a) in used.iter().enumerate() {
+            assert!(a.num_bits <= HUFFMAN_MAX_BITS, "code longer than 8 bits");
+            for b in &used[i + 1..] {
+                let (short, long) = if a.num_bits <= b.num_bits {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                assert_ne!(
+                    long.bits >> (long.num_bits - short.num_bits),
+                    short.bits,
+                    "code {:0w$b} is a prefix of {:0v$b}",
+                    short.bits,
+                    long.bits,
+                    w = short.num_bits as usize,
+                    v = long.num_bits as usize,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn huffman_known_histogram_gets_expected_lengths() {
+        let mut histo = [0u32; HUFFMAN_CODES];
+        histo[..5].copy_from_slice(&[8, 4, 2, 1, 1]);
+        let encoder = encoder_for(histo);
+
+        let mut expected = [0u8; HUFFMAN_CODES];
+        expected[..5].copy_from_slice(&[1, 2, 3, 4, 4]);
+        assert_eq!(code_lengths(&encoder), expected);
+        assert_prefix_free(&encoder);
+        assert_tree_round_trips(&encoder);
+    }
+
+    #[test]
+    fn huffman_single_symbol_uses_one_bit() {
+        let mut histo = [0u32; HUFFMAN_CODES];
+        histo[7] = 42;
+        let encoder = encoder_for(histo);
+        assert_eq!(encoder.nodes[7].num_bits, 1);
+        assert_tree_round_trips(&encoder);
+    }
+
+    #[test]
+    fn huffman_random_histograms_stay_prefix_free_within_8_bits() {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..500 {
+            let mut histo = [0u32; HUFFMAN_CODES];
+            for count in &mut histo {
+                let r = next();
+                // Roughly a third of symbols unused, the rest spread over
+                // many orders of magnitude so the weight balancing kicks in.
+                if r % 3 != 0 {
+                    *count = ((r >> 8) % (1 << ((r >> 40) % 24))) as u32 + 1;
+                }
+            }
+            if histo.iter().all(|&c| c == 0) {
+                histo[0] = 1;
+            }
+
+            let encoder = encoder_for(histo);
+            for (code, &count) in histo.iter().enumerate() {
+                assert_eq!(
+                    count > 0,
+                    encoder.nodes[code].num_bits > 0,
+                    "histo {histo:?}"
+                );
+            }
+            assert_prefix_free(&encoder);
+            assert_tree_round_trips(&encoder);
+        }
+    }
+
     #[test]
     fn bits_for_value_zero_is_zero() {
         assert_eq!(bits_for_value(0), 0);
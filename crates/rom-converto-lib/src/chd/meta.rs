@@ -0,0 +1,365 @@
+//! In-place CHD metadata editing: add and delete tagged entries and
+//! rewrite CHT2 track fields without touching the compressed hunks.
+//!
+//! Metadata is a linked list of entries addressed by absolute offsets,
+//! so an edit writes the new chain at the end of the file and then
+//! rewrites the header's `meta_offset` and overall SHA-1. The old chain
+//! stays behind as a few hundred bytes of dead space, the same as
+//! chdman's `addmeta`/`delmeta`, and a crash before the header rewrite
+//! leaves the file as it was.
+
+use crate::chd::compute_overall_sha1;
+use crate::chd::error::{ChdError, ChdResult};
+use crate::chd::models::{
+    CHD_METADATA_FLAG_HASHED, CHD_METADATA_RESERVED_BYTES, CHD_METADATA_TAG_CD,
+    CHD_METADATA_TAG_DVD, CHD_V5_HEADER_SIZE, ChdMetadataHeader,
+};
+use crate::chd::reader::open_chd_sync;
+use crate::chd::writer::metadata::MetadataHash;
+use binrw::BinWrite;
+use sha1::{Digest, Sha1};
+use std::io::{Cursor, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const TRACK_KEY: &str = "TRACK";
+const MAX_METADATA_LEN: usize = 0xFF_FFFF;
+
+/// New `KEY:VALUE` fields for one track of the CHT2 metadata. Only
+/// fields the track already carries can be replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdTrackEdit {
+    pub track: u8,
+    pub fields: Vec<(String, String)>,
+}
+
+impl ChdTrackEdit {
+    /// Parse `TRACK:N` followed by `KEY:VALUE` fields. Each argument may
+    /// itself hold several whitespace-separated fields, so both
+    /// `TRACK:1 TYPE:MODE2_RAW` as one argument and as two work.
+    pub fn parse<S: AsRef<str>>(args: &[S]) -> ChdResult<Self> {
+        let joined = args.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(" ");
+        let invalid = || ChdError::InvalidTrackEdit(joined.clone());
+
+        let mut tokens = joined.split_whitespace().map(|t| t.split_once(':'));
+        let track = match tokens.next().flatten() {
+            Some((TRACK_KEY, n)) => n.parse::<u8>().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+        let fields = tokens
+            .map(|t| match t {
+                Some((key, value)) if !key.is_empty() && !value.is_empty() && key != TRACK_KEY => {
+                    Ok((key.to_ascii_uppercase(), value.to_string()))
+                }
+                _ => Err(invalid()),
+            })
+            .collect::<ChdResult<Vec<_>>>()?;
+        if fields.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { track, fields })
+    }
+
+    /// Split a run of fields into one edit per `TRACK:N` token, so
+    /// `TRACK:1 TYPE:MODE1_RAW TRACK:2 TYPE:AUDIO` edits two tracks.
+    pub fn parse_many<S: AsRef<str>>(args: &[S]) -> ChdResult<Vec<Self>> {
+        let tokens: Vec<&str> = args
+            .iter()
+            .flat_map(|arg| arg.as_ref().split_whitespace())
+            .collect();
+        let mut edits = Vec::new();
+        let mut start = 0;
+        for end in 1..=tokens.len() {
+            if end == tokens.len() || tokens[end].starts_with("TRACK:") {
+                edits.push(Self::parse(&tokens[start..end])?);
+                start = end;
+            }
+        }
+        Ok(edits)
+    }
+}
+
+/// The changes one [`edit_chd_metadata`] call applies, in order:
+/// deletions, track field rewrites, then additions.
+#[derive(Debug, Clone, Default)]
+pub struct ChdMetaEdit {
+    /// Tags whose entries are all removed. `CHT2` and `DVD ` describe the
+    /// disc layout and are refused.
+    pub delete: Vec<String>,
+    pub set_track: Vec<ChdTrackEdit>,
+    /// `(tag, text)` entries appended as hashed, NUL-terminated strings,
+    /// like chdman's `addmeta`.
+    pub add: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChdMetaEditReport {
+    pub old_sha1: String,
+    pub new_sha1: String,
+    /// Tags of the rewritten chain, in order.
+    pub tags: Vec<String>,
+}
+
+/// Turn a 1-4 character ASCII tag into its on-disk form, space-padded
+/// the way chdman pads `DVD `.
+pub fn parse_metadata_tag(tag: &str) -> ChdResult<[u8; 4]> {
+    if tag.is_empty() || tag.len() > 4 || !tag.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(ChdError::InvalidMetadataTag(tag.to_string()));
+    }
+    let mut out = *b"    ";
+    out[..tag.len()].copy_from_slice(tag.as_bytes());
+    Ok(out)
+}
+
+fn tag_label(tag: &[u8; 4]) -> String {
+    String::from_utf8_lossy(tag).trim_end().to_string()
+}
+
+/// Apply `edit` to the CHD at `path` in place.
+pub fn edit_chd_metadata(path: &Path, edit: &ChdMetaEdit) -> ChdResult<ChdMetaEditReport> {
+    let handle = open_chd_sync(path)?;
+    let mut header = handle.header;
+    let mut entries = handle.metadata;
+    drop(handle.file);
+
+    for tag in &edit.delete {
+        let tag = parse_metadata_tag(tag)?;
+        if tag == CHD_METADATA_TAG_CD || tag == CHD_METADATA_TAG_DVD {
+            return Err(ChdError::ProtectedMetadataTag(tag_label(&tag)));
+        }
+        entries.retain(|entry| entry.tag != tag);
+    }
+    for track_edit in &edit.set_track {
+        set_track_fields(&mut entries, track_edit)?;
+    }
+    for (tag, text) in &edit.add {
+        let mut data = text.clone().into_bytes();
+        data.push(0);
+        entries.push(ChdMetadataHeader {
+            tag: parse_metadata_tag(tag)?,
+            flags: CHD_METADATA_FLAG_HASHED,
+            reserved: [0; CHD_METADATA_RESERVED_BYTES],
+            data,
+        });
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+    let chain_start = file.seek(SeekFrom::End(0))?;
+    let chain = serialize_chain(&mut entries, chain_start)?;
+    file.write_all(&chain)?;
+    file.sync_all()?;
+
+    let old_sha1 = header.sha1;
+    let hashes: Vec<MetadataHash> = entries
+        .iter()
+        .filter(|entry| entry.flags & CHD_METADATA_FLAG_HASHED != 0)
+        .map(|entry| MetadataHash {
+            tag: entry.tag,
+            sha1: Sha1::digest(&entry.data).into(),
+        })
+        .collect();
+    header.meta_offset = if entries.is_empty() { 0 } else { chain_start };
+    header.sha1 = compute_overall_sha1(header.raw_sha1, &hashes);
+
+    let mut header_buf = Cursor::new(Vec::with_capacity(CHD_V5_HEADER_SIZE as usize));
+    header.write(&mut header_buf)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(header_buf.get_ref())?;
+    file.sync_all()?;
+
+    Ok(ChdMetaEditReport {
+        old_sha1: hex::encode(old_sha1),
+        new_sha1: hex::encode(header.sha1),
+        tags: entries.iter().map(|entry| tag_label(&entry.tag)).collect(),
+    })
+}
+
+/// Serialize `entries` as a chain starting at absolute offset `start`,
+/// linking each entry's next-pointer to the one after it.
+fn serialize_chain(entries: &mut [ChdMetadataHeader], start: u64) -> ChdResult<Vec<u8>> {
+    let mut out = Vec::new();
+    let count = entries.len();
+    for (i, entry) in entries.iter_mut().enumerate() {
+        if entry.data.len() > MAX_METADATA_LEN {
+            return Err(ChdError::MetadataTooLong(entry.data.len()));
+        }
+        let entry_len = (16 + entry.data.len()) as u64;
+        let next = if i + 1 < count {
+            start + out.len() as u64 + entry_len
+        } else {
+            0
+        };
+        entry.reserved = next.to_be_bytes();
+        let mut bytes = Vec::new();
+        entry.write(&mut Cursor::new(&mut bytes))?;
+        out.extend_from_slice(&bytes);
+    }
+    Ok(out)
+}
+
+/// Replace fields of one track inside the CHT2 entries. chdman writes
+/// one entry per track; this crate's writer lists every track in a
+/// single entry, so tracks are found by their `TRACK:` token either way.
+fn set_track_fields(entries: &mut [ChdMetadataHeader], edit: &ChdTrackEdit) -> ChdResult<()> {
+    let track_token = format!("{TRACK_KEY}:{}", edit.track);
+    for entry in entries.iter_mut().filter(|e| e.tag == CHD_METADATA_TAG_CD) {
+        let text = String::from_utf8_lossy(&entry.data)
+            .trim_end_matches('\0')
+            .to_string();
+        let mut tokens: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        let Some(start) = tokens.iter().position(|t| *t == track_token) else {
+            continue;
+        };
+        let end = tokens[start + 1..]
+            .iter()
+            .position(|t| t.starts_with("TRACK:"))
+            .map_or(tokens.len(), |offset| start + 1 + offset);
+
+        for (key, value) in &edit.fields {
+            let prefix = format!("{key}:");
+            let token = tokens[start + 1..end]
+                .iter_mut()
+                .find(|t| t.starts_with(&prefix))
+                .ok_or_else(|| ChdError::UnknownTrackField {
+                    track: edit.track,
+                    field: key.clone(),
+                })?;
+            *token = format!("{prefix}{value}");
+        }
+
+        let mut data = tokens.join(" ").into_bytes();
+        data.push(0);
+        entry.data = data;
+        return Ok(());
+    }
+    Err(ChdError::TrackNotInMetadata(edit.track))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chd::info::read_info;
+    use crate::chd::test_fixtures::mixed_iso;
+    use crate::chd::{ChdDvdOptions, DiscMode, convert_disc_to_chd, extract_from_chd, verify_chd};
+    use crate::util::NoProgress;
+
+    async fn cd_chd(dir: &Path) -> std::path::PathBuf {
+        let iso_path = dir.join("game.iso");
+        std::fs::write(&iso_path, mixed_iso(6)).unwrap();
+        let chd_path = dir.join("game.chd");
+        convert_disc_to_chd(
+            &NoProgress,
+            iso_path,
+            chd_path.clone(),
+            Some(DiscMode::Cd),
+            ChdDvdOptions::default(),
+        )
+        .await
+        .unwrap();
+        chd_path
+    }
+
+    #[test]
+    fn track_edit_parses_split_and_joined_fields() {
+        let split = ChdTrackEdit::parse(&["TRACK:1", "TYPE:MODE2_RAW"]).unwrap();
+        let joined = ChdTrackEdit::parse(&["TRACK:1 type:MODE2_RAW"]).unwrap();
+        assert_eq!(split, joined);
+        assert_eq!(split.fields, vec![("TYPE".into(), "MODE2_RAW".into())]);
+        assert!(ChdTrackEdit::parse(&["TYPE:MODE2_RAW"]).is_err());
+        assert!(ChdTrackEdit::parse(&["TRACK:1"]).is_err());
+        assert!(ChdTrackEdit::parse(&["TRACK:x", "TYPE:AUDIO"]).is_err());
+
+        let many =
+            ChdTrackEdit::parse_many(&["TRACK:1", "TYPE:MODE1_RAW", "TRACK:2 TYPE:AUDIO"]).unwrap();
+        assert_eq!(many.len(), 2);
+        assert_eq!(many[1].track, 2);
+        assert!(ChdTrackEdit::parse_many(&["TYPE:AUDIO", "TRACK:1"]).is_err());
+    }
+
+    #[test]
+    fn tags_are_space_padded() {
+        assert_eq!(parse_metadata_tag("DVD").unwrap(), *b"DVD ");
+        assert_eq!(parse_metadata_tag("IDNT").unwrap(), *b"IDNT");
+        assert!(parse_metadata_tag("TOOLONG").is_err());
+        assert!(parse_metadata_tag("").is_err());
+    }
+
+    #[tokio::test]
+    async fn edits_update_metadata_and_sha1_without_touching_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let chd = cd_chd(dir.path()).await;
+        let before = read_info(&chd).unwrap();
+
+        let edit = ChdMetaEdit {
+            set_track: vec![ChdTrackEdit::parse(&["TRACK:1", "TYPE:MODE2_RAW"]).unwrap()],
+            add: vec![("IDNT".into(), "SLUS-00001".into())],
+            ..ChdMetaEdit::default()
+        };
+        let report = edit_chd_metadata(&chd, &edit).unwrap();
+        assert_eq!(report.old_sha1, before.sha1);
+        assert_ne!(report.new_sha1, before.sha1);
+        assert_eq!(report.tags, ["CHT2", "IDNT"]);
+
+        let after = read_info(&chd).unwrap();
+        assert_eq!(after.sha1, report.new_sha1);
+        assert_eq!(after.raw_sha1, before.raw_sha1);
+        assert_eq!(after.tracks[0].track_type, "MODE2_RAW");
+        verify_chd(&NoProgress, chd.clone(), None, false)
+            .await
+            .unwrap();
+
+        // Dropping the added tag and restoring the type brings the
+        // original SHA-1 back: the hash covers content, not placement.
+        let undo = ChdMetaEdit {
+            delete: vec!["IDNT".into()],
+            set_track: vec![ChdTrackEdit::parse(&["TRACK:1 TYPE:MODE1"]).unwrap()],
+            ..ChdMetaEdit::default()
+        };
+        let report = edit_chd_metadata(&chd, &undo).unwrap();
+        assert_eq!(report.new_sha1, before.sha1);
+
+        let out_cue = dir.path().join("restored.cue");
+        extract_from_chd(&NoProgress, chd, out_cue.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(out_cue.with_extension("bin")).unwrap(),
+            mixed_iso(6)
+        );
+    }
+
+    #[tokio::test]
+    async fn layout_tags_and_unknown_fields_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let chd = cd_chd(dir.path()).await;
+        let original = std::fs::read(&chd).unwrap();
+
+        let delete = ChdMetaEdit {
+            delete: vec!["CHT2".into()],
+            ..ChdMetaEdit::default()
+        };
+        assert!(matches!(
+            edit_chd_metadata(&chd, &delete),
+            Err(ChdError::ProtectedMetadataTag(_))
+        ));
+        let missing_track = ChdMetaEdit {
+            set_track: vec![ChdTrackEdit::parse(&["TRACK:9", "TYPE:AUDIO"]).unwrap()],
+            ..ChdMetaEdit::default()
+        };
+        assert!(matches!(
+            edit_chd_metadata(&chd, &missing_track),
+            Err(ChdError::TrackNotInMetadata(9))
+        ));
+        let unknown_field = ChdMetaEdit {
+            set_track: vec![ChdTrackEdit::parse(&["TRACK:1", "COLOR:RED"]).unwrap()],
+            ..ChdMetaEdit::default()
+        };
+        assert!(matches!(
+            edit_chd_metadata(&chd, &unknown_field),
+            Err(ChdError::UnknownTrackField { track: 1, .. })
+        ));
+        assert_eq!(std::fs::read(&chd).unwrap(), original);
+    }
+}
@@ -5,34 +5,47 @@
 //! to disc form is called extract rather than decompress; see
 //! [`crate::chd::error`] for the failure modes.
 
-use crate::cd::{CD_HUNK_BYTES, IO_BUFFER_SIZE, SECTOR_SIZE};
+use crate::cd::{
+    CD_HUNK_BYTES, CD_SECTOR_SIZES, COOKED_SECTOR_SIZE, FRAMES_PER_HUNK, FRAMES_PER_SECOND,
+    IO_BUFFER_SIZE, SECTOR_SIZE,
+};
+use crate::chd::compression::ChdCodec;
+use crate::chd::compression::dvd::DvdCodecLevels;
 use crate::chd::error::{ChdError, ChdResult};
-use crate::chd::models::{CHD_METADATA_TAG_CD, CHD_METADATA_TAG_DVD, ChdHeaderV5, SHA1_BYTES};
+use crate::chd::models::{
+    CHD_METADATA_TAG_CD, CHD_METADATA_TAG_CUE_REM, CHD_METADATA_TAG_DVD, ChdHeaderV5, SHA1_BYTES,
+};
 use crate::chd::reader::cue_generator::{
     ChdTrackInfo, chd_type_datasize, generate_cue_sheet, parse_chd_track_metadata,
+    parse_cue_rem_metadata,
 };
-use crate::chd::writer::ChdWriter;
 use crate::chd::writer::metadata::MetadataHash;
+use crate::chd::writer::{ChdBufferPlan, ChdTimings, ChdWriter};
 use crate::cue::CueParser;
 use crate::cue::models::{CueFile, CueSheet, FileType, Index, Msf, Track, TrackType};
 use crate::util::hash::{FileDigests, HashAlgo, MultiHasher};
 use crate::util::iso9660::{DiscKind, detect_disc_kind};
 use crate::util::{
-    BYTES_PER_MB, CancelToken, DREAMCAST_CHD_WARNING, ProgressReporter, await_with_progress_cancel,
-    dreamcast_boot_signature, scratch_output_path,
+    BYTES_PER_MB, CancelToken, DREAMCAST_CHD_WARNING, NoProgress, ProgressReporter,
+    await_with_progress_cancel, dreamcast_boot_signature, scratch_output_path, throughput_mb_s,
 };
 use log::{debug, info, warn};
 use sha1::{Digest, Sha1};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+pub mod benchmark;
 pub mod compression;
+pub mod dedupe;
 pub mod error;
 pub mod info;
 pub(crate) mod map;
+pub mod meta;
 mod models;
 pub(crate) mod reader;
 pub(crate) mod writer;
@@ -53,7 +66,68 @@ pub struct ChdDvdOptions {
     /// Add zstd as a third codec. Off by default: the libchdr in
     /// AetherSX2/NetherSX2 rejects CHDs that list zstd.
     pub allow_zstd: bool,
+    /// LZMA preset (0..=9) for the `lzma` codec; defaults to chdman's 8.
+    pub lzma_level: Option<u32>,
+    /// zstd level (1..=22) for the `zstd` codec; defaults to chdman's 19.
+    /// Only used together with `allow_zstd`.
+    pub zstd_level: Option<i32>,
     pub force: bool,
+    /// Log where the write spent its time: sector reads, each codec's
+    /// trials, and the map.
+    pub timing: bool,
+    /// Ceiling in bytes for the reader and writer buffers and the hunks
+    /// in flight; `None` keeps the default 8 MiB buffers and two hunks
+    /// per core.
+    pub max_memory: Option<u64>,
+    /// Compress into `<output>.chdtmp` with a `<output>.chdtmp.state`
+    /// journal, and continue from them if a previous run left both
+    /// behind. Both stay on disk when the write is interrupted. DVD
+    /// mode only.
+    pub resume: bool,
+    /// Provenance note stored in an unhashed `CMNT` metadata entry,
+    /// prefixed with the rom-converto version. `None` writes no entry,
+    /// keeping the output byte-identical to a plain compress.
+    pub comment: Option<String>,
+    /// CD mode only: skip the LZMA trial on audio hunks, leaving them to
+    /// FLAC and zlib. Data hunks and DVD mode are unaffected.
+    pub fast: bool,
+    /// CD mode with `.cue` input only: bytes per sector in the bin, one
+    /// of [`CD_SECTOR_SIZES`]. `None` reads raw 2352-byte sectors.
+    pub sector_size: Option<usize>,
+    /// Leave trailing hunks that hold only zeros out of the CHD, for
+    /// images padded past the end of the disc. Changes the logical size,
+    /// so the CHD no longer matches hashes of the full image.
+    pub trim_trailing_zeros: bool,
+    /// CD mode with `.cue` input only: fail instead of warning when the
+    /// bin is too short for the cue's last track.
+    pub strict: bool,
+    /// Developer aid: store the hunk map uncompressed (see
+    /// [`ChdWriter::with_uncompressed_map`]). chdman and libchdr cannot
+    /// read the result.
+    pub uncompressed_map: bool,
+    /// Compress every hunk with this codec alone (see
+    /// [`ChdWriter::with_single_codec`]); it must belong to the mode
+    /// being written. `zstd` turns on `allow_zstd` for DVD mode.
+    pub single_codec: Option<ChdCodec>,
+}
+
+/// Byte order of the 16-bit samples in an extracted CD bin's audio
+/// tracks. CD-mode CHDs store audio as read from the source bin, which
+/// is little-endian for every bin this crate writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioEndian {
+    /// Write audio samples as stored.
+    #[default]
+    Little,
+    /// Byte-swap every audio sample. Data tracks are never touched.
+    Big,
+}
+
+/// Options for CHD extraction.
+#[derive(Debug, Clone, Default)]
+pub struct ChdExtractOptions {
+    /// Ignored for DVD-mode CHDs, which carry no audio tracks.
+    pub audio_endian: AudioEndian,
 }
 
 /// Which CHD flavor to produce.
@@ -63,9 +137,23 @@ pub enum DiscMode {
     Dvd,
 }
 
-/// A sibling temp path in the output directory so an interrupted write
-/// never lands on the final name and a pre-existing overwrite target
-/// survives until the rename.
+/// Log the `--timing` breakdown of one CHD write.
+fn log_timings(timings: &ChdTimings) {
+    info!("Timing breakdown:");
+    for line in timings.breakdown() {
+        info!("  {line}");
+    }
+}
+
+/// `, compressed at X MB/s` for the size line, from the source bytes
+/// fed through the writer and the wall time of the write; empty when
+/// nothing was timed.
+fn compress_rate(source_bytes: u64, elapsed: Duration) -> String {
+    throughput_mb_s(source_bytes, elapsed)
+        .map(|rate| format!(", compressed at {rate:.1} MB/s"))
+        .unwrap_or_default()
+}
+
 /// Remove the scratch file and report the cancellation; used as the
 /// `on_cancel` fallback for the race where the blocking pipeline
 /// finished a hunk just as the token fired.
@@ -116,11 +204,43 @@ pub async fn convert_disc_to_chd_cancellable(
         .is_some_and(|e| e.eq_ignore_ascii_case("cue"));
     match (mode, is_cue) {
         (None | Some(DiscMode::Cd), true) => {
-            convert_to_chd(progress, input_path, output_path, opts.force, cancel).await
+            warn_resume_is_dvd_only(&opts, &input_path);
+            convert_to_chd(
+                progress,
+                input_path,
+                output_path,
+                opts.force,
+                opts.timing,
+                opts.max_memory,
+                opts.comment.as_deref(),
+                opts.fast,
+                opts.sector_size.unwrap_or(SECTOR_SIZE),
+                opts.trim_trailing_zeros,
+                opts.strict,
+                opts.uncompressed_map,
+                opts.single_codec,
+                cancel,
+            )
+            .await
         }
         (Some(DiscMode::Dvd), true) => Err(ChdError::DvdModeNeedsIso),
         (Some(DiscMode::Cd), false) => {
-            convert_iso_to_cd_chd(progress, input_path, output_path, opts.force, cancel).await
+            warn_resume_is_dvd_only(&opts, &input_path);
+            convert_iso_to_cd_chd(
+                progress,
+                input_path,
+                output_path,
+                opts.force,
+                opts.timing,
+                opts.max_memory,
+                opts.comment.as_deref(),
+                opts.fast,
+                opts.trim_trailing_zeros,
+                opts.uncompressed_map,
+                opts.single_codec,
+                cancel,
+            )
+            .await
         }
         (Some(DiscMode::Dvd), false) => {
             convert_iso_to_chd(progress, input_path, output_path, opts, cancel).await
@@ -139,8 +259,22 @@ pub async fn convert_disc_to_chd_cancellable(
                             input_path
                         );
                     }
-                    convert_iso_to_cd_chd(progress, input_path, output_path, opts.force, cancel)
-                        .await
+                    warn_resume_is_dvd_only(&opts, &input_path);
+                    convert_iso_to_cd_chd(
+                        progress,
+                        input_path,
+                        output_path,
+                        opts.force,
+                        opts.timing,
+                        opts.max_memory,
+                        opts.comment.as_deref(),
+                        opts.fast,
+                        opts.trim_trailing_zeros,
+                        opts.uncompressed_map,
+                        opts.single_codec,
+                        cancel,
+                    )
+                    .await
                 }
                 DiscKind::Ps2Dvd | DiscKind::Psp | DiscKind::UnknownIso => {
                     info!("{} detected, writing DVD-mode CHD", kind.label());
@@ -159,6 +293,16 @@ pub async fn convert_disc_to_chd_cancellable(
     }
 }
 
+/// CD-mode writes are short next to DVD ones and do not checkpoint.
+fn warn_resume_is_dvd_only(opts: &ChdDvdOptions, input_path: &std::path::Path) {
+    if opts.resume {
+        warn!(
+            "--resume only applies to DVD-mode CHDs; compressing {} from the start",
+            input_path.display()
+        );
+    }
+}
+
 /// Compress every `.cue` and `.iso` under `input_dir`, descending into
 /// subdirectories up to `max_depth` (`None` for unlimited). Outputs land
 /// next to their inputs with the extension replaced by `.chd`, or mirror
@@ -204,6 +348,104 @@ pub async fn convert_disc_to_chd_batch(
     Ok(())
 }
 
+/// A multi-disc game written by [`convert_disc_set_to_chd`]: its CHDs in
+/// disc order and the `.m3u` listing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdDiscSet {
+    pub base_title: String,
+    pub m3u_path: PathBuf,
+    pub discs: Vec<PathBuf>,
+}
+
+/// Group the `.cue` files at the top level of `input_dir` whose names
+/// carry a disc number ("Game (Disc 1).cue", "Game Disc 2 of 3.cue") into
+/// games, discs in order. Cues without a disc number are skipped.
+pub fn plan_disc_sets(input_dir: &std::path::Path) -> ChdResult<Vec<crate::playlist::DiscGroup>> {
+    let cues = crate::util::fs::collect_files_with_exts(input_dir, &["cue"], Some(1))?;
+    let (numbered, unnumbered): (Vec<_>, Vec<_>) = cues.into_iter().partition(|cue| {
+        cue.file_stem()
+            .and_then(|stem| crate::playlist::parse_disc_token(&stem.to_string_lossy()))
+            .is_some()
+    });
+    for cue in &unnumbered {
+        warn!("Skipping {}: no disc number in its name", cue.display());
+    }
+    if numbered.is_empty() {
+        warn!("No numbered .cue discs found in {}", input_dir.display());
+    }
+    Ok(crate::playlist::group_disc_files(&numbered))
+}
+
+/// Compress every disc [`plan_disc_sets`] finds in `input_dir` into
+/// `output_dir`, and write one `<base title>.m3u` per game listing its
+/// CHDs in disc order. A failed disc stops the run before its playlist
+/// is written, since a set with a missing disc is not playable. Each
+/// playlist goes through a scratch file and a rename.
+pub async fn convert_disc_set_to_chd(
+    progress: &dyn ProgressReporter,
+    total_progress: &dyn ProgressReporter,
+    input_dir: &std::path::Path,
+    output_dir: &std::path::Path,
+    opts: ChdDvdOptions,
+    cancel: CancelToken,
+) -> ChdResult<Vec<ChdDiscSet>> {
+    let groups = plan_disc_sets(input_dir)?;
+    if groups.is_empty() {
+        return Ok(Vec::new());
+    }
+    let disc_count: usize = groups.iter().map(|group| group.len()).sum();
+
+    std::fs::create_dir_all(output_dir)?;
+    total_progress.start(
+        disc_count as u64,
+        &format!("Compressing {disc_count} discs"),
+    );
+
+    let mut sets = Vec::new();
+    for group in groups {
+        if group.has_duplicate_numbers {
+            warn!(
+                "Duplicate disc numbers in set {}, including all entries",
+                group.base_title
+            );
+        }
+        let m3u_path = output_dir.join(format!("{}.m3u", group.base_title));
+        if m3u_path.exists() && !opts.force {
+            return Err(ChdError::PlaylistAlreadyExists(m3u_path));
+        }
+        let mut discs = Vec::with_capacity(group.len());
+        let mut playlist = String::new();
+        for cue in &group.discs {
+            let output = output_dir.join(cue.with_extension("chd").file_name().unwrap_or_default());
+            convert_disc_to_chd_cancellable(
+                progress,
+                cue.clone(),
+                output.clone(),
+                Some(DiscMode::Cd),
+                opts.clone(),
+                cancel.clone(),
+            )
+            .await?;
+            playlist.push_str(&output.file_name().unwrap_or_default().to_string_lossy());
+            playlist.push('\n');
+            discs.push(output);
+            total_progress.inc(1);
+        }
+        let write_path = scratch_output_path(&m3u_path)?;
+        fs::write(&write_path, playlist).await?;
+        crate::util::publish_temp(write_path, &m3u_path, opts.force)?;
+        info!("Wrote {} ({} discs)", m3u_path.display(), discs.len());
+        sets.push(ChdDiscSet {
+            base_title: group.base_title,
+            m3u_path,
+            discs,
+        });
+    }
+
+    total_progress.finish();
+    Ok(sets)
+}
+
 /// Compress a 2048-byte-sector ISO (PS2 DVD, PSP UMD) to a DVD-mode
 /// CHD, the equivalent of `chdman createdvd`.
 pub async fn convert_iso_to_chd(
@@ -222,15 +464,16 @@ async fn convert_iso_to_chd_with_kind(
     progress: &dyn ProgressReporter,
     iso_path: PathBuf,
     output_path: PathBuf,
-    opts: ChdDvdOptions,
+    mut opts: ChdDvdOptions,
     kind: Option<DiscKind>,
     cancel: CancelToken,
 ) -> ChdResult<()> {
+    opts.allow_zstd |= opts.single_codec == Some(ChdCodec::Zstd);
     if fs::metadata(&output_path).await.is_ok() && !opts.force {
         return Err(ChdError::ChdFileAlreadyExists);
     }
 
-    let iso_size = fs::metadata(&iso_path).await?.len();
+    let mut iso_size = fs::metadata(&iso_path).await?.len();
 
     let kind = match kind {
         Some(kind) => kind,
@@ -248,36 +491,82 @@ async fn convert_iso_to_chd_with_kind(
         );
     }
 
+    let levels = DvdCodecLevels::new(opts.lzma_level, opts.zstd_level)?;
     let hunk_size = opts.hunk_size.unwrap_or(match kind {
         DiscKind::Psp => DVD_HUNK_BYTES_PSP,
         _ => DVD_HUNK_BYTES_DEFAULT,
     });
+    let buffers = ChdBufferPlan::new(opts.max_memory, hunk_size as usize);
+    if opts.trim_trailing_zeros {
+        let trimmed = trim_trailing_zero_hunks(&iso_path, iso_size, hunk_size as u64, 1).await?;
+        warn_trimmed_sectors(&iso_path, (iso_size - trimmed) / COOKED_SECTOR_SIZE as u64);
+        iso_size = trimmed;
+    }
 
     let total_mb = iso_size as f64 / BYTES_PER_MB;
     progress.start(
         iso_size,
         &format!("Compressing to CHD (~{:.2} MB)", total_mb),
     );
+    let started = Instant::now();
+
+    if opts.resume {
+        let timings = compress_dvd_resumable(
+            progress,
+            &iso_path,
+            &output_path,
+            iso_size,
+            hunk_size,
+            opts.allow_zstd,
+            opts.comment.as_deref(),
+            opts.uncompressed_map,
+            opts.single_codec,
+            levels,
+            opts.fast,
+            buffers,
+            cancel,
+        )
+        .await?;
+        log_dvd_summary(
+            &output_path,
+            iso_size,
+            started.elapsed(),
+            opts.timing.then_some(&timings),
+        )
+        .await?;
+        return Ok(());
+    }
 
     let write_path = scratch_output_path(&output_path)?;
     let iso_owned = iso_path.clone();
     let write_owned = write_path.to_path_buf();
     let allow_zstd = opts.allow_zstd;
+    let comment = opts.comment.clone();
+    let uncompressed_map = opts.uncompressed_map;
+    let single_codec = opts.single_codec;
     let cancel_bg = cancel.clone();
     let bytes_done = Arc::new(AtomicU64::new(0));
     let bytes_done_bg = bytes_done.clone();
 
-    let handle = tokio::task::spawn_blocking(move || -> ChdResult<()> {
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<ChdTimings> {
         let iso_file = std::fs::File::open(&iso_owned)?;
-        let mut iso_reader = std::io::BufReader::with_capacity(IO_BUFFER_SIZE, iso_file);
+        let mut iso_reader = std::io::BufReader::with_capacity(buffers.io_buffer, iso_file);
 
-        let mut writer = ChdWriter::create_dvd(&write_owned, iso_size, hunk_size, allow_zstd)?;
-        writer.compress_all_hunks_dvd(&mut iso_reader, &bytes_done_bg, &cancel_bg)?;
-        writer.finalize()?;
-        Ok(())
+        let mut writer = ChdWriter::create_dvd(
+            &write_owned,
+            iso_size,
+            hunk_size,
+            allow_zstd,
+            comment.as_deref(),
+            buffers,
+        )?
+        .with_uncompressed_map(uncompressed_map)
+        .with_single_codec(single_codec)?;
+        writer.compress_all_hunks_dvd(&mut iso_reader, levels, &bytes_done_bg, &cancel_bg)?;
+        writer.finalize()
     });
 
-    if let Err(err) = await_with_progress_cancel(
+    let timings = match await_with_progress_cancel(
         progress,
         &bytes_done,
         handle,
@@ -286,22 +575,121 @@ async fn convert_iso_to_chd_with_kind(
     )
     .await
     {
-        let _ = fs::remove_file(&write_path).await;
-        return Err(err);
-    }
+        Ok(timings) => timings,
+        Err(err) => {
+            let _ = fs::remove_file(&write_path).await;
+            return Err(err);
+        }
+    };
     crate::util::publish_temp(write_path, &output_path, true)?;
+    log_dvd_summary(
+        &output_path,
+        iso_size,
+        started.elapsed(),
+        opts.timing.then_some(&timings),
+    )
+    .await
+}
 
-    let chd_size = fs::metadata(&output_path).await?.len();
+/// The size and speed line after a DVD-mode write, plus the `--timing`
+/// breakdown when `timings` is given.
+async fn log_dvd_summary(
+    output_path: &std::path::Path,
+    iso_size: u64,
+    elapsed: Duration,
+    timings: Option<&ChdTimings>,
+) -> ChdResult<()> {
+    let total_mb = iso_size as f64 / BYTES_PER_MB;
+
+    let chd_size = fs::metadata(output_path).await?.len();
     let compression_ratio = (chd_size as f64 / iso_size as f64) * 100.0;
     info!(
-        "Original: {:.2} MB, CHD: {:.2} MB ({:.1}% compression ratio)",
+        "Original: {:.2} MB, CHD: {:.2} MB ({:.1}% compression ratio){}",
         total_mb,
         chd_size as f64 / BYTES_PER_MB,
-        compression_ratio
+        compression_ratio,
+        compress_rate(iso_size, elapsed)
     );
+    if let Some(timings) = timings {
+        log_timings(timings);
+    }
     Ok(())
 }
 
+/// DVD-mode write through [`writer::checkpoint`]: `<output>.chdtmp`
+/// plus its journal, renamed into place on success and kept for the
+/// next `resume` run when the write fails or is cancelled.
+#[allow(clippy::too_many_arguments)]
+async fn compress_dvd_resumable(
+    progress: &dyn ProgressReporter,
+    iso_path: &std::path::Path,
+    output_path: &std::path::Path,
+    iso_size: u64,
+    hunk_size: u32,
+    allow_zstd: bool,
+    comment: Option<&str>,
+    uncompressed_map: bool,
+    single_codec: Option<ChdCodec>,
+    levels: DvdCodecLevels,
+    fast: bool,
+    buffers: ChdBufferPlan,
+    cancel: CancelToken,
+) -> ChdResult<ChdTimings> {
+    let scratch = writer::checkpoint::scratch_path(output_path);
+    let state = writer::checkpoint::state_path(output_path);
+    let iso_owned = iso_path.to_path_buf();
+    let scratch_owned = scratch.clone();
+    let state_owned = state.clone();
+    let comment = comment.map(str::to_owned);
+    let cancel_bg = cancel.clone();
+    let bytes_done = Arc::new(AtomicU64::new(0));
+    let bytes_done_bg = bytes_done.clone();
+
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<ChdTimings> {
+        let mut writer = ChdWriter::create_dvd_resumable(
+            &scratch_owned,
+            &state_owned,
+            &iso_owned,
+            iso_size,
+            hunk_size,
+            allow_zstd,
+            comment.as_deref(),
+            writer::checkpoint::CheckpointOptions {
+                levels,
+                fast,
+                single_codec,
+            },
+            buffers,
+        )?
+        .with_uncompressed_map(uncompressed_map)
+        .with_single_codec(single_codec)?;
+        let iso_file = std::fs::File::open(&iso_owned)?;
+        let mut iso_reader = std::io::BufReader::with_capacity(buffers.io_buffer, iso_file);
+        writer.compress_all_hunks_dvd(&mut iso_reader, levels, &bytes_done_bg, &cancel_bg)?;
+        writer.finalize()
+    });
+
+    let timings = match await_with_progress_cancel(progress, &bytes_done, handle, &cancel, || {
+        ChdError::Cancelled
+    })
+    .await
+    {
+        Ok(timings) => timings,
+        Err(err) => {
+            if fs::metadata(&state).await.is_ok() {
+                info!(
+                    "Partial CHD kept at {}; rerun with --resume to continue",
+                    scratch.display()
+                );
+            }
+            return Err(err);
+        }
+    };
+    fs::rename(&scratch, output_path).await?;
+    let _ = fs::remove_file(&state).await;
+    Ok(timings)
+}
+
 /// chdman pads every track, including a lone final one, to a 4-frame
 /// boundary; the zero padding frames count into the logical size and
 /// the raw SHA-1, while CHT2 `FRAMES:` records the real count.
@@ -332,17 +720,26 @@ fn synth_mode1_2048_cue_sheet() -> CueSheet {
             postgap: None,
             file_index: 0,
         }],
+        rems: Vec::new(),
     }
 }
 
 /// Compress a CD-media 2048-byte-sector ISO (PS1, PS2-CD) to a
 /// CD-mode CHD with a single MODE1/2048 track, the equivalent of
 /// `chdman createcd -i game.iso`.
+#[allow(clippy::too_many_arguments)]
 pub async fn convert_iso_to_cd_chd(
     progress: &dyn ProgressReporter,
     iso_path: PathBuf,
     output_path: PathBuf,
     force: bool,
+    timing: bool,
+    max_memory: Option<u64>,
+    comment: Option<&str>,
+    fast: bool,
+    trim_trailing_zeros: bool,
+    uncompressed_map: bool,
+    single_codec: Option<ChdCodec>,
     cancel: CancelToken,
 ) -> ChdResult<()> {
     if fs::metadata(&output_path).await.is_ok() && !force {
@@ -350,15 +747,23 @@ pub async fn convert_iso_to_cd_chd(
     }
 
     let sector_data_size = TrackType::Mode1_2048.block_size() as u64;
-    let iso_size = fs::metadata(&iso_path).await?.len();
+    let mut iso_size = fs::metadata(&iso_path).await?.len();
     if iso_size == 0 || !iso_size.is_multiple_of(sector_data_size) {
         return Err(ChdError::IsoNotSectorAligned { size: iso_size });
     }
+    if trim_trailing_zeros {
+        let hunk_bytes = sector_data_size * FRAMES_PER_HUNK as u64;
+        let trimmed = trim_trailing_zero_hunks(&iso_path, iso_size, hunk_bytes, 1).await?;
+        warn_trimmed_sectors(&iso_path, (iso_size - trimmed) / sector_data_size);
+        iso_size = trimmed;
+    }
     let data_sectors: u32 = (iso_size / sector_data_size)
         .try_into()
         .map_err(|_| ChdError::InvalidHunkSize)?;
     let total_sectors = padded_track_frames(data_sectors);
     let cue_sheet = synth_mode1_2048_cue_sheet();
+    let comment = comment.map(str::to_owned);
+    let buffers = ChdBufferPlan::new(max_memory, CD_HUNK_BYTES as usize);
 
     debug!("CD-mode iso: {data_sectors} data sectors, {total_sectors} padded frames");
     let total_mb = iso_size as f64 / BYTES_PER_MB;
@@ -366,6 +771,7 @@ pub async fn convert_iso_to_cd_chd(
         iso_size,
         &format!("Compressing to CHD (~{:.2} MB)", total_mb),
     );
+    let started = Instant::now();
 
     let write_path = scratch_output_path(&output_path)?;
     let iso_owned = iso_path.clone();
@@ -374,30 +780,35 @@ pub async fn convert_iso_to_cd_chd(
     let bytes_done = Arc::new(AtomicU64::new(0));
     let bytes_done_bg = bytes_done.clone();
 
-    let handle = tokio::task::spawn_blocking(move || -> ChdResult<()> {
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<ChdTimings> {
         let iso_file = std::fs::File::open(&iso_owned)?;
-        let mut iso_reader = std::io::BufReader::with_capacity(IO_BUFFER_SIZE, iso_file);
+        let mut iso_reader = std::io::BufReader::with_capacity(buffers.io_buffer, iso_file);
 
         let mut writer = ChdWriter::create(
             &write_owned,
             total_sectors,
             data_sectors,
             CD_HUNK_BYTES,
+            sector_data_size as usize,
             &cue_sheet,
-        )?;
+            comment.as_deref(),
+            buffers,
+        )?
+        .with_uncompressed_map(uncompressed_map)
+        .with_single_codec(single_codec)?;
         writer.compress_all_hunks(
             &mut iso_reader,
             total_sectors,
             data_sectors,
             sector_data_size as usize,
+            fast,
             &bytes_done_bg,
             &cancel_bg,
         )?;
-        writer.finalize()?;
-        Ok(())
+        writer.finalize()
     });
 
-    if let Err(err) = await_with_progress_cancel(
+    let timings = match await_with_progress_cancel(
         progress,
         &bytes_done,
         handle,
@@ -406,19 +817,26 @@ pub async fn convert_iso_to_cd_chd(
     )
     .await
     {
-        let _ = fs::remove_file(&write_path).await;
-        return Err(err);
-    }
+        Ok(timings) => timings,
+        Err(err) => {
+            let _ = fs::remove_file(&write_path).await;
+            return Err(err);
+        }
+    };
     crate::util::publish_temp(write_path, &output_path, true)?;
 
     let chd_size = fs::metadata(&output_path).await?.len();
     let compression_ratio = (chd_size as f64 / iso_size as f64) * 100.0;
     info!(
-        "Original: {:.2} MB, CHD: {:.2} MB ({:.1}% compression ratio)",
+        "Original: {:.2} MB, CHD: {:.2} MB ({:.1}% compression ratio){}",
         total_mb,
         chd_size as f64 / BYTES_PER_MB,
-        compression_ratio
+        compression_ratio,
+        compress_rate(iso_size, started.elapsed())
     );
+    if timing {
+        log_timings(&timings);
+    }
     Ok(())
 }
 
@@ -440,11 +858,216 @@ async fn dreamcast_head_bytes(bin_path: &std::path::Path) -> Vec<u8> {
     }
 }
 
+/// A cue sheet's tracks as [`convert_to_chd`] would lay them out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdCdLayout {
+    /// Every `FILE` the cue names, in order. Only the first is read.
+    pub files: Vec<String>,
+    pub tracks: Vec<ChdCdTrackLayout>,
+}
+
+/// One track of a [`ChdCdLayout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChdCdTrackLayout {
+    pub number: u8,
+    /// The cue track type, e.g. `MODE1/2352`.
+    pub track_type: &'static str,
+    /// The CHD metadata type it is stored as, e.g. `MODE1_RAW`.
+    pub chd_type: &'static str,
+    pub start_lba: u32,
+    pub frames: u32,
+    /// The `FILE` this track belongs to.
+    pub file: String,
+}
+
+/// Parse `cue_path` and work out the track layout [`convert_to_chd`]
+/// would record, without writing anything. Frame counts come from the
+/// same spans as the CD metadata, over the first bin's sector count
+/// at `sector_size` bytes per sector.
+pub async fn plan_cd_layout(
+    cue_path: &std::path::Path,
+    sector_size: usize,
+) -> ChdResult<ChdCdLayout> {
+    let cue_sheet = CueParser::new(cue_path).parse().await?;
+    let Some(first) = cue_sheet.files.first() else {
+        return Err(ChdError::NoFileReferencedInCueSheet);
+    };
+    let cue_dir = cue_path.parent().unwrap_or(std::path::Path::new("."));
+    let bin_size = fs::metadata(cue_dir.join(&first.filename)).await?.len();
+    check_cooked_track_modes(&cue_sheet, sector_size)?;
+    let total_frames = bin_sector_count(&NoProgress, bin_size, sector_size, false)?;
+
+    let spans = writer::metadata::track_frame_spans(&cue_sheet, total_frames)?;
+    let tracks = cue_sheet
+        .tracks
+        .iter()
+        .zip(spans)
+        .map(|(track, (start_lba, frames))| ChdCdTrackLayout {
+            number: track.number,
+            track_type: track.track_type.cue_string(),
+            chd_type: track.track_type.chd_metadata_type(),
+            start_lba,
+            frames,
+            file: cue_sheet.files[track.file_index].filename.clone(),
+        })
+        .collect();
+    Ok(ChdCdLayout {
+        files: cue_sheet.files.iter().map(|f| f.filename.clone()).collect(),
+        tracks,
+    })
+}
+
+/// Whole sectors in a bin of `bin_size` bytes read at `sector_size`
+/// bytes each, rejecting sizes the writer cannot store. A partial
+/// sector at the end is dropped with a warning, or is
+/// [`ChdError::BinNotSectorAligned`] when `strict`.
+fn bin_sector_count(
+    progress: &dyn ProgressReporter,
+    bin_size: u64,
+    sector_size: usize,
+    strict: bool,
+) -> ChdResult<u32> {
+    if !CD_SECTOR_SIZES.contains(&sector_size) {
+        return Err(ChdError::UnsupportedSectorSize(sector_size));
+    }
+    let partial = bin_size % sector_size as u64;
+    if partial != 0 {
+        let err = ChdError::BinNotSectorAligned {
+            size: bin_size,
+            sector_size,
+        };
+        if strict {
+            return Err(err);
+        }
+        progress.warn(&format!("{err}; ignoring the last {partial} bytes"));
+    }
+    (bin_size / sector_size as u64)
+        .try_into()
+        .map_err(|_| ChdError::InvalidHunkSize)
+}
+
+/// Cooked 2048-byte input holds only user data, so every track the cue
+/// lists must be a 2048-byte data mode; the writer cannot rebuild an
+/// audio or raw track from it.
+fn check_cooked_track_modes(cue_sheet: &CueSheet, sector_size: usize) -> ChdResult<()> {
+    if sector_size != COOKED_SECTOR_SIZE {
+        return Ok(());
+    }
+    match cue_sheet
+        .tracks
+        .iter()
+        .find(|track| track.track_type.block_size() as usize != COOKED_SECTOR_SIZE)
+    {
+        Some(track) => Err(ChdError::CookedTrackMode {
+            track: track.number,
+            mode: track.track_type.cue_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Red Book's minimum track length, 4 seconds. A cue whose last track
+/// gets less from the bin does not describe that bin.
+const MIN_TRACK_FRAMES: u32 = 4 * FRAMES_PER_SECOND;
+
+/// Check that a bin of `bin_frames` frames holds the start of the cue's
+/// last track plus [`MIN_TRACK_FRAMES`]. A shortfall is a warning, or
+/// [`ChdError::BinShorterThanCue`] when `strict`.
+fn check_bin_covers_cue(
+    progress: &dyn ProgressReporter,
+    cue_sheet: &CueSheet,
+    bin_frames: u32,
+    strict: bool,
+) -> ChdResult<()> {
+    let spans = writer::metadata::track_frame_spans(cue_sheet, bin_frames)?;
+    let (Some(track), Some(&(start, frames))) = (cue_sheet.tracks.last(), spans.last()) else {
+        return Ok(());
+    };
+    if start < bin_frames && frames >= MIN_TRACK_FRAMES {
+        return Ok(());
+    }
+    let err = ChdError::BinShorterThanCue {
+        track: track.number,
+        start,
+        bin_frames,
+        min_frames: MIN_TRACK_FRAMES,
+    };
+    if strict {
+        return Err(err);
+    }
+    progress.warn(&format!("{err}; the CHD track layout may be wrong"));
+    Ok(())
+}
+
+/// The length of the file at `path` once trailing all-zero hunks of
+/// `hunk_bytes`, counted from the start of the file, are cut off. A
+/// hunk starting before `keep` bytes is never cut, so pass at least 1
+/// to keep one hunk.
+async fn trim_trailing_zero_hunks(
+    path: &std::path::Path,
+    len: u64,
+    hunk_bytes: u64,
+    keep: u64,
+) -> ChdResult<u64> {
+    if hunk_bytes == 0 {
+        return Ok(len);
+    }
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> ChdResult<u64> {
+        let mut file = std::fs::File::open(&path)?;
+        let mut buf = vec![0u8; hunk_bytes as usize];
+        let mut end = len;
+        while end > 0 {
+            let start = (end - 1) / hunk_bytes * hunk_bytes;
+            if start < keep {
+                break;
+            }
+            let hunk = &mut buf[..(end - start) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(hunk)?;
+            if hunk.iter().any(|&b| b != 0) {
+                break;
+            }
+            end = start;
+        }
+        Ok(end)
+    })
+    .await?
+}
+
+fn warn_trimmed_sectors(path: &std::path::Path, sectors: u64) {
+    if sectors > 0 {
+        warn!(
+            "Trimmed {sectors} trailing zero sectors from {}; the CHD no longer matches hashes of the full image",
+            path.display()
+        );
+    }
+}
+
+/// Compress the bin a `.cue` references into a CD-mode CHD. The bin is
+/// read at `sector_size` bytes per sector: 2352 for raw dumps, 2048
+/// for cooked user data, or 2448 for raw sectors with subchannel.
+/// `trim_trailing_zeros` drops all-zero hunks from the end of the bin,
+/// shortening the last track. A bin too short for the cue's last track
+/// is a warning, or an error when `strict`. `uncompressed_map` stores
+/// the hunk map raw, see [`ChdWriter::with_uncompressed_map`], and
+/// `single_codec` limits every hunk to one codec, see
+/// [`ChdWriter::with_single_codec`].
+#[allow(clippy::too_many_arguments)]
 pub async fn convert_to_chd(
     progress: &dyn ProgressReporter,
     cue_path: PathBuf,
     output_path: PathBuf,
     force: bool,
+    timing: bool,
+    max_memory: Option<u64>,
+    comment: Option<&str>,
+    fast: bool,
+    sector_size: usize,
+    trim_trailing_zeros: bool,
+    strict: bool,
+    uncompressed_map: bool,
+    single_codec: Option<ChdCodec>,
     cancel: CancelToken,
 ) -> ChdResult<()> {
     if fs::metadata(&output_path).await.is_ok() && !force {
@@ -469,12 +1092,29 @@ pub async fn convert_to_chd(
     }
 
     debug!("Opening BIN file: {:?}", bin_path);
-    let bin_size = fs::metadata(&bin_path).await?.len();
-    let total_sectors: u32 = (bin_size / SECTOR_SIZE as u64)
-        .try_into()
-        .map_err(|_| ChdError::InvalidHunkSize)?;
+    let mut bin_size = fs::metadata(&bin_path).await?.len();
+    check_cooked_track_modes(&cue_sheet, sector_size)?;
+    let mut total_sectors = bin_sector_count(progress, bin_size, sector_size, strict)?;
+    // Only whole sectors are compressed; a dropped partial one must
+    // not count towards progress or the trim.
+    bin_size = total_sectors as u64 * sector_size as u64;
+    check_bin_covers_cue(progress, &cue_sheet, total_sectors, strict)?;
+    if trim_trailing_zeros {
+        // Keep at least the first frame of the last track, so every
+        // track the cue lists still has one.
+        let last_start = writer::metadata::track_frame_spans(&cue_sheet, total_sectors)?
+            .last()
+            .map_or(0, |&(start_lba, _)| start_lba as u64);
+        let hunk_bytes = sector_size as u64 * FRAMES_PER_HUNK as u64;
+        let keep = (last_start + 1) * sector_size as u64;
+        let trimmed = trim_trailing_zero_hunks(&bin_path, bin_size, hunk_bytes, keep).await?;
+        warn_trimmed_sectors(&bin_path, (bin_size - trimmed) / sector_size as u64);
+        bin_size = trimmed;
+        total_sectors = bin_sector_count(progress, bin_size, sector_size, strict)?;
+    }
 
     debug!("Total sectors: {}", total_sectors);
+    let buffers = ChdBufferPlan::new(max_memory, CD_HUNK_BYTES as usize);
     debug!("Creating CHD file: {:?}", output_path);
 
     let total_mb = (bin_size as f64) / BYTES_PER_MB;
@@ -482,6 +1122,7 @@ pub async fn convert_to_chd(
         bin_size,
         &format!("Compressing to CHD (~{:.2} MB)", total_mb),
     );
+    let started = Instant::now();
 
     // Hand the full blocking pipeline (open bin + compress +
     // finalize) to a single `spawn_blocking` and poll a shared
@@ -491,35 +1132,41 @@ pub async fn convert_to_chd(
     let bin_path_owned = bin_path.clone();
     let write_owned = write_path.to_path_buf();
     let cue_sheet_owned = cue_sheet.clone();
+    let comment = comment.map(str::to_owned);
     let cancel_bg = cancel.clone();
     let bytes_done = Arc::new(AtomicU64::new(0));
     let bytes_done_bg = bytes_done.clone();
 
-    let handle = tokio::task::spawn_blocking(move || -> ChdResult<()> {
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<ChdTimings> {
         let bin_file = std::fs::File::open(&bin_path_owned)?;
-        let mut bin_reader = std::io::BufReader::with_capacity(IO_BUFFER_SIZE, bin_file);
+        let mut bin_reader = std::io::BufReader::with_capacity(buffers.io_buffer, bin_file);
 
         let mut writer = ChdWriter::create(
             &write_owned,
             total_sectors,
             total_sectors,
             CD_HUNK_BYTES,
+            sector_size,
             &cue_sheet_owned,
-        )?;
+            comment.as_deref(),
+            buffers,
+        )?
+        .with_uncompressed_map(uncompressed_map)
+        .with_single_codec(single_codec)?;
 
         writer.compress_all_hunks(
             &mut bin_reader,
             total_sectors,
             total_sectors,
-            SECTOR_SIZE,
+            sector_size,
+            fast,
             &bytes_done_bg,
             &cancel_bg,
         )?;
-        writer.finalize()?;
-        Ok(())
+        writer.finalize()
     });
 
-    if let Err(err) = await_with_progress_cancel(
+    let timings = match await_with_progress_cancel(
         progress,
         &bytes_done,
         handle,
@@ -528,9 +1175,12 @@ pub async fn convert_to_chd(
     )
     .await
     {
-        let _ = fs::remove_file(&write_path).await;
-        return Err(err);
-    }
+        Ok(timings) => timings,
+        Err(err) => {
+            let _ = fs::remove_file(&write_path).await;
+            return Err(err);
+        }
+    };
     crate::util::publish_temp(write_path, &output_path, true)?;
 
     let chd_size = fs::metadata(&output_path).await?.len();
@@ -541,9 +1191,16 @@ pub async fn convert_to_chd(
     let chd_mb = chd_size as f64 / BYTES_PER_MB;
 
     info!(
-        "Original: {:.2} MB, CHD: {:.2} MB, Saved: {:.2} MB ({:.1}% compression ratio)",
-        total_mb, chd_mb, saved_mb, compression_ratio
+        "Original: {:.2} MB, CHD: {:.2} MB, Saved: {:.2} MB ({:.1}% compression ratio){}",
+        total_mb,
+        chd_mb,
+        saved_mb,
+        compression_ratio,
+        compress_rate(original_size, started.elapsed())
     );
+    if timing {
+        log_timings(&timings);
+    }
 
     debug!("Conversion complete");
     Ok(())
@@ -628,6 +1285,26 @@ pub async fn extract_from_chd_cancellable(
     output_path: PathBuf,
     parent_path: Option<PathBuf>,
     cancel: CancelToken,
+) -> ChdResult<()> {
+    extract_from_chd_with_options(
+        progress,
+        input_path,
+        output_path,
+        parent_path,
+        ChdExtractOptions::default(),
+        cancel,
+    )
+    .await
+}
+
+/// [`extract_from_chd_cancellable`] with non-default [`ChdExtractOptions`].
+pub async fn extract_from_chd_with_options(
+    progress: &dyn ProgressReporter,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    parent_path: Option<PathBuf>,
+    options: ChdExtractOptions,
+    cancel: CancelToken,
 ) -> ChdResult<()> {
     if parent_path.is_some() {
         return Err(ChdError::ParentChdNotSupported);
@@ -691,7 +1368,8 @@ pub async fn extract_from_chd_cancellable(
     let bin_filename = bin_path
         .file_name()
         .unwrap_or_default()
-        .to_string_lossy()
+        .to_str()
+        .ok_or_else(|| ChdError::NonUtf8BinName(bin_path.display().to_string()))?
         .to_string();
 
     let total_mb = total_bin_bytes as f64 / BYTES_PER_MB;
@@ -728,6 +1406,12 @@ pub async fn extract_from_chd_cancellable(
         let meta_str = String::from_utf8_lossy(&cd_meta.data);
         let meta_str = meta_str.trim_end_matches('\0');
         let tracks = parse_chd_track_metadata(meta_str)?;
+        let rems = handle
+            .metadata
+            .iter()
+            .find(|m| m.tag == CHD_METADATA_TAG_CUE_REM)
+            .map(|m| parse_cue_rem_metadata(&m.data))
+            .unwrap_or_default();
 
         let hunk_bytes = handle.header.hunk_bytes as usize;
         // Use the CHT2 `FRAMES:` sums, not `logical_bytes`; see
@@ -736,6 +1420,13 @@ pub async fn extract_from_chd_cancellable(
             .iter()
             .flat_map(|t| std::iter::repeat_n(chd_type_datasize(&t.track_type), t.frames as usize))
             .collect();
+        let swap_frames: Vec<bool> = match options.audio_endian {
+            AudioEndian::Little => Vec::new(),
+            AudioEndian::Big => tracks
+                .iter()
+                .flat_map(|t| std::iter::repeat_n(t.track_type == "AUDIO", t.frames as usize))
+                .collect(),
+        };
 
         let bin_file = std::fs::File::create(&bin_owned)?;
         let mut bin_writer = std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, bin_file);
@@ -750,6 +1441,7 @@ pub async fn extract_from_chd_cancellable(
             &mut bin_writer,
             hunk_bytes,
             &frame_sizes,
+            &swap_frames,
             &bytes_done_bg,
             &cancel_bg,
         );
@@ -759,7 +1451,7 @@ pub async fn extract_from_chd_cancellable(
         use std::io::Write as _;
         bin_writer.flush()?;
 
-        let cue_content = generate_cue_sheet(&bin_filename_owned, &tracks);
+        let cue_content = generate_cue_sheet(&bin_filename_owned, &tracks, &rems);
         std::fs::write(&cue_owned, cue_content)?;
 
         Ok(())
@@ -788,85 +1480,275 @@ pub async fn extract_from_chd_cancellable(
     Ok(())
 }
 
-/// Peek a CHD's metadata to tell DVD-mode (flat ISO) apart from
-/// CD-mode (bin/cue with CHT2 track metadata) without extracting
-/// anything. Used by [`crate::pipeline::chd_to_cso_cancellable`] to
-/// reject CD-mode CHDs up front, since CSO/ZSO have no track layout.
-pub async fn is_dvd_mode_chd(path: PathBuf) -> ChdResult<bool> {
-    tokio::task::spawn_blocking(move || -> ChdResult<bool> {
-        let handle = crate::chd::reader::open_chd_sync(&path)?;
-        Ok(handle
-            .metadata
-            .iter()
-            .any(|m| m.tag == CHD_METADATA_TAG_DVD))
-    })
-    .await?
+/// Result of [`compare_chd_to_source`]. Sector numbers are LBAs from
+/// the start of the image, as the extracted bin or iso lays it out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChdComparison {
+    /// Every sector matches and both sides end together.
+    Identical { sectors: u64 },
+    /// The first sector whose bytes differ. `track` is the CD track
+    /// number; DVD-mode CHDs have none.
+    SectorDiffers {
+        lba: u64,
+        track: Option<u8>,
+        byte_offset: u64,
+    },
+    /// All shared sectors match but the source ends first.
+    SourceShorter { lba: u64 },
+    /// All shared sectors match but the CHD ends first.
+    ChdShorter { lba: u64 },
 }
 
-/// DVD extract path: one flat `.iso`, no cue sheet.
-async fn extract_dvd_iso(
+impl ChdComparison {
+    pub fn is_identical(&self) -> bool {
+        matches!(self, ChdComparison::Identical { .. })
+    }
+}
+
+impl std::fmt::Display for ChdComparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChdComparison::Identical { sectors } => {
+                write!(f, "identical ({sectors} sectors)")
+            }
+            ChdComparison::SectorDiffers {
+                lba,
+                track: Some(track),
+                byte_offset,
+            } => write!(
+                f,
+                "sector at LBA {lba} (track {track:02}) differs, first at byte offset {byte_offset:#x}"
+            ),
+            ChdComparison::SectorDiffers {
+                lba,
+                track: None,
+                byte_offset,
+            } => write!(
+                f,
+                "sector at LBA {lba} differs, first at byte offset {byte_offset:#x}"
+            ),
+            ChdComparison::SourceShorter { lba } => {
+                write!(f, "source ends at LBA {lba}; the CHD holds more sectors")
+            }
+            ChdComparison::ChdShorter { lba } => {
+                write!(f, "CHD ends at LBA {lba}; the source holds more sectors")
+            }
+        }
+    }
+}
+
+/// Decode `chd_path` and compare it, sector by sector, with the image
+/// it was made from: a `.cue` (its files read back to back) or a flat
+/// image such as an `.iso`. The CHD side is shaped exactly as
+/// [`extract_from_chd`] would write it, so `Identical` means the
+/// extracted image would equal the source byte for byte. Decoding
+/// stops at the first difference.
+pub async fn compare_chd_to_source(
     progress: &dyn ProgressReporter,
-    input_path: PathBuf,
-    output_path: PathBuf,
-    logical_bytes: u64,
+    chd_path: PathBuf,
+    source_path: PathBuf,
     cancel: CancelToken,
-) -> ChdResult<()> {
-    let iso_path = if output_path.extension().is_some() {
-        output_path.clone()
+) -> ChdResult<ChdComparison> {
+    let is_cue = source_path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("cue"));
+    let sources = if is_cue {
+        let cue_sheet = CueParser::new(&source_path).parse().await?;
+        if cue_sheet.files.is_empty() {
+            return Err(ChdError::NoFileReferencedInCueSheet);
+        }
+        let cue_dir = source_path.parent().unwrap_or(std::path::Path::new("."));
+        cue_sheet
+            .files
+            .iter()
+            .map(|f| cue_dir.join(&f.filename))
+            .collect()
     } else {
-        output_path.with_extension("iso")
+        vec![source_path]
     };
+    let mut source_bytes = 0u64;
+    for path in &sources {
+        source_bytes += fs::metadata(path).await?.len();
+    }
 
-    let total_mb = logical_bytes as f64 / BYTES_PER_MB;
     progress.start(
-        logical_bytes,
-        &format!("Extracting from CHD (~{:.2} MB)", total_mb),
+        source_bytes,
+        &format!(
+            "Comparing CHD with source (~{:.2} MB)",
+            source_bytes as f64 / BYTES_PER_MB
+        ),
     );
-
-    let write_path = scratch_output_path(&iso_path)?;
-    let input_owned = input_path.clone();
-    let write_owned = write_path.to_path_buf();
     let cancel_bg = cancel.clone();
     let bytes_done = Arc::new(AtomicU64::new(0));
     let bytes_done_bg = bytes_done.clone();
 
-    let handle = tokio::task::spawn_blocking(move || -> ChdResult<()> {
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<ChdComparison> {
+        use crate::chd::models::DVD_SECTOR_SIZE;
         use crate::chd::reader::open_chd_sync;
         use crate::chd::reader::worker::{
-            ChdExtractWork, ChdExtractedOut, extract_hunks_dvd, make_chd_dvd_extract_workers,
+            ChdExtractWork, ChdExtractedOut, CompareStop, cd_hunk_payload, compare_hunks,
+            dvd_hunk_payload, make_chd_dvd_extract_workers, make_chd_extract_workers,
         };
         use crate::util::worker_pool::{Pool, parallelism};
+        use std::io::Read;
 
-        let handle = open_chd_sync(&input_owned)?;
+        let mut source: Box<dyn Read> = Box::new(std::io::empty());
+        for path in &sources {
+            let file = std::fs::File::open(path)?;
+            source =
+                Box::new(source.chain(std::io::BufReader::with_capacity(IO_BUFFER_SIZE, file)));
+        }
+
+        let handle = open_chd_sync(&chd_path)?;
         let hunk_bytes = handle.header.hunk_bytes as usize;
-        let logical_bytes = handle.header.logical_bytes;
+        let is_dvd = handle
+            .metadata
+            .iter()
+            .any(|m| m.tag == CHD_METADATA_TAG_DVD);
 
-        let iso_file = std::fs::File::create(&write_owned)?;
-        let mut iso_writer = std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, iso_file);
+        if is_dvd {
+            let logical_bytes = handle.header.logical_bytes;
+            let pool: Pool<ChdExtractWork, ChdExtractedOut, ChdError> =
+                Pool::spawn(make_chd_dvd_extract_workers(
+                    parallelism(),
+                    &handle.file,
+                    hunk_bytes,
+                    handle.header.compressors(),
+                )?);
+            let result = compare_hunks(
+                &pool,
+                &handle.map,
+                &mut source,
+                &bytes_done_bg,
+                &cancel_bg,
+                |seq, out| dvd_hunk_payload(out.hunk, seq, hunk_bytes, logical_bytes),
+            );
+            pool.shutdown();
+            let sector = DVD_SECTOR_SIZE as u64;
+            return Ok(match result? {
+                None => ChdComparison::Identical {
+                    sectors: logical_bytes / sector,
+                },
+                Some(CompareStop::Differs(offset)) => ChdComparison::SectorDiffers {
+                    lba: offset / sector,
+                    track: None,
+                    byte_offset: offset,
+                },
+                Some(CompareStop::SourceEnds(offset)) => ChdComparison::SourceShorter {
+                    lba: offset / sector,
+                },
+                Some(CompareStop::ChdEnds(offset)) => ChdComparison::ChdShorter {
+                    lba: offset / sector,
+                },
+            });
+        }
 
-        let workers = make_chd_dvd_extract_workers(
-            parallelism(),
-            &handle.file,
-            hunk_bytes,
-            handle.header.compressors(),
-        )?;
-        let pool: Pool<ChdExtractWork, ChdExtractedOut, ChdError> = Pool::spawn(workers);
+        let cd_meta = handle
+            .metadata
+            .iter()
+            .find(|m| m.tag == CHD_METADATA_TAG_CD)
+            .ok_or_else(|| ChdError::InvalidTrackMetadata("no CHT2 metadata found".to_string()))?;
+        let meta_str = String::from_utf8_lossy(&cd_meta.data);
+        let meta_str = meta_str.trim_end_matches('\0');
+        let tracks = parse_chd_track_metadata(meta_str)?;
+        let (frame_sizes, frame_track) = chd_frame_spans(&tracks);
+        let frame_starts: Vec<u64> = frame_sizes
+            .iter()
+            .scan(0u64, |next, &size| {
+                let start = *next;
+                *next += size as u64;
+                Some(start)
+            })
+            .collect();
+        // LBA of the frame holding stream byte `offset`.
+        let lba_of = |offset: u64| frame_starts.partition_point(|&start| start <= offset) - 1;
 
-        let extract_result = extract_hunks_dvd(
+        let pool: Pool<ChdExtractWork, ChdExtractedOut, ChdError> = Pool::spawn(
+            make_chd_extract_workers(parallelism(), &handle.file, hunk_bytes)?,
+        );
+        let result = compare_hunks(
             &pool,
             &handle.map,
-            &mut iso_writer,
-            hunk_bytes,
-            logical_bytes,
+            &mut source,
             &bytes_done_bg,
             &cancel_bg,
+            |seq, out| cd_hunk_payload(&out.hunk, seq, hunk_bytes, &frame_sizes, &[]),
         );
         pool.shutdown();
-        extract_result?;
+        Ok(match result? {
+            None => ChdComparison::Identical {
+                sectors: frame_sizes.len() as u64,
+            },
+            Some(CompareStop::Differs(offset)) => {
+                let lba = lba_of(offset);
+                ChdComparison::SectorDiffers {
+                    lba: lba as u64,
+                    track: Some(tracks[frame_track[lba]].track_number),
+                    byte_offset: offset,
+                }
+            }
+            Some(CompareStop::SourceEnds(offset)) => ChdComparison::SourceShorter {
+                lba: lba_of(offset) as u64,
+            },
+            Some(CompareStop::ChdEnds(_)) => ChdComparison::ChdShorter {
+                lba: frame_sizes.len() as u64,
+            },
+        })
+    });
 
-        use std::io::Write as _;
-        iso_writer.flush()?;
-        Ok(())
+    await_with_progress_cancel(progress, &bytes_done, handle, &cancel, || {
+        ChdError::Cancelled
+    })
+    .await
+}
+
+/// Peek a CHD's metadata to tell DVD-mode (flat ISO) apart from
+/// CD-mode (bin/cue with CHT2 track metadata) without extracting
+/// anything. Used by [`crate::pipeline::chd_to_cso_cancellable`] to
+/// reject CD-mode CHDs up front, since CSO/ZSO have no track layout.
+pub async fn is_dvd_mode_chd(path: PathBuf) -> ChdResult<bool> {
+    tokio::task::spawn_blocking(move || -> ChdResult<bool> {
+        let handle = crate::chd::reader::open_chd_sync(&path)?;
+        Ok(handle
+            .metadata
+            .iter()
+            .any(|m| m.tag == CHD_METADATA_TAG_DVD))
+    })
+    .await?
+}
+
+/// DVD extract path: one flat `.iso`, no cue sheet.
+async fn extract_dvd_iso(
+    progress: &dyn ProgressReporter,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    logical_bytes: u64,
+    cancel: CancelToken,
+) -> ChdResult<()> {
+    let iso_path = if output_path.extension().is_some() {
+        output_path.clone()
+    } else {
+        output_path.with_extension("iso")
+    };
+
+    let total_mb = logical_bytes as f64 / BYTES_PER_MB;
+    progress.start(
+        logical_bytes,
+        &format!("Extracting from CHD (~{:.2} MB)", total_mb),
+    );
+
+    let write_path = scratch_output_path(&iso_path)?;
+    let input_owned = input_path.clone();
+    let write_owned = write_path.to_path_buf();
+    let cancel_bg = cancel.clone();
+    let bytes_done = Arc::new(AtomicU64::new(0));
+    let bytes_done_bg = bytes_done.clone();
+
+    let handle = tokio::task::spawn_blocking(move || -> ChdResult<()> {
+        let handle = crate::chd::reader::open_chd_sync(&input_owned)?;
+        let iso_file = std::fs::File::create(&write_owned)?;
+        let mut iso_writer = std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, iso_file);
+        write_dvd_stream(&handle, &mut iso_writer, &bytes_done_bg, &cancel_bg)
     });
 
     if let Err(err) = await_with_progress_cancel(
@@ -891,6 +1773,96 @@ async fn extract_dvd_iso(
     Ok(())
 }
 
+/// Extract a DVD-mode CHD's ISO straight into `writer`, such as stdout,
+/// with no scratch file: the ISO is written front to back and never
+/// revisited. A CD-mode CHD is [`ChdError::CdModeNotStreamable`]. On
+/// error or cancel, whatever was already written stays written.
+pub async fn extract_dvd_to_writer_cancellable<W>(
+    progress: &dyn ProgressReporter,
+    input_path: PathBuf,
+    writer: W,
+    parent_path: Option<PathBuf>,
+    cancel: CancelToken,
+) -> ChdResult<()>
+where
+    W: std::io::Write + Send + 'static,
+{
+    if parent_path.is_some() {
+        return Err(ChdError::ParentChdNotSupported);
+    }
+    let input_owned = input_path.clone();
+    let handle =
+        tokio::task::spawn_blocking(move || crate::chd::reader::open_chd_sync(&input_owned))
+            .await??;
+    if !handle
+        .metadata
+        .iter()
+        .any(|m| m.tag == CHD_METADATA_TAG_DVD)
+    {
+        return Err(ChdError::CdModeNotStreamable);
+    }
+
+    let logical_bytes = handle.header.logical_bytes;
+    let total_mb = logical_bytes as f64 / BYTES_PER_MB;
+    progress.start(
+        logical_bytes,
+        &format!("Extracting from CHD (~{:.2} MB)", total_mb),
+    );
+    let cancel_bg = cancel.clone();
+    let bytes_done = Arc::new(AtomicU64::new(0));
+    let bytes_done_bg = bytes_done.clone();
+    let task = tokio::task::spawn_blocking(move || -> ChdResult<()> {
+        let mut writer = std::io::BufWriter::with_capacity(IO_BUFFER_SIZE, writer);
+        write_dvd_stream(&handle, &mut writer, &bytes_done_bg, &cancel_bg)
+    });
+    await_with_progress_cancel(progress, &bytes_done, task, &cancel, || ChdError::Cancelled)
+        .await?;
+
+    info!(
+        "Extracted: {:.2} MB ISO from {}",
+        total_mb,
+        input_path.display()
+    );
+    Ok(())
+}
+
+/// Decode every hunk of the DVD-mode CHD behind `handle` into `writer`
+/// in order, the last one cut to the logical size, then flush.
+fn write_dvd_stream<W: std::io::Write + Send>(
+    handle: &crate::chd::reader::SyncChdHandle,
+    writer: &mut W,
+    bytes_done: &Arc<AtomicU64>,
+    cancel: &CancelToken,
+) -> ChdResult<()> {
+    use crate::chd::reader::worker::{
+        ChdExtractWork, ChdExtractedOut, extract_hunks_dvd, make_chd_dvd_extract_workers,
+    };
+    use crate::util::worker_pool::{Pool, parallelism};
+
+    let hunk_bytes = handle.header.hunk_bytes as usize;
+    let workers = make_chd_dvd_extract_workers(
+        parallelism(),
+        &handle.file,
+        hunk_bytes,
+        handle.header.compressors(),
+    )?;
+    let pool: Pool<ChdExtractWork, ChdExtractedOut, ChdError> = Pool::spawn(workers);
+
+    let extract_result = extract_hunks_dvd(
+        &pool,
+        &handle.map,
+        writer,
+        hunk_bytes,
+        handle.header.logical_bytes,
+        bytes_done,
+        cancel,
+    );
+    pool.shutdown();
+    extract_result?;
+    writer.flush()?;
+    Ok(())
+}
+
 pub async fn verify_chd(
     progress: &dyn ProgressReporter,
     input_path: PathBuf,
@@ -1284,6 +2256,7 @@ pub(crate) mod test_fixtures {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cd::FRAME_SIZE;
     use crate::util::NoProgress;
     use test_fixtures::mixed_iso;
 
@@ -1301,7 +2274,19 @@ mod tests {
             ChdDvdOptions {
                 hunk_size,
                 allow_zstd,
+                lzma_level: None,
+                zstd_level: None,
                 force: false,
+                timing: false,
+                max_memory: None,
+                resume: false,
+                comment: None,
+                fast: false,
+                sector_size: None,
+                trim_trailing_zeros: false,
+                strict: false,
+                uncompressed_map: false,
+                single_codec: None,
             },
             CancelToken::new(),
         )
@@ -1321,6 +2306,88 @@ mod tests {
         assert_eq!(restored, iso);
     }
 
+    /// Every CHD writer fills a sibling scratch file and renames it over
+    /// the target only after `finalize`, so a failed or cancelled run must
+    /// leave neither a truncated `.chd` nor the scratch file behind.
+    #[tokio::test]
+    async fn failed_or_cancelled_compress_leaves_no_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, mixed_iso(4)).unwrap();
+        let chd_path = dir.path().join("game.chd");
+
+        let bad_hunk = ChdDvdOptions {
+            hunk_size: Some(3000),
+            allow_zstd: false,
+            lzma_level: None,
+            zstd_level: None,
+            force: false,
+            timing: false,
+            max_memory: None,
+            resume: false,
+            comment: None,
+            fast: false,
+            sector_size: None,
+            trim_trailing_zeros: false,
+            strict: false,
+            uncompressed_map: false,
+            single_codec: None,
+        };
+        let cancelled = CancelToken::new();
+        cancelled.cancel();
+        for (opts, cancel) in [
+            (bad_hunk, CancelToken::new()),
+            (ChdDvdOptions::default(), cancelled),
+        ] {
+            let result = convert_iso_to_chd(
+                &NoProgress,
+                iso_path.clone(),
+                chd_path.clone(),
+                opts,
+                cancel,
+            )
+            .await;
+            assert!(result.is_err());
+            let names: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|e| e.unwrap().file_name())
+                .collect();
+            assert_eq!(names, ["game.iso"], "leftover files: {names:?}");
+        }
+    }
+
+    /// A resumable write that finishes publishes the CHD and removes
+    /// both `.chdtmp` files.
+    #[tokio::test]
+    async fn resumable_dvd_write_cleans_up_its_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso = mixed_iso(10);
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, &iso).unwrap();
+        let chd_path = dir.path().join("game.chd");
+
+        convert_iso_to_chd(
+            &NoProgress,
+            iso_path,
+            chd_path.clone(),
+            ChdDvdOptions {
+                resume: true,
+                ..ChdDvdOptions::default()
+            },
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!writer::checkpoint::scratch_path(&chd_path).exists());
+        assert!(!writer::checkpoint::state_path(&chd_path).exists());
+        let restored = dir.path().join("restored.iso");
+        extract_from_chd(&NoProgress, chd_path, restored.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(restored).unwrap(), iso);
+    }
+
     #[tokio::test]
     async fn dvd_chd_round_trips_with_default_codecs() {
         round_trip(false, None).await;
@@ -1331,6 +2398,55 @@ mod tests {
         round_trip(true, Some(2048)).await;
     }
 
+    #[tokio::test]
+    async fn comment_is_unhashed_and_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso = mixed_iso(6);
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, &iso).unwrap();
+
+        let plain = dir.path().join("plain.chd");
+        let commented = dir.path().join("commented.chd");
+        for (out, comment) in [(&plain, None), (&commented, Some("redump 2026-10-18"))] {
+            let opts = ChdDvdOptions {
+                comment: comment.map(str::to_owned),
+                ..ChdDvdOptions::default()
+            };
+            convert_iso_to_chd(
+                &NoProgress,
+                iso_path.clone(),
+                out.clone(),
+                opts,
+                CancelToken::new(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let plain_info = info::read_info(&plain).unwrap();
+        let info = info::read_info(&commented).unwrap();
+        assert_eq!(plain_info.comment, None);
+        assert_eq!(
+            info.comment.as_deref(),
+            Some(concat!(
+                "rom-converto (",
+                env!("CARGO_PKG_VERSION"),
+                "): redump 2026-10-18"
+            ))
+        );
+        assert_eq!(info.sha1, plain_info.sha1);
+        assert_eq!(info.raw_sha1, plain_info.raw_sha1);
+
+        verify_chd(&NoProgress, commented.clone(), None, false)
+            .await
+            .unwrap();
+        let out_base = dir.path().join("restored");
+        extract_from_chd(&NoProgress, commented, out_base.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(out_base.with_extension("iso")).unwrap(), iso);
+    }
+
     #[tokio::test]
     async fn corrupted_dvd_chd_fails_verify_and_extract() {
         let dir = tempfile::tempdir().unwrap();
@@ -1565,6 +2681,36 @@ mod tests {
         assert_eq!(std::fs::read(out_cue.with_extension("bin")).unwrap(), iso);
     }
 
+    /// The generated cue names the bin as text, so a non-UTF-8 bin name
+    /// is refused up front instead of writing a cue that points at a
+    /// lossily renamed file.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cd_extract_refuses_non_utf8_bin_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, mixed_iso(4)).unwrap();
+        let chd_path = dir.path().join("game.chd");
+        convert_disc_to_chd(
+            &NoProgress,
+            iso_path,
+            chd_path.clone(),
+            Some(DiscMode::Cd),
+            ChdDvdOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let out_cue = dir.path().join(std::ffi::OsStr::from_bytes(b"g\xffme.cue"));
+        let err = extract_from_chd(&NoProgress, chd_path, out_cue.clone(), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ChdError::NonUtf8BinName(_)), "{err}");
+        assert!(!out_cue.with_extension("bin").exists());
+    }
+
     /// digest_chd_tracks over a CD-mode CHD must match the extracted
     /// bin: `whole` equals the bin's hash and the single track's digest
     /// equals the same, with track datasize accounting for padding
@@ -1584,6 +2730,13 @@ mod tests {
             iso_path,
             chd_path.clone(),
             false,
+            false,
+            None,
+            None,
+            false,
+            false,
+            false,
+            None,
             CancelToken::new(),
         )
         .await
@@ -1613,37 +2766,679 @@ mod tests {
         assert_eq!(tracks[0].digests, bin_hash, "single track equals whole bin");
     }
 
-    /// digest_chd_tracks over a DVD-mode CHD returns an empty track
-    /// list and the flat ISO digest, matching a hash of the extracted
-    /// iso.
     #[tokio::test]
-    async fn digest_chd_tracks_dvd_matches_extracted_iso() {
-        use crate::util::hash::{HashAlgo, hash_file};
+    async fn plan_cd_layout_matches_the_metadata_spans() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("game.bin"), vec![0u8; 8 * 2352]).unwrap();
+        let cue_path = dir.path().join("game.cue");
+        std::fs::write(
+            &cue_path,
+            "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 00:00:03\n    INDEX 01 00:00:05\n",
+        )
+        .unwrap();
+
+        let layout = plan_cd_layout(&cue_path, SECTOR_SIZE).await.unwrap();
+        assert_eq!(layout.files, vec!["game.bin".to_string()]);
+        let rows: Vec<_> = layout
+            .tracks
+            .iter()
+            .map(|t| (t.number, t.track_type, t.chd_type, t.start_lba, t.frames))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (1, "MODE1/2352", "MODE1_RAW", 0, 5),
+                (2, "AUDIO", "AUDIO", 5, 3),
+            ]
+        );
+        assert!(layout.tracks.iter().all(|t| t.file == "game.bin"));
+        assert!(!dir.path().join("game.chd").exists());
+    }
+
+    /// Compress `bin` behind a one-track `track_type` cue, reading it at
+    /// `sector_size`, and return the CHD path.
+    async fn compress_bin(
+        dir: &std::path::Path,
+        bin: &[u8],
+        track_type: &str,
+        sector_size: usize,
+    ) -> ChdResult<PathBuf> {
+        std::fs::write(dir.join("game.bin"), bin).unwrap();
+        let cue_path = dir.join("game.cue");
+        std::fs::write(
+            &cue_path,
+            format!("FILE \"game.bin\" BINARY\n  TRACK 01 {track_type}\n    INDEX 01 00:00:00\n"),
+        )
+        .unwrap();
+        let chd_path = dir.join("game.chd");
+        convert_to_chd(
+            &NoProgress,
+            cue_path,
+            chd_path.clone(),
+            false,
+            false,
+            None,
+            None,
+            false,
+            sector_size,
+            false,
+            false,
+            false,
+            None,
+            CancelToken::new(),
+        )
+        .await?;
+        Ok(chd_path)
+    }
 
+    #[tokio::test]
+    async fn trim_trailing_zeros_drops_zero_hunks_from_an_iso() {
         let dir = tempfile::tempdir().unwrap();
-        let iso = mixed_iso(20);
+        // 3 data sectors then 5 zero ones, in 2-sector hunks: the hunk
+        // holding the last data sector stays, the two after it go.
+        let mut iso = mixed_iso(3);
+        iso.resize(8 * 2048, 0);
         let iso_path = dir.path().join("game.iso");
         std::fs::write(&iso_path, &iso).unwrap();
+
         let chd_path = dir.path().join("game.chd");
+        let opts = ChdDvdOptions {
+            hunk_size: Some(4096),
+            trim_trailing_zeros: true,
+            ..ChdDvdOptions::default()
+        };
         convert_iso_to_chd(
             &NoProgress,
             iso_path,
             chd_path.clone(),
-            ChdDvdOptions::default(),
+            opts,
             CancelToken::new(),
         )
         .await
         .unwrap();
 
-        let out_iso = dir.path().join("restored.iso");
-        extract_from_chd(&NoProgress, chd_path.clone(), out_iso.clone(), None)
+        let restored = dir.path().join("restored.iso");
+        extract_from_chd(&NoProgress, chd_path, restored.clone(), None)
             .await
             .unwrap();
+        assert_eq!(std::fs::read(restored).unwrap(), iso[..4 * 2048]);
+    }
 
-        let algos = [HashAlgo::Sha1, HashAlgo::Md5];
-        let bytes_done = Arc::new(AtomicU64::new(0));
-        let (tracks, whole) = tokio::task::spawn_blocking({
-            let chd_path = chd_path.clone();
+    #[tokio::test]
+    async fn trim_trailing_zeros_keeps_the_last_track_of_a_cue() {
+        let dir = tempfile::tempdir().unwrap();
+        // Data in the first hunk only, zeros to sector 40, and an audio
+        // track starting at sector 12 that the trim must not remove.
+        let mut bin: Vec<u8> = (0..6 * SECTOR_SIZE).map(|i| (i % 249) as u8 + 1).collect();
+        bin.resize(40 * SECTOR_SIZE, 0);
+        std::fs::write(dir.path().join("game.bin"), &bin).unwrap();
+        let cue_path = dir.path().join("game.cue");
+        std::fs::write(
+            &cue_path,
+            "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:12\n",
+        )
+        .unwrap();
+
+        let chd_path = dir.path().join("game.chd");
+        convert_to_chd(
+            &NoProgress,
+            cue_path,
+            chd_path.clone(),
+            false,
+            false,
+            None,
+            None,
+            false,
+            SECTOR_SIZE,
+            true,
+            false,
+            false,
+            None,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let out_cue = dir.path().join("restored.cue");
+        extract_from_chd(&NoProgress, chd_path, out_cue.clone(), None)
+            .await
+            .unwrap();
+        let restored = std::fs::read(out_cue.with_extension("bin")).unwrap();
+        assert_eq!(restored, bin[..16 * SECTOR_SIZE]);
+        let cue = std::fs::read_to_string(out_cue).unwrap();
+        assert!(cue.contains("TRACK 02 AUDIO"), "{cue}");
+    }
+
+    /// Records the warnings an operation raises.
+    #[derive(Default)]
+    struct WarnLog(std::sync::Mutex<Vec<String>>);
+
+    impl ProgressReporter for WarnLog {
+        fn start(&self, _: u64, _: &str) {}
+        fn inc(&self, _: u64) {}
+        fn finish(&self) {}
+        fn warn(&self, message: &str) {
+            self.0.lock().unwrap().push(message.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn bin_too_short_for_its_three_track_cue_warns_or_fails_when_strict() {
+        let dir = tempfile::tempdir().unwrap();
+        // Tracks start at 0, 300, and 600, but the bin ends at 650, 250
+        // frames short of the last track's minimum length.
+        std::fs::write(dir.path().join("game.bin"), vec![0u8; 650 * SECTOR_SIZE]).unwrap();
+        let cue_path = dir.path().join("game.cue");
+        std::fs::write(
+            &cue_path,
+            "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:04:00\n  TRACK 03 AUDIO\n    INDEX 01 00:08:00\n",
+        )
+        .unwrap();
+        let compress = |progress, strict| {
+            convert_to_chd(
+                progress,
+                cue_path.clone(),
+                dir.path().join("game.chd"),
+                false,
+                false,
+                None,
+                None,
+                false,
+                SECTOR_SIZE,
+                false,
+                strict,
+                false,
+                None,
+                CancelToken::new(),
+            )
+        };
+
+        let err = compress(&NoProgress, true).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ChdError::BinShorterThanCue {
+                    track: 3,
+                    start: 600,
+                    bin_frames: 650,
+                    min_frames: 300,
+                }
+            ),
+            "{err}"
+        );
+        assert!(!dir.path().join("game.chd").exists());
+
+        let short = WarnLog::default();
+        compress(&short, false).await.unwrap();
+        assert!(dir.path().join("game.chd").is_file());
+        let warnings = short.0.lock().unwrap().clone();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(
+            warnings[0].contains("track 3 starts at frame 600"),
+            "{warnings:?}"
+        );
+
+        // Long enough: no warning.
+        std::fs::write(dir.path().join("game.bin"), vec![0u8; 900 * SECTOR_SIZE]).unwrap();
+        std::fs::remove_file(dir.path().join("game.chd")).unwrap();
+        let long = WarnLog::default();
+        compress(&long, true).await.unwrap();
+        assert!(long.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cue_rems_survive_compress_and_extract_without_touching_the_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin: Vec<u8> = (0..16 * SECTOR_SIZE).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join("game.bin"), &bin).unwrap();
+        let body = "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n";
+        let rems = "REM GENRE \"Platform\"\nREM DATE 1998\nREM DISCID 0A01B202\nREM X-DUMPER my \"tool\" 1.0\n";
+        let mut sha1s = Vec::new();
+        for (name, cue) in [
+            ("plain", body.to_string()),
+            ("rems", format!("{rems}{body}")),
+        ] {
+            let cue_path = dir.path().join(format!("{name}.cue"));
+            std::fs::write(&cue_path, cue).unwrap();
+            let chd_path = dir.path().join(format!("{name}.chd"));
+            convert_to_chd(
+                &NoProgress,
+                cue_path,
+                chd_path.clone(),
+                false,
+                false,
+                None,
+                None,
+                false,
+                SECTOR_SIZE,
+                false,
+                false,
+                false,
+                None,
+                CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            let info = crate::chd::info::read_info(&chd_path).unwrap();
+            assert_eq!(
+                info.metadata_tags.iter().any(|t| t.tag == "CREM"),
+                name == "rems"
+            );
+            sha1s.push(info.sha1);
+
+            let out_cue = dir.path().join(format!("{name}-out.cue"));
+            extract_from_chd(&NoProgress, chd_path, out_cue.clone(), None)
+                .await
+                .unwrap();
+            let cue = std::fs::read_to_string(&out_cue).unwrap();
+            if name == "rems" {
+                assert!(
+                    cue.starts_with(
+                        "REM GENRE \"Platform\"\r\nREM DATE 1998\r\nREM DISCID 0A01B202\r\n\
+                         REM X-DUMPER my \"tool\" 1.0\r\nFILE \"rems-out.bin\" BINARY\r\n"
+                    ),
+                    "{cue}"
+                );
+            } else {
+                assert!(cue.starts_with("FILE "), "{cue}");
+            }
+            assert_eq!(std::fs::read(out_cue.with_extension("bin")).unwrap(), bin);
+        }
+        // CREM is unhashed: the REMs leave the CHD SHA-1 as chdman has it.
+        assert_eq!(sha1s[0], sha1s[1]);
+    }
+
+    #[tokio::test]
+    async fn uncompressed_map_round_trips_with_the_same_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin: Vec<u8> = (0..24 * SECTOR_SIZE).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join("game.bin"), &bin).unwrap();
+        let cue_path = dir.path().join("game.cue");
+        std::fs::write(
+            &cue_path,
+            "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+        )
+        .unwrap();
+        let mut infos = Vec::new();
+        for uncompressed_map in [false, true] {
+            let chd_path = dir.path().join(format!("{uncompressed_map}.chd"));
+            convert_to_chd(
+                &NoProgress,
+                cue_path.clone(),
+                chd_path.clone(),
+                false,
+                false,
+                None,
+                None,
+                false,
+                SECTOR_SIZE,
+                false,
+                false,
+                uncompressed_map,
+                None,
+                CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            verify_chd(&NoProgress, chd_path.clone(), None, false)
+                .await
+                .unwrap();
+
+            let chd = std::fs::read(&chd_path).unwrap();
+            let map_offset = u64::from_be_bytes(chd[40..48].try_into().unwrap()) as usize;
+            assert_eq!(chd[map_offset + 15], uncompressed_map as u8);
+
+            let out_cue = dir.path().join(format!("{uncompressed_map}-out.cue"));
+            extract_from_chd(&NoProgress, chd_path.clone(), out_cue.clone(), None)
+                .await
+                .unwrap();
+            assert_eq!(std::fs::read(out_cue.with_extension("bin")).unwrap(), bin);
+            infos.push(crate::chd::info::read_info(&chd_path).unwrap());
+        }
+        // Only the map's encoding differs; the hashes cover the data.
+        assert_eq!(infos[0].sha1, infos[1].sha1);
+        assert_eq!(infos[0].raw_sha1, infos[1].raw_sha1);
+    }
+
+    #[tokio::test]
+    async fn single_codec_limits_every_hunk_and_must_match_the_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        let iso: Vec<u8> = (0..8 * 4096).map(|i| (i / 3 % 251) as u8).collect();
+        std::fs::write(&iso_path, &iso).unwrap();
+
+        let chd_path = dir.path().join("game.chd");
+        let opts = ChdDvdOptions {
+            single_codec: Some(ChdCodec::Zstd),
+            ..ChdDvdOptions::default()
+        };
+        convert_iso_to_chd(
+            &NoProgress,
+            iso_path.clone(),
+            chd_path.clone(),
+            opts,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        let map = crate::chd::info::read_map(&chd_path).unwrap();
+        assert!(map.iter().all(|entry| entry.storage.to_string() == "zstd"));
+        verify_chd(&NoProgress, chd_path, None, false)
+            .await
+            .unwrap();
+
+        let cd_path = dir.path().join("cd.chd");
+        let opts = ChdDvdOptions {
+            single_codec: Some(ChdCodec::Cdlz),
+            ..ChdDvdOptions::default()
+        };
+        let err = convert_iso_to_chd(
+            &NoProgress,
+            iso_path,
+            cd_path.clone(),
+            opts,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ChdError::SingleCodecUnavailable { codec: "cdlz" }
+        ));
+        assert!(!cd_path.exists());
+    }
+
+    #[tokio::test]
+    async fn cooked_2048_byte_bin_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin: Vec<u8> = (0..10 * 2048).map(|i| (i % 251) as u8).collect();
+        let chd_path = compress_bin(dir.path(), &bin, "MODE1/2048", 2048)
+            .await
+            .unwrap();
+
+        verify_chd(&NoProgress, chd_path.clone(), None, false)
+            .await
+            .unwrap();
+        let out_cue = dir.path().join("restored.cue");
+        extract_from_chd(&NoProgress, chd_path, out_cue.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(out_cue.with_extension("bin")).unwrap(), bin);
+    }
+
+    #[tokio::test]
+    async fn subchannel_bin_is_stored_with_rw_raw_subtype() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin: Vec<u8> = (0..10 * FRAME_SIZE).map(|i| (i % 251) as u8).collect();
+        let chd_path = compress_bin(dir.path(), &bin, "MODE1/2352", FRAME_SIZE)
+            .await
+            .unwrap();
+
+        let chd = std::fs::read(&chd_path).unwrap();
+        assert!(
+            chd.windows(b"SUBTYPE:RW_RAW FRAMES:10 ".len())
+                .any(|w| w == b"SUBTYPE:RW_RAW FRAMES:10 ")
+        );
+        verify_chd(&NoProgress, chd_path, None, false)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sector_size_must_be_supported_and_cover_the_tracks() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = compress_bin(dir.path(), &vec![0u8; 4 * 2336], "MODE2/2336", 2336)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ChdError::UnsupportedSectorSize(2336)));
+        let err = compress_bin(dir.path(), &vec![0u8; 4 * 2048], "AUDIO", 2048)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ChdError::CookedTrackMode {
+                track: 1,
+                mode: "AUDIO"
+            }
+        ));
+        assert!(!dir.path().join("game.chd").exists());
+    }
+
+    /// A partial sector at the end of the bin is dropped, unless strict.
+    #[tokio::test]
+    async fn a_partial_trailing_sector_is_dropped_unless_strict() {
+        assert_eq!(
+            bin_sector_count(&NoProgress, 4 * 2352 + 1, 2352, false).unwrap(),
+            4
+        );
+        assert!(matches!(
+            bin_sector_count(&NoProgress, 4 * 2352 + 1, 2352, true).unwrap_err(),
+            ChdError::BinNotSectorAligned {
+                size: 9409,
+                sector_size: 2352
+            }
+        ));
+        let dir = tempfile::tempdir().unwrap();
+        let chd_path = compress_bin(dir.path(), &vec![0u8; 4 * 2352 + 1], "MODE1/2352", 2352)
+            .await
+            .unwrap();
+        verify_chd(&NoProgress, chd_path, None, false)
+            .await
+            .unwrap();
+    }
+
+    /// `AudioEndian::Big` swaps each 16-bit sample of the audio track
+    /// and leaves the data track byte-identical.
+    #[tokio::test]
+    async fn big_endian_audio_extract_swaps_only_audio_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..4 * 2352).map(|i| (i % 251) as u8).collect();
+        let audio: Vec<u8> = (0..4 * 2352).map(|i| (i % 239) as u8).collect();
+        let bin_path = dir.path().join("game.bin");
+        std::fs::write(&bin_path, [data.clone(), audio.clone()].concat()).unwrap();
+        let cue_path = dir.path().join("game.cue");
+        std::fs::write(
+            &cue_path,
+            "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:04\n",
+        )
+        .unwrap();
+        let chd_path = dir.path().join("game.chd");
+        convert_to_chd(
+            &NoProgress,
+            cue_path,
+            chd_path.clone(),
+            false,
+            false,
+            None,
+            None,
+            false,
+            SECTOR_SIZE,
+            false,
+            false,
+            false,
+            None,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let out_cue = dir.path().join("restored.cue");
+        extract_from_chd_with_options(
+            &NoProgress,
+            chd_path,
+            out_cue.clone(),
+            None,
+            ChdExtractOptions {
+                audio_endian: AudioEndian::Big,
+            },
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let restored = std::fs::read(out_cue.with_extension("bin")).unwrap();
+        assert_eq!(restored.len(), data.len() + audio.len());
+        assert_eq!(restored[..data.len()], data[..]);
+        let mut swapped = audio.clone();
+        crate::chd::reader::worker::swap_sample_bytes(&mut swapped);
+        assert_eq!(restored[data.len()..], swapped[..]);
+    }
+
+    /// Two-track (data + audio) cue/bin and its CD-mode CHD.
+    async fn two_track_cd(dir: &std::path::Path) -> (PathBuf, PathBuf, PathBuf) {
+        let bin: Vec<u8> = (0..8 * 2352).map(|i| (i % 251) as u8).collect();
+        let bin_path = dir.join("game.bin");
+        std::fs::write(&bin_path, bin).unwrap();
+        let cue_path = dir.join("game.cue");
+        std::fs::write(
+            &cue_path,
+            "FILE \"game.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 01 00:00:04\n",
+        )
+        .unwrap();
+        let chd_path = dir.join("game.chd");
+        convert_to_chd(
+            &NoProgress,
+            cue_path.clone(),
+            chd_path.clone(),
+            false,
+            false,
+            None,
+            None,
+            false,
+            SECTOR_SIZE,
+            false,
+            false,
+            false,
+            None,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        (cue_path, bin_path, chd_path)
+    }
+
+    async fn compare(chd: &std::path::Path, source: &std::path::Path) -> ChdComparison {
+        compare_chd_to_source(
+            &NoProgress,
+            chd.to_path_buf(),
+            source.to_path_buf(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn compare_reports_the_first_differing_cd_sector() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cue_path, bin_path, chd_path) = two_track_cd(dir.path()).await;
+        assert_eq!(
+            compare(&chd_path, &cue_path).await,
+            ChdComparison::Identical { sectors: 8 }
+        );
+
+        let mut bin = std::fs::read(&bin_path).unwrap();
+        let offset = 5 * 2352 + 100;
+        bin[offset] ^= 0xFF;
+        bin[7 * 2352] ^= 0xFF;
+        std::fs::write(&bin_path, &bin).unwrap();
+        let result = compare(&chd_path, &cue_path).await;
+        assert_eq!(
+            result,
+            ChdComparison::SectorDiffers {
+                lba: 5,
+                track: Some(2),
+                byte_offset: offset as u64,
+            }
+        );
+        assert!(result.to_string().contains("LBA 5 (track 02)"), "{result}");
+    }
+
+    #[tokio::test]
+    async fn compare_reports_a_length_difference() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cue_path, bin_path, chd_path) = two_track_cd(dir.path()).await;
+        let bin = std::fs::read(&bin_path).unwrap();
+
+        std::fs::write(&bin_path, &bin[..6 * 2352 + 10]).unwrap();
+        assert_eq!(
+            compare(&chd_path, &cue_path).await,
+            ChdComparison::SourceShorter { lba: 6 }
+        );
+
+        let mut longer = bin.clone();
+        longer.extend_from_slice(&[0u8; 2352]);
+        std::fs::write(&bin_path, &longer).unwrap();
+        assert_eq!(
+            compare(&chd_path, &cue_path).await,
+            ChdComparison::ChdShorter { lba: 8 }
+        );
+    }
+
+    #[tokio::test]
+    async fn compare_checks_a_dvd_chd_against_its_iso() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut iso = mixed_iso(11);
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, &iso).unwrap();
+        let chd_path = dir.path().join("game.chd");
+        convert_iso_to_chd(
+            &NoProgress,
+            iso_path.clone(),
+            chd_path.clone(),
+            ChdDvdOptions::default(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            compare(&chd_path, &iso_path).await,
+            ChdComparison::Identical { sectors: 11 }
+        );
+
+        iso[9 * 2048 + 1] ^= 0x01;
+        std::fs::write(&iso_path, &iso).unwrap();
+        assert_eq!(
+            compare(&chd_path, &iso_path).await,
+            ChdComparison::SectorDiffers {
+                lba: 9,
+                track: None,
+                byte_offset: 9 * 2048 + 1,
+            }
+        );
+    }
+
+    /// digest_chd_tracks over a DVD-mode CHD returns an empty track
+    /// list and the flat ISO digest, matching a hash of the extracted
+    /// iso.
+    #[tokio::test]
+    async fn digest_chd_tracks_dvd_matches_extracted_iso() {
+        use crate::util::hash::{HashAlgo, hash_file};
+
+        let dir = tempfile::tempdir().unwrap();
+        let iso = mixed_iso(20);
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, &iso).unwrap();
+        let chd_path = dir.path().join("game.chd");
+        convert_iso_to_chd(
+            &NoProgress,
+            iso_path,
+            chd_path.clone(),
+            ChdDvdOptions::default(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let out_iso = dir.path().join("restored.iso");
+        extract_from_chd(&NoProgress, chd_path.clone(), out_iso.clone(), None)
+            .await
+            .unwrap();
+
+        let algos = [HashAlgo::Sha1, HashAlgo::Md5];
+        let bytes_done = Arc::new(AtomicU64::new(0));
+        let (tracks, whole) = tokio::task::spawn_blocking({
+            let chd_path = chd_path.clone();
             let bytes_done = bytes_done.clone();
             move || digest_chd_tracks(&chd_path, &algos, &bytes_done, &CancelToken::new())
         })
@@ -1656,6 +3451,66 @@ mod tests {
         assert_eq!(whole, iso_hash, "whole digest must equal extracted iso");
     }
 
+    /// A write-only sink shared with the test, standing in for a pipe.
+    #[derive(Clone, Default)]
+    struct PipeSink(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for PipeSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn dvd_extract_streams_into_a_plain_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso = mixed_iso(20);
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, &iso).unwrap();
+        let chd_path = dir.path().join("game.chd");
+        convert_iso_to_chd(
+            &NoProgress,
+            iso_path,
+            chd_path.clone(),
+            ChdDvdOptions::default(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let sink = PipeSink::default();
+        extract_dvd_to_writer_cancellable(
+            &NoProgress,
+            chd_path,
+            sink.clone(),
+            None,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(*sink.0.lock().unwrap(), iso);
+
+        let cd_dir = tempfile::tempdir().unwrap();
+        let cd_chd = compress_bin(cd_dir.path(), &vec![0u8; 4 * 2352], "MODE1/2352", 2352)
+            .await
+            .unwrap();
+        let err = extract_dvd_to_writer_cancellable(
+            &NoProgress,
+            cd_chd,
+            PipeSink::default(),
+            None,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ChdError::CdModeNotStreamable));
+    }
+
     #[tokio::test]
     async fn dvd_flag_on_cue_is_rejected() {
         let dir = tempfile::tempdir().unwrap();
@@ -1682,6 +3537,13 @@ mod tests {
             iso_path,
             dir.path().join("game.chd"),
             false,
+            false,
+            None,
+            None,
+            false,
+            false,
+            false,
+            None,
             CancelToken::new(),
         )
         .await;
@@ -1721,6 +3583,68 @@ mod tests {
         assert!(!dreamcast_boot_signature(&head));
     }
 
+    #[tokio::test]
+    async fn disc_set_compresses_each_disc_and_writes_m3u_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in");
+        std::fs::create_dir(&input).unwrap();
+        for (stem, frames) in [
+            ("Game (USA) (Disc 2)", 3),
+            ("Game (USA) (Disc 1)", 2),
+            ("Other", 1),
+        ] {
+            std::fs::write(
+                input.join(format!("{stem}.cue")),
+                format!(
+                    "FILE \"{stem}.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n"
+                ),
+            )
+            .unwrap();
+            std::fs::write(input.join(format!("{stem}.bin")), vec![0u8; frames * 2352]).unwrap();
+        }
+        let output = dir.path().join("out");
+
+        let sets = convert_disc_set_to_chd(
+            &NoProgress,
+            &NoProgress,
+            &input,
+            &output,
+            ChdDvdOptions::default(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].base_title, "Game (USA)");
+        assert_eq!(sets[0].m3u_path, output.join("Game (USA).m3u"));
+        assert_eq!(
+            std::fs::read_to_string(&sets[0].m3u_path).unwrap(),
+            "Game (USA) (Disc 1).chd\nGame (USA) (Disc 2).chd\n"
+        );
+        for disc in &sets[0].discs {
+            verify_chd(&NoProgress, disc.clone(), None, false)
+                .await
+                .unwrap();
+        }
+        assert!(!output.join("Other.chd").exists());
+
+        let err = convert_disc_set_to_chd(
+            &NoProgress,
+            &NoProgress,
+            &input,
+            &output,
+            ChdDvdOptions::default(),
+            CancelToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(&err, ChdError::PlaylistAlreadyExists(path) if *path == sets[0].m3u_path),
+            "{err}"
+        );
+    }
+
     #[test]
     fn padded_track_frames_rounds_to_four() {
         assert_eq!(padded_track_frames(10), 12);
@@ -1826,6 +3750,13 @@ mod tests {
             iso_path,
             our_chd.clone(),
             false,
+            false,
+            None,
+            None,
+            false,
+            false,
+            false,
+            None,
             CancelToken::new(),
         )
         .await
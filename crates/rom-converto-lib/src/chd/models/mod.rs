@@ -6,6 +6,12 @@ use binrw::{BinRead, BinWrite, binrw};
 pub const CHD_V5_HEADER_SIZE: u32 = 124;
 pub const CHD_METADATA_TAG_CD: [u8; 4] = *b"CHT2";
 pub const CHD_METADATA_TAG_DVD: [u8; 4] = *b"DVD ";
+pub const CHD_METADATA_TAG_COMMENT: [u8; 4] = *b"CMNT";
+/// Disc-level `REM` lines of the source cue. rom-converto's own tag;
+/// chdman neither writes nor reads it.
+pub const CHD_METADATA_TAG_CUE_REM: [u8; 4] = *b"CREM";
+/// The only metadata flag CHD v5 defines. Entries have no compression
+/// flag, so metadata is always stored raw and read back as-is.
 pub const CHD_METADATA_FLAG_HASHED: u8 = 0x01;
 pub const CHD_METADATA_RESERVED_BYTES: usize = 8;
 pub const SHA1_BYTES: usize = 20;
@@ -116,4 +122,38 @@ impl ChdMetadataHeader {
             data: vec![0],
         }
     }
+
+    /// Free-form provenance note. Unhashed, so adding one leaves the
+    /// CHD's overall SHA-1 untouched.
+    pub fn new_comment_metadata(comment: String) -> Self {
+        let mut data = comment.into_bytes();
+        data.push(0);
+
+        Self {
+            tag: CHD_METADATA_TAG_COMMENT,
+            flags: 0,
+            reserved: [0; CHD_METADATA_RESERVED_BYTES],
+            data,
+        }
+    }
+
+    /// The source cue's disc-level `REM` lines, one `KEY value` per line.
+    /// Unhashed like the comment, so the CHD's SHA-1 still matches
+    /// chdman's for the same image.
+    pub fn new_cue_rem_metadata(rems: &[(String, String)]) -> Self {
+        let mut data = rems
+            .iter()
+            .map(|(key, value)| format!("{key} {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_bytes();
+        data.push(0);
+
+        Self {
+            tag: CHD_METADATA_TAG_CUE_REM,
+            flags: 0,
+            reserved: [0; CHD_METADATA_RESERVED_BYTES],
+            data,
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use crate::chd::error::{ChdError, ChdResult};
-use crate::cue::models::Msf;
+use crate::cue::models::{Msf, rem_lines};
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct ChdTrackInfo {
@@ -93,12 +93,32 @@ pub(crate) fn parse_chd_track_metadata(metadata_str: &str) -> ChdResult<Vec<ChdT
     Ok(tracks)
 }
 
-pub(crate) fn generate_cue_sheet(bin_filename: &str, tracks: &[ChdTrackInfo]) -> String {
+/// The `REM` key/value pairs stored in a `CREM` metadata entry, one
+/// `KEY value` per line.
+pub(crate) fn parse_cue_rem_metadata(data: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(data)
+        .trim_end_matches('\0')
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The cue for an extracted bin. `rems` come first, as disc-level `REM`
+/// lines; with none the cue matches chdman's.
+pub(crate) fn generate_cue_sheet(
+    bin_filename: &str,
+    tracks: &[ChdTrackInfo],
+    rems: &[(String, String)],
+) -> String {
     // CRLF line endings and the exact indentation below match
     // chdman's `output_track_metadata` in `src/tools/chdman.cpp`
     // so `chd extract` output is byte-identical to `chdman
     // extractcd` for the same input.
-    let mut cue = format!("FILE \"{bin_filename}\" BINARY\r\n");
+    let mut cue = rem_lines(rems);
+    cue.push_str(&format!("FILE \"{bin_filename}\" BINARY\r\n"));
     let mut frame_offset: u32 = 0;
 
     for track in tracks {
@@ -226,7 +246,7 @@ mod tests {
             pregap: 0,
             ..ChdTrackInfo::default()
         }];
-        let cue = generate_cue_sheet("game.bin", &tracks);
+        let cue = generate_cue_sheet("game.bin", &tracks, &[]);
         assert!(cue.starts_with("FILE \"game.bin\" BINARY\r\n"));
         assert!(cue.contains("TRACK 01 MODE1/2352"));
         assert!(cue.contains("INDEX 01 00:00:00"));
@@ -251,7 +271,7 @@ mod tests {
                 ..ChdTrackInfo::default()
             },
         ];
-        let cue = generate_cue_sheet("game.bin", &tracks);
+        let cue = generate_cue_sheet("game.bin", &tracks, &[]);
         assert!(cue.contains("TRACK 02 AUDIO"));
         assert!(cue.contains("PREGAP 00:02:00")); // 150 frames = 2 seconds
         // Track 2 starts at frame 300 = 00:04:00
@@ -276,7 +296,7 @@ mod tests {
                 ..ChdTrackInfo::default()
             },
         ];
-        let cue = generate_cue_sheet("game.bin", &tracks);
+        let cue = generate_cue_sheet("game.bin", &tracks, &[]);
         // Track 1 at 00:00:00, track 2 at 00:01:00 (75 frames = 1 second)
         let lines: Vec<&str> = cue.lines().collect();
         let idx_lines: Vec<&&str> = lines.iter().filter(|l| l.contains("INDEX 01")).collect();
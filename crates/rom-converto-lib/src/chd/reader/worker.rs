@@ -24,7 +24,7 @@ use crate::util::hash::MultiHasher;
 use crate::util::pread::file_read_exact_at;
 use crate::util::worker_pool::{Pool, Worker, drive, parallelism};
 use sha1::{Digest, Sha1};
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Read, Write};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 
@@ -218,66 +218,111 @@ fn resolve_entry(map: &[MapEntry], hunk_index: u32) -> ChdResult<MapEntry> {
 
 /// Drive the extract pipeline: pool of decompressors reading a
 /// shared file via positional reads, reorder-buffered drive,
-/// dedicated writer thread for the output bin.
+/// dedicated writer thread for the output bin. Frames flagged in
+/// `swap_frames` (empty when nothing is swapped) get their 16-bit
+/// samples byte-swapped on the way out.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn extract_hunks(
     pool: &Pool<ChdExtractWork, ChdExtractedOut, ChdError>,
     map: &[MapEntry],
     writer: &mut BufWriter<std::fs::File>,
     hunk_bytes: usize,
     frame_sizes: &[usize],
+    swap_frames: &[bool],
     bytes_done: &Arc<AtomicU64>,
     cancel: &CancelToken,
 ) -> ChdResult<()> {
-    let frames_per_hunk = hunk_bytes / FRAME_SIZE;
-    let total_frames = frame_sizes.len();
-
     run_extract_pipeline(pool, map, writer, bytes_done, cancel, |seq, out| {
-        // Gather payload bytes from the interleaved hunk, dropping
-        // the subcode and any tail past each track's datasize.
-        // `chdman extractcd` writes datasize-wide bins; track padding
-        // frames past the CHT2 frame counts are dropped entirely.
-        let first_frame = seq as usize * frames_per_hunk;
-        let frames_in_hunk = frames_per_hunk.min(total_frames.saturating_sub(first_frame));
-        let mut sectors = Vec::with_capacity(frames_in_hunk * SECTOR_SIZE);
-        for frame in 0..frames_in_hunk {
-            let off = frame * FRAME_SIZE;
-            sectors.extend_from_slice(&out.hunk[off..off + frame_sizes[first_frame + frame]]);
-        }
-        Ok(sectors)
+        Ok(cd_hunk_payload(
+            &out.hunk,
+            seq,
+            hunk_bytes,
+            frame_sizes,
+            swap_frames,
+        ))
     })
 }
 
+/// Gather payload bytes from one decoded CD hunk, dropping the
+/// subcode and any tail past each track's datasize. `chdman
+/// extractcd` writes datasize-wide bins; track padding frames past
+/// the CHT2 frame counts are dropped entirely.
+pub(crate) fn cd_hunk_payload(
+    hunk: &[u8],
+    seq: u64,
+    hunk_bytes: usize,
+    frame_sizes: &[usize],
+    swap_frames: &[bool],
+) -> Vec<u8> {
+    let frames_per_hunk = hunk_bytes / FRAME_SIZE;
+    let first_frame = seq as usize * frames_per_hunk;
+    let frames_in_hunk = frames_per_hunk.min(frame_sizes.len().saturating_sub(first_frame));
+    let mut sectors = Vec::with_capacity(frames_in_hunk * SECTOR_SIZE);
+    for frame in 0..frames_in_hunk {
+        let off = frame * FRAME_SIZE;
+        let start = sectors.len();
+        sectors.extend_from_slice(&hunk[off..off + frame_sizes[first_frame + frame]]);
+        if swap_frames
+            .get(first_frame + frame)
+            .copied()
+            .unwrap_or(false)
+        {
+            swap_sample_bytes(&mut sectors[start..]);
+        }
+    }
+    sectors
+}
+
+/// Swap the two bytes of every 16-bit sample in a CD audio payload.
+pub(crate) fn swap_sample_bytes(payload: &mut [u8]) {
+    for sample in payload.chunks_exact_mut(2) {
+        sample.swap(0, 1);
+    }
+}
+
 /// DVD extract: hunks are already flat sector data, so each hunk is
 /// written as-is, with the final one truncated to `logical_bytes`.
-pub(crate) fn extract_hunks_dvd(
+pub(crate) fn extract_hunks_dvd<W: Write + Send>(
     pool: &Pool<ChdExtractWork, ChdExtractedOut, ChdError>,
     map: &[MapEntry],
-    writer: &mut BufWriter<std::fs::File>,
+    writer: &mut W,
     hunk_bytes: usize,
     logical_bytes: u64,
     bytes_done: &Arc<AtomicU64>,
     cancel: &CancelToken,
 ) -> ChdResult<()> {
     run_extract_pipeline(pool, map, writer, bytes_done, cancel, |seq, out| {
-        let offset = seq * hunk_bytes as u64;
-        let take = ((logical_bytes - offset.min(logical_bytes)) as usize).min(hunk_bytes);
-        let mut hunk = out.hunk;
-        hunk.truncate(take);
-        Ok(hunk)
+        Ok(dvd_hunk_payload(out.hunk, seq, hunk_bytes, logical_bytes))
     })
 }
 
+/// One decoded DVD hunk as it lands in the iso: the final hunk is
+/// truncated to `logical_bytes`.
+pub(crate) fn dvd_hunk_payload(
+    mut hunk: Vec<u8>,
+    seq: u64,
+    hunk_bytes: usize,
+    logical_bytes: u64,
+) -> Vec<u8> {
+    let offset = seq * hunk_bytes as u64;
+    let take = ((logical_bytes - offset.min(logical_bytes)) as usize).min(hunk_bytes);
+    hunk.truncate(take);
+    hunk
+}
+
 /// Shared extract scaffold; `shape` turns one decoded hunk into the
-/// bytes that belong in the output stream.
-fn run_extract_pipeline<F>(
+/// bytes that belong in the output stream. The output is only ever
+/// appended to, so `writer` can be a pipe as well as a file.
+fn run_extract_pipeline<W, F>(
     pool: &Pool<ChdExtractWork, ChdExtractedOut, ChdError>,
     map: &[MapEntry],
-    writer: &mut BufWriter<std::fs::File>,
+    writer: &mut W,
     bytes_done: &Arc<AtomicU64>,
     cancel: &CancelToken,
     mut shape: F,
 ) -> ChdResult<()>
 where
+    W: Write + Send,
     F: FnMut(u64, ChdExtractedOut) -> ChdResult<Vec<u8>>,
 {
     let hunk_count = map.len() as u64;
@@ -286,7 +331,7 @@ where
     let (write_tx, write_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(max_in_flight * 2);
 
     let scope_result: ChdResult<()> = std::thread::scope(|s| {
-        let writer_slot: &mut BufWriter<std::fs::File> = writer;
+        let writer_slot: &mut W = writer;
         let writer_handle = s.spawn(move || -> ChdResult<()> {
             while let Ok(bytes) = write_rx.recv() {
                 writer_slot.write_all(&bytes)?;
@@ -327,6 +372,100 @@ where
     scope_result
 }
 
+/// Where [`compare_hunks`] found the shaped CHD stream and the source
+/// first disagree, as a byte offset into the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompareStop {
+    /// Both sides hold a byte here and the bytes differ.
+    Differs(u64),
+    /// The source ran out while the CHD had more.
+    SourceEnds(u64),
+    /// The CHD ran out while the source had more.
+    ChdEnds(u64),
+}
+
+/// Compare-side variant of [`run_extract_pipeline`]: each hunk is
+/// shaped the way extract would write it and checked against the
+/// next bytes of `source`. Decoding stops at the first difference.
+pub(crate) fn compare_hunks<R, F>(
+    pool: &Pool<ChdExtractWork, ChdExtractedOut, ChdError>,
+    map: &[MapEntry],
+    source: &mut R,
+    bytes_done: &Arc<AtomicU64>,
+    cancel: &CancelToken,
+    mut shape: F,
+) -> ChdResult<Option<CompareStop>>
+where
+    R: Read,
+    F: FnMut(u64, ChdExtractedOut) -> Vec<u8>,
+{
+    // A child token stops the dispatcher on a difference without
+    // cancelling the caller's token.
+    let stop = cancel.child_token();
+    let mut found = None;
+    let mut pos = 0u64;
+    let mut expected = Vec::new();
+
+    let result = drive(
+        pool,
+        map.len() as u64,
+        parallelism() * 2,
+        |chunk_idx| -> ChdResult<ChdExtractWork> {
+            if stop.is_cancelled() {
+                return Err(ChdError::Cancelled);
+            }
+            let entry = resolve_entry(map, chunk_idx as u32)?;
+            Ok(ChdExtractWork { entry })
+        },
+        |seq, out| -> ChdResult<()> {
+            if found.is_some() {
+                return Ok(());
+            }
+            let actual = shape(seq, out);
+            expected.resize(actual.len(), 0);
+            let read = read_up_to(source, &mut expected)?;
+            if let Some(i) = actual[..read]
+                .iter()
+                .zip(&expected[..read])
+                .position(|(a, b)| a != b)
+            {
+                found = Some(CompareStop::Differs(pos + i as u64));
+            } else if read < actual.len() {
+                found = Some(CompareStop::SourceEnds(pos + read as u64));
+            }
+            pos += actual.len() as u64;
+            bytes_done.fetch_add(actual.len() as u64, Ordering::Relaxed);
+            if found.is_some() {
+                stop.cancel();
+            }
+            Ok(())
+        },
+    );
+    match result {
+        Err(ChdError::Cancelled) if found.is_some() => {}
+        other => other?,
+    }
+
+    if found.is_none() && read_up_to(source, &mut [0u8; 1])? > 0 {
+        found = Some(CompareStop::ChdEnds(pos));
+    }
+    Ok(found)
+}
+
+/// Fill as much of `buf` as `source` has left.
+fn read_up_to<R: Read>(source: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match source.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
 /// Verify-side variant: same pool shape but instead of writing
 /// decoded bytes to a file, hash them in order with a single
 /// rolling `Sha1`. The dispatcher's consume closure runs on the
@@ -0,0 +1,493 @@
+//! Resume checkpoints for interrupted CHD writes.
+//!
+//! A resumable write compresses into `<output>.chdtmp` and keeps a
+//! journal at `<output>.chdtmp.state`: a header naming the source and
+//! the header/metadata prefix the write started with, then one record
+//! per hunk carrying its map entry and a CRC-32 of the compressed bytes.
+//! Records are appended only after the hunk bytes they describe have
+//! been flushed, so the journal never runs ahead of the file. On resume
+//! every journaled hunk is re-read and checked against its CRC; the
+//! write continues after the last hunk that checks out.
+
+use crate::chd::compression::ChdCodec;
+use crate::chd::compression::dvd::DvdCodecLevels;
+use crate::chd::error::ChdResult;
+use crate::chd::map::MapEntry;
+use crate::chd::models::SHA1_BYTES;
+use binrw::{BinRead, BinWrite, binrw};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use sha1::{Digest, Sha1};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Bumped whenever the journal layout changes; older journals are
+/// ignored and the write starts over.
+const JOURNAL_VERSION: u32 = 2;
+
+/// How often hunk records are made durable. Ctrl-C and errors always
+/// write a final checkpoint; the interval bounds what a crash loses.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// On-disk size of one [`HunkRecord`].
+const RECORD_BYTES: usize = 11;
+
+const DATA_CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// `<output>.chdtmp`, the partial CHD of a resumable write.
+pub(crate) fn scratch_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".chdtmp");
+    PathBuf::from(name)
+}
+
+/// `<output>.chdtmp.state`, the journal next to [`scratch_path`].
+pub(crate) fn state_path(output: &Path) -> PathBuf {
+    let mut name = scratch_path(output).into_os_string();
+    name.push(".state");
+    PathBuf::from(name)
+}
+
+/// Settings that shape the compressed hunks without showing up in the
+/// CHD header, hashed into [`CheckpointHeader::prefix_sha1`] so a resume
+/// never mixes hunks compressed two different ways.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct CheckpointOptions {
+    pub levels: DvdCodecLevels,
+    pub fast: bool,
+    pub single_codec: Option<ChdCodec>,
+}
+
+impl CheckpointOptions {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(13);
+        bytes.extend_from_slice(&self.levels.lzma.to_le_bytes());
+        bytes.extend_from_slice(&self.levels.zstd.to_le_bytes());
+        bytes.push(self.fast as u8);
+        bytes.extend_from_slice(self.single_codec.map_or("none", ChdCodec::name).as_bytes());
+        bytes
+    }
+}
+
+/// SHA-1 of a write's header/metadata prefix followed by its `options`.
+fn prefix_digest(prefix: &[u8], options: CheckpointOptions) -> [u8; SHA1_BYTES] {
+    let mut hasher = Sha1::new();
+    hasher.update(prefix);
+    hasher.update(options.to_bytes());
+    hasher.finalize().into()
+}
+
+/// What a partial write was built from. A resume only proceeds when
+/// the current run produces an identical header.
+#[binrw]
+#[brw(little, magic = b"RCCHDRES")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CheckpointHeader {
+    pub version: u32,
+    pub source_len: u64,
+    pub source_mtime_secs: u64,
+    pub source_mtime_nanos: u32,
+    /// Bytes of CHD header and metadata ahead of the first hunk.
+    pub prefix_len: u64,
+    /// SHA-1 of that prefix as first written, before `finalize`
+    /// fills in the offsets and hashes, followed by the run's
+    /// [`CheckpointOptions`]. Pins the hunk size, codecs, logical size,
+    /// codec levels, `fast`, and the single codec.
+    pub prefix_sha1: [u8; SHA1_BYTES],
+}
+
+impl CheckpointHeader {
+    pub fn new(source: &Path, prefix: &[u8], options: CheckpointOptions) -> std::io::Result<Self> {
+        let meta = std::fs::metadata(source)?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Ok(Self {
+            version: JOURNAL_VERSION,
+            source_len: meta.len(),
+            source_mtime_secs: mtime.as_secs(),
+            source_mtime_nanos: mtime.subsec_nanos(),
+            prefix_len: prefix.len() as u64,
+            prefix_sha1: prefix_digest(prefix, options),
+        })
+    }
+
+    /// Why `self`, read back from a journal, does not fit the current
+    /// run described by `expected`.
+    fn mismatch(&self, expected: &Self) -> Option<&'static str> {
+        if self.version != expected.version {
+            Some("the checkpoint was written by a different version")
+        } else if self.source_len != expected.source_len
+            || self.source_mtime_secs != expected.source_mtime_secs
+            || self.source_mtime_nanos != expected.source_mtime_nanos
+        {
+            Some("the source file changed since the checkpoint")
+        } else if self.prefix_len != expected.prefix_len || self.prefix_sha1 != expected.prefix_sha1
+        {
+            Some("the checkpoint was written with different CHD options")
+        } else {
+            None
+        }
+    }
+}
+
+/// One journaled hunk: its map entry minus the offset, which follows
+/// from the lengths before it, plus a CRC-32 over the compressed bytes.
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HunkRecord {
+    compression: u8,
+    length: u32,
+    crc16: u16,
+    data_crc32: u32,
+}
+
+/// A verified partial write: the map entries for the hunks already in
+/// the scratch file and the file position after the last of them.
+#[derive(Debug)]
+pub(crate) struct ResumePoint {
+    pub map_entries: Vec<MapEntry>,
+    pub writer_pos: u64,
+}
+
+/// Check the partial CHD at `scratch` against the journal at `state`,
+/// written with `options`. `Err` carries the reason nothing can be
+/// resumed; a journaled hunk that is missing or fails its CRC ends the
+/// resume point there rather than failing it.
+pub(crate) fn load_resume_point(
+    scratch: &Path,
+    state: &Path,
+    expected: &CheckpointHeader,
+    options: CheckpointOptions,
+) -> Result<ResumePoint, String> {
+    let journal = std::fs::read(state).map_err(|e| format!("cannot read the checkpoint: {e}"))?;
+    let mut cursor = Cursor::new(&journal);
+    let header = CheckpointHeader::read(&mut cursor)
+        .map_err(|_| "the checkpoint file is not a CHD resume journal".to_string())?;
+    if let Some(reason) = header.mismatch(expected) {
+        return Err(reason.to_string());
+    }
+
+    let mut file = File::open(scratch).map_err(|e| format!("cannot open the partial CHD: {e}"))?;
+    let mut prefix = vec![0u8; header.prefix_len as usize];
+    file.read_exact(&mut prefix)
+        .map_err(|_| "the partial CHD is shorter than its header".to_string())?;
+    if prefix_digest(&prefix, options) != header.prefix_sha1 {
+        return Err("the partial CHD header does not match the checkpoint".to_string());
+    }
+
+    let records = &journal[cursor.position() as usize..];
+    let mut reader = BufReader::new(file);
+    let mut map_entries = Vec::with_capacity(records.len() / RECORD_BYTES);
+    let mut writer_pos = header.prefix_len;
+    let mut hunk = Vec::new();
+    for raw in records.chunks_exact(RECORD_BYTES) {
+        let Ok(record) = HunkRecord::read(&mut Cursor::new(raw)) else {
+            break;
+        };
+        hunk.resize(record.length as usize, 0);
+        if reader.read_exact(&mut hunk).is_err() || DATA_CRC.checksum(&hunk) != record.data_crc32 {
+            break;
+        }
+        map_entries.push(MapEntry {
+            compression: record.compression,
+            length: record.length,
+            offset: writer_pos,
+            crc16: record.crc16,
+        });
+        writer_pos += record.length as u64;
+    }
+    Ok(ResumePoint {
+        map_entries,
+        writer_pos,
+    })
+}
+
+/// Writer-side journal. Records queue in memory until [`Self::sync`]
+/// has flushed the output they describe.
+pub(crate) struct Checkpoint {
+    journal: File,
+    pending: Vec<u8>,
+    last_sync: Instant,
+}
+
+impl Checkpoint {
+    /// Start a fresh journal at `path`, replacing any old one.
+    pub fn create(path: &Path, header: &CheckpointHeader) -> ChdResult<Self> {
+        let mut journal = File::create(path)?;
+        let mut bytes = Cursor::new(Vec::new());
+        header.write(&mut bytes)?;
+        journal.write_all(bytes.get_ref())?;
+        journal.sync_data()?;
+        Ok(Self::with_journal(journal))
+    }
+
+    /// Reopen the journal at `path` after a resume, dropping records
+    /// past the `hunks` that were verified.
+    pub fn reopen(path: &Path, hunks: usize) -> ChdResult<Self> {
+        let mut journal = OpenOptions::new().read(true).write(true).open(path)?;
+        let header_len = {
+            let mut reader = BufReader::new(&mut journal);
+            CheckpointHeader::read(&mut reader)?;
+            reader.stream_position()?
+        };
+        let len = header_len + (hunks * RECORD_BYTES) as u64;
+        journal.set_len(len)?;
+        journal.seek(SeekFrom::Start(len))?;
+        Ok(Self::with_journal(journal))
+    }
+
+    fn with_journal(journal: File) -> Self {
+        Self {
+            journal,
+            pending: Vec::new(),
+            last_sync: Instant::now(),
+        }
+    }
+
+    /// Queue the record for a hunk whose `compressed` bytes were just
+    /// handed to the output writer.
+    pub fn record(&mut self, entry: &MapEntry, compressed: &[u8]) -> ChdResult<()> {
+        let record = HunkRecord {
+            compression: entry.compression,
+            length: entry.length,
+            crc16: entry.crc16,
+            data_crc32: DATA_CRC.checksum(compressed),
+        };
+        let mut bytes = Cursor::new(Vec::with_capacity(RECORD_BYTES));
+        record.write(&mut bytes)?;
+        self.pending.extend_from_slice(bytes.get_ref());
+        Ok(())
+    }
+
+    /// [`Self::sync`] once [`CHECKPOINT_INTERVAL`] has passed.
+    pub fn maybe_sync(&mut self, output: &mut BufWriter<File>) -> ChdResult<()> {
+        if self.last_sync.elapsed() >= CHECKPOINT_INTERVAL {
+            self.sync(output)?;
+        }
+        Ok(())
+    }
+
+    /// Make the output durable, then append the queued records, so the
+    /// journal never names bytes the output does not hold.
+    pub fn sync(&mut self, output: &mut BufWriter<File>) -> ChdResult<()> {
+        output.flush()?;
+        output.get_ref().sync_data()?;
+        self.journal.write_all(&self.pending)?;
+        self.journal.sync_data()?;
+        self.pending.clear();
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chd::compression::dvd::DvdCodecLevels;
+    use crate::chd::test_fixtures::mixed_iso;
+    use crate::chd::writer::{ChdBufferPlan, ChdWriter};
+    use crate::util::CancelToken;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicU64;
+
+    const HUNK: u32 = 4096;
+
+    /// Run a resumable DVD write of `iso_path` into `out`. Without
+    /// `finish` the write stops after the hunks, as a killed run would.
+    fn resumable_write(iso_path: &Path, out: &Path, finish: bool) -> u64 {
+        resumable_write_with(iso_path, out, finish, CheckpointOptions::default())
+    }
+
+    fn resumable_write_with(
+        iso_path: &Path,
+        out: &Path,
+        finish: bool,
+        options: CheckpointOptions,
+    ) -> u64 {
+        let iso_bytes = std::fs::metadata(iso_path).unwrap().len();
+        let mut writer = ChdWriter::create_dvd_resumable(
+            &scratch_path(out),
+            &state_path(out),
+            iso_path,
+            iso_bytes,
+            HUNK,
+            false,
+            None,
+            options,
+            ChdBufferPlan::default(),
+        )
+        .unwrap()
+        .with_single_codec(options.single_codec)
+        .unwrap();
+        let first_hunk = writer.first_hunk;
+        let mut reader = BufReader::new(File::open(iso_path).unwrap());
+        writer
+            .compress_all_hunks_dvd(
+                &mut reader,
+                options.levels,
+                &Arc::new(AtomicU64::new(0)),
+                &CancelToken::new(),
+            )
+            .unwrap();
+        if finish {
+            writer.finalize().unwrap();
+        }
+        first_hunk
+    }
+
+    /// Keep the journal header and the first `hunks` records.
+    fn truncate_journal(out: &Path, hunks: usize) {
+        let state = state_path(out);
+        let len = std::fs::metadata(&state).unwrap().len();
+        let total = (len as usize - header_len()) / RECORD_BYTES;
+        assert!(hunks <= total);
+        File::options()
+            .write(true)
+            .open(&state)
+            .unwrap()
+            .set_len((header_len() + hunks * RECORD_BYTES) as u64)
+            .unwrap();
+    }
+
+    fn header_len() -> usize {
+        let header = CheckpointHeader {
+            version: JOURNAL_VERSION,
+            source_len: 0,
+            source_mtime_secs: 0,
+            source_mtime_nanos: 0,
+            prefix_len: 0,
+            prefix_sha1: [0; SHA1_BYTES],
+        };
+        let mut bytes = Cursor::new(Vec::new());
+        header.write(&mut bytes).unwrap();
+        bytes.into_inner().len()
+    }
+
+    fn fresh_chd(iso: &[u8], dir: &Path) -> Vec<u8> {
+        let iso_path = dir.join("fresh.iso");
+        std::fs::write(&iso_path, iso).unwrap();
+        let out = dir.join("fresh.chd");
+        let mut writer = ChdWriter::create_dvd(
+            &out,
+            iso.len() as u64,
+            HUNK,
+            false,
+            None,
+            ChdBufferPlan::default(),
+        )
+        .unwrap();
+        writer
+            .compress_all_hunks_dvd(
+                &mut BufReader::new(File::open(&iso_path).unwrap()),
+                DvdCodecLevels::default(),
+                &Arc::new(AtomicU64::new(0)),
+                &CancelToken::new(),
+            )
+            .unwrap();
+        writer.finalize().unwrap();
+        std::fs::read(out).unwrap()
+    }
+
+    #[test]
+    fn resumed_write_matches_an_uninterrupted_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso = mixed_iso(17);
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, &iso).unwrap();
+        let out = dir.path().join("game.chd");
+
+        assert_eq!(resumable_write(&iso_path, &out, false), 0);
+        // Lose the tail of the journal; the scratch file keeps the
+        // unjournaled hunks, which the resume must drop.
+        truncate_journal(&out, 3);
+        assert_eq!(resumable_write(&iso_path, &out, true), 3);
+
+        assert_eq!(
+            std::fs::read(scratch_path(&out)).unwrap(),
+            fresh_chd(&iso, dir.path())
+        );
+    }
+
+    #[test]
+    fn a_changed_source_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, mixed_iso(9)).unwrap();
+        let out = dir.path().join("game.chd");
+        resumable_write(&iso_path, &out, false);
+
+        std::fs::write(&iso_path, mixed_iso(12)).unwrap();
+        assert_eq!(resumable_write(&iso_path, &out, false), 0);
+    }
+
+    #[test]
+    fn changed_codec_options_start_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, mixed_iso(9)).unwrap();
+        let out = dir.path().join("game.chd");
+        resumable_write(&iso_path, &out, false);
+        truncate_journal(&out, 3);
+
+        let changed = [
+            CheckpointOptions {
+                levels: DvdCodecLevels::new(Some(1), None).unwrap(),
+                ..CheckpointOptions::default()
+            },
+            CheckpointOptions {
+                fast: true,
+                ..CheckpointOptions::default()
+            },
+            CheckpointOptions {
+                single_codec: Some(ChdCodec::Zlib),
+                ..CheckpointOptions::default()
+            },
+        ];
+        for options in changed {
+            assert_eq!(resumable_write_with(&iso_path, &out, false, options), 0);
+            // Each fresh start journals under the new options; put the
+            // defaults back for the next one.
+            resumable_write(&iso_path, &out, false);
+            truncate_journal(&out, 3);
+        }
+        assert_eq!(resumable_write(&iso_path, &out, false), 3);
+    }
+
+    #[test]
+    fn a_corrupt_hunk_ends_the_resume_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso_path = dir.path().join("game.iso");
+        std::fs::write(&iso_path, mixed_iso(12)).unwrap();
+        let out = dir.path().join("game.chd");
+        resumable_write(&iso_path, &out, false);
+
+        let journal = std::fs::read(state_path(&out)).unwrap();
+        let header = CheckpointHeader::read(&mut Cursor::new(&journal)).unwrap();
+        let mut scratch = std::fs::read(scratch_path(&out)).unwrap();
+        // Flip a byte inside the third hunk.
+        let records = &journal[header_len()..];
+        let lengths: Vec<u64> = records
+            .chunks_exact(RECORD_BYTES)
+            .map(|raw| HunkRecord::read(&mut Cursor::new(raw)).unwrap().length as u64)
+            .collect();
+        let third = header.prefix_len + lengths[0] + lengths[1];
+        scratch[third as usize] ^= 0xFF;
+        std::fs::write(scratch_path(&out), &scratch).unwrap();
+
+        let point = load_resume_point(
+            &scratch_path(&out),
+            &state_path(&out),
+            &header,
+            CheckpointOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(point.map_entries.len(), 2);
+        assert_eq!(point.writer_pos, third);
+        assert_eq!(point.map_entries[1].offset, header.prefix_len + lengths[0]);
+    }
+}
@@ -1,5 +1,9 @@
-use crate::chd::error::ChdResult;
-use crate::chd::models::{CHD_METADATA_FLAG_HASHED, ChdMetadataHeader, SHA1_BYTES};
+use crate::chd::error::{ChdError, ChdResult};
+use crate::chd::models::{
+    CHD_METADATA_FLAG_HASHED, CHD_METADATA_RESERVED_BYTES, CHD_V5_HEADER_SIZE, ChdMetadataHeader,
+    SHA1_BYTES,
+};
+use crate::cue::error::CueError;
 use crate::cue::models::CueSheet;
 use binrw::BinWrite;
 use sha1::{Digest, Sha1};
@@ -9,6 +13,10 @@ const TRACK_INFO_SEPARATOR: char = ' ';
 // chdman leaves PGTYPE at its MODE1 default unless the pregap data is
 // stored in-file, which this writer never does.
 const PREGAP_TYPE: &str = "MODE1";
+/// Offset of the next-entry pointer inside a metadata entry: after the
+/// tag, the flags byte, and the 24-bit length.
+const NEXT_OFFSET_POS: usize = 8;
+const MAX_METADATA_LEN: usize = 0xFF_FFFF;
 
 #[derive(Debug, Clone)]
 pub struct MetadataHash {
@@ -20,6 +28,49 @@ pub struct MetadataHash {
 pub struct MetadataBlock {
     pub bytes: Vec<u8>,
     pub hashes: Vec<MetadataHash>,
+    /// Start of the last entry in `bytes`, whose next-entry pointer a
+    /// chained entry patches.
+    last_entry: usize,
+}
+
+impl MetadataBlock {
+    /// Chain a `CMNT` entry carrying `comment` and the tool version
+    /// behind the existing entries; `None` leaves the block as is. The
+    /// block always lands straight after the header, which fixes the
+    /// absolute offset the previous entry points at.
+    pub fn with_comment(self, comment: Option<&str>) -> ChdResult<Self> {
+        let Some(comment) = comment else {
+            return Ok(self);
+        };
+        let text = format!("rom-converto ({}): {comment}", env!("CARGO_PKG_VERSION"));
+        self.chain(ChdMetadataHeader::new_comment_metadata(text))
+    }
+
+    /// Chain a `CREM` entry carrying the cue's disc-level `REM` lines; none
+    /// leaves the block as is, so a cue without them still matches chdman.
+    pub fn with_cue_rems(self, rems: &[(String, String)]) -> ChdResult<Self> {
+        if rems.is_empty() {
+            return Ok(self);
+        }
+        self.chain(ChdMetadataHeader::new_cue_rem_metadata(rems))
+    }
+
+    /// Append `entry` and point the previous last entry at it.
+    fn chain(mut self, entry: ChdMetadataHeader) -> ChdResult<Self> {
+        if entry.data.len() > MAX_METADATA_LEN {
+            return Err(ChdError::MetadataTooLong(entry.data.len()));
+        }
+        let mut entry_bytes = Vec::new();
+        entry.write(&mut Cursor::new(&mut entry_bytes))?;
+
+        let next = CHD_V5_HEADER_SIZE as u64 + self.bytes.len() as u64;
+        let pointer = self.last_entry + NEXT_OFFSET_POS;
+        self.bytes[pointer..pointer + CHD_METADATA_RESERVED_BYTES]
+            .copy_from_slice(&next.to_be_bytes());
+        self.last_entry = self.bytes.len();
+        self.bytes.extend_from_slice(&entry_bytes);
+        Ok(self)
+    }
 }
 
 /// Serialized `DVD ` marker block: chdman's whole DVD metadata is the
@@ -36,35 +87,79 @@ pub fn generate_dvd_metadata() -> ChdResult<MetadataBlock> {
             tag: metadata.tag,
             sha1,
         }],
+        last_entry: 0,
     })
 }
 
-pub fn generate_cd_metadata(cue_sheet: &CueSheet, total_frames: u32) -> ChdResult<MetadataBlock> {
+/// Start frame of every track. A track without `INDEX 01` starts at its
+/// `INDEX 00`; one with neither is an error, since defaulting it to frame
+/// zero would skew the frame counts of every track around it.
+fn track_start_frames(cue_sheet: &CueSheet) -> ChdResult<Vec<u32>> {
+    cue_sheet
+        .tracks
+        .iter()
+        .map(|track| {
+            let start = track
+                .start_lba()
+                .ok_or(CueError::MissingTrackIndex(track.number))?;
+            if track.primary_index_lba().is_none() {
+                log::warn!(
+                    "Track {} has no INDEX 01; starting it at INDEX 00",
+                    track.number
+                );
+            }
+            Ok(start)
+        })
+        .collect()
+}
+
+/// `(start frame, frame count)` of every track, the spans the CD
+/// metadata records. Each track runs to the next one's start and the
+/// last one to `total_frames`.
+pub(crate) fn track_frame_spans(
+    cue_sheet: &CueSheet,
+    total_frames: u32,
+) -> ChdResult<Vec<(u32, u32)>> {
+    let track_starts = track_start_frames(cue_sheet)?;
+    Ok(track_starts
+        .iter()
+        .enumerate()
+        .map(|(idx, &start_frame)| {
+            let end_frame = track_starts.get(idx + 1).copied().unwrap_or(total_frames);
+            (start_frame, end_frame.saturating_sub(start_frame))
+        })
+        .collect())
+}
+
+/// Build the CHT2 track metadata. `subcode` marks every track as
+/// carrying raw interleaved subchannel (`SUBTYPE:RW_RAW`), for bins
+/// dumped with 2448-byte sectors.
+pub fn generate_cd_metadata(
+    cue_sheet: &CueSheet,
+    total_frames: u32,
+    subcode: bool,
+) -> ChdResult<MetadataBlock> {
     let mut metadata_buffer = Vec::new();
 
     // CDs use a single metadata entry that lists every track.
     let mut track_info = String::new();
-    let track_starts: Vec<u32> = cue_sheet
-        .tracks
-        .iter()
-        .map(|track| track.primary_index_lba().unwrap_or(0))
-        .collect();
+    let spans = track_frame_spans(cue_sheet, total_frames)?;
+    let subtype = if subcode { "RW_RAW" } else { "NONE" };
 
     for (idx, track) in cue_sheet.tracks.iter().enumerate() {
         if idx > 0 {
             track_info.push(TRACK_INFO_SEPARATOR);
         }
 
-        let start_frame = track_starts[idx];
-        let end_frame = track_starts.get(idx + 1).copied().unwrap_or(total_frames);
-        let frames = end_frame.saturating_sub(start_frame);
+        let (_, frames) = spans[idx];
         let pregap = track.pregap.map(|p| p.to_lba()).unwrap_or(0);
 
-        // Format: TRACK:n TYPE:type SUBTYPE:NONE FRAMES:nnn PREGAP:n PGTYPE:type PGSUB:NONE POSTGAP:0
+        // Format: TRACK:n TYPE:type SUBTYPE:sub FRAMES:nnn PREGAP:n PGTYPE:type PGSUB:NONE POSTGAP:0
         track_info.push_str(&format!(
-            "TRACK:{} TYPE:{} SUBTYPE:NONE FRAMES:{} PREGAP:{} PGTYPE:{} PGSUB:NONE POSTGAP:0",
+            "TRACK:{} TYPE:{} SUBTYPE:{} FRAMES:{} PREGAP:{} PGTYPE:{} PGSUB:NONE POSTGAP:0",
             track.number,
             track.track_type.chd_metadata_type(),
+            subtype,
             frames,
             pregap,
             PREGAP_TYPE
@@ -87,5 +182,89 @@ pub fn generate_cd_metadata(cue_sheet: &CueSheet, total_frames: u32) -> ChdResul
     Ok(MetadataBlock {
         bytes: metadata_buffer,
         hashes,
+        last_entry: 0,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chd::error::ChdError;
+    use crate::cue::CueParser;
+
+    async fn parse(text: &str) -> CueSheet {
+        let dir = tempfile::tempdir().unwrap();
+        let cue = dir.path().join("disc.cue");
+        tokio::fs::write(&cue, text).await.unwrap();
+        CueParser::new(&cue).parse().await.unwrap()
+    }
+
+    fn track_info(block: &MetadataBlock) -> String {
+        String::from_utf8_lossy(&block.bytes).into_owned()
+    }
+
+    #[tokio::test]
+    async fn track_without_index_01_starts_at_index_00() {
+        let sheet = parse(concat!(
+            "FILE \"disc.bin\" BINARY\n",
+            "  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+            "  TRACK 02 AUDIO\n    INDEX 00 00:02:00\n",
+            "  TRACK 03 AUDIO\n    INDEX 01 00:03:00\n",
+        ))
+        .await;
+        let info = track_info(&generate_cd_metadata(&sheet, 300, false).unwrap());
+        assert!(info.contains("TRACK:1 TYPE:MODE1_RAW SUBTYPE:NONE FRAMES:150 "));
+        assert!(info.contains("TRACK:2 TYPE:AUDIO SUBTYPE:NONE FRAMES:75 "));
+        assert!(info.contains("TRACK:3 TYPE:AUDIO SUBTYPE:NONE FRAMES:75 "));
+    }
+
+    #[tokio::test]
+    async fn subcode_marks_every_track_rw_raw() {
+        let sheet = parse(concat!(
+            "FILE \"disc.bin\" BINARY\n",
+            "  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+            "  TRACK 02 AUDIO\n    INDEX 01 00:02:00\n",
+        ))
+        .await;
+        let info = track_info(&generate_cd_metadata(&sheet, 300, true).unwrap());
+        assert!(info.contains("TRACK:1 TYPE:MODE1_RAW SUBTYPE:RW_RAW FRAMES:150 "));
+        assert!(info.contains("TRACK:2 TYPE:AUDIO SUBTYPE:RW_RAW FRAMES:150 "));
+        assert!(!info.contains("SUBTYPE:NONE"));
+    }
+
+    #[tokio::test]
+    async fn track_without_index_00_or_01_names_the_track() {
+        let sheet = parse(concat!(
+            "FILE \"disc.bin\" BINARY\n",
+            "  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n",
+            "  TRACK 02 AUDIO\n    INDEX 02 00:02:00\n",
+        ))
+        .await;
+        let err = generate_cd_metadata(&sheet, 300, false).unwrap_err();
+        assert!(matches!(
+            err,
+            ChdError::CueError(CueError::MissingTrackIndex(2))
+        ));
+    }
+
+    #[tokio::test]
+    async fn comment_entry_chains_after_track_metadata() {
+        let sheet =
+            parse("FILE \"disc.bin\" BINARY\n  TRACK 01 MODE1/2352\n    INDEX 01 00:00:00\n").await;
+        let plain = generate_cd_metadata(&sheet, 300, false).unwrap();
+        let first_len = plain.bytes.len();
+        let block = generate_cd_metadata(&sheet, 300, false)
+            .unwrap()
+            .with_comment(Some("note"))
+            .unwrap();
+
+        let next = u64::from_be_bytes(block.bytes[8..16].try_into().unwrap());
+        assert_eq!(next, CHD_V5_HEADER_SIZE as u64 + first_len as u64);
+        let comment = &block.bytes[first_len..];
+        assert_eq!(&comment[..4], b"CMNT");
+        assert_eq!(comment[4], 0, "comment must stay out of the SHA-1");
+        assert_eq!(&comment[8..16], &[0; 8]);
+        assert!(comment[16..].ends_with(b": note\0"));
+        assert_eq!(block.hashes.len(), plain.hashes.len());
+    }
+}
@@ -1,38 +1,135 @@
 //! CHD hunk writer: compresses hunks on a worker pool and assembles the
 //! header, compressed map, and metadata blocks into the final file.
 
+pub(crate) mod checkpoint;
 pub(crate) mod metadata;
 pub(crate) mod worker;
 
 use crate::cd::{FRAME_SIZE, IO_BUFFER_SIZE};
-use crate::chd::compression::dvd::dvd_compressors;
-use crate::chd::compression::tag_to_bytes;
+use crate::chd::compression::dvd::{DvdCodecLevels, dvd_compressors};
+use crate::chd::compression::{ChdCodec, CodecTimes, tag_to_bytes};
 use crate::chd::compute_overall_sha1;
 use crate::chd::error::{ChdError, ChdResult};
-use crate::chd::map::{MapEntry, compress_v5_map};
+use crate::chd::map::{MapEntry, compress_v5_map, encode_uncompressed_v5_map};
 use crate::chd::models::{
     CHD_V5_HEADER_SIZE, ChdHeaderV5, ChdVersion, DVD_SECTOR_SIZE, SHA1_BYTES,
 };
+use crate::chd::writer::checkpoint::{
+    Checkpoint, CheckpointHeader, CheckpointOptions, load_resume_point,
+};
 use crate::chd::writer::metadata::{
     MetadataBlock, MetadataHash, generate_cd_metadata, generate_dvd_metadata,
 };
 use crate::chd::writer::worker::{
-    compress_hunks, compress_hunks_dvd, make_chd_compress_workers, make_chd_dvd_compress_workers,
+    Resume, compress_hunks, compress_hunks_dvd, make_chd_compress_workers,
+    make_chd_dvd_compress_workers,
 };
 use crate::cue::models::CueSheet;
-use crate::util::CancelToken;
 use crate::util::worker_pool::{Pool, parallelism};
+use crate::util::{BYTES_PER_MB, CancelToken};
 use binrw::BinWrite;
 use sha1::{Digest, Sha1};
 use std::io::{BufReader, BufWriter, Cursor, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
 
 /// Largest accepted DVD hunk: chdman never goes near this; the cap
 /// only guards against absurd `--hunk-size` values.
 const MAX_DVD_HUNK_BYTES: u32 = 1024 * 1024;
 
+/// Smallest reader and writer buffer a memory cap shrinks them to.
+const MIN_IO_BUFFER: usize = 64 * 1024;
+
+/// Buffer sizing for one CHD write: the source reader and output
+/// writer capacities, the worker count, and how many hunks may be in
+/// flight between them. The default is the uncapped pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChdBufferPlan {
+    pub io_buffer: usize,
+    pub workers: usize,
+    pub max_in_flight: usize,
+}
+
+impl Default for ChdBufferPlan {
+    fn default() -> Self {
+        Self {
+            io_buffer: IO_BUFFER_SIZE,
+            workers: parallelism(),
+            max_in_flight: parallelism() * 2,
+        }
+    }
+}
+
+impl ChdBufferPlan {
+    /// Fit the pipeline under `max_memory` bytes, or keep the default
+    /// when there is no cap. A quarter of the budget, at most 8 MiB
+    /// each, goes to the reader and writer buffers; the rest bounds
+    /// the hunks in flight. One in-flight slot holds a raw hunk and its
+    /// compressed output, and the writer queue holds up to two more
+    /// outputs per slot, so a slot costs four hunks. At least one hunk
+    /// stays in flight, so a budget below that floor is best effort.
+    /// Codec state is not counted, but workers are capped to the
+    /// in-flight depth, so a tight budget also runs fewer codec sets.
+    pub fn new(max_memory: Option<u64>, hunk_bytes: usize) -> Self {
+        let default = Self::default();
+        let Some(budget) = max_memory else {
+            return default;
+        };
+        let budget = usize::try_from(budget).unwrap_or(usize::MAX);
+        let io_buffer = (budget / 8).clamp(MIN_IO_BUFFER, IO_BUFFER_SIZE);
+        let hunk_budget = budget.saturating_sub(2 * io_buffer);
+        let max_in_flight = (hunk_budget / (4 * hunk_bytes)).clamp(1, default.max_in_flight);
+        Self {
+            io_buffer,
+            workers: default.workers.min(max_in_flight),
+            max_in_flight,
+        }
+    }
+
+    /// Upper bound on what the buffers and queued hunks hold.
+    pub fn estimated_bytes(&self, hunk_bytes: usize) -> usize {
+        2 * self.io_buffer + 4 * self.max_in_flight * hunk_bytes
+    }
+}
+
+/// Where the time of one CHD write went. Reads and the map run on one
+/// thread, so they are wall time; codec trials run on every worker, so
+/// each codec's entry is summed across threads and can exceed the
+/// total elapsed time.
+#[derive(Debug, Clone, Default)]
+pub struct ChdTimings {
+    /// Reading source sectors on the dispatcher thread.
+    pub read: Duration,
+    /// Codec trial time per header compressor slot.
+    pub codecs: CodecTimes,
+    /// Building and compressing the V5 map in [`ChdWriter::finalize`].
+    pub map: Duration,
+    /// Header compressor tags, naming the `codecs` slots.
+    pub compressors: [[u8; 4]; 4],
+}
+
+impl ChdTimings {
+    /// One line per stage, codecs named by their fourcc; unused
+    /// compressor slots are skipped.
+    pub fn breakdown(&self) -> Vec<String> {
+        let mut lines = vec![format!("read sectors: {:.3}s", self.read.as_secs_f64())];
+        for (tag, time) in self.compressors.iter().zip(self.codecs) {
+            if *tag == [0; 4] {
+                continue;
+            }
+            lines.push(format!(
+                "{}: {:.3}s (summed across workers)",
+                String::from_utf8_lossy(tag),
+                time.as_secs_f64()
+            ));
+        }
+        lines.push(format!("compress map: {:.3}s", self.map.as_secs_f64()));
+        lines
+    }
+}
+
 /// Sync CHD writer. One instance is created per output file; it
 /// owns the `BufWriter<File>`, the running raw SHA-1, and the map
 /// entries accumulated across every hunk. The heavy compress work
@@ -45,21 +142,38 @@ pub struct ChdWriter {
     map_entries: Vec<MapEntry>,
     raw_sha1: Sha1,
     metadata_hashes: Vec<MetadataHash>,
+    timings: ChdTimings,
+    buffers: ChdBufferPlan,
+    checkpoint: Option<Checkpoint>,
+    /// Hunks already in the output when a checkpointed write resumed.
+    first_hunk: u64,
+    /// Write the map uncompressed, see [`Self::with_uncompressed_map`].
+    uncompressed_map: bool,
+    /// Header slot of the codec set by [`Self::with_single_codec`].
+    single_codec: Option<u8>,
 }
 
 impl ChdWriter {
     /// `total_sectors` sizes the logical data (it includes track
     /// padding frames); `data_sectors` is the real frame count the
-    /// CHT2 `FRAMES:` metadata records, matching chdman.
+    /// CHT2 `FRAMES:` metadata records, matching chdman. The cue's
+    /// disc-level REMs go into a `CREM` entry and `comment` adds a `CMNT`
+    /// one (see [`MetadataBlock::with_comment`]).
+    /// `sector_size` is the source's bytes per sector; 2448-byte
+    /// sectors carry subchannel, recorded as `SUBTYPE:RW_RAW`.
+    #[allow(clippy::too_many_arguments)]
     pub fn create(
         output_path: impl AsRef<Path>,
         total_sectors: u32,
         data_sectors: u32,
         hunk_size: u32,
+        sector_size: usize,
         cue_sheet: &CueSheet,
+        comment: Option<&str>,
+        buffers: ChdBufferPlan,
     ) -> ChdResult<Self> {
         let file = std::fs::File::create(output_path)?;
-        let writer = BufWriter::with_capacity(IO_BUFFER_SIZE, file);
+        let writer = BufWriter::with_capacity(buffers.io_buffer, file);
 
         let logical_bytes = total_sectors as u64 * FRAME_SIZE as u64;
         let unit_bytes = FRAME_SIZE as u32;
@@ -87,8 +201,10 @@ impl ChdWriter {
             parent_sha1: [0; SHA1_BYTES],
         };
 
-        let metadata = generate_cd_metadata(cue_sheet, data_sectors)?;
-        Self::init(writer, header, metadata)
+        let metadata = generate_cd_metadata(cue_sheet, data_sectors, sector_size == FRAME_SIZE)?
+            .with_cue_rems(&cue_sheet.rems)?
+            .with_comment(comment)?;
+        Self::init(writer, header, metadata, buffers)
     }
 
     /// DVD-mode writer: flat 2048-byte sectors, `logical_bytes` =
@@ -100,79 +216,192 @@ impl ChdWriter {
         iso_bytes: u64,
         hunk_size: u32,
         allow_zstd: bool,
+        comment: Option<&str>,
+        buffers: ChdBufferPlan,
     ) -> ChdResult<Self> {
-        if iso_bytes == 0 || !iso_bytes.is_multiple_of(DVD_SECTOR_SIZE as u64) {
-            return Err(ChdError::IsoNotSectorAligned { size: iso_bytes });
-        }
-        if !(DVD_SECTOR_SIZE..=MAX_DVD_HUNK_BYTES).contains(&hunk_size)
-            || !hunk_size.is_multiple_of(DVD_SECTOR_SIZE)
-        {
-            return Err(ChdError::InvalidHunkSize);
-        }
-
+        let header = dvd_header(iso_bytes, hunk_size, allow_zstd)?;
+        let metadata = generate_dvd_metadata()?.with_comment(comment)?;
         let file = std::fs::File::create(output_path)?;
-        let writer = BufWriter::with_capacity(IO_BUFFER_SIZE, file);
+        let writer = BufWriter::with_capacity(buffers.io_buffer, file);
+        Self::init(writer, header, metadata, buffers)
+    }
 
-        let compressors = dvd_compressors(allow_zstd);
-        let header = ChdHeaderV5 {
-            length: CHD_V5_HEADER_SIZE,
-            version: ChdVersion::V5,
-            compressor_0: compressors[0],
-            compressor_1: compressors[1],
-            compressor_2: compressors[2],
-            compressor_3: compressors[3],
-            logical_bytes: iso_bytes,
-            map_offset: 0,
-            meta_offset: 0,
-            hunk_bytes: hunk_size,
-            unit_bytes: DVD_SECTOR_SIZE,
-            raw_sha1: [0; SHA1_BYTES],
-            sha1: [0; SHA1_BYTES],
-            parent_sha1: [0; SHA1_BYTES],
-        };
+    /// [`Self::create_dvd`] with a resume journal at `state_path`
+    /// (see [`checkpoint`]). A partial write already at `output_path`
+    /// whose journal matches `iso_path`, these options, and the
+    /// `checkpoint_options` the hunks will be compressed with is picked
+    /// up after its last verified hunk; anything else starts over.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn create_dvd_resumable(
+        output_path: &Path,
+        state_path: &Path,
+        iso_path: &Path,
+        iso_bytes: u64,
+        hunk_size: u32,
+        allow_zstd: bool,
+        comment: Option<&str>,
+        checkpoint_options: CheckpointOptions,
+        buffers: ChdBufferPlan,
+    ) -> ChdResult<Self> {
+        let header = dvd_header(iso_bytes, hunk_size, allow_zstd)?;
+        let metadata = generate_dvd_metadata()?.with_comment(comment)?;
+        let mut prefix = header_bytes(&header)?;
+        prefix.extend_from_slice(&metadata.bytes);
+        let expected = CheckpointHeader::new(iso_path, &prefix, checkpoint_options)?;
+
+        if output_path.exists() {
+            match load_resume_point(output_path, state_path, &expected, checkpoint_options) {
+                Ok(point) => {
+                    let total_hunks = iso_bytes.div_ceil(hunk_size as u64);
+                    log::info!(
+                        "Resuming {}: {} of {} hunks already compressed",
+                        output_path.display(),
+                        point.map_entries.len(),
+                        total_hunks
+                    );
+                    let mut file = std::fs::OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .open(output_path)?;
+                    file.set_len(point.writer_pos)?;
+                    file.seek(SeekFrom::Start(point.writer_pos))?;
+                    let mut writer = Self::with_header(
+                        BufWriter::with_capacity(buffers.io_buffer, file),
+                        point.writer_pos,
+                        header,
+                        metadata.hashes,
+                        buffers,
+                    );
+                    writer.first_hunk = point.map_entries.len() as u64;
+                    writer.checkpoint =
+                        Some(Checkpoint::reopen(state_path, point.map_entries.len())?);
+                    writer.map_entries = point.map_entries;
+                    return Ok(writer);
+                }
+                Err(reason) => log::warn!(
+                    "Cannot resume {}: {reason}; starting over",
+                    output_path.display()
+                ),
+            }
+        }
 
-        let metadata = generate_dvd_metadata()?;
-        Self::init(writer, header, metadata)
+        let file = std::fs::File::create(output_path)?;
+        let writer = BufWriter::with_capacity(buffers.io_buffer, file);
+        let mut writer = Self::init(writer, header, metadata, buffers)?;
+        writer.checkpoint = Some(Checkpoint::create(state_path, &expected)?);
+        Ok(writer)
     }
 
     fn init(
         mut writer: BufWriter<std::fs::File>,
         header: ChdHeaderV5,
         metadata: MetadataBlock,
+        buffers: ChdBufferPlan,
     ) -> ChdResult<Self> {
-        let mut header_buf = Cursor::new(Vec::new());
-        header.write(&mut header_buf)?;
-        let header_bytes = header_buf.into_inner();
+        log::debug!(
+            "CHD buffers: {buffers:?}, up to {:.1} MB",
+            buffers.estimated_bytes(header.hunk_bytes as usize) as f64 / BYTES_PER_MB
+        );
+        let header_bytes = header_bytes(&header)?;
         writer.write_all(&header_bytes)?;
         let mut writer_pos = header_bytes.len() as u64;
 
         writer.write_all(metadata.bytes.as_slice())?;
         writer_pos += metadata.bytes.len() as u64;
 
-        Ok(Self {
+        Ok(Self::with_header(
+            writer,
+            writer_pos,
+            header,
+            metadata.hashes,
+            buffers,
+        ))
+    }
+
+    fn with_header(
+        writer: BufWriter<std::fs::File>,
+        writer_pos: u64,
+        header: ChdHeaderV5,
+        metadata_hashes: Vec<MetadataHash>,
+        buffers: ChdBufferPlan,
+    ) -> Self {
+        let timings = ChdTimings {
+            compressors: [
+                header.compressor_0,
+                header.compressor_1,
+                header.compressor_2,
+                header.compressor_3,
+            ],
+            ..ChdTimings::default()
+        };
+        Self {
             writer,
             writer_pos,
             header,
             map_entries: Vec::new(),
             raw_sha1: Sha1::new(),
-            metadata_hashes: metadata.hashes,
-        })
+            metadata_hashes,
+            timings,
+            buffers,
+            checkpoint: None,
+            first_hunk: 0,
+            uncompressed_map: false,
+            single_codec: None,
+        }
+    }
+
+    /// Compress every hunk with `codec` alone, or store it raw when
+    /// that does not shrink it, instead of keeping the smallest of all
+    /// the header's codecs. The output then depends on one codec
+    /// library rather than on which of several wins each hunk. Fails
+    /// when `codec` is not in this CHD's compressor list.
+    pub fn with_single_codec(mut self, codec: Option<ChdCodec>) -> ChdResult<Self> {
+        let Some(codec) = codec else {
+            self.single_codec = None;
+            return Ok(self);
+        };
+        let compressors = [
+            self.header.compressor_0,
+            self.header.compressor_1,
+            self.header.compressor_2,
+            self.header.compressor_3,
+        ];
+        let slot = compressors
+            .iter()
+            .position(|tag| *tag == tag_to_bytes(codec.name()))
+            .ok_or(ChdError::SingleCodecUnavailable {
+                codec: codec.name(),
+            })?;
+        self.single_codec = Some(slot as u8);
+        Ok(self)
+    }
+
+    /// Store the hunk map as raw 12-byte entries instead of Huffman
+    /// coding it (see [`encode_uncompressed_v5_map`]), a developer aid
+    /// for telling map coder bugs from hunk codec bugs. Only this
+    /// crate's reader understands the result; chdman and libchdr do not.
+    pub fn with_uncompressed_map(mut self, uncompressed_map: bool) -> Self {
+        self.uncompressed_map = uncompressed_map;
+        self
     }
 
     /// `total_sectors` includes track padding frames; `data_sectors`
     /// of `sector_data_size` bytes each are read from the source.
+    /// `fast` narrows the per-hunk codec trials, see `CdCodecSet::new`.
+    #[allow(clippy::too_many_arguments)]
     pub fn compress_all_hunks(
         &mut self,
         bin_reader: &mut BufReader<std::fs::File>,
         total_sectors: u32,
         data_sectors: u32,
         sector_data_size: usize,
+        fast: bool,
         bytes_done: &Arc<AtomicU64>,
         cancel: &CancelToken,
     ) -> ChdResult<()> {
         let hunk_bytes = self.header.hunk_bytes as usize;
-        let n_threads = parallelism();
-        let workers = make_chd_compress_workers(n_threads, hunk_bytes)?;
+        let workers =
+            make_chd_compress_workers(self.buffers.workers, hunk_bytes, fast, self.single_codec)?;
         let pool: Pool<worker::ChdCompressWork, worker::ChdCompressedOut, ChdError> =
             Pool::spawn(workers);
 
@@ -183,10 +412,16 @@ impl ChdWriter {
             &mut self.writer_pos,
             &mut self.map_entries,
             &mut self.raw_sha1,
+            Resume {
+                first_hunk: self.first_hunk,
+                checkpoint: self.checkpoint.as_mut(),
+            },
             total_sectors,
             data_sectors,
             sector_data_size,
             hunk_bytes,
+            self.buffers.max_in_flight,
+            &mut self.timings,
             bytes_done,
             cancel,
         );
@@ -198,12 +433,19 @@ impl ChdWriter {
     pub fn compress_all_hunks_dvd(
         &mut self,
         iso_reader: &mut BufReader<std::fs::File>,
+        levels: DvdCodecLevels,
         bytes_done: &Arc<AtomicU64>,
         cancel: &CancelToken,
     ) -> ChdResult<()> {
         let hunk_bytes = self.header.hunk_bytes as usize;
         let allow_zstd = self.header.compressor_2 == tag_to_bytes("zstd");
-        let workers = make_chd_dvd_compress_workers(parallelism(), hunk_bytes, allow_zstd)?;
+        let workers = make_chd_dvd_compress_workers(
+            self.buffers.workers,
+            hunk_bytes,
+            allow_zstd,
+            levels,
+            self.single_codec,
+        )?;
         let pool: Pool<worker::ChdCompressWork, worker::ChdCompressedOut, ChdError> =
             Pool::spawn(workers);
 
@@ -214,8 +456,14 @@ impl ChdWriter {
             &mut self.writer_pos,
             &mut self.map_entries,
             &mut self.raw_sha1,
+            Resume {
+                first_hunk: self.first_hunk,
+                checkpoint: self.checkpoint.as_mut(),
+            },
             self.header.logical_bytes,
             hunk_bytes,
+            self.buffers.max_in_flight,
+            &mut self.timings,
             bytes_done,
             cancel,
         );
@@ -224,15 +472,23 @@ impl ChdWriter {
         result
     }
 
-    pub fn finalize(mut self) -> ChdResult<u64> {
-        // Append the compressed map table right after the last
+    /// Write the map and the final header, returning where the time
+    /// of the whole write went.
+    pub fn finalize(mut self) -> ChdResult<ChdTimings> {
+        // Append the map table right after the last
         // hunk. The map offset goes into the header on the final
         // seek-and-rewrite.
-        let map_data = compress_v5_map(
-            &self.map_entries,
-            self.header.hunk_bytes,
-            self.header.unit_bytes,
-        )?;
+        let started = Instant::now();
+        let map_data = if self.uncompressed_map {
+            encode_uncompressed_v5_map(&self.map_entries)
+        } else {
+            compress_v5_map(
+                &self.map_entries,
+                self.header.hunk_bytes,
+                self.header.unit_bytes,
+            )?
+        };
+        self.timings.map = started.elapsed();
 
         let map_offset = self.writer_pos;
         self.writer.write_all(&map_data)?;
@@ -259,10 +515,48 @@ impl ChdWriter {
         self.writer.write_all(&header_buf)?;
         self.writer.flush()?;
 
-        Ok(self.writer_pos)
+        Ok(self.timings)
     }
 }
 
+/// Serialized V5 header as [`ChdWriter::init`] first writes it.
+fn header_bytes(header: &ChdHeaderV5) -> ChdResult<Vec<u8>> {
+    let mut header_buf = Cursor::new(Vec::new());
+    header.write(&mut header_buf)?;
+    Ok(header_buf.into_inner())
+}
+
+/// DVD-mode header: flat 2048-byte sectors, `logical_bytes` = exact
+/// input size. Offsets and hashes are filled in by `finalize`.
+fn dvd_header(iso_bytes: u64, hunk_size: u32, allow_zstd: bool) -> ChdResult<ChdHeaderV5> {
+    if iso_bytes == 0 || !iso_bytes.is_multiple_of(DVD_SECTOR_SIZE as u64) {
+        return Err(ChdError::IsoNotSectorAligned { size: iso_bytes });
+    }
+    if !(DVD_SECTOR_SIZE..=MAX_DVD_HUNK_BYTES).contains(&hunk_size)
+        || !hunk_size.is_multiple_of(DVD_SECTOR_SIZE)
+    {
+        return Err(ChdError::InvalidHunkSize);
+    }
+
+    let compressors = dvd_compressors(allow_zstd);
+    Ok(ChdHeaderV5 {
+        length: CHD_V5_HEADER_SIZE,
+        version: ChdVersion::V5,
+        compressor_0: compressors[0],
+        compressor_1: compressors[1],
+        compressor_2: compressors[2],
+        compressor_3: compressors[3],
+        logical_bytes: iso_bytes,
+        map_offset: 0,
+        meta_offset: 0,
+        hunk_bytes: hunk_size,
+        unit_bytes: DVD_SECTOR_SIZE,
+        raw_sha1: [0; SHA1_BYTES],
+        sha1: [0; SHA1_BYTES],
+        parent_sha1: [0; SHA1_BYTES],
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -279,6 +573,10 @@ mod tests {
     use crate::chd::test_fixtures::mixed_iso;
 
     fn write_dvd_chd(iso: &[u8], hunk_size: u32, allow_zstd: bool) -> Vec<u8> {
+        write_dvd_chd_timed(iso, hunk_size, allow_zstd).0
+    }
+
+    fn write_dvd_chd_timed(iso: &[u8], hunk_size: u32, allow_zstd: bool) -> (Vec<u8>, ChdTimings) {
         let dir = tempfile::tempdir().unwrap();
         let iso_path = dir.path().join("in.iso");
         let chd_path = dir.path().join("out.chd");
@@ -286,16 +584,28 @@ mod tests {
 
         let iso_file = std::fs::File::open(&iso_path).unwrap();
         let mut reader = BufReader::with_capacity(IO_BUFFER_SIZE, iso_file);
-        let mut writer =
-            ChdWriter::create_dvd(&chd_path, iso.len() as u64, hunk_size, allow_zstd).unwrap();
+        let mut writer = ChdWriter::create_dvd(
+            &chd_path,
+            iso.len() as u64,
+            hunk_size,
+            allow_zstd,
+            None,
+            ChdBufferPlan::default(),
+        )
+        .unwrap();
         let bytes_done = Arc::new(AtomicU64::new(0));
         writer
-            .compress_all_hunks_dvd(&mut reader, &bytes_done, &CancelToken::new())
+            .compress_all_hunks_dvd(
+                &mut reader,
+                DvdCodecLevels::default(),
+                &bytes_done,
+                &CancelToken::new(),
+            )
             .unwrap();
         assert_eq!(bytes_done.load(Ordering::Relaxed), iso.len() as u64);
-        writer.finalize().unwrap();
+        let timings = writer.finalize().unwrap();
 
-        std::fs::read(&chd_path).unwrap()
+        (std::fs::read(&chd_path).unwrap(), timings)
     }
 
     fn decode_hunks(chd: &[u8], header: &ChdHeaderV5) -> Vec<u8> {
@@ -355,6 +665,76 @@ mod tests {
         assert_eq!(decode_hunks(&chd, &header), iso);
     }
 
+    #[test]
+    fn timings_cover_every_listed_codec() {
+        let iso = mixed_iso(16);
+        let (_, timings) = write_dvd_chd_timed(&iso, 4096, true);
+
+        assert_eq!(&timings.compressors[2], b"zstd");
+        for slot in 0..3 {
+            assert!(timings.codecs[slot] > Duration::ZERO, "slot {slot} untimed");
+        }
+        assert_eq!(timings.codecs[3], Duration::ZERO);
+
+        let lines = timings.breakdown();
+        assert_eq!(lines.len(), 5, "{lines:?}");
+        assert!(lines[0].starts_with("read sectors: "));
+        assert!(lines[1].starts_with("lzma: "));
+        assert!(lines[2].starts_with("zlib: "));
+        assert!(lines[3].starts_with("zstd: "));
+        assert!(lines[4].starts_with("compress map: "));
+    }
+
+    #[test]
+    fn buffer_plan_fits_the_memory_cap() {
+        let default = ChdBufferPlan::default();
+        assert_eq!(ChdBufferPlan::new(None, 4096), default);
+
+        for hunk_bytes in [2048, 4096, 19584] {
+            for mb in [4u64, 16, 64, 256] {
+                let budget = mb * 1024 * 1024;
+                let plan = ChdBufferPlan::new(Some(budget), hunk_bytes);
+                assert!(
+                    plan.estimated_bytes(hunk_bytes) as u64 <= budget,
+                    "{plan:?}"
+                );
+                assert!(plan.io_buffer <= default.io_buffer);
+                assert!((1..=default.max_in_flight).contains(&plan.max_in_flight));
+                assert!(plan.workers >= 1 && plan.workers <= plan.max_in_flight);
+            }
+        }
+
+        // A cap below one hunk's worth still leaves one hunk in flight.
+        let tiny = ChdBufferPlan::new(Some(1024), 19584);
+        assert_eq!(tiny.max_in_flight, 1);
+        assert_eq!(tiny.workers, 1);
+        assert_eq!(tiny.io_buffer, MIN_IO_BUFFER);
+    }
+
+    #[test]
+    fn finalize_appends_a_v5_map_covering_every_hunk() {
+        let iso = mixed_iso(9);
+        let chd = write_dvd_chd(&iso, 4096, false);
+        let header = ChdHeaderV5::read(&mut IoCursor::new(&chd)).unwrap();
+
+        let hunk_count = iso.len().div_ceil(4096) as u32;
+        let map = decompress_v5_map(
+            &chd[header.map_offset as usize..],
+            hunk_count,
+            header.hunk_bytes,
+            header.unit_bytes,
+        )
+        .unwrap();
+        assert_eq!(map.len(), hunk_count as usize);
+
+        // Hunks are laid out back to back and the map follows the last one.
+        for pair in map.windows(2) {
+            assert_eq!(pair[0].offset + pair[0].length as u64, pair[1].offset);
+        }
+        let last = map.last().unwrap();
+        assert_eq!(last.offset + last.length as u64, header.map_offset);
+    }
+
     #[test]
     fn dvd_chd_with_zstd_round_trips() {
         let iso = mixed_iso(8);
@@ -388,15 +768,15 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let out = dir.path().join("out.chd");
         assert!(matches!(
-            ChdWriter::create_dvd(&out, 4096 + 1, 4096, false),
+            ChdWriter::create_dvd(&out, 4096 + 1, 4096, false, None, ChdBufferPlan::default()),
             Err(ChdError::IsoNotSectorAligned { .. })
         ));
         assert!(matches!(
-            ChdWriter::create_dvd(&out, 4096, 3000, false),
+            ChdWriter::create_dvd(&out, 4096, 3000, false, None, ChdBufferPlan::default()),
             Err(ChdError::InvalidHunkSize)
         ));
         assert!(matches!(
-            ChdWriter::create_dvd(&out, 4096, 0, false),
+            ChdWriter::create_dvd(&out, 4096, 0, false, None, ChdBufferPlan::default()),
             Err(ChdError::InvalidHunkSize)
         ));
     }
@@ -12,16 +12,19 @@
 //! for the lifetime of the compress call.
 
 use crate::cd::FRAME_SIZE;
-use crate::chd::compression::dvd::DvdCodecSet;
-use crate::chd::compression::{CdCodecSet, ChdCompression};
+use crate::chd::compression::dvd::{DvdCodecLevels, DvdCodecSet};
+use crate::chd::compression::{CdCodecSet, ChdCompression, CodecTimes};
 use crate::chd::error::{ChdError, ChdResult};
 use crate::chd::map::{MapEntry, crc16_ccitt};
+use crate::chd::writer::ChdTimings;
+use crate::chd::writer::checkpoint::Checkpoint;
 use crate::util::CancelToken;
-use crate::util::worker_pool::{Pool, Worker, drive, parallelism};
+use crate::util::worker_pool::{Pool, Worker, drive};
 use sha1::{Digest, Sha1};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 /// One hunk worth of input bytes, already interleaved as
 /// `[sector0 || zero_subcode0 || sector1 || zero_subcode1 || ...]`
@@ -33,11 +36,13 @@ pub(super) struct ChdCompressWork {
 
 /// Compressed output plus the codec slot the best-of trial picked
 /// and a CRC-16 over the raw hunk (matches chdman's
-/// `hunk_write_compressed` input).
+/// `hunk_write_compressed` input). `codec_time` is what each codec
+/// trial on this hunk cost, for the timing breakdown.
 pub(super) struct ChdCompressedOut {
     pub compressed: Vec<u8>,
     pub compression: u8,
     pub crc16: u16,
+    pub codec_time: CodecTimes,
 }
 
 /// Per-thread CHD compress worker. Owns one persistent
@@ -48,9 +53,9 @@ pub(super) struct ChdCompressWorker {
 }
 
 impl ChdCompressWorker {
-    pub fn new(hunk_bytes: usize) -> ChdResult<Self> {
+    pub fn new(hunk_bytes: usize, fast: bool, only: Option<u8>) -> ChdResult<Self> {
         Ok(Self {
-            codecs: CdCodecSet::new(hunk_bytes)?,
+            codecs: CdCodecSet::new(hunk_bytes, fast)?.only(only),
         })
     }
 }
@@ -66,6 +71,7 @@ impl Worker<ChdCompressWork, ChdCompressedOut, ChdError> for ChdCompressWorker {
             compressed,
             compression,
             crc16,
+            codec_time: self.codecs.take_codec_time(),
         })
     }
 }
@@ -73,8 +79,12 @@ impl Worker<ChdCompressWork, ChdCompressedOut, ChdError> for ChdCompressWorker {
 pub(super) fn make_chd_compress_workers(
     n: usize,
     hunk_bytes: usize,
+    fast: bool,
+    only: Option<u8>,
 ) -> ChdResult<Vec<ChdCompressWorker>> {
-    (0..n).map(|_| ChdCompressWorker::new(hunk_bytes)).collect()
+    (0..n)
+        .map(|_| ChdCompressWorker::new(hunk_bytes, fast, only))
+        .collect()
 }
 
 /// DVD twin of [`ChdCompressWorker`]: same work/output shape, raw
@@ -94,6 +104,7 @@ impl Worker<ChdCompressWork, ChdCompressedOut, ChdError> for ChdDvdCompressWorke
             compressed,
             compression,
             crc16,
+            codec_time: self.codecs.take_codec_time(),
         })
     }
 }
@@ -102,11 +113,13 @@ pub(super) fn make_chd_dvd_compress_workers(
     n: usize,
     hunk_bytes: usize,
     allow_zstd: bool,
+    levels: DvdCodecLevels,
+    only: Option<u8>,
 ) -> ChdResult<Vec<ChdDvdCompressWorker>> {
     (0..n)
         .map(|_| {
             Ok(ChdDvdCompressWorker {
-                codecs: DvdCodecSet::new(hunk_bytes, allow_zstd)?,
+                codecs: DvdCodecSet::new(hunk_bytes, allow_zstd, levels)?.only(only),
             })
         })
         .collect()
@@ -133,6 +146,8 @@ pub(super) fn make_chd_dvd_compress_workers(
 /// `writer_pos` is the file position **before** the next
 /// compressed hunk would land. The caller owns it and passes it
 /// through; this function updates it in place.
+///
+/// `resume` picks up a checkpointed write: see [`Resume`].
 #[allow(clippy::too_many_arguments)]
 pub(super) fn compress_hunks(
     pool: &Pool<ChdCompressWork, ChdCompressedOut, ChdError>,
@@ -141,22 +156,29 @@ pub(super) fn compress_hunks(
     writer_pos: &mut u64,
     map_entries: &mut Vec<MapEntry>,
     raw_sha1: &mut Sha1,
+    resume: Resume<'_>,
     total_sectors: u32,
     data_sectors: u32,
     sector_data_size: usize,
     hunk_bytes: usize,
+    max_in_flight: usize,
+    timings: &mut ChdTimings,
     bytes_done: &Arc<AtomicU64>,
     cancel: &CancelToken,
 ) -> ChdResult<()> {
     let frames_per_hunk = hunk_bytes / FRAME_SIZE;
     let total_hunks = total_sectors.div_ceil(frames_per_hunk as u32) as u64;
+    let read_time: &mut Duration = &mut timings.read;
 
     run_pipeline(
         pool,
         writer,
         writer_pos,
         map_entries,
+        resume,
+        &mut timings.codecs,
         total_hunks,
+        max_in_flight,
         // produce: zero padding on the short final hunk comes for
         // free from the `vec![0; hunk_bytes]` allocation.
         |chunk_idx| -> ChdResult<ChdCompressWork> {
@@ -170,7 +192,9 @@ pub(super) fn compress_hunks(
             let read_bytes = read_sectors * sector_data_size;
 
             let mut sector_buf = vec![0u8; read_bytes];
+            let started = Instant::now();
             bin_reader.read_exact(&mut sector_buf)?;
+            *read_time += started.elapsed();
 
             let mut hunk = vec![0u8; hunk_bytes];
             for s in 0..read_sectors {
@@ -201,19 +225,26 @@ pub(super) fn compress_hunks_dvd(
     writer_pos: &mut u64,
     map_entries: &mut Vec<MapEntry>,
     raw_sha1: &mut Sha1,
+    resume: Resume<'_>,
     logical_bytes: u64,
     hunk_bytes: usize,
+    max_in_flight: usize,
+    timings: &mut ChdTimings,
     bytes_done: &Arc<AtomicU64>,
     cancel: &CancelToken,
 ) -> ChdResult<()> {
     let total_hunks = logical_bytes.div_ceil(hunk_bytes as u64);
+    let read_time: &mut Duration = &mut timings.read;
 
     run_pipeline(
         pool,
         writer,
         writer_pos,
         map_entries,
+        resume,
+        &mut timings.codecs,
         total_hunks,
+        max_in_flight,
         |chunk_idx| -> ChdResult<ChdCompressWork> {
             if cancel.is_cancelled() {
                 return Err(ChdError::Cancelled);
@@ -222,7 +253,9 @@ pub(super) fn compress_hunks_dvd(
             let take = ((logical_bytes - offset) as usize).min(hunk_bytes);
 
             let mut hunk = vec![0u8; hunk_bytes];
+            let started = Instant::now();
             iso_reader.read_exact(&mut hunk[..take])?;
+            *read_time += started.elapsed();
             raw_sha1.update(&hunk[..take]);
             bytes_done.fetch_add(take as u64, Ordering::Relaxed);
             Ok(ChdCompressWork { hunk })
@@ -230,51 +263,86 @@ pub(super) fn compress_hunks_dvd(
     )
 }
 
+/// Checkpoint state for one compress call. `first_hunk` hunks are
+/// already in the output (their map entries preloaded); they are read
+/// and hashed again so the raw SHA-1 covers them, but not compressed.
+/// With a `checkpoint`, every written hunk is journaled.
+#[derive(Default)]
+pub(super) struct Resume<'a> {
+    pub first_hunk: u64,
+    pub checkpoint: Option<&'a mut Checkpoint>,
+}
+
 /// Shared compress scaffold: `drive` the pool with the mode-specific
 /// `produce` closure while a dedicated writer thread drains a bounded
 /// channel, so reads, codec trials, and writes overlap. The consume
-/// side is mode-independent: append a map entry, forward bytes,
-/// advance the writer position.
+/// side is mode-independent: append a map entry, add the hunk's codec
+/// times, forward bytes, advance the writer position.
+#[allow(clippy::too_many_arguments)]
 fn run_pipeline<F>(
     pool: &Pool<ChdCompressWork, ChdCompressedOut, ChdError>,
     writer: &mut BufWriter<std::fs::File>,
     writer_pos: &mut u64,
     map_entries: &mut Vec<MapEntry>,
+    resume: Resume<'_>,
+    codec_time: &mut CodecTimes,
     total_hunks: u64,
-    produce: F,
+    max_in_flight: usize,
+    mut produce: F,
 ) -> ChdResult<()>
 where
     F: FnMut(u64) -> ChdResult<ChdCompressWork>,
 {
-    let max_in_flight = parallelism() * 2;
+    let Resume {
+        first_hunk,
+        mut checkpoint,
+    } = resume;
+    for chunk_idx in 0..first_hunk {
+        produce(chunk_idx)?;
+    }
+
     let mut local_writer_pos = *writer_pos;
-    let (write_tx, write_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(max_in_flight * 2);
+    let (write_tx, write_rx) =
+        std::sync::mpsc::sync_channel::<(MapEntry, Vec<u8>)>(max_in_flight * 2);
 
     let scope_result: ChdResult<()> = std::thread::scope(|s| {
         let writer_slot: &mut BufWriter<std::fs::File> = writer;
         let writer_handle = s.spawn(move || -> ChdResult<()> {
-            while let Ok(bytes) = write_rx.recv() {
+            while let Ok((entry, bytes)) = write_rx.recv() {
                 writer_slot.write_all(&bytes)?;
+                if let Some(checkpoint) = checkpoint.as_deref_mut() {
+                    checkpoint.record(&entry, &bytes)?;
+                    checkpoint.maybe_sync(writer_slot)?;
+                }
+            }
+            // The channel also closes on cancel or a failed hunk;
+            // journal everything written so far either way.
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.sync(writer_slot)?;
             }
             Ok(())
         });
 
         let drive_result = drive(
             pool,
-            total_hunks,
+            total_hunks - first_hunk,
             max_in_flight,
-            produce,
+            |seq| produce(seq + first_hunk),
             |_seq, out: ChdCompressedOut| -> ChdResult<()> {
                 let offset = local_writer_pos;
                 let length = out.compressed.len() as u32;
-                map_entries.push(MapEntry {
+                let entry = MapEntry {
                     compression: out.compression,
                     length,
                     offset,
                     crc16: out.crc16,
-                });
+                };
+                map_entries.push(entry);
+                for (total, spent) in codec_time.iter_mut().zip(out.codec_time) {
+                    *total += spent;
+                }
                 write_tx
-                    .send(out.compressed)
+                    .send((entry, out.compressed))
                     .map_err(|_| ChdError::WorkerPoolClosed)?;
                 local_writer_pos += length as u64;
                 Ok(())
@@ -35,6 +35,22 @@ pub enum CueError {
     /// A quoted string field is missing its closing quote.
     #[error("missing closing quote")]
     MissingClosingQuote,
+
+    /// A track has neither `INDEX 01` nor `INDEX 00`, so its start cannot
+    /// be placed.
+    #[error("track {0} has no INDEX 01 or INDEX 00")]
+    MissingTrackIndex(u8),
+
+    /// The sheet has no `TRACK` line, so there is nothing to place on the
+    /// disc. Blank and `FILE`-only sheets both end up here.
+    #[error("cue sheet has no TRACK entries")]
+    NoTracks,
+
+    /// A `TRACK` line is not followed by any `INDEX` before the next
+    /// `TRACK`, `FILE`, or the end of the sheet. `line` is the 1-based line
+    /// of the `TRACK` entry.
+    #[error("track {track} on line {line} has no INDEX entries")]
+    TrackWithoutIndex { track: u8, line: usize },
 }
 
 pub type CueResult<T> = Result<T, CueError>;
@@ -1,8 +1,8 @@
 use crate::cd::IO_BUFFER_SIZE;
 use crate::cue::CueParser;
 use crate::cue::error::CueError;
-use crate::cue::models::{CueSheet, FileType, Msf};
-use crate::util::{BYTES_PER_MB, CancelToken, ProgressReporter, scratch_output_path};
+use crate::cue::models::{CueSheet, FileType, Msf, rem_lines};
+use crate::util::{BYTES_PER_MB, CancelToken, ProgressReporter, ScratchPath, scratch_output_path};
 use log::{debug, info};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
@@ -52,6 +52,11 @@ pub enum MergeError {
     #[error("operation cancelled")]
     Cancelled,
 
+    /// The output bin's file name is not valid UTF-8, so the merged `.cue`
+    /// cannot name it.
+    #[error("output bin name is not valid UTF-8, a .cue cannot reference it: {0}")]
+    NonUtf8BinName(String),
+
     /// A `FILE` entry names a type other than `BINARY`, which cannot be concatenated as raw bytes.
     #[error("CUE sheet references a non-BINARY file, only raw .bin tracks can be merged: {0}")]
     NonBinaryFile(String),
@@ -79,7 +84,7 @@ pub(crate) struct MergeFilePlan {
 /// Builds the single-file cue sheet for the merged bin. Index positions in a
 /// multi-file cue are relative to their own file, so each one is rebased by
 /// the sector count of all preceding files. Unlike binmerge, PREGAP and
-/// POSTGAP lines are preserved.
+/// POSTGAP lines are preserved, and so are the disc-level REM lines.
 pub(crate) fn build_merged_cue(
     out_bin_filename: &str,
     sheet: &CueSheet,
@@ -92,7 +97,8 @@ pub(crate) fn build_merged_cue(
         total_sectors += plan.sectors;
     }
 
-    let mut cue = format!("FILE \"{out_bin_filename}\" BINARY\r\n");
+    let mut cue = rem_lines(&sheet.rems);
+    cue.push_str(&format!("FILE \"{out_bin_filename}\" BINARY\r\n"));
     for track in &sheet.tracks {
         cue.push_str(&format!(
             "  TRACK {:02} {}\r\n",
@@ -143,9 +149,9 @@ fn restore_output(path: &Path, backup: Option<tempfile::TempPath>) -> std::io::R
 }
 
 fn publish_pair(
-    bin_temp: tempfile::TempPath,
+    bin_temp: ScratchPath,
     bin_path: &Path,
-    cue_temp: tempfile::TempPath,
+    cue_temp: ScratchPath,
     cue_path: &Path,
     force: bool,
 ) -> std::io::Result<()> {
@@ -307,10 +313,13 @@ pub async fn merge_bin_cancellable(
         ),
     );
 
-    let out_bin_filename = output_bin_path
-        .file_name()
-        .map(|name| name.to_string_lossy().into_owned())
-        .unwrap_or_else(|| "merged.bin".to_string());
+    let out_bin_filename = match output_bin_path.file_name() {
+        Some(name) => name
+            .to_str()
+            .ok_or_else(|| MergeError::NonUtf8BinName(output_bin_path.display().to_string()))?
+            .to_string(),
+        None => "merged.bin".to_string(),
+    };
     let cue_text = build_merged_cue(&out_bin_filename, &cue_sheet, &plans);
     if cancel.is_cancelled() {
         return Err(MergeError::Cancelled);
@@ -420,6 +429,7 @@ mod tests {
                 })
                 .collect(),
             tracks,
+            rems: Vec::new(),
         }
     }
 
@@ -29,15 +29,26 @@ impl CueParser {
         let mut cue_sheet = CueSheet {
             files: Vec::new(),
             tracks: Vec::new(),
+            rems: Vec::new(),
         };
 
-        let mut current_track: Option<Track> = None;
+        // The open track and the 1-based line its TRACK entry was on.
+        let mut current_track: Option<(Track, usize)> = None;
 
-        for line in reader.lines() {
+        for (line_index, line) in reader.lines().enumerate() {
             let line = line?;
             let line = line.trim();
 
-            if line.is_empty() || line.starts_with("REM") {
+            if let Some(rem) = line.strip_prefix("REM") {
+                if cue_sheet.files.is_empty()
+                    && current_track.is_none()
+                    && let Some(rem) = parse_rem(rem)
+                {
+                    cue_sheet.rems.push(rem);
+                }
+                continue;
+            }
+            if line.is_empty() {
                 continue;
             }
 
@@ -48,8 +59,8 @@ impl CueParser {
 
             match parts[0] {
                 "FILE" => {
-                    if let Some(track) = current_track.take() {
-                        cue_sheet.tracks.push(track);
+                    if let Some((track, track_line)) = current_track.take() {
+                        push_track(&mut cue_sheet, track, track_line)?;
                     }
 
                     let filename = self.extract_quoted_string(line)?;
@@ -69,27 +80,30 @@ impl CueParser {
                     if parts.len() < 3 {
                         return Err(CueError::InvalidTrackType(line.to_string()));
                     }
-                    if let Some(track) = current_track.take() {
-                        cue_sheet.tracks.push(track);
+                    if let Some((track, track_line)) = current_track.take() {
+                        push_track(&mut cue_sheet, track, track_line)?;
                     }
 
                     let number = parts[1].parse::<u8>()?;
                     let track_type = self.parse_track_type(parts[2])?;
 
-                    current_track = Some(Track {
-                        number,
-                        track_type,
-                        indices: Vec::new(),
-                        pregap: None,
-                        postgap: None,
-                        file_index: cue_sheet.files.len().saturating_sub(1),
-                    });
+                    current_track = Some((
+                        Track {
+                            number,
+                            track_type,
+                            indices: Vec::new(),
+                            pregap: None,
+                            postgap: None,
+                            file_index: cue_sheet.files.len().saturating_sub(1),
+                        },
+                        line_index + 1,
+                    ));
                 }
                 "INDEX" => {
                     if parts.len() < 3 {
                         return Err(CueError::InvalidMsfFormat(line.to_string()));
                     }
-                    if let Some(track) = &mut current_track {
+                    if let Some((track, _)) = &mut current_track {
                         let number = parts[1].parse::<u8>()?;
                         let position = self.parse_msf(parts[2])?;
 
@@ -100,7 +114,7 @@ impl CueParser {
                     if parts.len() < 2 {
                         return Err(CueError::InvalidMsfFormat(line.to_string()));
                     }
-                    if let Some(track) = &mut current_track {
+                    if let Some((track, _)) = &mut current_track {
                         track.pregap = Some(self.parse_msf(parts[1])?);
                     }
                 }
@@ -108,7 +122,7 @@ impl CueParser {
                     if parts.len() < 2 {
                         return Err(CueError::InvalidMsfFormat(line.to_string()));
                     }
-                    if let Some(track) = &mut current_track {
+                    if let Some((track, _)) = &mut current_track {
                         track.postgap = Some(self.parse_msf(parts[1])?);
                     }
                 }
@@ -116,8 +130,11 @@ impl CueParser {
             }
         }
 
-        if let Some(track) = current_track {
-            cue_sheet.tracks.push(track);
+        if let Some((track, track_line)) = current_track {
+            push_track(&mut cue_sheet, track, track_line)?;
+        }
+        if cue_sheet.tracks.is_empty() {
+            return Err(CueError::NoTracks);
         }
 
         Ok(cue_sheet)
@@ -150,6 +167,8 @@ impl CueParser {
             "CDG" => Ok(TrackType::CdG),
             "MODE1/2048" => Ok(TrackType::Mode1_2048),
             "MODE1/2352" => Ok(TrackType::Mode1_2352),
+            "MODE2/2048" => Ok(TrackType::Mode2_2048),
+            "MODE2/2324" => Ok(TrackType::Mode2_2324),
             "MODE2/2336" => Ok(TrackType::Mode2_2336),
             "MODE2/2352" => Ok(TrackType::Mode2_2352),
             "CDI/2336" => Ok(TrackType::CdI2336),
@@ -172,6 +191,34 @@ impl CueParser {
     }
 }
 
+/// Splits what follows `REM` into its key and the rest of the line.
+/// `REMARK` and a bare `REM` carry no key and are skipped.
+fn parse_rem(rest: &str) -> Option<(String, String)> {
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if key.is_empty() {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
+/// Closes a track once its entries end. A track with no `INDEX` has no
+/// position on the disc, so it is rejected here rather than surfacing later
+/// as a zero-length track.
+fn push_track(cue_sheet: &mut CueSheet, track: Track, line: usize) -> CueResult<()> {
+    if track.indices.is_empty() {
+        return Err(CueError::TrackWithoutIndex {
+            track: track.number,
+            line,
+        });
+    }
+    cue_sheet.tracks.push(track);
+    Ok(())
+}
+
 /// Sums the on-disk size of the FILE entries a CUE sheet references, resolved
 /// relative to the CUE's own directory. Used to estimate output size for space
 /// preflight checks (raw sectors are larger than the ISO/output they produce,
@@ -281,6 +328,22 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parse_track_type_mode2_2048() {
+        let track_type = parser().parse_track_type("MODE2/2048").unwrap();
+        assert!(matches!(track_type, TrackType::Mode2_2048));
+        assert_eq!(track_type.block_size(), 2048);
+        assert_eq!(track_type.chd_metadata_type(), "MODE2_FORM1");
+    }
+
+    #[test]
+    fn parse_track_type_mode2_2324() {
+        let track_type = parser().parse_track_type("MODE2/2324").unwrap();
+        assert!(matches!(track_type, TrackType::Mode2_2324));
+        assert_eq!(track_type.block_size(), 2324);
+        assert_eq!(track_type.chd_metadata_type(), "MODE2_FORM2");
+    }
+
     #[test]
     fn parse_track_type_unknown_fails() {
         assert!(parser().parse_track_type("MODE3/2048").is_err());
@@ -326,4 +389,87 @@ mod tests {
         .unwrap();
         assert!(CueParser::new(&cue).parse().await.is_err());
     }
+
+    #[tokio::test]
+    async fn parse_file_only_cue_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let cue = dir.path().join("bad.cue");
+        tokio::fs::write(&cue, "FILE \"a.bin\" BINARY\r\n")
+            .await
+            .unwrap();
+        let err = CueParser::new(&cue).parse().await.unwrap_err();
+        assert!(matches!(err, CueError::NoTracks));
+
+        tokio::fs::write(&cue, "REM blank\r\n\r\n").await.unwrap();
+        let err = CueParser::new(&cue).parse().await.unwrap_err();
+        assert!(matches!(err, CueError::NoTracks));
+    }
+
+    #[tokio::test]
+    async fn parse_keeps_disc_level_rems_verbatim() {
+        let dir = tempfile::tempdir().unwrap();
+        let cue = dir.path().join("game.cue");
+        tokio::fs::write(
+            &cue,
+            concat!(
+                "REM GENRE \"Action / Adventure\"\r\n",
+                "REM DATE 1997\r\n",
+                "REM DISCID 8A0C3B0B\r\n",
+                "REM COMMENT \"ExactAudioCopy v1.6\"\r\n",
+                "REM REPLAYGAIN_ALBUM_GAIN -7.20 dB\r\n",
+                "REM\r\n",
+                "REMARK not a rem\r\n",
+                "FILE \"a.bin\" BINARY\r\n",
+                "REM SESSION 01\r\n",
+                "  TRACK 01 MODE1/2352\r\n",
+                "    INDEX 01 00:00:00\r\n",
+            ),
+        )
+        .await
+        .unwrap();
+        let sheet = CueParser::new(&cue).parse().await.unwrap();
+        let rems: Vec<(&str, &str)> = sheet
+            .rems
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            rems,
+            [
+                ("GENRE", "\"Action / Adventure\""),
+                ("DATE", "1997"),
+                ("DISCID", "8A0C3B0B"),
+                ("COMMENT", "\"ExactAudioCopy v1.6\""),
+                ("REPLAYGAIN_ALBUM_GAIN", "-7.20 dB"),
+            ]
+        );
+        assert_eq!(
+            crate::cue::models::rem_lines(&sheet.rems[1..3]),
+            "REM DATE 1997\r\nREM DISCID 8A0C3B0B\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn parse_track_without_index_names_its_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let cue = dir.path().join("bad.cue");
+        tokio::fs::write(
+            &cue,
+            concat!(
+                "FILE \"a.bin\" BINARY\r\n",
+                "  TRACK 01 MODE1/2352\r\n",
+                "    INDEX 01 00:00:00\r\n",
+                "  TRACK 02 AUDIO\r\n",
+                "  TRACK 03 AUDIO\r\n",
+                "    INDEX 01 00:04:00\r\n",
+            ),
+        )
+        .await
+        .unwrap();
+        let err = CueParser::new(&cue).parse().await.unwrap_err();
+        assert!(matches!(
+            err,
+            CueError::TrackWithoutIndex { track: 2, line: 4 }
+        ));
+    }
 }
@@ -1,11 +1,31 @@
 use crate::cd::{FRAMES_PER_SECOND, SECONDS_PER_MINUTE};
 
+const PREGAP_INDEX: u8 = 0;
 const PRIMARY_INDEX: u8 = 1;
 
 #[derive(Debug, Clone)]
 pub struct CueSheet {
     pub files: Vec<CueFile>,
     pub tracks: Vec<Track>,
+    /// Disc-level `REM <key> <value>` comments, the ones before the first
+    /// `FILE`, in file order. The value is kept verbatim, quotes included.
+    /// Redump and EAC cues carry `GENRE`, `DATE`, `DISCID`, and `COMMENT`
+    /// here.
+    pub rems: Vec<(String, String)>,
+}
+
+/// [`CueSheet::rems`] as cue lines, CRLF-terminated like the rest of the
+/// cues this crate writes.
+pub fn rem_lines(rems: &[(String, String)]) -> String {
+    rems.iter()
+        .map(|(key, value)| {
+            if value.is_empty() {
+                format!("REM {key}\r\n")
+            } else {
+                format!("REM {key} {value}\r\n")
+            }
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +96,18 @@ impl Track {
             .find(|index| index.number == PRIMARY_INDEX)
             .map(|index| index.position.to_lba())
     }
+
+    /// Where the track begins in its file: `INDEX 01`, or `INDEX 00` for
+    /// sheets that never mark a separate track start. `None` when the track
+    /// has neither.
+    pub fn start_lba(&self) -> Option<u32> {
+        self.primary_index_lba().or_else(|| {
+            self.indices
+                .iter()
+                .find(|index| index.number == PREGAP_INDEX)
+                .map(|index| index.position.to_lba())
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -84,6 +116,8 @@ pub enum TrackType {
     CdG,
     Mode1_2048,
     Mode1_2352,
+    Mode2_2048,
+    Mode2_2324,
     Mode2_2336,
     Mode2_2352,
     CdI2336,
@@ -98,7 +132,8 @@ impl TrackType {
             | TrackType::Mode2_2352
             | TrackType::CdI2352 => 2352,
             TrackType::CdG => 2448,
-            TrackType::Mode1_2048 => 2048,
+            TrackType::Mode1_2048 | TrackType::Mode2_2048 => 2048,
+            TrackType::Mode2_2324 => 2324,
             TrackType::Mode2_2336 | TrackType::CdI2336 => 2336,
         }
     }
@@ -109,6 +144,8 @@ impl TrackType {
             TrackType::CdG => "CDG",
             TrackType::Mode1_2048 => "MODE1/2048",
             TrackType::Mode1_2352 => "MODE1/2352",
+            TrackType::Mode2_2048 => "MODE2/2048",
+            TrackType::Mode2_2324 => "MODE2/2324",
             TrackType::Mode2_2336 => "MODE2/2336",
             TrackType::Mode2_2352 => "MODE2/2352",
             TrackType::CdI2336 => "CDI/2336",
@@ -123,6 +160,8 @@ impl TrackType {
             TrackType::Mode1_2048 => "MODE1",
             TrackType::Mode2_2352 => "MODE2_RAW",
             TrackType::Mode2_2336 => "MODE2_FORM1",
+            TrackType::Mode2_2048 => "MODE2_FORM1",
+            TrackType::Mode2_2324 => "MODE2_FORM2",
             _ => "MODE1_RAW",
         }
     }
@@ -235,6 +274,45 @@ mod tests {
             file_index: 0,
         };
         assert_eq!(track.primary_index_lba(), None);
+        assert_eq!(track.start_lba(), Some(0));
+    }
+
+    #[test]
+    fn start_lba_prefers_index_01() {
+        let track = Track {
+            number: 2,
+            track_type: TrackType::Audio,
+            indices: vec![
+                Index {
+                    number: 0,
+                    position: Msf::from_lba(100),
+                },
+                Index {
+                    number: 1,
+                    position: Msf::from_lba(250),
+                },
+            ],
+            pregap: None,
+            postgap: None,
+            file_index: 0,
+        };
+        assert_eq!(track.start_lba(), Some(250));
+    }
+
+    #[test]
+    fn start_lba_none_without_index_00_or_01() {
+        let track = Track {
+            number: 3,
+            track_type: TrackType::Audio,
+            indices: vec![Index {
+                number: 2,
+                position: Msf::from_lba(10),
+            }],
+            pregap: None,
+            postgap: None,
+            file_index: 0,
+        };
+        assert_eq!(track.start_lba(), None);
     }
 
     #[test]
@@ -244,6 +322,8 @@ mod tests {
         assert_eq!(TrackType::Mode1_2048.chd_metadata_type(), "MODE1");
         assert_eq!(TrackType::Mode2_2352.chd_metadata_type(), "MODE2_RAW");
         assert_eq!(TrackType::Mode2_2336.chd_metadata_type(), "MODE2_FORM1");
+        assert_eq!(TrackType::Mode2_2048.chd_metadata_type(), "MODE2_FORM1");
+        assert_eq!(TrackType::Mode2_2324.chd_metadata_type(), "MODE2_FORM2");
     }
 
     #[test]
@@ -263,6 +343,8 @@ mod tests {
         assert_eq!(TrackType::CdG.block_size(), 2448);
         assert_eq!(TrackType::Mode1_2048.block_size(), 2048);
         assert_eq!(TrackType::Mode2_2336.block_size(), 2336);
+        assert_eq!(TrackType::Mode2_2048.block_size(), 2048);
+        assert_eq!(TrackType::Mode2_2324.block_size(), 2324);
         assert_eq!(TrackType::CdI2336.block_size(), 2336);
     }
 
@@ -274,6 +356,8 @@ mod tests {
             TrackType::CdG,
             TrackType::Mode1_2048,
             TrackType::Mode1_2352,
+            TrackType::Mode2_2048,
+            TrackType::Mode2_2324,
             TrackType::Mode2_2336,
             TrackType::Mode2_2352,
             TrackType::CdI2336,
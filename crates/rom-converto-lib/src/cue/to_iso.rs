@@ -88,6 +88,7 @@ fn is_supported_data_track(track_type: TrackType) -> bool {
         track_type,
         TrackType::Mode1_2048
             | TrackType::Mode1_2352
+            | TrackType::Mode2_2048
             | TrackType::Mode2_2352
             | TrackType::Mode2_2336
     )
@@ -101,8 +102,8 @@ fn extract_user_data(
     sector_index: u64,
 ) -> ToIsoResult<&[u8]> {
     match track_type {
-        // Already bare user data.
-        TrackType::Mode1_2048 => Ok(&sector[..USER_DATA_SIZE]),
+        // Already bare user data; MODE2/2048 is Form1 payload only.
+        TrackType::Mode1_2048 | TrackType::Mode2_2048 => Ok(&sector[..USER_DATA_SIZE]),
         // 12-byte sync + 4-byte header precede the user data.
         TrackType::Mode1_2352 => Ok(&sector[16..16 + USER_DATA_SIZE]),
         // 16-byte header + 8-byte XA subheader; submode byte at offset 18.
@@ -284,7 +285,7 @@ mod tests {
         let mut sector = vec![0u8; block];
         let submode = if form2 { FORM2_SUBMODE_BIT } else { 0 };
         let payload_start = match track_type {
-            TrackType::Mode1_2048 => 0,
+            TrackType::Mode1_2048 | TrackType::Mode2_2048 => 0,
             TrackType::Mode1_2352 => 16,
             TrackType::Mode2_2352 => {
                 sector[18] = submode;
@@ -361,6 +362,11 @@ mod tests {
         assert_extracts(TrackType::Mode2_2336).await;
     }
 
+    #[tokio::test]
+    async fn extracts_mode2_2048() {
+        assert_extracts(TrackType::Mode2_2048).await;
+    }
+
     #[tokio::test]
     async fn form2_sector_errors_with_index() {
         let dir = tempfile::tempdir().unwrap();
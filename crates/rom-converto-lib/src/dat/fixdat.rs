@@ -1,6 +1,6 @@
 use crate::dat::digest::TrackDigests;
 use crate::dat::model::{DatFileGame, DatFileSummary, PlaymatchGameFile};
-use crate::util::{CancelToken, FileDigests};
+use crate::util::{CancelToken, FileDigests, OutputHashes};
 use std::borrow::Cow;
 use std::collections::HashSet;
 
@@ -128,49 +128,24 @@ pub fn write_fixdat_xml_cancellable<W: std::io::Write>(
 ) -> std::io::Result<()> {
     check_cancel(cancel)?;
     let title = format!("fixdat - {}", dat.name);
-    writeln!(w, "<?xml version=\"1.0\"?>")?;
-    writeln!(
-        w,
-        "<!DOCTYPE datafile PUBLIC \"-//Logiqx//DTD ROM Management Datafile//EN\" \"http://www.logiqx.com/Dats/datafile.dtd\">"
-    )?;
-    writeln!(w, "<datafile>")?;
-    writeln!(w, "\t<header>")?;
-    writeln!(w, "\t\t<name>{}</name>", xml_escape(&title))?;
-    writeln!(w, "\t\t<description>{}</description>", xml_escape(&title))?;
-    writeln!(
-        w,
-        "\t\t<version>{}</version>",
-        xml_escape(&dat.current_version)
-    )?;
-    writeln!(w, "\t</header>")?;
+    write_dat_header(w, &title, &dat.current_version)?;
 
     for entry in entries {
         check_cancel(cancel)?;
-        writeln!(w, "\t<game name=\"{}\">", xml_escape(&entry.game_name))?;
-        writeln!(
-            w,
-            "\t\t<description>{}</description>",
-            xml_escape(&entry.game_name)
-        )?;
+        write_game_open(w, &entry.game_name)?;
         for f in &entry.missing {
             check_cancel(cancel)?;
-            write!(w, "\t\t<rom name=\"{}\"", xml_escape(&f.file_name))?;
-            if let Some(size) = f.file_size_in_bytes {
-                write!(w, " size=\"{size}\"")?;
-            }
-            if let Some(crc) = &f.crc {
-                write!(w, " crc=\"{}\"", xml_escape(crc))?;
-            }
-            if let Some(md5) = &f.md5 {
-                write!(w, " md5=\"{}\"", xml_escape(md5))?;
-            }
-            if let Some(sha1) = &f.sha1 {
-                write!(w, " sha1=\"{}\"", xml_escape(sha1))?;
-            }
-            if let Some(sha256) = &f.sha256 {
-                write!(w, " sha256=\"{}\"", xml_escape(sha256))?;
-            }
-            writeln!(w, "/>")?;
+            write_rom(
+                w,
+                &f.file_name,
+                f.file_size_in_bytes,
+                [
+                    ("crc", f.crc.as_deref()),
+                    ("md5", f.md5.as_deref()),
+                    ("sha1", f.sha1.as_deref()),
+                    ("sha256", f.sha256.as_deref()),
+                ],
+            )?;
         }
         writeln!(w, "\t</game>")?;
     }
@@ -180,6 +155,79 @@ pub fn write_fixdat_xml_cancellable<W: std::io::Write>(
     Ok(())
 }
 
+/// Emit a one-game Logiqx DAT for a single file, so a converted title
+/// can be added to a custom DAT and checked by a ROM manager.
+pub fn write_game_dat_xml<W: std::io::Write>(
+    w: &mut W,
+    game_name: &str,
+    rom_name: &str,
+    size: u64,
+    hashes: &OutputHashes,
+) -> std::io::Result<()> {
+    write_dat_header(w, game_name, env!("CARGO_PKG_VERSION"))?;
+    write_game_open(w, game_name)?;
+    write_rom(
+        w,
+        rom_name,
+        Some(size),
+        [
+            ("crc", Some(hashes.crc32.as_str())),
+            ("md5", Some(hashes.md5.as_str())),
+            ("sha1", Some(hashes.sha1.as_str())),
+            ("sha256", None),
+        ],
+    )?;
+    writeln!(w, "\t</game>")?;
+    writeln!(w, "</datafile>")?;
+    Ok(())
+}
+
+fn write_dat_header<W: std::io::Write>(
+    w: &mut W,
+    title: &str,
+    version: &str,
+) -> std::io::Result<()> {
+    writeln!(w, "<?xml version=\"1.0\"?>")?;
+    writeln!(
+        w,
+        "<!DOCTYPE datafile PUBLIC \"-//Logiqx//DTD ROM Management Datafile//EN\" \"http://www.logiqx.com/Dats/datafile.dtd\">"
+    )?;
+    writeln!(w, "<datafile>")?;
+    writeln!(w, "\t<header>")?;
+    writeln!(w, "\t\t<name>{}</name>", xml_escape(title))?;
+    writeln!(w, "\t\t<description>{}</description>", xml_escape(title))?;
+    writeln!(w, "\t\t<version>{}</version>", xml_escape(version))?;
+    writeln!(w, "\t</header>")
+}
+
+fn write_game_open<W: std::io::Write>(w: &mut W, game_name: &str) -> std::io::Result<()> {
+    writeln!(w, "\t<game name=\"{}\">", xml_escape(game_name))?;
+    writeln!(
+        w,
+        "\t\t<description>{}</description>",
+        xml_escape(game_name)
+    )
+}
+
+/// One `<rom/>` line; absent digests are left out.
+fn write_rom<W: std::io::Write>(
+    w: &mut W,
+    name: &str,
+    size: Option<u64>,
+    digests: [(&str, Option<&str>); 4],
+) -> std::io::Result<()> {
+    write!(w, "\t\t<rom name=\"{}\"", xml_escape(name))?;
+    if let Some(size) = size {
+        write!(w, " size=\"{size}\"")?;
+    }
+    for (attr, value) in digests {
+        if let Some(value) = value {
+            write!(w, " {attr}=\"{}\"", xml_escape(value))?;
+        }
+    }
+    writeln!(w, "/>")
+}
+
 fn check_cancel(cancel: &CancelToken) -> std::io::Result<()> {
     if cancel.is_cancelled() {
         return Err(std::io::Error::new(
@@ -374,4 +422,36 @@ mod tests {
         );
         assert_eq!(out, expected);
     }
+
+    #[test]
+    fn write_game_dat_golden() {
+        let hashes = OutputHashes {
+            crc32: "1234abcd".to_string(),
+            md5: "bbbb".to_string(),
+            sha1: "aaaa".to_string(),
+        };
+        let mut buf = Vec::new();
+        write_game_dat_xml(&mut buf, "Pilot & Co", "Pilot & Co.cia", 4096, &hashes).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+
+        let expected = format!(
+            concat!(
+                "<?xml version=\"1.0\"?>\n",
+                "<!DOCTYPE datafile PUBLIC \"-//Logiqx//DTD ROM Management Datafile//EN\" \"http://www.logiqx.com/Dats/datafile.dtd\">\n",
+                "<datafile>\n",
+                "\t<header>\n",
+                "\t\t<name>Pilot &amp; Co</name>\n",
+                "\t\t<description>Pilot &amp; Co</description>\n",
+                "\t\t<version>{}</version>\n",
+                "\t</header>\n",
+                "\t<game name=\"Pilot &amp; Co\">\n",
+                "\t\t<description>Pilot &amp; Co</description>\n",
+                "\t\t<rom name=\"Pilot &amp; Co.cia\" size=\"4096\" crc=\"1234abcd\" md5=\"bbbb\" sha1=\"aaaa\"/>\n",
+                "\t</game>\n",
+                "</datafile>\n",
+            ),
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(out, expected);
+    }
 }
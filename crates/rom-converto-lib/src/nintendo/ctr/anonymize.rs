@@ -0,0 +1,194 @@
+//! Scrubs the console-unique fields from a 3DS ticket.
+//!
+//! eShop tickets carry the console ID and eShop account ID of the unit
+//! they were bought on, which ties a dump to a person. Zeroing both turns
+//! the ticket into the global form CDN tickets use. The ticket is rewritten
+//! in place through its binrw model; every other byte of a CIA stays
+//! untouched. The ticket signature covers both fields, so a real signature
+//! no longer verifies afterwards.
+
+use crate::nintendo::ctr::format::sniff_format;
+use crate::nintendo::ctr::info::CtrFormat;
+use crate::nintendo::ctr::models::cia::CiaHeader;
+use crate::nintendo::ctr::models::ticket::{Ticket, TicketData};
+use crate::nintendo::ctr::util::align_64;
+use anyhow::{Context, Result, bail};
+use binrw::{BinRead, BinWrite};
+use std::fs::OpenOptions;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Where the anonymized ticket was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TicketContainer {
+    Cia,
+    Ticket,
+}
+
+/// The identifying values a ticket held before anonymizing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnonymizeReport {
+    pub container: TicketContainer,
+    pub console_id: u32,
+    pub eshop_account_id: u32,
+}
+
+impl AnonymizeReport {
+    /// Whether the ticket had anything to scrub. When false the file was
+    /// left as is.
+    pub fn changed(&self) -> bool {
+        self.console_id != 0 || self.eshop_account_id != 0
+    }
+}
+
+/// Zero the console ID and eShop account ID of `ticket`.
+pub fn anonymize_ticket(ticket: &mut TicketData) {
+    ticket.console_id = 0;
+    ticket.eshop_account_id = 0;
+}
+
+/// Anonymize the ticket of the CIA or standalone ticket file at `path` in
+/// place. A CIA is detected by its header; anything else is parsed as a
+/// bare ticket.
+pub fn anonymize(path: &Path) -> Result<AnonymizeReport> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("ctr anonymize: open {}", path.display()))?;
+
+    let mut head = [0u8; 4];
+    let read = file.read(&mut head)?;
+    let (container, ticket_offset, ticket_size) = match sniff_format(&head[..read]) {
+        CtrFormat::Cia => {
+            file.seek(SeekFrom::Start(0))?;
+            let header =
+                CiaHeader::read_le(&mut file).context("ctr anonymize: parse CIA header")?;
+            let cert_start = align_64(header.header_size as u64);
+            let ticket_start = align_64(cert_start + header.cert_chain_size as u64);
+            (
+                TicketContainer::Cia,
+                ticket_start,
+                Some(header.ticket_size as u64),
+            )
+        }
+        _ => (TicketContainer::Ticket, 0, None),
+    };
+
+    file.seek(SeekFrom::Start(ticket_offset))?;
+    let mut ticket = Ticket::read_be(&mut file).context("ctr anonymize: parse ticket")?;
+    let parsed_size = file.stream_position()? - ticket_offset;
+    if let Some(declared) = ticket_size
+        && parsed_size > declared
+    {
+        bail!(
+            "ctr anonymize: ticket parses to 0x{parsed_size:X} bytes but the CIA header declares 0x{declared:X}"
+        );
+    }
+
+    let report = AnonymizeReport {
+        container,
+        console_id: ticket.ticket_data.console_id,
+        eshop_account_id: ticket.ticket_data.eshop_account_id,
+    };
+    if !report.changed() {
+        return Ok(report);
+    }
+
+    anonymize_ticket(&mut ticket.ticket_data);
+    let mut buf = Vec::with_capacity(parsed_size as usize);
+    ticket.write_be(&mut Cursor::new(&mut buf))?;
+    if buf.len() as u64 != parsed_size {
+        bail!(
+            "ctr anonymize: rewritten ticket is 0x{:X} bytes, expected 0x{parsed_size:X}",
+            buf.len()
+        );
+    }
+    file.seek(SeekFrom::Start(ticket_offset))?;
+    file.write_all(&buf)?;
+    file.flush()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::test_fixtures::{make_ticket, minimal_cia, write_cia_file};
+    use binrw::Endian;
+
+    fn read_cia_ticket(path: &Path) -> Ticket {
+        let bytes = std::fs::read(path).unwrap();
+        let cia = crate::nintendo::ctr::models::cia::CiaFileWithoutContent::read_options(
+            &mut Cursor::new(&bytes),
+            Endian::Little,
+            (),
+        )
+        .unwrap();
+        cia.ticket
+    }
+
+    #[test]
+    fn scrubs_a_cia_ticket_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cia");
+        let mut cia = minimal_cia();
+        cia.ticket.ticket_data.console_id = 0x1234_5678;
+        cia.ticket.ticket_data.eshop_account_id = 0x0BAD_F00D;
+        write_cia_file(&path, &cia);
+        let before = std::fs::read(&path).unwrap();
+
+        let report = anonymize(&path).unwrap();
+        assert_eq!(report.container, TicketContainer::Cia);
+        assert_eq!(report.console_id, 0x1234_5678);
+        assert_eq!(report.eshop_account_id, 0x0BAD_F00D);
+        assert!(report.changed());
+
+        let after = std::fs::read(&path).unwrap();
+        assert_eq!(after.len(), before.len());
+        let ticket = read_cia_ticket(&path);
+        assert_eq!(ticket.ticket_data.console_id, 0);
+        assert_eq!(ticket.ticket_data.eshop_account_id, 0);
+        assert_eq!(
+            ticket.ticket_data.title_key,
+            cia.ticket.ticket_data.title_key
+        );
+
+        // Only the two ticket words differ.
+        let differing = before.iter().zip(&after).filter(|(a, b)| a != b).count();
+        assert!(differing <= 8, "{differing} bytes changed");
+    }
+
+    #[test]
+    fn scrubs_a_standalone_ticket_and_keeps_trailing_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("title.tik");
+        let mut ticket = make_ticket(0x0004_0000_0012_3400);
+        ticket.ticket_data.console_id = 0xCAFE_BABE;
+        let mut bytes = Vec::new();
+        ticket.write_be(&mut Cursor::new(&mut bytes)).unwrap();
+        let ticket_len = bytes.len();
+        bytes.extend_from_slice(&[0xEE; 0x40]);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let report = anonymize(&path).unwrap();
+        assert_eq!(report.container, TicketContainer::Ticket);
+        assert_eq!(report.console_id, 0xCAFE_BABE);
+
+        let after = std::fs::read(&path).unwrap();
+        assert_eq!(after[ticket_len..], [0xEE; 0x40]);
+        let parsed = Ticket::read_be(&mut Cursor::new(&after)).unwrap();
+        assert_eq!(parsed.ticket_data.console_id, 0);
+    }
+
+    #[test]
+    fn leaves_an_already_global_ticket_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cia");
+        write_cia_file(&path, &minimal_cia());
+        let before = std::fs::read(&path).unwrap();
+
+        let report = anonymize(&path).unwrap();
+        assert!(!report.changed());
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+    }
+}
@@ -1,30 +1,36 @@
-use crate::nintendo::ctr::constants::{
-    CERT_SIG_TYPE_MAX, CERT_SIG_TYPE_MIN, CIA_CERT_CHAIN_SIZE, CIA_CONTENT_INDEX_SIZE,
-};
+use crate::nintendo::ctr::constants::{CIA_CERT_CHAIN_SIZE, CIA_CONTENT_INDEX_SIZE};
+use crate::nintendo::ctr::decrypt::DecryptOptions;
 use crate::nintendo::ctr::decrypt::cia::parse_and_decrypt_cia;
 use crate::nintendo::ctr::error::NintendoCTRError;
+use crate::nintendo::ctr::fix_tmd::update_tmd_hashes;
 use crate::nintendo::ctr::models::certificate::Certificate;
 use crate::nintendo::ctr::models::cia::{
     CIA_HEADER_SIZE, CiaFile, CiaFileWithoutContent, CiaHeader,
 };
 use crate::nintendo::ctr::models::ticket::Ticket;
-use crate::nintendo::ctr::models::title_metadata::TitleMetadata;
+use crate::nintendo::ctr::models::title_metadata::{ContentChunkRecord, TitleMetadata};
 use crate::nintendo::ctr::util::align_64;
 use crate::util::{CancelToken, ProgressReporter};
+use anyhow::Context;
 use binrw::{BinRead, BinWrite, Endian};
-use byteorder::{BigEndian, ReadBytesExt};
-use sha2::{Digest, Sha256};
+use log::warn;
+use std::collections::VecDeque;
 use std::io::{Cursor, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
 
 /// Buffer size for streaming content files from disk to the CIA output.
 const CONTENT_COPY_BUF: usize = 4 * 1024 * 1024;
 
+/// Chunks each content reader may queue ahead of the writer.
+const CONTENT_READ_AHEAD: usize = 2;
+
 pub async fn decrypt_from_encrypted_cia(
     input: &Path,
     out_writer: &mut BufWriter<File>,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> anyhow::Result<()> {
@@ -78,57 +84,10 @@ pub async fn decrypt_from_encrypted_cia(
 
     let out_file = out_writer.get_mut();
     out_file.seek(SeekFrom::Start(preamble_len)).await?;
-    let content_hashes = parse_and_decrypt_cia(input, out_file, progress, cancel).await?;
+    let content_hashes = parse_and_decrypt_cia(input, out_file, opts, progress, cancel).await?;
     progress.finish();
 
-    if content_hashes.len() != decrypted_cia.tmd.content_chunk_records.len() {
-        anyhow::bail!(
-            "decrypted {} contents but TMD declares {} records",
-            content_hashes.len(),
-            decrypted_cia.tmd.content_chunk_records.len()
-        );
-    }
-    for (record, hash) in decrypted_cia
-        .tmd
-        .content_chunk_records
-        .iter_mut()
-        .zip(content_hashes)
-    {
-        record.hash = hash.to_vec();
-    }
-
-    for content_info_record in &mut decrypted_cia.tmd.content_info_records {
-        let start = content_info_record.content_index_offset as usize;
-        let count = content_info_record.content_command_count as usize;
-        let mut hasher = Sha256::new();
-
-        for chunk in &decrypted_cia.tmd.content_chunk_records[start..start + count] {
-            let mut buf = Cursor::new(Vec::new());
-            chunk.write_be(&mut buf)?;
-            hasher.update(buf.get_ref());
-        }
-
-        content_info_record.hash = hasher.finalize().to_vec();
-    }
-
-    let mut hasher = Sha256::new();
-
-    for content_info_record in &mut decrypted_cia.tmd.content_info_records {
-        let mut cursor = Cursor::new(Vec::new());
-        content_info_record
-            .content_index_offset
-            .write_be(&mut cursor)?;
-        content_info_record
-            .content_command_count
-            .write_be(&mut cursor)?;
-        cursor
-            .get_mut()
-            .extend_from_slice(&content_info_record.hash);
-
-        hasher.update(cursor.get_ref());
-    }
-
-    decrypted_cia.tmd.header.content_info_records_hash = hasher.finalize().to_vec();
+    update_tmd_hashes(&mut decrypted_cia.tmd, &content_hashes)?;
 
     let mut finalized = Cursor::new(Vec::new());
     decrypted_cia.write_le(&mut finalized)?;
@@ -156,16 +115,32 @@ pub async fn decrypt_from_encrypted_cia(
 
 /// Writes out the CIA file by streaming content files from disk directly to
 /// the output, avoiding the previous behavior of loading every `.app` into
-/// memory and then serializing a full in-memory CIA. Peak memory is bounded by
-/// the TMD/ticket preamble (a few KB) plus a 4 MB copy buffer.
+/// memory and then serializing a full in-memory CIA. With a
+/// `content_concurrency` of 1, each file is copied inline through a single
+/// 4 MB buffer; above that, up to `content_concurrency` content files are
+/// read at once on their own tasks and written strictly in TMD order. Peak
+/// memory is bounded by the TMD/ticket preamble (a few KB) plus, per file
+/// being read, a few 4 MB chunks.
+///
+/// With `fix_content_size`, a content file whose length differs from the
+/// TMD is trimmed or zero-padded to the declared size instead of refused.
+/// With `raw_certs`, the certificate chain is the TMD's and ticket's
+/// chains as they are (see [`concat_certificate_chains`]) instead of one
+/// CA, XS, and CP certificate picked by name.
+///
+/// `tmd_certs` and `tik_certs` are the chains appended to the TMD and
+/// ticket files (see [`parse_certificate_chain`]).
 #[allow(clippy::too_many_arguments)]
 pub async fn write_cia(
     path: &Path,
     out: &mut BufWriter<File>,
-    tmd_path: &Path,
-    tik_path: &Path,
     tmd: TitleMetadata,
     tik: Ticket,
+    tmd_certs: Vec<Certificate>,
+    tik_certs: Vec<Certificate>,
+    fix_content_size: bool,
+    raw_certs: bool,
+    content_concurrency: usize,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> anyhow::Result<()> {
@@ -176,9 +151,14 @@ pub async fn write_cia(
         .sum();
     progress.start(total_content_size, "Building CIA");
 
-    let tmd_certs = read_certificate_chain(tmd_path).await?;
-    let tik_certs = read_certificate_chain(tik_path).await?;
-    let cert_chain: Vec<Certificate> = merge_certificate_chains(tmd_certs, tik_certs);
+    let (cert_chain, cert_chain_size) = if raw_certs {
+        concat_certificate_chains(tmd_certs, tik_certs)?
+    } else {
+        (
+            merge_certificate_chains(tmd_certs, tik_certs),
+            CIA_CERT_CHAIN_SIZE,
+        )
+    };
 
     // Ticket and TMD have variable BinWrite sizes. Serialize them to scratch
     // buffers so the CIA header declares lengths that match BinRead.
@@ -197,7 +177,7 @@ pub async fn write_cia(
             header_size: CIA_HEADER_SIZE,
             cia_type: 0,
             version: 0,
-            cert_chain_size: CIA_CERT_CHAIN_SIZE,
+            cert_chain_size,
             ticket_size,
             tmd_size,
             meta_size: 0,
@@ -208,86 +188,230 @@ pub async fn write_cia(
         ticket: tik,
         tmd,
     };
-    for record in &cia_wo.tmd.content_chunk_records {
-        cia_wo
-            .header
-            .set_content_index(record.content_index as usize);
-    }
-
-    let mut preamble = Vec::new();
-    cia_wo.write_options(&mut Cursor::new(&mut preamble), Endian::Little, ())?;
-    out.write_all(&preamble).await?;
-
-    // Stream each content file directly to the output. The size check below
-    // is what stops a truncated/corrupt .app from producing a CIA whose
-    // header content_size disagrees with the bytes actually written.
-    let mut buf = vec![0u8; CONTENT_COPY_BUF];
+    cia_wo
+        .header
+        .rebuild_content_indexes(&cia_wo.tmd.content_chunk_records);
+
+    // Check every content file against its TMD size before writing
+    // anything, so an incomplete CDN download fails up front and names the
+    // content instead of producing a CIA whose header content_size
+    // disagrees with the bytes actually written.
     for entry in &cia_wo.tmd.content_chunk_records {
-        let content_file = format!("{:08x}", entry.content_id);
-        let content_path = path.join(&content_file);
-
-        let actual_size = tokio::fs::metadata(&content_path).await?.len();
-        if actual_size != entry.content_size {
+        let content_path = path.join(format!("{:08x}", entry.content_id));
+        let actual_size = tokio::fs::metadata(&content_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "content {:08x} is missing: {}",
+                    entry.content_id,
+                    content_path.display()
+                )
+            })?
+            .len();
+        if actual_size == 0 && entry.content_size != 0 {
             anyhow::bail!(
-                "content file {} size mismatch: TMD declares {} bytes but file is {} bytes",
+                "content {:08x} ({}) is empty but the TMD declares {} bytes; the CDN download looks incomplete",
+                entry.content_id,
                 content_path.display(),
                 entry.content_size,
-                actual_size,
             );
         }
-
-        let mut f = File::open(&content_path).await?;
-        let mut written: u64 = 0;
-        loop {
-            if cancel.is_cancelled() {
-                return Err(NintendoCTRError::Cancelled.into());
-            }
-            let n = f.read(&mut buf).await?;
-            if n == 0 {
-                break;
+        if actual_size != entry.content_size && fix_content_size {
+            if actual_size > entry.content_size {
+                warn!(
+                    "content {:08x}: trimming {} trailing bytes to the TMD size of {} bytes",
+                    entry.content_id,
+                    actual_size - entry.content_size,
+                    entry.content_size,
+                );
+            } else {
+                warn!(
+                    "content {:08x}: zero-padding {} bytes to the TMD size of {} bytes",
+                    entry.content_id,
+                    entry.content_size - actual_size,
+                    entry.content_size,
+                );
             }
-            out.write_all(&buf[..n]).await?;
-            progress.inc(n as u64);
-            written += n as u64;
-        }
-        if written != entry.content_size {
+        } else if actual_size != entry.content_size {
             anyhow::bail!(
-                "content file {} short read: expected {} bytes, got {}",
+                "content {:08x} ({}) size mismatch: TMD declares {} bytes but file is {} bytes",
+                entry.content_id,
                 content_path.display(),
                 entry.content_size,
-                written,
+                actual_size,
             );
         }
     }
 
+    let mut preamble = Vec::new();
+    cia_wo.write_options(&mut Cursor::new(&mut preamble), Endian::Little, ())?;
+    out.write_all(&preamble).await?;
+
+    // Stream each content file directly to the output. The short-read check
+    // in finish_content catches a file that shrank after the size check.
+    let records = &cia_wo.tmd.content_chunk_records;
+    let limit_for = |entry: &ContentChunkRecord| {
+        if fix_content_size {
+            entry.content_size
+        } else {
+            u64::MAX
+        }
+    };
+    if content_concurrency <= 1 {
+        let mut buf = vec![0u8; CONTENT_COPY_BUF];
+        for entry in records {
+            let content_path = path.join(format!("{:08x}", entry.content_id));
+            let mut f = File::open(&content_path).await?.take(limit_for(entry));
+            let mut written: u64 = 0;
+            loop {
+                if cancel.is_cancelled() {
+                    return Err(NintendoCTRError::Cancelled.into());
+                }
+                let n = f.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                out.write_all(&buf[..n]).await?;
+                progress.inc(n as u64);
+                written += n as u64;
+            }
+            finish_content(
+                out,
+                entry,
+                &content_path,
+                written,
+                fix_content_size,
+                &mut buf,
+                progress,
+            )
+            .await?;
+        }
+    } else {
+        // Keep a window of readers ahead of the writer, each paired with
+        // the record it reads so the two cannot drift apart.
+        let reader_for = |entry: &ContentChunkRecord| {
+            let content_path = path.join(format!("{:08x}", entry.content_id));
+            let chunks = spawn_content_reader(content_path.clone(), limit_for(entry));
+            (content_path, chunks)
+        };
+        let mut upcoming = records.iter();
+        let mut readers: VecDeque<_> = upcoming
+            .by_ref()
+            .take(content_concurrency)
+            .map(|entry| (entry, reader_for(entry)))
+            .collect();
+        let mut zeros = Vec::new();
+        while let Some((entry, (content_path, mut chunks))) = readers.pop_front() {
+            if let Some(next) = upcoming.next() {
+                readers.push_back((next, reader_for(next)));
+            }
+            let mut written: u64 = 0;
+            loop {
+                if cancel.is_cancelled() {
+                    return Err(NintendoCTRError::Cancelled.into());
+                }
+                let Some(chunk) = chunks.recv().await else {
+                    break;
+                };
+                let chunk = chunk?;
+                out.write_all(&chunk).await?;
+                progress.inc(chunk.len() as u64);
+                written += chunk.len() as u64;
+            }
+            finish_content(
+                out,
+                entry,
+                &content_path,
+                written,
+                fix_content_size,
+                &mut zeros,
+                progress,
+            )
+            .await?;
+        }
+    }
+
     out.flush().await?;
     progress.finish();
 
     Ok(())
 }
 
-/// Reads a certificate chain from the end of a TMD or Ticket file
-async fn read_certificate_chain(file_path: &Path) -> anyhow::Result<Vec<Certificate>> {
-    let content = tokio::fs::read(file_path).await?;
-    let mut cursor = Cursor::new(&content);
-
-    // First, parse the main structure to find where certificates start
-    let _ = {
-        let start_pos = cursor.position();
+/// Zero-pads a content that came up `written` bytes short of its TMD size
+/// when `fix_content_size` is set, then fails if the size still differs.
+/// `buf` is reused, grown to [`CONTENT_COPY_BUF`] if needed, as the source
+/// of zeros.
+async fn finish_content(
+    out: &mut BufWriter<File>,
+    entry: &ContentChunkRecord,
+    content_path: &Path,
+    mut written: u64,
+    fix_content_size: bool,
+    buf: &mut Vec<u8>,
+    progress: &dyn ProgressReporter,
+) -> anyhow::Result<()> {
+    if fix_content_size && written < entry.content_size {
+        buf.clear();
+        buf.resize(CONTENT_COPY_BUF, 0);
+        while written < entry.content_size {
+            let n = (entry.content_size - written).min(buf.len() as u64) as usize;
+            out.write_all(&buf[..n]).await?;
+            progress.inc(n as u64);
+            written += n as u64;
+        }
+    }
+    if written != entry.content_size {
+        anyhow::bail!(
+            "content file {} short read: expected {} bytes, got {}",
+            content_path.display(),
+            entry.content_size,
+            written,
+        );
+    }
+    Ok(())
+}
 
-        // The file starts with either a TMD or a Ticket; certificates follow.
-        if let Ok(_tmd) = TitleMetadata::read_options(&mut cursor, Endian::Big, ()) {
-            cursor.position()
-        } else {
-            std::io::Seek::seek(&mut cursor, SeekFrom::Start(start_pos))?;
-            if let Ok(_ticket) = Ticket::read_options(&mut cursor, Endian::Big, ()) {
-                cursor.position()
-            } else {
-                return Err(anyhow::anyhow!("file is neither TMD nor ticket"));
+/// Reads one content file in [`CONTENT_COPY_BUF`] chunks on its own task,
+/// stopping after `limit` bytes, and queues at most [`CONTENT_READ_AHEAD`]
+/// of them for the writer. An open or read error is passed along in order.
+/// The task ends early once the writer drops the receiver.
+fn spawn_content_reader(path: PathBuf, limit: u64) -> mpsc::Receiver<std::io::Result<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel(CONTENT_READ_AHEAD);
+    tokio::spawn(async move {
+        let mut f = match File::open(&path).await {
+            Ok(f) => f.take(limit),
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        };
+        loop {
+            let mut buf = vec![0u8; CONTENT_COPY_BUF];
+            match f.read(&mut buf).await {
+                Ok(0) => return,
+                Ok(n) => {
+                    buf.truncate(n);
+                    if tx.send(Ok(buf)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
             }
         }
-    };
+    });
+    rx
+}
 
+/// Parses the certificates at the start of `content`, the bytes that
+/// follow a TMD or ticket, stopping at the first thing that is not a
+/// whole certificate. Every 3DS certificate is issued under `Root`, so a
+/// footer some tools append is dropped even when its first bytes happen
+/// to look like a certificate header.
+pub(crate) fn parse_certificate_chain(content: &[u8]) -> anyhow::Result<Vec<Certificate>> {
+    let mut cursor = Cursor::new(content);
     let mut certificates = Vec::new();
 
     while cursor.position() < content.len() as u64 {
@@ -295,22 +419,19 @@ async fn read_certificate_chain(file_path: &Path) -> anyhow::Result<Vec<Certific
             break;
         }
 
-        // Peek at the sig type so the loop stops cleanly when the trailing
-        // region hits padding or unrelated data instead of a valid cert.
-        let pos = cursor.position();
-        let sig_type_bytes = match ReadBytesExt::read_u32::<BigEndian>(&mut cursor) {
-            Ok(val) => val,
-            Err(_) => break,
+        // Peek at the sig and key type so the loop stops cleanly when the
+        // trailing region hits padding or unrelated data, or a header whose
+        // sizes would run past the end of the file.
+        let Some(size) = Certificate::peek_size(&mut cursor)? else {
+            break;
         };
-        std::io::Seek::seek(&mut cursor, SeekFrom::Start(pos))?;
-
-        if !matches!(sig_type_bytes, CERT_SIG_TYPE_MIN..=CERT_SIG_TYPE_MAX) {
+        if cursor.position() + size > content.len() as u64 {
             break;
         }
 
         match Certificate::read_options(&mut cursor, Endian::Big, ()) {
-            Ok(cert) => certificates.push(cert),
-            Err(_) => break,
+            Ok(cert) if cert.issuer.starts_with(b"Root") => certificates.push(cert),
+            _ => break,
         }
     }
 
@@ -325,17 +446,21 @@ fn merge_certificate_chains(
     let mut merged = Vec::new();
     let mut seen_names = std::collections::HashSet::new();
 
-    fn get_cert_name(cert: &Certificate) -> String {
-        String::from_utf8_lossy(&cert.name)
-            .trim_end_matches('\0')
-            .to_string()
+    // Names are compared as raw bytes: a name that is not valid UTF-8
+    // must not be folded into U+FFFD and collide with another one.
+    fn get_cert_name(cert: &Certificate) -> &[u8] {
+        let end = cert
+            .name
+            .iter()
+            .rposition(|&b| b != 0)
+            .map_or(0, |last| last + 1);
+        &cert.name[..end]
     }
 
     // CIA cert chain order is CA → XS → CP. CA may live in either source.
     for cert in tmd_certs.iter().chain(tik_certs.iter()) {
         let name = get_cert_name(cert);
-        if name.starts_with("CA") && !seen_names.contains(&name) {
-            seen_names.insert(name.clone());
+        if name.starts_with(b"CA") && seen_names.insert(name) {
             merged.push(cert.clone());
             break;
         }
@@ -343,8 +468,7 @@ fn merge_certificate_chains(
 
     for cert in tik_certs.iter() {
         let name = get_cert_name(cert);
-        if name.starts_with("XS") && !seen_names.contains(&name) {
-            seen_names.insert(name.clone());
+        if name.starts_with(b"XS") && seen_names.insert(name) {
             merged.push(cert.clone());
             break;
         }
@@ -352,8 +476,7 @@ fn merge_certificate_chains(
 
     for cert in tmd_certs.iter() {
         let name = get_cert_name(cert);
-        if name.starts_with("CP") && !seen_names.contains(&name) {
-            seen_names.insert(name.clone());
+        if name.starts_with(b"CP") && seen_names.insert(name) {
             merged.push(cert.clone());
             break;
         }
@@ -362,12 +485,37 @@ fn merge_certificate_chains(
     merged
 }
 
+/// The TMD's certificate chain followed by the ticket's, in file order,
+/// dropping only certificates byte-identical to one already kept, with
+/// the chain's serialized size for the CIA header.
+fn concat_certificate_chains(
+    tmd_certs: Vec<Certificate>,
+    tik_certs: Vec<Certificate>,
+) -> anyhow::Result<(Vec<Certificate>, u32)> {
+    let mut chain = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut size = 0u32;
+    for cert in tmd_certs.into_iter().chain(tik_certs) {
+        let mut bytes = Vec::new();
+        cert.write_options(&mut Cursor::new(&mut bytes), Endian::Big, ())?;
+        let len = bytes.len() as u32;
+        if seen.insert(bytes) {
+            size += len;
+            chain.push(cert);
+        }
+    }
+    Ok((chain, size))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::nintendo::ctr::models::cia::CiaFile;
-    use crate::nintendo::ctr::test_fixtures::{append_be, make_cert, make_ticket, make_tmd};
+    use crate::nintendo::ctr::test_fixtures::{
+        append_be, make_cert, make_ticket, make_tmd, serialized_size,
+    };
     use crate::util::NoProgress;
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn tmd_with_trailing_certs_parses_as_tmd() {
@@ -383,6 +531,67 @@ mod tests {
         assert_eq!(tmd_read.header.content_count, 1);
     }
 
+    #[tokio::test]
+    async fn concurrent_content_reads_keep_the_tmd_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path();
+        let title_id = 0x0004000000030000u64;
+        let mut records = Vec::new();
+        // TMD order differs from the content IDs' order on disk.
+        for (index, id) in [4u32, 0, 2, 1, 3].into_iter().enumerate() {
+            let data: Vec<u8> = (0..0x300 + id as usize * 0x111)
+                .map(|i| (i as u8) ^ (id as u8 * 0x35))
+                .collect();
+            std::fs::write(cdn.join(format!("{id:08x}")), &data).unwrap();
+            records.push((id, index as u16, data, [0u8; 32]));
+        }
+        let tmd = make_tmd(title_id, records);
+
+        let mut built = Vec::new();
+        for (concurrency, fix_content_size) in
+            [(1, false), (3, false), (8, false), (1, true), (3, true)]
+        {
+            if fix_content_size && concurrency == 1 {
+                // A content cut short is zero-padded the same way on both paths.
+                let short = cdn.join("00000002");
+                let data = std::fs::read(&short).unwrap();
+                std::fs::write(&short, &data[..data.len() - 0x10]).unwrap();
+            }
+            let out_path = cdn.join(format!("out{concurrency}-{fix_content_size}.cia"));
+            let mut out = BufWriter::new(File::create(&out_path).await.unwrap());
+            write_cia(
+                cdn,
+                &mut out,
+                tmd.clone(),
+                make_ticket(title_id),
+                vec![
+                    make_cert(b"CP0000000b", 0xBB),
+                    make_cert(b"CA00000003", 0xAA),
+                ],
+                vec![make_cert(b"XS0000000c", 0xCC)],
+                fix_content_size,
+                false,
+                concurrency,
+                &NoProgress,
+                &CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            out.flush().await.unwrap();
+            built.push(std::fs::read(&out_path).unwrap());
+        }
+        assert_eq!(built[0], built[1]);
+        assert_eq!(built[0], built[2]);
+        assert_eq!(built[3], built[4]);
+        assert_eq!(built[3].len(), built[0].len());
+        assert_ne!(built[3], built[0]);
+
+        let cia = CiaFile::read_options(&mut Cursor::new(&built[1]), Endian::Little, ()).unwrap();
+        let content_start = built[1].len() - cia.content_data.len();
+        let first = &built[1][content_start..content_start + 0x300 + 4 * 0x111];
+        assert_eq!(first, std::fs::read(cdn.join("00000004")).unwrap());
+    }
+
     #[tokio::test]
     async fn write_cia_streams_content_and_parses_back() {
         let tmp = tempfile::tempdir().unwrap();
@@ -420,24 +629,11 @@ mod tests {
         );
         let ticket = make_ticket(title_id);
 
-        // Write TMD file: serialized TMD + trailing CP + CA cert.
-        let tmd_path = cdn.join("tmd");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &tmd);
-            append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
-            append_be(&mut buf, &make_cert(b"CA00000003", 0xAA));
-            std::fs::write(&tmd_path, &buf).unwrap();
-        }
-
-        // Write Ticket file: serialized Ticket + trailing XS cert.
-        let tik_path = cdn.join("cetk");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &ticket);
-            append_be(&mut buf, &make_cert(b"XS0000000c", 0xCC));
-            std::fs::write(&tik_path, &buf).unwrap();
-        }
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+        let tik_certs = vec![make_cert(b"XS0000000c", 0xCC)];
 
         let out_path = cdn.join("out.cia");
         {
@@ -446,10 +642,13 @@ mod tests {
             write_cia(
                 cdn,
                 &mut out,
-                &tmd_path,
-                &tik_path,
                 tmd.clone(),
                 ticket,
+                tmd_certs,
+                tik_certs,
+                false,
+                false,
+                1,
                 &NoProgress,
                 &CancelToken::new(),
             )
@@ -476,6 +675,117 @@ mod tests {
         assert!(!cia.cert_chain.is_empty());
     }
 
+    #[tokio::test]
+    async fn raw_certs_keep_both_chains_and_drop_only_exact_duplicates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path();
+        let content = vec![0x5Au8; 0x400];
+        std::fs::write(cdn.join("00000000"), &content).unwrap();
+        let title_id = 0x0004000000030000u64;
+        let tmd = make_tmd(title_id, vec![(0, 0, content.clone(), [0u8; 32])]);
+        // The fixture's content index under-declares its size; match it
+        // to the bytes written so the certificates after it parse.
+        let mut ticket = make_ticket(title_id);
+        ticket.ticket_data.content_index.total_size = 28;
+
+        // Two CP certificates, and a CA both files carry.
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+            make_cert(b"CP0000000c", 0xBC),
+        ];
+        let tik_certs = vec![
+            make_cert(b"XS0000000c", 0xCC),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+
+        let mut chains = Vec::new();
+        for raw_certs in [false, true] {
+            let out_path = cdn.join("out.cia");
+            let mut out = BufWriter::new(File::create(&out_path).await.unwrap());
+            write_cia(
+                cdn,
+                &mut out,
+                tmd.clone(),
+                ticket.clone(),
+                tmd_certs.clone(),
+                tik_certs.clone(),
+                false,
+                raw_certs,
+                1,
+                &NoProgress,
+                &CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            out.flush().await.unwrap();
+            let bytes = std::fs::read(&out_path).unwrap();
+            let cia = CiaFile::read_options(&mut Cursor::new(&bytes), Endian::Little, ()).unwrap();
+            assert_eq!(cia.content_data, content);
+            let names: Vec<String> = cia
+                .cert_chain
+                .iter()
+                .map(|cert| String::from_utf8_lossy(&cert.name[..10]).into_owned())
+                .collect();
+            chains.push((names, cia.header.cert_chain_size));
+        }
+
+        assert_eq!(chains[0].0, ["CA00000003", "XS0000000c", "CP0000000b"]);
+        assert_eq!(chains[0].1, CIA_CERT_CHAIN_SIZE);
+        assert_eq!(
+            chains[1].0,
+            ["CP0000000b", "CA00000003", "CP0000000c", "XS0000000c"]
+        );
+        let cert_bytes: u32 = [
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+            make_cert(b"CP0000000c", 0xBC),
+            make_cert(b"XS0000000c", 0xCC),
+        ]
+        .iter()
+        .map(serialized_size)
+        .sum();
+        assert_eq!(chains[1].1, cert_bytes);
+    }
+
+    #[test]
+    fn certificate_chain_after_a_tmd_stops_at_padding() {
+        let tmd = make_tmd(0x0004000000030000, vec![(0, 0, vec![0u8; 0x10], [0u8; 32])]);
+        let mut buf = Vec::new();
+        append_be(&mut buf, &tmd);
+        let tmd_len = buf.len();
+        append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
+        append_be(&mut buf, &make_cert(b"CA00000003", 0xAA));
+        // Trailing padding is not a certificate and ends the chain.
+        buf.extend_from_slice(&[0u8; 0x40]);
+
+        let names: Vec<Vec<u8>> = parse_certificate_chain(&buf[tmd_len..])
+            .unwrap()
+            .iter()
+            .map(|cert| cert.name[..10].to_vec())
+            .collect();
+        assert_eq!(names, [b"CP0000000b".to_vec(), b"CA00000003".to_vec()]);
+    }
+
+    #[test]
+    fn certificate_chain_stops_at_a_footer_shaped_like_a_certificate() {
+        let mut buf = Vec::new();
+        append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
+        append_be(&mut buf, &make_cert(b"CA00000003", 0xAA));
+        // A tool footer whose first bytes parse as a certificate header but
+        // whose issuer is not under Root.
+        let mut footer = Vec::new();
+        append_be(&mut footer, &make_cert(b"FOOTER", 0x00));
+        footer[0x140..0x180].fill(0);
+        footer[0x140..0x149].copy_from_slice(b"WADFOOTER");
+        buf.extend_from_slice(&footer);
+        buf.extend_from_slice(b"trailing tool data");
+
+        let certs = parse_certificate_chain(&buf).unwrap();
+        assert_eq!(certs.len(), 2);
+        assert_eq!(&certs[1].name[..10], b"CA00000003");
+    }
+
     #[tokio::test]
     async fn write_cia_verifies_via_streaming_verify() {
         // End-to-end: write_cia → verify_cia (streaming content hashes).
@@ -497,21 +807,11 @@ mod tests {
         let tmd = make_tmd(title_id, vec![(0, 0, content_a.clone(), hash_a)]);
         let ticket = make_ticket(title_id);
 
-        let tmd_path = cdn.join("tmd");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &tmd);
-            append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
-            append_be(&mut buf, &make_cert(b"CA00000003", 0xAA));
-            std::fs::write(&tmd_path, &buf).unwrap();
-        }
-        let tik_path = cdn.join("cetk");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &ticket);
-            append_be(&mut buf, &make_cert(b"XS0000000c", 0xCC));
-            std::fs::write(&tik_path, &buf).unwrap();
-        }
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+        let tik_certs = vec![make_cert(b"XS0000000c", 0xCC)];
 
         let out_path = cdn.join("streamed.cia");
         {
@@ -520,10 +820,13 @@ mod tests {
             write_cia(
                 cdn,
                 &mut out,
-                &tmd_path,
-                &tik_path,
                 tmd,
                 ticket,
+                tmd_certs,
+                tik_certs,
+                false,
+                false,
+                1,
                 &NoProgress,
                 &CancelToken::new(),
             )
@@ -571,21 +874,11 @@ mod tests {
         let tmd = make_tmd(title_id, vec![(0, 0, declared, hash)]);
         let ticket = make_ticket(title_id);
 
-        let tmd_path = cdn.join("tmd");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &tmd);
-            append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
-            append_be(&mut buf, &make_cert(b"CA00000003", 0xAA));
-            std::fs::write(&tmd_path, &buf).unwrap();
-        }
-        let tik_path = cdn.join("cetk");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &ticket);
-            append_be(&mut buf, &make_cert(b"XS0000000c", 0xCC));
-            std::fs::write(&tik_path, &buf).unwrap();
-        }
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+        let tik_certs = vec![make_cert(b"XS0000000c", 0xCC)];
 
         let out_path = cdn.join("truncated.cia");
         let f = File::create(&out_path).await.unwrap();
@@ -593,10 +886,13 @@ mod tests {
         let err = write_cia(
             cdn,
             &mut out,
-            &tmd_path,
-            &tik_path,
             tmd,
             ticket,
+            tmd_certs,
+            tik_certs,
+            false,
+            false,
+            1,
             &NoProgress,
             &CancelToken::new(),
         )
@@ -633,21 +929,11 @@ mod tests {
         let tmd = make_tmd(title_id, vec![(0, 0, declared, hash)]);
         let ticket = make_ticket(title_id);
 
-        let tmd_path = cdn.join("tmd");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &tmd);
-            append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
-            append_be(&mut buf, &make_cert(b"CA00000003", 0xAA));
-            std::fs::write(&tmd_path, &buf).unwrap();
-        }
-        let tik_path = cdn.join("cetk");
-        {
-            let mut buf = Vec::new();
-            append_be(&mut buf, &ticket);
-            append_be(&mut buf, &make_cert(b"XS0000000c", 0xCC));
-            std::fs::write(&tik_path, &buf).unwrap();
-        }
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+        let tik_certs = vec![make_cert(b"XS0000000c", 0xCC)];
 
         let out_path = cdn.join("oversized.cia");
         let f = File::create(&out_path).await.unwrap();
@@ -655,10 +941,13 @@ mod tests {
         let err = write_cia(
             cdn,
             &mut out,
-            &tmd_path,
-            &tik_path,
             tmd,
             ticket,
+            tmd_certs,
+            tik_certs,
+            false,
+            false,
+            1,
             &NoProgress,
             &CancelToken::new(),
         )
@@ -670,6 +959,107 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn write_cia_fix_content_size_trims_and_pads_to_the_tmd() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path();
+
+        // One download carries stray trailing bytes, the other lost the
+        // zero padding at its end.
+        let long: Vec<u8> = (0..0x800u16).map(|i| (i as u8).wrapping_mul(3)).collect();
+        let mut short = vec![0x42u8; 0x600];
+        short.resize(0x800, 0);
+        std::fs::write(
+            cdn.join("00000000"),
+            [long.as_slice(), &[0xFF; 0x10]].concat(),
+        )
+        .unwrap();
+        std::fs::write(cdn.join("00000001"), &short[..0x600]).unwrap();
+
+        let title_id = 0x0004000000550000u64;
+        let tmd = make_tmd(
+            title_id,
+            vec![
+                (0, 0, long.clone(), Sha256::digest(&long).into()),
+                (1, 1, short.clone(), Sha256::digest(&short).into()),
+            ],
+        );
+        let ticket = make_ticket(title_id);
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+        let tik_certs = vec![make_cert(b"XS0000000c", 0xCC)];
+
+        let out_path = cdn.join("fixed.cia");
+        let mut out = BufWriter::new(File::create(&out_path).await.unwrap());
+        write_cia(
+            cdn,
+            &mut out,
+            tmd,
+            ticket,
+            tmd_certs,
+            tik_certs,
+            true,
+            false,
+            1,
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        out.flush().await.unwrap();
+        drop(out);
+
+        let bytes = std::fs::read(&out_path).unwrap();
+        let cia = CiaFile::read_options(&mut Cursor::new(&bytes), Endian::Little, ()).unwrap();
+        assert_eq!(cia.content_data, [long, short].concat());
+    }
+
+    #[tokio::test]
+    async fn write_cia_names_an_empty_content_file_before_writing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path();
+
+        let declared = vec![0x5Au8; 0x400];
+        let hash: [u8; 32] = Sha256::digest(&declared).into();
+        let title_id = 0x0004000000440000u64;
+        let tmd = make_tmd(title_id, vec![(0x0000_00AB, 0, declared, hash)]);
+        let ticket = make_ticket(title_id);
+        std::fs::write(cdn.join("000000ab"), b"").unwrap();
+
+        let tmd_certs = vec![
+            make_cert(b"CP0000000b", 0xBB),
+            make_cert(b"CA00000003", 0xAA),
+        ];
+        let tik_certs = vec![make_cert(b"XS0000000c", 0xCC)];
+
+        let out_path = cdn.join("empty.cia");
+        let mut out = BufWriter::new(File::create(&out_path).await.unwrap());
+        let err = write_cia(
+            cdn,
+            &mut out,
+            tmd,
+            ticket,
+            tmd_certs,
+            tik_certs,
+            false,
+            false,
+            1,
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.contains("content 000000ab") && msg.contains("is empty"),
+            "{msg}"
+        );
+        drop(out);
+        assert_eq!(std::fs::metadata(&out_path).unwrap().len(), 0);
+    }
+
     /// A CIA whose header declares a Meta section must keep that section in
     /// the decrypted output, both in the header's meta_size field and as
     /// trailing bytes after the content.
@@ -683,9 +1073,15 @@ mod tests {
 
         let f = File::create(&out_path).await.unwrap();
         let mut out = BufWriter::new(f);
-        decrypt_from_encrypted_cia(&in_path, &mut out, &NoProgress, &CancelToken::new())
-            .await
-            .unwrap();
+        decrypt_from_encrypted_cia(
+            &in_path,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
         out.flush().await.unwrap();
         drop(out);
 
@@ -759,9 +1155,15 @@ mod tests {
         let out_path = in_path.with_extension("dec.cia");
         let f = File::create(&out_path).await.unwrap();
         let mut out = BufWriter::new(f);
-        decrypt_from_encrypted_cia(&in_path, &mut out, &NoProgress, &CancelToken::new())
-            .await
-            .unwrap();
+        decrypt_from_encrypted_cia(
+            &in_path,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
         out.flush().await.unwrap();
         drop(out);
 
@@ -794,9 +1196,15 @@ mod tests {
 
         let f = File::create(&out_path).await.unwrap();
         let mut out = BufWriter::new(f);
-        decrypt_from_encrypted_cia(&in_path, &mut out, &NoProgress, &CancelToken::new())
-            .await
-            .expect("decrypt must succeed for a content_id containing hex letters");
+        decrypt_from_encrypted_cia(
+            &in_path,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .expect("decrypt must succeed for a content_id containing hex letters");
         out.flush().await.unwrap();
         drop(out);
 
@@ -86,8 +86,6 @@ pub const CTR_KEY_SCRAMBLE_C: u128 = 42503689118608475533858958821215598218;
 // CIA format
 pub const CIA_CONTENT_INDEX_SIZE: usize = 0x2000;
 pub const CIA_CERT_CHAIN_SIZE: u32 = 0xA00;
-pub const CERT_SIG_TYPE_MIN: u32 = 0x010000;
-pub const CERT_SIG_TYPE_MAX: u32 = 0x010005;
 
 // Title key derivation
 pub const CTR_TITLE_KEY_PBKDF2_ITERATIONS: u32 = 20;
@@ -144,11 +144,9 @@ pub async fn cci_to_cia_cancellable(
         ticket,
         tmd,
     };
-    for record in &cia_wo.tmd.content_chunk_records {
-        cia_wo
-            .header
-            .set_content_index(record.content_index as usize);
-    }
+    cia_wo
+        .header
+        .rebuild_content_indexes(&cia_wo.tmd.content_chunk_records);
 
     let tmp = scratch_output_path(output)?;
     let out = File::create(&tmp).await.context("creating CIA output")?;
@@ -255,7 +253,7 @@ async fn patch_cxi_prefix(file: &mut File, p: &PartitionInfo) -> Result<PatchedC
         Some(derive_ctr_key(CTR_KEY_0X2C, key_y))
     };
 
-    let counter = get_ncch_aes_counter(&ncch_header, NcchSection::ExHeader);
+    let counter = get_ncch_aes_counter(&ncch_header, NcchSection::ExHeader)?;
 
     if let Some(key) = normal_key {
         let mut cipher = Aes128Ctr::new((&key).into(), (&counter).into());
@@ -430,7 +428,7 @@ fn build_ticket(title_id: u64, title_version: u16) -> crate::nintendo::ctr::mode
     t
 }
 
-fn build_info_records(chunks: &[ContentChunkRecord]) -> Result<Vec<ContentInfoRecord>> {
+pub(crate) fn build_info_records(chunks: &[ContentChunkRecord]) -> Result<Vec<ContentInfoRecord>> {
     let mut hasher = Sha256::new();
     for chunk in chunks {
         let mut buf = Vec::new();
@@ -455,7 +453,7 @@ fn build_info_records(chunks: &[ContentChunkRecord]) -> Result<Vec<ContentInfoRe
     Ok(records)
 }
 
-fn hash_info_records(records: &[ContentInfoRecord]) -> Result<Vec<u8>> {
+pub(crate) fn hash_info_records(records: &[ContentInfoRecord]) -> Result<Vec<u8>> {
     let mut hasher = Sha256::new();
     for r in records {
         let mut buf = Vec::new();
@@ -5,14 +5,16 @@ mod cci_to_cia;
 mod cia_to_cci;
 mod template;
 
+pub(crate) use cci_to_cia::{build_info_records, hash_info_records};
 pub use cci_to_cia::{cci_to_cia, cci_to_cia_cancellable};
 pub use cia_to_cci::{cia_to_cci, cia_to_cci_cancellable};
 
 use crate::nintendo::ctr::error::NintendoCTRError;
-use crate::util::{CancelToken, ProgressReporter};
+use crate::util::{CancelToken, ProgressReporter, Tally};
 use anyhow::{Result, bail};
 use log::{debug, warn};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::fs;
 
 const CIA_EXTS: &[&str] = &["cia"];
@@ -20,7 +22,6 @@ const CCI_EXTS: &[&str] = &["3ds", "cci"];
 const CONVERT_EXTS: &[&str] = &["cia", "3ds", "cci"];
 
 pub fn derive_converted_path(input: &Path) -> PathBuf {
-    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("out");
     let ext = input
         .extension()
         .and_then(|s| s.to_str())
@@ -31,7 +32,7 @@ pub fn derive_converted_path(input: &Path) -> PathBuf {
         "3ds" | "cci" => "cia",
         _ => "out",
     };
-    input.with_file_name(format!("{stem}.{new_ext}"))
+    input.with_extension(new_ext)
 }
 
 pub async fn convert_rom(
@@ -79,17 +80,25 @@ pub async fn convert_rom_batch(
         progress,
         total_progress,
         max_depth,
+        false,
+        &mut Tally::new(),
         CancelToken::new(),
     )
     .await
 }
 
+/// Runs the convert over every matching file under `input_dir`, recording each
+/// item in `tally`. A failed item is logged and skipped, or with `fail_fast`
+/// ends the run with its error.
+#[allow(clippy::too_many_arguments)]
 pub async fn convert_rom_batch_cancellable(
     input_dir: &Path,
     output_dir: Option<&Path>,
     progress: &dyn ProgressReporter,
     total_progress: &dyn ProgressReporter,
     max_depth: Option<usize>,
+    fail_fast: bool,
+    tally: &mut Tally,
     cancel: CancelToken,
 ) -> Result<()> {
     let roms = crate::util::fs::collect_files_with_exts(input_dir, CONVERT_EXTS, max_depth)?;
@@ -125,14 +134,22 @@ pub async fn convert_rom_batch_cancellable(
         }
         debug!("Converting {} -> {}", path.display(), output.display());
 
-        if let Err(err) = convert_rom_cancellable(&path, &output, progress, cancel.clone()).await {
-            if matches!(
-                err.downcast_ref::<NintendoCTRError>(),
-                Some(NintendoCTRError::Cancelled)
-            ) {
-                return Err(err);
+        let started = Instant::now();
+        match convert_rom_cancellable(&path, &output, progress, cancel.clone()).await {
+            Ok(()) => tally.record_ok_files(&path, &output, started.elapsed()),
+            Err(err) => {
+                if matches!(
+                    err.downcast_ref::<NintendoCTRError>(),
+                    Some(NintendoCTRError::Cancelled)
+                ) {
+                    return Err(err);
+                }
+                tally.record_failed();
+                if fail_fast {
+                    return Err(err.context(format!("Failed to convert {}", path.display())));
+                }
+                warn!("Failed to convert {}: {err}", path.display());
             }
-            warn!("Failed to convert {}: {err}", path.display());
         }
 
         total_progress.inc(1);
@@ -5,40 +5,50 @@ use aes::{
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 
 use crate::nintendo::ctr::constants::{
-    CTR_COMMON_KEYS_HEX, CTR_KEY_SCRAMBLE_C, CTR_KEYS_0, CTR_KEYS_1, CTR_MEDIA_UNIT_SIZE,
-    CTR_NCSD_PARTITIONS, CTR_SEED_COUNTRIES, EXEFS_ENTRY_SIZE, EXEFS_HEADER_SIZE,
-    EXEFS_MAX_FILE_ENTRIES, EXEFS_SECTION_BANNER, EXEFS_SECTION_ICON,
-    NCCH_FLAGS_EXTRA_CRYPTO_INDEX, NCCH_FLAGS_OFFSET, NCCH_FLAGS7_CRYPTO_METHOD,
-    NCCH_FLAGS7_FIXED_KEY, NCCH_FLAGS7_NOCRYPTO, NCCH_FLAGS7_SEED_CRYPTO, NCCH_MAGIC,
-    NCCH_MAGIC_OFFSET, NCSD_PARTITION_COUNT, NCSD_PARTITION_ENTRY_SIZE,
-    NCSD_PARTITION_TABLE_OFFSET, NCSD_TITLE_ID_OFFSET, TICKET_COMMON_KEY_IDX_OFFSET,
-    TICKET_SIG_BODY_OFFSET, TICKET_TITLE_ID_OFFSET, TICKET_TITLE_KEY_OFFSET,
-    TMD_CONTENT_COUNT_OFFSET, TMD_CONTENT_RECORD_SIZE, TMD_CONTENT_RECORDS_OFFSET,
+    CTR_KEY_SCRAMBLE_C, CTR_KEYS_0, CTR_KEYS_1, CTR_MEDIA_UNIT_SIZE, CTR_NCSD_PARTITIONS,
+    CTR_SEED_COUNTRIES, EXEFS_ENTRY_SIZE, EXEFS_HEADER_SIZE, EXEFS_MAX_FILE_ENTRIES,
+    EXEFS_SECTION_BANNER, EXEFS_SECTION_ICON, NCCH_FLAGS_EXTRA_CRYPTO_INDEX, NCCH_FLAGS_OFFSET,
+    NCCH_FLAGS7_CRYPTO_METHOD, NCCH_FLAGS7_FIXED_KEY, NCCH_FLAGS7_NOCRYPTO,
+    NCCH_FLAGS7_SEED_CRYPTO, NCCH_MAGIC, NCCH_MAGIC_OFFSET, NCSD_PARTITION_COUNT,
+    NCSD_PARTITION_ENTRY_SIZE, NCSD_PARTITION_TABLE_OFFSET, NCSD_TITLE_ID_OFFSET,
+    TICKET_COMMON_KEY_IDX_OFFSET, TICKET_SIG_BODY_OFFSET, TICKET_TITLE_ID_OFFSET,
+    TICKET_TITLE_KEY_OFFSET, TMD_CONTENT_COUNT_OFFSET, TMD_CONTENT_RECORD_SIZE,
+    TMD_CONTENT_RECORDS_OFFSET,
 };
+use crate::nintendo::ctr::decrypt::DecryptOptions;
 use crate::nintendo::ctr::decrypt::model::{CiaContent, NcchSection};
 use crate::nintendo::ctr::decrypt::reader::CiaReader;
 use crate::nintendo::ctr::decrypt::romfs_worker::{
     RomfsChunk, RomfsChunkWork, RomfsDecryptWorker, advance_counter,
 };
-use crate::nintendo::ctr::decrypt::util::{cbc_decrypt, gen_iv};
-use crate::nintendo::ctr::error::NintendoCTRError;
+use crate::nintendo::ctr::decrypt::util::{cbc_decrypt, common_key, gen_iv};
+use crate::nintendo::ctr::error::{NintendoCTRError, NintendoCTRResult};
 use crate::nintendo::ctr::models::cia::{CIA_HEADER_SIZE, CiaHeader};
 use crate::nintendo::ctr::models::exe_fs_header::ExeFSHeader;
 use crate::nintendo::ctr::models::ncch_header::NcchHeader;
-use crate::nintendo::ctr::models::seeddb::SeedDatabase;
 use crate::nintendo::ctr::models::title_metadata::ContentChunkRecord;
-use crate::nintendo::ctr::util::align_64;
+use crate::nintendo::ctr::util::{align_64, file_stem_or};
 use crate::nintendo::ctr::z3ds::models::underlying_magic;
 use crate::util::worker_pool::{Pool, parallelism};
-use crate::util::{CancelToken, ProgressReporter};
+use crate::util::{
+    BYTES_PER_MB, CancelToken, ProgressReporter, ScratchPath, publish_temp, scratch_output_path,
+    throughput_mb_s,
+};
 use anyhow::{Context, anyhow};
 use binrw::BinRead;
-use futures::future::select_ok;
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, info, warn};
 use sha2::{Digest, Sha256};
 use std::io::{Cursor, SeekFrom};
-use std::{collections::HashMap, path::Path, vec};
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    time::Instant,
+    vec,
+};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
 
@@ -64,12 +74,46 @@ pub(crate) fn extra_crypto_index(uses_extra_crypto: u8) -> usize {
     }
 }
 
+/// Keyslot behind each `extra_crypto_index` value. Slot 0x2C always covers
+/// the NCCH header area; the listed slot covers the ExeFS code and RomFS.
+pub(crate) const NCCH_KEYSLOTS: [u8; 4] = [0x2C, 0x25, 0x18, 0x1B];
+
+/// Fixed-key selector for an NCCH: 0 when the keyslots apply, otherwise
+/// 1 for the zero key or 2 for the system fixed key (system titles).
+pub(crate) fn fixed_crypto_kind(header: &NcchHeader) -> u8 {
+    if (header.flags[7] & NCCH_FLAGS7_FIXED_KEY) == 0 {
+        return 0;
+    }
+    let mut tid = header.titleid;
+    tid.reverse();
+    if (tid[3] & 16) != 0 { 2 } else { 1 }
+}
+
 pub(crate) fn derive_ctr_key(key_x: u128, key_y: u128) -> [u8; 16] {
     u128::to_be_bytes(scramblekey(key_x, key_y))
 }
 
-pub(crate) fn fixed_key(fixed_crypto: u8) -> Option<[u8; 16]> {
-    (fixed_crypto != 0).then(|| u128::to_be_bytes(CTR_KEYS_1[(fixed_crypto as usize) - 1]))
+/// Human-readable name of each `CTR_KEYS_1` entry, for logging.
+const FIXED_KEY_NAMES: [&str; 2] = ["zero key (devkit)", "system fixed key"];
+
+/// Name of the fixed key `fixed_crypto` selects, or `None` when the
+/// keyslots apply or the selector is out of range.
+pub(crate) fn fixed_key_name(fixed_crypto: u8) -> Option<&'static str> {
+    (fixed_crypto as usize)
+        .checked_sub(1)
+        .and_then(|i| FIXED_KEY_NAMES.get(i).copied())
+}
+
+/// Fixed key for a `fixed_crypto_kind` selector. `Ok(None)` means the
+/// keyslots apply; a selector past the end of `CTR_KEYS_1` is an error.
+pub(crate) fn fixed_key(fixed_crypto: u8) -> NintendoCTRResult<Option<[u8; 16]>> {
+    let Some(index) = (fixed_crypto as usize).checked_sub(1) else {
+        return Ok(None);
+    };
+    CTR_KEYS_1
+        .get(index)
+        .map(|&key| Some(u128::to_be_bytes(key)))
+        .ok_or(NintendoCTRError::UnknownFixedKey(fixed_crypto))
 }
 
 type ContentHasher<'a> = Option<&'a mut Sha256>;
@@ -161,7 +205,7 @@ async fn write_exheader_section(
     progress: &dyn ProgressReporter,
 ) -> anyhow::Result<()> {
     let mut key = base_key;
-    if let Some(fixed) = fixed_key(fixed_crypto) {
+    if let Some(fixed) = fixed_key(fixed_crypto)? {
         key = fixed;
     }
 
@@ -193,7 +237,7 @@ async fn write_exefs_section(
     progress: &dyn ProgressReporter,
 ) -> anyhow::Result<()> {
     let mut working_key = opts.base_key;
-    if let Some(fixed) = fixed_key(opts.fixed_crypto) {
+    if let Some(fixed) = fixed_key(opts.fixed_crypto)? {
         working_key = fixed;
     }
 
@@ -213,29 +257,7 @@ async fn write_exefs_section(
         );
         Aes128Ctr::new_from_slices(&extra_key, &opts.ctr)?.apply_keystream(&mut extra_decrypted);
 
-        for entry_idx in 0usize..EXEFS_MAX_FILE_ENTRIES {
-            let entry_bytes =
-                &decrypted_exefs[entry_idx * EXEFS_ENTRY_SIZE..(entry_idx + 1) * EXEFS_ENTRY_SIZE];
-            let exe_info = ExeFSHeader::read(&mut Cursor::new(entry_bytes))?;
-
-            let offset = LittleEndian::read_u32(&exe_info.file_offset) as usize + EXEFS_HEADER_SIZE;
-            let size = LittleEndian::read_u32(&exe_info.file_size) as usize;
-
-            match exe_info.file_name.iter().rposition(|&x| x != 0) {
-                Some(name_end) if exe_info.file_name[..=name_end].is_ascii() => {
-                    if exe_info.file_name[..=name_end] != EXEFS_SECTION_ICON
-                        && exe_info.file_name[..=name_end] != EXEFS_SECTION_BANNER
-                    {
-                        decrypted_exefs[offset..offset + size]
-                            .copy_from_slice(&extra_decrypted[offset..offset + size]);
-                    }
-                }
-                _ => {
-                    decrypted_exefs[offset..offset + size]
-                        .copy_from_slice(&extra_decrypted[offset..offset + size]);
-                }
-            }
-        }
+        splice_extra_crypto_files(&mut decrypted_exefs, &extra_decrypted)?;
     }
 
     hash_bytes(hasher, &decrypted_exefs);
@@ -247,6 +269,42 @@ async fn write_exefs_section(
     Ok(())
 }
 
+/// Every ExeFS file except `icon` and `banner` is encrypted with the
+/// extra-crypto key, so copy those regions over the base-key pass. Stops at
+/// the first all-zero file header and clamps each region to the buffer, so a
+/// malformed ExeFS header cannot index past the section.
+fn splice_extra_crypto_files(decrypted: &mut [u8], extra: &[u8]) -> anyhow::Result<()> {
+    let len = decrypted.len().min(extra.len());
+    for entry_idx in 0..EXEFS_MAX_FILE_ENTRIES {
+        let entry_start = entry_idx * EXEFS_ENTRY_SIZE;
+        let Some(entry_bytes) = decrypted.get(entry_start..entry_start + EXEFS_ENTRY_SIZE) else {
+            break;
+        };
+        if entry_bytes.iter().all(|&b| b == 0) {
+            break;
+        }
+        let exe_info = ExeFSHeader::read(&mut Cursor::new(entry_bytes))?;
+
+        let name_end = exe_info
+            .file_name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(exe_info.file_name.len());
+        let name = &exe_info.file_name[..name_end];
+        if name == EXEFS_SECTION_ICON || name == EXEFS_SECTION_BANNER {
+            continue;
+        }
+
+        let offset = (LittleEndian::read_u32(&exe_info.file_offset) as usize)
+            .saturating_add(EXEFS_HEADER_SIZE)
+            .min(len);
+        let size = LittleEndian::read_u32(&exe_info.file_size) as usize;
+        let end = offset.saturating_add(size).min(len);
+        decrypted[offset..end].copy_from_slice(&extra[offset..end]);
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn write_romfs_section(
     cia: &mut CiaReader,
@@ -261,7 +319,7 @@ async fn write_romfs_section(
     cancel: &CancelToken,
 ) -> anyhow::Result<()> {
     let mut key = derive_ctr_key(CTR_KEYS_0[extra_crypto_index(uses_extra_crypto)], key_y);
-    if let Some(fixed) = fixed_key(fixed_crypto) {
+    if let Some(fixed) = fixed_key(fixed_crypto)? {
         key = fixed;
     }
 
@@ -333,27 +391,35 @@ async fn write_romfs_section(
     result
 }
 
-pub(crate) fn get_ncch_aes_counter(hdr: &NcchHeader, section: NcchSection) -> [u8; 16] {
+pub(crate) fn get_ncch_aes_counter(
+    hdr: &NcchHeader,
+    section: NcchSection,
+) -> NintendoCTRResult<[u8; 16]> {
     let mut counter: [u8; 16] = [0; 16];
-    if hdr.formatversion == 2 || hdr.formatversion == 0 {
-        let mut titleid: [u8; 8] = hdr.titleid;
-        titleid.reverse();
-        counter[0..8].copy_from_slice(&titleid);
-        counter[8] = section as u8;
-    } else if hdr.formatversion == 1 {
-        let x = match section {
-            NcchSection::ExHeader => 512,
-            NcchSection::ExeFS => hdr.exefsoffset * CTR_MEDIA_UNIT_SIZE,
-            NcchSection::RomFS => hdr.romfsoffset * CTR_MEDIA_UNIT_SIZE,
-        };
-
-        counter[0..8].copy_from_slice(&hdr.titleid);
-        for i in 0..4 {
-            counter[12 + i] = (x >> ((3 - i) * 8) & 255) as u8
+    match hdr.formatversion {
+        0 | 2 => {
+            let mut titleid: [u8; 8] = hdr.titleid;
+            titleid.reverse();
+            counter[0..8].copy_from_slice(&titleid);
+            counter[8] = section as u8;
+        }
+        1 => {
+            let x = match section {
+                NcchSection::ExHeader => 512,
+                NcchSection::ExeFS => hdr.exefsoffset * CTR_MEDIA_UNIT_SIZE,
+                NcchSection::RomFS => hdr.romfsoffset * CTR_MEDIA_UNIT_SIZE,
+            };
+
+            counter[0..8].copy_from_slice(&hdr.titleid);
+            for i in 0..4 {
+                counter[12 + i] = (x >> ((3 - i) * 8) & 255) as u8
+            }
         }
+        // A zero counter would decrypt to garbage without any error.
+        other => return Err(NintendoCTRError::UnsupportedNcchFormatVersion(other)),
     }
 
-    counter
+    Ok(counter)
 }
 
 fn scramblekey(key_x: u128, key_y: u128) -> u128 {
@@ -365,7 +431,64 @@ fn scramblekey(key_x: u128, key_y: u128) -> u128 {
     rol(value.wrapping_add(CTR_KEY_SCRAMBLE_C), 87)
 }
 
-async fn fetch_seed(title_id: &str) -> anyhow::Result<[u8; 16]> {
+/// One failed request to the seed CDN. `transient` marks a timeout,
+/// connection failure, or 5xx/429 answer that a later round may not hit.
+struct SeedFetchError {
+    error: anyhow::Error,
+    transient: bool,
+}
+
+/// Delay before seed CDN retry `attempt` (0-based): 500ms doubling per
+/// round, capped at 8s.
+fn seed_retry_delay(attempt: u32) -> std::time::Duration {
+    std::time::Duration::from_millis(500u64.saturating_mul(1u64 << attempt.min(4)).min(8_000))
+}
+
+/// Run `round` until it succeeds, retrying up to `retries` times while it
+/// fails transiently and waiting `delay(attempt)` between rounds.
+async fn retry_seed_rounds<T, F, Fut>(
+    title_id: &str,
+    retries: u32,
+    delay: impl Fn(u32) -> std::time::Duration,
+    mut round: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, SeedFetchError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match round().await {
+            Ok(value) => return Ok(value),
+            Err(err) if err.transient && attempt < retries => {
+                let wait = delay(attempt);
+                attempt += 1;
+                debug!(
+                    "Seed CDN request for {title_id} failed ({}); retry {attempt}/{retries} in {wait:?}",
+                    err.error
+                );
+                tokio::time::sleep(wait).await;
+            }
+            Err(err) => return Err(err.error),
+        }
+    }
+}
+
+async fn fetch_seed(title_id: &str, retries: u32) -> anyhow::Result<[u8; 16]> {
+    let bytes = retry_seed_rounds(title_id, retries, seed_retry_delay, || {
+        fetch_seed_round(title_id)
+    })
+    .await?;
+
+    let key: [u8; 16] = <[u8; 16]>::try_from(bytes.as_slice())
+        .map_err(|e| anyhow!("failed to parse key bytes: {}", e))?;
+
+    Ok(key)
+}
+
+/// Ask every seed CDN country at once and take the first answer. The
+/// round counts as transient when any country failed transiently.
+async fn fetch_seed_round(title_id: &str) -> Result<Vec<u8>, SeedFetchError> {
     lazy_static! {
         // Nintendo's seed CDN serves a custom certificate that won't chain to the
         // standard root store, so disabling TLS validation is the simplest way to
@@ -377,30 +500,52 @@ async fn fetch_seed(title_id: &str) -> anyhow::Result<[u8; 16]> {
     }
 
     // Build a future for each country, returning Ok(bytes) on 200 or Err otherwise
-    let requests = CTR_SEED_COUNTRIES.iter().map(|&country| {
-        let client = &*CLIENT;
-        debug!("Fetching seed for {country} ({title_id})");
-        let url = format!(
-            "https://kagiya-ctr.cdn.nintendo.net/title/0x{title_id}/ext_key?country={country}"
-        );
-        Box::pin(async move {
-            let resp = client.get(&url).send().await?;
-            if resp.status().is_success() {
-                let bytes = resp.bytes().await?;
-                Ok(bytes)
-            } else {
-                Err(anyhow!("HTTP {} for {}", resp.status(), country))
+    let mut requests: FuturesUnordered<_> = CTR_SEED_COUNTRIES
+        .iter()
+        .map(|&country| {
+            let client = &*CLIENT;
+            debug!("Fetching seed for {country} ({title_id})");
+            let url = format!(
+                "https://kagiya-ctr.cdn.nintendo.net/title/0x{title_id}/ext_key?country={country}"
+            );
+            async move {
+                let resp = client.get(&url).send().await.map_err(|e| SeedFetchError {
+                    error: e.into(),
+                    transient: true,
+                })?;
+                let status = resp.status();
+                if !status.is_success() {
+                    return Err(SeedFetchError {
+                        error: anyhow!("HTTP {} for {}", status, country),
+                        transient: status.is_server_error() || status.as_u16() == 429,
+                    });
+                }
+                resp.bytes()
+                    .await
+                    .map(|bytes| bytes.to_vec())
+                    .map_err(|e| SeedFetchError {
+                        error: e.into(),
+                        transient: true,
+                    })
             }
         })
-    });
-
-    // Run all requests in parallel and take the first successful one
-    let (bytes, _others) = select_ok(requests).await?;
-
-    let key: [u8; 16] = <[u8; 16]>::try_from(bytes.as_ref())
-        .map_err(|e| anyhow!("failed to parse key bytes: {}", e))?;
-
-    Ok(key)
+        .collect();
+
+    let mut transient = false;
+    let mut last_error = None;
+    while let Some(result) = requests.next().await {
+        match result {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) => {
+                transient |= err.transient;
+                last_error = Some(err.error);
+            }
+        }
+    }
+    Err(SeedFetchError {
+        error: last_error.unwrap_or_else(|| anyhow!("no seed CDN countries to ask")),
+        transient,
+    })
 }
 
 /// Parameters required to write a decrypted NCCH section.
@@ -492,30 +637,24 @@ pub(crate) async fn get_new_key(
     key_y: u128,
     header: &NcchHeader,
     title_id: String,
+    opts: &DecryptOptions,
 ) -> anyhow::Result<u128> {
-    lazy_static! {
-        static ref SEEDS: HashMap<String, [u8; 16]> = {
-            let db_path = Path::new("seeddb.bin");
-            if let Ok(data) = std::fs::read(db_path)
-                && let Ok(seeddb) = SeedDatabase::read(&mut Cursor::new(data))
-            {
-                debug!("Loading {} seeds from seeddb.bin", seeddb.seed_count);
-                seeddb
-                    .seeds
-                    .into_iter()
-                    .map(|seed| (seed.key, seed.value))
-                    .collect()
-            } else {
-                debug!("No seeddb.bin found, starting with an empty seed map");
-                HashMap::new()
-            }
-        };
+    if let Some(seed) = opts.seed {
+        let mut title_id_le: [u8; 8] = hex::decode(&title_id)?
+            .try_into()
+            .map_err(|_| anyhow!("title id {title_id} is not 8 bytes"))?;
+        title_id_le.reverse();
+        if !crate::nintendo::ctr::seed::seed_matches(&seed, &header.seedcheck, &title_id_le) {
+            return Err(NintendoCTRError::SuppliedSeedMismatch(title_id).into());
+        }
+        info!("Supplied seed matches the NCCH seedcheck for title {title_id}");
+        return Ok(crate::nintendo::ctr::seed::derive_seed_key_y(key_y, &seed));
     }
 
-    let mut seed = SEEDS.get(&title_id).copied();
+    let mut seed = crate::nintendo::ctr::seed::seeds().get(&title_id).copied();
 
     if seed.is_none() {
-        let api_seed = fetch_seed(&title_id).await?;
+        let api_seed = fetch_seed(&title_id, opts.seed_retries).await?;
         seed = Some(api_seed)
     }
 
@@ -531,9 +670,7 @@ pub(crate) async fn get_new_key(
         }
     }
 
-    Err(anyhow!(
-        "Seed verification failed: SHA256 mismatch for title {title_id}"
-    ))
+    Err(NintendoCTRError::SeedVerificationFailed(title_id).into())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -544,6 +681,7 @@ pub async fn parse_ncch(
     offs: u64,
     mut title_id: [u8; 8],
     mut hasher: ContentHasher<'_>,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> anyhow::Result<()> {
@@ -568,25 +706,20 @@ pub async fn parse_ncch(
     }
 
     let ncch_key_y = BigEndian::read_u128(header.signature[0..16].try_into()?);
-    let mut tid: [u8; 8] = header.titleid;
-    tid.reverse();
-
     let uses_extra_crypto: u8 = header.flags[NCCH_FLAGS_EXTRA_CRYPTO_INDEX];
 
     if uses_extra_crypto != 0 {
-        debug!("  Uses extra NCCH crypto, keyslot 0x25");
+        debug!(
+            "  Uses extra NCCH crypto, keyslot 0x{:02X}",
+            NCCH_KEYSLOTS[extra_crypto_index(uses_extra_crypto)]
+        );
     }
 
-    let mut fixed_crypto: u8 = 0;
+    let fixed_crypto = fixed_crypto_kind(&header);
     let mut encrypted: bool = true;
 
-    if (header.flags[7] & NCCH_FLAGS7_FIXED_KEY) != 0 {
-        if (tid[3] & 16) != 0 {
-            fixed_crypto = 2
-        } else {
-            fixed_crypto = 1
-        }
-        debug!("  Uses fixed-key crypto")
+    if let Some(name) = fixed_key_name(fixed_crypto) {
+        debug!("  Uses fixed-key crypto with the {name}")
     }
 
     if (header.flags[7] & NCCH_FLAGS7_NOCRYPTO) != 0 {
@@ -596,15 +729,35 @@ pub async fn parse_ncch(
 
     let use_seed_crypto: bool = (header.flags[7] & NCCH_FLAGS7_SEED_CRYPTO) != 0;
     let mut key_y = ncch_key_y;
+    let mut seed_missing = false;
 
     if use_seed_crypto {
-        key_y = get_new_key(ncch_key_y, &header, hex::encode(title_id)).await?;
-        debug!("Uses 9.6 NCCH Seed crypto with KeyY: {key_y:032X}");
+        match get_new_key(ncch_key_y, &header, hex::encode(title_id), opts).await {
+            Ok(seeded) => {
+                key_y = seeded;
+                debug!("Uses 9.6 NCCH Seed crypto with KeyY: {key_y:032X}");
+            }
+            Err(err) if opts.allow_missing_seed => {
+                warn!(
+                    "{} (content {}): no seed for title {} ({err:#}); the NCCH is copied through still encrypted",
+                    cia.path.display(),
+                    cia.cidx,
+                    hex::encode_upper(title_id)
+                );
+                seed_missing = true;
+                encrypted = false;
+            }
+            Err(err) => return Err(err),
+        }
     }
 
-    // Preserve the crypto-method bit, set the NoCrypto flag (content is now decrypted)
-    tmp[NCCH_FLAGS_OFFSET + 7] =
-        tmp[NCCH_FLAGS_OFFSET + 7] & NCCH_FLAGS7_CRYPTO_METHOD | NCCH_FLAGS7_NOCRYPTO;
+    // Preserve the crypto-method bit, set the NoCrypto flag (content is now
+    // decrypted). A seedless NCCH keeps its flags, so the copy stays
+    // decryptable once the seed turns up.
+    if !seed_missing {
+        tmp[NCCH_FLAGS_OFFSET + 7] =
+            tmp[NCCH_FLAGS_OFFSET + 7] & NCCH_FLAGS7_CRYPTO_METHOD | NCCH_FLAGS7_NOCRYPTO;
+    }
 
     out.seek(SeekFrom::Start(out_base)).await?;
     let mut writer = BufWriter::new(out);
@@ -614,7 +767,7 @@ pub async fn parse_ncch(
 
     let mut counter: [u8; 16];
     if header.exhdrsize != 0 {
-        counter = get_ncch_aes_counter(&header, NcchSection::ExHeader);
+        counter = get_ncch_aes_counter(&header, NcchSection::ExHeader)?;
         write_to_file(
             &mut writer,
             cia,
@@ -638,7 +791,7 @@ pub async fn parse_ncch(
     }
 
     if header.exefssize != 0 {
-        counter = get_ncch_aes_counter(&header, NcchSection::ExeFS);
+        counter = get_ncch_aes_counter(&header, NcchSection::ExeFS)?;
         write_to_file(
             &mut writer,
             cia,
@@ -662,7 +815,7 @@ pub async fn parse_ncch(
     }
 
     if header.romfssize != 0 {
-        counter = get_ncch_aes_counter(&header, NcchSection::RomFS);
+        counter = get_ncch_aes_counter(&header, NcchSection::RomFS)?;
         write_to_file(
             &mut writer,
             cia,
@@ -687,13 +840,84 @@ pub async fn parse_ncch(
 
     writer.flush().await?;
 
+    if opts.check_romfs && header.romfssize != 0 && !seed_missing {
+        let out = writer.into_inner();
+        match romfs_superblock_matches(out, out_base, &header).await? {
+            Some(true) => info!(
+                "{} (content {}): RomFS superblock hash OK",
+                cia.path.display(),
+                cia.cidx
+            ),
+            Some(false) => warn!(
+                "{} (content {}): RomFS superblock hash mismatch, the RomFS did not decrypt correctly",
+                cia.path.display(),
+                cia.cidx
+            ),
+            None => debug!("  RomFS superblock has no hash region to check"),
+        }
+    }
+
     Ok(())
 }
 
+/// Re-read the decrypted RomFS superblock (the leading `romfshashsize`
+/// media units, holding the IVFC header and master hash) and compare its
+/// SHA-256 with the NCCH header's `romfshash`. `None` when the header
+/// declares no hash region. `out` must be open for reading; a superblock
+/// that cannot be read back is an error. Leaves the file position where it
+/// was.
+async fn romfs_superblock_matches(
+    out: &mut File,
+    out_base: u64,
+    header: &NcchHeader,
+) -> anyhow::Result<Option<bool>> {
+    if header.romfshashsize == 0 {
+        return Ok(None);
+    }
+    let region = header.romfshashsize.min(header.romfssize) as usize * CTR_MEDIA_UNIT_SIZE as usize;
+    let start = out_base + header.romfsoffset as u64 * CTR_MEDIA_UNIT_SIZE as u64;
+
+    let restore = out.stream_position().await?;
+    out.seek(SeekFrom::Start(start)).await?;
+    let mut superblock = vec![0u8; region];
+    out.read_exact(&mut superblock)
+        .await
+        .context("reading back the decrypted RomFS superblock")?;
+    out.seek(SeekFrom::Start(restore)).await?;
+
+    let digest: [u8; 32] = Sha256::digest(&superblock).into();
+    Ok(Some(
+        header.romfshashsize <= header.romfssize && digest == header.romfshash,
+    ))
+}
+
+/// The used entries of an NCSD partition table, as (partition index, byte
+/// offset, byte size) in table order. Entries are little-endian media unit
+/// offset and size pairs; an all-zero entry is an unused slot.
+fn ncsd_partitions(table: &[u8]) -> Vec<(usize, u64, u64)> {
+    table
+        .chunks_exact(NCSD_PARTITION_ENTRY_SIZE)
+        .take(NCSD_PARTITION_COUNT)
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            let offset_mu = LittleEndian::read_u32(&entry[0..4]) as u64;
+            let size_mu = LittleEndian::read_u32(&entry[4..8]) as u64;
+            (offset_mu != 0 || size_mu != 0).then(|| {
+                (
+                    i,
+                    offset_mu * CTR_MEDIA_UNIT_SIZE as u64,
+                    size_mu * CTR_MEDIA_UNIT_SIZE as u64,
+                )
+            })
+        })
+        .collect()
+}
+
 pub async fn parse_and_decrypt_ncsd(
     input: &Path,
     out: &mut File,
     partition: Option<u8>,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> anyhow::Result<()> {
@@ -722,28 +946,19 @@ pub async fn parse_and_decrypt_ncsd(
     let mut table_buf = [0u8; NCSD_PARTITION_COUNT * NCSD_PARTITION_ENTRY_SIZE];
     rom_file.read_exact(&mut table_buf).await?;
 
-    for (i, partition_name) in CTR_NCSD_PARTITIONS.iter().enumerate() {
+    for (i, partition_offset, partition_size) in ncsd_partitions(&table_buf) {
         if cancel.is_cancelled() {
             return Err(NintendoCTRError::Cancelled.into());
         }
-        let entry_offset = i * NCSD_PARTITION_ENTRY_SIZE;
-        let offset_mu = u32::from_le_bytes(table_buf[entry_offset..entry_offset + 4].try_into()?);
-        let size_mu = u32::from_le_bytes(table_buf[entry_offset + 4..entry_offset + 8].try_into()?);
-
-        if offset_mu == 0 && size_mu == 0 {
-            continue;
-        }
-
         if let Some(target) = partition
             && i as u8 != target
         {
             continue;
         }
 
-        let partition_offset = offset_mu as u64 * CTR_MEDIA_UNIT_SIZE as u64;
-
         debug!(
-            "  Partition {i} ({partition_name}) at offset 0x{partition_offset:X}, size {size_mu} MU",
+            "  Partition {i} ({}) at offset 0x{partition_offset:X}, size 0x{partition_size:X}",
+            CTR_NCSD_PARTITIONS[i]
         );
 
         let mut reader = CiaReader::new(
@@ -765,6 +980,7 @@ pub async fn parse_and_decrypt_ncsd(
             partition_offset,
             title_id,
             None,
+            opts,
             progress,
             cancel,
         )
@@ -777,6 +993,7 @@ pub async fn parse_and_decrypt_ncsd(
 pub async fn parse_and_decrypt_ncch(
     input: &Path,
     out: &mut File,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> anyhow::Result<()> {
@@ -796,7 +1013,18 @@ pub async fn parse_and_decrypt_ncch(
         false,
     );
 
-    parse_ncch(&mut reader, out, 0, 0, [0u8; 8], None, progress, cancel).await?;
+    parse_ncch(
+        &mut reader,
+        out,
+        0,
+        0,
+        [0u8; 8],
+        None,
+        opts,
+        progress,
+        cancel,
+    )
+    .await?;
 
     Ok(())
 }
@@ -808,6 +1036,85 @@ pub async fn parse_and_decrypt_ncch(
 pub async fn parse_and_decrypt_cia(
     input: &Path,
     out: &mut File,
+    opts: &DecryptOptions,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> anyhow::Result<Vec<[u8; 32]>> {
+    decrypt_cia_contents(input, ContentSink::Stream(out), opts, progress, cancel).await
+}
+
+/// Decrypts every NCCH content of a CIA into its own file under `dir`, named
+/// by [`ncch_content_file_name`], in TMD-record order. Existing files are
+/// replaced only when `overwrite` is set. Every content is decrypted to a
+/// scratch file first, and only once all of them are done are they renamed
+/// into place and pushed onto `written`, so on a later rename error the
+/// caller can remove the ones already published.
+pub async fn split_and_decrypt_cia(
+    input: &Path,
+    dir: &Path,
+    overwrite: bool,
+    written: &mut Vec<PathBuf>,
+    opts: &DecryptOptions,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> anyhow::Result<()> {
+    let stem = file_stem_or(input, "content");
+    let mut staged = Vec::new();
+    let sink = ContentSink::Split {
+        dir,
+        stem,
+        overwrite,
+        staged: &mut staged,
+    };
+    decrypt_cia_contents(input, sink, opts, progress, cancel).await?;
+    for (scratch, path) in staged {
+        publish_temp(scratch, &path, overwrite)
+            .with_context(|| format!("creating {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(())
+}
+
+/// File name for one decrypted CIA content: `<stem>.<index>.<content id>.ncch`,
+/// the index in four decimal digits and the id in eight hex digits. The
+/// stem is kept as raw `OsStr` bytes, like [`file_stem_or`] returns it.
+pub fn ncch_content_file_name(stem: &OsStr, cidx: u16, cid: u32) -> OsString {
+    let mut name = stem.to_os_string();
+    name.push(format!(".{cidx:04}.{cid:08x}.ncch"));
+    name
+}
+
+/// The content index in a name written by [`ncch_content_file_name`], or
+/// `None` when the name does not have that shape.
+pub fn ncch_content_index_from_name(name: &str) -> Option<u16> {
+    let rest = name.strip_suffix(".ncch")?;
+    let (rest, cid) = rest.rsplit_once('.')?;
+    let (_, cidx) = rest.rsplit_once('.')?;
+    let is_hex_id = cid.len() == 8 && cid.bytes().all(|b| b.is_ascii_hexdigit());
+    if !is_hex_id || cidx.len() != 4 || !cidx.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    cidx.parse().ok()
+}
+
+/// Where [`decrypt_cia_contents`] puts each decrypted content.
+enum ContentSink<'a> {
+    /// All contents back to back in one file, from its current position.
+    Stream(&'a mut File),
+    /// One file per content under `dir`, each decrypted to a scratch file
+    /// that is pushed onto `staged` with its final path.
+    Split {
+        dir: &'a Path,
+        stem: OsString,
+        overwrite: bool,
+        staged: &'a mut Vec<(ScratchPath, PathBuf)>,
+    },
+}
+
+async fn decrypt_cia_contents(
+    input: &Path,
+    mut sink: ContentSink<'_>,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> anyhow::Result<Vec<[u8; 32]>> {
@@ -843,33 +1150,14 @@ pub async fn parse_and_decrypt_cia(
         return Err(anyhow::anyhow!("unsupported CIA file"));
     }
 
-    rom_file
-        .seek(SeekFrom::Start(
-            tikoff + TICKET_SIG_BODY_OFFSET + TICKET_COMMON_KEY_IDX_OFFSET,
-        ))
-        .await?;
-    let mut cmnkeyidx: u8 = 0;
-    rom_file
-        .read_exact(std::slice::from_mut(&mut cmnkeyidx))
-        .await?;
-
-    cbc_decrypt(&CTR_COMMON_KEYS_HEX[cmnkeyidx as usize], &tid, &mut enckey)?;
-    let title_key = enckey;
-
     rom_file
         .seek(SeekFrom::Start(tmdoff + TMD_CONTENT_COUNT_OFFSET))
         .await?;
     let mut content_count: [u8; 2] = [0; 2];
     rom_file.read_exact(&mut content_count).await?;
 
-    let mut hashes: Vec<[u8; 32]> =
-        Vec::with_capacity(BigEndian::read_u16(&content_count) as usize);
-    let mut next_content_offs = 0;
-    let mut out_pos = out.stream_position().await?;
+    let mut records = Vec::with_capacity(BigEndian::read_u16(&content_count) as usize);
     for i in 0..BigEndian::read_u16(&content_count) {
-        if cancel.is_cancelled() {
-            return Err(NintendoCTRError::Cancelled.into());
-        }
         rom_file
             .seek(SeekFrom::Start(
                 tmdoff + TMD_CONTENT_RECORDS_OFFSET + (TMD_CONTENT_RECORD_SIZE * i as u64),
@@ -877,8 +1165,55 @@ pub async fn parse_and_decrypt_cia(
             .await?;
         let mut record_buf = vec![0u8; TMD_CONTENT_RECORD_SIZE as usize];
         rom_file.read_exact(&mut record_buf).await?;
-        let record = ContentChunkRecord::read_be(&mut Cursor::new(&record_buf))?;
+        records.push(ContentChunkRecord::read_be(&mut Cursor::new(&record_buf))?);
+    }
+
+    // Contents whose TMD record has the encrypted bit clear are stored as
+    // plain NCCH already; only the others need the title key.
+    let encrypted = records
+        .iter()
+        .filter(|record| record.content_type.is_encrypted())
+        .count();
+    let title_key = if encrypted == 0 {
+        info!(
+            "{} appears pre-decrypted: no content is flagged encrypted, skipping title key decryption",
+            input.display()
+        );
+        [0; 16]
+    } else {
+        if encrypted < records.len() {
+            info!(
+                "{} of {} contents are flagged unencrypted and are copied without title key decryption",
+                records.len() - encrypted,
+                records.len()
+            );
+        }
+        rom_file
+            .seek(SeekFrom::Start(
+                tikoff + TICKET_SIG_BODY_OFFSET + TICKET_COMMON_KEY_IDX_OFFSET,
+            ))
+            .await?;
+        let mut cmnkeyidx: u8 = 0;
+        rom_file
+            .read_exact(std::slice::from_mut(&mut cmnkeyidx))
+            .await?;
+
+        cbc_decrypt(common_key(cmnkeyidx)?, &tid, &mut enckey)?;
+        enckey
+    };
 
+    let mut hashes: Vec<[u8; 32]> = Vec::with_capacity(records.len());
+    let mut next_content_offs = 0;
+    let mut content_bytes = 0;
+    let started = Instant::now();
+    let mut out_pos = match &mut sink {
+        ContentSink::Stream(out) => out.stream_position().await?,
+        ContentSink::Split { .. } => 0,
+    };
+    for record in records {
+        if cancel.is_cancelled() {
+            return Err(NintendoCTRError::Cancelled.into());
+        }
         let content = CiaContent {
             cid: record.content_id,
             cidx: record.content_index,
@@ -886,7 +1221,7 @@ pub async fn parse_and_decrypt_cia(
             csize: record.content_size,
         };
 
-        let cenc = (content.ctype & 1) != 0;
+        let cenc = record.content_type.is_encrypted();
 
         rom_file
             .seek(SeekFrom::Start(contentoffs + next_content_offs))
@@ -902,56 +1237,852 @@ pub async fn parse_and_decrypt_cia(
             magic = probe_buf[256..260].try_into()?;
         }
 
-        match std::str::from_utf8(&magic) {
-            Ok(utf8) => {
-                if utf8 == NCCH_MAGIC {
-                    rom_file
-                        .seek(SeekFrom::Start(contentoffs + next_content_offs))
-                        .await?;
-                    let mut cia_handle = CiaReader::new(
-                        rom_file.try_clone().await?,
-                        cenc,
-                        input.to_path_buf(),
-                        title_key,
-                        content.cid,
-                        content.cidx,
-                        contentoffs + next_content_offs,
-                        false,
-                        false,
-                    );
-                    next_content_offs += align_64(content.csize);
-
-                    let mut hasher = Sha256::new();
-                    parse_ncch(
-                        &mut cia_handle,
-                        out,
-                        out_pos,
-                        0,
-                        tid[0..8].try_into()?,
-                        Some(&mut hasher),
-                        progress,
-                        cancel,
-                    )
-                    .await?;
-                    out_pos = out.stream_position().await?;
-                    hashes.push(hasher.finalize().into());
-                } else {
-                    return Err(anyhow!("CIA file cannot be parsed"));
+        let ncsd_header = if magic == underlying_magic::NCSD {
+            debug!("  Content {} holds an NCSD image", content.cidx);
+            Some(probe_buf)
+        } else if magic == NCCH_MAGIC.as_bytes() {
+            None
+        } else {
+            return Err(anyhow!("CIA file cannot be parsed"));
+        };
+        let source = ContentSource {
+            rom_file: &rom_file,
+            input,
+            encrypted: cenc,
+            title_key,
+            content: &content,
+            offset: contentoffs + next_content_offs,
+            title_id: tid[0..8].try_into()?,
+            opts,
+        };
+        next_content_offs += align_64(content.csize);
+        content_bytes += content.csize;
+
+        let mut hasher = Sha256::new();
+        match &mut sink {
+            ContentSink::Stream(out) => {
+                decrypt_content(
+                    &source,
+                    ncsd_header.as_ref(),
+                    out,
+                    out_pos,
+                    &mut hasher,
+                    progress,
+                    cancel,
+                )
+                .await?;
+                out_pos = out.stream_position().await?;
+            }
+            ContentSink::Split {
+                dir,
+                stem,
+                overwrite,
+                staged,
+            } => {
+                let path = dir.join(ncch_content_file_name(stem, content.cidx, content.cid));
+                if !*overwrite && path.exists() {
+                    return Err(std::io::Error::from(std::io::ErrorKind::AlreadyExists))
+                        .with_context(|| format!("creating {}", path.display()));
                 }
+                let scratch = scratch_output_path(&path)
+                    .with_context(|| format!("creating {}", path.display()))?;
+                let mut file = tokio::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(&scratch)
+                    .await
+                    .with_context(|| format!("creating {}", path.display()))?;
+                staged.push((scratch, path));
+                decrypt_content(
+                    &source,
+                    ncsd_header.as_ref(),
+                    &mut file,
+                    0,
+                    &mut hasher,
+                    progress,
+                    cancel,
+                )
+                .await?;
+                file.flush().await?;
             }
-            Err(e) => return Err(anyhow!(e)),
         }
+        hashes.push(hasher.finalize().into());
     }
 
+    if let Some(rate) = throughput_mb_s(content_bytes, started.elapsed()) {
+        info!(
+            "Decrypted {} contents ({:.2} MB) at {rate:.1} MB/s",
+            hashes.len(),
+            content_bytes as f64 / BYTES_PER_MB
+        );
+    }
     Ok(hashes)
 }
 
+/// One CIA content as [`decrypt_content`] reads it.
+struct ContentSource<'a> {
+    rom_file: &'a File,
+    input: &'a Path,
+    /// Whether the content sits under the title key's CBC layer.
+    encrypted: bool,
+    title_key: [u8; 16],
+    content: &'a CiaContent,
+    /// Absolute offset of the content in the CIA.
+    offset: u64,
+    title_id: [u8; 8],
+    opts: &'a DecryptOptions,
+}
+
+/// Decrypts one CIA content into `out` at `out_base`: a single NCCH, or,
+/// when `ncsd_header` holds the content's first 512 bytes with an NCSD
+/// magic, a whole NCSD image. For the latter the NCSD header and any bytes
+/// between and after the partitions are copied through the title key layer
+/// as they are, and each partition's NCCH is decrypted from its offset
+/// inside the content.
+async fn decrypt_content(
+    source: &ContentSource<'_>,
+    ncsd_header: Option<&[u8; 512]>,
+    out: &mut File,
+    out_base: u64,
+    hasher: &mut Sha256,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> anyhow::Result<()> {
+    let content = source.content;
+    let Some(ncsd_header) = ncsd_header else {
+        let mut reader = CiaReader::new(
+            source.rom_file.try_clone().await?,
+            source.encrypted,
+            source.input.to_path_buf(),
+            source.title_key,
+            content.cid,
+            content.cidx,
+            source.offset,
+            false,
+            false,
+        );
+        return parse_ncch(
+            &mut reader,
+            out,
+            out_base,
+            0,
+            source.title_id,
+            Some(hasher),
+            source.opts,
+            progress,
+            cancel,
+        )
+        .await;
+    };
+
+    let table_end = NCSD_PARTITION_TABLE_OFFSET + NCSD_PARTITION_COUNT * NCSD_PARTITION_ENTRY_SIZE;
+    let mut partitions = ncsd_partitions(&ncsd_header[NCSD_PARTITION_TABLE_OFFSET..table_end]);
+    partitions.sort_by_key(|&(_, offset, _)| offset);
+
+    // Reads the bytes outside the partitions; cidx is the content's, so a
+    // read from offset 0 starts from the content IV.
+    let mut plain = CiaReader::new(
+        source.rom_file.try_clone().await?,
+        source.encrypted,
+        source.input.to_path_buf(),
+        source.title_key,
+        content.cid,
+        content.cidx,
+        source.offset,
+        false,
+        true,
+    );
+    let mut pos = 0u64;
+    for (index, offset, size) in partitions {
+        if cancel.is_cancelled() {
+            return Err(NintendoCTRError::Cancelled.into());
+        }
+        if offset < pos || offset + size > content.csize {
+            return Err(anyhow!(
+                "content {:08x}: NCSD partition {index} at 0x{offset:X} (0x{size:X} bytes) overlaps another or runs past the content's 0x{:X} bytes",
+                content.cid,
+                content.csize
+            ));
+        }
+        copy_ncsd_bytes(&mut plain, out, out_base, pos..offset, hasher, progress).await?;
+
+        debug!(
+            "  Partition {index} ({}) at offset 0x{offset:X}",
+            CTR_NCSD_PARTITIONS[index]
+        );
+        let mut reader = CiaReader::new(
+            source.rom_file.try_clone().await?,
+            source.encrypted,
+            source.input.to_path_buf(),
+            source.title_key,
+            content.cid,
+            index as u16,
+            source.offset,
+            false,
+            true,
+        );
+        parse_ncch(
+            &mut reader,
+            out,
+            out_base + offset,
+            offset,
+            source.title_id,
+            Some(&mut *hasher),
+            source.opts,
+            progress,
+            cancel,
+        )
+        .await?;
+        pos = out.stream_position().await? - out_base;
+    }
+    if pos > content.csize {
+        return Err(anyhow!(
+            "content {:08x}: NCSD partitions run past the content's 0x{:X} bytes",
+            content.cid,
+            content.csize
+        ));
+    }
+    copy_ncsd_bytes(
+        &mut plain,
+        out,
+        out_base,
+        pos..content.csize,
+        hasher,
+        progress,
+    )
+    .await
+}
+
+/// Copies `range` of an NCSD content, relative to the content start,
+/// through the reader's title key layer to the same range after
+/// `out_base`.
+async fn copy_ncsd_bytes(
+    reader: &mut CiaReader,
+    out: &mut File,
+    out_base: u64,
+    range: std::ops::Range<u64>,
+    hasher: &mut Sha256,
+    progress: &dyn ProgressReporter,
+) -> anyhow::Result<()> {
+    if range.is_empty() {
+        return Ok(());
+    }
+    reader.seek(range.start).await?;
+    out.seek(SeekFrom::Start(out_base + range.start)).await?;
+    let mut remaining = range.end - range.start;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    while remaining > 0 {
+        let chunk = &mut buf[..remaining.min(CHUNK_SIZE as u64) as usize];
+        reader
+            .read(chunk)
+            .await
+            .context("reading NCSD bytes outside the partitions")?;
+        hasher.update(&*chunk);
+        out.write_all(chunk).await?;
+        progress.inc(chunk.len() as u64);
+        remaining -= chunk.len() as u64;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::util::NoProgress;
+    use std::time::Duration;
     use tokio::io::AsyncReadExt;
 
+    #[test]
+    fn content_index_reads_back_from_split_file_names() {
+        let name = ncch_content_file_name(OsStr::new("Game (USA)"), 2, 0x0000_000a);
+        assert_eq!(
+            ncch_content_index_from_name(name.to_str().unwrap()),
+            Some(2)
+        );
+        assert_eq!(ncch_content_index_from_name("game.cxi"), None);
+        assert_eq!(ncch_content_index_from_name("game.ncch"), None);
+        assert_eq!(ncch_content_index_from_name("game.12.0000000a.ncch"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn split_file_names_keep_a_non_utf8_stem() {
+        use std::os::unix::ffi::OsStrExt;
+        let input = Path::new(OsStr::from_bytes(b"g\xffme.cia"));
+        let name = ncch_content_file_name(&file_stem_or(input, "content"), 0, 1);
+        assert_eq!(name.as_bytes(), b"g\xffme.0000.00000001.ncch");
+    }
+
+    fn seed_round_failure(transient: bool) -> SeedFetchError {
+        SeedFetchError {
+            error: anyhow!("HTTP 503 for JP"),
+            transient,
+        }
+    }
+
+    #[tokio::test]
+    async fn seed_rounds_retry_transient_failures_until_one_succeeds() {
+        let calls = std::cell::Cell::new(0u32);
+        let key = retry_seed_rounds(
+            "0004000000155400",
+            2,
+            |_| Duration::ZERO,
+            || {
+                calls.set(calls.get() + 1);
+                let call = calls.get();
+                async move {
+                    if call < 3 {
+                        Err(seed_round_failure(true))
+                    } else {
+                        Ok([0x11u8; 16])
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(key, [0x11; 16]);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn seed_rounds_stop_after_the_retry_budget_or_a_permanent_failure() {
+        for (retries, transient, expected_calls) in [(2, true, 3), (0, true, 1), (2, false, 1)] {
+            let calls = std::cell::Cell::new(0u32);
+            let result: anyhow::Result<()> = retry_seed_rounds(
+                "0004000000155400",
+                retries,
+                |_| Duration::ZERO,
+                || {
+                    calls.set(calls.get() + 1);
+                    async move { Err(seed_round_failure(transient)) }
+                },
+            )
+            .await;
+            assert!(result.is_err());
+            assert_eq!(
+                calls.get(),
+                expected_calls,
+                "retries={retries} transient={transient}"
+            );
+        }
+    }
+
+    #[test]
+    fn seed_retry_delay_doubles_up_to_a_cap() {
+        assert_eq!(seed_retry_delay(0), Duration::from_millis(500));
+        assert_eq!(seed_retry_delay(1), Duration::from_millis(1000));
+        assert_eq!(seed_retry_delay(9), Duration::from_millis(8000));
+    }
+
+    fn exefs_entry(name: &[u8], offset: u32, size: u32) -> [u8; EXEFS_ENTRY_SIZE] {
+        let mut entry = [0u8; EXEFS_ENTRY_SIZE];
+        entry[..name.len()].copy_from_slice(name);
+        entry[8..12].copy_from_slice(&offset.to_le_bytes());
+        entry[12..16].copy_from_slice(&size.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn fixed_key_selects_zero_or_system_key_and_rejects_out_of_range() {
+        assert_eq!(fixed_key(0).unwrap(), None);
+        assert_eq!(fixed_key(1).unwrap(), Some([0u8; 16]));
+        assert_eq!(
+            fixed_key(2).unwrap(),
+            Some(u128::to_be_bytes(CTR_KEYS_1[1]))
+        );
+        assert!(matches!(
+            fixed_key(3),
+            Err(NintendoCTRError::UnknownFixedKey(3))
+        ));
+        assert_eq!(fixed_key_name(0), None);
+        assert_eq!(fixed_key_name(2), Some("system fixed key"));
+        assert_eq!(fixed_key_name(3), None);
+    }
+
+    #[tokio::test]
+    async fn unknown_common_key_index_is_an_error_not_a_panic() {
+        use crate::nintendo::ctr::test_fixtures::{minimal_cia, write_cia_file};
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("bad.cia");
+        let mut cia = minimal_cia();
+        cia.ticket.ticket_data.common_key_index = 7;
+        cia.tmd.content_chunk_records[0]
+            .content_type
+            .set_encrypted(true);
+        write_cia_file(&input, &cia);
+
+        let mut out = File::create(dir.path().join("out.bin")).await.unwrap();
+        let err = parse_and_decrypt_cia(
+            &input,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NintendoCTRError>(),
+            Some(NintendoCTRError::UnknownCommonKeyIndex(7))
+        ));
+        assert_eq!(err.to_string(), "unknown common key index 7");
+    }
+
+    /// A NoCrypto NCCH laid out like a real one: a 0x800-byte ExHeader right
+    /// after the 512-byte header, a gap, then one media unit each of ExeFS and
+    /// RomFS. Every region carries its own byte pattern so a slipped offset
+    /// shows up as a mismatch.
+    fn crafted_ncch() -> Vec<u8> {
+        use crate::nintendo::ctr::test_fixtures::{SYNTH_CIA_TITLE_ID, make_ncch_header_bytes};
+
+        let mut bytes = make_ncch_header_bytes(SYNTH_CIA_TITLE_ID);
+        bytes[0x104..0x108].copy_from_slice(&8u32.to_le_bytes());
+        bytes[0x180..0x184].copy_from_slice(&0x400u32.to_le_bytes());
+        bytes[0x1A0..0x1A4].copy_from_slice(&6u32.to_le_bytes());
+        bytes[0x1A4..0x1A8].copy_from_slice(&1u32.to_le_bytes());
+        bytes[0x1B0..0x1B4].copy_from_slice(&7u32.to_le_bytes());
+        bytes[0x1B4..0x1B8].copy_from_slice(&1u32.to_le_bytes());
+        for (len, fill) in [(0x800, 0xE1u8), (0x200, 0x6A), (0x200, 0xEF), (0x200, 0x0F)] {
+            bytes.extend((0..len).map(|i| fill ^ (i as u8)));
+        }
+        assert_eq!(bytes.len(), 8 * CTR_MEDIA_UNIT_SIZE as usize);
+        bytes
+    }
+
+    fn write_single_content_cia(dir: &Path, content: Vec<u8>) -> std::path::PathBuf {
+        use crate::nintendo::ctr::test_fixtures::{
+            SYNTH_CIA_TITLE_ID, cia_with_content, write_cia_file,
+        };
+
+        let input = dir.join("crafted.cia");
+        write_cia_file(&input, &cia_with_content(SYNTH_CIA_TITLE_ID, content, None));
+        input
+    }
+
+    #[tokio::test]
+    async fn parse_and_decrypt_cia_copies_a_plain_ncch_byte_for_byte() {
+        let dir = tempfile::tempdir().unwrap();
+        let ncch = crafted_ncch();
+        let input = write_single_content_cia(dir.path(), ncch.clone());
+
+        // Start past a prefix, as the CIA rebuild does after its preamble.
+        let out_path = dir.path().join("out.bin");
+        let mut out = File::create(&out_path).await.unwrap();
+        out.write_all(b"PREAMBLE").await.unwrap();
+        let hashes = parse_and_decrypt_cia(
+            &input,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        out.flush().await.unwrap();
+        drop(out);
+
+        let written = std::fs::read(&out_path).unwrap();
+        assert_eq!(&written[..8], b"PREAMBLE");
+        assert_eq!(&written[8..], ncch.as_slice());
+        assert_eq!(hashes, vec![<[u8; 32]>::from(Sha256::digest(&ncch))]);
+    }
+
+    #[tokio::test]
+    async fn pre_decrypted_cia_never_touches_the_title_key() {
+        use crate::nintendo::ctr::test_fixtures::{
+            SYNTH_CIA_TITLE_ID, cia_with_content, write_cia_file,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let ncch = crafted_ncch();
+        let input = dir.path().join("plain.cia");
+        let mut cia = cia_with_content(SYNTH_CIA_TITLE_ID, ncch.clone(), None);
+        // A key index no common key exists for: reaching the title key
+        // path would fail the decrypt.
+        cia.ticket.ticket_data.common_key_index = 7;
+        write_cia_file(&input, &cia);
+
+        let out_path = dir.path().join("out.bin");
+        let mut out = File::create(&out_path).await.unwrap();
+        parse_and_decrypt_cia(
+            &input,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        drop(out);
+
+        assert_eq!(std::fs::read(&out_path).unwrap(), ncch);
+    }
+
+    #[tokio::test]
+    async fn mixed_cia_decrypts_only_the_contents_flagged_encrypted() {
+        use crate::nintendo::ctr::test_fixtures::{
+            SYNTH_CIA_TITLE_ID, cia_with_content, make_ncch_header_bytes, make_tmd,
+            serialized_size, write_cia_file,
+        };
+        use aes::cipher::BlockModeEncrypt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let plain = crafted_ncch();
+        let mut second = make_ncch_header_bytes(SYNTH_CIA_TITLE_ID);
+        second[0x40] = 0x5A;
+
+        let mut cia = cia_with_content(SYNTH_CIA_TITLE_ID, plain.clone(), None);
+        let mut title_key: [u8; 16] = cia.ticket.ticket_data.title_key[..].try_into().unwrap();
+        let mut tid_iv = [0u8; 16];
+        tid_iv[..8].copy_from_slice(&SYNTH_CIA_TITLE_ID.to_be_bytes());
+        cbc_decrypt(
+            common_key(cia.ticket.ticket_data.common_key_index).unwrap(),
+            &tid_iv,
+            &mut title_key,
+        )
+        .unwrap();
+        let mut encrypted = second.clone();
+        let mut cipher =
+            cbc::Encryptor::<aes::Aes128>::new_from_slices(&title_key, &gen_iv(1)).unwrap();
+        for block in encrypted.chunks_exact_mut(16) {
+            cipher.encrypt_block(<&mut aes::cipher::array::Array<_, _>>::try_from(block).unwrap());
+        }
+
+        cia.tmd = make_tmd(
+            SYNTH_CIA_TITLE_ID,
+            vec![
+                (0, 0, plain.clone(), Sha256::digest(&plain).into()),
+                (1, 1, encrypted.clone(), Sha256::digest(&encrypted).into()),
+            ],
+        );
+        cia.tmd.content_chunk_records[1]
+            .content_type
+            .set_encrypted(true);
+        cia.content_data = [plain.as_slice(), encrypted.as_slice()].concat();
+        cia.header.content_size = cia.content_data.len() as u64;
+        cia.header.tmd_size = serialized_size(&cia.tmd);
+        let input = dir.path().join("mixed.cia");
+        write_cia_file(&input, &cia);
+
+        let out_path = dir.path().join("out.bin");
+        let mut out = File::create(&out_path).await.unwrap();
+        let hashes = parse_and_decrypt_cia(
+            &input,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        drop(out);
+
+        let expected = [plain.as_slice(), second.as_slice()].concat();
+        assert_eq!(std::fs::read(&out_path).unwrap(), expected);
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[1], <[u8; 32]>::from(Sha256::digest(&second)));
+    }
+
+    #[tokio::test]
+    async fn cia_content_holding_an_ncsd_decrypts_each_partition() {
+        use crate::nintendo::ctr::test_fixtures::{
+            SYNTH_CIA_TITLE_ID, cia_with_content, make_ncch_header_bytes, make_tmd,
+            serialized_size, write_cia_file,
+        };
+        use aes::cipher::BlockModeEncrypt;
+
+        // NCSD header, then partition 0 at media unit 0x20 and partition 1
+        // right after it, then a trailing media unit outside both. The
+        // bytes outside the partitions carry a pattern so a slipped copy
+        // shows up.
+        let mut ncsd: Vec<u8> = (0..0x4000).map(|i| (i as u8) ^ 0x3C).collect();
+        ncsd[0x100..0x104].copy_from_slice(b"NCSD");
+        ncsd[0x120..0x160].fill(0);
+        ncsd[0x120..0x124].copy_from_slice(&0x20u32.to_le_bytes());
+        ncsd[0x124..0x128].copy_from_slice(&8u32.to_le_bytes());
+        ncsd[0x128..0x12C].copy_from_slice(&0x28u32.to_le_bytes());
+        ncsd[0x12C..0x130].copy_from_slice(&1u32.to_le_bytes());
+        let mut second = make_ncch_header_bytes(SYNTH_CIA_TITLE_ID);
+        second[0x40] = 0x5A;
+        ncsd.extend(crafted_ncch());
+        ncsd.extend(&second);
+        ncsd.extend((0..0x200).map(|i| (i as u8) ^ 0xC3));
+
+        for encrypted in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut cia = cia_with_content(SYNTH_CIA_TITLE_ID, ncsd.clone(), None);
+            let mut stored = ncsd.clone();
+            if encrypted {
+                let mut title_key: [u8; 16] =
+                    cia.ticket.ticket_data.title_key[..].try_into().unwrap();
+                let mut tid_iv = [0u8; 16];
+                tid_iv[..8].copy_from_slice(&SYNTH_CIA_TITLE_ID.to_be_bytes());
+                cbc_decrypt(
+                    common_key(cia.ticket.ticket_data.common_key_index).unwrap(),
+                    &tid_iv,
+                    &mut title_key,
+                )
+                .unwrap();
+                let mut cipher =
+                    cbc::Encryptor::<aes::Aes128>::new_from_slices(&title_key, &gen_iv(0)).unwrap();
+                for block in stored.chunks_exact_mut(16) {
+                    cipher.encrypt_block(
+                        <&mut aes::cipher::array::Array<_, _>>::try_from(block).unwrap(),
+                    );
+                }
+                cia.tmd = make_tmd(
+                    SYNTH_CIA_TITLE_ID,
+                    vec![(0, 0, stored.clone(), Sha256::digest(&stored).into())],
+                );
+                cia.tmd.content_chunk_records[0]
+                    .content_type
+                    .set_encrypted(true);
+                cia.content_data = stored;
+                cia.header.tmd_size = serialized_size(&cia.tmd);
+            }
+            let input = dir.path().join("ncsd.cia");
+            write_cia_file(&input, &cia);
+
+            let out_path = dir.path().join("out.bin");
+            let mut out = File::create(&out_path).await.unwrap();
+            let hashes = parse_and_decrypt_cia(
+                &input,
+                &mut out,
+                &DecryptOptions::default(),
+                &NoProgress,
+                &CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            drop(out);
+
+            assert!(
+                std::fs::read(&out_path).unwrap() == ncsd,
+                "encrypted: {encrypted}"
+            );
+            assert_eq!(hashes, vec![<[u8; 32]>::from(Sha256::digest(&ncsd))]);
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_and_decrypt_cia_clears_content_index_byte_after_header() {
+        let dir = tempfile::tempdir().unwrap();
+        // No ExHeader: the ExeFS starts at media unit 2, so the bytes right
+        // after the 512-byte header are copied as a gap and byte 1 of it is
+        // the content-index field the decrypt zeroes.
+        let mut ncch = crafted_ncch();
+        ncch[0x180..0x184].fill(0);
+        ncch[0x1A0..0x1A4].copy_from_slice(&2u32.to_le_bytes());
+        ncch.truncate(0x600);
+        ncch[0x1B0..0x1B8].fill(0);
+        assert_ne!(ncch[0x201], 0);
+        let input = write_single_content_cia(dir.path(), ncch.clone());
+
+        let out_path = dir.path().join("out.bin");
+        let mut out = File::create(&out_path).await.unwrap();
+        let hashes = parse_and_decrypt_cia(
+            &input,
+            &mut out,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        drop(out);
+
+        let mut expected = ncch;
+        expected[0x201] = 0;
+        assert_eq!(std::fs::read(&out_path).unwrap(), expected);
+        assert_eq!(hashes, vec![<[u8; 32]>::from(Sha256::digest(&expected))]);
+    }
+
+    fn romfs_header(superblock: &[u8]) -> NcchHeader {
+        let mut bytes = crate::nintendo::ctr::test_fixtures::make_ncch_header_bytes(0);
+        bytes[0x1B0..0x1B4].copy_from_slice(&1u32.to_le_bytes());
+        bytes[0x1B4..0x1B8].copy_from_slice(&2u32.to_le_bytes());
+        bytes[0x1B8..0x1BC].copy_from_slice(&1u32.to_le_bytes());
+        bytes[0x1E0..0x200].copy_from_slice(&Sha256::digest(superblock));
+        NcchHeader::read(&mut Cursor::new(&bytes)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn romfs_superblock_check_reports_match_and_mismatch() {
+        let superblock = vec![0x5Au8; CTR_MEDIA_UNIT_SIZE as usize];
+        let header = romfs_header(&superblock);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("out.cxi");
+        let mut image = vec![0u8; 3 * CTR_MEDIA_UNIT_SIZE as usize];
+        image[0x200..0x400].copy_from_slice(&superblock);
+        std::fs::write(&path, &image).unwrap();
+
+        let mut out = File::options()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .await
+            .unwrap();
+        out.seek(SeekFrom::Start(7)).await.unwrap();
+        assert_eq!(
+            romfs_superblock_matches(&mut out, 0, &header)
+                .await
+                .unwrap(),
+            Some(true)
+        );
+        assert_eq!(out.stream_position().await.unwrap(), 7);
+
+        image[0x200] ^= 0xFF;
+        std::fs::write(&path, &image).unwrap();
+        let mut out = File::options()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .await
+            .unwrap();
+        assert_eq!(
+            romfs_superblock_matches(&mut out, 0, &header)
+                .await
+                .unwrap(),
+            Some(false)
+        );
+
+        // Truncated output: the superblock is not all there.
+        std::fs::write(&path, &image[..0x300]).unwrap();
+        let mut out = File::options()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .await
+            .unwrap();
+        assert!(
+            romfs_superblock_matches(&mut out, 0, &header)
+                .await
+                .is_err()
+        );
+
+        // A write-only handle cannot read the superblock back.
+        let mut out = File::options().write(true).open(&path).await.unwrap();
+        assert!(
+            romfs_superblock_matches(&mut out, 0, &header)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn splice_extra_crypto_skips_icon_and_banner() {
+        let mut decrypted = vec![0x11u8; EXEFS_HEADER_SIZE + 0x300];
+        decrypted[..EXEFS_HEADER_SIZE].fill(0);
+        for (i, entry) in [
+            exefs_entry(b".code", 0, 0x100),
+            exefs_entry(b"icon", 0x100, 0x100),
+            exefs_entry(b"banner", 0x200, 0x100),
+        ]
+        .iter()
+        .enumerate()
+        {
+            decrypted[i * EXEFS_ENTRY_SIZE..(i + 1) * EXEFS_ENTRY_SIZE].copy_from_slice(entry);
+        }
+        let extra = vec![0x22u8; decrypted.len()];
+
+        splice_extra_crypto_files(&mut decrypted, &extra).unwrap();
+
+        let code = EXEFS_HEADER_SIZE..EXEFS_HEADER_SIZE + 0x100;
+        assert!(decrypted[code].iter().all(|&b| b == 0x22));
+        assert!(
+            decrypted[EXEFS_HEADER_SIZE + 0x100..]
+                .iter()
+                .all(|&b| b == 0x11)
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_seed_copies_the_ncch_through_unchanged() {
+        use crate::nintendo::ctr::test_fixtures::{SYNTH_CIA_TITLE_ID, make_ncch_header_bytes};
+
+        let mut ncch = vec![0u8; 2 * CTR_MEDIA_UNIT_SIZE as usize];
+        ncch[..0x200].copy_from_slice(&make_ncch_header_bytes(SYNTH_CIA_TITLE_ID));
+        ncch[0x118..0x120].copy_from_slice(&SYNTH_CIA_TITLE_ID.to_le_bytes());
+        ncch[NCCH_FLAGS_OFFSET + 7] = NCCH_FLAGS7_SEED_CRYPTO;
+        ncch[0x1B0..0x1B4].copy_from_slice(&1u32.to_le_bytes());
+        ncch[0x1B4..0x1B8].copy_from_slice(&1u32.to_le_bytes());
+        for (i, b) in ncch[0x200..].iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(31);
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let in_path = tmp.path().join("seeded.cxi");
+        std::fs::write(&in_path, &ncch).unwrap();
+        let out_path = tmp.path().join("out.cxi");
+
+        // A seed that fails the seedcheck stands in for one that cannot be found.
+        let refuse = DecryptOptions {
+            seed: Some([0xEE; 16]),
+            ..DecryptOptions::default()
+        };
+        let mut out = File::create(&out_path).await.unwrap();
+        let refused = parse_and_decrypt_ncch(
+            &in_path,
+            &mut out,
+            &refuse,
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await;
+        let mut out = File::create(&out_path).await.unwrap();
+        let allowed = parse_and_decrypt_ncch(
+            &in_path,
+            &mut out,
+            &DecryptOptions {
+                allow_missing_seed: true,
+                ..refuse
+            },
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await;
+
+        assert!(refused.is_err());
+        allowed.unwrap();
+        out.flush().await.unwrap();
+        assert_eq!(std::fs::read(&out_path).unwrap(), ncch);
+    }
+
+    #[test]
+    fn splice_extra_crypto_survives_malformed_headers() {
+        // Out-of-range offset and size, then an empty entry followed by a
+        // garbage one that must never be reached.
+        let mut decrypted = vec![0u8; EXEFS_HEADER_SIZE + 0x40];
+        decrypted[..EXEFS_ENTRY_SIZE].copy_from_slice(&exefs_entry(b".code", 0x20, u32::MAX));
+        decrypted[EXEFS_ENTRY_SIZE..2 * EXEFS_ENTRY_SIZE].copy_from_slice(&exefs_entry(
+            b"logo",
+            u32::MAX,
+            0x10,
+        ));
+        decrypted[3 * EXEFS_ENTRY_SIZE..4 * EXEFS_ENTRY_SIZE]
+            .copy_from_slice(&exefs_entry(b"junk", 0, 0x40));
+        let extra = vec![0x22u8; decrypted.len()];
+
+        splice_extra_crypto_files(&mut decrypted, &extra).unwrap();
+
+        assert!(
+            decrypted[EXEFS_HEADER_SIZE..EXEFS_HEADER_SIZE + 0x20]
+                .iter()
+                .all(|&b| b == 0)
+        );
+        assert!(
+            decrypted[EXEFS_HEADER_SIZE + 0x20..]
+                .iter()
+                .all(|&b| b == 0x22)
+        );
+
+        // A section shorter than the file header table is left alone.
+        let mut short = vec![0x33u8; 0x18];
+        splice_extra_crypto_files(&mut short, &[0u8; 0x18]).unwrap();
+        assert!(short.iter().all(|&b| b == 0x33));
+    }
+
     /// The pooled RomFS path must decrypt to the exact bytes a single
     /// continuous AES-CTR stream would produce, including the per-region cidx
     /// fixup on byte 1. Uses a standalone-NCCH reader (no CIA outer CBC) so the
@@ -1206,6 +2337,34 @@ mod tests {
         assert_eq!(extra_crypto_index(11), 3);
     }
 
+    fn header_with_format_version(version: u8) -> NcchHeader {
+        let mut bytes =
+            crate::nintendo::ctr::test_fixtures::make_ncch_header_bytes(0x0004000000055D00);
+        bytes[0x112] = version;
+        NcchHeader::read(&mut Cursor::new(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn aes_counter_for_known_format_versions() {
+        let v2 = get_ncch_aes_counter(&header_with_format_version(2), NcchSection::ExeFS).unwrap();
+        assert_eq!(&v2[0..8], &0x0004000000055D00u64.to_be_bytes());
+        assert_eq!(v2[8], NcchSection::ExeFS as u8);
+
+        let v1 =
+            get_ncch_aes_counter(&header_with_format_version(1), NcchSection::ExHeader).unwrap();
+        assert_eq!(&v1[12..16], &512u32.to_be_bytes());
+    }
+
+    #[test]
+    fn aes_counter_rejects_unknown_format_version() {
+        let err =
+            get_ncch_aes_counter(&header_with_format_version(3), NcchSection::RomFS).unwrap_err();
+        assert!(matches!(
+            err,
+            NintendoCTRError::UnsupportedNcchFormatVersion(3)
+        ));
+    }
+
     #[test]
     fn extra_crypto_index_unknown_defaults_to_zero() {
         assert_eq!(extra_crypto_index(2), 0);
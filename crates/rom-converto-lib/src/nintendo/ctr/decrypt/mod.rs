@@ -6,3 +6,34 @@ pub(crate) mod model;
 pub(crate) mod reader;
 pub(crate) mod romfs_worker;
 pub mod util;
+
+/// Settings for one decrypt run, passed down to every NCCH it touches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptOptions {
+    /// When a seed-crypto NCCH's seed cannot be found, copy that NCCH
+    /// through still encrypted, header flags included, with a warning
+    /// instead of failing the decrypt.
+    pub allow_missing_seed: bool,
+    /// Use this seed for every seed-crypto NCCH instead of the seed
+    /// database or the CDN. Each NCCH checks it against its `seedcheck`
+    /// first, so a wrong seed fails the decrypt instead of producing
+    /// garbage.
+    pub seed: Option<[u8; 16]>,
+    /// Extra seed CDN rounds after the first when every country fails
+    /// transiently, with exponential backoff between them.
+    pub seed_retries: u32,
+    /// Re-read each decrypted RomFS superblock and compare its SHA-256
+    /// with the NCCH header's `romfshash`, logging whether it matches.
+    pub check_romfs: bool,
+}
+
+impl Default for DecryptOptions {
+    fn default() -> Self {
+        Self {
+            allow_missing_seed: false,
+            seed: None,
+            seed_retries: crate::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+            check_romfs: false,
+        }
+    }
+}
@@ -52,8 +52,10 @@ impl CiaReader {
     }
 
     pub async fn seek(&mut self, offs: u64) -> anyhow::Result<()> {
-        if self.single_ncch || self.from_ncsd {
-            self.file.seek(SeekFrom::Start(offs)).await?;
+        if self.single_ncch || (self.from_ncsd && !self.encrypted) {
+            self.file
+                .seek(SeekFrom::Start(self.contentoff + offs))
+                .await?;
         } else if offs == 0 {
             self.file.seek(SeekFrom::Start(self.contentoff)).await?;
             self.iv = gen_iv(self.cidx);
@@ -73,6 +75,15 @@ impl CiaReader {
         if self.encrypted {
             let last_enc_block = BigEndian::read_u128(&data[(data.len() - 16)..]);
             cbc_decrypt(&self.key, &self.iv, data)?;
+
+            if self.from_ncsd {
+                // An NCSD inside a CIA content is read from arbitrary
+                // partition offsets, so chain each read from the previous
+                // ciphertext block instead of the content IV.
+                self.iv = last_enc_block.to_be_bytes();
+                return Ok(());
+            }
+
             let first_dec_block = BigEndian::read_u128(&data[0..16]);
 
             // XOR the last encrypted block with the first decrypted block
@@ -10,6 +10,7 @@ use crate::nintendo::ctr::constants::{
     CTR_COMMON_KEYS_HEX, TICKET_COMMON_KEY_IDX_OFFSET, TICKET_SIG_BODY_OFFSET,
     TICKET_TITLE_ID_OFFSET, TICKET_TITLE_KEY_OFFSET,
 };
+use crate::nintendo::ctr::error::{NintendoCTRError, NintendoCTRResult};
 
 pub type Aes128Cbc = cbc::Decryptor<Aes128>;
 
@@ -28,6 +29,14 @@ pub fn cbc_decrypt(key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) -> anyhow::Re
     Ok(())
 }
 
+/// Common key for a ticket's `common_key_index`. Retail titles use 0..=5;
+/// anything past the table is a malformed or unknown ticket.
+pub(crate) fn common_key(index: u8) -> NintendoCTRResult<&'static [u8; 16]> {
+    CTR_COMMON_KEYS_HEX
+        .get(index as usize)
+        .ok_or(NintendoCTRError::UnknownCommonKeyIndex(index))
+}
+
 /// `ticket_offset` is the absolute byte offset of the ticket section
 /// in the source file.
 pub fn derive_title_key_from_ticket<R: Read + Seek>(
@@ -47,15 +56,8 @@ pub fn derive_title_key_from_ticket<R: Read + Seek>(
     reader.seek(SeekFrom::Start(sig_body + TICKET_COMMON_KEY_IDX_OFFSET))?;
     let mut cmnkey_idx = [0u8; 1];
     reader.read_exact(&mut cmnkey_idx)?;
-    let idx = cmnkey_idx[0] as usize;
-    if idx >= CTR_COMMON_KEYS_HEX.len() {
-        return Err(anyhow::anyhow!(
-            "ticket common key index out of range: {}",
-            idx
-        ));
-    }
 
-    cbc_decrypt(&CTR_COMMON_KEYS_HEX[idx], &tid_iv, &mut enckey)?;
+    cbc_decrypt(common_key(cmnkey_idx[0])?, &tid_iv, &mut enckey)?;
     Ok(enckey)
 }
 
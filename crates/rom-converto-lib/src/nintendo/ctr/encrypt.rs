@@ -9,44 +9,39 @@ use log::{debug, info, warn};
 use sha2::{Digest, Sha256};
 use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::fs::{self, File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
 
 use crate::nintendo::ctr::constants::{
     CTR_KEYS_0, CTR_MEDIA_UNIT_SIZE, CTR_NCSD_PARTITIONS, EXEFS_ENTRY_SIZE, EXEFS_HEADER_SIZE,
     EXEFS_MAX_FILE_ENTRIES, EXEFS_SECTION_BANNER, EXEFS_SECTION_ICON,
-    NCCH_FLAGS_EXTRA_CRYPTO_INDEX, NCCH_FLAGS_OFFSET, NCCH_FLAGS7_FIXED_KEY, NCCH_FLAGS7_NOCRYPTO,
+    NCCH_FLAGS_EXTRA_CRYPTO_INDEX, NCCH_FLAGS_OFFSET, NCCH_FLAGS7_NOCRYPTO,
     NCCH_FLAGS7_SEED_CRYPTO, NCCH_MAGIC_OFFSET, NCSD_PARTITION_COUNT, NCSD_PARTITION_ENTRY_SIZE,
     NCSD_PARTITION_TABLE_OFFSET, NCSD_TITLE_ID_OFFSET,
 };
 use crate::nintendo::ctr::decrypt::cia::{
-    Aes128Ctr, ROMFS_CHUNK_SIZE, derive_ctr_key, extra_crypto_index, fixed_key,
-    get_ncch_aes_counter, get_new_key,
+    Aes128Ctr, ROMFS_CHUNK_SIZE, derive_ctr_key, extra_crypto_index, fixed_crypto_kind, fixed_key,
+    fixed_key_name, get_ncch_aes_counter, get_new_key,
 };
 use crate::nintendo::ctr::decrypt::model::NcchSection;
 use crate::nintendo::ctr::decrypt::util::{derive_title_key_from_ticket, gen_iv};
 use crate::nintendo::ctr::error::NintendoCTRError;
+use crate::nintendo::ctr::fix_tmd::update_tmd_hashes;
+use crate::nintendo::ctr::format::{KnownFormat, detect_format};
 use crate::nintendo::ctr::models::cia::{CIA_HEADER_SIZE, CiaFile, CiaFileWithoutContent};
 use crate::nintendo::ctr::models::exe_fs_header::ExeFSHeader;
 use crate::nintendo::ctr::models::ncch_header::NcchHeader;
-use crate::nintendo::ctr::models::title_metadata::{ContentInfoRecord, TitleMetadata};
 use crate::nintendo::ctr::util::align_64;
 use crate::nintendo::ctr::z3ds::models::underlying_magic;
-use crate::util::{CancelToken, ProgressReporter, scratch_output_path};
+use crate::util::{CancelToken, ProgressReporter, Tally, scratch_output_path};
 
 const ENCRYPT_EXTS: &[&str] = &["cia", "3ds", "cci", "cxi"];
 const COPY_BUF: usize = 4 * 1024 * 1024;
 const CRYPTO_BUF: usize = 4 * 1024 * 1024;
 
 pub fn derive_encrypted_path(input: &Path) -> PathBuf {
-    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("out");
-    let ext = input.extension().and_then(|s| s.to_str()).unwrap_or("");
-    let name = if ext.is_empty() {
-        format!("{stem}.encrypted")
-    } else {
-        format!("{stem}.encrypted.{ext}")
-    };
-    input.with_file_name(name)
+    crate::nintendo::ctr::util::tagged_sibling_path(input, "encrypted")
 }
 
 pub async fn encrypt_rom(
@@ -63,32 +58,18 @@ pub async fn encrypt_rom_cancellable(
     progress: &dyn ProgressReporter,
     cancel: CancelToken,
 ) -> Result<()> {
-    let mut file = File::open(input).await?;
-
-    let mut magic_buf = [0u8; 4];
-    file.seek(SeekFrom::Start(NCCH_MAGIC_OFFSET as u64)).await?;
-    file.read_exact(&mut magic_buf).await?;
-    drop(file);
-
-    if magic_buf == underlying_magic::NCSD {
-        info!("Detected NCSD format (.3ds/.cci)");
-        encrypt_ncsd_cancellable(input, output, progress, &cancel).await
-    } else if magic_buf == underlying_magic::NCCH {
-        info!("Detected standalone NCCH format (.cxi)");
-        encrypt_ncch_cancellable(input, output, progress, &cancel).await
-    } else {
-        let mut file = File::open(input).await?;
-        let mut header_check = [0u8; 4];
-        file.read_exact(&mut header_check).await?;
-        drop(file);
-
-        if u32::from_le_bytes(header_check) == CIA_HEADER_SIZE {
+    match detect_format(input).await? {
+        KnownFormat::Ncsd => {
+            info!("Detected NCSD format (.3ds/.cci)");
+            encrypt_ncsd_cancellable(input, output, progress, &cancel).await
+        }
+        KnownFormat::Ncch => {
+            info!("Detected standalone NCCH format (.cxi)");
+            encrypt_ncch_cancellable(input, output, NcchSource::Standalone, progress, &cancel).await
+        }
+        KnownFormat::Cia => {
             info!("Detected CIA format");
             encrypt_cia_cancellable(input, output, progress, &cancel).await
-        } else {
-            Err(anyhow!(
-                "unrecognized format: no NCSD/NCCH magic at 0x100 and not a CIA file"
-            ))
         }
     }
 }
@@ -121,9 +102,37 @@ async fn encrypt_ncsd_cancellable(
     Ok(())
 }
 
+/// Encrypts a standalone decrypted NCCH (`.ncch`, `.cxi`, `.cfa`), refusing
+/// CIA and NCSD input that [`encrypt_rom_cancellable`] would also take. The
+/// crypto method comes from the header flags, read as the decrypt reads
+/// them, and the no-crypto flag is cleared. `content_index` is the CIA
+/// content an NCCH from `decrypt --ncch-only` was split from; index 1 and
+/// up re-applies the RomFS fixup the CIA decrypt left in those files.
+pub async fn encrypt_ncch_file_cancellable(
+    input: &Path,
+    output: &Path,
+    content_index: Option<u16>,
+    progress: &dyn ProgressReporter,
+    cancel: CancelToken,
+) -> Result<()> {
+    let format = detect_format(input).await?;
+    if format != KnownFormat::Ncch {
+        anyhow::bail!(
+            "{} is not a standalone NCCH ({format:?}); use `ctr encrypt` for CIA and NCSD images",
+            input.display()
+        );
+    }
+    let source = match content_index {
+        Some(content_index) => NcchSource::CiaContent { content_index },
+        None => NcchSource::Standalone,
+    };
+    encrypt_ncch_cancellable(input, output, source, progress, &cancel).await
+}
+
 async fn encrypt_ncch_cancellable(
     input: &Path,
     output: &Path,
+    source: NcchSource,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> Result<()> {
@@ -133,16 +142,7 @@ async fn encrypt_ncch_cancellable(
     let tmp = scratch_output_path(output)?;
     let result = async {
         fs::copy(input, &tmp).await?;
-        encrypt_ncch_at(
-            input,
-            &tmp,
-            0,
-            [0u8; 8],
-            NcchSource::Standalone,
-            progress,
-            cancel,
-        )
-        .await?;
+        encrypt_ncch_at(input, &tmp, 0, [0u8; 8], source, progress, cancel).await?;
         Ok::<(), anyhow::Error>(())
     }
     .await;
@@ -158,12 +158,18 @@ async fn encrypt_ncch_cancellable(
     Ok(())
 }
 
+/// Runs the encrypt over every matching file under `input_dir`, recording each
+/// item in `tally`. A failed item is logged and skipped, or with `fail_fast`
+/// ends the run with its error.
+#[allow(clippy::too_many_arguments)]
 pub async fn encrypt_rom_batch_cancellable(
     input_dir: &Path,
     output_dir: Option<&Path>,
     progress: &dyn ProgressReporter,
     total_progress: &dyn ProgressReporter,
     max_depth: Option<usize>,
+    fail_fast: bool,
+    tally: &mut Tally,
     cancel: CancelToken,
 ) -> Result<()> {
     let roms = crate::util::fs::collect_files_with_exts(input_dir, ENCRYPT_EXTS, max_depth)?;
@@ -200,14 +206,22 @@ pub async fn encrypt_rom_batch_cancellable(
         }
 
         debug!("Encrypting {} -> {}", path.display(), output.display());
-        if let Err(err) = encrypt_rom_cancellable(&path, &output, progress, cancel.clone()).await {
-            if matches!(
-                err.downcast_ref::<NintendoCTRError>(),
-                Some(NintendoCTRError::Cancelled)
-            ) {
-                return Err(err);
+        let started = Instant::now();
+        match encrypt_rom_cancellable(&path, &output, progress, cancel.clone()).await {
+            Ok(()) => tally.record_ok_files(&path, &output, started.elapsed()),
+            Err(err) => {
+                if matches!(
+                    err.downcast_ref::<NintendoCTRError>(),
+                    Some(NintendoCTRError::Cancelled)
+                ) {
+                    return Err(err);
+                }
+                tally.record_failed();
+                if fail_fast {
+                    return Err(err.context(format!("Failed to encrypt {}", path.display())));
+                }
+                warn!("Failed to encrypt {}: {err}", path.display());
             }
-            warn!("Failed to encrypt {}: {err}", path.display());
         }
 
         total_progress.inc(1);
@@ -460,7 +474,7 @@ async fn encrypt_ncch_at(
     write.write_all(&header_bytes).await?;
 
     if header.exhdrsize != 0 {
-        let counter = get_ncch_aes_counter(&header, NcchSection::ExHeader);
+        let counter = get_ncch_aes_counter(&header, NcchSection::ExHeader)?;
         let key = crypto.base_or_fixed_key();
         encrypt_stream_section(
             &mut read,
@@ -477,7 +491,7 @@ async fn encrypt_ncch_at(
     }
 
     if header.exefssize != 0 {
-        let counter = get_ncch_aes_counter(&header, NcchSection::ExeFS);
+        let counter = get_ncch_aes_counter(&header, NcchSection::ExeFS)?;
         encrypt_exefs_section(
             &mut read,
             &mut write,
@@ -491,7 +505,7 @@ async fn encrypt_ncch_at(
     }
 
     if header.romfssize != 0 {
-        let counter = get_ncch_aes_counter(&header, NcchSection::RomFS);
+        let counter = get_ncch_aes_counter(&header, NcchSection::RomFS)?;
         let key = crypto.romfs_key();
         let cia_fixup = source
             .cia_content_index()
@@ -518,7 +532,7 @@ async fn encrypt_ncch_at(
 struct NcchCrypto {
     uses_extra_crypto: u8,
     use_seed_crypto: bool,
-    fixed_crypto: u8,
+    fixed_key: Option<[u8; 16]>,
     ncch_key_y: u128,
     key_y: u128,
 }
@@ -528,30 +542,35 @@ impl NcchCrypto {
         let ncch_key_y = BigEndian::read_u128(header.signature[0..16].try_into()?);
         let uses_extra_crypto = header.flags[NCCH_FLAGS_EXTRA_CRYPTO_INDEX];
 
-        let mut fixed_crypto = 0;
-        if (header.flags[7] & NCCH_FLAGS7_FIXED_KEY) != 0 {
-            let mut tid = header.titleid;
-            tid.reverse();
-            fixed_crypto = if (tid[3] & 16) != 0 { 2 } else { 1 };
+        let fixed_crypto = fixed_crypto_kind(header);
+        if let Some(name) = fixed_key_name(fixed_crypto) {
+            debug!("  Uses fixed-key crypto with the {name}");
         }
+        let fixed_key = fixed_key(fixed_crypto)?;
 
         let use_seed_crypto = (header.flags[7] & NCCH_FLAGS7_SEED_CRYPTO) != 0;
         let mut key_y = ncch_key_y;
         if use_seed_crypto {
-            key_y = get_new_key(ncch_key_y, header, hex::encode(title_id)).await?;
+            key_y = get_new_key(
+                ncch_key_y,
+                header,
+                hex::encode(title_id),
+                &Default::default(),
+            )
+            .await?;
         }
 
         Ok(Self {
             uses_extra_crypto,
             use_seed_crypto,
-            fixed_crypto,
+            fixed_key,
             ncch_key_y,
             key_y,
         })
     }
 
     fn base_or_fixed_key(&self) -> [u8; 16] {
-        fixed_key(self.fixed_crypto)
+        self.fixed_key
             .unwrap_or_else(|| derive_ctr_key(CTR_KEYS_0[0], self.ncch_key_y))
     }
 
@@ -563,7 +582,7 @@ impl NcchCrypto {
     }
 
     fn romfs_key(&self) -> [u8; 16] {
-        fixed_key(self.fixed_crypto).unwrap_or_else(|| self.extra_key())
+        self.fixed_key.unwrap_or_else(|| self.extra_key())
     }
 }
 
@@ -771,49 +790,6 @@ async fn write_cbc_encrypted_content(
     Ok(out)
 }
 
-fn update_tmd_hashes(tmd: &mut TitleMetadata, content_hashes: &[[u8; 32]]) -> Result<()> {
-    if content_hashes.len() != tmd.content_chunk_records.len() {
-        anyhow::bail!(
-            "encrypted {} contents but TMD declares {} records",
-            content_hashes.len(),
-            tmd.content_chunk_records.len()
-        );
-    }
-
-    for (record, hash) in tmd.content_chunk_records.iter_mut().zip(content_hashes) {
-        record.hash = hash.to_vec();
-    }
-
-    for content_info_record in &mut tmd.content_info_records {
-        let start = content_info_record.content_index_offset as usize;
-        let count = content_info_record.content_command_count as usize;
-        let end = start + count;
-        let mut hasher = Sha256::new();
-        for chunk in &tmd.content_chunk_records[start..end] {
-            let mut buf = Cursor::new(Vec::new());
-            chunk.write_be(&mut buf)?;
-            hasher.update(buf.get_ref());
-        }
-        content_info_record.hash = hasher.finalize().to_vec();
-    }
-
-    let mut hasher = Sha256::new();
-    for content_info_record in &tmd.content_info_records {
-        hash_content_info_record(&mut hasher, content_info_record)?;
-    }
-    tmd.header.content_info_records_hash = hasher.finalize().to_vec();
-    Ok(())
-}
-
-fn hash_content_info_record(hasher: &mut Sha256, record: &ContentInfoRecord) -> Result<()> {
-    let mut cursor = Cursor::new(Vec::new());
-    record.content_index_offset.write_be(&mut cursor)?;
-    record.content_command_count.write_be(&mut cursor)?;
-    cursor.get_mut().extend_from_slice(&record.hash);
-    hasher.update(cursor.get_ref());
-    Ok(())
-}
-
 struct CiaLayout {
     ticket_offset: u64,
     content_offset: u64,
@@ -875,14 +851,75 @@ mod tests {
             .await
             .unwrap();
         let mut out = File::create(&decrypted_path).await.unwrap();
-        parse_and_decrypt_ncch(&encrypted_path, &mut out, &NoProgress, &CancelToken::new())
-            .await
-            .unwrap();
+        parse_and_decrypt_ncch(
+            &encrypted_path,
+            &mut out,
+            &Default::default(),
+            &NoProgress,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
         out.flush().await.unwrap();
 
         assert_eq!(std::fs::read(&decrypted_path).unwrap(), plain);
     }
 
+    #[tokio::test]
+    async fn ncch_only_encrypt_matches_encrypt_and_refuses_ncsd() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain_path = dir.path().join("plain.ncch");
+        let via_rom = dir.path().join("rom.ncch");
+        let via_ncch = dir.path().join("ncch.ncch");
+        let as_content = dir.path().join("content.ncch");
+        std::fs::write(&plain_path, make_plain_ncch_with_romfs()).unwrap();
+
+        encrypt_rom(&plain_path, &via_rom, &NoProgress)
+            .await
+            .unwrap();
+        encrypt_ncch_file_cancellable(
+            &plain_path,
+            &via_ncch,
+            None,
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        let expected = std::fs::read(&via_rom).unwrap();
+        assert_eq!(std::fs::read(&via_ncch).unwrap(), expected);
+
+        // Content 1 of a CIA carries the RomFS fixup on byte 1 of each chunk.
+        encrypt_ncch_file_cancellable(
+            &plain_path,
+            &as_content,
+            Some(1),
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        let mut fixed = expected.clone();
+        fixed[2 * CTR_MEDIA_UNIT_SIZE as usize + 1] ^= 1;
+        assert_eq!(std::fs::read(&as_content).unwrap(), fixed);
+
+        let ncsd_path = dir.path().join("game.3ds");
+        let mut ncsd = vec![0u8; 0x400];
+        ncsd[0x100..0x104].copy_from_slice(b"NCSD");
+        std::fs::write(&ncsd_path, ncsd).unwrap();
+        let err = encrypt_ncch_file_cancellable(
+            &ncsd_path,
+            &dir.path().join("out.3ds"),
+            None,
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("not a standalone NCCH"), "{err}");
+    }
+
     #[tokio::test]
     async fn cia_encrypt_decrypt_round_trip() {
         let dir = tempfile::tempdir().unwrap();
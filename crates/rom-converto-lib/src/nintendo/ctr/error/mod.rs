@@ -13,6 +13,41 @@ pub enum NintendoCTRError {
     #[error("could not find at least one TMD file in the specified path: {0}")]
     NoTmdFileFound(PathBuf),
 
+    #[error("no tmd.{version} in {}; TMD files present: {available}", .dir.display())]
+    TmdVersionNotFound {
+        dir: PathBuf,
+        version: u32,
+        available: String,
+    },
+
+    #[error("unsupported NCCH format version {0}, cannot derive the AES counter")]
+    UnsupportedNcchFormatVersion(u8),
+
+    #[error("fixed-key selector {0} does not name a known fixed key")]
+    UnknownFixedKey(u8),
+
+    #[error("unknown common key index {0}")]
+    UnknownCommonKeyIndex(u8),
+
+    #[error("Supplied seed does not match the NCCH seedcheck for title {0}")]
+    SuppliedSeedMismatch(String),
+
+    #[error("Seed verification failed: SHA256 mismatch for title {0}")]
+    SeedVerificationFailed(String),
+
+    #[error("title key {0:?} is not 32 hex digits, cannot fill the ticket template")]
+    InvalidTicketTitleKey(String),
+
+    #[error("generated ticket does not round-trip: {field} is {actual}, expected {expected}")]
+    GeneratedTicketMismatch {
+        field: &'static str,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("verify FAIL for title {title_id:016X}: {problems}")]
+    BuiltCiaVerifyFailed { title_id: u64, problems: String },
+
     #[error("operation cancelled")]
     Cancelled,
 
@@ -21,20 +21,18 @@ use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use std::io::Cursor;
 
 use crate::nintendo::ctr::constants::{
-    CTR_KEYS_0, CTR_KEYS_1, EXEFS_ENTRY_SIZE, EXEFS_HEADER_SIZE, EXEFS_MAX_FILE_ENTRIES,
-    EXEFS_SECTION_ICON, NCCH_FLAGS7_FIXED_KEY, NCCH_FLAGS7_NOCRYPTO, NCCH_FLAGS7_SEED_CRYPTO,
+    CTR_KEYS_0, EXEFS_ENTRY_SIZE, EXEFS_HEADER_SIZE, EXEFS_MAX_FILE_ENTRIES, EXEFS_SECTION_ICON,
+    NCCH_FLAGS7_NOCRYPTO, NCCH_FLAGS7_SEED_CRYPTO,
+};
+use crate::nintendo::ctr::decrypt::cia::{
+    derive_ctr_key, fixed_crypto_kind, fixed_key, get_ncch_aes_counter,
 };
-use crate::nintendo::ctr::decrypt::cia::{derive_ctr_key, get_ncch_aes_counter};
 use crate::nintendo::ctr::decrypt::model::NcchSection;
 use crate::nintendo::ctr::models::exe_fs_header::ExeFSHeader;
 use crate::nintendo::ctr::models::ncch_header::NcchHeader;
 
 type Aes128Ctr = ctr::Ctr128BE<Aes128>;
 
-fn fixed_key(fixed_crypto: u8) -> Option<[u8; 16]> {
-    (fixed_crypto != 0).then(|| u128::to_be_bytes(CTR_KEYS_1[(fixed_crypto as usize) - 1]))
-}
-
 fn decrypt_exefs_with_base(base_key: &[u8; 16], ctr: &[u8; 16], exefs: &mut [u8]) -> Result<()> {
     Aes128Ctr::new_from_slices(base_key, ctr)
         .map_err(|e| anyhow!("aes ctr init: {}", e))?
@@ -54,7 +52,6 @@ pub fn read_exefs_section(
     section_name: &[u8],
 ) -> Result<Vec<u8>> {
     let nocrypto = header.flags[7] & NCCH_FLAGS7_NOCRYPTO != 0;
-    let fixed = header.flags[7] & NCCH_FLAGS7_FIXED_KEY != 0;
     let needs_seed = header.flags[7] & NCCH_FLAGS7_SEED_CRYPTO != 0;
 
     if needs_seed {
@@ -63,22 +60,16 @@ pub fn read_exefs_section(
         ));
     }
 
-    let fixed_crypto = if fixed {
-        let mut tid_normal: [u8; 8] = header.titleid;
-        tid_normal.reverse();
-        if (tid_normal[3] & 16) != 0 { 2u8 } else { 1u8 }
-    } else {
-        0u8
-    };
+    let fixed_crypto = fixed_crypto_kind(header);
 
     let key_y = BigEndian::read_u128(header.signature[0..16].try_into()?);
     let base_key = derive_ctr_key(CTR_KEYS_0[0], key_y);
-    let working_key = match fixed_key(fixed_crypto) {
+    let working_key = match fixed_key(fixed_crypto)? {
         Some(fk) => fk,
         None => base_key,
     };
 
-    let ctr = get_ncch_aes_counter(header, NcchSection::ExeFS);
+    let ctr = get_ncch_aes_counter(header, NcchSection::ExeFS)?;
 
     let mut decrypted = exefs_encrypted.to_vec();
     if !nocrypto {
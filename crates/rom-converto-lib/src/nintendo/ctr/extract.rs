@@ -0,0 +1,645 @@
+//! Splits a CIA back into the CDN layout `cdn-to-cia` consumes: one file
+//! per content named by its content id, the TMD as `tmd` or
+//! `tmd.<version>`, and the ticket as `cetk` or `<title id>.tik`. Content
+//! bytes are copied as stored, so title-key encrypted
+//! contents stay encrypted. The TMD and ticket keep their original bytes
+//! and get the matching certificates from the CIA chain appended, the way
+//! Nintendo's CDN serves them. [`extract_content`] pulls out a single
+//! content instead, optionally with its title key layer removed.
+
+use crate::nintendo::ctr::decrypt::util::{cbc_decrypt, derive_title_key_from_ticket, gen_iv};
+use crate::nintendo::ctr::error::NintendoCTRError;
+use crate::nintendo::ctr::models::certificate::Certificate;
+use crate::nintendo::ctr::models::cia::{CIA_HEADER_SIZE, CiaFileWithoutContent, CiaHeader};
+use crate::nintendo::ctr::util::align_64;
+use crate::util::{CancelToken, ProgressReporter, ScratchPath, publish_temp, scratch_output_path};
+use anyhow::{Context, Result, bail};
+use binrw::{BinRead, BinWrite, Endian};
+use log::{debug, info};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter, SeekFrom};
+
+const CONTENT_COPY_BUF: usize = 4 * 1024 * 1024;
+
+/// File naming for [`extract_contents`]. The defaults match what the CDN
+/// serves for a title's latest version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CdnLayout {
+    /// Name the TMD `tmd.<title version>` instead of `tmd`.
+    pub versioned_tmd: bool,
+    /// Name the ticket `<title id>.tik` instead of `cetk`.
+    pub tik_ticket: bool,
+}
+
+/// Files written by [`extract_contents`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedContents {
+    pub title_id: u64,
+    pub tmd: PathBuf,
+    pub ticket: PathBuf,
+    pub contents: Vec<PathBuf>,
+}
+
+pub async fn extract_contents(
+    input: &Path,
+    out_dir: &Path,
+    layout: CdnLayout,
+    overwrite: bool,
+    progress: &dyn ProgressReporter,
+) -> Result<ExtractedContents> {
+    extract_contents_cancellable(
+        input,
+        out_dir,
+        layout,
+        overwrite,
+        progress,
+        &CancelToken::new(),
+    )
+    .await
+}
+
+/// Write the contents, TMD, and ticket of the CIA at `input` into
+/// `out_dir` as a CDN directory named per `layout`. Existing files are
+/// only replaced when `overwrite` is set; otherwise nothing is written.
+/// Every file goes to a scratch path first and none is renamed into place
+/// until all of them are complete, so a failed or cancelled run leaves
+/// no partial CDN directory behind.
+pub async fn extract_contents_cancellable(
+    input: &Path,
+    out_dir: &Path,
+    layout: CdnLayout,
+    overwrite: bool,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<ExtractedContents> {
+    let mut in_file = File::open(input)
+        .await
+        .with_context(|| format!("ctr extract-contents: open {}", input.display()))?;
+    let file_size = in_file.metadata().await?.len();
+
+    let mut header_buf = vec![0u8; CIA_HEADER_SIZE as usize];
+    in_file
+        .read_exact(&mut header_buf)
+        .await
+        .context("ctr extract-contents: CIA header is truncated")?;
+    let header = CiaHeader::read_le(&mut Cursor::new(&header_buf))
+        .context("ctr extract-contents: parse CIA header")?;
+    let ticket_start =
+        align_64(align_64(header.header_size as u64) + header.cert_chain_size as u64);
+    let tmd_start = align_64(ticket_start + header.ticket_size as u64);
+    let content_start = align_64(tmd_start + header.tmd_size as u64);
+    if content_start > file_size {
+        bail!("ctr extract-contents: CIA ends before its content area at 0x{content_start:X}");
+    }
+
+    let mut preamble = vec![0u8; content_start as usize];
+    in_file.seek(SeekFrom::Start(0)).await?;
+    in_file.read_exact(&mut preamble).await?;
+    let cia = CiaFileWithoutContent::read_le(&mut Cursor::new(&preamble))
+        .context("ctr extract-contents: parse CIA certificates, ticket, and TMD")?;
+
+    let records = &cia.tmd.content_chunk_records;
+    let content_total: u64 = records.iter().map(|r| r.content_size).sum();
+    if content_start + content_total > file_size {
+        bail!(
+            "ctr extract-contents: TMD declares 0x{content_total:X} content bytes but the CIA holds 0x{:X}",
+            file_size - content_start
+        );
+    }
+
+    let title_id = cia.tmd.header.title_id;
+    let tmd_path = if layout.versioned_tmd {
+        out_dir.join(format!("tmd.{}", cia.tmd.header.title_version))
+    } else {
+        out_dir.join("tmd")
+    };
+    let ticket_path = if layout.tik_ticket {
+        out_dir.join(format!("{title_id:016X}.tik"))
+    } else {
+        out_dir.join("cetk")
+    };
+    let content_paths: Vec<PathBuf> = records
+        .iter()
+        .map(|r| out_dir.join(format!("{:08x}", r.content_id)))
+        .collect();
+    if !overwrite {
+        for path in content_paths.iter().chain([&tmd_path, &ticket_path]) {
+            if path.exists() {
+                bail!(
+                    "ctr extract-contents: {} already exists (use --force to overwrite)",
+                    path.display()
+                );
+            }
+        }
+    }
+    tokio::fs::create_dir_all(out_dir).await?;
+
+    // CDN TMDs carry CP then CA, tickets XS then CA.
+    let tmd_bytes = &preamble[tmd_start as usize..(tmd_start + header.tmd_size as u64) as usize];
+    let ticket_bytes =
+        &preamble[ticket_start as usize..(ticket_start + header.ticket_size as u64) as usize];
+    let mut written = vec![
+        (
+            write_with_certs(&tmd_path, tmd_bytes, &cia.cert_chain, &["CP", "CA"]).await?,
+            &tmd_path,
+        ),
+        (
+            write_with_certs(&ticket_path, ticket_bytes, &cia.cert_chain, &["XS", "CA"]).await?,
+            &ticket_path,
+        ),
+    ];
+
+    progress.start(content_total, "Extracting contents");
+    let mut buf = vec![0u8; CONTENT_COPY_BUF];
+    let mut offset = content_start;
+    for (record, path) in records.iter().zip(&content_paths) {
+        debug!(
+            "Content {:08x} (index {}): 0x{:X} bytes at 0x{offset:X}",
+            record.content_id, record.content_index, record.content_size
+        );
+        in_file.seek(SeekFrom::Start(offset)).await?;
+        let write_path = scratch_output_path(path)?;
+        let mut out = BufWriter::new(File::create(&write_path).await?);
+        let mut remaining = record.content_size;
+        while remaining > 0 {
+            if cancel.is_cancelled() {
+                return Err(NintendoCTRError::Cancelled.into());
+            }
+            let n = remaining.min(buf.len() as u64) as usize;
+            in_file.read_exact(&mut buf[..n]).await?;
+            out.write_all(&buf[..n]).await?;
+            progress.inc(n as u64);
+            remaining -= n as u64;
+        }
+        out.flush().await?;
+        out.get_ref().sync_all().await?;
+        drop(out);
+        written.push((write_path, path));
+        offset += record.content_size;
+    }
+    for (write_path, path) in written {
+        publish_temp(write_path, path, overwrite)?;
+    }
+    progress.finish();
+
+    Ok(ExtractedContents {
+        title_id,
+        tmd: tmd_path,
+        ticket: ticket_path,
+        contents: content_paths,
+    })
+}
+
+/// The content written by [`extract_content`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractedContent {
+    pub content_id: u32,
+    pub content_index: u16,
+    pub size: u64,
+    /// Whether the title key layer was removed. False when decryption was
+    /// not asked for or the TMD flags the content as unencrypted.
+    pub decrypted: bool,
+}
+
+pub async fn extract_content(
+    input: &Path,
+    index: u16,
+    output: &Path,
+    decrypt: bool,
+    overwrite: bool,
+    progress: &dyn ProgressReporter,
+) -> Result<ExtractedContent> {
+    extract_content_cancellable(
+        input,
+        index,
+        output,
+        decrypt,
+        overwrite,
+        progress,
+        &CancelToken::new(),
+    )
+    .await
+}
+
+/// Write the content with TMD content index `index` of the CIA at `input`
+/// to `output`, reading only that content. With `decrypt` the title key
+/// is derived from the CIA's ticket and the content's AES-CBC layer is
+/// removed, leaving the NCCH's own encryption in place. An existing
+/// `output` is only replaced when `overwrite` is set.
+pub async fn extract_content_cancellable(
+    input: &Path,
+    index: u16,
+    output: &Path,
+    decrypt: bool,
+    overwrite: bool,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<ExtractedContent> {
+    if !overwrite && output.exists() {
+        bail!(
+            "ctr extract-content: {} already exists (use --force to overwrite)",
+            output.display()
+        );
+    }
+    let mut in_file = File::open(input)
+        .await
+        .with_context(|| format!("ctr extract-content: open {}", input.display()))?;
+    let file_size = in_file.metadata().await?.len();
+
+    let mut header_buf = vec![0u8; CIA_HEADER_SIZE as usize];
+    in_file
+        .read_exact(&mut header_buf)
+        .await
+        .context("ctr extract-content: CIA header is truncated")?;
+    let header = CiaHeader::read_le(&mut Cursor::new(&header_buf))
+        .context("ctr extract-content: parse CIA header")?;
+    let ticket_start =
+        align_64(align_64(header.header_size as u64) + header.cert_chain_size as u64);
+    let tmd_start = align_64(ticket_start + header.ticket_size as u64);
+    let content_start = align_64(tmd_start + header.tmd_size as u64);
+    if content_start > file_size {
+        bail!("ctr extract-content: CIA ends before its content area at 0x{content_start:X}");
+    }
+
+    let mut preamble = vec![0u8; content_start as usize];
+    in_file.seek(SeekFrom::Start(0)).await?;
+    in_file.read_exact(&mut preamble).await?;
+    let cia = CiaFileWithoutContent::read_le(&mut Cursor::new(&preamble))
+        .context("ctr extract-content: parse CIA certificates, ticket, and TMD")?;
+
+    // Contents follow each other in TMD record order, each starting on a
+    // 64-byte boundary.
+    let records = &cia.tmd.content_chunk_records;
+    let Some(position) = records.iter().position(|r| r.content_index == index) else {
+        let present: Vec<String> = records
+            .iter()
+            .map(|r| r.content_index.to_string())
+            .collect();
+        bail!(
+            "ctr extract-content: the CIA has no content with index {index} (present: {})",
+            present.join(", ")
+        );
+    };
+    let record = &records[position];
+    let offset = content_start
+        + records[..position]
+            .iter()
+            .map(|r| align_64(r.content_size))
+            .sum::<u64>();
+    if offset + record.content_size > file_size {
+        bail!(
+            "ctr extract-content: content {index} runs past the end of the CIA at 0x{:X}",
+            offset + record.content_size
+        );
+    }
+    debug!(
+        "Content {:08x} (index {index}): 0x{:X} bytes at 0x{offset:X}",
+        record.content_id, record.content_size
+    );
+
+    let title_key = match (decrypt, record.content_type.is_encrypted()) {
+        (false, _) => None,
+        (true, false) => {
+            info!("Content {index} is not flagged encrypted, copying it as stored");
+            None
+        }
+        (true, true) => {
+            if !record.content_size.is_multiple_of(16) {
+                bail!(
+                    "ctr extract-content: encrypted content {index} is 0x{:X} bytes, not a whole number of AES blocks",
+                    record.content_size
+                );
+            }
+            Some(derive_title_key_from_ticket(
+                &mut Cursor::new(&preamble),
+                ticket_start,
+            )?)
+        }
+    };
+
+    let write_path = scratch_output_path(output)?;
+    let mut out = BufWriter::new(File::create(&write_path).await?);
+    in_file.seek(SeekFrom::Start(offset)).await?;
+    progress.start(record.content_size, "Extracting content");
+    let mut buf = vec![0u8; CONTENT_COPY_BUF];
+    let mut iv = gen_iv(index);
+    let mut remaining = record.content_size;
+    while remaining > 0 {
+        if cancel.is_cancelled() {
+            return Err(NintendoCTRError::Cancelled.into());
+        }
+        let n = remaining.min(buf.len() as u64) as usize;
+        in_file.read_exact(&mut buf[..n]).await?;
+        if let Some(key) = &title_key {
+            decrypt_cbc_chunk(key, &mut iv, &mut buf[..n])?;
+        }
+        out.write_all(&buf[..n]).await?;
+        progress.inc(n as u64);
+        remaining -= n as u64;
+    }
+    out.flush().await?;
+    out.get_ref().sync_all().await?;
+    drop(out);
+    progress.finish();
+    publish_temp(write_path, output, overwrite)?;
+
+    Ok(ExtractedContent {
+        content_id: record.content_id,
+        content_index: index,
+        size: record.content_size,
+        decrypted: title_key.is_some(),
+    })
+}
+
+/// Decrypt one chunk of an AES-CBC stream in place and advance `iv` to
+/// the chunk's last ciphertext block, so the next chunk continues it.
+fn decrypt_cbc_chunk(key: &[u8; 16], iv: &mut [u8; 16], chunk: &mut [u8]) -> Result<()> {
+    let next_iv: [u8; 16] = chunk[chunk.len() - 16..].try_into()?;
+    cbc_decrypt(key, iv, chunk)?;
+    *iv = next_iv;
+    Ok(())
+}
+
+/// Write `body` followed by the certificates whose names start with each
+/// of `prefixes`, in that order, to a scratch file beside `path` that the
+/// caller publishes.
+async fn write_with_certs(
+    path: &Path,
+    body: &[u8],
+    chain: &[Certificate],
+    prefixes: &[&str],
+) -> Result<ScratchPath> {
+    let mut bytes = Cursor::new(body.to_vec());
+    bytes.set_position(body.len() as u64);
+    for prefix in prefixes {
+        let cert = chain
+            .iter()
+            .find(|cert| cert.name.starts_with(prefix.as_bytes()));
+        match cert {
+            Some(cert) => cert.write_options(&mut bytes, Endian::Big, ())?,
+            None => debug!(
+                "CIA chain has no {prefix} certificate for {}",
+                path.display()
+            ),
+        }
+    }
+    let write_path = scratch_output_path(path)?;
+    tokio::fs::write(&write_path, bytes.into_inner()).await?;
+    Ok(write_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::models::ticket::Ticket;
+    use crate::nintendo::ctr::models::title_metadata::TitleMetadata;
+    use crate::nintendo::ctr::test_fixtures::{
+        SYNTH_CIA_TITLE_ID, synth_cia, synth_encrypted_cia_multi_content,
+    };
+    use crate::nintendo::ctr::title_key::CommonKeyIndex;
+    use crate::nintendo::ctr::util::fs::{find_title_file, find_tmd_file};
+    use crate::nintendo::ctr::{CdnNameFrom, CdnToCiaOptions, convert_cdn_to_cia};
+    use crate::util::{ConflictPolicy, NoProgress};
+
+    fn cdn_opts(cdn_dir: PathBuf, output: PathBuf) -> CdnToCiaOptions {
+        CdnToCiaOptions {
+            cdn_dir,
+            output: Some(output),
+            cleanup: false,
+            recursive: false,
+            ensure_ticket_exists: false,
+            decrypt: false,
+            compress: false,
+            output_dir: None,
+            on_conflict: ConflictPolicy::Error,
+            name_from: CdnNameFrom::Dir,
+            verify_after: false,
+            fix_content_size: false,
+            tmd_version: None,
+            common_key_index: CommonKeyIndex::Auto,
+            title_key: None,
+            raw_certs: false,
+            ticket: None,
+            output_template: None,
+            region: None,
+            content_concurrency: 1,
+            seed_retries: crate::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+            fail_fast: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn extracts_a_cdn_directory_that_rebuilds_the_cia() {
+        let (dir, cia_path, _) = synth_cia(0x2000);
+        let out_dir = dir.path().join("cdn");
+
+        let extracted = extract_contents(
+            &cia_path,
+            &out_dir,
+            CdnLayout::default(),
+            false,
+            &NoProgress,
+        )
+        .await
+        .unwrap();
+        assert_eq!(extracted.title_id, SYNTH_CIA_TITLE_ID);
+        assert_eq!(extracted.contents, vec![out_dir.join("00000000")]);
+
+        let source = std::fs::read(&cia_path).unwrap();
+        let content = std::fs::read(&extracted.contents[0]).unwrap();
+        assert_eq!(content.len(), 0x2000);
+        assert!(source.ends_with(&content));
+
+        let tmd = std::fs::read(&extracted.tmd).unwrap();
+        TitleMetadata::read_be(&mut Cursor::new(&tmd)).unwrap();
+        let ticket = std::fs::read(&extracted.ticket).unwrap();
+        let ticket = Ticket::read_be(&mut Cursor::new(&ticket)).unwrap();
+        assert_eq!(ticket.ticket_data.title_id, SYNTH_CIA_TITLE_ID);
+
+        let rebuilt = dir.path().join("rebuilt.cia");
+        convert_cdn_to_cia(cdn_opts(out_dir, rebuilt.clone()), &NoProgress, &NoProgress)
+            .await
+            .unwrap();
+        let rebuilt = std::fs::read(&rebuilt).unwrap();
+        assert!(rebuilt.ends_with(&content));
+    }
+
+    #[tokio::test]
+    async fn cdn_round_trip_rebuilds_an_identical_cia() {
+        let (dir, source, _) = synth_cia(0x1800);
+        let layout = CdnLayout {
+            versioned_tmd: true,
+            tik_ticket: true,
+        };
+        // The fixture's chain order differs from what cdn-to-cia writes,
+        // so normalize through one round trip before diffing.
+        let first = dir.path().join("first.cia");
+        let second = dir.path().join("second.cia");
+        for (input, cdn, output) in [(&source, "cdn1", &first), (&first, "cdn2", &second)] {
+            let cdn = dir.path().join(cdn);
+            let extracted = extract_contents(input, &cdn, layout, false, &NoProgress)
+                .await
+                .unwrap();
+            assert_eq!(extracted.tmd, cdn.join("tmd.256"));
+            assert_eq!(
+                extracted.ticket,
+                cdn.join(format!("{SYNTH_CIA_TITLE_ID:016X}.tik"))
+            );
+            assert_eq!(find_tmd_file(&cdn, None).await.unwrap(), extracted.tmd);
+            assert_eq!(find_title_file(&cdn).await.unwrap(), extracted.ticket);
+            convert_cdn_to_cia(cdn_opts(cdn, output.clone()), &NoProgress, &NoProgress)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            std::fs::read(&first).unwrap(),
+            std::fs::read(&second).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_overwrite_without_force() {
+        let (dir, cia_path, _) = synth_cia(0x400);
+        let out_dir = dir.path().join("cdn");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        std::fs::write(out_dir.join("tmd"), b"keep").unwrap();
+
+        let err = extract_contents(
+            &cia_path,
+            &out_dir,
+            CdnLayout::default(),
+            false,
+            &NoProgress,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{err}");
+        assert_eq!(std::fs::read(out_dir.join("tmd")).unwrap(), b"keep");
+        assert!(!out_dir.join("00000000").exists());
+
+        extract_contents(&cia_path, &out_dir, CdnLayout::default(), true, &NoProgress)
+            .await
+            .unwrap();
+        assert_ne!(std::fs::read(out_dir.join("tmd")).unwrap(), b"keep");
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_extraction_publishes_nothing() {
+        let (dir, cia_path, _) = synth_cia(0x400);
+        let out_dir = dir.path().join("cdn");
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let err = extract_contents_cancellable(
+            &cia_path,
+            &out_dir,
+            CdnLayout::default(),
+            false,
+            &NoProgress,
+            &cancel,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("cancel"), "{err}");
+        assert_eq!(std::fs::read_dir(&out_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn extracts_one_content_by_index() {
+        let (dir, cia_path, contents) = synth_encrypted_cia_multi_content(&[0x10, 0x20, 0x30]);
+        let output = dir.path().join("content.app");
+
+        let extracted = extract_content(&cia_path, 1, &output, false, false, &NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(
+            extracted,
+            ExtractedContent {
+                content_id: 0x20,
+                content_index: 1,
+                size: contents[1].len() as u64,
+                decrypted: false,
+            }
+        );
+        assert_eq!(std::fs::read(&output).unwrap(), contents[1]);
+
+        let err = extract_content(&cia_path, 2, &output, false, false, &NoProgress)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{err}");
+        let err = extract_content(&cia_path, 7, &output, false, true, &NoProgress)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("no content with index 7 (present: 0, 1, 2)"),
+            "{err}"
+        );
+        assert_eq!(std::fs::read(&output).unwrap(), contents[1]);
+    }
+
+    #[tokio::test]
+    async fn decrypt_removes_the_title_key_layer() {
+        use crate::nintendo::ctr::decrypt::util::common_key;
+        use crate::nintendo::ctr::test_fixtures::{
+            cia_with_content, make_ncch_header_bytes, make_tmd, serialized_size, write_cia_file,
+        };
+        use aes::cipher::{BlockModeEncrypt, KeyIvInit};
+
+        let dir = tempfile::tempdir().unwrap();
+        let plain = make_ncch_header_bytes(SYNTH_CIA_TITLE_ID);
+        let mut cia = cia_with_content(SYNTH_CIA_TITLE_ID, plain.clone(), None);
+        let mut title_key: [u8; 16] = cia.ticket.ticket_data.title_key[..].try_into().unwrap();
+        let mut tid_iv = [0u8; 16];
+        tid_iv[..8].copy_from_slice(&SYNTH_CIA_TITLE_ID.to_be_bytes());
+        cbc_decrypt(
+            common_key(cia.ticket.ticket_data.common_key_index).unwrap(),
+            &tid_iv,
+            &mut title_key,
+        )
+        .unwrap();
+        let mut encrypted = plain.clone();
+        let mut cipher =
+            cbc::Encryptor::<aes::Aes128>::new_from_slices(&title_key, &gen_iv(0)).unwrap();
+        for block in encrypted.chunks_exact_mut(16) {
+            cipher.encrypt_block(<&mut aes::cipher::array::Array<_, _>>::try_from(block).unwrap());
+        }
+        cia.tmd = make_tmd(SYNTH_CIA_TITLE_ID, vec![(0, 0, encrypted.clone(), [0; 32])]);
+        cia.tmd.content_chunk_records[0]
+            .content_type
+            .set_encrypted(true);
+        cia.content_data = encrypted.clone();
+        cia.header.tmd_size = serialized_size(&cia.tmd);
+        let input = dir.path().join("encrypted.cia");
+        write_cia_file(&input, &cia);
+
+        let stored = dir.path().join("stored.app");
+        extract_content(&input, 0, &stored, false, false, &NoProgress)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&stored).unwrap(), encrypted);
+
+        let decrypted = dir.path().join("decrypted.app");
+        let extracted = extract_content(&input, 0, &decrypted, true, false, &NoProgress)
+            .await
+            .unwrap();
+        assert!(extracted.decrypted);
+        assert_eq!(std::fs::read(&decrypted).unwrap(), plain);
+    }
+
+    #[test]
+    fn cbc_chunks_chain_into_one_stream() {
+        let key = [7u8; 16];
+        let mut whole: Vec<u8> = (0..64).collect();
+        let mut chunked = whole.clone();
+        cbc_decrypt(&key, &gen_iv(3), &mut whole).unwrap();
+
+        let mut iv = gen_iv(3);
+        let (first, second) = chunked.split_at_mut(32);
+        decrypt_cbc_chunk(&key, &mut iv, first).unwrap();
+        decrypt_cbc_chunk(&key, &mut iv, second).unwrap();
+        assert_eq!(chunked, whole);
+    }
+}
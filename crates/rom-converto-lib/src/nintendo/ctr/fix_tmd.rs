@@ -0,0 +1,284 @@
+//! Refreshes the hashes a TMD holds for its contents.
+//!
+//! Each content chunk record carries the SHA-256 of its decrypted content,
+//! each content info record hashes a run of chunk records, and the header
+//! hashes the info records. [`update_tmd_hashes`] rebuilds that chain from
+//! new content hashes for the CIA decryptor and encryptor; [`fix_tmd`]
+//! applies it to a TMD file next to hand-edited contents. The TMD
+//! signature covers the header hash, so a signed TMD stops verifying once
+//! anything changes.
+
+use crate::nintendo::ctr::models::title_metadata::{ContentInfoRecord, TitleMetadata};
+use anyhow::{Context, Result, bail};
+use binrw::{BinRead, BinWrite, Endian};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// What [`fix_tmd`] did to a TMD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixTmdReport {
+    pub title_id: u64,
+    /// Contents hashed.
+    pub contents: usize,
+    /// Content ids whose recorded hash was wrong, in TMD order.
+    pub updated: Vec<u32>,
+    /// Whether the TMD file was rewritten. False when every hash in the
+    /// chain already matched.
+    pub rewritten: bool,
+}
+
+/// Store `content_hashes` in the chunk records of `tmd`, in record order,
+/// then recompute the hashes of the content info records that cover any
+/// and the header's `content_info_records_hash` over all of them.
+pub(crate) fn update_tmd_hashes(
+    tmd: &mut TitleMetadata,
+    content_hashes: &[[u8; 32]],
+) -> Result<()> {
+    if content_hashes.len() != tmd.content_chunk_records.len() {
+        bail!(
+            "hashed {} contents but TMD declares {} records",
+            content_hashes.len(),
+            tmd.content_chunk_records.len()
+        );
+    }
+
+    for (record, hash) in tmd.content_chunk_records.iter_mut().zip(content_hashes) {
+        record.hash = hash.to_vec();
+    }
+
+    for content_info_record in &mut tmd.content_info_records {
+        let start = content_info_record.content_index_offset as usize;
+        let count = content_info_record.content_command_count as usize;
+        if count == 0 {
+            // Unused records stay as stored, all zeros in Nintendo's TMDs.
+            continue;
+        }
+        let end = start + count;
+        let Some(chunks) = tmd.content_chunk_records.get(start..end) else {
+            bail!(
+                "content info record covers chunk records {start}..{end} but TMD has {}",
+                tmd.content_chunk_records.len()
+            );
+        };
+        let mut hasher = Sha256::new();
+        for chunk in chunks {
+            let mut buf = Cursor::new(Vec::new());
+            chunk.write_be(&mut buf)?;
+            hasher.update(buf.get_ref());
+        }
+        content_info_record.hash = hasher.finalize().to_vec();
+    }
+
+    let mut hasher = Sha256::new();
+    for content_info_record in &tmd.content_info_records {
+        hash_content_info_record(&mut hasher, content_info_record)?;
+    }
+    tmd.header.content_info_records_hash = hasher.finalize().to_vec();
+    Ok(())
+}
+
+fn hash_content_info_record(hasher: &mut Sha256, record: &ContentInfoRecord) -> Result<()> {
+    let mut cursor = Cursor::new(Vec::new());
+    record.content_index_offset.write_be(&mut cursor)?;
+    record.content_command_count.write_be(&mut cursor)?;
+    cursor.get_mut().extend_from_slice(&record.hash);
+    hasher.update(cursor.get_ref());
+    Ok(())
+}
+
+/// Hash every content of the TMD at `tmd_path`, read from `content_dir`
+/// by content id (`00000000`, `00000001`, ...), and rewrite the TMD in
+/// place with the new chunk, info record, and header hashes. Certificates
+/// or other bytes after the TMD are kept. Contents must be decrypted and
+/// the size the TMD declares, since the hashes cover the plaintext; a
+/// record flagged encrypted is refused.
+pub fn fix_tmd(tmd_path: &Path, content_dir: &Path) -> Result<FixTmdReport> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(tmd_path)
+        .with_context(|| format!("ctr fix-tmd: open {}", tmd_path.display()))?;
+    let mut tmd = TitleMetadata::read_options(&mut file, Endian::Big, ())
+        .with_context(|| format!("ctr fix-tmd: parse TMD {}", tmd_path.display()))?;
+    let parsed_size = file.stream_position()?;
+
+    let mut hashes = Vec::with_capacity(tmd.content_chunk_records.len());
+    let mut updated = Vec::new();
+    for record in &tmd.content_chunk_records {
+        if record.content_type.is_encrypted() {
+            bail!(
+                "content {:08x} is flagged encrypted; its TMD hash covers the decrypted data, so decrypt it first",
+                record.content_id
+            );
+        }
+        let content_path = content_dir.join(format!("{:08x}", record.content_id));
+        let mut content = File::open(&content_path).with_context(|| {
+            format!(
+                "content {:08x} is missing: {}",
+                record.content_id,
+                content_path.display()
+            )
+        })?;
+        let mut hasher = Sha256::new();
+        let size = std::io::copy(&mut content, &mut hasher)?;
+        if size != record.content_size {
+            bail!(
+                "content {:08x} ({}) size mismatch: TMD declares {} bytes but file is {} bytes",
+                record.content_id,
+                content_path.display(),
+                record.content_size,
+                size,
+            );
+        }
+        let hash: [u8; 32] = hasher.finalize().into();
+        if record.hash != hash {
+            updated.push(record.content_id);
+        }
+        hashes.push(hash);
+    }
+
+    let before = tmd.header.content_info_records_hash.clone();
+    let info_before: Vec<Vec<u8>> = tmd
+        .content_info_records
+        .iter()
+        .map(|record| record.hash.clone())
+        .collect();
+    update_tmd_hashes(&mut tmd, &hashes)?;
+    let unchanged = updated.is_empty()
+        && tmd.header.content_info_records_hash == before
+        && tmd
+            .content_info_records
+            .iter()
+            .map(|record| &record.hash)
+            .eq(info_before.iter());
+
+    let report = FixTmdReport {
+        title_id: tmd.header.title_id,
+        contents: hashes.len(),
+        updated,
+        rewritten: !unchanged,
+    };
+    if unchanged {
+        return Ok(report);
+    }
+
+    let mut buf = Vec::with_capacity(parsed_size as usize);
+    tmd.write_options(&mut Cursor::new(&mut buf), Endian::Big, ())?;
+    if buf.len() as u64 != parsed_size {
+        bail!(
+            "ctr fix-tmd: rewritten TMD is 0x{:X} bytes, expected 0x{parsed_size:X}",
+            buf.len()
+        );
+    }
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&buf)?;
+    file.flush()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::test_fixtures::{append_be, make_cert, make_tmd};
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+
+    fn write_title(dir: &Path, contents: &[Vec<u8>]) -> TitleMetadata {
+        let records = contents
+            .iter()
+            .enumerate()
+            .map(|(i, data)| (i as u32, i as u16, data.clone(), sha256(data)))
+            .collect();
+        let tmd = make_tmd(0x0004_0000_0012_3400, records);
+        for (i, data) in contents.iter().enumerate() {
+            std::fs::write(dir.join(format!("{i:08x}")), data).unwrap();
+        }
+        let mut buf = Vec::new();
+        append_be(&mut buf, &tmd);
+        append_be(&mut buf, &make_cert(b"CP0000000b", 0xBB));
+        std::fs::write(dir.join("tmd"), &buf).unwrap();
+        tmd
+    }
+
+    #[test]
+    fn rehashes_an_edited_content_and_keeps_the_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = vec![vec![0x11; 0x200], vec![0x22; 0x80]];
+        write_title(dir.path(), &contents);
+        let before = std::fs::read(dir.path().join("tmd")).unwrap();
+        let edited = vec![0x33; 0x80];
+        std::fs::write(dir.path().join("00000001"), &edited).unwrap();
+
+        let report = fix_tmd(&dir.path().join("tmd"), dir.path()).unwrap();
+        assert_eq!(report.contents, 2);
+        assert_eq!(report.updated, [1]);
+        assert!(report.rewritten);
+
+        // The same TMD built from the edited contents has the same hashes.
+        let expected = make_tmd(
+            0x0004_0000_0012_3400,
+            vec![
+                (0, 0, contents[0].clone(), sha256(&contents[0])),
+                (1, 1, edited.clone(), sha256(&edited)),
+            ],
+        );
+        let after = std::fs::read(dir.path().join("tmd")).unwrap();
+        assert_eq!(after.len(), before.len());
+        let mut cursor = Cursor::new(&after);
+        let tmd = TitleMetadata::read_options(&mut cursor, Endian::Big, ()).unwrap();
+        assert_eq!(tmd.content_chunk_records[1].hash, sha256(&edited));
+        assert_eq!(
+            tmd.content_info_records[0].hash,
+            expected.content_info_records[0].hash
+        );
+        assert_eq!(
+            tmd.header.content_info_records_hash,
+            expected.header.content_info_records_hash
+        );
+        let tmd_len = cursor.position() as usize;
+        assert_eq!(after[tmd_len..], before[tmd_len..]);
+    }
+
+    #[test]
+    fn leaves_a_matching_tmd_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        write_title(dir.path(), &[vec![0x44; 0x100]]);
+        let before = std::fs::read(dir.path().join("tmd")).unwrap();
+
+        let report = fix_tmd(&dir.path().join("tmd"), dir.path()).unwrap();
+        assert!(report.updated.is_empty());
+        assert!(!report.rewritten);
+        assert_eq!(std::fs::read(dir.path().join("tmd")).unwrap(), before);
+    }
+
+    #[test]
+    fn refuses_missing_resized_and_encrypted_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        write_title(dir.path(), &[vec![0x55; 0x100]]);
+        let tmd_path = dir.path().join("tmd");
+        let before = std::fs::read(&tmd_path).unwrap();
+
+        std::fs::write(dir.path().join("00000000"), vec![0x55; 0x101]).unwrap();
+        let err = fix_tmd(&tmd_path, dir.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("size mismatch"), "{err:#}");
+
+        std::fs::remove_file(dir.path().join("00000000")).unwrap();
+        let err = fix_tmd(&tmd_path, dir.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("is missing"), "{err:#}");
+
+        let mut tmd =
+            TitleMetadata::read_options(&mut Cursor::new(&before), Endian::Big, ()).unwrap();
+        tmd.content_chunk_records[0]
+            .content_type
+            .set_encrypted(true);
+        let mut buf = Vec::new();
+        append_be(&mut buf, &tmd);
+        std::fs::write(&tmd_path, &buf).unwrap();
+        let err = fix_tmd(&tmd_path, dir.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("flagged encrypted"), "{err:#}");
+    }
+}
@@ -0,0 +1,104 @@
+//! Container detection for 3DS images by header magic. File extensions
+//! are only a hint (a CIA saved as `.3ds`, a dump named `.bin`), so every
+//! entry point that routes on format sniffs the first sector instead.
+
+use crate::nintendo::ctr::constants::NCCH_MAGIC_OFFSET;
+use crate::nintendo::ctr::info::CtrFormat;
+use crate::nintendo::ctr::models::cia::CIA_HEADER_SIZE;
+use crate::nintendo::ctr::z3ds::models::underlying_magic;
+use anyhow::{Result, bail};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Bytes read to identify a container: the NCSD/NCCH magic sits at 0x100
+/// and a CIA starts with its little-endian header size.
+pub const FORMAT_PROBE_SIZE: usize = 0x200;
+
+/// A container [`detect_format`] accepted. Unlike [`CtrFormat`] it has
+/// no unknown case, so callers routing on it match exhaustively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownFormat {
+    Cia,
+    Ncsd,
+    Ncch,
+}
+
+/// Identify a CIA, NCSD or NCCH from its leading bytes. Shorter inputs
+/// are matched on whatever fields they cover.
+pub fn sniff_format(header: &[u8]) -> CtrFormat {
+    if let Some(magic) = header.get(NCCH_MAGIC_OFFSET..NCCH_MAGIC_OFFSET + 4) {
+        if magic == underlying_magic::NCSD {
+            return CtrFormat::Ncsd;
+        }
+        if magic == underlying_magic::NCCH {
+            return CtrFormat::Ncch;
+        }
+    }
+    match header.get(0..4) {
+        Some(size) if u32::from_le_bytes(size.try_into().unwrap()) == CIA_HEADER_SIZE => {
+            CtrFormat::Cia
+        }
+        _ => CtrFormat::Unknown,
+    }
+}
+
+/// Read the head of `path` and identify it, erroring on anything that is
+/// not a CIA, NCSD or NCCH.
+pub async fn detect_format(path: &Path) -> Result<KnownFormat> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut header = Vec::with_capacity(FORMAT_PROBE_SIZE);
+    file.take(FORMAT_PROBE_SIZE as u64)
+        .read_to_end(&mut header)
+        .await?;
+    match sniff_format(&header) {
+        CtrFormat::Cia => Ok(KnownFormat::Cia),
+        CtrFormat::Ncsd => Ok(KnownFormat::Ncsd),
+        CtrFormat::Ncch => Ok(KnownFormat::Ncch),
+        CtrFormat::Unknown => bail!(
+            "unrecognized format in {}: no NCSD/NCCH magic at 0x100 and not a CIA file",
+            path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::test_fixtures::{
+        make_ncch_header_bytes, minimal_cia, write_cia_file,
+    };
+
+    #[test]
+    fn sniffs_each_container_by_magic() {
+        assert_eq!(sniff_format(&make_ncch_header_bytes(0)), CtrFormat::Ncch);
+
+        let mut ncsd = vec![0u8; FORMAT_PROBE_SIZE];
+        ncsd[0x100..0x104].copy_from_slice(b"NCSD");
+        assert_eq!(sniff_format(&ncsd), CtrFormat::Ncsd);
+
+        assert_eq!(sniff_format(&CIA_HEADER_SIZE.to_le_bytes()), CtrFormat::Cia);
+        assert_eq!(sniff_format(b"3DSX"), CtrFormat::Unknown);
+        assert_eq!(sniff_format(&[]), CtrFormat::Unknown);
+    }
+
+    #[tokio::test]
+    async fn detection_ignores_the_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let cia = dir.path().join("game.3ds");
+        write_cia_file(&cia, &minimal_cia());
+        assert_eq!(detect_format(&cia).await.unwrap(), KnownFormat::Cia);
+
+        let ncch = dir.path().join("game.bin");
+        std::fs::write(&ncch, make_ncch_header_bytes(0)).unwrap();
+        assert_eq!(detect_format(&ncch).await.unwrap(), KnownFormat::Ncch);
+    }
+
+    #[tokio::test]
+    async fn unknown_magic_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cia");
+        std::fs::write(&path, vec![0xAAu8; FORMAT_PROBE_SIZE]).unwrap();
+        let err = detect_format(&path).await.unwrap_err();
+        assert!(err.to_string().contains("unrecognized format"));
+    }
+}
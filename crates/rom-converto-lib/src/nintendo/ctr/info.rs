@@ -4,19 +4,22 @@
 //! per-format metadata the verify path exposes plus a parsed SMDH (and
 //! its 48x48 icon as PNG) when one is available. CIA inputs without a
 //! MetaData block fall back to ExeFS extraction from the boot content.
+//! CIA inputs also surface their ticket's license and install limits.
 
 use crate::info::Image;
 use crate::nintendo::ctr::constants::{
-    CTR_MEDIA_UNIT_SIZE, NCCH_FLAGS7_SEED_CRYPTO, NCCH_MAGIC, NCCH_MAGIC_OFFSET,
-    NCSD_PARTITION_COUNT, NCSD_PARTITION_ENTRY_SIZE, NCSD_PARTITION_TABLE_OFFSET,
-    NCSD_TITLE_ID_OFFSET, TMD_CONTENT_RECORD_SIZE, TMD_CONTENT_RECORDS_OFFSET,
+    CTR_MEDIA_UNIT_SIZE, NCCH_FLAGS7_SEED_CRYPTO, NCCH_MAGIC_OFFSET, NCSD_PARTITION_COUNT,
+    NCSD_PARTITION_ENTRY_SIZE, NCSD_PARTITION_TABLE_OFFSET, NCSD_TITLE_ID_OFFSET,
+    TMD_CONTENT_RECORD_SIZE, TMD_CONTENT_RECORDS_OFFSET,
 };
 use crate::nintendo::ctr::decrypt::util::{decrypt_first_ncch_block, derive_title_key_from_ticket};
 use crate::nintendo::ctr::exefs::read_icon_section;
+use crate::nintendo::ctr::format::{FORMAT_PROBE_SIZE, sniff_format};
 use crate::nintendo::ctr::models::cia::{CIA_HEADER_SIZE, CiaHeader, MetaData};
 use crate::nintendo::ctr::models::ncch_header::NcchHeader;
 use crate::nintendo::ctr::models::smdh::{AgeRating, SMDH_LARGE_ICON_DIM, Smdh};
-use crate::nintendo::ctr::models::title_metadata::ContentChunkRecord;
+use crate::nintendo::ctr::models::ticket::Ticket;
+use crate::nintendo::ctr::models::title_metadata::{ContentChunkRecord, TitleMetadata};
 use crate::nintendo::ctr::util::align_64;
 use crate::nintendo::ctr::z3ds::models::{
     Z3DS_HEADER_SIZE, Z3DS_MAGIC, Z3dsHeader, underlying_magic,
@@ -49,8 +52,43 @@ pub struct CtrInfo {
     pub small_icon: Option<Image>,
     #[serde(default)]
     pub compressed: bool,
+    /// The ticket's license fields; CIA inputs only.
+    #[serde(default)]
+    pub ticket: Option<CtrTicketInfo>,
+}
+
+/// License and install restrictions a CIA's ticket carries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CtrTicketInfo {
+    pub ticket_id: String,
+    /// Zero for a ticket valid on every console.
+    pub console_id: u32,
+    pub license_type: u8,
+    /// Zero for tickets not tied to an eShop account.
+    pub eshop_account_id: u32,
+    pub audit: u8,
+    /// The set entries of the ticket's limit table; empty when the title
+    /// has no play restrictions.
+    pub limits: Vec<CtrTicketLimit>,
 }
 
+/// One entry of a ticket's limit table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CtrTicketLimit {
+    /// The entry's type word as stored.
+    pub limit_type: u32,
+    /// `play_minutes`, `launch_count`, or `unknown`.
+    pub kind: String,
+    pub value: u32,
+}
+
+/// Limit type of a total play time cap, in minutes.
+const TICKET_LIMIT_PLAY_MINUTES: u32 = 1;
+/// Limit type of a launch count cap.
+const TICKET_LIMIT_LAUNCH_COUNT: u32 = 4;
+/// Types that mark an unused entry.
+const TICKET_LIMIT_NONE: [u32; 2] = [0, 3];
+
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum CtrFormat {
@@ -94,7 +132,7 @@ pub fn read_info(path: &Path) -> Result<CtrInfo> {
         .len();
 
     let mut file = File::open(path)?;
-    let mut probe = [0u8; 0x104];
+    let mut probe = [0u8; FORMAT_PROBE_SIZE];
     let n = file.read(&mut probe)?;
     file.seek(SeekFrom::Start(0))?;
 
@@ -106,25 +144,15 @@ pub fn read_info(path: &Path) -> Result<CtrInfo> {
         return read_z3ds_info(path, physical_bytes);
     }
 
-    // NCSD / NCCH have magic at 0x100; CIA has a 4-byte header_size at 0.
-    if n >= 0x104 {
-        let magic = &probe[0x100..0x104];
-        if magic == NCCH_MAGIC.as_bytes() {
-            return read_ncch_info(path, physical_bytes);
-        }
-        if magic == b"NCSD" {
-            return read_ncsd_info(path, physical_bytes);
-        }
+    match sniff_format(&probe[..n]) {
+        CtrFormat::Ncch => read_ncch_info(path, physical_bytes),
+        CtrFormat::Ncsd => read_ncsd_info(path, physical_bytes),
+        CtrFormat::Cia => read_cia_info(path, physical_bytes),
+        CtrFormat::Unknown => Err(anyhow!(
+            "ctr info: unrecognized format at {}",
+            path.display()
+        )),
     }
-    let cia_hdr = u32::from_le_bytes(probe[0..4].try_into()?);
-    if cia_hdr == CIA_HEADER_SIZE {
-        return read_cia_info(path, physical_bytes);
-    }
-
-    Err(anyhow!(
-        "ctr info: unrecognized format at {}",
-        path.display()
-    ))
 }
 
 fn read_z3ds_info(path: &Path, physical_bytes: u64) -> Result<CtrInfo> {
@@ -182,6 +210,18 @@ fn read_cia_info(path: &Path, physical_bytes: u64) -> Result<CtrInfo> {
     let content_end = content_start + cia_header.content_size;
     let meta_start = align_64(content_end);
 
+    reader.seek(SeekFrom::Start(tmd_start))?;
+    if let Ok(tmd) = TitleMetadata::read(&mut reader) {
+        let mismatch = cia_header
+            .check_content_indexes(tmd.content_chunk_records.iter().map(|r| r.content_index));
+        if !mismatch.is_empty() {
+            log::warn!(
+                "{}: CIA content index bitmap {mismatch}; emulators may skip content",
+                path.display()
+            );
+        }
+    }
+
     let first_chunk = read_first_content_chunk(&mut reader, tmd_start)?;
     let content_encrypted = first_chunk.content_type.is_encrypted();
 
@@ -219,6 +259,15 @@ fn read_cia_info(path: &Path, physical_bytes: u64) -> Result<CtrInfo> {
     };
     let smdh_info = smdh.map(smdh_to_info);
 
+    reader.seek(SeekFrom::Start(ticket_start))?;
+    let ticket = match Ticket::read(&mut reader) {
+        Ok(ticket) => Some(ticket_to_info(&ticket)),
+        Err(e) => {
+            log::debug!("ctr info: ticket read skipped ({})", e);
+            None
+        }
+    };
+
     Ok(CtrInfo {
         format: CtrFormat::Cia,
         compressed: false,
@@ -237,6 +286,7 @@ fn read_cia_info(path: &Path, physical_bytes: u64) -> Result<CtrInfo> {
         smdh: smdh_info,
         icon,
         small_icon,
+        ticket,
     })
 }
 
@@ -304,6 +354,7 @@ fn read_ncsd_info(path: &Path, physical_bytes: u64) -> Result<CtrInfo> {
         smdh: smdh_info,
         icon,
         small_icon,
+        ticket: None,
     })
 }
 
@@ -353,6 +404,7 @@ fn read_ncch_info(path: &Path, physical_bytes: u64) -> Result<CtrInfo> {
         smdh: smdh_info,
         icon,
         small_icon,
+        ticket: None,
     })
 }
 
@@ -366,7 +418,7 @@ struct NcchSummary {
 
 /// Detect NCCH seed-crypto and, when present, resolve the seed from a local
 /// `seeddb.bin` (offline). Returns `(seed_crypto, seed_found, derived_keyy)`.
-fn seed_fields(hdr: &NcchHeader) -> (bool, Option<bool>, Option<String>) {
+pub(crate) fn seed_fields(hdr: &NcchHeader) -> (bool, Option<bool>, Option<String>) {
     if (hdr.flags[7] & NCCH_FLAGS7_SEED_CRYPTO) == 0 {
         return (false, None, None);
     }
@@ -443,6 +495,44 @@ fn read_exefs_icon_as_smdh<R: Read + Seek>(
     Smdh::parse(&icon_bytes)
 }
 
+fn ticket_to_info(ticket: &Ticket) -> CtrTicketInfo {
+    let data = &ticket.ticket_data;
+    CtrTicketInfo {
+        ticket_id: format!("{:016X}", data.ticket_id),
+        console_id: data.console_id,
+        license_type: data.license_type,
+        eshop_account_id: data.eshop_account_id,
+        audit: data.audit,
+        limits: decode_ticket_limits(&data.limits),
+    }
+}
+
+/// Decode a ticket's 0x40-byte limit table: eight big-endian
+/// `(type, value)` word pairs, the layout the DSi and Wii tickets it
+/// descends from use. Unused entries are dropped.
+fn decode_ticket_limits(limits: &[u8]) -> Vec<CtrTicketLimit> {
+    limits
+        .chunks_exact(8)
+        .filter_map(|entry| {
+            let limit_type = u32::from_be_bytes(entry[0..4].try_into().ok()?);
+            let value = u32::from_be_bytes(entry[4..8].try_into().ok()?);
+            if TICKET_LIMIT_NONE.contains(&limit_type) {
+                return None;
+            }
+            let kind = match limit_type {
+                TICKET_LIMIT_PLAY_MINUTES => "play_minutes",
+                TICKET_LIMIT_LAUNCH_COUNT => "launch_count",
+                _ => "unknown",
+            };
+            Some(CtrTicketLimit {
+                limit_type,
+                kind: kind.to_string(),
+                value,
+            })
+        })
+        .collect()
+}
+
 fn smdh_to_info(s: Smdh) -> CtrSmdhInfo {
     let titles = s
         .titles
@@ -543,6 +633,27 @@ mod tests {
         data
     }
 
+    #[test]
+    fn ticket_limits_decode_set_entries_only() {
+        let mut limits = vec![0u8; 0x40];
+        for (slot, (limit_type, value)) in [(1u32, 60u32), (3, 9), (4, 5), (7, 2)]
+            .into_iter()
+            .enumerate()
+        {
+            limits[slot * 8..slot * 8 + 4].copy_from_slice(&limit_type.to_be_bytes());
+            limits[slot * 8 + 4..slot * 8 + 8].copy_from_slice(&value.to_be_bytes());
+        }
+
+        let decoded = decode_ticket_limits(&limits);
+        let kinds: Vec<(&str, u32)> = decoded.iter().map(|l| (l.kind.as_str(), l.value)).collect();
+        assert_eq!(
+            kinds,
+            [("play_minutes", 60), ("launch_count", 5), ("unknown", 2)]
+        );
+        assert_eq!(decoded[2].limit_type, 7);
+        assert!(decode_ticket_limits(&[0u8; 0x40]).is_empty());
+    }
+
     #[tokio::test]
     async fn read_info_on_compressed_cxi_returns_ncch_compressed() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,499 @@
+//! Crypto report for the NCCH partitions of a 3DS image.
+//!
+//! Reads only the NCCH headers (plus the first CBC block of each CIA
+//! content when the title key layer is on) and reports which key setup
+//! each partition uses, so a user can tell up front whether decrypting
+//! will need seeds, the system fixed key, or nothing at all. The hidden
+//! `dump-keys` command goes one step further and prints the key material
+//! itself for a CIA.
+
+use crate::nintendo::ctr::constants::{
+    CTR_MEDIA_UNIT_SIZE, CTR_NCSD_PARTITIONS, NCCH_FLAGS_EXTRA_CRYPTO_INDEX,
+    NCCH_FLAGS7_SEED_CRYPTO, NCCH_MAGIC, NCCH_MAGIC_OFFSET, NCSD_PARTITION_COUNT,
+    NCSD_PARTITION_ENTRY_SIZE, NCSD_PARTITION_TABLE_OFFSET,
+};
+use crate::nintendo::ctr::decrypt::cia::{NCCH_KEYSLOTS, extra_crypto_index, fixed_crypto_kind};
+use crate::nintendo::ctr::decrypt::util::{decrypt_first_ncch_block, derive_title_key_from_ticket};
+use crate::nintendo::ctr::format::{FORMAT_PROBE_SIZE, sniff_format};
+use crate::nintendo::ctr::info::{CtrFormat, seed_fields};
+use crate::nintendo::ctr::models::cia::CiaFileWithoutContent;
+use crate::nintendo::ctr::models::ncch_header::NcchHeader;
+use crate::nintendo::ctr::util::align_64;
+use crate::nintendo::ctr::z3ds::models::Z3DS_MAGIC;
+use anyhow::{Context, Result, anyhow, bail};
+use binrw::{BinRead, Endian};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NcchCryptoReport {
+    pub format: CtrFormat,
+    pub partitions: Vec<NcchCryptoInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NcchCryptoInfo {
+    /// NCSD partition name, or `Content <index>` for a CIA content.
+    pub partition: String,
+    pub title_id: String,
+    /// CIA only: whether the content sits under the ticket's title key.
+    pub title_key_encrypted: Option<bool>,
+    pub encrypted: bool,
+    pub method: NcchCryptoMethod,
+    pub seed_crypto: bool,
+    pub seed_found: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum NcchCryptoMethod {
+    /// NoCrypto flag set; the partition is already decrypted.
+    None,
+    /// One fixed key for every section. `system` selects the system
+    /// fixed key over the all-zero key.
+    FixedKey { system: bool },
+    /// Keyslot 0x2C for the header area and `slot` for the ExeFS code
+    /// and RomFS (0x2C again when no extra crypto is set).
+    Keyslot { slot: u8 },
+}
+
+impl fmt::Display for NcchCryptoMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NcchCryptoMethod::None => f.write_str("none"),
+            NcchCryptoMethod::FixedKey { system: true } => f.write_str("fixed key (system)"),
+            NcchCryptoMethod::FixedKey { system: false } => f.write_str("fixed key (zero)"),
+            NcchCryptoMethod::Keyslot { slot: 0x2C } => f.write_str("keyslot 0x2C"),
+            NcchCryptoMethod::Keyslot { slot } => write!(f, "keyslot 0x2C + 0x{slot:02X}"),
+        }
+    }
+}
+
+pub fn crypto_method(hdr: &NcchHeader) -> NcchCryptoMethod {
+    if !hdr.is_encrypted() {
+        return NcchCryptoMethod::None;
+    }
+    match fixed_crypto_kind(hdr) {
+        0 => NcchCryptoMethod::Keyslot {
+            slot: NCCH_KEYSLOTS[extra_crypto_index(hdr.flags[NCCH_FLAGS_EXTRA_CRYPTO_INDEX])],
+        },
+        kind => NcchCryptoMethod::FixedKey { system: kind == 2 },
+    }
+}
+
+/// Report the crypto setup of every NCCH partition in a CIA, NCSD or NCCH
+/// file. Nothing is decrypted or written.
+pub fn inspect_ncch(path: &Path) -> Result<NcchCryptoReport> {
+    let file = File::open(path).with_context(|| format!("ctr inspect: open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut probe = [0u8; FORMAT_PROBE_SIZE];
+    let n = reader.read(&mut probe)?;
+    if n < 4 {
+        bail!("ctr inspect: file is too small");
+    }
+
+    if &probe[0..4] == Z3DS_MAGIC.as_slice() {
+        bail!(
+            "{} is Z3DS-compressed; compressed images only hold decrypted NCCHs",
+            path.display()
+        );
+    }
+    match sniff_format(&probe[..n]) {
+        CtrFormat::Ncch => {
+            reader.seek(SeekFrom::Start(0))?;
+            let hdr = read_ncch_header(&mut reader)?;
+            Ok(NcchCryptoReport {
+                format: CtrFormat::Ncch,
+                partitions: vec![partition_info("Main".to_string(), &hdr, None)],
+            })
+        }
+        CtrFormat::Ncsd => inspect_ncsd(&mut reader),
+        CtrFormat::Cia => inspect_cia(&mut reader),
+        CtrFormat::Unknown => Err(anyhow!(
+            "ctr inspect: unrecognized format at {}",
+            path.display()
+        )),
+    }
+}
+
+fn inspect_ncsd<R: Read + Seek>(reader: &mut R) -> Result<NcchCryptoReport> {
+    reader.seek(SeekFrom::Start(NCSD_PARTITION_TABLE_OFFSET as u64))?;
+    let mut table = [0u8; NCSD_PARTITION_COUNT * NCSD_PARTITION_ENTRY_SIZE];
+    reader.read_exact(&mut table)?;
+
+    let mut partitions = Vec::new();
+    for (i, name) in CTR_NCSD_PARTITIONS.iter().enumerate() {
+        let entry = &table[i * NCSD_PARTITION_ENTRY_SIZE..];
+        let offset_mu = u32::from_le_bytes(entry[0..4].try_into()?);
+        let size_mu = u32::from_le_bytes(entry[4..8].try_into()?);
+        if offset_mu == 0 && size_mu == 0 {
+            continue;
+        }
+        reader.seek(SeekFrom::Start(
+            offset_mu as u64 * CTR_MEDIA_UNIT_SIZE as u64,
+        ))?;
+        let hdr = read_ncch_header(reader)
+            .with_context(|| format!("ctr inspect: NCSD partition {i} ({name})"))?;
+        partitions.push(partition_info(name.to_string(), &hdr, None));
+    }
+    if partitions.is_empty() {
+        bail!("ctr inspect: NCSD has no partitions");
+    }
+
+    Ok(NcchCryptoReport {
+        format: CtrFormat::Ncsd,
+        partitions,
+    })
+}
+
+fn inspect_cia<R: Read + Seek>(reader: &mut R) -> Result<NcchCryptoReport> {
+    let layout = read_cia_layout(reader)?;
+    let mut title_key = None;
+    let partitions = cia_content_headers(reader, &layout, &mut title_key)?
+        .into_iter()
+        .map(|content| {
+            partition_info(
+                content_name(content.index),
+                &content.header,
+                Some(content.title_key_encrypted),
+            )
+        })
+        .collect();
+
+    Ok(NcchCryptoReport {
+        format: CtrFormat::Cia,
+        partitions,
+    })
+}
+
+/// Key material of a CIA, for diagnosing decryption failures.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CiaKeyDump {
+    pub title_id: String,
+    pub common_key_index: u8,
+    /// Title key after decrypting the ticket's copy with the common key.
+    pub title_key: String,
+    pub contents: Vec<ContentKeyDump>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentKeyDump {
+    /// `Content <index>`, as in [`NcchCryptoInfo::partition`].
+    pub content: String,
+    pub method: NcchCryptoMethod,
+    /// KeyY from the first 16 bytes of the NCCH header signature.
+    pub key_y: String,
+    /// Seed-derived KeyY for the ExeFS code and RomFS, when seed crypto is
+    /// on and the seed is in the local seeddb.
+    pub seed_key_y: Option<String>,
+}
+
+/// Decrypt a CIA's title key and read each content's NCCH KeyY. Nothing
+/// is decrypted past the first block of each content, and nothing is
+/// written.
+pub fn dump_cia_keys(path: &Path) -> Result<CiaKeyDump> {
+    let file =
+        File::open(path).with_context(|| format!("ctr dump-keys: open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut probe = [0u8; FORMAT_PROBE_SIZE];
+    let n = reader.read(&mut probe)?;
+    if sniff_format(&probe[..n]) != CtrFormat::Cia {
+        bail!("ctr dump-keys: {} is not a CIA", path.display());
+    }
+
+    let layout = read_cia_layout(&mut reader)?;
+    let title_key = derive_title_key_from_ticket(&mut reader, layout.ticket_start)?;
+    let contents = cia_content_headers(&mut reader, &layout, &mut Some(title_key))?
+        .into_iter()
+        .map(|content| {
+            let (_, _, seed_key_y) = seed_fields(&content.header);
+            ContentKeyDump {
+                content: content_name(content.index),
+                method: crypto_method(&content.header),
+                key_y: hex::encode_upper(&content.header.signature[..16]),
+                seed_key_y,
+            }
+        })
+        .collect();
+
+    let ticket = &layout.cia.ticket.ticket_data;
+    Ok(CiaKeyDump {
+        title_id: format!("{:016X}", ticket.title_id),
+        common_key_index: ticket.common_key_index,
+        title_key: hex::encode_upper(title_key),
+        contents,
+    })
+}
+
+struct CiaLayout {
+    cia: CiaFileWithoutContent,
+    ticket_start: u64,
+    content_start: u64,
+}
+
+fn read_cia_layout<R: Read + Seek>(reader: &mut R) -> Result<CiaLayout> {
+    reader.seek(SeekFrom::Start(0))?;
+    let cia = CiaFileWithoutContent::read_options(reader, Endian::Little, ())
+        .context("ctr inspect: parse CIA")?;
+
+    let header = &cia.header;
+    let cert_start = align_64(header.header_size as u64);
+    let ticket_start = align_64(cert_start + header.cert_chain_size as u64);
+    let tmd_start = align_64(ticket_start + header.ticket_size as u64);
+    let content_start = align_64(tmd_start + header.tmd_size as u64);
+    Ok(CiaLayout {
+        cia,
+        ticket_start,
+        content_start,
+    })
+}
+
+struct CiaContentHeader {
+    index: u16,
+    title_key_encrypted: bool,
+    header: NcchHeader,
+}
+
+fn content_name(index: u16) -> String {
+    format!("Content {index:04X}")
+}
+
+/// NCCH header of every CIA content, taking the title key layer off the
+/// first block where it is on. The title key is derived from the ticket
+/// on first use. Contents that are not NCCHs are skipped.
+fn cia_content_headers<R: Read + Seek>(
+    reader: &mut R,
+    layout: &CiaLayout,
+    title_key: &mut Option<[u8; 16]>,
+) -> Result<Vec<CiaContentHeader>> {
+    let mut offset = layout.content_start;
+    let mut headers = Vec::new();
+    for record in &layout.cia.tmd.content_chunk_records {
+        let name = content_name(record.content_index);
+        let title_key_encrypted = record.content_type.is_encrypted();
+        let block = if title_key_encrypted {
+            let key = match *title_key {
+                Some(key) => key,
+                None => {
+                    *title_key.insert(derive_title_key_from_ticket(reader, layout.ticket_start)?)
+                }
+            };
+            decrypt_first_ncch_block(reader, offset, record.content_index, &key)?
+        } else {
+            reader.seek(SeekFrom::Start(offset))?;
+            let mut block = [0u8; 0x200];
+            reader.read_exact(&mut block)?;
+            block
+        };
+        offset += align_64(record.content_size);
+
+        if &block[NCCH_MAGIC_OFFSET..NCCH_MAGIC_OFFSET + 4] != NCCH_MAGIC.as_bytes() {
+            log::debug!("ctr inspect: {name} is not an NCCH, skipping");
+            continue;
+        }
+        let header = NcchHeader::read(&mut Cursor::new(&block))
+            .with_context(|| format!("ctr inspect: parse NCCH header of {name}"))?;
+        headers.push(CiaContentHeader {
+            index: record.content_index,
+            title_key_encrypted,
+            header,
+        });
+    }
+    Ok(headers)
+}
+
+fn partition_info(
+    partition: String,
+    hdr: &NcchHeader,
+    title_key_encrypted: Option<bool>,
+) -> NcchCryptoInfo {
+    let mut tid = hdr.titleid;
+    tid.reverse();
+    let method = crypto_method(hdr);
+    let (seed_crypto, seed_found) = if matches!(method, NcchCryptoMethod::Keyslot { .. }) {
+        let (seed_crypto, seed_found, _) = seed_fields(hdr);
+        (seed_crypto, seed_found)
+    } else {
+        ((hdr.flags[7] & NCCH_FLAGS7_SEED_CRYPTO) != 0, None)
+    };
+    NcchCryptoInfo {
+        partition,
+        title_id: hex::encode_upper(tid),
+        title_key_encrypted,
+        encrypted: hdr.is_encrypted(),
+        method,
+        seed_crypto,
+        seed_found,
+    }
+}
+
+fn read_ncch_header<R: Read>(reader: &mut R) -> Result<NcchHeader> {
+    let mut buf = [0u8; 0x200];
+    reader.read_exact(&mut buf)?;
+    if &buf[NCCH_MAGIC_OFFSET..NCCH_MAGIC_OFFSET + 4] != NCCH_MAGIC.as_bytes() {
+        bail!("ctr inspect: missing NCCH magic");
+    }
+    NcchHeader::read(&mut Cursor::new(&buf)).context("ctr inspect: parse NCCH header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::constants::{
+        NCCH_FLAGS_OFFSET, NCCH_FLAGS7_FIXED_KEY, NCCH_FLAGS7_NOCRYPTO,
+    };
+    use crate::nintendo::ctr::test_fixtures::{
+        cia_with_content, make_ncch_header_bytes, write_cia_file,
+    };
+
+    const TID: u64 = 0x0004_0000_0005_5D00;
+
+    fn header(flags3: u8, flags7: u8) -> NcchHeader {
+        let mut bytes = make_ncch_header_bytes(TID);
+        bytes[NCCH_FLAGS_OFFSET + NCCH_FLAGS_EXTRA_CRYPTO_INDEX] = flags3;
+        bytes[NCCH_FLAGS_OFFSET + 7] = flags7;
+        NcchHeader::read(&mut Cursor::new(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn crypto_method_follows_header_flags() {
+        assert_eq!(
+            crypto_method(&header(0, NCCH_FLAGS7_NOCRYPTO)),
+            NcchCryptoMethod::None
+        );
+        assert_eq!(
+            crypto_method(&header(0, 0)),
+            NcchCryptoMethod::Keyslot { slot: 0x2C }
+        );
+        assert_eq!(
+            crypto_method(&header(1, 0)),
+            NcchCryptoMethod::Keyslot { slot: 0x25 }
+        );
+        assert_eq!(
+            crypto_method(&header(10, 0)),
+            NcchCryptoMethod::Keyslot { slot: 0x18 }
+        );
+        assert_eq!(
+            crypto_method(&header(11, 0)),
+            NcchCryptoMethod::Keyslot { slot: 0x1B }
+        );
+        assert_eq!(
+            crypto_method(&header(0, NCCH_FLAGS7_FIXED_KEY)),
+            NcchCryptoMethod::FixedKey { system: false }
+        );
+    }
+
+    #[test]
+    fn system_title_uses_the_system_fixed_key() {
+        let mut bytes = make_ncch_header_bytes(0x0004_0010_0002_1000);
+        bytes[NCCH_FLAGS_OFFSET + 7] = NCCH_FLAGS7_FIXED_KEY;
+        let hdr = NcchHeader::read(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(
+            crypto_method(&hdr),
+            NcchCryptoMethod::FixedKey { system: true }
+        );
+    }
+
+    #[test]
+    fn inspects_every_ncsd_partition() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.3ds");
+        let mut image = vec![0u8; 0x600];
+        image[NCCH_MAGIC_OFFSET..NCCH_MAGIC_OFFSET + 4].copy_from_slice(b"NCSD");
+        // Main at media unit 1, Manual at media unit 2.
+        image[NCSD_PARTITION_TABLE_OFFSET..NCSD_PARTITION_TABLE_OFFSET + 4]
+            .copy_from_slice(&1u32.to_le_bytes());
+        image[NCSD_PARTITION_TABLE_OFFSET + 4..NCSD_PARTITION_TABLE_OFFSET + 8]
+            .copy_from_slice(&1u32.to_le_bytes());
+        image[NCSD_PARTITION_TABLE_OFFSET + 8..NCSD_PARTITION_TABLE_OFFSET + 12]
+            .copy_from_slice(&2u32.to_le_bytes());
+        image[NCSD_PARTITION_TABLE_OFFSET + 12..NCSD_PARTITION_TABLE_OFFSET + 16]
+            .copy_from_slice(&1u32.to_le_bytes());
+        let mut main = make_ncch_header_bytes(TID);
+        main[NCCH_FLAGS_OFFSET + NCCH_FLAGS_EXTRA_CRYPTO_INDEX] = 1;
+        main[NCCH_FLAGS_OFFSET + 7] = 0;
+        image[0x200..0x400].copy_from_slice(&main);
+        image[0x400..0x600].copy_from_slice(&make_ncch_header_bytes(TID | 1));
+        std::fs::write(&path, &image).unwrap();
+
+        let report = inspect_ncch(&path).unwrap();
+        assert_eq!(report.format, CtrFormat::Ncsd);
+        assert_eq!(report.partitions.len(), 2);
+        assert_eq!(report.partitions[0].partition, "Main");
+        assert!(report.partitions[0].encrypted);
+        assert_eq!(
+            report.partitions[0].method,
+            NcchCryptoMethod::Keyslot { slot: 0x25 }
+        );
+        assert_eq!(report.partitions[1].partition, "Manual");
+        assert!(!report.partitions[1].encrypted);
+        assert_eq!(report.partitions[1].title_id, "0004000000055D01");
+    }
+
+    #[test]
+    fn inspects_decrypted_cia_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cia");
+        let mut content = make_ncch_header_bytes(TID);
+        content.resize(0x400, 0);
+        write_cia_file(&path, &cia_with_content(TID, content, None));
+
+        let report = inspect_ncch(&path).unwrap();
+        assert_eq!(report.format, CtrFormat::Cia);
+        assert_eq!(report.partitions.len(), 1);
+        let part = &report.partitions[0];
+        assert_eq!(part.partition, "Content 0000");
+        assert_eq!(part.title_key_encrypted, Some(false));
+        assert!(!part.encrypted);
+        assert_eq!(part.method, NcchCryptoMethod::None);
+    }
+
+    #[test]
+    fn dumps_the_title_key_and_each_content_key_y() {
+        use crate::nintendo::ctr::decrypt::util::{cbc_decrypt, common_key};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cia");
+        let mut content = make_ncch_header_bytes(TID);
+        content[..16].copy_from_slice(&[0x5A; 16]);
+        content[NCCH_FLAGS_OFFSET + 7] = 0;
+        content.resize(0x400, 0);
+        let cia = cia_with_content(TID, content, None);
+        write_cia_file(&path, &cia);
+
+        let dump = dump_cia_keys(&path).unwrap();
+        let ticket = &cia.ticket.ticket_data;
+        let mut expected_key: [u8; 16] = ticket.title_key.clone().try_into().unwrap();
+        let mut iv = [0u8; 16];
+        iv[..8].copy_from_slice(&TID.to_be_bytes());
+        cbc_decrypt(
+            common_key(ticket.common_key_index).unwrap(),
+            &iv,
+            &mut expected_key,
+        )
+        .unwrap();
+
+        assert_eq!(dump.title_id, "0004000000055D00");
+        assert_eq!(dump.common_key_index, ticket.common_key_index);
+        assert_eq!(dump.title_key, hex::encode_upper(expected_key));
+        assert_eq!(dump.contents.len(), 1);
+        assert_eq!(dump.contents[0].content, "Content 0000");
+        assert_eq!(dump.contents[0].key_y, "5A".repeat(16));
+        assert_eq!(
+            dump.contents[0].method,
+            NcchCryptoMethod::Keyslot { slot: 0x2C }
+        );
+        assert_eq!(dump.contents[0].seed_key_y, None);
+    }
+
+    #[test]
+    fn dump_keys_refuses_anything_but_a_cia() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cxi");
+        std::fs::write(&path, make_ncch_header_bytes(TID)).unwrap();
+        assert!(dump_cia_keys(&path).is_err());
+    }
+}
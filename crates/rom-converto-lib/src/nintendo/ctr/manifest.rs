@@ -0,0 +1,304 @@
+//! Manifest-driven CDN-to-CIA builds: a TOML file lists CDN directories,
+//! where each CIA goes, and the options for that title, so a curated
+//! collection rebuilds the same way every time.
+//!
+//! ```toml
+//! [[title]]
+//! cdn_dir = "dumps/0004000000155400"
+//! output = "cia/Game.cia"
+//! decrypt = true
+//! ensure_ticket = true
+//! common_key_index = 0
+//! ```
+
+use crate::nintendo::ctr::title_key::{CommonKeyIndex, parse_title_key};
+use crate::nintendo::ctr::{CdnNameFrom, CdnToCiaOptions, convert_cdn_to_cia_cancellable};
+use crate::util::{CancelToken, ConflictPolicy, NoProgress, ProgressReporter, Tally};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CdnManifest {
+    #[serde(rename = "title", default)]
+    pub titles: Vec<CdnManifestEntry>,
+}
+
+/// One `[[title]]` table. Relative paths resolve against the manifest's
+/// own directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CdnManifestEntry {
+    pub cdn_dir: PathBuf,
+    /// Output CIA path, defaults to `<cdn_dir>.cia` next to the CDN
+    /// directory. With `compress` the `.zcia` is derived from it.
+    pub output: Option<PathBuf>,
+    #[serde(default)]
+    pub decrypt: bool,
+    #[serde(default)]
+    pub compress: bool,
+    /// Generate a ticket when the CDN directory has none.
+    #[serde(default)]
+    pub ensure_ticket: bool,
+    /// `"auto"` or 0-5, for a generated ticket.
+    #[serde(default, deserialize_with = "de_common_key_index")]
+    pub common_key_index: Option<CommonKeyIndex>,
+    /// 32 hex digits, the encrypted title key for a generated ticket.
+    #[serde(default, deserialize_with = "de_title_key")]
+    pub title_key: Option<[u8; 16]>,
+    #[serde(default)]
+    pub verify_after: bool,
+    #[serde(default)]
+    pub fix_content_size: bool,
+    pub tmd_version: Option<u32>,
+    #[serde(default)]
+    pub raw_certs: bool,
+    /// Ticket to use instead of the one found in `cdn_dir`.
+    pub ticket: Option<PathBuf>,
+}
+
+impl CdnManifestEntry {
+    pub fn to_options(&self, on_conflict: ConflictPolicy, seed_retries: u32) -> CdnToCiaOptions {
+        CdnToCiaOptions {
+            cdn_dir: self.cdn_dir.clone(),
+            output: self.output.clone(),
+            cleanup: false,
+            recursive: false,
+            ensure_ticket_exists: self.ensure_ticket,
+            decrypt: self.decrypt,
+            compress: self.compress,
+            output_dir: None,
+            on_conflict,
+            name_from: CdnNameFrom::Dir,
+            verify_after: self.verify_after,
+            fix_content_size: self.fix_content_size,
+            tmd_version: self.tmd_version,
+            common_key_index: self.common_key_index.unwrap_or_default(),
+            title_key: self.title_key,
+            raw_certs: self.raw_certs,
+            ticket: self.ticket.clone(),
+            output_template: None,
+            region: None,
+            content_concurrency: 1,
+            seed_retries,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A common key index written either as a number or as a string, so both
+/// `common_key_index = 1` and `common_key_index = "auto"` parse.
+fn de_common_key_index<'de, D>(deserializer: D) -> Result<Option<CommonKeyIndex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Index(i64),
+        Text(String),
+    }
+    let text = match Raw::deserialize(deserializer)? {
+        Raw::Index(index) => index.to_string(),
+        Raw::Text(text) => text,
+    };
+    text.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn de_title_key<'de, D>(deserializer: D) -> Result<Option<[u8; 16]>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_title_key(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Read and validate the manifest at `path`.
+pub fn load_cdn_manifest(path: &Path) -> Result<CdnManifest> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read manifest: {}", path.display()))?;
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+    parse_str(&content, base).with_context(|| format!("invalid manifest: {}", path.display()))
+}
+
+fn parse_str(content: &str, base: &Path) -> Result<CdnManifest> {
+    let mut manifest: CdnManifest = toml::from_str(content)?;
+    if manifest.titles.is_empty() {
+        anyhow::bail!("no [[title]] entries");
+    }
+    for (n, entry) in manifest.titles.iter_mut().enumerate() {
+        if !entry.ensure_ticket && (entry.common_key_index.is_some() || entry.title_key.is_some()) {
+            anyhow::bail!(
+                "title {} ({}): common_key_index and title_key only apply to a generated ticket; set ensure_ticket = true",
+                n + 1,
+                entry.cdn_dir.display()
+            );
+        }
+        entry.cdn_dir = base.join(&entry.cdn_dir);
+        if let Some(output) = entry.output.as_mut() {
+            *output = base.join(&*output);
+        }
+        if let Some(ticket) = entry.ticket.as_mut() {
+            *ticket = base.join(&*ticket);
+        }
+    }
+    Ok(manifest)
+}
+
+/// How one manifest entry went.
+#[derive(Debug)]
+pub struct CdnManifestOutcome {
+    pub cdn_dir: PathBuf,
+    pub result: Result<()>,
+    pub elapsed: Duration,
+}
+
+/// Build every title in `manifest`, up to `jobs` at a time. A failed
+/// title does not stop the others; outcomes come back in manifest order.
+/// `progress` follows the running title when `jobs` is 1 and is left
+/// idle otherwise, since several titles cannot share one bar;
+/// `total_progress` counts finished titles. `seed_retries` applies to the
+/// titles that decrypt.
+pub async fn run_cdn_manifest(
+    manifest: &CdnManifest,
+    on_conflict: ConflictPolicy,
+    jobs: usize,
+    seed_retries: u32,
+    progress: &dyn ProgressReporter,
+    total_progress: &dyn ProgressReporter,
+    cancel: CancelToken,
+) -> Vec<CdnManifestOutcome> {
+    let jobs = jobs.max(1);
+    let entry_progress: &dyn ProgressReporter = if jobs == 1 { progress } else { &NoProgress };
+    let count = manifest.titles.len() as u64;
+    total_progress.start(count, &format!("Processing {count} titles"));
+
+    let mut outcomes: Vec<(usize, CdnManifestOutcome)> =
+        futures::stream::iter(manifest.titles.iter().enumerate())
+            .map(|(index, entry)| {
+                let cancel = cancel.clone();
+                async move {
+                    let started = Instant::now();
+                    let result = convert_cdn_to_cia_cancellable(
+                        entry.to_options(on_conflict, seed_retries),
+                        entry_progress,
+                        &NoProgress,
+                        &mut Tally::new(),
+                        cancel,
+                    )
+                    .await;
+                    total_progress.inc(1);
+                    let outcome = CdnManifestOutcome {
+                        cdn_dir: entry.cdn_dir.clone(),
+                        result,
+                        elapsed: started.elapsed(),
+                    };
+                    (index, outcome)
+                }
+            })
+            .buffer_unordered(jobs)
+            .collect()
+            .await;
+    total_progress.finish();
+
+    outcomes.sort_by_key(|(index, _)| *index);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::extract::{CdnLayout, extract_contents};
+    use crate::nintendo::ctr::test_fixtures::synth_cia;
+
+    #[test]
+    fn parses_entries_and_resolves_paths_against_the_manifest() {
+        let manifest = parse_str(
+            r#"
+            [[title]]
+            cdn_dir = "dumps/a"
+            output = "cia/a.cia"
+            ticket = "tickets/a.tik"
+            decrypt = true
+            ensure_ticket = true
+            common_key_index = 1
+            title_key = "3c7faeff5b1d784d25011149f33f50a7"
+
+            [[title]]
+            cdn_dir = "/abs/b"
+            ensure_ticket = true
+            common_key_index = "auto"
+            "#,
+            Path::new("/lib"),
+        )
+        .unwrap();
+
+        let [a, b] = manifest.titles.as_slice() else {
+            panic!("expected two titles");
+        };
+        assert_eq!(a.cdn_dir, Path::new("/lib/dumps/a"));
+        assert_eq!(a.output.as_deref(), Some(Path::new("/lib/cia/a.cia")));
+        assert_eq!(a.ticket.as_deref(), Some(Path::new("/lib/tickets/a.tik")));
+        assert_eq!(a.common_key_index, Some(CommonKeyIndex::Fixed(1)));
+        assert_eq!(
+            a.title_key.map(hex::encode).as_deref(),
+            Some("3c7faeff5b1d784d25011149f33f50a7")
+        );
+        assert!(a.decrypt && !a.compress);
+        assert_eq!(b.cdn_dir, Path::new("/abs/b"));
+        assert_eq!(b.output, None);
+        assert_eq!(b.common_key_index, Some(CommonKeyIndex::Auto));
+    }
+
+    #[test]
+    fn rejects_bad_entries() {
+        for content in [
+            "",
+            "[[title]]\ncdn_dir = \"a\"\nunknown = 1\n",
+            "[[title]]\ncdn_dir = \"a\"\nensure_ticket = true\ncommon_key_index = 6\n",
+            "[[title]]\ncdn_dir = \"a\"\nensure_ticket = true\ntitle_key = \"abcd\"\n",
+            "[[title]]\ncdn_dir = \"a\"\ncommon_key_index = 1\n",
+        ] {
+            assert!(parse_str(content, Path::new(".")).is_err(), "{content:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_every_title_and_reports_each() {
+        let (dir, cia_path, _) = synth_cia(0x2000);
+        let cdn = dir.path().join("cdn");
+        extract_contents(&cia_path, &cdn, CdnLayout::default(), false, &NoProgress)
+            .await
+            .unwrap();
+        std::fs::write(
+            dir.path().join("titles.toml"),
+            "[[title]]\ncdn_dir = \"cdn\"\noutput = \"one.cia\"\n\n\
+             [[title]]\ncdn_dir = \"missing\"\noutput = \"two.cia\"\n\n\
+             [[title]]\ncdn_dir = \"cdn\"\noutput = \"three.cia\"\n",
+        )
+        .unwrap();
+        let manifest = load_cdn_manifest(&dir.path().join("titles.toml")).unwrap();
+
+        let outcomes = run_cdn_manifest(
+            &manifest,
+            ConflictPolicy::Error,
+            2,
+            crate::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+            &NoProgress,
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await;
+
+        let ok: Vec<bool> = outcomes.iter().map(|o| o.result.is_ok()).collect();
+        assert_eq!(ok, [true, false, true]);
+        assert_eq!(outcomes[1].cdn_dir, dir.path().join("missing"));
+        assert!(dir.path().join("one.cia").is_file());
+        assert!(dir.path().join("three.cia").is_file());
+    }
+}
@@ -0,0 +1,367 @@
+//! Merges update and DLC CIAs into a base title CIA. The base keeps its
+//! certificate chain, ticket, and meta block. Update contents are layered
+//! on top by content index, so an update's program content replaces the
+//! base one. DLC numbers its contents from 0 as well, so its contents are
+//! re-indexed to follow the base and update ones instead.
+//!
+//! Each title's contents are encrypted under its own title key, so only
+//! decrypted CIAs can be merged: the base ticket could not unlock the rest.
+
+use crate::nintendo::ctr::convert::{build_info_records, hash_info_records};
+use crate::nintendo::ctr::error::NintendoCTRError;
+use crate::nintendo::ctr::models::cia::CiaFileWithoutContent;
+use crate::nintendo::ctr::models::title_metadata::ContentChunkRecord;
+use crate::nintendo::ctr::util::align_64;
+use crate::util::{CancelToken, ProgressReporter, scratch_output_path};
+use anyhow::{Context, Result, bail};
+use binrw::{BinRead, BinWrite, Endian};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter, SeekFrom};
+
+const CONTENT_COPY_BUF: usize = 4 * 1024 * 1024;
+
+/// Title id bits shared by a base title and its update and DLC: the
+/// unique id and variation. The high word names the title kind.
+const TITLE_UNIQUE_ID_MASK: u64 = 0xFFFF_FFFF;
+
+/// Title id high words of an update and of a DLC title.
+const UPDATE_TITLE_KIND: u64 = 0x0004_000E;
+const DLC_TITLE_KIND: u64 = 0x0004_008C;
+
+/// One parsed input: its preamble plus where its content area starts.
+struct MergeSource {
+    path: PathBuf,
+    cia: CiaFileWithoutContent,
+    content_start: u64,
+    meta: Option<Vec<u8>>,
+}
+
+/// A content record in the merged TMD and where to copy its bytes from.
+struct MergedContent {
+    record: ContentChunkRecord,
+    source: usize,
+    offset: u64,
+}
+
+pub async fn merge_cia(
+    base: &Path,
+    additions: &[PathBuf],
+    output: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    merge_cia_cancellable(base, additions, output, progress, CancelToken::new()).await
+}
+
+/// Merge `additions` (updates and DLC, applied in order) into `base` and
+/// write the result to `output`. A later non-DLC input's content overrides
+/// an earlier one with the same content index. DLC contents are appended
+/// after all of those, renumbered from the next free index. The merged TMD
+/// takes the title version of the last update.
+pub async fn merge_cia_cancellable(
+    base: &Path,
+    additions: &[PathBuf],
+    output: &Path,
+    progress: &dyn ProgressReporter,
+    cancel: CancelToken,
+) -> Result<()> {
+    let mut sources = Vec::with_capacity(additions.len() + 1);
+    for path in std::iter::once(base).chain(additions.iter().map(PathBuf::as_path)) {
+        sources.push(read_source(path).await?);
+    }
+
+    let base_title_id = sources[0].cia.tmd.header.title_id;
+    for source in &sources[1..] {
+        let title_id = source.cia.tmd.header.title_id;
+        if title_id & TITLE_UNIQUE_ID_MASK != base_title_id & TITLE_UNIQUE_ID_MASK {
+            warn!(
+                event = "title_id_mismatch", file:% = source.path.display();
+                "{} has title ID {title_id:016X}, which does not belong to base title {base_title_id:016X}",
+                source.path.display()
+            );
+        }
+    }
+
+    let is_dlc =
+        |index: usize| index > 0 && sources[index].cia.tmd.header.title_id >> 32 == DLC_TITLE_KIND;
+    let mut merged: BTreeMap<u16, MergedContent> = BTreeMap::new();
+    // Base and updates first, so the DLC is numbered after all of them.
+    let order = (0..sources.len())
+        .filter(|&i| !is_dlc(i))
+        .chain((0..sources.len()).filter(|&i| is_dlc(i)));
+    for index in order {
+        let source = &sources[index];
+        let mut next_index = merged
+            .last_key_value()
+            .map_or(Some(0), |(&last, _)| last.checked_add(1));
+        let mut offset = source.content_start;
+        for record in &source.cia.tmd.content_chunk_records {
+            if record.content_type.is_encrypted() {
+                bail!(
+                    "{} has encrypted content {:08x}; decrypt it before merging",
+                    source.path.display(),
+                    record.content_id
+                );
+            }
+            let size = record.content_size;
+            let mut record = record.clone();
+            if is_dlc(index) {
+                record.content_index =
+                    next_index.context("merged title has more than 65535 contents")?;
+                next_index = record.content_index.checked_add(1);
+            }
+            merged.insert(
+                record.content_index,
+                MergedContent {
+                    record,
+                    source: index,
+                    offset,
+                },
+            );
+            offset += align_64(size);
+        }
+    }
+    let contents: Vec<MergedContent> = merged.into_values().collect();
+    let update_version = sources[1..]
+        .iter()
+        .rev()
+        .find(|source| source.cia.tmd.header.title_id >> 32 == UPDATE_TITLE_KIND)
+        .map(|source| source.cia.tmd.header.title_version);
+
+    let base = sources.remove(0);
+    let meta = base.meta;
+    let mut cia = base.cia;
+    if let Some(version) = update_version {
+        cia.tmd.header.title_version = version;
+    }
+    let records: Vec<ContentChunkRecord> = contents.iter().map(|c| c.record.clone()).collect();
+    cia.tmd.header.content_count =
+        u16::try_from(records.len()).context("merged title has more than 65535 contents")?;
+    cia.tmd.content_info_records = build_info_records(&records)?;
+    cia.tmd.header.content_info_records_hash = hash_info_records(&cia.tmd.content_info_records)?;
+    cia.tmd.content_chunk_records = records;
+
+    let mut tmd_buf = Vec::new();
+    cia.tmd
+        .write_options(&mut Cursor::new(&mut tmd_buf), Endian::Big, ())?;
+    cia.header.tmd_size = tmd_buf.len() as u32;
+    cia.header.meta_size = meta.as_ref().map_or(0, |m| m.len() as u32);
+    // The base header still carries bits for contents an addition
+    // replaced, so the bitmap is rebuilt from the merged list.
+    cia.header
+        .rebuild_content_indexes(&cia.tmd.content_chunk_records);
+    cia.header.content_size = contents
+        .iter()
+        .map(|c| align_64(c.record.content_size))
+        .sum();
+
+    let total: u64 = contents.iter().map(|c| c.record.content_size).sum();
+    progress.start(total, "Merging CIA");
+
+    let tmp = scratch_output_path(output)?;
+    let out = File::create(&tmp).await.context("creating CIA output")?;
+    let mut out = BufWriter::new(out);
+
+    let stream = async {
+        let mut preamble = Vec::new();
+        cia.write_options(&mut Cursor::new(&mut preamble), Endian::Little, ())?;
+        out.write_all(&preamble).await?;
+
+        // Index 0 is the base, which `sources` no longer holds.
+        let mut inputs = Vec::with_capacity(sources.len() + 1);
+        for path in std::iter::once(&base.path).chain(sources.iter().map(|s| &s.path)) {
+            inputs.push(File::open(path).await?);
+        }
+
+        let mut buf = vec![0u8; CONTENT_COPY_BUF];
+        for content in &contents {
+            let input = &mut inputs[content.source];
+            input.seek(SeekFrom::Start(content.offset)).await?;
+            let mut remaining = content.record.content_size;
+            while remaining > 0 {
+                if cancel.is_cancelled() {
+                    return Err(NintendoCTRError::Cancelled.into());
+                }
+                let n = remaining.min(buf.len() as u64) as usize;
+                input.read_exact(&mut buf[..n]).await.with_context(|| {
+                    format!("reading content {:08x}", content.record.content_id)
+                })?;
+                out.write_all(&buf[..n]).await?;
+                progress.inc(n as u64);
+                remaining -= n as u64;
+            }
+            let pad = align_64(content.record.content_size) - content.record.content_size;
+            out.write_all(&vec![0u8; pad as usize]).await?;
+        }
+
+        if let Some(meta) = &meta {
+            out.write_all(meta).await?;
+        }
+        out.flush().await?;
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(err) = stream {
+        drop(out);
+        tokio::fs::remove_file(&tmp).await.ok();
+        return Err(err);
+    }
+
+    drop(out);
+    crate::util::publish_temp(tmp, output, true)?;
+    progress.finish();
+
+    info!(
+        "Merged {} CIA files into {} ({} contents)",
+        additions.len() + 1,
+        output.display(),
+        contents.len()
+    );
+    Ok(())
+}
+
+async fn read_source(path: &Path) -> Result<MergeSource> {
+    let owned = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<MergeSource> {
+        let file =
+            std::fs::File::open(&owned).with_context(|| format!("opening {}", owned.display()))?;
+        let file_size = file.metadata()?.len();
+        let mut reader = std::io::BufReader::new(file);
+        let cia = CiaFileWithoutContent::read_options(&mut reader, Endian::Little, ())
+            .with_context(|| format!("parsing CIA {}", owned.display()))?;
+
+        let header = &cia.header;
+        let cert_start = align_64(header.header_size as u64);
+        let ticket_start = align_64(cert_start + header.cert_chain_size as u64);
+        let tmd_start = align_64(ticket_start + header.ticket_size as u64);
+        let content_start = align_64(tmd_start + header.tmd_size as u64);
+
+        let mut offset = content_start;
+        let mut last_end = content_start;
+        for record in &cia.tmd.content_chunk_records {
+            last_end = offset + record.content_size;
+            offset += align_64(record.content_size);
+        }
+        if last_end > file_size {
+            bail!(
+                "{} is truncated: its contents end at {last_end} bytes but the file is {file_size}",
+                owned.display()
+            );
+        }
+
+        let meta = if header.meta_size > 0 {
+            let meta_start = align_64(content_start + header.content_size);
+            let mut meta = vec![0u8; header.meta_size as usize];
+            std::io::Seek::seek(&mut reader, std::io::SeekFrom::Start(meta_start))?;
+            std::io::Read::read_exact(&mut reader, &mut meta)
+                .with_context(|| format!("reading meta block of {}", owned.display()))?;
+            Some(meta)
+        } else {
+            None
+        };
+
+        Ok(MergeSource {
+            path: owned,
+            cia,
+            content_start,
+            meta,
+        })
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::models::cia::CiaFile;
+    use crate::nintendo::ctr::models::title_metadata::ContentType;
+    use crate::nintendo::ctr::test_fixtures::{cia_with_content, make_meta, write_cia_file};
+    use crate::util::NoProgress;
+
+    const BASE_TID: u64 = 0x0004_0000_0005_5D00;
+    const UPDATE_TID: u64 = 0x0004_000E_0005_5D00;
+    const DLC_TID: u64 = 0x0004_008C_0005_5D00;
+
+    fn write(dir: &Path, name: &str, cia: &CiaFile) -> PathBuf {
+        let path = dir.join(name);
+        write_cia_file(&path, cia);
+        path
+    }
+
+    fn read_back(path: &Path) -> CiaFile {
+        let bytes = std::fs::read(path).unwrap();
+        CiaFile::read_options(&mut Cursor::new(&bytes), Endian::Little, ()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn update_overrides_base_and_dlc_is_renumbered_after_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = write(
+            dir.path(),
+            "base.cia",
+            &cia_with_content(BASE_TID, vec![0x11; 0x400], Some(make_meta(3))),
+        );
+        let mut update_cia = cia_with_content(UPDATE_TID, vec![0x22; 0x300], None);
+        update_cia.tmd.header.title_version = 0x0C10;
+        let update = write(dir.path(), "update.cia", &update_cia);
+        // Real DLC numbers its contents from 0, like the base.
+        let dlc = write(
+            dir.path(),
+            "dlc.cia",
+            &cia_with_content(DLC_TID, vec![0x33; 0x200], None),
+        );
+
+        // The DLC comes first on the command line but still follows the
+        // update's contents.
+        let out = dir.path().join("merged.cia");
+        merge_cia(&base, &[dlc, update], &out, &NoProgress)
+            .await
+            .unwrap();
+
+        let merged = read_back(&out);
+        assert_eq!(merged.tmd.header.title_id, BASE_TID);
+        assert_eq!(merged.tmd.header.title_version, 0x0C10);
+        assert_eq!(merged.tmd.header.content_count, 2);
+        let indexes: Vec<u16> = merged
+            .tmd
+            .content_chunk_records
+            .iter()
+            .map(|r| r.content_index)
+            .collect();
+        assert_eq!(indexes, vec![0, 1]);
+        assert_eq!(merged.tmd.content_info_records[0].content_command_count, 2);
+        assert_eq!(
+            merged.tmd.header.content_info_records_hash,
+            hash_info_records(&merged.tmd.content_info_records).unwrap()
+        );
+
+        let mut expected = vec![0x22u8; 0x300];
+        expected.extend_from_slice(&[0x33; 0x200]);
+        assert_eq!(merged.content_data, expected);
+        assert_eq!(merged.meta_data.unwrap().icon_data, make_meta(3).icon_data);
+    }
+
+    #[tokio::test]
+    async fn encrypted_input_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = write(
+            dir.path(),
+            "base.cia",
+            &cia_with_content(BASE_TID, vec![0x11; 0x400], None),
+        );
+        let mut update_cia = cia_with_content(UPDATE_TID, vec![0x22; 0x300], None);
+        update_cia.tmd.content_chunk_records[0].content_type = ContentType(0x0001);
+        let update = write(dir.path(), "update.cia", &update_cia);
+
+        let out = dir.path().join("merged.cia");
+        let err = merge_cia(&base, &[update], &out, &NoProgress)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("decrypt it before merging"));
+        assert!(!out.exists());
+    }
+}
@@ -2,35 +2,42 @@
 //! assembly, decryption, conversion between CIA and CCI, verification, and
 //! the Z3DS compression pipeline.
 
-use crate::nintendo::ctr::cia::{decrypt_from_encrypted_cia, write_cia};
-use crate::nintendo::ctr::constants::NCCH_MAGIC_OFFSET;
-use crate::nintendo::ctr::decrypt::cia::{parse_and_decrypt_ncch, parse_and_decrypt_ncsd};
+use crate::nintendo::ctr::cia::{decrypt_from_encrypted_cia, parse_certificate_chain, write_cia};
+pub use crate::nintendo::ctr::decrypt::DecryptOptions;
+pub use crate::nintendo::ctr::decrypt::cia::{
+    ncch_content_file_name, ncch_content_index_from_name,
+};
+use crate::nintendo::ctr::decrypt::cia::{
+    parse_and_decrypt_ncch, parse_and_decrypt_ncsd, split_and_decrypt_cia,
+};
 pub use crate::nintendo::ctr::encrypt::{
-    derive_encrypted_path, encrypt_rom, encrypt_rom_batch_cancellable, encrypt_rom_cancellable,
+    derive_encrypted_path, encrypt_ncch_file_cancellable, encrypt_rom,
+    encrypt_rom_batch_cancellable, encrypt_rom_cancellable,
 };
 use crate::nintendo::ctr::error::NintendoCTRError;
-use crate::nintendo::ctr::models::cia::CIA_HEADER_SIZE;
+use crate::nintendo::ctr::format::{KnownFormat, detect_format};
 use crate::nintendo::ctr::models::ticket::Ticket;
 use crate::nintendo::ctr::models::title_metadata::TitleMetadata;
-use crate::nintendo::ctr::title_key::generate_title_key;
-use crate::nintendo::ctr::util::fs::{find_title_file, find_tmd_file};
-use crate::nintendo::ctr::z3ds::models::underlying_magic;
+use crate::nintendo::ctr::region::{CdnTicketSource, CtrRegion, cdn_title_region};
+use crate::nintendo::ctr::title_key::{CommonKeyIndex, generate_title_key_with_index};
+use crate::nintendo::ctr::util::fs::{find_title_file, find_tmd_file, tmd_file_version};
 use crate::nintendo::ctr::z3ds::{compress_rom_cancellable, derive_compressed_path};
 use crate::util::{
-    CancelToken, ConflictPolicy, ConflictResolution, ProgressReporter, resolve_conflict,
-    scratch_output_path,
+    CancelToken, ConflictPolicy, ConflictResolution, ProgressReporter, ScratchPath, Tally,
+    TemplateTokens, apply_template, resolve_conflict, scratch_output_path,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use binrw::BinRead;
 use futures::TryFutureExt;
 use log::{debug, info, warn};
-use std::io::{Cursor, SeekFrom, Write as _};
+use std::io::{Cursor, Write as _};
 use std::path::{Path, PathBuf};
-use tempfile::TempPath;
+use std::time::Instant;
 use tokio::fs;
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncWriteExt, BufWriter};
 
+pub mod anonymize;
 mod cia;
 mod constants;
 pub mod convert;
@@ -38,8 +45,16 @@ mod decrypt;
 mod encrypt;
 pub mod error;
 pub mod exefs;
+pub mod extract;
+pub mod fix_tmd;
+pub mod format;
 pub mod info;
+pub mod inspect;
+pub mod manifest;
+pub mod merge;
 pub mod models;
+pub mod region;
+pub mod romfs;
 pub mod seed;
 #[cfg(test)]
 mod test_fixtures;
@@ -59,17 +74,163 @@ pub struct CdnToCiaOptions {
     pub compress: bool,
     pub output_dir: Option<PathBuf>,
     pub on_conflict: ConflictPolicy,
+    pub name_from: CdnNameFrom,
+    /// Reopen the built CIA and check it against its TMD before publishing.
+    pub verify_after: bool,
+    /// Trim or zero-pad each content file to its TMD size instead of
+    /// refusing a size mismatch.
+    pub fix_content_size: bool,
+    /// Build from `tmd.<n>` instead of the default TMD pick, failing when
+    /// the CDN directory has no such file.
+    pub tmd_version: Option<u32>,
+    /// Common key for a ticket generated by `ensure_ticket_exists`.
+    pub common_key_index: CommonKeyIndex,
+    /// Encrypted title key written into a generated ticket as-is, for
+    /// titles whose key is known rather than derivable from a password.
+    pub title_key: Option<[u8; 16]>,
+    /// Keep the TMD's and ticket's certificate chains whole, dropping only
+    /// exact duplicates, instead of picking one CA, XS, and CP certificate
+    /// by name. For titles with atypical chains.
+    pub raw_certs: bool,
+    /// Ticket to build with instead of searching the CDN directory. Only
+    /// for a single title, not `recursive`.
+    pub ticket: Option<PathBuf>,
+    /// Output path template, resolved per title by
+    /// [`cdn_cia_templated_path`] and joined under `output_dir`, or next to
+    /// the CDN directory. Used instead of `name_from` when no `output` is
+    /// given.
+    pub output_template: Option<String>,
+    /// Only convert titles released for this region (or region free) when
+    /// `recursive`; read per title by [`cdn_title_region`] with the title
+    /// key the build uses. A title whose region cannot be read is skipped.
+    pub region: Option<CtrRegion>,
+    /// Content files read at once while the CIA is written, in TMD order
+    /// either way. `1` reads them one after another.
+    pub content_concurrency: usize,
+    /// Extra seed CDN rounds for `decrypt`, as
+    /// [`DecryptOptions::seed_retries`].
+    pub seed_retries: u32,
+    /// With `recursive`, stop at the first title that fails and return its
+    /// error instead of logging it and moving on to the next directory.
+    pub fail_fast: bool,
+}
+
+impl CdnToCiaOptions {
+    /// The ticket each title is built with, for [`cdn_title_region`].
+    pub fn ticket_source(&self) -> CdnTicketSource {
+        CdnTicketSource {
+            ticket: self.ticket.clone(),
+            generate: self.ensure_ticket_exists,
+            title_key: self.title_key,
+            common_key_index: self.common_key_index,
+        }
+    }
+}
+
+/// How a CDN-to-CIA output is named when no explicit output path is given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CdnNameFrom {
+    /// The CDN directory's own name, `<dir>.cia`.
+    #[default]
+    Dir,
+    /// The TMD title ID, `0004000000155400.cia`.
+    TitleId,
+    /// The TMD title ID and title version, `0004000000155400 v1024.cia`.
+    TitleIdVersion,
+}
+
+/// File name, including the `.cia` extension, for the CIA built from
+/// `cdn_dir`. The title-ID forms read the directory's TMD, so two CDN
+/// folders with the same name in different places no longer collide.
+/// `tmd_version` picks the TMD the same way [`CdnToCiaOptions::tmd_version`]
+/// does.
+pub async fn cdn_cia_file_name(
+    cdn_dir: &Path,
+    name_from: CdnNameFrom,
+    tmd_version: Option<u32>,
+) -> Result<String> {
+    if name_from == CdnNameFrom::Dir {
+        return cdn_dir
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("CDN directory path has no name"))?
+            .to_str()
+            .map(|name| format!("{name}.cia"))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "CDN directory name {} is not valid UTF-8; pass --name-from title-id or an explicit output",
+                    cdn_dir.display()
+                )
+            });
+    }
+    let tmd_path = find_tmd_file(cdn_dir, tmd_version).await?;
+    let tmd = TitleMetadata::read(&mut Cursor::new(fs::read(&tmd_path).await?))?;
+    let title_id = tmd.header.title_id;
+    Ok(match name_from {
+        CdnNameFrom::TitleIdVersion => {
+            format!("{title_id:016X} v{}.cia", tmd.header.title_version)
+        }
+        _ => format!("{title_id:016X}.cia"),
+    })
+}
+
+/// Where the CIA built from `cdn_dir` goes when no output path is given:
+/// `output_template` resolved by [`cdn_cia_templated_path`] if set, the
+/// [`cdn_cia_file_name`] otherwise, under `output_dir` or next to the CDN
+/// directory. Nothing is created.
+pub async fn cdn_cia_output_path(
+    cdn_dir: &Path,
+    name_from: CdnNameFrom,
+    output_template: Option<&str>,
+    tmd_version: Option<u32>,
+    output_dir: Option<&Path>,
+    tickets: &CdnTicketSource,
+) -> Result<PathBuf> {
+    let parent = cdn_dir.parent().unwrap_or_else(|| Path::new("."));
+    Ok(match output_template {
+        Some(template) => {
+            let rel = cdn_cia_templated_path(cdn_dir, template, tmd_version, tickets).await?;
+            output_dir.unwrap_or(parent).join(rel)
+        }
+        None => {
+            let name = cdn_cia_file_name(cdn_dir, name_from, tmd_version).await?;
+            crate::util::place_in_dir(&parent.join(name), output_dir)
+        }
+    })
+}
+
+/// Relative output path for the CIA built from `cdn_dir`, from an output
+/// template. `{titleId}` and `{titleVersion}` come from the TMD picked as
+/// [`CdnToCiaOptions::tmd_version`] says, `{region}` from
+/// [`cdn_title_region`] with `tickets` (empty when it cannot be read),
+/// `{basename}` is the directory name, `{console}` is `3DS`, and `{ext}`
+/// is `cia`.
+pub async fn cdn_cia_templated_path(
+    cdn_dir: &Path,
+    template: &str,
+    tmd_version: Option<u32>,
+    tickets: &CdnTicketSource,
+) -> Result<PathBuf> {
+    let tmd_path = find_tmd_file(cdn_dir, tmd_version).await?;
+    let tmd = TitleMetadata::read(&mut Cursor::new(fs::read(&tmd_path).await?))?;
+    let mut tokens = TemplateTokens::new(None, cdn_dir, "cia");
+    if let Some(name) = cdn_dir.file_name().and_then(|name| name.to_str()) {
+        tokens.basename = name.to_string();
+    }
+    tokens.title_id = Some(format!("{:016X}", tmd.header.title_id));
+    tokens.title_version = Some(tmd.header.title_version.to_string());
+    tokens.console = Some("3DS".to_string());
+    // Reading the region decrypts content 0, so only templates that use it pay.
+    if template.contains("{region}") {
+        tokens.region = cdn_title_region(cdn_dir, tmd_version, tickets)
+            .await
+            .ok()
+            .map(|region| region.lockout_name().to_string());
+    }
+    apply_template(template, &tokens)
 }
 
 pub fn derive_decrypted_path(input: &Path) -> PathBuf {
-    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("out");
-    let ext = input.extension().and_then(|s| s.to_str()).unwrap_or("");
-    let name = if ext.is_empty() {
-        format!("{stem}.decrypted")
-    } else {
-        format!("{stem}.decrypted.{ext}")
-    };
-    input.with_file_name(name)
+    util::tagged_sibling_path(input, "decrypted")
 }
 
 const DECRYPT_EXTS: &[&str] = &["cia", "3ds", "cci", "cxi"];
@@ -79,26 +240,44 @@ pub async fn decrypt_cia(
     output: &Path,
     progress: &dyn ProgressReporter,
 ) -> Result<()> {
-    decrypt_cia_cancellable(input, output, progress, CancelToken::new()).await
+    decrypt_cia_cancellable(
+        input,
+        output,
+        &DecryptOptions::default(),
+        progress,
+        CancelToken::new(),
+    )
+    .await
 }
 
 pub async fn decrypt_cia_cancellable(
     input: &Path,
     output: &Path,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: CancelToken,
 ) -> Result<()> {
     let tmp = scratch_output_path(output)?;
-    let out = File::create(&tmp).await?;
+    // Readable too, so the RomFS check can read the decrypted superblock back.
+    let out = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)
+        .await?;
     let mut out = BufWriter::new(out);
 
-    if let Err(err) = decrypt_from_encrypted_cia(input, &mut out, progress, &cancel).await {
+    if let Err(err) = decrypt_from_encrypted_cia(input, &mut out, opts, progress, &cancel).await {
         drop(out);
         fs::remove_file(&tmp).await.ok();
         return Err(err);
     }
 
     out.flush().await?;
+    // On disk before the rename, so a crash cannot leave a renamed but
+    // empty CIA where the previous output was.
+    out.get_ref().sync_all().await?;
     drop(out);
     crate::util::publish_temp(tmp, output, true)?;
 
@@ -107,52 +286,88 @@ pub async fn decrypt_cia_cancellable(
     Ok(())
 }
 
+/// Decrypts each content of a CIA into a loose `.ncch` file under `out_dir`
+/// without rebuilding a CIA, for emulators that load the contents directly.
+/// Returns the written paths in TMD-record order. Existing files are only
+/// replaced when `overwrite` is set; on failure every file this call created
+/// is removed.
+pub async fn decrypt_cia_to_ncch_cancellable(
+    input: &Path,
+    out_dir: &Path,
+    overwrite: bool,
+    opts: &DecryptOptions,
+    progress: &dyn ProgressReporter,
+    cancel: CancelToken,
+) -> Result<Vec<PathBuf>> {
+    if detect_format(input).await? != KnownFormat::Cia {
+        anyhow::bail!("NCCH-only decrypt needs a CIA input: {}", input.display());
+    }
+    fs::create_dir_all(out_dir).await?;
+
+    let file_size = fs::metadata(input).await?.len();
+    progress.start(file_size, "Decrypting");
+    let mut written = Vec::new();
+    let result = split_and_decrypt_cia(
+        input,
+        out_dir,
+        overwrite,
+        &mut written,
+        opts,
+        progress,
+        &cancel,
+    )
+    .await;
+    progress.finish();
+
+    if let Err(err) = result {
+        for path in &written {
+            fs::remove_file(path).await.ok();
+        }
+        return Err(err);
+    }
+
+    info!("Decrypted {} CIA contents to NCCH", written.len());
+
+    Ok(written)
+}
+
 pub async fn decrypt_rom(
     input: &Path,
     output: &Path,
     progress: &dyn ProgressReporter,
 ) -> Result<()> {
-    decrypt_rom_cancellable(input, output, progress, CancelToken::new()).await
+    decrypt_rom_cancellable(
+        input,
+        output,
+        &DecryptOptions::default(),
+        progress,
+        CancelToken::new(),
+    )
+    .await
 }
 
 pub async fn decrypt_rom_cancellable(
     input: &Path,
     output: &Path,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: CancelToken,
 ) -> Result<()> {
     let file_size = tokio::fs::metadata(input).await?.len();
     progress.start(file_size, "Decrypting");
 
-    let mut file = File::open(input).await?;
-
-    // Read magic at offset 0x100 (shared by NCSD and NCCH)
-    let mut magic_buf = [0u8; 4];
-    file.seek(SeekFrom::Start(NCCH_MAGIC_OFFSET as u64)).await?;
-    file.read_exact(&mut magic_buf).await?;
-    drop(file);
-
-    if magic_buf == underlying_magic::NCSD {
-        info!("Detected NCSD format (.3ds/.cci)");
-        decrypt_ncsd_cancellable(input, output, progress, &cancel).await?;
-    } else if magic_buf == underlying_magic::NCCH {
-        info!("Detected standalone NCCH format (.cxi)");
-        decrypt_ncch_cancellable(input, output, progress, &cancel).await?;
-    } else {
-        // Try CIA: check if the u32 at offset 0 matches CIA_HEADER_SIZE
-        let mut file = File::open(input).await?;
-        let mut header_check = [0u8; 4];
-        file.read_exact(&mut header_check).await?;
-        drop(file);
-
-        let header_size = u32::from_le_bytes(header_check);
-        if header_size == CIA_HEADER_SIZE {
+    match detect_format(input).await? {
+        KnownFormat::Ncsd => {
+            info!("Detected NCSD format (.3ds/.cci)");
+            decrypt_ncsd_cancellable(input, output, opts, progress, &cancel).await?;
+        }
+        KnownFormat::Ncch => {
+            info!("Detected standalone NCCH format (.cxi)");
+            decrypt_ncch_cancellable(input, output, opts, progress, &cancel).await?;
+        }
+        KnownFormat::Cia => {
             info!("Detected CIA format");
-            decrypt_cia_cancellable(input, output, progress, cancel).await?;
-        } else {
-            return Err(anyhow::anyhow!(
-                "unrecognized format: no NCSD/NCCH magic at 0x100 and not a CIA file"
-            ));
+            decrypt_cia_cancellable(input, output, opts, progress, cancel).await?;
         }
     }
 
@@ -164,6 +379,7 @@ pub async fn decrypt_rom_cancellable(
 async fn decrypt_ncsd_cancellable(
     input: &Path,
     output: &Path,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> Result<()> {
@@ -180,7 +396,7 @@ async fn decrypt_ncsd_cancellable(
             .read(true)
             .open(&tmp)
             .await?;
-        parse_and_decrypt_ncsd(input, &mut out, None, progress, cancel).await?;
+        parse_and_decrypt_ncsd(input, &mut out, None, opts, progress, cancel).await?;
         out.flush().await?;
         Ok::<(), anyhow::Error>(())
     }
@@ -200,14 +416,21 @@ async fn decrypt_ncsd_cancellable(
 async fn decrypt_ncch_cancellable(
     input: &Path,
     output: &Path,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     cancel: &CancelToken,
 ) -> Result<()> {
     let tmp = scratch_output_path(output)?;
 
     let result = async {
-        let mut out = File::create(&tmp).await?;
-        parse_and_decrypt_ncch(input, &mut out, progress, cancel).await?;
+        let mut out = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)
+            .await?;
+        parse_and_decrypt_ncch(input, &mut out, opts, progress, cancel).await?;
         out.flush().await?;
         Ok::<(), anyhow::Error>(())
     }
@@ -225,25 +448,60 @@ async fn decrypt_ncch_cancellable(
 }
 
 pub async fn generate_ticket_from_cdn(cdn_dir: &Path, output: &Path) -> Result<()> {
-    generate_ticket_from_cdn_cancellable(cdn_dir, output, &CancelToken::new()).await
+    generate_ticket_from_cdn_cancellable(
+        cdn_dir,
+        output,
+        None,
+        None,
+        CommonKeyIndex::Auto,
+        &CancelToken::new(),
+    )
+    .await
 }
 
+/// Build a ticket for the CDN title in `cdn_dir`. `title_version`
+/// overrides the TMD's title version written into the ticket; a ticket
+/// whose version does not match the TMD may be refused by retail units.
+/// `password` replaces the default `mypass` the title key is derived
+/// from, for titles whose community tickets use another one.
+/// `common_key_index` picks the common key the title key is encrypted
+/// with; [`CommonKeyIndex::Auto`] infers it from the title ID.
 pub async fn generate_ticket_from_cdn_cancellable(
     cdn_dir: &Path,
     output: &Path,
+    title_version: Option<u16>,
+    password: Option<&str>,
+    common_key_index: CommonKeyIndex,
     cancel: &CancelToken,
 ) -> Result<()> {
-    generate_ticket_from_cdn_with_publish(cdn_dir, output, cancel, true).await
+    generate_ticket_from_cdn_with_publish(
+        cdn_dir,
+        output,
+        None,
+        title_version,
+        password,
+        None,
+        common_key_index,
+        cancel,
+        true,
+    )
+    .await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn generate_ticket_from_cdn_with_publish(
     cdn_dir: &Path,
     output: &Path,
+    tmd_version: Option<u32>,
+    title_version: Option<u16>,
+    password: Option<&str>,
+    title_key: Option<[u8; 16]>,
+    common_key_index: CommonKeyIndex,
     cancel: &CancelToken,
     overwrite: bool,
 ) -> Result<()> {
     check_cancel(cancel)?;
-    let tmd_path = find_tmd_file(cdn_dir).await?;
+    let tmd_path = find_tmd_file(cdn_dir, tmd_version).await?;
     debug!("Found TMD file at {}", tmd_path.display());
 
     let mut ticket_metadata_data = Cursor::new(fs::read(&tmd_path).await?);
@@ -252,18 +510,49 @@ pub(crate) async fn generate_ticket_from_cdn_with_publish(
 
     let title_id_str = format!("{:016X}", title_metadata.header.title_id);
 
-    let title_key = generate_title_key(&title_id_str, None)?;
+    let key_index = common_key_index.resolve(title_metadata.header.title_id);
+    match common_key_index {
+        CommonKeyIndex::Auto => {
+            info!("Using common key index {key_index}, inferred from title ID {title_id_str}")
+        }
+        CommonKeyIndex::Fixed(_) => info!("Using common key index {key_index}"),
+    }
+    let title_key = match title_key {
+        Some(key) => {
+            info!("Using the supplied title key instead of deriving one");
+            hex::encode(key)
+        }
+        None => {
+            generate_title_key_with_index(&title_id_str, password.map(str::to_string), key_index)?
+        }
+    };
 
     const CETK_STRING_TEMPLATE: &str = "00010004d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0d15ea5e0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000526f6f742d434130303030303030332d585330303030303030630000000000000000000000000000000000000000000000000000000000000000000000000000feedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedfacefeedface010000eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee00000000000000000000000000dddddddddddddddd00001111000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010014000000ac000000140001001400000000000000280000000100000084000000840003000000000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010004919ebe464ad0f552cd1b72e7884910cf55a9f02e50789641d896683dc005bd0aea87079d8ac284c675065f74c8bf37c88044409502a022980bb8ad48383f6d28a79de39626ccb2b22a0f19e41032f094b39ff0133146dec8f6c1a9d55cd28d9e1c47b3d11f4f5426c2c780135a2775d3ca679bc7e834f0e0fb58e68860a71330fc95791793c8fba935a7a6908f229dee2a0ca6b9b23b12d495a6fe19d0d72648216878605a66538dbf376899905d3445fc5c727a0e13e0e2c8971c9cfa6c60678875732a4e75523d2f562f12aabd1573bf06c94054aefa81a71417af9a4a066d0ffc5ad64bab28b1ff60661f4437d49e1e0d9412eb4bcacf4cfd6a3408847982000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000526f6f742d43413030303030303033000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000158533030303030303063000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000137a0894ad505bb6c67e2e5bdd6a3bec43d910c772e9cc290da58588b77dcc11680bb3e29f4eabbb26e98c2601985c041bb14378e689181aad770568e928a2b98167ee3e10d072beef1fa22fa2aa3e13f11e1836a92a4281ef70aaf4e462998221c6fbb9bdd017e6ac590494e9cea9859ceb2d2a4c1766f2c33912c58f14a803e36fccdcccdc13fd7ae77c7a78d997e6acc35557e0d3e9eb64b43c92f4c50d67a602deb391b06661cd32880bd64912af1cbcb7162a06f02565d3b0ece4fcecddae8a4934db8ee67f3017986221155d131c6c3f09ab1945c206ac70c942b36f49a1183bcd78b6e4b47c6c5cac0f8d62f897c6953dd12f28b70c5b7df751819a9834652625000100010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010003704138efbbbda16a987dd901326d1c9459484c88a2861b91a312587ae70ef6237ec50e1032dc39dde89a96a8e859d76a98a6e7e36a0cfe352ca893058234ff833fcb3b03811e9f0dc0d9a52f8045b4b2f9411b67a51c44b5ef8ce77bd6d56ba75734a1856de6d4bed6d3a242c7c8791b3422375e5c779abf072f7695efa0f75bcb83789fc30e3fe4cc8392207840638949c7f688565f649b74d63d8d58ffadda571e9554426b1318fc468983d4c8a5628b06b6fc5d507c13e7a18ac1511eb6d62ea5448f83501447a9afb3ecc2903c9dd52f922ac9acdbef58c6021848d96e208732d3d1d9d9ea440d91621c7a99db8843c59c1f2e2c7d9b577d512c166d6f7e1aad4a774a37447e78fe2021e14a95d112a068ada019f463c7a55685aabb6888b9246483d18b9c806f474918331782344a4b8531334b26303263d9d2eb4f4bb99602b352f6ae4046c69a5e7e8e4a18ef9bc0a2ded61310417012fd824cc116cfb7c4c1f7ec7177a17446cbde96f3edd88fcd052f0b888a45fdaf2b631354f40d16e5fa9c2c4eda98e798d15e6046dc5363f3096b2c607a9d8dd55b1502a6ac7d3cc8d8c575998e7d796910c804c495235057e91ecd2637c9c1845151ac6b9a0490ae3ec6f47740a0db0ba36d075956cee7354ea3e9a4f2720b26550c7d394324bc0cb7e9317d8a8661f42191ff10b08256ce3fd25b745e5194906b4d61cb4c2e000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000526f6f7400000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001434130303030303030330000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000007be8ef6cb279c9e2eee121c6eaf44ff639f88f078b4b77ed9f9560b0358281b50e55ab721115a177703c7a30fe3ae9ef1c60bc1d974676b23a68cc04b198525bc968f11de2db50e4d9e7f071e562dae2092233e9d363f61dd7c19ff3a4a91e8f6553d471dd7b84b9f1b8ce7335f0f5540563a1eab83963e09be901011f99546361287020e9cc0dab487f140d6626a1836d27111f2068de4772149151cf69c61ba60ef9d949a0f71f5499f2d39ad28c7005348293c431ffbd33f6bca60dc7195ea2bcc56d200baf6d06d09c41db8de9c720154ca4832b69c08c69cd3b073a0063602f462d338061a5ea6c915cd5623579c3eb64ce44ef586d14baaa8834019b3eebeed3790001000100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+    // The ticket data's common key index byte, 0xB1 past the 0x140-byte
+    // signature block.
+    const CETK_COMMON_KEY_INDEX_OFFSET: usize = 0x1F1;
+
+    let tmd_version = title_metadata.header.title_version;
+    let version = match title_version {
+        Some(version) if version != tmd_version => {
+            warn!(
+                "Ticket title version {version} overrides TMD version {tmd_version}; retail units may refuse to install it"
+            );
+            version
+        }
+        _ => tmd_version,
+    };
+    let title_version_hex = format!("{version:04x}");
 
-    let title_version_hex = format!("{:04x}", title_metadata.header.title_version);
-
+    check_cetk_title_key(&title_key)?;
     let cetk = CETK_STRING_TEMPLATE
         .replace("eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee", &title_key)
         .replace("1111", &title_version_hex)
         .replace("dddddddddddddddd", &title_id_str);
 
-    let bytes = hex::decode(cetk)?;
+    let mut bytes = hex::decode(cetk)?;
+    bytes[CETK_COMMON_KEY_INDEX_OFFSET] = key_index;
+    let title_key_bytes = hex::decode(&title_key)?;
     check_cancel(cancel)?;
     let parent = output
         .parent()
@@ -272,6 +561,13 @@ pub(crate) async fn generate_ticket_from_cdn_with_publish(
     let mut file = tempfile::NamedTempFile::new_in(parent)?;
     file.write_all(&bytes)?;
     file.as_file().sync_all()?;
+    verify_generated_ticket(
+        &std::fs::read(file.path())?,
+        title_metadata.header.title_id,
+        &title_key_bytes,
+        version,
+        key_index,
+    )?;
     check_cancel(cancel)?;
     crate::util::publish_temp(file.into_temp_path(), output, overwrite)?;
 
@@ -280,6 +576,67 @@ pub(crate) async fn generate_ticket_from_cdn_with_publish(
     Ok(())
 }
 
+/// The ticket template holds the title key as a 32-digit placeholder;
+/// anything but 16 bytes of hex would shift every field after it.
+fn check_cetk_title_key(title_key: &str) -> Result<()> {
+    if title_key.len() != 32 || !title_key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(NintendoCTRError::InvalidTicketTitleKey(title_key.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Parse a freshly generated ticket back and confirm the template
+/// substitutions landed where the ticket fields are, so a bad template
+/// edit or a wrongly sized title key fails instead of writing a broken
+/// file.
+fn verify_generated_ticket(
+    bytes: &[u8],
+    title_id: u64,
+    title_key: &[u8],
+    title_version: u16,
+    common_key_index: u8,
+) -> Result<()> {
+    let ticket = Ticket::read(&mut Cursor::new(bytes))?;
+    let data = &ticket.ticket_data;
+    let mismatch = |field, expected: String, actual: String| {
+        Err(NintendoCTRError::GeneratedTicketMismatch {
+            field,
+            expected,
+            actual,
+        }
+        .into())
+    };
+    if data.title_id != title_id {
+        return mismatch(
+            "title ID",
+            format!("{title_id:016X}"),
+            format!("{:016X}", data.title_id),
+        );
+    }
+    if data.title_key != title_key {
+        return mismatch(
+            "title key",
+            hex::encode(title_key),
+            hex::encode(&data.title_key),
+        );
+    }
+    if data.ticket_title_version != title_version {
+        return mismatch(
+            "title version",
+            title_version.to_string(),
+            data.ticket_title_version.to_string(),
+        );
+    }
+    if data.common_key_index != common_key_index {
+        return mismatch(
+            "common key index",
+            common_key_index.to_string(),
+            data.common_key_index.to_string(),
+        );
+    }
+    Ok(())
+}
+
 fn check_cancel(cancel: &CancelToken) -> Result<()> {
     if cancel.is_cancelled() {
         return Err(NintendoCTRError::Cancelled.into());
@@ -292,15 +649,30 @@ pub async fn convert_cdn_to_cia(
     progress: &dyn ProgressReporter,
     total_progress: &dyn ProgressReporter,
 ) -> Result<()> {
-    convert_cdn_to_cia_cancellable(opts, progress, total_progress, CancelToken::new()).await
+    convert_cdn_to_cia_cancellable(
+        opts,
+        progress,
+        total_progress,
+        &mut Tally::new(),
+        CancelToken::new(),
+    )
+    .await
 }
 
+/// Builds a CIA from `opts.cdn_dir`, or with `recursive` from each of its
+/// subdirectories, recording every title in `tally`. A failed title in a
+/// recursive run is logged and skipped, or with `fail_fast` ends the run
+/// with its error.
 pub async fn convert_cdn_to_cia_cancellable(
     opts: CdnToCiaOptions,
     progress: &dyn ProgressReporter,
     total_progress: &dyn ProgressReporter,
+    tally: &mut Tally,
     cancel: CancelToken,
 ) -> Result<()> {
+    if opts.recursive && opts.ticket.is_some() {
+        anyhow::bail!("a ticket path names one title's ticket and cannot be used with recursive");
+    }
     if opts.recursive {
         let mut count: u64 = 0;
         let mut dirs = tokio::fs::read_dir(&opts.cdn_dir).await?;
@@ -331,30 +703,60 @@ pub async fn convert_cdn_to_cia_cancellable(
             }
 
             let child_dir = entry.path();
+            if let Some(filter) = opts.region {
+                match cdn_title_region(&child_dir, opts.tmd_version, &opts.ticket_source()).await {
+                    Ok(region) if filter.accepts(region) => {}
+                    Ok(region) => {
+                        info!(
+                            "Skipping {}: region {region} does not match {filter}",
+                            child_dir.display()
+                        );
+                        tally.record_skipped();
+                        total_progress.inc(1);
+                        continue;
+                    }
+                    Err(err) => {
+                        info!("Skipping {}: region unknown ({err})", child_dir.display());
+                        tally.record_skipped();
+                        total_progress.inc(1);
+                        continue;
+                    }
+                }
+            }
             let mut opts_clone = opts.clone();
-            opts_clone.output = opts.output_dir.as_deref().and_then(|dir| {
-                child_dir.file_name().map(|name| {
-                    let derived =
-                        child_dir.with_file_name(format!("{}.cia", name.to_string_lossy()));
-                    crate::util::place_in_dir(&derived, Some(dir))
-                })
-            });
+            opts_clone.output = None;
             opts_clone.cdn_dir = child_dir;
 
-            if let Err(err) = convert_cdn_to_cia_single(opts_clone, progress, cancel.clone()).await
-            {
-                if err
-                    .downcast_ref::<std::io::Error>()
-                    .is_some_and(|err| err.kind() == std::io::ErrorKind::InvalidInput)
-                {
-                    total_progress.finish();
-                    return Err(err);
+            let started = Instant::now();
+            let input_bytes = cdn_dir_bytes(&opts_clone.cdn_dir);
+            match convert_cdn_to_cia_single(opts_clone, progress, cancel.clone()).await {
+                Ok(Some(output)) => record_cdn_title(tally, input_bytes, &output, started),
+                Ok(None) => tally.record_skipped(),
+                Err(err) => {
+                    if matches!(
+                        err.downcast_ref::<NintendoCTRError>(),
+                        Some(NintendoCTRError::Cancelled)
+                    ) || err
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(|err| err.kind() == std::io::ErrorKind::InvalidInput)
+                    {
+                        total_progress.finish();
+                        return Err(err);
+                    }
+                    tally.record_failed();
+                    if opts.fail_fast {
+                        total_progress.finish();
+                        return Err(err.context(format!(
+                            "Failed to convert CDN directory {}",
+                            entry.path().display()
+                        )));
+                    }
+                    warn!(
+                        "Failed to convert CDN directory {}: {}",
+                        entry.path().display(),
+                        err
+                    );
                 }
-                warn!(
-                    "Failed to convert CDN directory {}: {}",
-                    entry.path().display(),
-                    err
-                );
             }
 
             total_progress.inc(1);
@@ -363,27 +765,61 @@ pub async fn convert_cdn_to_cia_cancellable(
         total_progress.finish();
         Ok(())
     } else {
-        convert_cdn_to_cia_single(opts, progress, cancel).await
+        let started = Instant::now();
+        let input_bytes = cdn_dir_bytes(&opts.cdn_dir);
+        match convert_cdn_to_cia_single(opts, progress, cancel).await {
+            Ok(Some(output)) => record_cdn_title(tally, input_bytes, &output, started),
+            Ok(None) => tally.record_skipped(),
+            Err(err) => {
+                tally.record_failed();
+                return Err(err);
+            }
+        }
+        Ok(())
     }
 }
 
+/// Bytes in the files directly under a CDN directory, read before the
+/// conversion since `cleanup` removes them.
+fn cdn_dir_bytes(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|meta| meta.is_file())
+                .map(|meta| meta.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn record_cdn_title(tally: &mut Tally, input_bytes: u64, output: &Path, started: Instant) {
+    let output_bytes = std::fs::metadata(output).map(|m| m.len()).unwrap_or(0);
+    tally.record_ok(input_bytes, output_bytes, started.elapsed());
+}
+
 async fn convert_cdn_to_cia_single(
     opts: CdnToCiaOptions,
     progress: &dyn ProgressReporter,
     cancel: CancelToken,
-) -> Result<()> {
+) -> Result<Option<PathBuf>> {
     let output = match opts.output {
         Some(path) => path,
         None => {
-            let name = opts
-                .cdn_dir
-                .file_name()
-                .and_then(|name| name.to_str())
-                .map(|name| format!("{name}.cia"))
-                .ok_or_else(|| anyhow::anyhow!("CDN directory path has no name"))?;
-
-            let parent = opts.cdn_dir.parent().unwrap_or_else(|| Path::new("."));
-            parent.join(name)
+            let path = cdn_cia_output_path(
+                &opts.cdn_dir,
+                opts.name_from,
+                opts.output_template.as_deref(),
+                opts.tmd_version,
+                opts.output_dir.as_deref(),
+                &opts.ticket_source(),
+            )
+            .await?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            path
         }
     };
 
@@ -407,48 +843,81 @@ async fn convert_cdn_to_cia_single(
     let final_output = match resolve_conflict(&final_path, opts.on_conflict)? {
         ConflictResolution::Skip => {
             info!("Skipped, output exists: {}", final_path.display());
-            return Ok(());
+            return Ok(None);
         }
         ConflictResolution::Write(resolved) => resolved,
     };
 
-    let ticket_path = find_title_file(cdn_dir)
-        .or_else(|err| async {
-            if opts.ensure_ticket_exists {
-                check_cancel(&cancel)?;
-                let desired = cdn_dir.join("ticket.tik");
-                let path = match resolve_conflict(&desired, opts.on_conflict)? {
-                    ConflictResolution::Skip => desired,
-                    ConflictResolution::Write(path) => {
-                        generate_ticket_from_cdn_with_publish(
-                            cdn_dir,
-                            &path,
-                            &cancel,
-                            opts.on_conflict == ConflictPolicy::Overwrite,
-                        )
-                        .await?;
-                        path
+    let ticket_path = match opts.ticket.clone() {
+        Some(path) => path,
+        None => {
+            find_title_file(cdn_dir)
+                .or_else(|err| async {
+                    if opts.ensure_ticket_exists {
+                        check_cancel(&cancel)?;
+                        let desired = cdn_dir.join("ticket.tik");
+                        let path = match resolve_conflict(&desired, opts.on_conflict)? {
+                            ConflictResolution::Skip => desired,
+                            ConflictResolution::Write(path) => {
+                                generate_ticket_from_cdn_with_publish(
+                                    cdn_dir,
+                                    &path,
+                                    opts.tmd_version,
+                                    None,
+                                    None,
+                                    opts.title_key,
+                                    opts.common_key_index,
+                                    &cancel,
+                                    opts.on_conflict == ConflictPolicy::Overwrite,
+                                )
+                                .await?;
+                                path
+                            }
+                        };
+                        debug!("Path for ticket file: {}", path.display());
+                        debug!("CDN Directory: {}", cdn_dir.display());
+                        Ok::<PathBuf, anyhow::Error>(path)
+                    } else {
+                        Err(err.into())
                     }
-                };
-                debug!("Path for ticket file: {}", path.display());
-                debug!("CDN Directory: {}", cdn_dir.display());
-                Ok::<PathBuf, anyhow::Error>(path)
-            } else {
-                Err(err.into())
-            }
-        })
-        .await?;
-    debug!("Found Ticket file at {}", ticket_path.display());
+                })
+                .await?
+        }
+    };
+    info!("Using ticket {}", ticket_path.display());
 
-    let title_metadata_path = find_tmd_file(cdn_dir).await?;
+    let title_metadata_path = find_tmd_file(cdn_dir, opts.tmd_version).await?;
     debug!("Found TMD file at {}", title_metadata_path.display());
 
     let mut ticket_metadata_data = Cursor::new(fs::read(&title_metadata_path).await?);
     let title_metadata = TitleMetadata::read(&mut ticket_metadata_data)?;
+    match tmd_file_version(&title_metadata_path) {
+        Some(file_version) => info!(
+            "Using {} (file version {file_version}, title version {})",
+            title_metadata_path.display(),
+            title_metadata.header.title_version
+        ),
+        None => info!(
+            "Using {} (title version {})",
+            title_metadata_path.display(),
+            title_metadata.header.title_version
+        ),
+    }
 
-    let mut ticket_data = Cursor::new(fs::read(&ticket_path).await?);
+    let ticket_bytes = fs::read(&ticket_path)
+        .await
+        .with_context(|| format!("cannot read ticket: {}", ticket_path.display()))?;
+    let mut ticket_data = Cursor::new(ticket_bytes);
     let ticket = Ticket::read(&mut ticket_data)?;
 
+    // Both files are already in memory, so their certificate chains are
+    // parsed from the bytes after the TMD and ticket instead of re-read.
+    let tmd_certs = parse_certificate_chain(
+        &ticket_metadata_data.get_ref()[ticket_metadata_data.position() as usize..],
+    )?;
+    let tik_certs =
+        parse_certificate_chain(&ticket_data.get_ref()[ticket_data.position() as usize..])?;
+
     debug!("Processing CIA conversion");
 
     let ticket_title_id = ticket.ticket_data.title_id;
@@ -466,10 +935,13 @@ async fn convert_cdn_to_cia_single(
     if let Err(err) = write_cia(
         cdn_dir,
         &mut out_buffered,
-        &title_metadata_path,
-        &ticket_path,
         title_metadata,
         ticket,
+        tmd_certs,
+        tik_certs,
+        opts.fix_content_size,
+        opts.raw_certs,
+        opts.content_concurrency,
         progress,
         &cancel,
     )
@@ -482,12 +954,38 @@ async fn convert_cdn_to_cia_single(
     drop(out_buffered);
     let decrypted = if opts.decrypt {
         let decrypted = private_temp_path(&final_output, ".cia")?;
-        decrypt_cia_cancellable(&encrypted, &decrypted, progress, cancel.clone()).await?;
+        decrypt_cia_cancellable(
+            &encrypted,
+            &decrypted,
+            &DecryptOptions {
+                seed_retries: opts.seed_retries,
+                ..DecryptOptions::default()
+            },
+            progress,
+            cancel.clone(),
+        )
+        .await?;
         Some(decrypted)
     } else {
         None
     };
 
+    if opts.verify_after {
+        let built = decrypted.as_deref().unwrap_or(&encrypted);
+        let problems = verify::check_built_cia(built, !opts.decrypt, &cancel).await?;
+        if !problems.is_empty() {
+            return Err(NintendoCTRError::BuiltCiaVerifyFailed {
+                title_id: title_metadata_title_id,
+                problems: problems.join("; "),
+            }
+            .into());
+        }
+        info!(
+            "verify PASS for title {title_metadata_title_id:016X}: {}",
+            final_output.display()
+        );
+    }
+
     if opts.compress {
         let output = decrypted.as_deref().unwrap_or(&encrypted);
         let compressed = private_temp_path(&final_output, ".zcia")?;
@@ -508,10 +1006,10 @@ async fn convert_cdn_to_cia_single(
         debug!("Deleted CDN directory: {}", cdn_dir.display());
     }
 
-    Ok(())
+    Ok(Some(final_output))
 }
 
-fn private_temp_path(output: &Path, suffix: &str) -> std::io::Result<TempPath> {
+fn private_temp_path(output: &Path, suffix: &str) -> std::io::Result<ScratchPath> {
     let parent = output.parent().unwrap_or_else(|| Path::new("."));
     let path = tempfile::Builder::new()
         .prefix(".rom-converto-")
@@ -519,10 +1017,20 @@ fn private_temp_path(output: &Path, suffix: &str) -> std::io::Result<TempPath> {
         .tempfile_in(parent)?
         .into_temp_path();
     std::fs::remove_file(&path)?;
-    Ok(path)
+    Ok(ScratchPath::new(path))
 }
 
-fn publish_temp_path(path: TempPath, output: &Path, policy: ConflictPolicy) -> std::io::Result<()> {
+/// Flush `path` to disk and rename it over `output`. The rename is atomic,
+/// so an existing output stays whole until the new one is complete.
+fn publish_temp_path(
+    path: ScratchPath,
+    output: &Path,
+    policy: ConflictPolicy,
+) -> std::io::Result<()> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)?
+        .sync_all()?;
     crate::util::publish_temp(path, output, policy == ConflictPolicy::Overwrite)
 }
 
@@ -582,20 +1090,30 @@ pub async fn decrypt_rom_batch(
     decrypt_rom_batch_cancellable(
         input_dir,
         output_dir,
+        &DecryptOptions::default(),
         progress,
         total_progress,
         max_depth,
+        false,
+        &mut Tally::new(),
         CancelToken::new(),
     )
     .await
 }
 
+/// Runs the decrypt over every matching file under `input_dir`, recording each
+/// item in `tally`. A failed item is logged and skipped, or with `fail_fast`
+/// ends the run with its error.
+#[allow(clippy::too_many_arguments)]
 pub async fn decrypt_rom_batch_cancellable(
     input_dir: &Path,
     output_dir: Option<&Path>,
+    opts: &DecryptOptions,
     progress: &dyn ProgressReporter,
     total_progress: &dyn ProgressReporter,
     max_depth: Option<usize>,
+    fail_fast: bool,
+    tally: &mut Tally,
     cancel: CancelToken,
 ) -> Result<()> {
     let roms = crate::util::fs::collect_files_with_exts(input_dir, DECRYPT_EXTS, max_depth)?;
@@ -631,14 +1149,22 @@ pub async fn decrypt_rom_batch_cancellable(
         }
         debug!("Decrypting {} -> {}", path.display(), output.display());
 
-        if let Err(err) = decrypt_rom_cancellable(&path, &output, progress, cancel.clone()).await {
-            if matches!(
-                err.downcast_ref::<NintendoCTRError>(),
-                Some(NintendoCTRError::Cancelled)
-            ) {
-                return Err(err);
+        let started = Instant::now();
+        match decrypt_rom_cancellable(&path, &output, opts, progress, cancel.clone()).await {
+            Ok(()) => tally.record_ok_files(&path, &output, started.elapsed()),
+            Err(err) => {
+                if matches!(
+                    err.downcast_ref::<NintendoCTRError>(),
+                    Some(NintendoCTRError::Cancelled)
+                ) {
+                    return Err(err);
+                }
+                tally.record_failed();
+                if fail_fast {
+                    return Err(err.context(format!("Failed to decrypt {}", path.display())));
+                }
+                warn!("Failed to decrypt {}: {err}", path.display());
             }
-            warn!("Failed to decrypt {}: {err}", path.display());
         }
 
         total_progress.inc(1);
@@ -653,14 +1179,18 @@ mod tests {
     use super::*;
     use crate::nintendo::ctr::models::cia::CiaFile;
     use crate::nintendo::ctr::test_fixtures::{append_be, make_cert, make_ticket, make_tmd};
+    use crate::nintendo::ctr::title_key::generate_title_key;
     use crate::util::NoProgress;
     use binrw::Endian;
     use sha2::{Digest, Sha256};
 
     fn write_cdn_title(dir: &Path, title_id: u64) {
+        write_cdn_title_with_content(dir, title_id, (0..0x400u32).map(|i| i as u8).collect());
+    }
+
+    fn write_cdn_title_with_content(dir: &Path, title_id: u64, content: Vec<u8>) {
         std::fs::create_dir_all(dir).unwrap();
 
-        let content: Vec<u8> = (0..0x400u32).map(|i| i as u8).collect();
         let mut hasher = Sha256::new();
         hasher.update(&content);
         let mut hash = [0u8; 32];
@@ -693,6 +1223,19 @@ mod tests {
             compress: false,
             output_dir: None,
             on_conflict,
+            name_from: CdnNameFrom::Dir,
+            verify_after: false,
+            fix_content_size: false,
+            tmd_version: None,
+            common_key_index: CommonKeyIndex::Auto,
+            title_key: None,
+            raw_certs: false,
+            ticket: None,
+            output_template: None,
+            region: None,
+            content_concurrency: 1,
+            seed_retries: crate::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+            fail_fast: false,
         }
     }
 
@@ -707,6 +1250,19 @@ mod tests {
             compress: false,
             output_dir: None,
             on_conflict: ConflictPolicy::Error,
+            name_from: CdnNameFrom::Dir,
+            verify_after: false,
+            fix_content_size: false,
+            tmd_version: None,
+            common_key_index: CommonKeyIndex::Auto,
+            title_key: None,
+            raw_certs: false,
+            ticket: None,
+            output_template: None,
+            region: None,
+            content_concurrency: 1,
+            seed_retries: crate::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+            fail_fast: false,
         }
     }
 
@@ -723,9 +1279,16 @@ mod tests {
         let cancel = CancelToken::new();
         cancel.cancel();
 
-        let err = generate_ticket_from_cdn_cancellable(tmp.path(), &output, &cancel)
-            .await
-            .unwrap_err();
+        let err = generate_ticket_from_cdn_cancellable(
+            tmp.path(),
+            &output,
+            None,
+            None,
+            CommonKeyIndex::Auto,
+            &cancel,
+        )
+        .await
+        .unwrap_err();
 
         assert!(matches!(
             err.downcast_ref::<NintendoCTRError>(),
@@ -734,6 +1297,240 @@ mod tests {
         assert_eq!(std::fs::read(output).unwrap(), b"existing");
     }
 
+    #[tokio::test]
+    async fn ticket_title_version_defaults_to_tmd_and_can_be_overridden() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+
+        for (override_version, expected) in [(None, 0x0100), (Some(1024), 1024)] {
+            let output = tmp.path().join("ticket.tik");
+            generate_ticket_from_cdn_cancellable(
+                &cdn,
+                &output,
+                override_version,
+                None,
+                CommonKeyIndex::Auto,
+                &CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            let ticket = Ticket::read(&mut Cursor::new(std::fs::read(&output).unwrap())).unwrap();
+            assert_eq!(ticket.ticket_data.ticket_title_version, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_ticket_carries_the_title_id_and_derived_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        let output = tmp.path().join("ticket.tik");
+
+        generate_ticket_from_cdn(&cdn, &output).await.unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let ticket = Ticket::read(&mut Cursor::new(&bytes)).unwrap();
+        let key = hex::decode(generate_title_key("0004000000155400", None).unwrap()).unwrap();
+        assert_eq!(ticket.ticket_data.title_id, 0x0004000000155400);
+        assert_eq!(ticket.ticket_data.title_key, key);
+        verify_generated_ticket(&bytes, 0x0004000000155400, &key, 0x0100, 0).unwrap();
+    }
+
+    #[tokio::test]
+    async fn generated_ticket_uses_the_given_password() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        let output = tmp.path().join("ticket.tik");
+
+        generate_ticket_from_cdn_cancellable(
+            &cdn,
+            &output,
+            None,
+            Some("password"),
+            CommonKeyIndex::Auto,
+            &CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let ticket = Ticket::read(&mut Cursor::new(std::fs::read(&output).unwrap())).unwrap();
+        let key = generate_title_key("0004000000155400", Some("password".to_string())).unwrap();
+        assert_eq!(ticket.ticket_data.title_key, hex::decode(key).unwrap());
+    }
+
+    #[tokio::test]
+    async fn generated_ticket_common_key_index_is_inferred_or_fixed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004001000021000);
+
+        for (choice, expected) in [(CommonKeyIndex::Auto, 1), (CommonKeyIndex::Fixed(0), 0)] {
+            let output = tmp.path().join("ticket.tik");
+            generate_ticket_from_cdn_cancellable(
+                &cdn,
+                &output,
+                None,
+                None,
+                choice,
+                &CancelToken::new(),
+            )
+            .await
+            .unwrap();
+            let ticket = Ticket::read(&mut Cursor::new(std::fs::read(&output).unwrap())).unwrap();
+            let key = generate_title_key_with_index("0004001000021000", None, expected).unwrap();
+            assert_eq!(ticket.ticket_data.common_key_index, expected);
+            assert_eq!(ticket.ticket_data.title_key, hex::decode(key).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_ticket_writes_a_supplied_title_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        let output = tmp.path().join("ticket.tik");
+        let key = [0x5Au8; 16];
+
+        generate_ticket_from_cdn_with_publish(
+            &cdn,
+            &output,
+            None,
+            None,
+            Some("ignored"),
+            Some(key),
+            CommonKeyIndex::Fixed(2),
+            &CancelToken::new(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        let ticket = Ticket::read(&mut Cursor::new(std::fs::read(&output).unwrap())).unwrap();
+        assert_eq!(ticket.ticket_data.title_key, key);
+        assert_eq!(ticket.ticket_data.common_key_index, 2);
+    }
+
+    #[test]
+    fn cetk_title_key_must_be_32_hex_digits() {
+        check_cetk_title_key("3c7faeff5b1d784d25011149f33f50a7").unwrap();
+        for bad in [
+            "3c7faeff5b1d784d25011149f33f50",
+            "3c7faeff5b1d784d25011149f33f50a7ff",
+            "3c7faeff5b1d784d25011149f33f50zz",
+            "",
+        ] {
+            let err = check_cetk_title_key(bad).unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<NintendoCTRError>(),
+                    Some(NintendoCTRError::InvalidTicketTitleKey(_))
+                ),
+                "{bad:?}: {err}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_ticket_mismatch_is_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        let output = tmp.path().join("ticket.tik");
+        generate_ticket_from_cdn(&cdn, &output).await.unwrap();
+        let bytes = std::fs::read(&output).unwrap();
+        let key = hex::decode(generate_title_key("0004000000155400", None).unwrap()).unwrap();
+
+        let err = verify_generated_ticket(&bytes, 0x0004000000155401, &key, 0x0100, 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<NintendoCTRError>(),
+            Some(NintendoCTRError::GeneratedTicketMismatch {
+                field: "title ID",
+                ..
+            })
+        ));
+        let err =
+            verify_generated_ticket(&bytes, 0x0004000000155400, &key[..8], 0x0100, 0).unwrap_err();
+        assert!(err.to_string().contains("title key"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_builds_a_cia_from_a_tmd_a_generated_ticket_and_contents() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        std::fs::create_dir_all(&cdn).unwrap();
+        let title_id = 0x0004000000155400u64;
+        let contents: Vec<(u32, Vec<u8>)> = vec![
+            (0x00, (0..0x800u32).map(|i| (i * 7) as u8).collect()),
+            (0x05, vec![0xA5; 0x240]),
+        ];
+        let records = contents
+            .iter()
+            .enumerate()
+            .map(|(index, (id, data))| {
+                std::fs::write(cdn.join(format!("{id:08x}")), data).unwrap();
+                (*id, index as u16, data.clone(), Sha256::digest(data).into())
+            })
+            .collect();
+        let mut tmd_buf = Vec::new();
+        append_be(&mut tmd_buf, &make_tmd(title_id, records));
+        append_be(&mut tmd_buf, &make_cert(b"CP0000000b", 0xBB));
+        append_be(&mut tmd_buf, &make_cert(b"CA00000003", 0xAA));
+        std::fs::write(cdn.join("tmd"), &tmd_buf).unwrap();
+        generate_ticket_from_cdn(&cdn, &cdn.join("cetk"))
+            .await
+            .unwrap();
+
+        let output = tmp.path().join("title.cia");
+        convert_cdn_to_cia(single_opts(cdn, output.clone()), &NoProgress, &NoProgress)
+            .await
+            .unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let cia = CiaFile::read_options(&mut Cursor::new(&bytes), Endian::Little, ()).unwrap();
+        assert_eq!(cia.ticket.ticket_data.title_id, title_id);
+        assert_eq!(cia.tmd.header.title_id, title_id);
+        let sizes: Vec<(u32, u64)> = cia
+            .tmd
+            .content_chunk_records
+            .iter()
+            .map(|record| (record.content_id, record.content_size))
+            .collect();
+        assert_eq!(sizes, [(0x00, 0x800), (0x05, 0x240)]);
+        assert_eq!(cia.header.content_size, 0x800 + 0x240);
+        assert_eq!(cia.content_data[..0x800], contents[0].1[..]);
+        assert_eq!(cia.content_data[0x800..], contents[1].1[..]);
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_ignores_footers_after_the_tmd_and_ticket_certs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        for name in ["tmd", "cetk"] {
+            let mut bytes = std::fs::read(cdn.join(name)).unwrap();
+            bytes.extend_from_slice(b"FOOTER");
+            bytes.extend_from_slice(&[0xA5; 0x3FA]);
+            std::fs::write(cdn.join(name), bytes).unwrap();
+        }
+        let output = tmp.path().join("title.cia");
+        let mut opts = single_opts(cdn, output.clone());
+        opts.raw_certs = true;
+        convert_cdn_to_cia(opts, &NoProgress, &NoProgress)
+            .await
+            .unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let cia = CiaFile::read_options(&mut Cursor::new(&bytes), Endian::Little, ()).unwrap();
+        let names: Vec<&[u8]> = cia.cert_chain.iter().map(|c| &c.name[..10]).collect();
+        assert_eq!(
+            names,
+            [b"CP0000000b".as_slice(), b"CA00000003", b"XS0000000c"]
+        );
+        assert_eq!(cia.tmd.header.title_id, 0x0004000000155400);
+    }
+
     #[tokio::test]
     async fn cdn_to_cia_recursive_converts_each_subfolder() {
         let tmp = tempfile::tempdir().unwrap();
@@ -755,6 +1552,225 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn cdn_to_cia_recursive_tallies_titles_and_honours_fail_fast() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("cdn");
+        write_cdn_title(&root.join("title_a"), 0x0004000000030000);
+        write_cdn_title(&root.join("title_b"), 0x0004000000030001);
+        std::fs::create_dir_all(root.join("broken")).unwrap();
+
+        let mut tally = Tally::new();
+        let opts = recursive_opts(root.clone(), ConflictPolicy::Error);
+        convert_cdn_to_cia_cancellable(
+            opts,
+            &NoProgress,
+            &NoProgress,
+            &mut tally,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((tally.ok_count(), tally.failed_count()), (2, 1));
+
+        let mut tally = Tally::new();
+        let mut opts = recursive_opts(root.clone(), ConflictPolicy::Overwrite);
+        opts.fail_fast = true;
+        let err = convert_cdn_to_cia_cancellable(
+            opts,
+            &NoProgress,
+            &NoProgress,
+            &mut tally,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("broken"), "{err:#}");
+        assert_eq!(tally.failed_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_recursive_names_outputs_from_title_id() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        write_cdn_title(&root.join("a").join("title"), 0x0004000000155400);
+        write_cdn_title(&root.join("b").join("title"), 0x0004000000155401);
+        let out_dir = tmp.path().join("out");
+
+        for parent in ["a", "b"] {
+            let mut opts = recursive_opts(root.join(parent), ConflictPolicy::Error);
+            opts.output_dir = Some(out_dir.clone());
+            opts.name_from = CdnNameFrom::TitleIdVersion;
+            convert_cdn_to_cia(opts, &NoProgress, &NoProgress)
+                .await
+                .unwrap();
+        }
+
+        for name in ["0004000000155400 v256.cia", "0004000000155401 v256.cia"] {
+            let out = out_dir.join(name);
+            assert!(parses_as_cia(&out), "{} is not a valid CIA", out.display());
+        }
+        assert_eq!(
+            cdn_cia_file_name(&root.join("a").join("title"), CdnNameFrom::TitleId, None)
+                .await
+                .unwrap(),
+            "0004000000155400.cia"
+        );
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_recursive_output_template_resolves_per_title() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("dumps");
+        write_cdn_title(&root.join("Game (Disc 1)"), 0x0004000000155400);
+        write_cdn_title(&root.join("Other"), 0x0004000000155401);
+        let out_dir = tmp.path().join("out");
+
+        let mut opts = recursive_opts(root.clone(), ConflictPolicy::Error);
+        opts.output_dir = Some(out_dir.clone());
+        opts.output_template = Some("{console}/{titleId}/{basename} v{titleVersion}.{ext}".into());
+        convert_cdn_to_cia(opts, &NoProgress, &NoProgress)
+            .await
+            .unwrap();
+
+        for out in [
+            out_dir.join("3DS/0004000000155400/Game (Disc 1) v256.cia"),
+            out_dir.join("3DS/0004000000155401/Other v256.cia"),
+        ] {
+            assert!(parses_as_cia(&out), "{} is not a valid CIA", out.display());
+        }
+        let rel = cdn_cia_templated_path(
+            &root.join("Game (Disc 1)"),
+            "{disc}.{ext}",
+            None,
+            &CdnTicketSource::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(rel, Path::new("1.cia"));
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_recursive_region_skips_other_regions() {
+        use crate::nintendo::ctr::test_fixtures::make_ncch_header_bytes;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("dumps");
+        for (i, (name, code)) in [
+            ("usa", "CTR-P-AXXE"),
+            ("jpn", "CTR-P-AXXJ"),
+            ("free", "CTR-P-AXXA"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let title_id = 0x0004000000155400 + i as u64;
+            let mut content = make_ncch_header_bytes(title_id);
+            content[0x150..0x150 + code.len()].copy_from_slice(code.as_bytes());
+            write_cdn_title_with_content(&root.join(name), title_id, content);
+        }
+        write_cdn_title(&root.join("unknown"), 0x0004000000155410);
+
+        assert_eq!(
+            cdn_title_region(&root.join("jpn"), None, &CdnTicketSource::default())
+                .await
+                .unwrap(),
+            CtrRegion::Jpn
+        );
+        assert!(
+            cdn_title_region(&root.join("unknown"), None, &CdnTicketSource::default())
+                .await
+                .is_err()
+        );
+        for (name, expected) in [("usa", "NorthAmerica/usa.cia"), ("unknown", "unknown.cia")] {
+            let rel = cdn_cia_templated_path(
+                &root.join(name),
+                "{region}/{basename}.{ext}",
+                None,
+                &CdnTicketSource::default(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(rel, Path::new(expected));
+        }
+
+        let mut opts = recursive_opts(root.clone(), ConflictPolicy::Error);
+        opts.region = Some(CtrRegion::Usa);
+        let mut tally = Tally::new();
+        convert_cdn_to_cia_cancellable(
+            opts,
+            &NoProgress,
+            &NoProgress,
+            &mut tally,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((tally.ok_count(), tally.skipped_count()), (2, 2));
+
+        assert!(parses_as_cia(&root.join("usa.cia")));
+        assert!(parses_as_cia(&root.join("free.cia")));
+        assert!(!root.join("jpn.cia").exists());
+        assert!(!root.join("unknown.cia").exists());
+    }
+
+    /// Turn the bare `tmd` of a [`write_cdn_title`] directory into `tmd.256`
+    /// and add a `tmd.1024` whose header carries title version 1024.
+    fn split_cdn_tmd_versions(dir: &Path) {
+        // Title version sits 0x9C into the TMD header, after the 0x140-byte
+        // RSA-2048 signature block.
+        const TITLE_VERSION_OFFSET: usize = 0x140 + 0x9C;
+        let tmd = std::fs::read(dir.join("tmd")).unwrap();
+        std::fs::remove_file(dir.join("tmd")).unwrap();
+        std::fs::write(dir.join("tmd.256"), &tmd).unwrap();
+        let mut newer = tmd;
+        newer[TITLE_VERSION_OFFSET..TITLE_VERSION_OFFSET + 2]
+            .copy_from_slice(&1024u16.to_be_bytes());
+        std::fs::write(dir.join("tmd.1024"), &newer).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_tmd_version_selects_that_tmd_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        split_cdn_tmd_versions(&cdn);
+
+        let name = |version| cdn_cia_file_name(&cdn, CdnNameFrom::TitleIdVersion, version);
+        assert_eq!(name(None).await.unwrap(), "0004000000155400 v1024.cia");
+        assert_eq!(name(Some(256)).await.unwrap(), "0004000000155400 v256.cia");
+
+        let mut opts = single_opts(cdn.clone(), PathBuf::new());
+        opts.output = None;
+        opts.name_from = CdnNameFrom::TitleIdVersion;
+        opts.tmd_version = Some(256);
+        convert_cdn_to_cia(opts, &NoProgress, &NoProgress)
+            .await
+            .unwrap();
+        assert!(parses_as_cia(&tmp.path().join("0004000000155400 v256.cia")));
+    }
+
+    #[tokio::test]
+    async fn cdn_to_cia_missing_tmd_version_lists_the_ones_present() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn = tmp.path().join("title");
+        write_cdn_title(&cdn, 0x0004000000155400);
+        split_cdn_tmd_versions(&cdn);
+
+        let err = cdn_cia_file_name(&cdn, CdnNameFrom::TitleId, Some(512))
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<NintendoCTRError>() {
+            Some(NintendoCTRError::TmdVersionNotFound {
+                version, available, ..
+            }) => {
+                assert_eq!(*version, 512);
+                assert_eq!(available, "tmd.1024, tmd.256");
+            }
+            other => panic!("expected TmdVersionNotFound, got {other:?}"),
+        }
+    }
+
     #[tokio::test]
     async fn cdn_to_cia_recursive_default_error_does_not_overwrite() {
         let tmp = tempfile::tempdir().unwrap();
@@ -901,6 +1917,43 @@ mod tests {
         assert_eq!(std::fs::read(zcia).unwrap(), b"PREEXISTING ZCIA");
     }
 
+    #[tokio::test]
+    async fn verify_after_passes_a_matching_cdn_title() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn_dir = tmp.path().join("title");
+        write_cdn_title(&cdn_dir, 0x0004000000030000);
+        let output = tmp.path().join("title.cia");
+
+        let mut opts = single_opts(cdn_dir, output.clone());
+        opts.verify_after = true;
+        convert_cdn_to_cia(opts, &NoProgress, &NoProgress)
+            .await
+            .unwrap();
+
+        assert!(parses_as_cia(&output));
+    }
+
+    #[tokio::test]
+    async fn verify_after_refuses_content_that_misses_the_tmd_hash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cdn_dir = tmp.path().join("title");
+        write_cdn_title(&cdn_dir, 0x0004000000030000);
+        std::fs::write(cdn_dir.join("00000000"), vec![0xEEu8; 0x400]).unwrap();
+        let output = tmp.path().join("title.cia");
+
+        let mut opts = single_opts(cdn_dir, output.clone());
+        opts.verify_after = true;
+        let err = convert_cdn_to_cia(opts, &NoProgress, &NoProgress)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<NintendoCTRError>(),
+            Some(NintendoCTRError::BuiltCiaVerifyFailed { .. })
+        ));
+        assert!(!output.exists());
+    }
+
     #[tokio::test]
     async fn decrypted_cdn_failure_preserves_existing_output() {
         let tmp = tempfile::tempdir().unwrap();
@@ -932,7 +1985,7 @@ mod tests {
         let cancel = CancelToken::new();
         cancel.cancel();
 
-        convert_cdn_to_cia_cancellable(opts, &NoProgress, &NoProgress, cancel)
+        convert_cdn_to_cia_cancellable(opts, &NoProgress, &NoProgress, &mut Tally::new(), cancel)
             .await
             .expect_err("a pre-cancelled conversion must abort");
 
@@ -1043,6 +2096,40 @@ mod tests {
             .any(|e| e.path().extension().and_then(|s| s.to_str()) == Some("ncch"))
     }
 
+    #[tokio::test]
+    async fn decrypt_ncch_with_check_romfs_reads_the_superblock_back() {
+        use crate::nintendo::ctr::constants::CTR_MEDIA_UNIT_SIZE;
+        use crate::nintendo::ctr::test_fixtures::make_ncch_header_bytes;
+
+        let mut ncch = vec![0x5Au8; 2 * CTR_MEDIA_UNIT_SIZE as usize];
+        ncch[..0x200].copy_from_slice(&make_ncch_header_bytes(0x0004_0000_0012_3400));
+        ncch[0x1B0..0x1B4].copy_from_slice(&1u32.to_le_bytes());
+        ncch[0x1B4..0x1B8].copy_from_slice(&1u32.to_le_bytes());
+        ncch[0x1B8..0x1BC].copy_from_slice(&1u32.to_le_bytes());
+        let digest = Sha256::digest(&ncch[0x200..]);
+        ncch[0x1E0..0x200].copy_from_slice(&digest);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let input = tmp.path().join("plain.cxi");
+        let output = tmp.path().join("out.cxi");
+        std::fs::write(&input, &ncch).unwrap();
+
+        decrypt_rom_cancellable(
+            &input,
+            &output,
+            &DecryptOptions {
+                check_romfs: true,
+                ..DecryptOptions::default()
+            },
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&output).unwrap(), ncch);
+    }
+
     #[tokio::test]
     async fn decrypt_cancel_before_start_leaves_no_output() {
         use crate::nintendo::ctr::test_fixtures::synth_encrypted_cia_multi_content;
@@ -1052,7 +2139,14 @@ mod tests {
 
         let token = CancelToken::new();
         token.cancel();
-        let result = decrypt_rom_cancellable(&input, &output, &NoProgress, token).await;
+        let result = decrypt_rom_cancellable(
+            &input,
+            &output,
+            &DecryptOptions::default(),
+            &NoProgress,
+            token,
+        )
+        .await;
 
         let err = result.expect_err("a pre-cancelled token must abort the decrypt");
         assert!(
@@ -1067,6 +2161,89 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn decrypt_cia_to_ncch_writes_one_file_per_content() {
+        use crate::nintendo::ctr::test_fixtures::synth_encrypted_cia_multi_content;
+
+        let ids = [0x0000_0000u32, 0x0000_ABCDu32];
+        let (tmp, input, contents) = synth_encrypted_cia_multi_content(&ids);
+        let out_dir = tmp.path().join("ncch");
+
+        let written = decrypt_cia_to_ncch_cancellable(
+            &input,
+            &out_dir,
+            false,
+            &DecryptOptions::default(),
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            written,
+            vec![
+                out_dir.join("test.0000.00000000.ncch"),
+                out_dir.join("test.0001.0000abcd.ncch"),
+            ]
+        );
+        for (path, content) in written.iter().zip(&contents) {
+            assert_eq!(&std::fs::read(path).unwrap(), content);
+        }
+        assert!(!out_dir.join("test.cia").exists());
+    }
+
+    #[tokio::test]
+    async fn decrypt_cia_to_ncch_keeps_existing_files_without_overwrite() {
+        use crate::nintendo::ctr::test_fixtures::synth_encrypted_cia_multi_content;
+
+        let (tmp, input, _) = synth_encrypted_cia_multi_content(&[0x0000_0000u32, 0x0000_0001u32]);
+        let out_dir = tmp.path().join("ncch");
+        std::fs::create_dir(&out_dir).unwrap();
+        let existing = out_dir.join("test.0001.00000001.ncch");
+        std::fs::write(&existing, b"keep").unwrap();
+
+        decrypt_cia_to_ncch_cancellable(
+            &input,
+            &out_dir,
+            false,
+            &DecryptOptions::default(),
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .expect_err("an existing content file must not be replaced");
+
+        assert_eq!(std::fs::read(&existing).unwrap(), b"keep");
+        assert!(
+            !out_dir.join("test.0000.00000000.ncch").exists(),
+            "files written before the failure are removed"
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_decrypt_cia_to_ncch_leaves_no_files() {
+        use crate::nintendo::ctr::test_fixtures::synth_encrypted_cia_multi_content;
+
+        let (tmp, input, _) = synth_encrypted_cia_multi_content(&[0x0000_0000u32, 0x0000_0001u32]);
+        let out_dir = tmp.path().join("ncch");
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        decrypt_cia_to_ncch_cancellable(
+            &input,
+            &out_dir,
+            false,
+            &DecryptOptions::default(),
+            &NoProgress,
+            cancel,
+        )
+        .await
+        .expect_err("a pre-cancelled decrypt must abort");
+
+        assert_eq!(std::fs::read_dir(&out_dir).unwrap().count(), 0);
+    }
+
     #[tokio::test]
     async fn decrypt_leaves_only_final_output_no_scratch() {
         use crate::nintendo::ctr::test_fixtures::synth_encrypted_cia_multi_content;
@@ -1079,9 +2256,15 @@ mod tests {
         drop(src_tmp);
 
         let output = dir.path().join("game.decrypted.cia");
-        decrypt_rom_cancellable(&input2, &output, &NoProgress, CancelToken::new())
-            .await
-            .unwrap();
+        decrypt_rom_cancellable(
+            &input2,
+            &output,
+            &DecryptOptions::default(),
+            &NoProgress,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
 
         assert!(output.exists(), "final output must exist");
         assert!(parses_as_cia(&output), "final output is a valid CIA");
@@ -1116,9 +2299,15 @@ mod tests {
         let output = tmp.path().join("decrypted.cia");
 
         let token = CancelToken::new();
-        decrypt_rom_cancellable(&input, &output, &NoProgress, token.clone())
-            .await
-            .expect("decrypt must succeed with an uncancelled token");
+        decrypt_rom_cancellable(
+            &input,
+            &output,
+            &DecryptOptions::default(),
+            &NoProgress,
+            token.clone(),
+        )
+        .await
+        .expect("decrypt must succeed with an uncancelled token");
         token.cancel();
 
         assert!(output.exists(), "output survives a post-completion cancel");
@@ -1141,7 +2330,14 @@ mod tests {
 
         let token = CancelToken::new();
         token.cancel();
-        let result = decrypt_rom_cancellable(&input, &output, &NoProgress, token).await;
+        let result = decrypt_rom_cancellable(
+            &input,
+            &output,
+            &DecryptOptions::default(),
+            &NoProgress,
+            token,
+        )
+        .await;
 
         let err = result.expect_err("a pre-cancelled token must abort the decrypt");
         assert!(is_ctr_cancelled(&err));
@@ -1163,9 +2359,18 @@ mod tests {
 
         let token = CancelToken::new();
         token.cancel();
-        let result =
-            decrypt_rom_batch_cancellable(dir.path(), None, &NoProgress, &NoProgress, None, token)
-                .await;
+        let result = decrypt_rom_batch_cancellable(
+            dir.path(),
+            None,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &NoProgress,
+            None,
+            false,
+            &mut Tally::new(),
+            token,
+        )
+        .await;
 
         let err = result.expect_err("a pre-cancelled token must abort the batch");
         assert!(
@@ -1193,9 +2398,12 @@ mod tests {
         let result = decrypt_rom_batch_cancellable(
             dir.path(),
             None,
+            &DecryptOptions::default(),
             &NoProgress,
             &cancel_after_first,
             None,
+            false,
+            &mut Tally::new(),
             token,
         )
         .await;
@@ -1210,6 +2418,64 @@ mod tests {
         assert_eq!(produced, 1, "only the first file completes before cancel");
     }
 
+    fn batch_dir_with_broken_first_file() -> tempfile::TempDir {
+        use crate::nintendo::ctr::test_fixtures::synth_encrypted_cia_multi_content;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.cia"), b"not a cia").unwrap();
+        let (b_tmp, b_in, _) = synth_encrypted_cia_multi_content(&[0x0000_0001u32]);
+        std::fs::copy(&b_in, dir.path().join("b.cia")).unwrap();
+        drop(b_tmp);
+        dir
+    }
+
+    #[tokio::test]
+    async fn decrypt_batch_keeps_going_past_a_failure_by_default() {
+        let dir = batch_dir_with_broken_first_file();
+        let mut tally = Tally::new();
+
+        decrypt_rom_batch_cancellable(
+            dir.path(),
+            None,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &NoProgress,
+            None,
+            false,
+            &mut tally,
+            CancelToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!((tally.ok_count(), tally.failed_count()), (1, 1));
+        assert!(dir.path().join("b.decrypted.cia").exists());
+    }
+
+    #[tokio::test]
+    async fn decrypt_batch_fail_fast_stops_at_the_first_failure() {
+        let dir = batch_dir_with_broken_first_file();
+        let mut tally = Tally::new();
+
+        let err = decrypt_rom_batch_cancellable(
+            dir.path(),
+            None,
+            &DecryptOptions::default(),
+            &NoProgress,
+            &NoProgress,
+            None,
+            true,
+            &mut tally,
+            CancelToken::new(),
+        )
+        .await
+        .expect_err("fail-fast must surface the first failure");
+
+        assert!(err.to_string().contains("a.cia"), "{err}");
+        assert_eq!((tally.ok_count(), tally.failed_count()), (0, 1));
+        assert!(!dir.path().join("b.decrypted.cia").exists());
+    }
+
     #[test]
     fn decrypt_path_cia() {
         assert_eq!(
@@ -1,5 +1,10 @@
 use crate::nintendo::ctr::models::signature::SignatureType;
-use binrw::{BinRead, BinWrite};
+use binrw::{BinRead, BinResult, BinWrite, Endian};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Issuer, key type, name and expiration time: the fixed fields between
+/// the signature block and the public key.
+const CERT_BODY_SIZE: usize = 0x40 + 4 + 0x40 + 4;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead, BinWrite)]
 #[brw(repr = u32)]
@@ -12,6 +17,17 @@ pub enum KeyType {
     EllipticCurve = 0x2,
 }
 
+impl KeyType {
+    /// Serialized size of the matching [`PublicKey`] variant, padding included.
+    pub fn public_key_size(&self) -> usize {
+        match self {
+            Self::Rsa4096 => 0x200 + 4 + 0x34,
+            Self::Rsa2048 => 0x100 + 4 + 0x34,
+            Self::EllipticCurve => 0x3C + 0x3C,
+        }
+    }
+}
+
 /// Certificates contain cryptography information for verifying signatures. These
 /// certificates are also signed: the parent/child relationship between certificates
 /// makes all the certificates effectively signed by 'Root', the public key for which
@@ -45,6 +61,38 @@ pub struct Certificate {
     pub public_key: PublicKey,
 }
 
+impl Certificate {
+    /// Serialized size of a certificate with this signature and key type:
+    /// 0x400 for the RSA-4096-signed CA certificate and 0x300 for the
+    /// RSA-2048-signed XS and CP certificates.
+    pub fn expected_size(signature_type: SignatureType, key_type: KeyType) -> usize {
+        4 + signature_type.signature_size()
+            + signature_type.padding_size()
+            + CERT_BODY_SIZE
+            + key_type.public_key_size()
+    }
+
+    /// Peek the signature and key type of the certificate starting at the
+    /// reader's position and return its expected size, leaving the position
+    /// unchanged. `None` means the bytes are not a certificate header
+    /// (padding, garbage, or too short to hold the key type), so a chain
+    /// reader should stop there instead of parsing wild lengths.
+    pub fn peek_size<R: Read + Seek>(reader: &mut R) -> BinResult<Option<u64>> {
+        let start = reader.stream_position()?;
+        let size = Self::read_header_size(reader);
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(size.ok())
+    }
+
+    fn read_header_size<R: Read + Seek>(reader: &mut R) -> BinResult<u64> {
+        let signature_type = SignatureType::read_options(reader, Endian::Big, ())?;
+        let skip = signature_type.signature_size() + signature_type.padding_size() + 0x40;
+        reader.seek(SeekFrom::Current(skip as i64))?;
+        let key_type = KeyType::read_options(reader, Endian::Big, ())?;
+        Ok(Self::expected_size(signature_type, key_type) as u64)
+    }
+}
+
 // The type of public key stored is determined by checking the key type.
 #[derive(Debug, Clone, BinRead, BinWrite)]
 #[br(import(key_type: KeyType))]
@@ -145,4 +193,73 @@ mod tests {
             _ => panic!("Wrong public key type"),
         }
     }
+
+    fn rsa2048_cert(signature_type: SignatureType) -> Certificate {
+        Certificate {
+            signature_type,
+            signature: vec![0xAA; signature_type.signature_size()],
+            padding: vec![0x00; signature_type.padding_size()],
+            issuer: vec![0x00; 0x40],
+            key_type: KeyType::Rsa2048,
+            name: vec![0x00; 0x40],
+            expiration_time: 0,
+            public_key: PublicKey::Rsa2048 {
+                modulus: vec![0xFF; 0x100],
+                public_exponent: 65537,
+                padding: vec![0x00; 0x34],
+            },
+        }
+    }
+
+    #[test]
+    fn expected_size_matches_written_size() {
+        assert_eq!(
+            Certificate::expected_size(SignatureType::Rsa4096Sha256, KeyType::Rsa2048),
+            0x400
+        );
+        assert_eq!(
+            Certificate::expected_size(SignatureType::Rsa2048Sha256, KeyType::Rsa2048),
+            0x300
+        );
+        for signature_type in [SignatureType::Rsa4096Sha256, SignatureType::Rsa2048Sha256] {
+            let mut buf = Vec::new();
+            rsa2048_cert(signature_type)
+                .write_be(&mut Cursor::new(&mut buf))
+                .unwrap();
+            let mut cursor = Cursor::new(&buf);
+            assert_eq!(
+                Certificate::peek_size(&mut cursor).unwrap(),
+                Some(buf.len() as u64)
+            );
+            assert_eq!(cursor.position(), 0);
+        }
+    }
+
+    #[test]
+    fn peek_size_rejects_garbage_and_truncated_headers() {
+        let mut buf = Vec::new();
+        rsa2048_cert(SignatureType::Rsa2048Sha256)
+            .write_be(&mut Cursor::new(&mut buf))
+            .unwrap();
+
+        // Unknown key type after a valid signature block.
+        let mut bad_key = buf.clone();
+        bad_key[0x180..0x184].copy_from_slice(&7u32.to_be_bytes());
+        assert_eq!(
+            Certificate::peek_size(&mut Cursor::new(&bad_key)).unwrap(),
+            None
+        );
+
+        // A valid signature type right before EOF.
+        assert_eq!(
+            Certificate::peek_size(&mut Cursor::new(&buf[..8])).unwrap(),
+            None
+        );
+
+        // Zero padding instead of a certificate.
+        assert_eq!(
+            Certificate::peek_size(&mut Cursor::new(&[0u8; 0x300])).unwrap(),
+            None
+        );
+    }
 }
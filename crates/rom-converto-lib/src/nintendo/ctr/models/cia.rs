@@ -1,7 +1,7 @@
-use crate::nintendo::ctr::constants::{CERT_SIG_TYPE_MAX, CERT_SIG_TYPE_MIN};
+use crate::nintendo::ctr::constants::CIA_CONTENT_INDEX_SIZE;
 use crate::nintendo::ctr::models::certificate::Certificate;
 use crate::nintendo::ctr::models::ticket::Ticket;
-use crate::nintendo::ctr::models::title_metadata::TitleMetadata;
+use crate::nintendo::ctr::models::title_metadata::{ContentChunkRecord, TitleMetadata};
 use crate::nintendo::ctr::util::{align_64, pad_to_align_64};
 use binrw::{BinRead, BinResult, BinWrite, Endian};
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -31,6 +31,90 @@ impl CiaHeader {
             self.content_index[byte_index] |= 1 << bit_index;
         }
     }
+
+    /// Clear the bitmap and set exactly the indices of `records`, the
+    /// final content list, so no bit outlives a content that was dropped
+    /// or replaced.
+    pub fn rebuild_content_indexes<'a>(
+        &mut self,
+        records: impl IntoIterator<Item = &'a ContentChunkRecord>,
+    ) {
+        self.content_index = vec![0u8; CIA_CONTENT_INDEX_SIZE];
+        for record in records {
+            self.set_content_index(record.content_index as usize);
+        }
+    }
+
+    /// Content indices whose bit is set in the bitmap, ascending.
+    pub fn content_indexes(&self) -> Vec<u16> {
+        let bits = self.content_index.len().min(u16::MAX as usize / 8 + 1) * 8;
+        (0..bits)
+            .filter(|&i| self.content_index[i / 8] & (0x80 >> (i % 8)) != 0)
+            .map(|i| i as u16)
+            .collect()
+    }
+
+    /// Compare the bitmap with the content indices the TMD lists. Emulators
+    /// and installers trust the bitmap, so content whose bit is clear may be
+    /// skipped.
+    pub fn check_content_indexes(
+        &self,
+        tmd_indexes: impl IntoIterator<Item = u16>,
+    ) -> ContentIndexMismatch {
+        let set = self.content_indexes();
+        let mut listed: Vec<u16> = tmd_indexes.into_iter().collect();
+        listed.sort_unstable();
+        listed.dedup();
+        ContentIndexMismatch {
+            missing: listed
+                .iter()
+                .copied()
+                .filter(|i| set.binary_search(i).is_err())
+                .collect(),
+            unexpected: set
+                .into_iter()
+                .filter(|i| listed.binary_search(i).is_err())
+                .collect(),
+        }
+    }
+}
+
+/// Disagreement between a CIA header's content-index bitmap and its TMD.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentIndexMismatch {
+    /// Indices the TMD lists whose bitmap bit is clear.
+    pub missing: Vec<u16>,
+    /// Bitmap bits set for indices the TMD does not list.
+    pub unexpected: Vec<u16>,
+}
+
+impl ContentIndexMismatch {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+impl std::fmt::Display for ContentIndexMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |indexes: &[u16]| {
+            indexes
+                .iter()
+                .map(u16::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (self.missing.is_empty(), self.unexpected.is_empty()) {
+            (true, true) => write!(f, "matches the TMD"),
+            (false, true) => write!(f, "TMD contents {} not set", list(&self.missing)),
+            (true, false) => write!(f, "set for absent contents {}", list(&self.unexpected)),
+            (false, false) => write!(
+                f,
+                "TMD contents {} not set; set for absent contents {}",
+                list(&self.missing),
+                list(&self.unexpected)
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, BinRead, BinWrite)]
@@ -47,18 +131,23 @@ pub struct MetaData {
     pub icon_data: Vec<u8>,
 }
 
-/// Reads certificates from a reader until padding or end of section.
+/// Reads certificates from a reader until padding or end of section. A
+/// certificate whose signature and key type put its end past `cert_end`
+/// is an error rather than a read into the ticket.
 fn read_cert_chain<R: Read + Seek>(reader: &mut R, cert_end: u64) -> BinResult<Vec<Certificate>> {
     let mut cert_chain = Vec::new();
     while reader.stream_position()? < cert_end {
         let current_pos = reader.stream_position()?;
-        let mut sig_type_bytes = [0u8; 4];
-        reader.read_exact(&mut sig_type_bytes)?;
-        reader.seek(SeekFrom::Start(current_pos))?;
-
-        let sig_type_value = u32::from_be_bytes(sig_type_bytes);
-        if !matches!(sig_type_value, CERT_SIG_TYPE_MIN..=CERT_SIG_TYPE_MAX) {
+        let Some(size) = Certificate::peek_size(reader)? else {
             break;
+        };
+        if current_pos + size > cert_end {
+            return Err(binrw::Error::AssertFail {
+                pos: current_pos,
+                message: format!(
+                    "certificate of 0x{size:X} bytes overruns the certificate chain ending at 0x{cert_end:X}"
+                ),
+            });
         }
         cert_chain.push(Certificate::read_options(reader, Endian::Big, ())?);
     }
@@ -109,10 +198,10 @@ pub struct CiaFileWithoutContent {
 }
 
 impl CiaFile {
+    /// Rebuild the header bitmap from the TMD's content records.
     pub fn apply_content_indexes(&mut self) {
-        for record in &self.tmd.content_chunk_records {
-            self.header.set_content_index(record.content_index as usize);
-        }
+        self.header
+            .rebuild_content_indexes(&self.tmd.content_chunk_records);
     }
 }
 
@@ -210,6 +299,17 @@ impl BinRead for CiaFile {
         let tmd = TitleMetadata::read_options(reader, Endian::Big, ())?;
 
         let content_start = align_64(reader.stream_position()?);
+        let stream_end = reader.seek(SeekFrom::End(0))?;
+        let available = stream_end.saturating_sub(content_start);
+        if header.content_size > available {
+            return Err(binrw::Error::AssertFail {
+                pos: content_start,
+                message: format!(
+                    "CIA header declares 0x{:X} content bytes but only 0x{available:X} follow the TMD",
+                    header.content_size
+                ),
+            });
+        }
         reader.seek(SeekFrom::Start(content_start))?;
         let mut content_data = vec![0u8; header.content_size as usize];
         reader.read_exact(&mut content_data)?;
@@ -278,9 +378,7 @@ mod tests {
     use crate::nintendo::ctr::models::certificate::{KeyType, PublicKey};
     use crate::nintendo::ctr::models::signature::{SignatureData, SignatureType};
     use crate::nintendo::ctr::models::ticket::{ContentIndex, TicketData};
-    use crate::nintendo::ctr::models::title_metadata::{
-        ContentChunkRecord, ContentInfoRecord, ContentType, TitleMetadataHeader,
-    };
+    use crate::nintendo::ctr::test_fixtures::{make_tmd, minimal_cia};
     use binrw::{BinRead, BinWrite};
     use std::io::Cursor;
 
@@ -415,182 +513,9 @@ mod tests {
 
     #[test]
     fn test_simple_cia_file() {
-        // Cert chain size is 0xA00. Each RSA-2048 cert is 0x300 bytes, so the
-        // fixture uses 3 certs (0x900 total) plus 0x100 bytes of padding.
-
-        let cia_file = CiaFile {
-            header: CiaHeader {
-                header_size: CIA_HEADER_SIZE,
-                cia_type: 0,
-                version: 0,
-                cert_chain_size: 0x0A00,
-                ticket_size: 0x0350,
-                tmd_size: 0x0B34,
-                meta_size: 0,
-                content_size: 0x1000,
-                content_index: vec![0x00; 0x2000],
-            },
-            cert_chain: vec![
-                // CA Certificate
-                Certificate {
-                    signature_type: SignatureType::Rsa2048Sha256,
-                    signature: vec![0xAA; 0x100],
-                    padding: vec![0x00; 0x3C],
-                    issuer: {
-                        let mut issuer = b"Root".to_vec();
-                        issuer.resize(0x40, 0);
-                        issuer
-                    },
-                    key_type: KeyType::Rsa2048,
-                    name: {
-                        let mut name = b"CA00000003".to_vec();
-                        name.resize(0x40, 0);
-                        name
-                    },
-                    expiration_time: 0x5F5E0F00,
-                    public_key: PublicKey::Rsa2048 {
-                        modulus: vec![0xFF; 0x100],
-                        public_exponent: 65537,
-                        padding: vec![0x00; 0x34],
-                    },
-                },
-                // TMD Certificate
-                Certificate {
-                    signature_type: SignatureType::Rsa2048Sha256,
-                    signature: vec![0xBB; 0x100],
-                    padding: vec![0x00; 0x3C],
-                    issuer: {
-                        let mut issuer = b"Root-CA00000003".to_vec();
-                        issuer.resize(0x40, 0);
-                        issuer
-                    },
-                    key_type: KeyType::Rsa2048,
-                    name: {
-                        let mut name = b"CP0000000b".to_vec();
-                        name.resize(0x40, 0);
-                        name
-                    },
-                    expiration_time: 0x5F5E0F00,
-                    public_key: PublicKey::Rsa2048 {
-                        modulus: vec![0xEE; 0x100],
-                        public_exponent: 65537,
-                        padding: vec![0x00; 0x34],
-                    },
-                },
-                // Ticket Certificate
-                Certificate {
-                    signature_type: SignatureType::Rsa2048Sha256,
-                    signature: vec![0xCC; 0x100],
-                    padding: vec![0x00; 0x3C],
-                    issuer: {
-                        let mut issuer = b"Root-CA00000003".to_vec();
-                        issuer.resize(0x40, 0);
-                        issuer
-                    },
-                    key_type: KeyType::Rsa2048,
-                    name: {
-                        let mut name = b"XS0000000c".to_vec();
-                        name.resize(0x40, 0);
-                        name
-                    },
-                    expiration_time: 0x5F5E0F00,
-                    public_key: PublicKey::Rsa2048 {
-                        modulus: vec![0xDD; 0x100],
-                        public_exponent: 65537,
-                        padding: vec![0x00; 0x34],
-                    },
-                },
-            ],
-            ticket: Ticket {
-                signature_data: SignatureData {
-                    signature_type: SignatureType::Rsa2048Sha256,
-                    signature: vec![0xBB; 0x100],
-                    padding: vec![0x00; 0x3C],
-                },
-                ticket_data: TicketData {
-                    issuer: {
-                        let mut issuer = b"Root-CA00000003-XS0000000c".to_vec();
-                        issuer.resize(0x40, 0);
-                        issuer
-                    },
-                    ecc_public_key: vec![0x00; 0x3C],
-                    version: 1,
-                    ca_crl_version: 0,
-                    signer_crl_version: 0,
-                    title_key: vec![0xFF; 0x10],
-                    reserved1: 0,
-                    ticket_id: 0x0123456789ABCDEF,
-                    console_id: 0x12345678,
-                    title_id: 0xFEDCBA9876543210,
-                    reserved2: 0,
-                    ticket_title_version: 0x0100,
-                    reserved3: 0,
-                    license_type: 0,
-                    common_key_index: 1,
-                    reserved4: vec![0x00; 0x2A],
-                    eshop_account_id: 0,
-                    reserved5: 0,
-                    audit: 0,
-                    reserved6: vec![0x00; 0x42],
-                    limits: vec![0x00; 0x40],
-                    content_index: ContentIndex {
-                        header_word: 0,
-                        total_size: 22,
-                        data: vec![0x00; 20],
-                    },
-                },
-            },
-            tmd: TitleMetadata {
-                signature_data: SignatureData {
-                    signature_type: SignatureType::Rsa2048Sha256,
-                    signature: vec![0xCC; 0x100],
-                    padding: vec![0x00; 0x3C],
-                },
-                header: TitleMetadataHeader {
-                    signature_issuer: {
-                        let mut issuer = b"Root-CA00000003-CP0000000b".to_vec();
-                        issuer.resize(0x40, 0);
-                        issuer
-                    },
-                    version: 1,
-                    ca_crl_version: 0,
-                    signer_crl_version: 0,
-                    reserved1: 0,
-                    system_version: 0,
-                    title_id: 0x0004000000030000,
-                    title_type: 0x00040010,
-                    group_id: 0,
-                    save_data_size: 0x00080000,
-                    srl_private_save_data_size: 0,
-                    reserved2: 0,
-                    srl_flag: 0,
-                    reserved3: vec![0x00; 0x31],
-                    access_rights: 0,
-                    title_version: 0x0100,
-                    content_count: 1,
-                    boot_content: 0,
-                    padding: 0,
-                    content_info_records_hash: vec![0x00; 0x20],
-                },
-                content_info_records: vec![
-                    ContentInfoRecord {
-                        content_index_offset: 0,
-                        content_command_count: 1,
-                        hash: vec![0x00; 0x20],
-                    };
-                    64
-                ],
-                content_chunk_records: vec![ContentChunkRecord {
-                    content_id: 0,
-                    content_index: 0,
-                    content_type: ContentType(0x0001),
-                    content_size: 0x1000,
-                    hash: vec![0xAB; 0x20],
-                }],
-            },
-            content_data: vec![0x00; 0x1000],
-            meta_data: None,
-        };
+        let mut cia_file = minimal_cia();
+        cia_file.ticket.ticket_data.console_id = 0x12345678;
+        cia_file.ticket.ticket_data.title_id = 0xFEDCBA9876543210;
 
         let mut buf = Vec::new();
         cia_file
@@ -603,80 +528,63 @@ mod tests {
         assert_eq!(cia_file.header.header_size, read_cia.header.header_size);
         assert_eq!(cia_file.header.content_size, read_cia.header.content_size);
         assert_eq!(cia_file.cert_chain.len(), read_cia.cert_chain.len());
+        assert_eq!(
+            cia_file.ticket.ticket_data.console_id,
+            read_cia.ticket.ticket_data.console_id
+        );
         assert_eq!(
             cia_file.ticket.ticket_data.title_id,
             read_cia.ticket.ticket_data.title_id
         );
         assert_eq!(cia_file.tmd.header.title_id, read_cia.tmd.header.title_id);
-        assert_eq!(cia_file.content_data.len(), read_cia.content_data.len());
+        assert_eq!(cia_file.content_data, read_cia.content_data);
+    }
+
+    #[test]
+    fn inflated_content_size_errors_instead_of_allocating() {
+        let mut buf = Vec::new();
+        minimal_cia()
+            .write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
+            .unwrap();
+        // content_size follows the u32/u16/u16/u32/u32/u32/u32 header fields.
+        buf[0x18..0x20].copy_from_slice(&0x4000_0000_0000_0000u64.to_le_bytes());
+
+        let err = CiaFile::read_options(&mut Cursor::new(&buf), Endian::Little, ()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("declares 0x4000000000000000 content bytes"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn minimal_cia_sizes_match_serialized_sections() {
+        let cia = minimal_cia();
+        let mut buf = Vec::new();
+        cia.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
+            .unwrap();
+
+        let header = &cia.header;
+        let expected = align_64(header.header_size as u64)
+            + align_64(header.cert_chain_size as u64)
+            + align_64(header.ticket_size as u64)
+            + align_64(header.tmd_size as u64)
+            + header.content_size;
+        assert_eq!(buf.len() as u64, expected);
+        assert_eq!(header.content_size, cia.content_data.len() as u64);
     }
 
     /// A CIA with `meta_size > 0` must round-trip the `MetaData` block
     /// byte-for-byte through `write_le` then `read_le`.
     #[test]
     fn cia_file_round_trip_preserves_meta_section() {
-        use crate::nintendo::ctr::test_fixtures::{make_cert, make_meta, make_ticket, make_tmd};
-        use sha2::{Digest, Sha256};
+        use crate::nintendo::ctr::test_fixtures::{cia_with_content, make_meta};
 
         let title_id = 0x0004_000E_0012_5600u64;
         let content: Vec<u8> = (0..0x800usize).map(|i| (i as u8).wrapping_mul(3)).collect();
-        let content_hash = {
-            let mut h = Sha256::new();
-            h.update(&content);
-            let d = h.finalize();
-            let mut arr = [0u8; 32];
-            arr.copy_from_slice(&d);
-            arr
-        };
-
-        let meta = make_meta(0x7E);
-        let meta_size: u32 = {
-            let mut buf = Vec::new();
-            meta.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
-                .unwrap();
-            buf.len() as u32
-        };
-
-        let cert_chain = vec![
-            make_cert(b"CA00000003", 0xAA),
-            make_cert(b"CP0000000b", 0xBB),
-            make_cert(b"XS0000000c", 0xCC),
-        ];
-        let ticket = make_ticket(title_id);
-        let tmd = make_tmd(title_id, vec![(0, 0, content.clone(), content_hash)]);
-
-        let ticket_size = {
-            let mut buf = Vec::new();
-            ticket
-                .write_options(&mut Cursor::new(&mut buf), Endian::Big, ())
-                .unwrap();
-            buf.len() as u32
-        };
-        let tmd_size = {
-            let mut buf = Vec::new();
-            tmd.write_options(&mut Cursor::new(&mut buf), Endian::Big, ())
-                .unwrap();
-            buf.len() as u32
-        };
-
-        let original = CiaFile {
-            header: CiaHeader {
-                header_size: CIA_HEADER_SIZE,
-                cia_type: 0,
-                version: 0,
-                cert_chain_size: 0x0A00,
-                ticket_size,
-                tmd_size,
-                meta_size,
-                content_size: content.len() as u64,
-                content_index: vec![0x00; 0x2000],
-            },
-            cert_chain,
-            ticket,
-            tmd,
-            content_data: content,
-            meta_data: Some(meta),
-        };
+        let original = cia_with_content(title_id, content, Some(make_meta(0x7E)));
+        let meta_size = original.header.meta_size;
+        assert!(meta_size > 0, "fixture declares a meta block");
 
         let mut buf = Vec::new();
         original
@@ -747,6 +655,53 @@ mod tests {
         assert_eq!(h.content_index[1], 0x80);
     }
 
+    #[test]
+    fn content_index_check_reports_both_directions() {
+        let mut h = blank_header();
+        h.set_content_index(0);
+        h.set_content_index(2);
+        h.set_content_index(9);
+        assert_eq!(h.content_indexes(), vec![0, 2, 9]);
+
+        assert!(h.check_content_indexes([9, 0, 2]).is_empty());
+        let mismatch = h.check_content_indexes([0, 1, 2]);
+        assert_eq!(mismatch.missing, vec![1]);
+        assert_eq!(mismatch.unexpected, vec![9]);
+        assert_eq!(
+            mismatch.to_string(),
+            "TMD contents 1 not set; set for absent contents 9"
+        );
+    }
+
+    #[test]
+    fn filtered_cia_bitmap_lists_only_the_kept_contents() {
+        let mut cia = minimal_cia();
+        cia.tmd = make_tmd(
+            cia.tmd.header.title_id,
+            (0..3u16)
+                .map(|i| (i as u32, i, vec![i as u8; 0x40], [0u8; 32]))
+                .collect(),
+        );
+        cia.apply_content_indexes();
+        assert_eq!(cia.header.content_indexes(), vec![0, 1, 2]);
+
+        cia.tmd
+            .content_chunk_records
+            .retain(|record| record.content_index != 1);
+        cia.apply_content_indexes();
+        assert_eq!(cia.header.content_indexes(), vec![0, 2]);
+        assert!(
+            cia.header
+                .check_content_indexes(
+                    cia.tmd
+                        .content_chunk_records
+                        .iter()
+                        .map(|r| r.content_index)
+                )
+                .is_empty()
+        );
+    }
+
     #[test]
     fn set_content_index_is_idempotent_per_bit() {
         let mut h = blank_header();
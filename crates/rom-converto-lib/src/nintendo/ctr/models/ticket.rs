@@ -74,10 +74,11 @@ pub struct ContentIndex {
     pub header_word: u32,
 
     /// Total size of this block, including the first two bytes
+    #[br(assert(total_size >= 8, "content index total_size {} is smaller than its 8-byte header", total_size))]
     pub total_size: u32,
 
     /// The data of the content index, which is usually at least 20 bytes long.
-    #[br(count = total_size.saturating_sub(8) as usize)]
+    #[br(count = total_size - 8)]
     pub data: Vec<u8>,
 }
 
@@ -133,6 +134,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn content_index_smaller_than_its_header_is_a_parse_error() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&0x0001_0014u32.to_be_bytes());
+        buf.extend_from_slice(&4u32.to_be_bytes());
+        buf.extend_from_slice(&[0u8; 0x20]);
+
+        let err = ContentIndex::read(&mut Cursor::new(&buf)).unwrap_err();
+        assert!(
+            err.to_string().contains("smaller than its 8-byte header"),
+            "{err}"
+        );
+    }
+
     #[test]
     fn test_full_ticket() {
         let ticket = Ticket {
@@ -0,0 +1,292 @@
+//! Release region of a 3DS title, read from the product code in its first
+//! NCCH header (`CTR-P-ABCE`). The code's last letter names the region the
+//! title was released for; the SMDH region lockout sits in the ExeFS behind
+//! the NCCH crypto, and title IDs carry no region at all, so the product
+//! code is the one source a CDN download exposes with just its ticket.
+
+use crate::nintendo::ctr::decrypt::util::{
+    cbc_decrypt, common_key, derive_title_key_from_ticket, gen_iv,
+};
+use crate::nintendo::ctr::models::ncch_header::NcchHeader;
+use crate::nintendo::ctr::models::title_metadata::TitleMetadata;
+use crate::nintendo::ctr::title_key::{CommonKeyIndex, generate_title_key_with_index};
+use crate::nintendo::ctr::util::fs::{find_title_file, find_tmd_file};
+use anyhow::{Context, Result, anyhow, bail};
+use binrw::BinRead;
+use std::fmt;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtrRegion {
+    /// Region free (`A`), playable on every console.
+    Free,
+    Jpn,
+    Usa,
+    Eur,
+    Aus,
+    Chn,
+    Kor,
+    Twn,
+}
+
+impl CtrRegion {
+    pub const ALL: [CtrRegion; 8] = [
+        Self::Free,
+        Self::Jpn,
+        Self::Usa,
+        Self::Eur,
+        Self::Aus,
+        Self::Chn,
+        Self::Kor,
+        Self::Twn,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Free => "free",
+            Self::Jpn => "jpn",
+            Self::Usa => "usa",
+            Self::Eur => "eur",
+            Self::Aus => "aus",
+            Self::Chn => "chn",
+            Self::Kor => "kor",
+            Self::Twn => "twn",
+        }
+    }
+
+    /// The name the SMDH region lockout uses for this region, so a
+    /// `{region}` template token reads the same for CDN and CIA input.
+    pub fn lockout_name(self) -> &'static str {
+        match self {
+            Self::Free => "RegionFree",
+            Self::Jpn => "Japan",
+            Self::Usa => "NorthAmerica",
+            Self::Eur => "Europe",
+            Self::Aus => "Australia",
+            Self::Chn => "China",
+            Self::Kor => "Korea",
+            Self::Twn => "Taiwan",
+        }
+    }
+
+    /// Region named by the last letter of a product code. The European
+    /// language variants (`D`, `F`, `I`, `S`, ...) all count as `Eur`.
+    pub fn from_product_code(code: &str) -> Option<Self> {
+        let code = code.trim_end_matches(['\0', ' ']);
+        if !code.starts_with("CTR-") && !code.starts_with("KTR-") {
+            return None;
+        }
+        match code.chars().last()? {
+            'A' => Some(Self::Free),
+            'J' => Some(Self::Jpn),
+            'E' => Some(Self::Usa),
+            'P' | 'D' | 'F' | 'H' | 'I' | 'S' | 'V' | 'X' | 'Y' | 'Z' => Some(Self::Eur),
+            'U' => Some(Self::Aus),
+            'C' => Some(Self::Chn),
+            'K' => Some(Self::Kor),
+            'W' => Some(Self::Twn),
+            _ => None,
+        }
+    }
+
+    /// Whether a title released for `title` passes this region filter.
+    /// Region-free titles pass every filter.
+    pub fn accepts(self, title: CtrRegion) -> bool {
+        title == self || title == Self::Free
+    }
+}
+
+impl fmt::Display for CtrRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for CtrRegion {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|region| region.name().eq_ignore_ascii_case(text))
+            .ok_or_else(|| {
+                format!(
+                    "unknown region {text:?}; expected free, jpn, usa, eur, aus, chn, kor, or twn"
+                )
+            })
+    }
+}
+
+/// Where a CDN-to-CIA conversion gets a title's ticket, so
+/// [`cdn_title_region`] decrypts with the title key the build will use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CdnTicketSource {
+    /// Ticket to read instead of searching the CDN directory.
+    pub ticket: Option<PathBuf>,
+    /// When the directory has no ticket, use the title key a generated
+    /// ticket would carry, as `ensure_ticket_exists` does.
+    pub generate: bool,
+    /// Encrypted title key for a generated ticket; derived from the title
+    /// ID and the default password otherwise.
+    pub title_key: Option<[u8; 16]>,
+    /// Common key a generated ticket's title key is encrypted with.
+    pub common_key_index: CommonKeyIndex,
+}
+
+/// Region of the title in a CDN directory. Decrypts the first block of
+/// content 0 with the title key from `tickets` and reads the NCCH product
+/// code; fails when there is no title key or the code names no region.
+pub async fn cdn_title_region(
+    cdn_dir: &Path,
+    tmd_version: Option<u32>,
+    tickets: &CdnTicketSource,
+) -> Result<CtrRegion> {
+    let tmd_path = find_tmd_file(cdn_dir, tmd_version).await?;
+    let tmd = TitleMetadata::read(&mut Cursor::new(fs::read(&tmd_path).await?))?;
+    let record = tmd
+        .content_chunk_records
+        .iter()
+        .find(|r| r.content_index == 0)
+        .or_else(|| tmd.content_chunk_records.first())
+        .context("TMD lists no contents")?;
+
+    let content_path = cdn_dir.join(format!("{:08x}", record.content_id));
+    let mut block = [0u8; 0x200];
+    fs::File::open(&content_path)
+        .await
+        .with_context(|| format!("cannot open content: {}", content_path.display()))?
+        .read_exact(&mut block)
+        .await?;
+    if record.content_type.is_encrypted() {
+        let ticket_path = match &tickets.ticket {
+            Some(path) => Ok(path.clone()),
+            None => find_title_file(cdn_dir).await,
+        };
+        let title_key = match ticket_path {
+            Ok(path) => {
+                let ticket = fs::read(&path)
+                    .await
+                    .with_context(|| format!("cannot read ticket: {}", path.display()))?;
+                derive_title_key_from_ticket(&mut Cursor::new(ticket), 0)?
+            }
+            Err(_) if tickets.generate => generated_title_key(tmd.header.title_id, tickets)?,
+            Err(err) => return Err(err.into()),
+        };
+        cbc_decrypt(&title_key, &gen_iv(record.content_index), &mut block)?;
+    }
+
+    let header = NcchHeader::read(&mut Cursor::new(&block)).context("parse NCCH header")?;
+    let end = header
+        .productcode
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(header.productcode.len());
+    let code = String::from_utf8_lossy(&header.productcode[..end]);
+    match CtrRegion::from_product_code(&code) {
+        Some(region) => Ok(region),
+        None => bail!("product code {code:?} names no region"),
+    }
+}
+
+/// The decrypted title key of the ticket `generate_ticket_from_cdn` would
+/// write for `title_id`: its encrypted key run back through the common key
+/// the ticket names, as [`derive_title_key_from_ticket`] does.
+fn generated_title_key(title_id: u64, tickets: &CdnTicketSource) -> Result<[u8; 16]> {
+    let key_index = tickets.common_key_index.resolve(title_id);
+    let mut title_key = match tickets.title_key {
+        Some(key) => key,
+        None => {
+            let hex_key =
+                generate_title_key_with_index(&format!("{title_id:016X}"), None, key_index)?;
+            hex::decode(&hex_key)?
+                .try_into()
+                .map_err(|_| anyhow!("generated title key {hex_key} is not 16 bytes"))?
+        }
+    };
+    let mut iv = [0u8; 16];
+    iv[..8].copy_from_slice(&title_id.to_be_bytes());
+    cbc_decrypt(common_key(key_index)?, &iv, &mut title_key)?;
+    Ok(title_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn product_code_letter_picks_the_region() {
+        for (code, region) in [
+            ("CTR-P-AXXE", CtrRegion::Usa),
+            ("CTR-P-AXXJ", CtrRegion::Jpn),
+            ("CTR-P-AXXP", CtrRegion::Eur),
+            ("CTR-P-AXXD", CtrRegion::Eur),
+            ("KTR-P-AXXK", CtrRegion::Kor),
+            ("CTR-N-AXXA", CtrRegion::Free),
+        ] {
+            assert_eq!(CtrRegion::from_product_code(code), Some(region), "{code}");
+        }
+        assert_eq!(CtrRegion::from_product_code("CTR-P-AXXQ"), None);
+        assert_eq!(CtrRegion::from_product_code(""), None);
+    }
+
+    #[test]
+    fn region_free_titles_pass_every_filter() {
+        assert!(CtrRegion::Usa.accepts(CtrRegion::Usa));
+        assert!(CtrRegion::Usa.accepts(CtrRegion::Free));
+        assert!(!CtrRegion::Usa.accepts(CtrRegion::Jpn));
+        assert!(!CtrRegion::Free.accepts(CtrRegion::Usa));
+        assert_eq!("JPN".parse::<CtrRegion>(), Ok(CtrRegion::Jpn));
+        assert!("nowhere".parse::<CtrRegion>().is_err());
+    }
+
+    #[tokio::test]
+    async fn ticketless_title_region_uses_the_generated_title_key() {
+        use crate::nintendo::ctr::models::title_metadata::ContentType;
+        use crate::nintendo::ctr::test_fixtures::{
+            append_be, make_cert, make_ncch_header_bytes, make_tmd,
+        };
+        use crate::nintendo::ctr::title_key::generate_key;
+        use aes::cipher::{BlockModeEncrypt, KeyIvInit};
+
+        let title_id = 0x0004000000155400u64;
+        let mut content = make_ncch_header_bytes(title_id);
+        content[0x150..0x15A].copy_from_slice(b"CTR-P-AXXE");
+        let plain_key: [u8; 16] =
+            hex::decode(generate_key(&format!("{title_id:016X}"), "mypass").unwrap())
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let len = content.len();
+        cbc::Encryptor::<aes::Aes128>::new_from_slices(&plain_key, &gen_iv(0))
+            .unwrap()
+            .encrypt_padded::<block_padding::NoPadding>(&mut content, len)
+            .unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("00000000"), &content).unwrap();
+        let mut tmd = make_tmd(title_id, vec![(0, 0, content, [0u8; 32])]);
+        tmd.content_chunk_records[0].content_type = ContentType(0x0001);
+        let mut tmd_buf = Vec::new();
+        append_be(&mut tmd_buf, &tmd);
+        append_be(&mut tmd_buf, &make_cert(b"CP0000000b", 0xBB));
+        std::fs::write(dir.join("tmd"), &tmd_buf).unwrap();
+
+        assert!(
+            cdn_title_region(dir, None, &CdnTicketSource::default())
+                .await
+                .is_err()
+        );
+        let generated = CdnTicketSource {
+            generate: true,
+            ..CdnTicketSource::default()
+        };
+        assert_eq!(
+            cdn_title_region(dir, None, &generated).await.unwrap(),
+            CtrRegion::Usa
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! File listing for the RomFS of a decrypted 3DS NCCH.
+//!
+//! The NCCH RomFS region is an IVFC hash tree whose level 3 holds the
+//! actual filesystem: a 0x28-byte header, directory and file metadata
+//! tables, and the file data. Only the metadata tables are read; file
+//! contents are never touched, so listing a multi-gigabyte title is cheap.
+//!
+//! Retail titles store level 3 after the IVFC header and master hash,
+//! aligned to the level-3 block size. Some dumping tools instead strip the
+//! hash tree and leave a bare level-3 image at the start of the region, so
+//! both placements are accepted.
+//!
+//! Layout reference: 3dbrew.org/wiki/RomFS.
+
+use crate::nintendo::ctr::constants::{
+    CTR_MEDIA_UNIT_SIZE, NCCH_MAGIC, NCCH_MAGIC_OFFSET, NCSD_PARTITION_TABLE_OFFSET,
+};
+use crate::nintendo::ctr::format::{FORMAT_PROBE_SIZE, sniff_format};
+use crate::nintendo::ctr::info::CtrFormat;
+use crate::nintendo::ctr::models::cia::CiaFileWithoutContent;
+use crate::nintendo::ctr::models::ncch_header::NcchHeader;
+use crate::nintendo::ctr::util::align_64;
+use anyhow::{Context, Result, anyhow, bail};
+use binrw::{BinRead, Endian};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const IVFC_MAGIC: &[u8; 4] = b"IVFC";
+const IVFC_ROMFS_VERSION: u32 = 0x10000;
+/// IVFC header size rounded up to the 0x10 boundary the master hash
+/// starts on.
+const IVFC_HEADER_SIZE: u64 = 0x60;
+const IVFC_MASTER_HASH_SIZE_OFFSET: usize = 0x08;
+const IVFC_LEVEL3_BLOCK_LOG2_OFFSET: usize = 0x4C;
+const LEVEL3_HEADER_SIZE: u32 = 0x28;
+const DIR_ENTRY_SIZE: usize = 0x18;
+const FILE_ENTRY_SIZE: usize = 0x20;
+const INVALID_OFFSET: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RomfsListing {
+    /// Every file in the RomFS, in directory-walk order.
+    pub files: Vec<RomfsFileEntry>,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RomfsFileEntry {
+    /// Slash-separated path from the RomFS root, with a leading `/`.
+    pub path: String,
+    pub size: u64,
+}
+
+/// Level-3 header: every offset is relative to the start of level 3.
+#[derive(Debug, Clone, Copy)]
+struct Level3Header {
+    dir_meta_offset: u32,
+    dir_meta_size: u32,
+    file_meta_offset: u32,
+    file_meta_size: u32,
+}
+
+impl Level3Header {
+    fn parse(buf: &[u8; LEVEL3_HEADER_SIZE as usize]) -> Option<Self> {
+        let word = |i: usize| u32::from_le_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap());
+        if word(0) != LEVEL3_HEADER_SIZE {
+            return None;
+        }
+        Some(Self {
+            dir_meta_offset: word(3),
+            dir_meta_size: word(4),
+            file_meta_offset: word(7),
+            file_meta_size: word(8),
+        })
+    }
+}
+
+/// List the RomFS files of a decrypted NCCH, the main partition of an
+/// NCSD, or the first content of a CIA whose title key layer is off.
+pub fn list_romfs(path: &Path) -> Result<RomfsListing> {
+    let file = File::open(path).with_context(|| format!("ctr romfs: open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut probe = [0u8; FORMAT_PROBE_SIZE];
+    let n = reader.read(&mut probe)?;
+
+    let ncch_offset = match sniff_format(&probe[..n]) {
+        CtrFormat::Ncch => 0,
+        CtrFormat::Ncsd => {
+            let entry = &probe[NCSD_PARTITION_TABLE_OFFSET..NCSD_PARTITION_TABLE_OFFSET + 4];
+            u32::from_le_bytes(entry.try_into()?) as u64 * CTR_MEDIA_UNIT_SIZE as u64
+        }
+        CtrFormat::Cia => first_cia_content_offset(&mut reader)?,
+        CtrFormat::Unknown => {
+            return Err(anyhow!(
+                "ctr romfs: unrecognized format at {}",
+                path.display()
+            ));
+        }
+    };
+
+    reader.seek(SeekFrom::Start(ncch_offset))?;
+    let mut block = [0u8; 0x200];
+    reader.read_exact(&mut block)?;
+    if &block[NCCH_MAGIC_OFFSET..NCCH_MAGIC_OFFSET + 4] != NCCH_MAGIC.as_bytes() {
+        bail!("ctr romfs: missing NCCH magic");
+    }
+    let hdr = NcchHeader::read(&mut Cursor::new(&block)).context("ctr romfs: parse NCCH header")?;
+    if hdr.is_encrypted() {
+        bail!("ctr romfs: NCCH is encrypted; run `ctr decrypt` first");
+    }
+    if hdr.romfssize == 0 {
+        bail!("ctr romfs: NCCH has no RomFS");
+    }
+
+    let unit = CTR_MEDIA_UNIT_SIZE as u64;
+    let romfs_offset = ncch_offset + hdr.romfsoffset as u64 * unit;
+    let romfs_size = hdr.romfssize as u64 * unit;
+    read_listing(&mut reader, romfs_offset, romfs_size)
+}
+
+fn first_cia_content_offset<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    reader.seek(SeekFrom::Start(0))?;
+    let cia = CiaFileWithoutContent::read_options(reader, Endian::Little, ())
+        .context("ctr romfs: parse CIA")?;
+    let header = &cia.header;
+    let cert_start = align_64(header.header_size as u64);
+    let ticket_start = align_64(cert_start + header.cert_chain_size as u64);
+    let tmd_start = align_64(ticket_start + header.ticket_size as u64);
+    let content_start = align_64(tmd_start + header.tmd_size as u64);
+    let record = cia
+        .tmd
+        .content_chunk_records
+        .first()
+        .ok_or_else(|| anyhow!("ctr romfs: CIA has no contents"))?;
+    if record.content_type.is_encrypted() {
+        bail!("ctr romfs: CIA content is title-key encrypted; run `ctr decrypt` first");
+    }
+    Ok(content_start)
+}
+
+/// Locate level 3 inside the RomFS region at `romfs_offset` and walk its
+/// directory tree.
+fn read_listing<R: Read + Seek>(
+    reader: &mut R,
+    romfs_offset: u64,
+    romfs_size: u64,
+) -> Result<RomfsListing> {
+    reader.seek(SeekFrom::Start(romfs_offset))?;
+    let mut ivfc = [0u8; IVFC_HEADER_SIZE as usize];
+    reader.read_exact(&mut ivfc)?;
+
+    let level3_offset = if &ivfc[0..4] == IVFC_MAGIC {
+        let version = u32::from_le_bytes(ivfc[4..8].try_into()?);
+        if version != IVFC_ROMFS_VERSION {
+            bail!("ctr romfs: unsupported IVFC version 0x{version:X}");
+        }
+        let master_hash_size = u32::from_le_bytes(
+            ivfc[IVFC_MASTER_HASH_SIZE_OFFSET..IVFC_MASTER_HASH_SIZE_OFFSET + 4].try_into()?,
+        ) as u64;
+        let block_log2 = u32::from_le_bytes(
+            ivfc[IVFC_LEVEL3_BLOCK_LOG2_OFFSET..IVFC_LEVEL3_BLOCK_LOG2_OFFSET + 4].try_into()?,
+        );
+        if block_log2 >= 32 {
+            bail!("ctr romfs: invalid level 3 block size 2^{block_log2}");
+        }
+        (IVFC_HEADER_SIZE + master_hash_size).next_multiple_of(1u64 << block_log2)
+    } else {
+        0
+    };
+    if level3_offset + LEVEL3_HEADER_SIZE as u64 > romfs_size {
+        bail!("ctr romfs: level 3 lies outside the RomFS region");
+    }
+
+    let level3_start = romfs_offset + level3_offset;
+    reader.seek(SeekFrom::Start(level3_start))?;
+    let mut raw = [0u8; LEVEL3_HEADER_SIZE as usize];
+    reader.read_exact(&mut raw)?;
+    let header = Level3Header::parse(&raw).ok_or_else(|| {
+        anyhow!("ctr romfs: no level 3 header at RomFS offset 0x{level3_offset:X}")
+    })?;
+
+    let level3_size = romfs_size - level3_offset;
+    let dirs = read_table(
+        reader,
+        level3_start,
+        level3_size,
+        header.dir_meta_offset,
+        header.dir_meta_size,
+    )?;
+    let files = read_table(
+        reader,
+        level3_start,
+        level3_size,
+        header.file_meta_offset,
+        header.file_meta_size,
+    )?;
+    walk(&dirs, &files)
+}
+
+fn read_table<R: Read + Seek>(
+    reader: &mut R,
+    level3_start: u64,
+    level3_size: u64,
+    offset: u32,
+    size: u32,
+) -> Result<Vec<u8>> {
+    if offset as u64 + size as u64 > level3_size {
+        bail!("ctr romfs: metadata table lies outside level 3");
+    }
+    reader.seek(SeekFrom::Start(level3_start + offset as u64))?;
+    let mut table = vec![0u8; size as usize];
+    reader.read_exact(&mut table)?;
+    Ok(table)
+}
+
+struct DirEntry {
+    next_sibling: u32,
+    first_child: u32,
+    first_file: u32,
+    name: String,
+}
+
+struct FileEntry {
+    next_sibling: u32,
+    size: u64,
+    name: String,
+}
+
+fn u32_at(table: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(table[at..at + 4].try_into().unwrap())
+}
+
+/// Entry names are UTF-16LE, `len` bytes long, directly after the fixed
+/// fields.
+fn entry_name(table: &[u8], start: usize, len: u32) -> Result<String> {
+    let end = start
+        .checked_add(len as usize)
+        .filter(|&end| end <= table.len())
+        .ok_or_else(|| anyhow!("ctr romfs: entry name runs past its table"))?;
+    let units: Vec<u16> = table[start..end]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Ok(String::from_utf16_lossy(&units))
+}
+
+fn parse_dir(table: &[u8], offset: u32) -> Result<DirEntry> {
+    let at = offset as usize;
+    if at + DIR_ENTRY_SIZE > table.len() {
+        bail!("ctr romfs: directory entry 0x{offset:X} out of range");
+    }
+    Ok(DirEntry {
+        next_sibling: u32_at(table, at + 0x04),
+        first_child: u32_at(table, at + 0x08),
+        first_file: u32_at(table, at + 0x0C),
+        name: entry_name(table, at + DIR_ENTRY_SIZE, u32_at(table, at + 0x14))?,
+    })
+}
+
+fn parse_file(table: &[u8], offset: u32) -> Result<FileEntry> {
+    let at = offset as usize;
+    if at + FILE_ENTRY_SIZE > table.len() {
+        bail!("ctr romfs: file entry 0x{offset:X} out of range");
+    }
+    Ok(FileEntry {
+        next_sibling: u32_at(table, at + 0x04),
+        size: u64::from_le_bytes(table[at + 0x10..at + 0x18].try_into().unwrap()),
+        name: entry_name(table, at + FILE_ENTRY_SIZE, u32_at(table, at + 0x1C))?,
+    })
+}
+
+/// Depth-first walk from the root directory (offset 0). Offsets already
+/// visited are rejected so a corrupt sibling chain cannot loop forever.
+fn walk(dirs: &[u8], files: &[u8]) -> Result<RomfsListing> {
+    let mut out = Vec::new();
+    let mut seen_dirs = HashSet::new();
+    let mut seen_files = HashSet::new();
+    let mut stack = vec![(0u32, String::new())];
+    while let Some((dir_offset, prefix)) = stack.pop() {
+        if !seen_dirs.insert(dir_offset) {
+            bail!("ctr romfs: directory 0x{dir_offset:X} is linked twice");
+        }
+        let dir = parse_dir(dirs, dir_offset)?;
+
+        let mut file_offset = dir.first_file;
+        while file_offset != INVALID_OFFSET {
+            if !seen_files.insert(file_offset) {
+                bail!("ctr romfs: file 0x{file_offset:X} is linked twice");
+            }
+            let file = parse_file(files, file_offset)?;
+            out.push(RomfsFileEntry {
+                path: format!("{prefix}/{}", file.name),
+                size: file.size,
+            });
+            file_offset = file.next_sibling;
+        }
+
+        // Push children in reverse so they pop in table order.
+        let mut children = Vec::new();
+        let mut child_offset = dir.first_child;
+        while child_offset != INVALID_OFFSET {
+            let child = parse_dir(dirs, child_offset)?;
+            children.push((child_offset, format!("{prefix}/{}", child.name)));
+            child_offset = child.next_sibling;
+            if children.len() > dirs.len() / DIR_ENTRY_SIZE {
+                bail!("ctr romfs: directory sibling chain loops");
+            }
+        }
+        stack.extend(children.into_iter().rev());
+    }
+    let total_size = out.iter().map(|f| f.size).sum();
+    Ok(RomfsListing {
+        files: out,
+        total_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nintendo::ctr::constants::NCCH_FLAGS_OFFSET;
+    use crate::nintendo::ctr::test_fixtures::make_ncch_header_bytes;
+
+    fn utf16(name: &str) -> Vec<u8> {
+        let mut bytes: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+        bytes
+    }
+
+    fn push_u32s(buf: &mut Vec<u8>, words: &[u32]) {
+        for w in words {
+            buf.extend_from_slice(&w.to_le_bytes());
+        }
+    }
+
+    /// Level 3 with `/a.txt` (5 bytes), `/sub/b.bin` (3 bytes) and an
+    /// empty `/sub/empty` directory.
+    fn build_level3() -> Vec<u8> {
+        // Directory table: root @0, sub @0x18, empty @0x18+0x18+len("sub").
+        let sub_name = utf16("sub");
+        let empty_name = utf16("empty");
+        let sub_off = DIR_ENTRY_SIZE as u32;
+        let empty_off = sub_off + DIR_ENTRY_SIZE as u32 + sub_name.len() as u32;
+        let a_name = utf16("a.txt");
+        let b_off = FILE_ENTRY_SIZE as u32 + a_name.len() as u32;
+        let b_name = utf16("b.bin");
+
+        let mut dirs = Vec::new();
+        push_u32s(
+            &mut dirs,
+            &[0, INVALID_OFFSET, sub_off, 0, INVALID_OFFSET, 0],
+        );
+        push_u32s(
+            &mut dirs,
+            &[0, INVALID_OFFSET, empty_off, b_off, INVALID_OFFSET, 6],
+        );
+        dirs.extend_from_slice(&sub_name);
+        push_u32s(
+            &mut dirs,
+            &[
+                sub_off,
+                INVALID_OFFSET,
+                INVALID_OFFSET,
+                INVALID_OFFSET,
+                INVALID_OFFSET,
+                10,
+            ],
+        );
+        dirs.extend_from_slice(&empty_name);
+
+        let mut files = Vec::new();
+        push_u32s(
+            &mut files,
+            &[0, INVALID_OFFSET, 0, 0, 5, 0, INVALID_OFFSET, 10],
+        );
+        files.extend_from_slice(&a_name);
+        push_u32s(
+            &mut files,
+            &[sub_off, INVALID_OFFSET, 8, 0, 3, 0, INVALID_OFFSET, 10],
+        );
+        files.extend_from_slice(&b_name);
+
+        let dir_meta = LEVEL3_HEADER_SIZE + 4;
+        let file_meta = dir_meta + dirs.len() as u32;
+        let data = file_meta + files.len() as u32;
+        let mut level3 = Vec::new();
+        push_u32s(
+            &mut level3,
+            &[
+                LEVEL3_HEADER_SIZE,
+                LEVEL3_HEADER_SIZE,
+                4,
+                dir_meta,
+                dirs.len() as u32,
+                data,
+                0,
+                file_meta,
+                files.len() as u32,
+                data,
+            ],
+        );
+        push_u32s(&mut level3, &[0]);
+        level3.extend_from_slice(&dirs);
+        level3.extend_from_slice(&files);
+        level3.extend_from_slice(b"hello\0\0\0abc");
+        level3
+    }
+
+    fn ivfc_wrapped(level3: &[u8], master_hash_size: u32, block_log2: u32) -> Vec<u8> {
+        let mut romfs = vec![0u8; IVFC_HEADER_SIZE as usize];
+        romfs[0..4].copy_from_slice(IVFC_MAGIC);
+        romfs[4..8].copy_from_slice(&IVFC_ROMFS_VERSION.to_le_bytes());
+        romfs[8..12].copy_from_slice(&master_hash_size.to_le_bytes());
+        romfs[IVFC_LEVEL3_BLOCK_LOG2_OFFSET..IVFC_LEVEL3_BLOCK_LOG2_OFFSET + 4]
+            .copy_from_slice(&block_log2.to_le_bytes());
+        let start = (IVFC_HEADER_SIZE as usize + master_hash_size as usize)
+            .next_multiple_of(1 << block_log2);
+        romfs.resize(start, 0);
+        romfs.extend_from_slice(level3);
+        romfs
+    }
+
+    fn ncch_with_romfs(romfs: &[u8]) -> Vec<u8> {
+        let unit = CTR_MEDIA_UNIT_SIZE as usize;
+        let mut ncch = make_ncch_header_bytes(0x0004000000155400);
+        ncch[0x1B0..0x1B4].copy_from_slice(&1u32.to_le_bytes());
+        let units = romfs.len().div_ceil(unit) as u32;
+        ncch[0x1B4..0x1B8].copy_from_slice(&units.to_le_bytes());
+        ncch.resize(unit, 0);
+        ncch.extend_from_slice(romfs);
+        ncch.resize(unit * (1 + units as usize), 0);
+        ncch
+    }
+
+    fn expected() -> Vec<RomfsFileEntry> {
+        vec![
+            RomfsFileEntry {
+                path: "/a.txt".into(),
+                size: 5,
+            },
+            RomfsFileEntry {
+                path: "/sub/b.bin".into(),
+                size: 3,
+            },
+        ]
+    }
+
+    #[test]
+    fn lists_files_behind_ivfc_at_either_alignment() {
+        let level3 = build_level3();
+        for (master_hash, log2) in [(0x20, 12), (0x40, 4)] {
+            let romfs = ivfc_wrapped(&level3, master_hash, log2);
+            let len = romfs.len() as u64;
+            let listing = read_listing(&mut Cursor::new(romfs), 0, len).unwrap();
+            assert_eq!(listing.files, expected());
+            assert_eq!(listing.total_size, 8);
+        }
+    }
+
+    #[test]
+    fn lists_a_bare_level3_image() {
+        let level3 = build_level3();
+        let len = level3.len() as u64;
+        let listing = read_listing(&mut Cursor::new(level3), 0, len.max(IVFC_HEADER_SIZE)).unwrap();
+        assert_eq!(listing.files, expected());
+    }
+
+    #[test]
+    fn list_romfs_reads_a_decrypted_cxi() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cxi");
+        std::fs::write(
+            &path,
+            ncch_with_romfs(&ivfc_wrapped(&build_level3(), 0x20, 12)),
+        )
+        .unwrap();
+        assert_eq!(list_romfs(&path).unwrap().files, expected());
+    }
+
+    #[test]
+    fn rejects_encrypted_and_romfs_less_ncch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("game.cxi");
+        let mut ncch = ncch_with_romfs(&ivfc_wrapped(&build_level3(), 0x20, 12));
+        ncch[NCCH_FLAGS_OFFSET + 7] = 0;
+        std::fs::write(&path, &ncch).unwrap();
+        assert!(
+            list_romfs(&path)
+                .unwrap_err()
+                .to_string()
+                .contains("decrypt")
+        );
+
+        std::fs::write(&path, make_ncch_header_bytes(0)).unwrap();
+        assert!(
+            list_romfs(&path)
+                .unwrap_err()
+                .to_string()
+                .contains("no RomFS")
+        );
+    }
+}
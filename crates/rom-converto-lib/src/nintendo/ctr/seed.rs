@@ -1,19 +1,119 @@
-//! Offline NCCH seed-crypto resolution.
+//! NCCH seed databases and offline seed-crypto resolution.
 //!
-//! The decrypt path (`crate::nintendo::ctr::decrypt`) can fetch a title's
-//! seed from Nintendo's CDN. The `info` path stays offline: it only resolves
-//! seeds from a local `seeddb.bin` in the working directory and reports
+//! Seeds come from a seed database, by default `seeddb.bin` in the working
+//! directory. [`load_seed_database`] swaps in another file or a URL,
+//! optionally gzip-compressed, before any seed is looked up. The decrypt
+//! path (`crate::nintendo::ctr::decrypt`) falls back to Nintendo's CDN for
+//! titles the database lacks. The `info` path stays offline and reports
 //! whether the seed verifies against the NCCH `seedcheck`.
 
 use crate::nintendo::ctr::models::ncch_header::NcchHeader;
 use crate::nintendo::ctr::models::seeddb::SeedDatabase;
+use anyhow::{Context, Result, anyhow};
 use binrw::BinRead;
 use byteorder::{BigEndian, ByteOrder};
+use log::debug;
 use std::collections::HashMap;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 use std::path::Path;
+use std::sync::OnceLock;
 
-/// Outcome of resolving an NCCH's seed from a local `seeddb.bin`.
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// Seeds keyed by big-endian title id hex.
+pub type SeedMap = HashMap<String, [u8; 16]>;
+
+static SEEDS: OnceLock<SeedMap> = OnceLock::new();
+
+/// Extra seed CDN rounds after the first when every country fails
+/// transiently.
+pub const DEFAULT_SEED_RETRIES: u32 = 2;
+
+/// Parse a seed given as 32 hex digits.
+pub fn parse_seed_hex(text: &str) -> Result<[u8; 16]> {
+    let bytes = hex::decode(text).with_context(|| format!("seed {text:?} is not hex"))?;
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        anyhow!("seed must be 16 bytes (32 hex digits), got {}", bytes.len())
+    })
+}
+
+/// Whether `seed` produces the `seedcheck` stored in an NCCH header for the
+/// title whose id is `title_id_le` (little-endian, as the header stores it).
+pub fn seed_matches(seed: &[u8; 16], seedcheck: &[u8; 4], title_id_le: &[u8; 8]) -> bool {
+    seedcheck_value(seed, title_id_le) == BigEndian::read_u32(seedcheck)
+}
+
+/// Seeds from the database installed by [`load_seed_database`], or from
+/// `seeddb.bin` in the working directory when none was installed. A
+/// missing or unreadable default file yields an empty map.
+pub(crate) fn seeds() -> &'static SeedMap {
+    SEEDS.get_or_init(|| {
+        let seeds = std::fs::read(Path::new("seeddb.bin"))
+            .ok()
+            .and_then(|data| parse_seed_database(&data).ok());
+        match seeds {
+            Some(seeds) => {
+                debug!("Loading {} seeds from seeddb.bin", seeds.len());
+                seeds
+            }
+            None => {
+                debug!("No seeddb.bin found, starting with an empty seed map");
+                HashMap::new()
+            }
+        }
+    })
+}
+
+/// Parse a `seeddb.bin` image, gunzipping it first when it starts with the
+/// gzip magic.
+pub fn parse_seed_database(data: &[u8]) -> Result<SeedMap> {
+    let mut inflated = Vec::new();
+    let data = if data.starts_with(&GZIP_MAGIC) {
+        flate2::read::MultiGzDecoder::new(data)
+            .read_to_end(&mut inflated)
+            .context("decompress gzip seed database")?;
+        &inflated[..]
+    } else {
+        data
+    };
+    let db = SeedDatabase::read(&mut Cursor::new(data)).context("parse seed database")?;
+    Ok(db.seeds.into_iter().map(|s| (s.key, s.value)).collect())
+}
+
+/// Read and parse the seed database at `source`, a local path or an
+/// `http://`/`https://` URL.
+pub async fn read_seed_database(source: &str) -> Result<SeedMap> {
+    let data = if source.starts_with("http://") || source.starts_with("https://") {
+        let response = crate::util::http::CLIENT
+            .get(source)
+            .send()
+            .await
+            .with_context(|| format!("download seed database {source}"))?
+            .error_for_status()
+            .with_context(|| format!("download seed database {source}"))?;
+        response.bytes().await?.to_vec()
+    } else {
+        tokio::fs::read(source)
+            .await
+            .with_context(|| format!("read seed database {source}"))?
+    };
+    parse_seed_database(&data).with_context(|| format!("seed database {source}"))
+}
+
+/// Use the seed database at `source` instead of `./seeddb.bin` for the rest
+/// of the process. Must run before the first seed lookup. Returns the number
+/// of seeds loaded.
+pub async fn load_seed_database(source: &str) -> Result<usize> {
+    let seeds = read_seed_database(source).await?;
+    let count = seeds.len();
+    SEEDS
+        .set(seeds)
+        .map_err(|_| anyhow!("a seed database is already loaded"))?;
+    debug!("Loaded {count} seeds from {source}");
+    Ok(count)
+}
+
+/// Outcome of resolving an NCCH's seed from the local seed database.
 pub struct SeedResolution {
     /// A matching seed was present locally and verified against `seedcheck`.
     pub found: bool,
@@ -21,18 +121,7 @@ pub struct SeedResolution {
     pub derived_key_y: Option<u128>,
 }
 
-fn load_local_seeds() -> HashMap<String, [u8; 16]> {
-    let db_path = Path::new("seeddb.bin");
-    match std::fs::read(db_path) {
-        Ok(data) => match SeedDatabase::read(&mut Cursor::new(data)) {
-            Ok(db) => db.seeds.into_iter().map(|s| (s.key, s.value)).collect(),
-            Err(_) => HashMap::new(),
-        },
-        Err(_) => HashMap::new(),
-    }
-}
-
-/// Look up the NCCH's title seed in a local `seeddb.bin`, verify it against
+/// Look up the NCCH's title seed in the local seed database, verify it against
 /// the header's `seedcheck`, and derive the seed KeyY. Never touches the
 /// network. Returns `found = false` when no local seed matches or the seed
 /// fails verification.
@@ -43,8 +132,7 @@ pub fn resolve_seed_offline(header: &NcchHeader) -> SeedResolution {
     tid_be.reverse();
     let title_id_hex = hex::encode(tid_be);
 
-    let seeds = load_local_seeds();
-    let Some(seed) = seeds.get(&title_id_hex).copied() else {
+    let Some(seed) = seeds().get(&title_id_hex).copied() else {
         return SeedResolution {
             found: false,
             derived_key_y: None,
@@ -79,7 +167,7 @@ fn seedcheck_value(seed: &[u8; 16], title_id_le: &[u8; 8]) -> u32 {
 }
 
 /// Seed KeyY = first 16 bytes of `sha256(base_key_y || seed)`.
-fn derive_seed_key_y(base_key_y: u128, seed: &[u8; 16]) -> u128 {
+pub(crate) fn derive_seed_key_y(base_key_y: u128, seed: &[u8; 16]) -> u128 {
     let mut buf = Vec::with_capacity(16 + seed.len());
     buf.extend_from_slice(&base_key_y.to_be_bytes());
     buf.extend_from_slice(seed);
@@ -93,6 +181,43 @@ fn derive_seed_key_y(base_key_y: u128, seed: &[u8; 16]) -> u128 {
 mod tests {
     use super::*;
 
+    fn seeddb_bytes() -> Vec<u8> {
+        let mut data = vec![0u8; 0x10];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        let mut title_id = 0x0004000000155400u64.to_le_bytes().to_vec();
+        data.append(&mut title_id);
+        data.extend_from_slice(&[0x42; 16]);
+        data.extend_from_slice(&[0; 8]);
+        data
+    }
+
+    #[test]
+    fn parses_plain_and_gzipped_seed_databases() {
+        let plain = seeddb_bytes();
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut gz, &plain).unwrap();
+        let gz = gz.finish().unwrap();
+
+        for data in [plain, gz] {
+            let seeds = parse_seed_database(&data).unwrap();
+            assert_eq!(seeds.len(), 1);
+            assert_eq!(seeds["0004000000155400"], [0x42; 16]);
+        }
+        assert!(parse_seed_database(&GZIP_MAGIC).is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_a_seed_database_from_a_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seeddb.bin");
+        std::fs::write(&path, seeddb_bytes()).unwrap();
+
+        let seeds = read_seed_database(path.to_str().unwrap()).await.unwrap();
+        assert!(seeds.contains_key("0004000000155400"));
+        let missing = dir.path().join("missing.bin");
+        assert!(read_seed_database(missing.to_str().unwrap()).await.is_err());
+    }
+
     #[test]
     fn seedcheck_matches_self_generated_value() {
         let seed = [0x11u8; 16];
@@ -103,6 +228,29 @@ mod tests {
         assert_ne!(check, seedcheck_value(&[0x22u8; 16], &title_id_le));
     }
 
+    /// Seed 00..0f for title 0004000000155400: the seedcheck is the first
+    /// four bytes of sha256(seed || 0054150000000400), computed outside
+    /// this crate.
+    #[test]
+    fn supplied_seed_verifies_against_known_seedcheck() {
+        let seed = parse_seed_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let title_id_le = 0x0004000000155400u64.to_le_bytes();
+        assert!(seed_matches(&seed, &[0x60, 0x65, 0x5a, 0x34], &title_id_le));
+        assert!(!seed_matches(
+            &seed,
+            &[0x60, 0x65, 0x5a, 0x35],
+            &title_id_le
+        ));
+        assert!(!seed_matches(
+            &[0x42; 16],
+            &[0x60, 0x65, 0x5a, 0x34],
+            &title_id_le
+        ));
+
+        assert!(parse_seed_hex("0001").is_err());
+        assert!(parse_seed_hex("zz0102030405060708090a0b0c0d0e0f").is_err());
+    }
+
     #[test]
     fn derive_seed_key_y_is_deterministic_and_mixes_inputs() {
         let seed = [0xABu8; 16];
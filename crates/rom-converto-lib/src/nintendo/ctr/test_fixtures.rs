@@ -110,7 +110,7 @@ pub fn make_ticket(title_id: u64) -> Ticket {
             limits: vec![0x00; 0x40],
             content_index: ContentIndex {
                 header_word: 0,
-                total_size: 22,
+                total_size: 28,
                 data: vec![0x00; 20],
             },
         },
@@ -190,21 +190,10 @@ pub fn make_tmd(title_id: u64, records: Vec<(u32, u16, Vec<u8>, [u8; 32])>) -> T
     }
 }
 
-/// Build and persist a complete synthetic CIA file with `content_size` bytes
-/// of deterministic content. Returns the temp dir (drop guard), the on-disk
-/// path, and the SHA-256 of the content data.
-///
-/// The TMD title id is [`SYNTH_CIA_TITLE_ID`] and the ticket `console_id` is
-/// 0 (global). Signatures are forged dummy bytes, so downstream verifiers
-/// reject the signature checks. All layout, hash, and streaming checks pass.
-pub fn synth_cia(content_size: usize) -> (tempfile::TempDir, std::path::PathBuf, [u8; 32]) {
-    let tmp = tempfile::tempdir().unwrap();
-    let out_path = tmp.path().join("test.cia");
-
-    // Deterministic content that won't accidentally look like a valid NCCH.
-    let content_data: Vec<u8> = (0..content_size)
-        .map(|i| (i as u8).wrapping_mul(37))
-        .collect();
+/// Assemble an in-memory single-content [`CiaFile`] for `title_id`: the
+/// usual CA/CP/XS cert chain, a matching ticket and TMD, and header sizes
+/// taken from the serialized sections so the result round-trips as is.
+pub fn cia_with_content(title_id: u64, content_data: Vec<u8>, meta: Option<MetaData>) -> CiaFile {
     let content_hash = sha256_array(&content_data);
 
     let cert_chain = vec![
@@ -212,18 +201,21 @@ pub fn synth_cia(content_size: usize) -> (tempfile::TempDir, std::path::PathBuf,
         make_cert(b"CP0000000b", 0xBB),
         make_cert(b"XS0000000c", 0xCC),
     ];
-    let ticket = make_ticket(SYNTH_CIA_TITLE_ID);
-    let tmd = make_tmd(
-        SYNTH_CIA_TITLE_ID,
-        vec![(0, 0, content_data.clone(), content_hash)],
-    );
+    let ticket = make_ticket(title_id);
+    let tmd = make_tmd(title_id, vec![(0, 0, content_data.clone(), content_hash)]);
 
     // The CIA header must declare the real ticket and TMD lengths, since
     // their BinWrite impls do not pad to a fixed size.
     let ticket_size = serialized_size(&ticket);
     let tmd_size = serialized_size(&tmd);
+    let meta_size = meta.as_ref().map_or(0, |meta| {
+        let mut buf = Vec::new();
+        meta.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
+            .unwrap();
+        buf.len() as u32
+    });
 
-    let cia = CiaFile {
+    let mut cia = CiaFile {
         header: CiaHeader {
             header_size: CIA_HEADER_SIZE,
             cia_type: 0,
@@ -231,24 +223,57 @@ pub fn synth_cia(content_size: usize) -> (tempfile::TempDir, std::path::PathBuf,
             cert_chain_size: 0x0A00,
             ticket_size,
             tmd_size,
-            meta_size: 0,
-            content_size: content_size as u64,
+            meta_size,
+            content_size: content_data.len() as u64,
             content_index: vec![0x00; 0x2000],
         },
         cert_chain,
         ticket,
         tmd,
         content_data,
-        meta_data: None,
+        meta_data: meta,
     };
+    cia.apply_content_indexes();
+    cia
+}
+
+/// Smallest well-formed [`CiaFile`]: one 0x1000-byte zeroed content under
+/// [`SYNTH_CIA_TITLE_ID`] and no meta block. Tests tweak the one field
+/// they care about and keep everything else consistent.
+pub fn minimal_cia() -> CiaFile {
+    cia_with_content(SYNTH_CIA_TITLE_ID, vec![0x00; 0x1000], None)
+}
 
+/// Serialize `cia` and write it to `path`.
+pub fn write_cia_file(path: &std::path::Path, cia: &CiaFile) {
     let mut buf = Vec::new();
     cia.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
         .unwrap();
 
-    let mut f = std::fs::File::create(&out_path).unwrap();
+    let mut f = std::fs::File::create(path).unwrap();
     f.write_all(&buf).unwrap();
     f.flush().unwrap();
+}
+
+/// Build and persist a complete synthetic CIA file with `content_size` bytes
+/// of deterministic content. Returns the temp dir (drop guard), the on-disk
+/// path, and the SHA-256 of the content data.
+///
+/// The TMD title id is [`SYNTH_CIA_TITLE_ID`] and the ticket `console_id` is
+/// 0 (global). Signatures are forged dummy bytes, so downstream verifiers
+/// reject the signature checks. All layout, hash, and streaming checks pass.
+pub fn synth_cia(content_size: usize) -> (tempfile::TempDir, std::path::PathBuf, [u8; 32]) {
+    let tmp = tempfile::tempdir().unwrap();
+    let out_path = tmp.path().join("test.cia");
+
+    // Deterministic content that won't accidentally look like a valid NCCH.
+    let content_data: Vec<u8> = (0..content_size)
+        .map(|i| (i as u8).wrapping_mul(37))
+        .collect();
+    let content_hash = sha256_array(&content_data);
+
+    let cia = cia_with_content(SYNTH_CIA_TITLE_ID, content_data, None);
+    write_cia_file(&out_path, &cia);
 
     (tmp, out_path, content_hash)
 }
@@ -292,55 +317,9 @@ pub fn synth_cia_with_meta(
     let out_path = tmp.path().join("test.cia");
 
     let content_data = make_ncch_header_bytes(SYNTH_CIA_TITLE_ID);
-    let content_hash = sha256_array(&content_data);
-
-    let cert_chain = vec![
-        make_cert(b"CA00000003", 0xAA),
-        make_cert(b"CP0000000b", 0xBB),
-        make_cert(b"XS0000000c", 0xCC),
-    ];
-    let ticket = make_ticket(SYNTH_CIA_TITLE_ID);
-    let tmd = make_tmd(
-        SYNTH_CIA_TITLE_ID,
-        vec![(0, 0, content_data.clone(), content_hash)],
-    );
-
-    let ticket_size = serialized_size(&ticket);
-    let tmd_size = serialized_size(&tmd);
-
-    let meta_size: u32 = {
-        let mut buf = Vec::new();
-        meta.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
-            .unwrap();
-        buf.len() as u32
-    };
-
-    let cia = CiaFile {
-        header: CiaHeader {
-            header_size: CIA_HEADER_SIZE,
-            cia_type: 0,
-            version: 0,
-            cert_chain_size: 0x0A00,
-            ticket_size,
-            tmd_size,
-            meta_size,
-            content_size: content_data.len() as u64,
-            content_index: vec![0x00; 0x2000],
-        },
-        cert_chain,
-        ticket,
-        tmd,
-        content_data,
-        meta_data: Some(meta.clone()),
-    };
-
-    let mut buf = Vec::new();
-    cia.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
-        .unwrap();
-
-    let mut f = std::fs::File::create(&out_path).unwrap();
-    f.write_all(&buf).unwrap();
-    f.flush().unwrap();
+    let cia = cia_with_content(SYNTH_CIA_TITLE_ID, content_data, Some(meta.clone()));
+    let meta_size = cia.header.meta_size;
+    write_cia_file(&out_path, &cia);
 
     (tmp, out_path, meta, meta_size)
 }
@@ -414,14 +393,7 @@ pub fn synth_encrypted_cia_multi_content(
         content_data,
         meta_data: None,
     };
-
-    let mut buf = Vec::new();
-    cia.write_options(&mut Cursor::new(&mut buf), Endian::Little, ())
-        .unwrap();
-
-    let mut f = std::fs::File::create(&out_path).unwrap();
-    f.write_all(&buf).unwrap();
-    f.flush().unwrap();
+    write_cia_file(&out_path, &cia);
 
     (tmp, out_path, contents)
 }
@@ -459,7 +431,8 @@ fn sha256_serialized<T: BinWrite<Args<'static> = ()>>(items: &[T]) -> Vec<u8> {
     h.finalize().to_vec()
 }
 
-fn serialized_size<T: BinWrite<Args<'static> = ()>>(value: &T) -> u32 {
+/// Big-endian serialized length of `value`, for CIA header section sizes.
+pub fn serialized_size<T: BinWrite<Args<'static> = ()>>(value: &T) -> u32 {
     let mut buf = Vec::new();
     value
         .write_options(&mut Cursor::new(&mut buf), Endian::Big, ())
@@ -14,6 +14,18 @@ pub enum TitleKeyError {
 
     #[error("padding invalid: {0}")]
     PadError(String),
+
+    #[error("invalid title ID {0:?}: expected 16 hex digits, optionally prefixed with 0x")]
+    InvalidTitleId(String),
+
+    #[error("derived title key is {0} hex characters, expected 32")]
+    InvalidTitleKeyLength(usize),
+
+    #[error("invalid common key index {0:?}: expected auto or 0-5")]
+    InvalidCommonKeyIndex(String),
+
+    #[error("invalid title key {0:?}: expected 32 hex digits")]
+    InvalidTitleKey(String),
 }
 
 pub type TitleKeyResult<T> = Result<T, TitleKeyError>;
@@ -13,12 +13,90 @@ use hmac::Hmac;
 use log::debug;
 use pbkdf2::pbkdf2;
 use sha1::Sha1;
+use std::str::FromStr;
 
-pub fn generate_key(mut title_id: &str, password: &str) -> TitleKeyResult<String> {
-    if let Some(stripped) = title_id.strip_prefix("0x") {
-        title_id = stripped;
+/// Title ID category bit set on system titles: system applications,
+/// applets, modules, archives, and firmware.
+const SYSTEM_CATEGORY_FLAG: u16 = 0x0010;
+
+/// Which common key a generated ticket's title key is encrypted with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommonKeyIndex {
+    /// Pick the index retail tickets use for the title, see
+    /// [`infer_common_key_index`].
+    #[default]
+    Auto,
+    /// Use this index, 0..=5.
+    Fixed(u8),
+}
+
+impl CommonKeyIndex {
+    /// The index to write for `title_id`.
+    pub fn resolve(self, title_id: u64) -> u8 {
+        match self {
+            Self::Auto => infer_common_key_index(title_id),
+            Self::Fixed(index) => index,
+        }
     }
+}
 
+/// Parses `auto` or an index from 0 to 5.
+impl FromStr for CommonKeyIndex {
+    type Err = TitleKeyError;
+
+    fn from_str(value: &str) -> TitleKeyResult<Self> {
+        if value.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+        match value.parse::<u8>() {
+            Ok(index) if (index as usize) < CTR_COMMON_KEYS.len() => Ok(Self::Fixed(index)),
+            _ => Err(TitleKeyError::InvalidCommonKeyIndex(value.to_string())),
+        }
+    }
+}
+
+/// Common key index retail tickets use for `title_id`: 1 for system
+/// titles, whose category (the low half of the high word) has the system
+/// bit set, and 0 for everything else, such as eShop applications,
+/// updates, DLC, and demos.
+pub fn infer_common_key_index(title_id: u64) -> u8 {
+    let category = (title_id >> 32) as u16;
+    if category & SYSTEM_CATEGORY_FLAG != 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Parse a title ID as accepted on the command line: exactly 16 hex
+/// digits in either case, with an optional `0x` prefix.
+pub fn parse_title_id(title_id: &str) -> TitleKeyResult<u64> {
+    let digits = title_id
+        .strip_prefix("0x")
+        .or_else(|| title_id.strip_prefix("0X"))
+        .unwrap_or(title_id);
+    if digits.len() != 16 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(TitleKeyError::InvalidTitleId(title_id.to_string()));
+    }
+    u64::from_str_radix(digits, 16).map_err(|_| TitleKeyError::InvalidTitleId(title_id.to_string()))
+}
+
+/// Parse an encrypted title key, as stored in a ticket: exactly 32 hex
+/// digits in either case.
+pub fn parse_title_key(title_key: &str) -> TitleKeyResult<[u8; 16]> {
+    let mut key = [0u8; 16];
+    hex::decode_to_slice(title_key, &mut key)
+        .map_err(|_| TitleKeyError::InvalidTitleKey(title_key.to_string()))?;
+    Ok(key)
+}
+
+/// Canonical lowercase 16-digit form of a title ID string.
+fn normalize_title_id(title_id: &str) -> TitleKeyResult<String> {
+    Ok(format!("{:016x}", parse_title_id(title_id)?))
+}
+
+pub fn generate_key(title_id: &str, password: &str) -> TitleKeyResult<String> {
+    let title_id = normalize_title_id(title_id)?;
     let tid = &title_id[2..];
 
     // secret || trimmed-title-id → bytes
@@ -45,8 +123,7 @@ fn encrypt_title_key(
     title_key_hex: &str,
     ckey_hex: &str,
 ) -> TitleKeyResult<String> {
-    // Strip only the "0x" prefix. Do not drop any other hex digits.
-    let tid = title_id.strip_prefix("0x").unwrap_or(title_id);
+    let tid = normalize_title_id(title_id)?;
 
     // IV is the full 16-byte title id in hex, right-padded with zeros to 32 chars.
     let iv_hex = format!("{tid:0<32}");
@@ -69,8 +146,20 @@ fn encrypt_title_key(
 }
 
 pub fn generate_title_key(title_id: &str, password: Option<String>) -> TitleKeyResult<String> {
+    generate_title_key_with_index(title_id, password, 0)
+}
+
+/// Like [`generate_title_key`], but encrypts the derived key with the
+/// common key at `common_key_index` instead of index 0.
+pub fn generate_title_key_with_index(
+    title_id: &str,
+    password: Option<String>,
+    common_key_index: u8,
+) -> TitleKeyResult<String> {
     let password = password.unwrap_or_else(|| CTR_DEFAULT_TITLE_KEY_PASSWORD.to_string());
-    let common_key = CTR_COMMON_KEYS[0];
+    let common_key = CTR_COMMON_KEYS
+        .get(common_key_index as usize)
+        .ok_or_else(|| TitleKeyError::InvalidCommonKeyIndex(common_key_index.to_string()))?;
 
     debug!("Using title id: {title_id}");
     debug!("Using common key: {common_key}");
@@ -81,6 +170,9 @@ pub fn generate_title_key(title_id: &str, password: Option<String>) -> TitleKeyR
 
     let encrypted = encrypt_title_key(title_id, &un_encrypted, common_key)?;
     debug!("Generated encrypted key: {encrypted}");
+    if encrypted.len() != 32 {
+        return Err(TitleKeyError::InvalidTitleKeyLength(encrypted.len()));
+    }
 
     Ok(encrypted)
 }
@@ -97,6 +189,127 @@ mod tests {
         assert_eq!(key, "3c7faeff5b1d784d25011149f33f50a7");
     }
 
+    #[test]
+    fn parse_title_id_accepts_prefix_and_either_case() {
+        for input in [
+            "0004000000155400",
+            "0x0004000000155400",
+            "0X00040000001554AB",
+        ] {
+            assert!(parse_title_id(input).is_ok(), "{input}");
+        }
+        assert_eq!(
+            parse_title_id("0x00040000001554ab").unwrap(),
+            parse_title_id("00040000001554AB").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_title_id_rejects_malformed_input() {
+        for input in [
+            "",
+            "0x",
+            "0",
+            "000400000015540",
+            "00040000001554000",
+            "000400000015540g",
+            "0x0x00040000001554",
+            " 0004000000155400",
+        ] {
+            assert!(
+                matches!(parse_title_id(input), Err(TitleKeyError::InvalidTitleId(_))),
+                "{input:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_title_key_takes_exactly_16_bytes_of_hex() {
+        let key = parse_title_key("3C7FAEFF5B1D784D25011149F33F50A7").unwrap();
+        assert_eq!(hex::encode(key), "3c7faeff5b1d784d25011149f33f50a7");
+        for bad in [
+            "",
+            "3c7f",
+            "3c7faeff5b1d784d25011149f33f50a7ff",
+            "zz7faeff5b1d784d25011149f33f50a7",
+        ] {
+            assert!(
+                matches!(parse_title_key(bad), Err(TitleKeyError::InvalidTitleKey(_))),
+                "{bad:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn title_key_ignores_title_id_case() {
+        assert_eq!(
+            generate_title_key("0004008C0F70CD00", None).unwrap(),
+            generate_title_key("0x0004008c0f70cd00", None).unwrap()
+        );
+        assert!(generate_key("0x", "mypass").is_err());
+    }
+
+    #[test]
+    fn title_key_depends_on_password() {
+        let title_id = "0004008c0f70cd00";
+        let default = generate_title_key(title_id, None).unwrap();
+        assert_eq!(
+            generate_title_key(title_id, Some("mypass".to_string())).unwrap(),
+            default
+        );
+        let custom = generate_title_key(title_id, Some("password".to_string())).unwrap();
+        assert_eq!(custom.len(), 32);
+        assert_ne!(custom, default);
+    }
+
+    #[test]
+    fn common_key_index_is_inferred_from_the_category() {
+        for (title_id, expected) in [
+            (0x0004000000155400, 0),
+            (0x0004000e00155400, 0),
+            (0x0004008c00155400, 0),
+            (0x0004001000021000, 1),
+            (0x0004013000001502, 1),
+            (0x0004001b00010002, 1),
+        ] {
+            assert_eq!(
+                infer_common_key_index(title_id),
+                expected,
+                "{title_id:016x}"
+            );
+        }
+        assert_eq!(CommonKeyIndex::Fixed(3).resolve(0x0004001000021000), 3);
+    }
+
+    #[test]
+    fn common_key_index_parses_auto_and_in_range_indices() {
+        assert_eq!(
+            "auto".parse::<CommonKeyIndex>().unwrap(),
+            CommonKeyIndex::Auto
+        );
+        assert_eq!(
+            "5".parse::<CommonKeyIndex>().unwrap(),
+            CommonKeyIndex::Fixed(5)
+        );
+        for bad in ["6", "-1", "", "one"] {
+            assert!(bad.parse::<CommonKeyIndex>().is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn title_key_depends_on_common_key_index() {
+        let title_id = "0004008c0f70cd00";
+        assert_eq!(
+            generate_title_key_with_index(title_id, None, 0).unwrap(),
+            generate_title_key(title_id, None).unwrap()
+        );
+        assert_ne!(
+            generate_title_key_with_index(title_id, None, 1).unwrap(),
+            generate_title_key(title_id, None).unwrap()
+        );
+        assert!(generate_title_key_with_index(title_id, None, 6).is_err());
+    }
+
     #[test]
     fn test_generate_key() {
         let title_id = "0x00040000001adc00";
@@ -21,21 +21,46 @@ pub async fn get_all_files(dir_path: &Path) -> NintendoCTRResult<Vec<PathBuf>> {
     Ok(files)
 }
 
+/// How well a file name matches a ticket: `cetk`, then `*.cetk`, then
+/// `*.tik`, then anything starting with `ticket`. `None` for other files.
+fn ticket_name_rank(file: &Path) -> Option<u8> {
+    let file_name = file.file_name()?.to_str()?;
+    let extension = file.extension().and_then(|s| s.to_str()).unwrap_or("");
+    if file_name == "cetk" {
+        Some(0)
+    } else if extension == "cetk" {
+        Some(1)
+    } else if extension == "tik" {
+        Some(2)
+    } else if file_name.starts_with("ticket") {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+/// Finds the ticket in `folder_path`, preferring `cetk`, then `*.cetk`,
+/// `*.tik`, and `ticket*` names, and the first path in sorted order
+/// among files that match equally well.
 pub async fn find_title_file(folder_path: &Path) -> NintendoCTRResult<PathBuf> {
     let files = get_all_files(folder_path).await?;
 
     files
         .iter()
-        .find(|file| {
-            let file_name = file.file_name().and_then(|n| n.to_str());
-            let extension = file.extension().and_then(|s| s.to_str()).unwrap_or("");
-            file_name == Some("cetk") || extension == "tik"
-        })
-        .map(|file| file.to_path_buf())
+        .filter_map(|file| Some((ticket_name_rank(file)?, file)))
+        .min()
+        .map(|(_, file)| file.to_path_buf())
         .ok_or_else(|| NintendoCTRError::NoTitleFileFound(folder_path.to_path_buf()))
 }
 
-pub async fn find_tmd_file(folder_path: &Path) -> NintendoCTRResult<PathBuf> {
+/// Version encoded in a `tmd.<version>` file name, `None` for a bare `tmd`.
+pub fn tmd_file_version(path: &Path) -> Option<u32> {
+    path.extension()?.to_str()?.parse().ok()
+}
+
+/// Finds the TMD in `folder_path`. With `version` set only `tmd.<version>`
+/// is accepted; otherwise a bare `tmd` wins, then the highest `tmd.<n>`.
+pub async fn find_tmd_file(folder_path: &Path, version: Option<u32>) -> NintendoCTRResult<PathBuf> {
     let files = get_all_files(folder_path).await?;
 
     let mut tmd_files: Vec<_> = files
@@ -50,20 +75,71 @@ pub async fn find_tmd_file(folder_path: &Path) -> NintendoCTRResult<PathBuf> {
         })
         .collect();
 
+    if let Some(version) = version {
+        if let Some(file) = tmd_files
+            .iter()
+            .find(|file| tmd_file_version(file) == Some(version))
+        {
+            return Ok(file.to_path_buf());
+        }
+        let mut available: Vec<String> = tmd_files
+            .iter()
+            .filter_map(|file| file.file_name()?.to_str().map(str::to_string))
+            .collect();
+        available.sort();
+        return Err(NintendoCTRError::TmdVersionNotFound {
+            dir: folder_path.to_path_buf(),
+            version,
+            available: if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            },
+        });
+    }
+
     if let Some(tmd_file_exact) = tmd_files.iter().find(|file| {
         file.file_name().and_then(|n| n.to_str()) == Some("tmd") && file.extension().is_none()
     }) {
         return Ok(tmd_file_exact.to_path_buf());
     }
 
-    tmd_files.sort_by_key(|file| {
-        file.extension()
-            .and_then(|s| s.to_str()?.parse::<u32>().ok())
-            .unwrap_or(0)
-    });
+    tmd_files.sort_by_key(|file| tmd_file_version(file).unwrap_or(0));
 
     tmd_files
         .last()
         .map(|file| file.to_path_buf())
         .ok_or_else(|| NintendoCTRError::NoTmdFileFound(folder_path.to_path_buf()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn find_title_file_prefers_cetk_then_tik_then_ticket_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut expected = Vec::new();
+        for name in ["ticket", "b.tik", "a.tik", "title.cetk", "cetk"] {
+            std::fs::write(dir.path().join("00000000"), b"content").unwrap();
+            std::fs::write(dir.path().join(name), b"ticket").unwrap();
+            expected.push(find_title_file(dir.path()).await.unwrap());
+        }
+        let names: Vec<_> = expected
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["ticket", "b.tik", "a.tik", "title.cetk", "cetk"]);
+    }
+
+    #[tokio::test]
+    async fn find_title_file_reports_a_folder_without_a_ticket() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("tmd"), b"tmd").unwrap();
+        std::fs::write(dir.path().join("00000000"), b"content").unwrap();
+        assert!(matches!(
+            find_title_file(dir.path()).await,
+            Err(NintendoCTRError::NoTitleFileFound(_))
+        ));
+    }
+}
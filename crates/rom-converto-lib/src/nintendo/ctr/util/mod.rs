@@ -1,5 +1,7 @@
 use binrw::BinResult;
+use std::ffi::OsString;
 use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
 
 pub mod fs;
 
@@ -32,6 +34,29 @@ pub fn pad_to_align_64(aligned_pos: u64, writer: &mut (impl Write + Seek)) -> Bi
     Ok(())
 }
 
+/// The raw file stem of `input`, or `fallback` when it has none. Output
+/// names are built from it so a non-UTF-8 stem survives unchanged.
+pub fn file_stem_or(input: &Path, fallback: &str) -> OsString {
+    input
+        .file_stem()
+        .map(OsString::from)
+        .unwrap_or_else(|| OsString::from(fallback))
+}
+
+/// `input` renamed to `<stem>.<tag>.<ext>` (or `<stem>.<tag>` without an
+/// extension). Built from the raw `OsStr` parts, so a non-UTF-8 name keeps
+/// its stem instead of collapsing to `out`.
+pub fn tagged_sibling_path(input: &Path, tag: &str) -> PathBuf {
+    let mut name = file_stem_or(input, "out");
+    name.push(".");
+    name.push(tag);
+    if let Some(ext) = input.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    input.with_file_name(name)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -128,4 +153,29 @@ pub mod tests {
         assert_eq!(buffer.get_ref().len(), 1024);
         assert_eq!(&buffer.get_ref()[5..], vec![0u8; 1019].as_slice());
     }
+
+    #[test]
+    fn tagged_sibling_path_inserts_tag_before_extension() {
+        assert_eq!(
+            tagged_sibling_path(Path::new("/roms/game.cia"), "decrypted"),
+            PathBuf::from("/roms/game.decrypted.cia")
+        );
+        assert_eq!(
+            tagged_sibling_path(Path::new("game"), "encrypted"),
+            PathBuf::from("game.encrypted")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tagged_sibling_path_keeps_non_utf8_stem() {
+        use std::os::unix::ffi::OsStrExt;
+        let input = Path::new(std::ffi::OsStr::from_bytes(b"g\xffme.cia"));
+        assert_eq!(
+            tagged_sibling_path(input, "decrypted")
+                .as_os_str()
+                .as_bytes(),
+            b"g\xffme.decrypted.cia"
+        );
+    }
 }
@@ -4,6 +4,8 @@ use crate::nintendo::ctr::constants::{
 };
 use crate::nintendo::ctr::decrypt::util::{cbc_decrypt, gen_iv};
 use crate::nintendo::ctr::error::NintendoCTRError;
+use crate::nintendo::ctr::format::{FORMAT_PROBE_SIZE, sniff_format};
+use crate::nintendo::ctr::info::CtrFormat;
 use crate::nintendo::ctr::models::certificate::{Certificate, PublicKey};
 use crate::nintendo::ctr::models::cia::{CIA_HEADER_SIZE, CiaFileWithoutContent, CiaHeader};
 use crate::nintendo::ctr::models::ncch_header::NcchHeader;
@@ -153,7 +155,7 @@ pub async fn verify_ctr_cancellable(
     let mut file = tokio::fs::File::open(input).await?;
     let file_size = file.metadata().await?.len();
 
-    let mut probe = [0u8; 0x104];
+    let mut probe = [0u8; FORMAT_PROBE_SIZE];
     let probe_len = std::cmp::min(file_size, probe.len() as u64) as usize;
     file.read_exact(&mut probe[..probe_len]).await?;
     file.seek(SeekFrom::Start(0)).await?;
@@ -162,30 +164,24 @@ pub async fn verify_ctr_cancellable(
         return verify_compressed(input, options, progress, cancel).await;
     }
 
-    if probe_len >= 0x104 {
-        let magic = &probe[0x100..0x104];
-        if magic == b"NCSD" {
+    match sniff_format(&probe[..probe_len]) {
+        CtrFormat::Ncsd => {
             let result = verify_ncsd_file(input, progress, cancel).await?;
-            return Ok(CtrVerifyResult::Ncsd(result));
+            Ok(CtrVerifyResult::Ncsd(result))
         }
-        if magic == b"NCCH" {
+        CtrFormat::Ncch => {
             // Standalone NCCH gets reported as a single-partition NCSD result.
             let result = verify_standalone_ncch_file(input, progress, cancel).await?;
-            return Ok(CtrVerifyResult::Ncsd(result));
+            Ok(CtrVerifyResult::Ncsd(result))
         }
-    }
-
-    if probe_len >= 4 {
-        let header_size = u32::from_le_bytes(probe[0..4].try_into()?);
-        if header_size == CIA_HEADER_SIZE {
+        CtrFormat::Cia => {
             let result = verify_cia_cancellable(input, options, progress, cancel).await?;
-            return Ok(CtrVerifyResult::Cia(result));
+            Ok(CtrVerifyResult::Cia(result))
         }
+        CtrFormat::Unknown => Err(anyhow::anyhow!(
+            "Unrecognized format: not a CIA, NCSD, NCCH, or Z3DS file"
+        )),
     }
-
-    Err(anyhow::anyhow!(
-        "Unrecognized format: not a CIA, NCSD, NCCH, or Z3DS file"
-    ))
 }
 
 async fn verify_compressed(
@@ -269,27 +265,16 @@ async fn verify_compressed(
     Ok(result)
 }
 
-pub async fn verify_cia(
-    input: &Path,
-    options: &CtrVerifyOptions,
-    progress: &dyn ProgressReporter,
-) -> Result<CiaVerifyResult> {
-    verify_cia_cancellable(input, options, progress, &CancelToken::new()).await
-}
-
-pub async fn verify_cia_cancellable(
-    input: &Path,
-    options: &CtrVerifyOptions,
-    progress: &dyn ProgressReporter,
-    cancel: &CancelToken,
-) -> Result<CiaVerifyResult> {
-    check_cancel(cancel)?;
-    let mut file = tokio::fs::File::open(input).await?;
-    let file_size = file.metadata().await?.len();
-    progress.start(file_size, "Verifying CIA signatures");
-
+/// Parse everything in a CIA before its content region and return it with
+/// the content region's offset. Only the preamble (a few MB at most) is
+/// read; content bytes are streamed from `file` by the callers.
+async fn read_cia_preamble(
+    file: &mut tokio::fs::File,
+    file_size: u64,
+) -> Result<(CiaFileWithoutContent, u64)> {
     // The header's declared sizes drive the rest of the layout walk.
     let mut header_buf = vec![0u8; CIA_HEADER_SIZE as usize];
+    file.seek(SeekFrom::Start(0)).await?;
     file.read_exact(&mut header_buf).await?;
     let cia_header =
         CiaHeader::read_le(&mut Cursor::new(&header_buf)).context("failed to parse CIA header")?;
@@ -307,20 +292,93 @@ pub async fn verify_cia_cancellable(
         anyhow::bail!("CIA preamble exceeds file size (corrupt header)");
     }
 
-    // Read only the preamble [0..content_start] (few MB at most); content
-    // bytes will be hashed later by streaming directly from the file.
     let mut preamble = vec![0u8; content_start as usize];
     preamble[..CIA_HEADER_SIZE as usize].copy_from_slice(&header_buf);
-    file.seek(SeekFrom::Start(CIA_HEADER_SIZE as u64)).await?;
     file.read_exact(&mut preamble[CIA_HEADER_SIZE as usize..])
         .await?;
-    check_cancel(cancel)?;
-
-    let mut details = Vec::new();
 
     let mut cursor = Cursor::new(&preamble);
     let cia_without_content = CiaFileWithoutContent::read_options(&mut cursor, Endian::Little, ())
         .context("failed to parse CIA file")?;
+    Ok((cia_without_content, content_start))
+}
+
+/// Post-build check for a CIA this crate just wrote: the header's content
+/// size must equal the TMD's content sizes and fit in the file, and with
+/// `hash_contents` every content must hash to its TMD SHA-256. Returns the
+/// problems found; an empty list is a pass.
+pub async fn check_built_cia(
+    input: &Path,
+    hash_contents: bool,
+    cancel: &CancelToken,
+) -> Result<Vec<String>> {
+    check_cancel(cancel)?;
+    let mut file = tokio::fs::File::open(input).await?;
+    let file_size = file.metadata().await?.len();
+    let (cia, content_start) = read_cia_preamble(&mut file, file_size).await?;
+
+    let mut problems = Vec::new();
+    let tmd_size: u64 = cia
+        .tmd
+        .content_chunk_records
+        .iter()
+        .map(|record| record.content_size)
+        .sum();
+    if tmd_size != cia.header.content_size {
+        problems.push(format!(
+            "header content size {} does not match the TMD's {tmd_size}",
+            cia.header.content_size
+        ));
+    }
+    if content_start + tmd_size > file_size {
+        problems.push(format!(
+            "content region needs {} bytes, file is {file_size}",
+            content_start + tmd_size
+        ));
+    }
+    if hash_contents && problems.is_empty() {
+        let title_key = derive_title_key(&cia.ticket);
+        let mut details = Vec::new();
+        let valid = verify_content_hashes_streaming(
+            &mut file,
+            content_start,
+            file_size,
+            &cia.tmd,
+            title_key.as_ref(),
+            &mut details,
+            cancel,
+        )
+        .await?;
+        if !valid {
+            problems.extend(details.into_iter().filter(|line| !line.ends_with("OK")));
+        }
+    }
+    Ok(problems)
+}
+
+pub async fn verify_cia(
+    input: &Path,
+    options: &CtrVerifyOptions,
+    progress: &dyn ProgressReporter,
+) -> Result<CiaVerifyResult> {
+    verify_cia_cancellable(input, options, progress, &CancelToken::new()).await
+}
+
+pub async fn verify_cia_cancellable(
+    input: &Path,
+    options: &CtrVerifyOptions,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> Result<CiaVerifyResult> {
+    check_cancel(cancel)?;
+    let mut file = tokio::fs::File::open(input).await?;
+    let file_size = file.metadata().await?.len();
+    progress.start(file_size, "Verifying CIA signatures");
+
+    let (cia_without_content, content_start) = read_cia_preamble(&mut file, file_size).await?;
+    check_cancel(cancel)?;
+
+    let mut details = Vec::new();
 
     let title_id = format!("{:016X}", cia_without_content.tmd.header.title_id);
     let console_id = cia_without_content.ticket.ticket_data.console_id;
@@ -340,6 +398,20 @@ pub async fn verify_cia_cancellable(
         "Content Count: {}",
         cia_without_content.tmd.header.content_count
     ));
+    let index_mismatch = cia_without_content.header.check_content_indexes(
+        cia_without_content
+            .tmd
+            .content_chunk_records
+            .iter()
+            .map(|r| r.content_index),
+    );
+    if !index_mismatch.is_empty() {
+        log::warn!(
+            "{}: CIA content index bitmap {index_mismatch}; emulators may skip content",
+            input.display()
+        );
+    }
+    details.push(format!("Content index bitmap: {index_mismatch}"));
 
     progress.inc(file_size / 4);
     check_cancel(cancel)?;
@@ -1225,6 +1297,41 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn verify_cia_reports_content_index_bitmap() {
+        let (_tmp, path, _) = synth_cia(0x400);
+        let verify = |path: std::path::PathBuf| async move {
+            verify_cia(
+                &path,
+                &CtrVerifyOptions {
+                    verify_content_hashes: false,
+                },
+                &TestProgress::default(),
+            )
+            .await
+            .unwrap()
+        };
+        let result = verify(path.clone()).await;
+        assert!(
+            result
+                .details
+                .contains(&"Content index bitmap: matches the TMD".to_string())
+        );
+
+        // Clear the bit for content 0 (first byte of the bitmap at 0x20).
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0x20] = 0;
+        std::fs::write(&path, &bytes).unwrap();
+        let result = verify(path).await;
+        assert!(
+            result
+                .details
+                .contains(&"Content index bitmap: TMD contents 0 not set".to_string()),
+            "{:?}",
+            result.details
+        );
+    }
+
     #[tokio::test]
     async fn verify_cia_streaming_validates_content_hash() {
         let (_tmp, path, _) = synth_cia(0x4000);
@@ -1,7 +1,8 @@
-use crate::util::ProgressReporter;
+use crate::util::{ProgressReporter, Tally};
 use anyhow::Result;
 use log::{debug, warn};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::fs;
 
 mod compress;
@@ -64,6 +65,10 @@ pub fn derive_decompressed_path(input: &Path) -> PathBuf {
     map_extension(input, DECOMPRESS_MAP, "3ds")
 }
 
+/// Compresses every matching file under `input_dir`, recording each item in
+/// `tally`. A failed item is logged and skipped, or with `fail_fast` ends the
+/// run with its error.
+#[allow(clippy::too_many_arguments)]
 pub async fn compress_rom_batch(
     input_dir: &Path,
     level: Option<i32>,
@@ -72,6 +77,8 @@ pub async fn compress_rom_batch(
     total_progress: &dyn ProgressReporter,
     max_depth: Option<usize>,
     allow_encrypted: bool,
+    fail_fast: bool,
+    tally: &mut Tally,
 ) -> Result<()> {
     let roms = crate::util::fs::collect_files_with_exts(input_dir, COMPRESS_EXTS, max_depth)?;
     if roms.is_empty() {
@@ -103,8 +110,17 @@ pub async fn compress_rom_batch(
         }
         debug!("Compressing {} -> {}", path.display(), output.display());
 
-        if let Err(err) = compress_rom(&path, &output, level, allow_encrypted, progress).await {
-            warn!("Failed to compress {}: {err}", path.display());
+        let started = Instant::now();
+        match compress_rom(&path, &output, level, allow_encrypted, progress).await {
+            Ok(()) => tally.record_ok_files(&path, &output, started.elapsed()),
+            Err(err) => {
+                tally.record_failed();
+                if fail_fast {
+                    return Err(anyhow::Error::from(err)
+                        .context(format!("Failed to compress {}", path.display())));
+                }
+                warn!("Failed to compress {}: {err}", path.display());
+            }
         }
 
         total_progress.inc(1);
@@ -114,12 +130,17 @@ pub async fn compress_rom_batch(
     Ok(())
 }
 
+/// Decompresses every matching file under `input_dir`, recording each item
+/// in `tally`. A failed item is logged and skipped, or with `fail_fast` ends
+/// the run with its error.
 pub async fn decompress_rom_batch(
     input_dir: &Path,
     output_dir: Option<&Path>,
     progress: &dyn ProgressReporter,
     total_progress: &dyn ProgressReporter,
     max_depth: Option<usize>,
+    fail_fast: bool,
+    tally: &mut Tally,
 ) -> Result<()> {
     let roms = crate::util::fs::collect_files_with_exts(input_dir, DECOMPRESS_EXTS, max_depth)?;
     if roms.is_empty() {
@@ -151,8 +172,17 @@ pub async fn decompress_rom_batch(
         }
         debug!("Decompressing {} -> {}", path.display(), output.display());
 
-        if let Err(err) = decompress_rom(&path, &output, progress).await {
-            warn!("Failed to decompress {}: {err}", path.display());
+        let started = Instant::now();
+        match decompress_rom(&path, &output, progress).await {
+            Ok(()) => tally.record_ok_files(&path, &output, started.elapsed()),
+            Err(err) => {
+                tally.record_failed();
+                if fail_fast {
+                    return Err(anyhow::Error::from(err)
+                        .context(format!("Failed to decompress {}", path.display())));
+                }
+                warn!("Failed to decompress {}: {err}", path.display());
+            }
         }
 
         total_progress.inc(1);
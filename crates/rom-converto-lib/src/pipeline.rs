@@ -170,6 +170,7 @@ pub async fn cue_to_cso(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cd::SECTOR_SIZE;
     use crate::chd::{extract_from_chd, verify_chd};
     use crate::cso::{CsoFormat, decompress_from_cso};
     use crate::util::NoProgress;
@@ -333,6 +334,15 @@ mod tests {
             cue_path,
             chd_path.clone(),
             false,
+            false,
+            None,
+            None,
+            false,
+            SECTOR_SIZE,
+            false,
+            false,
+            false,
+            None,
             CancelToken::new(),
         )
         .await
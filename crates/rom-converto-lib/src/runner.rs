@@ -723,7 +723,19 @@ async fn chd_compress(
     let opts = ChdDvdOptions {
         hunk_size: opt_u32(&req, "hunk_size")?,
         allow_zstd: opt_bool(&req, "allow_zstd").unwrap_or(false),
+        lzma_level: None,
+        zstd_level: None,
         force: true,
+        timing: false,
+        max_memory: None,
+        resume: false,
+        comment: None,
+        fast: opt_bool(&req, "fast").unwrap_or(false),
+        sector_size: opt_usize(&req, "sector_size")?,
+        trim_trailing_zeros: opt_bool(&req, "trim_trailing_zeros").unwrap_or(false),
+        strict: opt_bool(&req, "strict").unwrap_or(false),
+        uncompressed_map: false,
+        single_codec: single_codec(opt_str(&req, "single_codec"))?,
     };
     let mode = disc_mode(opt_str(&req, "mode"))?;
     run_file_op(&input, &output, "chd.compress", || async {
@@ -827,7 +839,19 @@ async fn cso_to_chd(
     let opts = ChdDvdOptions {
         hunk_size: opt_u32(&req, "hunk_size")?,
         allow_zstd: opt_bool(&req, "allow_zstd").unwrap_or(false),
+        lzma_level: None,
+        zstd_level: None,
         force: true,
+        timing: false,
+        max_memory: None,
+        resume: false,
+        comment: None,
+        fast: false,
+        sector_size: None,
+        trim_trailing_zeros: false,
+        strict: false,
+        uncompressed_map: false,
+        single_codec: single_codec(opt_str(&req, "single_codec"))?,
     };
     run_file_op(&input, &output, "cso.to_chd", || async {
         crate::pipeline::cso_to_chd_cancellable(
@@ -1064,7 +1088,14 @@ async fn ctr_decrypt(
         ));
     }
     run_file_op(&input, &output, "ctr.decrypt", || async {
-        crate::nintendo::ctr::decrypt_rom_cancellable(&input, &output, progress, cancel).await
+        crate::nintendo::ctr::decrypt_rom_cancellable(
+            &input,
+            &output,
+            &Default::default(),
+            progress,
+            cancel,
+        )
+        .await
     })
     .await
 }
@@ -1245,13 +1276,51 @@ async fn ctr_cdn_to_cia(
         req.options.output_dir = req.options.output_dir_cia.clone();
     }
     let input = required_input(&req)?;
-    let cia_output = output_or(&req, || {
-        let name = input
-            .file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("title");
-        input.with_file_name(format!("{name}.cia"))
-    })?;
+    // An archive holding the CDN folder is unpacked for the length of the
+    // run, and outputs are placed as if that folder sat beside the archive.
+    let cdn_input = crate::util::resolve_cdn_input(&input)?;
+    let basis = cdn_input.output_basis();
+    let common_key_index = opt_str(&req, "common_key_index")
+        .map(str::parse::<crate::nintendo::ctr::title_key::CommonKeyIndex>)
+        .transpose()?
+        .unwrap_or_default();
+    let ensure_ticket_exists = opt_bool(&req, "ensure_ticket_exists").unwrap_or(false);
+    // A CDN directory has no metadata `read_info` understands, so a
+    // template resolves against its TMD instead of going through output_or.
+    let cia_output = match opt_str(&req, "output_template") {
+        Some(template) if req.output.is_none() => {
+            crate::util::validate_template(template).map_err(|e| invalid_arg(e.to_string()))?;
+            let rel = crate::nintendo::ctr::cdn_cia_templated_path(
+                cdn_input.path(),
+                template,
+                opt_u32(&req, "tmd_version")?,
+                &crate::nintendo::ctr::region::CdnTicketSource {
+                    ticket: opt_path(&req, "ticket"),
+                    generate: ensure_ticket_exists,
+                    title_key: None,
+                    common_key_index,
+                },
+            )
+            .await?;
+            let base = opt_path(&req, "output_dir")
+                .or_else(|| basis.parent().map(Path::to_path_buf))
+                .unwrap_or_else(|| PathBuf::from("."));
+            let joined = base.join(rel);
+            if !req.dry_run
+                && let Some(parent) = joined.parent()
+            {
+                std::fs::create_dir_all(parent)?;
+            }
+            joined
+        }
+        _ => output_or(&req, || {
+            let name = basis
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("title");
+            basis.with_file_name(format!("{name}.cia"))
+        })?,
+    };
     let compress = opt_bool(&req, "compress").unwrap_or(false);
     let output = if compress {
         crate::nintendo::ctr::z3ds::derive_compressed_path(&cia_output)
@@ -1269,25 +1338,56 @@ async fn ctr_cdn_to_cia(
         ));
     }
     let opts = crate::nintendo::ctr::CdnToCiaOptions {
-        cdn_dir: input.clone(),
+        cdn_dir: cdn_input.path().to_path_buf(),
         output: Some(cia_output),
         cleanup: opt_bool(&req, "cleanup").unwrap_or(false),
         recursive: false,
-        ensure_ticket_exists: opt_bool(&req, "ensure_ticket_exists").unwrap_or(false),
+        ensure_ticket_exists,
         decrypt: opt_bool(&req, "decrypt").unwrap_or(false),
         compress,
         output_dir: opt_path(&req, "output_dir"),
         on_conflict: conflict_policy(&req)?,
+        name_from: crate::nintendo::ctr::CdnNameFrom::Dir,
+        verify_after: false,
+        fix_content_size: false,
+        tmd_version: opt_u32(&req, "tmd_version")?,
+        common_key_index,
+        title_key: None,
+        raw_certs: opt_bool(&req, "raw_certs").unwrap_or(false),
+        ticket: opt_path(&req, "ticket"),
+        output_template: None,
+        region: None,
+        content_concurrency: 1,
+        seed_retries: crate::nintendo::ctr::seed::DEFAULT_SEED_RETRIES,
+        fail_fast: false,
     };
     run_file_op(&input, &output, "ctr.cdn_to_cia", || async {
-        crate::nintendo::ctr::convert_cdn_to_cia_cancellable(opts, progress, progress, cancel).await
+        crate::nintendo::ctr::convert_cdn_to_cia_cancellable(
+            opts,
+            progress,
+            progress,
+            &mut crate::util::Tally::new(),
+            cancel,
+        )
+        .await
     })
     .await
 }
 
 async fn ctr_generate_cdn_ticket(req: RunRequest, cancel: CancelToken) -> Result<RunResponse> {
     let input = required_input(&req)?;
-    let desired = output_or(&req, || input.join("ticket.tik"))?;
+    let common_key_index = opt_str(&req, "common_key_index")
+        .map(str::parse::<crate::nintendo::ctr::title_key::CommonKeyIndex>)
+        .transpose()?
+        .unwrap_or_default();
+    let cdn_input = crate::util::resolve_cdn_input(&input)?;
+    let desired = output_or(&req, || {
+        if cdn_input.path() == input {
+            input.join("ticket.tik")
+        } else {
+            cdn_input.output_basis().with_file_name("ticket.tik")
+        }
+    })?;
     let policy = conflict_policy(&req)?;
     let output = match resolve_conflict(&desired, policy)? {
         ConflictResolution::Write(path) => path,
@@ -1306,8 +1406,13 @@ async fn ctr_generate_cdn_ticket(req: RunRequest, cancel: CancelToken) -> Result
         ));
     }
     crate::nintendo::ctr::generate_ticket_from_cdn_with_publish(
-        &input,
+        cdn_input.path(),
         &output,
+        None,
+        None,
+        None,
+        None,
+        common_key_index,
         &cancel,
         policy == ConflictPolicy::Overwrite,
     )
@@ -1889,6 +1994,7 @@ fn rollback_renames(moves: &mut [StagedRename]) -> std::io::Result<()> {
     let mut first_error = None;
     for item in moves.iter_mut().filter(|item| item.published) {
         let result = crate::util::scratch_output_path(&item.to).and_then(|temp| {
+            let temp = temp.into_temp_path();
             std::fs::remove_file(&temp)?;
             std::fs::rename(&item.to, &temp)?;
             item.temp = Some(temp);
@@ -1928,8 +2034,10 @@ fn rename_transaction(
             rollback_renames(&mut moves)?;
             return Err(cancelled_io());
         }
+        // Staged sources hold the user's files, so they are untracked
+        // and survive a forced exit.
         let temp = match crate::util::scratch_output_path(from) {
-            Ok(temp) => temp,
+            Ok(temp) => temp.into_temp_path(),
             Err(err) => {
                 rollback_renames(&mut moves)?;
                 return Err(err);
@@ -2798,6 +2906,7 @@ fn output_or(req: &RunRequest, default: impl FnOnce() -> PathBuf) -> Result<Path
     }
     let derived = default();
     if let (Some(template), Some(input)) = (opt_str(req, "output_template"), req.input.as_deref()) {
+        crate::util::validate_template(template).map_err(|e| invalid_arg(e.to_string()))?;
         let ext = derived.extension().and_then(|s| s.to_str()).unwrap_or("");
         let keys_path = opt_path(req, "keys");
         let info = crate::info::read_info(
@@ -2833,11 +2942,13 @@ fn opt_str<'a>(req: &'a RunRequest, key: &str) -> Option<&'a str> {
         "input_checksum_max" => req.options.input_checksum_max.as_deref(),
         "on_conflict" => req.options.on_conflict.as_deref(),
         "output_template" => req.options.output_template.as_deref(),
+        "single_codec" => req.options.single_codec.as_deref(),
         "preset" => req.options.preset.as_deref(),
         "platform" => req.options.platform.as_deref(),
         "dat_id" => req.options.dat_id.as_deref(),
         "dat_name" => req.options.dat_name.as_deref(),
         "subset" => req.options.subset.as_deref(),
+        "common_key_index" => req.options.common_key_index.as_deref(),
         _ => None,
     }
 }
@@ -2847,6 +2958,9 @@ fn opt_bool(req: &RunRequest, key: &str) -> Option<bool> {
         "recursive" => req.options.recursive,
         "full" => req.options.full,
         "allow_zstd" => req.options.allow_zstd,
+        "fast" => req.options.fast,
+        "trim_trailing_zeros" => req.options.trim_trailing_zeros,
+        "strict" => req.options.strict,
         "fix" => req.options.fix,
         "skip_verify" => req.options.skip_verify,
         "deep" => req.options.deep,
@@ -2857,6 +2971,7 @@ fn opt_bool(req: &RunRequest, key: &str) -> Option<bool> {
         "cleanup" => req.options.cleanup,
         "ensure_ticket_exists" => req.options.ensure_ticket_exists,
         "decrypt" => req.options.decrypt,
+        "raw_certs" => req.options.raw_certs,
         _ => None,
     }
 }
@@ -2874,6 +2989,7 @@ fn opt_u32(req: &RunRequest, key: &str) -> Result<Option<u32>> {
         "hunk_size" => req.options.hunk_size,
         "chunk_size" => req.options.chunk_size,
         "block_size_exp" => req.options.block_size_exp,
+        "tmd_version" => req.options.tmd_version,
         _ => None,
     })
 }
@@ -2881,6 +2997,7 @@ fn opt_u32(req: &RunRequest, key: &str) -> Result<Option<u32>> {
 fn opt_usize(req: &RunRequest, key: &str) -> Result<Option<usize>> {
     Ok(match key {
         "max_depth" => req.options.max_depth,
+        "sector_size" => req.options.sector_size,
         _ => None,
     })
 }
@@ -2891,6 +3008,7 @@ fn opt_path(req: &RunRequest, key: &str) -> Option<PathBuf> {
         "report" => req.options.report.clone(),
         "output_dir" => req.options.output_dir.clone(),
         "parent" => req.options.parent.clone(),
+        "ticket" => req.options.ticket.clone(),
         "keys" => req.options.keys.clone(),
         "key" => req.options.key.clone(),
         _ => None,
@@ -2928,6 +3046,12 @@ fn disc_mode(value: Option<&str>) -> Result<Option<DiscMode>> {
     }
 }
 
+fn single_codec(value: Option<&str>) -> Result<Option<crate::chd::compression::ChdCodec>> {
+    value
+        .map(|name| name.parse().map_err(invalid_arg))
+        .transpose()
+}
+
 fn nx_mode(value: &str, block_size_exp: Option<u32>) -> Result<crate::nintendo::nx::NczMode> {
     match value {
         "solid" => Ok(crate::nintendo::nx::NczMode::Solid),
@@ -3204,6 +3328,38 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn chd_compress_options_are_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("game.cue");
+        std::fs::write(&input, b"").unwrap();
+
+        let req = json!({
+            "operation": "chd.compress",
+            "input": input,
+            "dry_run": true,
+            "options": { "fast": true, "sector_size": 2448, "trim_trailing_zeros": true, "strict": true, "single_codec": "cdzl" }
+        });
+        let res = run_json(&req.to_string(), CancelToken::new()).await;
+        assert!(res.data.is_some(), "{}", res.message);
+    }
+
+    #[tokio::test]
+    async fn cdn_tmd_version_and_ticket_options_are_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("cdn");
+        std::fs::create_dir(&input).unwrap();
+
+        let req = json!({
+            "operation": "ctr.cdn_to_cia",
+            "input": input,
+            "dry_run": true,
+            "options": { "tmd_version": 1024, "ticket": "title.tik" }
+        });
+        let res = run_json(&req.to_string(), CancelToken::new()).await;
+        assert!(res.data.is_some(), "{}", res.message);
+    }
+
     #[tokio::test]
     async fn output_conflict_skip_avoids_fixdat_and_ticket_inputs() {
         let dir = tempfile::tempdir().unwrap();
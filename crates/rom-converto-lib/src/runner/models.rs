@@ -425,8 +425,14 @@ pub struct RunOptions {
     pub block_size: Option<u32>,
     pub hunk_size: Option<u32>,
     pub allow_zstd: Option<bool>,
+    pub fast: Option<bool>,
+    pub sector_size: Option<usize>,
+    pub trim_trailing_zeros: Option<bool>,
+    pub strict: Option<bool>,
+    pub single_codec: Option<String>,
     pub mode: Option<String>,
     pub parent: Option<PathBuf>,
+    pub ticket: Option<PathBuf>,
     pub full: Option<bool>,
     pub fix: Option<bool>,
     pub level: Option<i32>,
@@ -441,9 +447,12 @@ pub struct RunOptions {
     pub cleanup: Option<bool>,
     pub ensure_ticket_exists: Option<bool>,
     pub decrypt: Option<bool>,
+    pub raw_certs: Option<bool>,
     pub output_dir_cia: Option<PathBuf>,
     pub keys: Option<PathBuf>,
     pub block_size_exp: Option<u32>,
+    pub tmd_version: Option<u32>,
+    pub common_key_index: Option<String>,
     pub key: Option<PathBuf>,
     pub extensions: Option<String>,
     pub playlist_mode: Option<String>,
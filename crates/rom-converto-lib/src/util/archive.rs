@@ -1,10 +1,11 @@
 //! Transparent archive input. Read-side commands accept a zip, 7z, rar, tar,
-//! or tar.gz container holding a supported image and operate on the first
-//! member matching the caller's extension set. The member (and, for a cue
-//! sheet, the bin tracks it references) is extracted to a temp dir that is
-//! removed when the returned [`ResolvedInput`] is dropped. Rar support is
-//! read-only and binds the vendored unrar sources, which are free to use but
-//! not OSI-permissive.
+//! or tar.gz container, or a single gzipped file, holding a supported image
+//! and operate on the first member matching the caller's extension set. The
+//! member (and, for a cue sheet, the bin tracks it references) is extracted
+//! to a temp dir that is removed when the returned [`ResolvedInput`] is
+//! dropped. A CDN title folder inside an archive resolves the same way
+//! through [`resolve_cdn_input`]. Rar support is read-only and binds the
+//! vendored unrar sources, which are free to use but not OSI-permissive.
 
 use crate::util::fs::{has_any_extension, is_os_junk_file};
 use crate::util::{DEFAULT_SPACE_HEADROOM, available_space, format_bytes, space_shortfall};
@@ -15,7 +16,7 @@ use std::path::{Path, PathBuf};
 
 /// Extensions the recursive walker treats as archives. Matches the form
 /// [`Path::extension`] returns, so `foo.tar.gz` is caught by `gz` and refined
-/// to a gzipped tar by [`kind_of`].
+/// to a gzipped tar or a single gzipped file by [`kind_of`].
 pub const ARCHIVE_EXTS: &[&str] = &["zip", "7z", "tar", "tgz", "gz", "rar"];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +26,8 @@ enum ArchiveKind {
     Tar,
     TarGz,
     Rar,
+    /// A single gzipped file with no tar container (`game.iso.gz`).
+    Gzip,
 }
 
 /// A single non-junk file inside an archive.
@@ -36,16 +39,67 @@ pub struct ArchiveMember {
     pub size: u64,
 }
 
-/// True when `path` carries an extension the archive layer recognizes. A bare
-/// `.gz` (a single gzipped file with no tar container) matches here but is
-/// reported unsupported once opened.
+/// True when `path` carries an extension the archive layer recognizes.
 pub fn is_archive_path(path: &Path) -> bool {
     has_any_extension(path, ARCHIVE_EXTS)
 }
 
+fn is_tar_gz_name(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_ascii_lowercase)
+        .is_some_and(|name| name.ends_with(".tar.gz") || name.ends_with(".tgz"))
+}
+
+/// The container a file's leading bytes announce, whatever its name. A gzip
+/// stream is refined to a gzipped tar by the name alone, since the tar header
+/// sits inside the compressed data. Old pre-POSIX tars carry no `ustar` magic
+/// and are left to the extension.
+///
+/// A gzip stream needs its whole fixed header prefix, not just the two magic
+/// bytes: deflate as the method and no reserved flag bits. Signed ROMs open
+/// with a random RSA signature, which would match two bytes now and then.
+fn sniff_kind(path: &Path) -> Option<ArchiveKind> {
+    use std::io::Read;
+    let mut head = Vec::with_capacity(262);
+    File::open(path)
+        .ok()?
+        .take(262)
+        .read_to_end(&mut head)
+        .ok()?;
+    if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+        Some(ArchiveKind::Zip)
+    } else if head.starts_with(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+        Some(ArchiveKind::SevenZ)
+    } else if head.starts_with(b"Rar!\x1A\x07") {
+        Some(ArchiveKind::Rar)
+    } else if is_gzip_header(&head) {
+        Some(if is_tar_gz_name(path) {
+            ArchiveKind::TarGz
+        } else {
+            ArchiveKind::Gzip
+        })
+    } else if head.get(257..262) == Some(b"ustar".as_slice()) {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// RFC 1952 header prefix: ID1, ID2, CM = 8 (deflate), and an FLG byte with
+/// the reserved bits 5 to 7 clear.
+fn is_gzip_header(head: &[u8]) -> bool {
+    matches!(head, [0x1F, 0x8B, 8, flags, ..] if flags & 0xE0 == 0)
+}
+
+/// Archive kind by magic, falling back to the extension when the bytes match
+/// nothing (or the file cannot be read, so the caller's open reports it).
 fn kind_of(path: &Path) -> Option<ArchiveKind> {
-    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
-    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+    sniff_kind(path).or_else(|| kind_by_name(path))
+}
+
+fn kind_by_name(path: &Path) -> Option<ArchiveKind> {
+    if is_tar_gz_name(path) {
         return Some(ArchiveKind::TarGz);
     }
     let ext = path.extension()?.to_str()?.to_ascii_lowercase();
@@ -54,6 +108,7 @@ fn kind_of(path: &Path) -> Option<ArchiveKind> {
         "7z" => Some(ArchiveKind::SevenZ),
         "tar" => Some(ArchiveKind::Tar),
         "rar" => Some(ArchiveKind::Rar),
+        "gz" => Some(ArchiveKind::Gzip),
         _ => None,
     }
 }
@@ -101,20 +156,21 @@ fn keep_member(name: &str, size: u64, is_dir: bool) -> Option<ArchiveMember> {
 
 /// List the convertible members of an archive: regular files only, with OS
 /// junk and nested archives filtered out, sorted by name for deterministic
-/// first-match selection. Bare gzip returns a clear unsupported error.
+/// first-match selection. A single gzipped file lists as one member named
+/// after the archive minus its `.gz`.
 pub fn list_members(path: &Path) -> Result<Vec<ArchiveMember>> {
-    let kind = kind_of(path).ok_or_else(|| {
-        anyhow!(
-            "gzip archives without a tar container are not supported, extract the file first: {}",
-            path.display()
-        )
-    })?;
+    let kind = kind_of(path).ok_or_else(|| anyhow!("not an archive: {}", path.display()))?;
+    list_members_of(path, kind)
+}
+
+fn list_members_of(path: &Path, kind: ArchiveKind) -> Result<Vec<ArchiveMember>> {
     let mut out = match kind {
         ArchiveKind::Zip => list_zip(path)?,
         ArchiveKind::SevenZ => list_7z(path)?,
         ArchiveKind::Tar => list_tar(path, false)?,
         ArchiveKind::TarGz => list_tar(path, true)?,
         ArchiveKind::Rar => list_rar(path)?,
+        ArchiveKind::Gzip => list_gzip(path)?,
     };
     out.sort_by(|a, b| a.name.cmp(&b.name));
     Ok(out)
@@ -182,6 +238,73 @@ fn list_rar(path: &Path) -> Result<Vec<ArchiveMember>> {
     Ok(out)
 }
 
+/// Member name of a single gzipped file: the archive's own name with the
+/// `.gz` suffix removed.
+fn gzip_member_name(path: &Path) -> Result<String> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow!("invalid gzip file name: {}", path.display()))?;
+    // Sniffed by magic, the file may not carry the suffix at all.
+    let stem = match name.len().checked_sub(".gz".len()) {
+        Some(cut) if name.is_char_boundary(cut) && name[cut..].eq_ignore_ascii_case(".gz") => {
+            &name[..cut]
+        }
+        _ => name,
+    };
+    if stem.is_empty() {
+        bail!("gzip file {} has no inner file name", path.display());
+    }
+    Ok(stem.to_string())
+}
+
+/// The listed size is exact when [`gzip_exact_size`] can count it, and is
+/// otherwise the trailer's ISIZE field, which can be far too low.
+fn list_gzip(path: &Path) -> Result<Vec<ArchiveMember>> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 2];
+    file.read_exact(&mut magic)?;
+    if magic != [0x1F, 0x8B] {
+        bail!("{} is not a gzip file", path.display());
+    }
+    let mut isize = [0u8; 4];
+    file.seek(SeekFrom::End(-4))?;
+    file.read_exact(&mut isize)?;
+    let size = match gzip_exact_size(path, GZIP_COUNT_LIMIT)? {
+        Some(size) => size,
+        None => u64::from(u32::from_le_bytes(isize)),
+    };
+    Ok(keep_member(&gzip_member_name(path)?, size, false)
+        .into_iter()
+        .collect())
+}
+
+/// Largest gzip file whose uncompressed size [`gzip_exact_size`] counts.
+/// Deflate expands at most about 1032 to 1, so anything this small
+/// decodes to under 4 GiB and counting it is cheap.
+const GZIP_COUNT_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// Uncompressed size of a gzip file, counted by decoding it, or `None`
+/// when the file is larger than `limit`. The trailer's ISIZE field cannot
+/// stand in: it is the size modulo 2^32, so it wraps for images over
+/// 4 GiB, and covers only the last member of a multi-member stream.
+fn gzip_exact_size(path: &Path, limit: u64) -> Result<Option<u64>> {
+    if std::fs::metadata(path)?.len() > limit {
+        return Ok(None);
+    }
+    let mut decoder = flate2::read::MultiGzDecoder::new(std::io::BufReader::new(File::open(path)?));
+    Ok(Some(std::io::copy(&mut decoder, &mut std::io::sink())?))
+}
+
+fn extract_gzip(path: &Path, dest_dir: &Path) -> Result<PathBuf> {
+    let out = dest_dir.join(safe_basename(&gzip_member_name(path)?)?);
+    let mut decoder = flate2::read::MultiGzDecoder::new(std::io::BufReader::new(File::open(path)?));
+    let mut writer = File::create(&out)?;
+    std::io::copy(&mut decoder, &mut writer)?;
+    Ok(out)
+}
+
 fn open_tar(path: &Path, gz: bool) -> Result<tar::Archive<Box<dyn std::io::Read>>> {
     let file = File::open(path)?;
     let reader: Box<dyn std::io::Read> = if gz {
@@ -272,9 +395,79 @@ fn extract_one(
             }
             bail!("member {member_name} not found in {}", path.display())
         }
+        ArchiveKind::Gzip => extract_gzip(path, dest_dir),
     }
 }
 
+/// Extract every member named in `names` to `dest_dir` in one pass over the
+/// archive, so a solid 7z or a tar is not decoded once per member.
+fn extract_members(
+    path: &Path,
+    kind: ArchiveKind,
+    names: &HashSet<String>,
+    dest_dir: &Path,
+) -> Result<()> {
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+            for name in names {
+                let mut entry = zip.by_name(name)?;
+                let mut writer = File::create(dest_dir.join(safe_basename(name)?))?;
+                std::io::copy(&mut entry, &mut writer)?;
+            }
+        }
+        ArchiveKind::SevenZ => {
+            let mut reader =
+                sevenz_rust2::ArchiveReader::open(path, sevenz_rust2::Password::empty())?;
+            let mut remaining = names.len();
+            let mut err: Option<anyhow::Error> = None;
+            reader.for_each_entries(|entry, rd| {
+                if !names.contains(entry.name()) {
+                    return Ok(true);
+                }
+                let written = safe_basename(entry.name()).and_then(|base| {
+                    let mut f = File::create(dest_dir.join(base))?;
+                    std::io::copy(rd, &mut f)?;
+                    Ok(())
+                });
+                if let Err(e) = written {
+                    err = Some(e);
+                    return Ok(false);
+                }
+                remaining -= 1;
+                Ok(remaining > 0)
+            })?;
+            if let Some(e) = err {
+                return Err(e);
+            }
+        }
+        ArchiveKind::Tar | ArchiveKind::TarGz => {
+            let mut archive = open_tar(path, kind == ArchiveKind::TarGz)?;
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let name = entry.path()?.to_string_lossy().replace('\\', "/");
+                if names.contains(&name) {
+                    let mut writer = File::create(dest_dir.join(safe_basename(&name)?))?;
+                    std::io::copy(&mut entry, &mut writer)?;
+                }
+            }
+        }
+        ArchiveKind::Rar => {
+            let mut archive = unrar::Archive::new(path).open_for_processing()?;
+            while let Some(header) = archive.read_header()? {
+                let name = header.entry().filename.to_string_lossy().replace('\\', "/");
+                archive = if names.contains(&name) {
+                    header.extract_to(dest_dir.join(safe_basename(&name)?))?
+                } else {
+                    header.skip()?
+                };
+            }
+        }
+        ArchiveKind::Gzip => bail!("{} holds a single file, not a folder", path.display()),
+    }
+    Ok(())
+}
+
 /// A read input resolved to a real file on disk. For a plain file this is a
 /// passthrough; for an archive it holds the extracted member and a temp dir
 /// that is deleted when this value drops.
@@ -325,11 +518,46 @@ fn cue_referenced_basenames(cue_path: &Path) -> Vec<String> {
         .collect()
 }
 
+/// A gzipped cue sheet cannot carry its bins, so look for each referenced
+/// track gzipped beside the archive (`track.bin.gz`) and unpack it next to
+/// the extracted cue.
+fn extract_gzipped_siblings(archive: &Path, cue_path: &Path, dest_dir: &Path) -> Result<()> {
+    let dir = archive.parent().unwrap_or_else(|| Path::new("."));
+    for name in cue_referenced_basenames(cue_path) {
+        let sibling = dir.join(format!("{name}.gz"));
+        if sibling.is_file() {
+            extract_gzip(&sibling, dest_dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// A temp dir to extract `size` bytes into, refused up front when its volume
+/// is too small to hold them.
+fn temp_dir_for(size: u64) -> Result<tempfile::TempDir> {
+    let tmp = tempfile::tempdir()?;
+    if let Ok(available) = available_space(tmp.path())
+        && space_shortfall(available, size, DEFAULT_SPACE_HEADROOM).is_some()
+    {
+        bail!(
+            "not enough space on the temp volume at {} to extract {}: need about {}, only {} free. Point TMPDIR at a larger volume or extract the archive first.",
+            tmp.path().display(),
+            format_bytes(size),
+            format_bytes(size.saturating_add(DEFAULT_SPACE_HEADROOM)),
+            format_bytes(available)
+        );
+    }
+    Ok(tmp)
+}
+
 /// Resolve a read input for `exts`. Plain files pass through unchanged. For an
 /// archive, extract the first member matching `exts` (plus the bin tracks a cue
-/// references) to a temp dir and point the pipeline at it.
+/// references) to a temp dir and point the pipeline at it. An archive is
+/// recognized by its extension or, failing that, by its magic bytes. A file
+/// already named with one of `exts` is never sniffed, so a ROM whose leading
+/// bytes happen to look like an archive is still read as the ROM.
 pub fn resolve_input(path: &Path, exts: &[&str]) -> Result<ResolvedInput> {
-    if !is_archive_path(path) {
+    if has_any_extension(path, exts) || (!is_archive_path(path) && sniff_kind(path).is_none()) {
         return Ok(ResolvedInput {
             path: path.to_path_buf(),
             output_basis: path.to_path_buf(),
@@ -337,12 +565,7 @@ pub fn resolve_input(path: &Path, exts: &[&str]) -> Result<ResolvedInput> {
         });
     }
 
-    let kind = kind_of(path).ok_or_else(|| {
-        anyhow!(
-            "gzip archives without a tar container are not supported, extract the file first: {}",
-            path.display()
-        )
-    })?;
+    let kind = kind_of(path).ok_or_else(|| anyhow!("not an archive: {}", path.display()))?;
     let members = list_members(path)?;
     let matches: Vec<&ArchiveMember> = members
         .iter()
@@ -367,18 +590,21 @@ pub fn resolve_input(path: &Path, exts: &[&str]) -> Result<ResolvedInput> {
         }
     };
 
-    let tmp = tempfile::tempdir()?;
-    if let Ok(available) = available_space(tmp.path())
-        && space_shortfall(available, member.size, DEFAULT_SPACE_HEADROOM).is_some()
-    {
-        bail!(
-            "not enough space on the temp volume at {} to extract {}: need about {}, only {} free. Point TMPDIR at a larger volume or extract the archive first.",
-            tmp.path().display(),
-            format_bytes(member.size),
-            format_bytes(member.size.saturating_add(DEFAULT_SPACE_HEADROOM)),
-            format_bytes(available)
-        );
-    }
+    let size = if kind == ArchiveKind::Gzip {
+        gzip_exact_size(path, GZIP_COUNT_LIMIT)?
+    } else {
+        Some(member.size)
+    };
+    let tmp = match size {
+        Some(size) => temp_dir_for(size)?,
+        None => {
+            log::warn!(
+                "{} is too large to size without decompressing it, skipping the temp space check",
+                path.display()
+            );
+            tempfile::tempdir()?
+        }
+    };
     let extracted = extract_one(path, kind, &member.name, tmp.path())?;
 
     if name_has_ext(&member.name, &["cue"]) {
@@ -386,6 +612,9 @@ pub fn resolve_input(path: &Path, exts: &[&str]) -> Result<ResolvedInput> {
             .into_iter()
             .map(|n| n.to_ascii_lowercase())
             .collect();
+        if kind == ArchiveKind::Gzip {
+            extract_gzipped_siblings(path, &extracted, tmp.path())?;
+        }
         for other in &members {
             let base = basename(&other.name).to_ascii_lowercase();
             if other.name != member.name && wanted.contains(&base) {
@@ -406,6 +635,102 @@ pub fn resolve_input(path: &Path, exts: &[&str]) -> Result<ResolvedInput> {
     })
 }
 
+/// Directory part of a member path, empty for a member at the archive root.
+fn member_dir(name: &str) -> &str {
+    name.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// `tmd` or a versioned `tmd.<n>`, the names the CDN tooling looks for.
+fn is_tmd_name(name: &str) -> bool {
+    let base = basename(name);
+    base.rsplit_once('.').map_or(base, |(stem, _)| stem) == "tmd"
+}
+
+/// Resolve a CDN title input. A directory passes through unchanged. An
+/// archive, recognized by its magic bytes, is searched for the folder holding
+/// a `tmd`; every file in that folder is extracted to a temp directory of the
+/// same name (the archive's name when the files sit at its root), which the
+/// CDN tooling then reads like any other title folder. The output basis is
+/// that folder name beside the archive.
+pub fn resolve_cdn_input(path: &Path) -> Result<ResolvedInput> {
+    if path.is_dir() {
+        return Ok(ResolvedInput {
+            path: path.to_path_buf(),
+            output_basis: path.to_path_buf(),
+            _tmp: None,
+        });
+    }
+    let kind = sniff_kind(path).ok_or_else(|| {
+        anyhow!(
+            "{} is neither a CDN directory nor a supported archive",
+            path.display()
+        )
+    })?;
+    if kind == ArchiveKind::Gzip {
+        bail!(
+            "{} is a single gzipped file and cannot hold a CDN folder",
+            path.display()
+        );
+    }
+
+    let members = list_members_of(path, kind)?;
+    let mut folders: Vec<&str> = members
+        .iter()
+        .filter(|m| is_tmd_name(&m.name))
+        .map(|m| member_dir(&m.name))
+        .collect();
+    folders.dedup();
+    let folder = match folders.as_slice() {
+        [] => bail!("archive {} holds no CDN folder (no tmd)", path.display()),
+        [first, rest @ ..] => {
+            if !rest.is_empty() {
+                log::warn!(
+                    "{} holds {} CDN folders; using {}",
+                    path.display(),
+                    folders.len(),
+                    if first.is_empty() { "the root" } else { first }
+                );
+            }
+            *first
+        }
+    };
+    let files: Vec<&ArchiveMember> = members
+        .iter()
+        .filter(|m| member_dir(&m.name) == folder)
+        .collect();
+
+    let folder_name = if folder.is_empty() {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow!("invalid archive file name: {}", path.display()))?;
+        let lower = name.to_ascii_lowercase();
+        let cut = [".tar.gz", ".tgz", ".zip", ".7z", ".tar", ".rar"]
+            .iter()
+            .find(|ext| lower.ends_with(*ext))
+            .map_or(name.len(), |ext| name.len() - ext.len());
+        safe_basename(&name[..cut])?
+    } else {
+        safe_basename(folder)?
+    };
+
+    let tmp = temp_dir_for(files.iter().map(|m| m.size).sum())?;
+    let root = tmp.path().join(&folder_name);
+    std::fs::create_dir(&root)?;
+    let names: HashSet<String> = files.iter().map(|m| m.name.clone()).collect();
+    extract_members(path, kind, &names, &root)?;
+
+    let output_basis = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(&folder_name);
+    Ok(ResolvedInput {
+        path: root,
+        output_basis,
+        _tmp: Some(tmp),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -586,16 +911,69 @@ mod tests {
         assert_eq!(resolved.output_basis(), dir.path().join("game.iso"));
     }
 
+    fn write_gzip(path: &Path, data: &[u8]) {
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap();
+    }
+
     #[test]
-    fn bare_gzip_reports_unsupported() {
+    fn bare_gzip_extracts_the_inner_file() {
         let dir = tempfile::tempdir().unwrap();
         let gz = dir.path().join("game.iso.gz");
-        std::fs::write(&gz, b"\x1f\x8b\x08\x00").unwrap();
-        let err = resolve_input(&gz, &["iso"]).unwrap_err().to_string();
-        assert!(
-            err.contains("gzip archives without a tar container"),
-            "{err}"
+        write_gzip(&gz, b"gz-only-payload");
+        let members = list_members(&gz).unwrap();
+        assert_eq!(
+            members,
+            [ArchiveMember {
+                name: "game.iso".into(),
+                size: 15,
+            }]
         );
+        let resolved = resolve_input(&gz, &["iso"]).unwrap();
+        assert_eq!(std::fs::read(resolved.path()).unwrap(), b"gz-only-payload");
+        assert_eq!(resolved.output_basis(), dir.path().join("game.iso"));
+        assert!(resolve_input(&gz, &["cue"]).is_err());
+    }
+
+    #[test]
+    fn gzip_size_counts_every_member_or_gives_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let gz = dir.path().join("game.iso.gz");
+        write_gzip(&gz, &[0x11; 3000]);
+        // A second member: the trailer's ISIZE only covers this one.
+        let first = std::fs::read(&gz).unwrap();
+        write_gzip(&gz, &[0x22; 10]);
+        let second = std::fs::read(&gz).unwrap();
+        std::fs::write(&gz, [first, second].concat()).unwrap();
+
+        assert_eq!(gzip_exact_size(&gz, GZIP_COUNT_LIMIT).unwrap(), Some(3010));
+        assert_eq!(list_members(&gz).unwrap()[0].size, 3010);
+        assert_eq!(gzip_exact_size(&gz, 16).unwrap(), None);
+    }
+
+    #[test]
+    fn gzipped_cue_pulls_in_gzipped_bins_beside_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let gz = dir.path().join("game.cue.gz");
+        write_gzip(
+            &gz,
+            b"FILE \"game (Track 1).bin\" BINARY\n  TRACK 01 MODE1/2352\n",
+        );
+        write_gzip(&dir.path().join("game (Track 1).bin.gz"), b"track-one");
+        let resolved = resolve_input(&gz, &["cue"]).unwrap();
+        let bin = resolved.path().with_file_name("game (Track 1).bin");
+        assert_eq!(std::fs::read(bin).unwrap(), b"track-one");
+    }
+
+    #[test]
+    fn bare_gzip_rejects_non_gzip_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let gz = dir.path().join("game.iso.gz");
+        std::fs::write(&gz, b"not gzip at all").unwrap();
+        let err = resolve_input(&gz, &["iso"]).unwrap_err().to_string();
+        assert!(err.contains("is not a gzip file"), "{err}");
     }
 
     #[test]
@@ -648,6 +1026,80 @@ mod tests {
         assert!(!dir.join("unrelated.bin").exists());
     }
 
+    #[test]
+    fn archive_without_an_archive_name_is_sniffed() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = dir.path().join("download");
+        write_zip(
+            &download,
+            zip::CompressionMethod::Deflated,
+            &[("game.cia", b"cia-bytes")],
+        );
+        let resolved = resolve_input(&download, &["cia"]).unwrap();
+        assert_eq!(std::fs::read(resolved.path()).unwrap(), b"cia-bytes");
+        assert_eq!(resolved.output_basis(), dir.path().join("game.cia"));
+    }
+
+    #[test]
+    fn rom_named_input_is_not_sniffed() {
+        let dir = tempfile::tempdir().unwrap();
+        // A signed ROM whose RSA signature happens to open like a gzip header.
+        let rom = dir.path().join("game.3ds");
+        std::fs::write(&rom, [0x1F, 0x8B, 0x08, 0x00, 0x12, 0x34]).unwrap();
+        let resolved = resolve_input(&rom, &["3ds", "cia"]).unwrap();
+        assert_eq!(resolved.path(), rom);
+    }
+
+    #[test]
+    fn gzip_sniff_needs_the_method_and_flag_bytes() {
+        assert!(is_gzip_header(&[0x1F, 0x8B, 0x08, 0x08, 0, 0]));
+        assert!(!is_gzip_header(&[0x1F, 0x8B, 0x07, 0x00]));
+        assert!(!is_gzip_header(&[0x1F, 0x8B, 0x08, 0x20]));
+        assert!(!is_gzip_header(&[0x1F, 0x8B]));
+
+        let dir = tempfile::tempdir().unwrap();
+        let download = dir.path().join("download");
+        std::fs::write(&download, [0x1F, 0x8B, 0x00, 0x00, 0x12, 0x34]).unwrap();
+        let resolved = resolve_input(&download, &["cia"]).unwrap();
+        assert_eq!(resolved.path(), download);
+    }
+
+    #[test]
+    fn cdn_folder_extracts_beside_its_siblings_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let sz = dir.path().join("title.7z");
+        write_7z(
+            &sz,
+            &[
+                ("0004000000155400/tmd", b"tmd-bytes"),
+                ("0004000000155400/cetk", b"cetk-bytes"),
+                ("0004000000155400/00000000", b"content"),
+                ("readme.txt", b"notes"),
+            ],
+        );
+        let resolved = resolve_cdn_input(&sz).unwrap();
+        let cdn = resolved.path();
+        assert_eq!(cdn.file_name().unwrap(), "0004000000155400");
+        assert_eq!(std::fs::read(cdn.join("00000000")).unwrap(), b"content");
+        assert_eq!(std::fs::read_dir(cdn).unwrap().count(), 3);
+        assert_eq!(resolved.output_basis(), dir.path().join("0004000000155400"));
+    }
+
+    #[test]
+    fn cdn_files_at_the_root_take_the_archive_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let tar = dir.path().join("Game.tar.gz");
+        write_tar(&tar, true, &[("tmd.0", b"tmd"), ("00000000", b"content")]);
+        let resolved = resolve_cdn_input(&tar).unwrap();
+        assert_eq!(resolved.path().file_name().unwrap(), "Game");
+        assert!(resolved.path().join("tmd.0").is_file());
+
+        let empty = dir.path().join("docs.zip");
+        write_zip(&empty, zip::CompressionMethod::Stored, &[("a.txt", b"x")]);
+        let err = resolve_cdn_input(&empty).unwrap_err().to_string();
+        assert!(err.contains("no CDN folder"), "{err}");
+    }
+
     #[test]
     fn plain_file_passes_through() {
         let dir = tempfile::tempdir().unwrap();
@@ -274,6 +274,31 @@ pub fn hash_file(
     hash_file_cancellable(path, algos, progress, &CancelToken::new())
 }
 
+/// The digests No-Intro and Redump DATs list for a dump, computed for a
+/// freshly written output so it can be checked against a database.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OutputHashes {
+    pub crc32: String,
+    pub md5: String,
+    pub sha1: String,
+}
+
+const OUTPUT_HASH_ALGOS: &[HashAlgo] = &[HashAlgo::Crc32, HashAlgo::Md5, HashAlgo::Sha1];
+
+/// CRC32, MD5 and SHA-1 of `path` in one streaming pass.
+pub fn hash_output_cancellable(
+    path: &Path,
+    progress: &dyn ProgressReporter,
+    cancel: &CancelToken,
+) -> std::io::Result<OutputHashes> {
+    let d = hash_file_cancellable(path, OUTPUT_HASH_ALGOS, progress, cancel)?;
+    Ok(OutputHashes {
+        crc32: d.crc32.unwrap_or_default(),
+        md5: d.md5.unwrap_or_default(),
+        sha1: d.sha1.unwrap_or_default(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -325,6 +350,17 @@ mod tests {
         assert_eq!(d.size_bytes, 3);
     }
 
+    #[test]
+    fn output_hashes_cover_dat_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.cia");
+        std::fs::write(&path, b"abc").unwrap();
+        let h = hash_output_cancellable(&path, &NoProgress, &CancelToken::new()).unwrap();
+        assert_eq!(h.crc32, "352441c2");
+        assert_eq!(h.md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(h.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
     #[test]
     fn single_pass_multi_hash_matches_individual() {
         let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
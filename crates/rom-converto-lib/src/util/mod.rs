@@ -16,13 +16,16 @@ pub mod maker_codes;
 pub mod pixel;
 pub mod plan;
 pub mod pread;
+pub mod progress_fn;
 pub mod report;
 pub mod tally;
 pub mod template;
 pub mod verify;
 pub mod worker_pool;
 
-pub use archive::{ArchiveMember, ResolvedInput, is_archive_path, list_members, resolve_input};
+pub use archive::{
+    ArchiveMember, ResolvedInput, is_archive_path, list_members, resolve_cdn_input, resolve_input,
+};
 pub use conflict::{ConflictPolicy, ConflictResolution, resolve_conflict};
 pub use footgun::{
     DREAMCAST_CHD_WARNING, NX_DAT_UNSUPPORTED_HINT, dreamcast_boot_signature,
@@ -30,33 +33,112 @@ pub use footgun::{
 };
 pub use fs::{DEFAULT_SPACE_HEADROOM, available_space, space_shortfall};
 pub use hash::{
-    ChecksumBounds, FileDigests, HashAlgo, hash_file, hash_file_cancellable, parse_algos,
-    parse_checksum_bound,
+    ChecksumBounds, FileDigests, HashAlgo, OutputHashes, hash_file, hash_file_cancellable,
+    hash_output_cancellable, parse_algos, parse_checksum_bound,
 };
 pub use hash_cache::{CachedTrack, CueDigests, HashCache};
 pub use plan::{PlanDecision, PlanLine, classify};
+pub use progress_fn::{Progress, ProgressFn};
 pub use report::{
     HashReportRecord, ReportFormat, ReportRecord, ReportTotals, write_dat_report_cancellable,
     write_hash_report, write_hash_report_cancellable, write_report, write_report_cancellable,
 };
 pub use tally::{FileEntry, FileStatus, Tally, TallyDirection, format_bytes};
-pub use template::{TemplateTokens, apply_template};
+pub use template::{TEMPLATE_TOKENS, TemplateTokens, apply_template, validate_template};
 pub use verify::{
     OutputVerify, VerifyOutcome, verify_existing_output, verify_existing_output_cancellable,
 };
 
 pub const BYTES_PER_MB: f64 = 1_000_000.0;
 
+/// `bytes` processed over `elapsed`, in MB/s; `None` when no time
+/// was measured.
+pub fn throughput_mb_s(bytes: u64, elapsed: std::time::Duration) -> Option<f64> {
+    let secs = elapsed.as_secs_f64();
+    (secs > 0.0).then(|| bytes as f64 / BYTES_PER_MB / secs)
+}
+
 /// Cooperative cancellation handle threaded into the long-running
 /// compress/decompress/extract loops. The blocking codec pipelines
 /// observe it at chunk/hunk/block boundaries and stop with the codec's
 /// `Cancelled` error.
 pub type CancelToken = tokio_util::sync::CancellationToken;
 
+/// Scratch files that currently exist, for [`remove_scratch_outputs`].
+static LIVE_SCRATCH: std::sync::Mutex<Vec<std::path::PathBuf>> = std::sync::Mutex::new(Vec::new());
+
+fn live_scratch() -> std::sync::MutexGuard<'static, Vec<std::path::PathBuf>> {
+    LIVE_SCRATCH
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Delete every scratch output still being written and return how many
+/// were removed. Dropping a [`ScratchPath`] already cleans up after it;
+/// this is for a process about to exit without unwinding, such as the
+/// CLI on a second Ctrl-C.
+pub fn remove_scratch_outputs() -> usize {
+    live_scratch()
+        .drain(..)
+        .filter(|path| std::fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// An in-progress output from [`scratch_output_path`]. The file is
+/// deleted on drop unless it was published with [`publish_temp`], and
+/// is listed for [`remove_scratch_outputs`] until then.
+pub(crate) struct ScratchPath {
+    temp: Option<tempfile::TempPath>,
+}
+
+impl ScratchPath {
+    pub(crate) fn new(temp: tempfile::TempPath) -> Self {
+        live_scratch().push(temp.to_path_buf());
+        Self { temp: Some(temp) }
+    }
+
+    /// Stop tracking the file and hand back the plain temp path.
+    pub(crate) fn into_temp_path(mut self) -> tempfile::TempPath {
+        let temp = self.temp.take().expect("scratch path already taken");
+        live_scratch().retain(|path| path != &*temp);
+        temp
+    }
+}
+
+impl From<ScratchPath> for tempfile::TempPath {
+    fn from(scratch: ScratchPath) -> Self {
+        scratch.into_temp_path()
+    }
+}
+
+impl std::ops::Deref for ScratchPath {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        self.temp.as_deref().expect("scratch path already taken")
+    }
+}
+
+impl AsRef<std::path::Path> for ScratchPath {
+    fn as_ref(&self) -> &std::path::Path {
+        self
+    }
+}
+
+impl Drop for ScratchPath {
+    fn drop(&mut self) {
+        if let Some(temp) = self.temp.take() {
+            let path = temp.to_path_buf();
+            drop(temp);
+            live_scratch().retain(|live| live != &path);
+        }
+    }
+}
+
 /// A sibling temp path in the output directory so an interrupted write
 /// never lands on the final name and a pre-existing overwrite target
 /// survives until the rename.
-pub(crate) fn scratch_output_path(output: &std::path::Path) -> std::io::Result<tempfile::TempPath> {
+pub(crate) fn scratch_output_path(output: &std::path::Path) -> std::io::Result<ScratchPath> {
     let parent = output
         .parent()
         .filter(|path| !path.as_os_str().is_empty())
@@ -68,14 +150,15 @@ pub(crate) fn scratch_output_path(output: &std::path::Path) -> std::io::Result<t
         .prefix(&prefix)
         .suffix(".tmp")
         .tempfile_in(parent)
-        .map(tempfile::NamedTempFile::into_temp_path)
+        .map(|file| ScratchPath::new(file.into_temp_path()))
 }
 
 pub(crate) fn publish_temp(
-    temp: tempfile::TempPath,
+    temp: impl Into<tempfile::TempPath>,
     output: &std::path::Path,
     overwrite: bool,
 ) -> std::io::Result<()> {
+    let temp = temp.into();
     let result = if overwrite {
         temp.persist(output)
     } else {
@@ -96,7 +179,9 @@ pub(crate) fn backup_existing(
             format!("not a regular file: {}", path.display()),
         ));
     }
-    let backup = scratch_output_path(path)?;
+    // The backup holds the original file, so it is never swept up by
+    // `remove_scratch_outputs`.
+    let backup = scratch_output_path(path)?.into_temp_path();
     std::fs::remove_file(&backup)?;
     std::fs::rename(path, &backup)?;
     Ok(Some(backup))
@@ -178,7 +263,8 @@ pub fn place_in_dir_mirrored(
 /// Trait for reporting progress from library operations.
 ///
 /// Consumers implement this to bridge progress updates to their
-/// preferred UI (CLI progress bars, GUI events, and similar).
+/// preferred UI (CLI progress bars, GUI events, and similar), or wrap a
+/// closure in [`ProgressFn`] to receive running totals instead.
 pub trait ProgressReporter: Send + Sync {
     fn start(&self, total: u64, msg: &str);
     fn inc(&self, delta: u64);
@@ -250,7 +336,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{place_in_dir_mirrored, publish_temp, scratch_output_path};
+    use super::{live_scratch, place_in_dir_mirrored, publish_temp, scratch_output_path};
     use crate::util::{NoProgress, ProgressReporter};
     use std::path::{Path, PathBuf};
 
@@ -301,6 +387,22 @@ mod tests {
         assert!(!first_path.exists());
     }
 
+    #[test]
+    fn scratch_outputs_are_tracked_until_published_or_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("game.chd");
+        let published = scratch_output_path(&output).unwrap();
+        let dropped = scratch_output_path(&output).unwrap();
+        let (published_path, dropped_path) = (published.to_path_buf(), dropped.to_path_buf());
+        let tracked = |path: &PathBuf| live_scratch().contains(path);
+        assert!(tracked(&published_path) && tracked(&dropped_path));
+
+        publish_temp(published, &output, false).unwrap();
+        drop(dropped);
+        assert!(!tracked(&published_path) && !tracked(&dropped_path));
+        assert!(output.exists());
+    }
+
     #[test]
     fn publish_temp_replaces_or_preserves_cross_platform() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,133 @@
+//! Closure-backed [`ProgressReporter`] for library consumers that want a
+//! plain callback with the running totals instead of implementing the trait.
+
+use super::ProgressReporter;
+use std::sync::Mutex;
+
+/// Snapshot handed to a [`ProgressFn`] callback after every update.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Units reported so far, usually bytes.
+    pub done: u64,
+    /// Units the operation expects in total; 0 when it did not say.
+    pub total: u64,
+    /// The active phase label, or the operation message when no phase is set.
+    pub phase: String,
+    /// Set once the operation reports it has finished.
+    pub finished: bool,
+}
+
+/// Adapts a closure to [`ProgressReporter`], tracking the totals the trait
+/// only reports as deltas and calling back with a [`Progress`] snapshot on
+/// every `start`, `inc`, `set_phase`, and `finish`.
+pub struct ProgressFn<F> {
+    callback: F,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    progress: Progress,
+    message: String,
+}
+
+impl<F: Fn(Progress) + Send + Sync> ProgressFn<F> {
+    pub fn new(callback: F) -> Self {
+        Self {
+            callback,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut State)) {
+        let snapshot = {
+            let mut state = self.state.lock().unwrap();
+            apply(&mut state);
+            state.progress.clone()
+        };
+        (self.callback)(snapshot);
+    }
+}
+
+impl<F: Fn(Progress) + Send + Sync> ProgressReporter for ProgressFn<F> {
+    fn start(&self, total: u64, msg: &str) {
+        self.update(|state| {
+            state.message = msg.to_string();
+            state.progress = Progress {
+                done: 0,
+                total,
+                phase: msg.to_string(),
+                finished: false,
+            };
+        });
+    }
+
+    fn inc(&self, delta: u64) {
+        self.update(|state| state.progress.done = state.progress.done.saturating_add(delta));
+    }
+
+    fn finish(&self) {
+        self.update(|state| {
+            state.progress.phase = state.message.clone();
+            state.progress.finished = true;
+        });
+    }
+
+    fn set_phase(&self, label: &str) {
+        self.update(|state| state.progress.phase = label.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_sees_running_totals_and_phases() {
+        let seen = Mutex::new(Vec::new());
+        let reporter = ProgressFn::new(|p| seen.lock().unwrap().push(p));
+
+        reporter.start(100, "Compressing");
+        reporter.set_phase("Hashing");
+        reporter.inc(40);
+        reporter.inc(60);
+        reporter.finish();
+
+        let seen = seen.into_inner().unwrap();
+        let summary: Vec<_> = seen
+            .iter()
+            .map(|p| (p.done, p.total, p.phase.as_str(), p.finished))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 100, "Compressing", false),
+                (0, 100, "Hashing", false),
+                (40, 100, "Hashing", false),
+                (100, 100, "Hashing", false),
+                (100, 100, "Compressing", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn restart_resets_the_count() {
+        let last = Mutex::new(Progress::default());
+        let reporter = ProgressFn::new(|p| *last.lock().unwrap() = p);
+
+        reporter.start(10, "First");
+        reporter.inc(10);
+        reporter.finish();
+        reporter.start(5, "Second");
+
+        assert_eq!(
+            *last.lock().unwrap(),
+            Progress {
+                done: 0,
+                total: 5,
+                phase: "Second".into(),
+                finished: false,
+            }
+        );
+    }
+}
@@ -1,8 +1,9 @@
 //! Run reports: per-file records and run totals written to CSV, JSON, or
 //! HTML at the end of a batch run, via `--report`.
 
-use crate::util::CancelToken;
+use crate::util::hash::OutputHashes;
 use crate::util::tally::{FileStatus, format_bytes};
+use crate::util::{CancelToken, throughput_mb_s};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::Cow;
@@ -23,7 +24,16 @@ pub struct ReportRecord {
     /// failed files, where there is no meaningful output size to compare.
     pub ratio_pct: Option<f64>,
     pub elapsed_ms: u64,
+    /// Input bytes processed per second, in MB/s, rounded to one decimal
+    /// place. Set for converted files that took measurable time. JSON
+    /// only, like `hashes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throughput_mb_s: Option<f64>,
     pub error: Option<String>,
+    /// Digests of the written output, present when the run used `--hashes`.
+    /// JSON only; the CSV and HTML layouts are unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<OutputHashes>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -83,6 +93,13 @@ impl ReportRecord {
             }
             _ => None,
         };
+        let throughput_mb_s = match status {
+            FileStatus::Ok => {
+                throughput_mb_s(input_bytes, std::time::Duration::from_millis(elapsed_ms))
+                    .map(|rate| (rate * 10.0).round() / 10.0)
+            }
+            _ => None,
+        };
         Self {
             input_path,
             output_path,
@@ -92,9 +109,16 @@ impl ReportRecord {
             output_bytes,
             ratio_pct,
             elapsed_ms,
+            throughput_mb_s,
             error,
+            hashes: None,
         }
     }
+
+    pub fn with_hashes(mut self, hashes: Option<OutputHashes>) -> Self {
+        self.hashes = hashes;
+        self
+    }
 }
 
 fn status_str(status: FileStatus) -> &'static str {
@@ -900,6 +924,25 @@ mod tests {
         assert!(v["files"][0]["ratio_pct"].is_number());
         assert_eq!(v["files"][0]["input_bytes"], 1024 * 1024);
         assert!(v["files"][1]["ratio_pct"].is_null());
+        assert_eq!(v["files"][0]["throughput_mb_s"], 0.7);
+        assert!(v["files"][1].get("throughput_mb_s").is_none());
+        assert!(v["files"][0].get("hashes").is_none());
+    }
+
+    #[test]
+    fn json_includes_output_hashes_when_present() {
+        let rec = ok_record().with_hashes(Some(OutputHashes {
+            crc32: "352441c2".into(),
+            md5: "900150983cd24fb0d6963f7d28e17f72".into(),
+            sha1: "a9993e364706816aba3e25717850c26c9cd0d89d".into(),
+        }));
+        let out = render(&[rec], &ReportTotals::default(), ReportFormat::Json);
+        let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(v["files"][0]["hashes"]["crc32"], "352441c2");
+        assert_eq!(
+            v["files"][0]["hashes"]["sha1"],
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
     }
 
     #[test]
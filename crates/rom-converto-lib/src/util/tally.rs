@@ -1,6 +1,7 @@
 //! Per-run byte and file-count tracking, rendered into the final
 //! `"{n} files: A -> B, saved C (p%) in T"`-style summary line.
 
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 const KIB: f64 = 1024.0;
@@ -82,6 +83,13 @@ impl Tally {
         });
     }
 
+    /// Records a successful item, reading its input and output sizes from
+    /// disk. A file that cannot be stat'ed counts as zero bytes.
+    pub fn record_ok_files(&mut self, input: &Path, output: &Path, elapsed: Duration) {
+        let len = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+        self.record_ok(len(input), len(output), elapsed);
+    }
+
     pub fn record_failed(&mut self) {
         self.record(FileEntry {
             input_bytes: 0,
@@ -12,6 +12,22 @@ use std::path::{Path, PathBuf};
 
 const MAX_COMPONENT_BYTES: usize = 200;
 
+/// Every token a template may use, in the order they are documented.
+pub const TEMPLATE_TOKENS: &[&str] = &[
+    "title",
+    "titleId",
+    "titleVersion",
+    "region",
+    "console",
+    "serial",
+    "disc",
+    "ext",
+    "basename",
+    "stem",
+    "title_id",
+    "title_version",
+];
+
 const WINDOWS_RESERVED: &[&str] = &[
     "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
     "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
@@ -20,9 +36,15 @@ const WINDOWS_RESERVED: &[&str] = &[
 pub struct TemplateTokens {
     pub title: Option<String>,
     pub title_id: Option<String>,
+    /// Decimal title version. Only CDN-to-CIA builds, which read it from
+    /// the TMD, fill it.
+    pub title_version: Option<String>,
     pub region: Option<String>,
     pub console: Option<String>,
     pub serial: Option<String>,
+    /// Disc number from a "(Disc N)" or "Disc N of M" token in the input
+    /// name, as multi-disc playlists detect it.
+    pub disc: Option<String>,
     pub ext: String,
     pub basename: String,
 }
@@ -35,13 +57,16 @@ impl TemplateTokens {
             .unwrap_or("output")
             .to_string();
         let ext = output_ext.trim_start_matches('.').to_string();
+        let disc = crate::playlist::parse_disc_token(&basename).map(|(_, n)| n.to_string());
 
         let mut tokens = Self {
             title: None,
             title_id: None,
+            title_version: None,
             region: None,
             console: None,
             serial: None,
+            disc,
             ext,
             basename,
         };
@@ -171,6 +196,38 @@ fn nx_title(titles: &[crate::nintendo::nx::info::NxNacpTitle]) -> Option<String>
         .and_then(non_empty)
 }
 
+/// Check a template before any input is read: it must be relative, must
+/// not climb out with `..`, and may only use [`TEMPLATE_TOKENS`]. Returns
+/// the template so it works as a clap value parser.
+pub fn validate_template(template: &str) -> Result<String> {
+    if template.starts_with('/') || template.starts_with('\\') || has_drive_prefix(template) {
+        bail!("output template must resolve to a relative path without parent traversal");
+    }
+    if template.split(['/', '\\']).any(|part| part.trim() == "..") {
+        bail!("output template must resolve to a relative path without parent traversal");
+    }
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            bail!("output template has an unclosed '{{'");
+        };
+        let name = &after[..close];
+        if !TEMPLATE_TOKENS.contains(&name) {
+            bail!(
+                "unknown output template token {{{name}}}; expected one of {}",
+                TEMPLATE_TOKENS
+                    .iter()
+                    .map(|token| format!("{{{token}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        rest = &after[close + 1..];
+    }
+    Ok(template.to_string())
+}
+
 pub fn apply_template(template: &str, tokens: &TemplateTokens) -> Result<PathBuf> {
     if template.starts_with('/') || template.starts_with('\\') || has_drive_prefix(template) {
         bail!("output template must resolve to a relative path without parent traversal");
@@ -241,7 +298,7 @@ fn resolve_token(name: &str, tokens: &TemplateTokens) -> Option<String> {
             .title
             .clone()
             .unwrap_or_else(|| tokens.basename.clone()),
-        "titleId" => tokens
+        "titleId" | "title_id" => tokens
             .title_id
             .clone()
             .unwrap_or_else(|| tokens.basename.clone()),
@@ -249,10 +306,12 @@ fn resolve_token(name: &str, tokens: &TemplateTokens) -> Option<String> {
             .serial
             .clone()
             .unwrap_or_else(|| tokens.basename.clone()),
+        "titleVersion" | "title_version" => tokens.title_version.clone().unwrap_or_default(),
         "region" => tokens.region.clone().unwrap_or_default(),
         "console" => tokens.console.clone().unwrap_or_default(),
+        "disc" => tokens.disc.clone().unwrap_or_default(),
         "ext" => tokens.ext.clone(),
-        "basename" => tokens.basename.clone(),
+        "basename" | "stem" => tokens.basename.clone(),
         _ => return None,
     };
     Some(neutralize_separators(&value))
@@ -309,9 +368,11 @@ mod tests {
         TemplateTokens {
             title: title.map(str::to_string),
             title_id: Some("ABCD".to_string()),
+            title_version: None,
             region: Some("USA".to_string()),
             console: Some("Wii".to_string()),
             serial: Some("RMCE01".to_string()),
+            disc: None,
             ext: "rvz".to_string(),
             basename: "game01".to_string(),
         }
@@ -427,6 +488,60 @@ mod tests {
         assert_eq!(p, PathBuf::from("{bogus}-Mario.rvz"));
     }
 
+    #[test]
+    fn disc_comes_from_the_input_name() {
+        let t = TemplateTokens::new(None, Path::new("Game (USA) (Disc 2).cue"), "chd");
+        assert_eq!(t.disc.as_deref(), Some("2"));
+        let p = apply_template("{stem}/Disc {disc}.{ext}", &t).unwrap();
+        assert_eq!(p, PathBuf::from("Game (USA) (Disc 2)/Disc 2.chd"));
+
+        let t = TemplateTokens::new(None, Path::new("Discworld.cue"), "chd");
+        assert!(t.disc.is_none());
+    }
+
+    #[test]
+    fn missing_title_version_collapses_component() {
+        let mut t = tokens(Some("Mario"));
+        let p = apply_template("{title}/{titleVersion}/{title}.{ext}", &t).unwrap();
+        assert_eq!(p, PathBuf::from("Mario/Mario.rvz"));
+        t.title_version = Some("1024".to_string());
+        let p = apply_template("{title} v{titleVersion}.{ext}", &t).unwrap();
+        assert_eq!(p, PathBuf::from("Mario v1024.rvz"));
+    }
+
+    #[test]
+    fn validate_accepts_known_tokens_and_rejects_the_rest() {
+        let all = TEMPLATE_TOKENS
+            .iter()
+            .map(|token| format!("{{{token}}}"))
+            .collect::<Vec<_>>()
+            .join("-");
+        assert_eq!(validate_template(&all).unwrap(), all);
+        for bad in [
+            "{bogus}.{ext}",
+            "{title",
+            "../{title}",
+            "a/../{title}",
+            "/abs/{title}",
+            "C:{title}",
+        ] {
+            assert!(validate_template(bad).is_err(), "{bad}");
+        }
+        let err = validate_template("{game_id}").unwrap_err().to_string();
+        assert!(
+            err.contains("{game_id}") && err.contains("{titleId}"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn snake_case_aliases_resolve_like_their_camel_case_tokens() {
+        let mut t = tokens(Some("Mario"));
+        t.title_version = Some("1024".to_string());
+        let p = apply_template("{title_id} v{title_version}.{ext}", &t).unwrap();
+        assert_eq!(p, PathBuf::from("ABCD v1024.rvz"));
+    }
+
     #[test]
     fn nx_without_keys_falls_back() {
         let info = InfoResult::Nx(NxInfo {